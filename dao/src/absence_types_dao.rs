@@ -0,0 +1,236 @@
+use diesel::dsl::*;
+use diesel::prelude::*;
+
+use crate::base_dao::{stale_version, Crud, HaveId, HaveVersion, Searchable};
+use crate::connection::DbConnection;
+use crate::crypto::KeyRing;
+use crate::error::DaoResult;
+use crate::models::{AbsenceType, NewAbsenceType};
+use crate::schema::absence_types::dsl::id as absence_type_id;
+use crate::schema::absence_types::dsl::*;
+use crate::validation::{Errors, ValidationRules};
+
+/// Kind of absence (vacation, sick leave...) with its yearly entitlement
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct AbsenceTypeDTO {
+    pub id: Option<i32>,
+    pub name: String,
+    /// Working days of such absence employee is entitled to every year - None when not limited
+    pub yearly_entitlement: Option<i32>,
+    /// How many unused days can be carried over to next year
+    #[serde(default)]
+    pub carry_over_limit: i32,
+    pub search_string: String,
+    pub version: Option<i32>,
+}
+
+impl From<AbsenceType> for AbsenceTypeDTO {
+    fn from(t: AbsenceType) -> Self {
+        AbsenceTypeDTO {
+            id: Some(t.id),
+            name: t.name,
+            yearly_entitlement: t.yearly_entitlement,
+            carry_over_limit: t.carry_over_limit,
+            search_string: t.search_string,
+            version: Some(t.version),
+        }
+    }
+}
+
+impl From<&AbsenceTypeDTO> for AbsenceType {
+    fn from(absence_type_dto: &AbsenceTypeDTO) -> Self {
+        AbsenceType {
+            id: absence_type_dto.id.unwrap(),
+            name: absence_type_dto.name.clone(),
+            yearly_entitlement: absence_type_dto.yearly_entitlement,
+            carry_over_limit: absence_type_dto.carry_over_limit,
+            search_string: absence_type_dto.search_string.clone(),
+            version: absence_type_dto.version.unwrap_or_default(),
+        }
+    }
+}
+
+impl From<&AbsenceTypeDTO> for NewAbsenceType {
+    fn from(absence_type_dto: &AbsenceTypeDTO) -> Self {
+        NewAbsenceType {
+            name: absence_type_dto.name.clone(),
+            yearly_entitlement: absence_type_dto.yearly_entitlement,
+            carry_over_limit: absence_type_dto.carry_over_limit,
+            search_string: absence_type_dto.search_string.clone(),
+        }
+    }
+}
+
+impl HaveId for AbsenceTypeDTO {
+    fn get_id(&self) -> Option<i32> {
+        self.id
+    }
+}
+
+impl HaveVersion for AbsenceTypeDTO {
+    fn get_version(&self) -> Option<i32> {
+        self.version
+    }
+}
+
+impl Crud for AbsenceTypeDTO {
+    fn update(&mut self, persisted: &Self) {
+        self.id = persisted.id;
+        self.version = persisted.version;
+    }
+
+    /// Name is required and unique, entitlement and carry-over limit can't be negative
+    fn validate(&self, _rules: &ValidationRules, _keys: Option<&KeyRing>, conn: &mut DbConnection) -> DaoResult<()> {
+        let mut errors = Errors::default();
+        if self.name.trim().is_empty() {
+            errors.add("", "name", "can't be empty".to_string());
+        }
+        let same_name = absence_types
+            .filter(name.eq(&self.name))
+            .select(absence_type_id)
+            .first::<i32>(conn)
+            .optional()?;
+        if let Some(other) = same_name
+            && Some(other) != self.id
+        {
+            errors.add("", "name", format!("'{}' is already name of absence type id = {}", self.name, other));
+        }
+        if self.yearly_entitlement.is_some_and(|days| days < 0) {
+            errors.add("", "yearly_entitlement", "can't be negative".to_string());
+        }
+        if self.carry_over_limit < 0 {
+            errors.add("", "carry_over_limit", "can't be negative".to_string());
+        }
+        errors.into_result()
+    }
+
+    fn get_simple(id_to_find: i32, _keys: Option<&KeyRing>, conn: &mut DbConnection) -> QueryResult<Self> {
+        absence_types
+            .filter(absence_type_id.eq(id_to_find))
+            .first(conn)
+            .map(|t: AbsenceType| AbsenceTypeDTO::from(t))
+    }
+
+    fn save_simple(&self, keys: Option<&KeyRing>, conn: &mut DbConnection) -> DaoResult<Self> {
+        fn insert(t: &AbsenceTypeDTO, conn: &mut DbConnection) -> QueryResult<AbsenceTypeDTO> {
+            insert_into(absence_types)
+                .values(NewAbsenceType::from(t))
+                .get_result(conn)
+                .map(|t: AbsenceType| AbsenceTypeDTO::from(t))
+        }
+        if let Some(self_id) = self.id {
+            let updated = match self.version {
+                Some(self_version) => diesel::update(
+                    absence_types
+                        .filter(absence_type_id.eq(self_id))
+                        .filter(version.eq(self_version)),
+                )
+                .set((AbsenceType::from(self), version.eq(version + 1)))
+                .execute(conn)?,
+                None => 0,
+            };
+            if updated == 0 {
+                let current = absence_types
+                    .filter(absence_type_id.eq(self_id))
+                    .select(version)
+                    .first::<i32>(conn)
+                    .optional()?;
+                match current {
+                    Some(current) => Err(stale_version(self_id, Some(current))),
+                    None => Ok(insert(self, conn)?),
+                }
+            } else {
+                Ok(Self::get_simple(self_id, keys, conn)?)
+            }
+        } else {
+            Ok(insert(self, conn)?)
+        }
+    }
+
+    /// Absence type which is used by absences can't be deleted (foreign key violation)
+    fn delete_simple(id_to_find: i32, conn: &mut DbConnection) -> QueryResult<usize> {
+        diesel::delete(absence_types.filter(absence_type_id.eq(id_to_find))).execute(conn)
+    }
+
+    fn delete_versioned_simple(
+        id_to_find: i32,
+        version_to_find: i32,
+        conn: &mut DbConnection,
+    ) -> QueryResult<usize> {
+        diesel::delete(
+            absence_types
+                .filter(absence_type_id.eq(id_to_find))
+                .filter(version.eq(version_to_find)),
+        )
+        .execute(conn)
+    }
+}
+
+impl Searchable for AbsenceTypeDTO {
+    fn get_all_with_connection(_keys: Option<&KeyRing>, conn: &mut DbConnection) -> DaoResult<Vec<Self>> {
+        Ok(absence_types
+            .order(absence_type_id)
+            .load::<AbsenceType>(conn)?
+            .into_iter()
+            .map(Self::from)
+            .collect())
+    }
+
+    fn search_with_connection(s: &str, _keys: Option<&KeyRing>, conn: &mut DbConnection) -> DaoResult<Vec<Self>> {
+        Ok(absence_types
+            .filter(search_string.like(s))
+            .load::<AbsenceType>(conn)?
+            .into_iter()
+            .map(Self::from)
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::common_for_tests::*;
+    use crate::error::DaoError;
+
+    use super::*;
+
+    impl CrudTests for AbsenceTypeDTO {}
+
+    fn absence_type(type_name: &str, entitlement: Option<i32>) -> AbsenceTypeDTO {
+        AbsenceTypeDTO {
+            id: None,
+            name: type_name.to_string(),
+            yearly_entitlement: entitlement,
+            carry_over_limit: 0,
+            search_string: "".to_string(),
+            version: None,
+        }
+    }
+
+    #[test]
+    fn crud_operations_on_absence_type() {
+        let conn = &mut initialize();
+        absence_type("Parental leave", None).test(conn);
+    }
+
+    #[test]
+    fn default_absence_types_are_valid() {
+        let conn = &mut initialize();
+        let rules = ValidationRules::default();
+        let defaults = AbsenceTypeDTO::get_all_with_connection(keys(), conn).unwrap();
+        let names: Vec<&str> = defaults.iter().map(|t| t.name.as_str()).collect();
+        assert_eq!(names, vec!["Vacation", "Sick leave", "Unpaid leave"]);
+        assert_eq!(defaults[0].yearly_entitlement, Some(26));
+        for t in &defaults {
+            assert!(t.validate(&rules, keys(), conn).is_ok());
+        }
+        for (t, field) in [
+            (absence_type("Vacation", None), "name"),
+            (absence_type("Training", Some(-1)), "yearly_entitlement"),
+        ] {
+            match t.try_save_in_transaction(&rules, keys(), conn) {
+                Err(DaoError::Validation(errors)) => assert_eq!(errors[0].field, field),
+                result => panic!("Should report validation error and instead I got {:?}", result),
+            }
+        }
+    }
+}
@@ -0,0 +1,777 @@
+use std::str::FromStr;
+
+use chrono::{Datelike, NaiveDate, Weekday};
+use diesel::dsl::*;
+use diesel::prelude::*;
+
+use crate::absence_types_dao::AbsenceTypeDTO;
+use crate::base_dao::{stale_version, Crud, HaveId, HaveVersion, Searchable, SearchableByParent};
+use crate::connection::{Database, DbConnection};
+use crate::crypto::KeyRing;
+use crate::employees_dao::not_deleted;
+use crate::error::DaoResult;
+use crate::hierarchy::{descendants, EmployeeScope, Tree};
+use crate::models::{Absence, NewAbsence};
+use crate::schema::absences::dsl::id as absence_id;
+use crate::schema::absences::dsl::*;
+use crate::validation::{check_no_overlap_with_existing, check_period, Errors, HavePeriod, ValidationRules};
+
+/// Where absence is in request/approve/reject workflow
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum AbsenceStatus {
+    /// Waiting for decision of manager or admin - its days are already counted as taken
+    #[default]
+    Requested,
+    Approved,
+    /// Rejected absences don't count (nor overlap with other absences)
+    Rejected,
+}
+
+impl AbsenceStatus {
+    /// How it is stored in DB
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AbsenceStatus::Requested => "requested",
+            AbsenceStatus::Approved => "approved",
+            AbsenceStatus::Rejected => "rejected",
+        }
+    }
+}
+
+impl FromStr for AbsenceStatus {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "requested" => Ok(AbsenceStatus::Requested),
+            "approved" => Ok(AbsenceStatus::Approved),
+            "rejected" => Ok(AbsenceStatus::Rejected),
+            _ => Err(format!(
+                "unknown absence status '{}' - should be one of requested, approved, rejected",
+                s
+            )),
+        }
+    }
+}
+
+/// Number of working days (Monday - Friday) from `from` to `to` (both inclusive) - public holidays are not known
+pub fn working_days(from: NaiveDate, to: NaiveDate) -> i32 {
+    from.iter_days()
+        .take_while(|d| *d <= to)
+        .filter(|d| !matches!(d.weekday(), Weekday::Sat | Weekday::Sun))
+        .count() as i32
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct AbsenceDTO {
+    pub id: Option<i32>,
+    pub employee_id: Option<i32>,
+    pub absence_type_id: i32,
+    pub from_date: NaiveDate,
+    pub to_date: NaiveDate,
+    /// Working days of absence - computed on save
+    #[serde(default)]
+    pub days: i32,
+    /// Changed just by approve or reject
+    #[serde(default)]
+    pub status: AbsenceStatus,
+    /// User who approved or rejected absence
+    #[serde(default)]
+    pub decided_by: Option<i32>,
+    #[serde(default)]
+    pub comment: Option<String>,
+    pub search_string: String,
+    pub version: Option<i32>,
+}
+
+impl From<Absence> for AbsenceDTO {
+    fn from(a: Absence) -> Self {
+        AbsenceDTO {
+            id: Some(a.id),
+            employee_id: Some(a.employee_id),
+            absence_type_id: a.absence_type_id,
+            from_date: a.from_date,
+            to_date: a.to_date,
+            days: a.days,
+            status: a.status.parse().expect("status is checked by DB"),
+            decided_by: a.decided_by,
+            comment: a.comment,
+            search_string: a.search_string,
+            version: Some(a.version),
+        }
+    }
+}
+
+impl From<&AbsenceDTO> for Absence {
+    fn from(absence_dto: &AbsenceDTO) -> Self {
+        Absence {
+            id: absence_dto.id.unwrap(),
+            employee_id: absence_dto.employee_id.unwrap(),
+            absence_type_id: absence_dto.absence_type_id,
+            from_date: absence_dto.from_date,
+            to_date: absence_dto.to_date,
+            days: working_days(absence_dto.from_date, absence_dto.to_date),
+            status: absence_dto.status.as_str().to_string(),
+            decided_by: absence_dto.decided_by,
+            comment: absence_dto.comment.clone(),
+            search_string: absence_dto.search_string.clone(),
+            version: absence_dto.version.unwrap_or_default(),
+        }
+    }
+}
+
+impl From<&AbsenceDTO> for NewAbsence {
+    fn from(absence_dto: &AbsenceDTO) -> Self {
+        NewAbsence {
+            employee_id: absence_dto.employee_id.unwrap(),
+            absence_type_id: absence_dto.absence_type_id,
+            from_date: absence_dto.from_date,
+            to_date: absence_dto.to_date,
+            days: working_days(absence_dto.from_date, absence_dto.to_date),
+            status: absence_dto.status.as_str().to_string(),
+            decided_by: absence_dto.decided_by,
+            comment: absence_dto.comment.clone(),
+            search_string: absence_dto.search_string.clone(),
+        }
+    }
+}
+
+impl HaveId for AbsenceDTO {
+    fn get_id(&self) -> Option<i32> {
+        self.id
+    }
+}
+
+impl HavePeriod for AbsenceDTO {
+    fn period_start(&self) -> NaiveDate {
+        self.from_date
+    }
+
+    fn period_end(&self) -> Option<NaiveDate> {
+        Some(self.to_date)
+    }
+}
+
+impl HaveVersion for AbsenceDTO {
+    fn get_version(&self) -> Option<i32> {
+        self.version
+    }
+}
+
+impl AbsenceDTO {
+    /// Working days of absence in given year
+    fn days_in(&self, year: i32) -> i32 {
+        let first = NaiveDate::from_ymd_opt(year, 1, 1).unwrap().max(self.from_date);
+        let last = NaiveDate::from_ymd_opt(year, 12, 31).unwrap().min(self.to_date);
+        if first > last { 0 } else { working_days(first, last) }
+    }
+}
+
+/// Yearly entitlement of employee to absence type and how much of it is taken
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct AbsenceBalance {
+    pub absence_type_id: i32,
+    pub absence_type: String,
+    pub year: i32,
+    /// None when absence type is not limited
+    pub entitlement: Option<i32>,
+    /// Days not used in previous year (up to carry-over limit of absence type)
+    pub carried_over: i32,
+    /// Days of approved absences
+    pub used: i32,
+    /// Days of absences waiting for approval
+    pub requested: i32,
+    /// entitlement + carried_over - used - requested (None when absence type is not limited)
+    pub remaining: Option<i32>,
+}
+
+/// Balance of absence type in `year` - `taken` are (not rejected) absences of the type. Unused days are carried over
+/// year by year since the year employee started (first contract) or the year of its first absence.
+fn balance_of(t: &AbsenceTypeDTO, year: i32, started: Option<i32>, taken: &[AbsenceDTO]) -> AbsenceBalance {
+    let days_with = |y: i32, wanted: AbsenceStatus| -> i32 {
+        taken
+            .iter()
+            .filter(|a| a.status == wanted)
+            .map(|a| a.days_in(y))
+            .sum()
+    };
+    let first_year = taken
+        .iter()
+        .map(|a| a.from_date.year())
+        .chain(started)
+        .min()
+        .unwrap_or(year)
+        .min(year);
+    let mut carried_over = 0;
+    if let Some(entitlement) = t.yearly_entitlement {
+        for y in first_year..year {
+            let unused = entitlement + carried_over - days_with(y, AbsenceStatus::Approved) - days_with(y, AbsenceStatus::Requested);
+            carried_over = unused.clamp(0, t.carry_over_limit);
+        }
+    }
+    let used = days_with(year, AbsenceStatus::Approved);
+    let requested = days_with(year, AbsenceStatus::Requested);
+    AbsenceBalance {
+        absence_type_id: t.id.unwrap_or_default(),
+        absence_type: t.name.clone(),
+        year,
+        entitlement: t.yearly_entitlement,
+        carried_over,
+        used,
+        requested,
+        remaining: t
+            .yearly_entitlement
+            .map(|entitlement| entitlement + carried_over - used - requested),
+    }
+}
+
+/// Year of first contract of employee
+fn started_in(e_id: i32, conn: &mut DbConnection) -> QueryResult<Option<i32>> {
+    use crate::schema::employment_contracts::dsl as c;
+
+    let first: Option<NaiveDate> = c::employment_contracts
+        .filter(c::employee_id.eq(e_id))
+        .select(min(c::from_date))
+        .first(conn)?;
+    Ok(first.map(|d| d.year()))
+}
+
+/// Absences of employee which are not rejected - all types or just given one
+fn taken(e_id: i32, type_id: Option<i32>, conn: &mut DbConnection) -> QueryResult<Vec<AbsenceDTO>> {
+    let mut query = absences
+        .filter(employee_id.eq(e_id))
+        .filter(status.ne(AbsenceStatus::Rejected.as_str()))
+        .into_boxed();
+    if let Some(type_id) = type_id {
+        query = query.filter(absence_type_id.eq(type_id));
+    }
+    Ok(query
+        .order(from_date)
+        .load::<Absence>(conn)?
+        .into_iter()
+        .map(AbsenceDTO::from)
+        .collect())
+}
+
+impl Crud for AbsenceDTO {
+    fn update(&mut self, persisted: &Self) {
+        self.id = persisted.id;
+        self.version = persisted.version;
+        self.days = persisted.days;
+    }
+
+    /// Period have to contain working day and can't overlap with other (not rejected) absences of the employee.
+    /// Absence can't exceed remaining entitlement and it can be changed just while it waits for decision.
+    fn validate(&self, _rules: &ValidationRules, keys: Option<&KeyRing>, conn: &mut DbConnection) -> DaoResult<()> {
+        let mut errors = Errors::default();
+        check_period(self, "", &mut errors);
+        if self.to_date >= self.from_date && working_days(self.from_date, self.to_date) == 0 {
+            errors.add(
+                "",
+                "to_date",
+                format!("there is no working day in {}", self.describe_period()),
+            );
+        }
+        if let Some(self_id) = self.id
+            && let Some(persisted) = Self::get_simple(self_id, keys, conn).optional()?
+            && persisted.status != AbsenceStatus::Requested
+        {
+            errors.add(
+                "",
+                "status",
+                format!("absence is already {} - it can't be changed", persisted.status.as_str()),
+            );
+        }
+        let absence_type = AbsenceTypeDTO::get_simple(self.absence_type_id, keys, conn).optional()?;
+        if absence_type.is_none() {
+            errors.add(
+                "",
+                "absence_type_id",
+                format!("there is no absence type with id = {}", self.absence_type_id),
+            );
+        }
+        if let Some(e_id) = self.employee_id {
+            let existing = taken(e_id, None, conn)?;
+            check_no_overlap_with_existing(self, &existing, "absence", &mut errors);
+            if let Some(t) = absence_type
+                && t.yearly_entitlement.is_some()
+                && self.status != AbsenceStatus::Rejected
+            {
+                let others: Vec<AbsenceDTO> = existing
+                    .into_iter()
+                    .filter(|a| a.absence_type_id == self.absence_type_id && a.id != self.id)
+                    .collect();
+                let started = started_in(e_id, conn)?;
+                for year in self.from_date.year()..=self.to_date.year() {
+                    let remaining = balance_of(&t, year, started, &others).remaining.unwrap_or_default();
+                    let wanted = self.days_in(year);
+                    if wanted > remaining {
+                        errors.add(
+                            "",
+                            "to_date",
+                            format!(
+                                "{} working days of {} wanted in {} but just {} remaining",
+                                wanted, t.name, year, remaining
+                            ),
+                        );
+                    }
+                }
+            }
+        }
+        errors.into_result()
+    }
+
+    fn get_simple(id_to_find: i32, _keys: Option<&KeyRing>, conn: &mut DbConnection) -> QueryResult<AbsenceDTO> {
+        absences
+            .filter(absence_id.eq(id_to_find))
+            .first(conn)
+            .map(|a: Absence| AbsenceDTO::from(a))
+    }
+
+    fn save_simple(&self, keys: Option<&KeyRing>, conn: &mut DbConnection) -> DaoResult<AbsenceDTO> {
+        fn insert(a: &AbsenceDTO, conn: &mut DbConnection) -> QueryResult<AbsenceDTO> {
+            insert_into(absences)
+                .values(NewAbsence::from(a))
+                .get_result(conn)
+                .map(|a: Absence| AbsenceDTO::from(a))
+        }
+        if let Some(self_id) = self.id {
+            let updated = match self.version {
+                Some(self_version) => diesel::update(
+                    absences
+                        .filter(absence_id.eq(self_id))
+                        .filter(version.eq(self_version)),
+                )
+                .set((Absence::from(self), version.eq(version + 1)))
+                .execute(conn)?,
+                None => 0,
+            };
+            if updated == 0 {
+                let current = absences
+                    .filter(absence_id.eq(self_id))
+                    .select(version)
+                    .first::<i32>(conn)
+                    .optional()?;
+                match current {
+                    Some(current) => Err(stale_version(self_id, Some(current))),
+                    None => Ok(insert(self, conn)?),
+                }
+            } else {
+                Ok(Self::get_simple(self_id, keys, conn)?)
+            }
+        } else {
+            Ok(insert(self, conn)?)
+        }
+    }
+
+    fn delete_simple(id_to_find: i32, conn: &mut DbConnection) -> QueryResult<usize> {
+        diesel::delete(absences.filter(absence_id.eq(id_to_find))).execute(conn)
+    }
+
+    fn delete_versioned_simple(
+        id_to_find: i32,
+        version_to_find: i32,
+        conn: &mut DbConnection,
+    ) -> QueryResult<usize> {
+        diesel::delete(
+            absences
+                .filter(absence_id.eq(id_to_find))
+                .filter(version.eq(version_to_find)),
+        )
+        .execute(conn)
+    }
+}
+
+/// Records of deleted employees are left out
+impl Searchable for AbsenceDTO {
+    fn get_all_with_connection(_keys: Option<&KeyRing>, conn: &mut DbConnection) -> DaoResult<Vec<Self>> {
+        Ok(absences
+            .filter(employee_id.eq_any(not_deleted()))
+            .load::<Absence>(conn)?
+            .into_iter()
+            .map(Self::from)
+            .collect())
+    }
+
+    fn search_with_connection(s: &str, _keys: Option<&KeyRing>, conn: &mut DbConnection) -> DaoResult<Vec<Self>> {
+        Ok(absences
+            .filter(search_string.like(s))
+            .filter(employee_id.eq_any(not_deleted()))
+            .load::<Absence>(conn)?
+            .into_iter()
+            .map(Self::from)
+            .collect())
+    }
+}
+
+impl SearchableByParent for AbsenceDTO {
+    fn search_by_parent_id_with_connection(
+        parent_id: i32,
+        _keys: Option<&KeyRing>,
+        conn: &mut DbConnection,
+    ) -> DaoResult<Vec<Self>> {
+        Ok(absences
+            .filter(employee_id.eq(parent_id))
+            .order(from_date)
+            .load::<Absence>(conn)?
+            .into_iter()
+            .map(Self::from)
+            .collect())
+    }
+}
+
+impl AbsenceDTO {
+    /// Approve or reject absence waiting for decision - `user_id` is deciding user (see can_approve()) and
+    /// `expected_version` version of absence the decision is based on
+    pub fn decide_with_connection(
+        id_to_find: i32,
+        expected_version: i32,
+        decision: AbsenceStatus,
+        user_id: i32,
+        conn: &mut DbConnection,
+    ) -> DaoResult<AbsenceDTO> {
+        conn.transaction(|conn| {
+            let mut absence = Self::get_simple(id_to_find, None, conn)?;
+            if absence.version != Some(expected_version) {
+                return Err(stale_version(id_to_find, absence.version));
+            }
+            let mut errors = Errors::default();
+            if absence.status != AbsenceStatus::Requested {
+                errors.add(
+                    "",
+                    "status",
+                    format!("absence is already {}", absence.status.as_str()),
+                );
+            }
+            if decision == AbsenceStatus::Requested {
+                errors.add("", "status", "decision should be approved or rejected".to_string());
+            }
+            errors.into_result()?;
+            absence.status = decision;
+            absence.decided_by = Some(user_id);
+            absence.save_simple(None, conn)
+        })
+    }
+
+    /// Balance of every absence type of employee in given year
+    pub fn balance(db: &Database, e_id: i32, year: i32) -> DaoResult<Vec<AbsenceBalance>> {
+        let mut conn = db.try_get_connection()?;
+        Self::balance_with_connection(e_id, year, &mut conn)
+    }
+
+    pub fn balance_with_connection(e_id: i32, year: i32, conn: &mut DbConnection) -> DaoResult<Vec<AbsenceBalance>> {
+        let started = started_in(e_id, conn)?;
+        let all_taken = taken(e_id, None, conn)?;
+        Ok(AbsenceTypeDTO::get_all_with_connection(None, conn)?
+            .iter()
+            .map(|t| {
+                let of_type: Vec<AbsenceDTO> = all_taken
+                    .iter()
+                    .filter(|a| Some(a.absence_type_id) == t.id)
+                    .cloned()
+                    .collect();
+                balance_of(t, year, started, &of_type)
+            })
+            .collect())
+    }
+}
+
+/// Absence in team calendar
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct CalendarEntry {
+    pub absence_id: i32,
+    pub employee_id: i32,
+    pub first_name: String,
+    pub last_name: String,
+    pub absence_type_id: i32,
+    pub absence_type: String,
+    pub from_date: NaiveDate,
+    pub to_date: NaiveDate,
+    pub days: i32,
+    pub status: AbsenceStatus,
+}
+
+/// Absences (requested or approved) of employees in scope overlapping period from `from` to `to` - optionally
+/// just employees of department and its sub-departments. Deleted employees are left out. Ordered by start
+/// of absence.
+pub fn calendar_with_connection(
+    from: NaiveDate,
+    to: NaiveDate,
+    department: Option<i32>,
+    scope: EmployeeScope,
+    conn: &mut DbConnection,
+) -> DaoResult<Vec<CalendarEntry>> {
+    use crate::schema::absence_types::dsl as t;
+    use crate::schema::employees::dsl as e;
+
+    let mut errors = Errors::default();
+    if to < from {
+        errors.add("", "to", format!("can't be before from {}", from));
+    }
+    errors.into_result()?;
+    let mut query = absences
+        .inner_join(e::employees)
+        .inner_join(t::absence_types)
+        .filter(from_date.le(to))
+        .filter(to_date.ge(from))
+        .filter(status.ne(AbsenceStatus::Rejected.as_str()))
+        .filter(e::deleted_at.is_null())
+        .select((
+            absence_id,
+            e::id,
+            e::first_name,
+            e::last_name,
+            t::id,
+            t::name,
+            from_date,
+            to_date,
+            days,
+            status,
+        ))
+        .order((from_date, e::last_name, e::first_name, absence_id))
+        .into_boxed();
+    if let Some(ids) = scope.employee_ids(conn)? {
+        query = query.filter(e::id.eq_any(ids));
+    }
+    if let Some(department) = department {
+        query = query.filter(e::department_id.eq_any(descendants(Tree::Departments, department, conn)?));
+    }
+    type Row = (i32, i32, String, String, i32, String, NaiveDate, NaiveDate, i32, String);
+    Ok(query
+        .load::<Row>(conn)?
+        .into_iter()
+        .map(|r| CalendarEntry {
+            absence_id: r.0,
+            employee_id: r.1,
+            first_name: r.2,
+            last_name: r.3,
+            absence_type_id: r.4,
+            absence_type: r.5,
+            from_date: r.6,
+            to_date: r.7,
+            days: r.8,
+            status: r.9.parse().expect("status is checked by DB"),
+        })
+        .collect())
+}
+
+pub fn calendar(
+    db: &Database,
+    from: NaiveDate,
+    to: NaiveDate,
+    department: Option<i32>,
+    scope: EmployeeScope,
+) -> DaoResult<Vec<CalendarEntry>> {
+    let mut conn = db.try_get_connection()?;
+    calendar_with_connection(from, to, department, scope, &mut conn)
+}
+
+/// Absences of deleted employee are deleted with it
+pub(crate) fn delete_absences_of(e_id: i32, conn: &mut DbConnection) -> QueryResult<usize> {
+    diesel::delete(absences.filter(employee_id.eq(e_id))).execute(conn)
+}
+
+/// Comments of absences of anonymized employee are dropped - they can say anything about the employee
+pub(crate) fn anonymize_absences_of(e_id: i32, conn: &mut DbConnection) -> QueryResult<usize> {
+    diesel::update(absences.filter(employee_id.eq(e_id)))
+        .set((comment.eq(None::<String>), search_string.eq(""), version.eq(version + 1)))
+        .execute(conn)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::common_for_tests::*;
+    use crate::error::DaoError;
+    use crate::hierarchy::can_approve;
+    use crate::models::NewUser;
+    use crate::users_dao::create_user;
+    use crate::EmployeeDTO;
+
+    use super::*;
+
+    const VACATION: i32 = 1;
+    const SICK_LEAVE: i32 = 2;
+
+    impl CrudTests for AbsenceDTO {}
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    fn save_employee(last_name: &str, manager: Option<i32>, conn: &mut DbConnection) -> i32 {
+        EmployeeDTO {
+            id: None,
+            first_name: "Jan".to_string(),
+            last_name: last_name.to_string(),
+            search_string: "".to_string(),
+            salaries: vec![],
+            contacts: vec![],
+            version: None,
+            department_id: None,
+            manager_id: manager,
+            contracts: vec![],
+            ..Default::default()
+        }
+        .save_in_transaction(keys(), conn)
+        .unwrap()
+        .id
+        .unwrap()
+    }
+
+    fn absence(e_id: i32, type_id: i32, from: NaiveDate, to: NaiveDate) -> AbsenceDTO {
+        AbsenceDTO {
+            id: None,
+            employee_id: Some(e_id),
+            absence_type_id: type_id,
+            from_date: from,
+            to_date: to,
+            days: 0,
+            status: AbsenceStatus::Requested,
+            decided_by: None,
+            comment: None,
+            search_string: "".to_string(),
+            version: None,
+        }
+    }
+
+    fn validation_error(a: &AbsenceDTO, conn: &mut DbConnection) -> String {
+        match a.try_save_in_transaction(&Default::default(), keys(), conn) {
+            Err(DaoError::Validation(errors)) => errors[0].field.clone(),
+            result => panic!("Should report validation error and instead I got {:?}", result),
+        }
+    }
+
+    #[test]
+    fn working_days_skip_weekends() {
+        // 2021-03-01 is Monday
+        assert_eq!(working_days(date(2021, 3, 1), date(2021, 3, 5)), 5);
+        assert_eq!(working_days(date(2021, 3, 1), date(2021, 3, 7)), 5);
+        assert_eq!(working_days(date(2021, 3, 6), date(2021, 3, 7)), 0);
+        assert_eq!(working_days(date(2021, 3, 1), date(2021, 3, 14)), 10);
+        assert_eq!(working_days(date(2021, 3, 2), date(2021, 3, 1)), 0);
+    }
+
+    #[test]
+    fn crud_operations_on_absence() {
+        let conn = &mut initialize();
+        let e_id = save_employee("Kowalski", None, conn);
+        absence(e_id, VACATION, date(2021, 3, 1), date(2021, 3, 5)).test(conn);
+    }
+
+    #[test]
+    fn absences_are_requested_and_decided() {
+        let conn = &mut initialize();
+        let manager = save_employee("Manager", None, conn);
+        let e_id = save_employee("Kowalski", Some(manager), conn);
+        let manager_user = create_user(
+            &NewUser {
+                username: "manager".to_string(),
+                password: "".to_string(),
+                is_admin: false,
+                employee_id: Some(manager),
+                privacy_officer: false,
+            },
+            conn,
+        )
+        .unwrap();
+
+        let requested = absence(e_id, VACATION, date(2021, 3, 1), date(2021, 3, 7))
+            .try_save_in_transaction(&Default::default(), keys(), conn)
+            .unwrap();
+        assert_eq!(requested.days, 5);
+        assert_eq!(requested.status, AbsenceStatus::Requested);
+
+        // Overlapping absences and periods without working day are rejected
+        assert_eq!(validation_error(&absence(e_id, SICK_LEAVE, date(2021, 3, 5), date(2021, 3, 9)), conn), "from_date");
+        assert_eq!(validation_error(&absence(e_id, SICK_LEAVE, date(2021, 3, 13), date(2021, 3, 14)), conn), "to_date");
+        assert_eq!(validation_error(&absence(e_id, 1000, date(2021, 4, 1), date(2021, 4, 1)), conn), "absence_type_id");
+
+        // Manager decide about absences of its reports - but not about its own ones
+        assert!(can_approve(manager_user.id, e_id, conn).unwrap());
+        assert!(!can_approve(manager_user.id, manager, conn).unwrap());
+        assert!(!can_approve(1, e_id, conn).unwrap());
+        assert!(can_approve(2, manager, conn).unwrap());
+
+        let a_id = requested.id.unwrap();
+        assert!(AbsenceDTO::decide_with_connection(a_id, 5, AbsenceStatus::Approved, manager_user.id, conn)
+            .unwrap_err()
+            .is_stale_version());
+        let approved =
+            AbsenceDTO::decide_with_connection(a_id, 1, AbsenceStatus::Approved, manager_user.id, conn).unwrap();
+        assert_eq!(approved.status, AbsenceStatus::Approved);
+        assert_eq!(approved.decided_by, Some(manager_user.id));
+        match AbsenceDTO::decide_with_connection(a_id, 2, AbsenceStatus::Rejected, manager_user.id, conn) {
+            Err(DaoError::Validation(errors)) => assert_eq!(errors[0].field, "status"),
+            result => panic!("Should report validation error and instead I got {:?}", result),
+        }
+        // Decided absence can't be changed
+        let changed = AbsenceDTO {
+            to_date: date(2021, 3, 2),
+            ..approved
+        };
+        assert_eq!(validation_error(&changed, conn), "status");
+
+        // Rejected absence doesn't block the period
+        let sick = absence(e_id, SICK_LEAVE, date(2021, 4, 1), date(2021, 4, 2))
+            .try_save_in_transaction(&Default::default(), keys(), conn)
+            .unwrap();
+        AbsenceDTO::decide_with_connection(sick.id.unwrap(), 1, AbsenceStatus::Rejected, 2, conn).unwrap();
+        assert!(absence(e_id, VACATION, date(2021, 4, 1), date(2021, 4, 2))
+            .try_save_in_transaction(&Default::default(), keys(), conn)
+            .is_ok());
+
+        let entries = calendar_with_connection(date(2021, 3, 1), date(2021, 4, 30), None, EmployeeScope::All, conn)
+            .unwrap();
+        let statuses: Vec<(NaiveDate, AbsenceStatus)> = entries.iter().map(|e| (e.from_date, e.status)).collect();
+        assert_eq!(
+            statuses,
+            vec![
+                (date(2021, 3, 1), AbsenceStatus::Approved),
+                (date(2021, 4, 1), AbsenceStatus::Requested)
+            ]
+        );
+        assert_eq!(entries[0].absence_type, "Vacation");
+        let manager_only =
+            calendar_with_connection(date(2021, 3, 1), date(2021, 4, 30), None, EmployeeScope::Subtree(manager), conn)
+                .unwrap();
+        assert_eq!(manager_only.len(), 2);
+        let nothing = calendar_with_connection(date(2021, 5, 1), date(2021, 5, 31), None, EmployeeScope::All, conn)
+            .unwrap();
+        assert!(nothing.is_empty());
+    }
+
+    #[test]
+    fn unused_days_are_carried_over() {
+        let conn = &mut initialize();
+        let e_id = save_employee("Kowalski", None, conn);
+        let approve = |a: AbsenceDTO, conn: &mut DbConnection| {
+            let saved = a.try_save_in_transaction(&Default::default(), keys(), conn).unwrap();
+            AbsenceDTO::decide_with_connection(saved.id.unwrap(), 1, AbsenceStatus::Approved, 2, conn).unwrap()
+        };
+        // 2020: 10 of 26 days taken - 16 carried over to 2021
+        approve(absence(e_id, VACATION, date(2020, 6, 1), date(2020, 6, 12)), conn);
+        // Absence over new year is counted in both years: 2 days in 2020, 4 days in 2021
+        approve(absence(e_id, VACATION, date(2020, 12, 30), date(2021, 1, 6)), conn);
+        absence(e_id, VACATION, date(2021, 2, 1), date(2021, 2, 5))
+            .try_save_in_transaction(&Default::default(), keys(), conn)
+            .unwrap();
+
+        let balance = AbsenceDTO::balance_with_connection(e_id, 2021, conn).unwrap();
+        let vacation = &balance[0];
+        assert_eq!(vacation.absence_type, "Vacation");
+        assert_eq!(vacation.carried_over, 14);
+        assert_eq!(vacation.used, 4);
+        assert_eq!(vacation.requested, 5);
+        assert_eq!(vacation.remaining, Some(26 + 14 - 4 - 5));
+        assert_eq!(balance[1].remaining, None);
+
+        // Absence can't exceed remaining days (31 working days from 2021-03-01)
+        let too_long = absence(e_id, VACATION, date(2021, 3, 1), date(2021, 4, 13));
+        assert_eq!(working_days(too_long.from_date, too_long.to_date), 32);
+        assert_eq!(validation_error(&too_long, conn), "to_date");
+        assert!(absence(e_id, VACATION, date(2021, 3, 1), date(2021, 4, 12))
+            .try_save_in_transaction(&Default::default(), keys(), conn)
+            .is_ok());
+    }
+}
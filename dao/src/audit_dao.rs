@@ -0,0 +1,100 @@
+use std::str::FromStr;
+
+use chrono::{Local, NaiveDateTime};
+use diesel::prelude::*;
+
+use crate::connection::DbConnection;
+use crate::models::{AuditEntry, NewAuditEntry};
+use crate::schema::audit_log::dsl::*;
+
+/// What was done with personal data of employee
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditAction {
+    PersonalDataExport,
+    Anonymization,
+}
+
+impl AuditAction {
+    /// How it is stored in DB
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AuditAction::PersonalDataExport => "personal_data_export",
+            AuditAction::Anonymization => "anonymization",
+        }
+    }
+}
+
+impl FromStr for AuditAction {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "personal_data_export" => Ok(AuditAction::PersonalDataExport),
+            "anonymization" => Ok(AuditAction::Anonymization),
+            _ => Err(format!(
+                "unknown audit action '{}' - should be one of personal_data_export, anonymization",
+                s
+            )),
+        }
+    }
+}
+
+/// Entry of audit log - entries are never changed nor deleted (user who is deleted is just forgotten)
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct AuditEntryDTO {
+    pub id: i32,
+    pub at: NaiveDateTime,
+    pub user_id: Option<i32>,
+    pub employee_id: Option<i32>,
+    pub action: AuditAction,
+    /// What exactly was done - it never contains personal data itself
+    pub details: String,
+}
+
+impl From<AuditEntry> for AuditEntryDTO {
+    fn from(a: AuditEntry) -> Self {
+        AuditEntryDTO {
+            id: a.id,
+            at: a.at,
+            user_id: a.user_id,
+            employee_id: a.employee_id,
+            action: a.action.parse().expect("action is checked by DB"),
+            details: a.details,
+        }
+    }
+}
+
+/// Record that `user` did `audit_action` with personal data of `employee` - it should be done in the
+/// same transaction as the action itself
+pub(crate) fn record(
+    user: i32,
+    employee: i32,
+    audit_action: AuditAction,
+    audit_details: &str,
+    conn: &mut DbConnection,
+) -> QueryResult<AuditEntryDTO> {
+    diesel::insert_into(audit_log)
+        .values(NewAuditEntry {
+            at: Local::now().naive_local(),
+            user_id: Some(user),
+            employee_id: Some(employee),
+            action: audit_action.as_str().to_string(),
+            details: audit_details.to_string(),
+        })
+        .get_result::<AuditEntry>(conn)
+        .map(AuditEntryDTO::from)
+}
+
+impl AuditEntryDTO {
+    /// Entries about employee - the oldest first
+    pub fn of_employee_with_connection(e_id: i32, conn: &mut DbConnection) -> QueryResult<Vec<Self>> {
+        Ok(audit_log
+            .filter(employee_id.eq(e_id))
+            .order(id)
+            .load::<AuditEntry>(conn)?
+            .into_iter()
+            .map(Self::from)
+            .collect())
+    }
+}
@@ -1,129 +1,205 @@
+use chrono::NaiveDate;
 use diesel::prelude::*;
-use diesel::sqlite::SqliteConnection;
 
-use crate::connection::get_connection;
+use crate::connection::{Database, DbConnection};
+use crate::crypto::KeyRing;
+use crate::error::{DaoError, DaoResult};
+use crate::validation::ValidationRules;
 
 pub trait HaveId {
     fn get_id(&self) -> Option<i32>;
 }
 
-/// Implement CRUD operations
+/// Record with row version - it is incremented on every update and update/delete succeed
+/// only when version is the same as the one which was read (optimistic locking)
+pub trait HaveVersion {
+    fn get_version(&self) -> Option<i32>;
+}
+
+/// Implement CRUD operations - `keys` (see Database::keys()) encrypt and decrypt encrypted columns of record,
+/// records without them ignore keys
 pub trait Crud
 where
-    Self: Sized + HaveId,
+    Self: Sized + HaveId + HaveVersion,
 {
     /// Update self from persisted - used in persist*()
     fn update(&mut self, persisted: &Self);
     /// Just retrieve T by id
-    fn get_simple(id_to_find: i32, conn: &mut SqliteConnection) -> QueryResult<Self>;
+    fn get_simple(id_to_find: i32, keys: Option<&KeyRing>, conn: &mut DbConnection) -> QueryResult<Self>;
     /// Save or update - as result should return just saved record (NOT self)  
-    fn save_simple(&self, conn: &mut SqliteConnection) -> QueryResult<Self>;
+    /// Update is done only when version match - DaoError::StaleVersion otherwise
+    fn save_simple(&self, keys: Option<&KeyRing>, conn: &mut DbConnection) -> DaoResult<Self>;
     /// Delete record
-    fn delete_simple(id_to_find: i32, conn: &mut SqliteConnection) -> QueryResult<usize>;
+    fn delete_simple(id_to_find: i32, conn: &mut DbConnection) -> QueryResult<usize>;
+    /// Delete record only when it is still in given version - 0 otherwise
+    fn delete_versioned_simple(
+        id_to_find: i32,
+        version_to_find: i32,
+        conn: &mut DbConnection,
+    ) -> QueryResult<usize>;
+
+    /// Check business rules before save - DaoError::Validation with all violations
+    fn validate(&self, _rules: &ValidationRules, _keys: Option<&KeyRing>, _conn: &mut DbConnection) -> DaoResult<()> {
+        Ok(())
+    }
 
-    /// Save using provided connection - uses save_simple()
-    fn save_in_transaction(&self, conn: &mut SqliteConnection) -> Option<Self> {
-        conn.transaction(|conn| self.save_simple(conn))
-            .optional()
-            .unwrap_or(None)
+    /// Validate and save using provided connection - uses validate() and save_simple()
+    fn try_save_in_transaction(
+        &self,
+        rules: &ValidationRules,
+        keys: Option<&KeyRing>,
+        conn: &mut DbConnection,
+    ) -> DaoResult<Self> {
+        conn.transaction(|conn| {
+            self.validate(rules, keys, conn)?;
+            self.save_simple(keys, conn)
+        })
+    }
+
+    /// The same as try_save_in_transaction() but then update Self by result
+    fn try_persist_in_transaction(
+        &mut self,
+        rules: &ValidationRules,
+        keys: Option<&KeyRing>,
+        conn: &mut DbConnection,
+    ) -> DaoResult<Self> {
+        self.try_save_in_transaction(rules, keys, conn)
+            .inspect(|s| self.update(s))
+    }
+
+    /// Save using provided connection (and default ValidationRules) - uses try_save_in_transaction()
+    fn save_in_transaction(&self, keys: Option<&KeyRing>, conn: &mut DbConnection) -> Option<Self> {
+        self.try_save_in_transaction(&ValidationRules::default(), keys, conn)
+            .ok()
     }
 
     /// The same as save_in_transaction() but then update Self by result - useful when you want save new record without ID and update Self with ID from database
-    fn persist_in_transaction(&mut self, conn: &mut SqliteConnection) -> Option<Self> {
-        self.save_in_transaction(conn).map(|s| {
-            self.update(&s);
-            s
-        })
+    fn persist_in_transaction(&mut self, keys: Option<&KeyRing>, conn: &mut DbConnection) -> Option<Self> {
+        self.try_persist_in_transaction(&ValidationRules::default(), keys, conn)
+            .ok()
     }
 
     /// Get by ID and provided connection
-    fn get_with_conn(id_to_find: i32, conn: &mut SqliteConnection) -> Option<Self> {
-        Self::get_simple(id_to_find, conn)
+    fn get_with_conn(id_to_find: i32, keys: Option<&KeyRing>, conn: &mut DbConnection) -> Option<Self> {
+        Self::get_simple(id_to_find, keys, conn)
             .optional()
             .unwrap_or(None)
     }
 
+    /// Get by ID and provided connection - None when it is not found, other errors are reported
+    fn try_get_with_conn(id_to_find: i32, keys: Option<&KeyRing>, conn: &mut DbConnection) -> DaoResult<Option<Self>> {
+        Ok(Self::get_simple(id_to_find, keys, conn).optional()?)
+    }
+
     /// Delete by ID and provided connection
-    fn delete_by_id_with_conn(id_to_find: i32, conn: &mut SqliteConnection) -> Option<usize> {
+    fn delete_by_id_with_conn(id_to_find: i32, conn: &mut DbConnection) -> Option<usize> {
         Self::delete_simple(id_to_find, conn)
             .optional()
             .unwrap_or(Some(0))
     }
 
-    /// Delete by provided connection
-    fn delete_with_conn(&self, conn: &mut SqliteConnection) -> Option<usize> {
-        if let Some(id) = self.get_id() {
-            Self::delete_simple(id, conn).optional().unwrap_or(Some(0))
-        } else {
-            Some(0)
+    /// Delete by provided connection - uses try_delete_with_conn()
+    fn delete_with_conn(&self, keys: Option<&KeyRing>, conn: &mut DbConnection) -> Option<usize> {
+        match self.try_delete_with_conn(keys, conn) {
+            Err(e) if e.is_not_found() => None,
+            result => Some(result.unwrap_or(0)),
         }
     }
 
-    /// Get by ID but it use default connection - uses get_with_conn()
-    fn get(id_to_find: i32) -> Option<Self> {
-        let conn = &mut get_connection();
-        Self::get_with_conn(id_to_find, conn)
+    /// Delete self but only when it is not modified since it was read -
+    /// DaoError::StaleVersion when it was, NotFound when there is nothing to delete
+    fn try_delete_with_conn(&self, keys: Option<&KeyRing>, conn: &mut DbConnection) -> DaoResult<usize> {
+        let id = match self.get_id() {
+            Some(id) => id,
+            None => return Ok(0),
+        };
+        conn.transaction(|conn| {
+            let deleted = match self.get_version() {
+                Some(version) => Self::delete_versioned_simple(id, version, conn)?,
+                None => 0,
+            };
+            if deleted == 0 {
+                let current = Self::get_simple(id, keys, conn)?;
+                return Err(stale_version(id, current.get_version()));
+            }
+            Ok(deleted)
+        })
+    }
+
+    /// Get by ID but it use connection from provided Database - uses get_with_conn()
+    fn get(db: &Database, id_to_find: i32) -> DaoResult<Option<Self>> {
+        let conn = &mut db.try_get_connection()?;
+        Ok(Self::get_with_conn(id_to_find, db.keys().as_deref(), conn))
     }
 
-    /// Save but it use default connection - uses save_in_transaction()
+    /// Save but it use connection and ValidationRules from provided Database - uses try_save_in_transaction()
     /// It return saved value. NOT mutate self
-    fn save(&self) -> Option<Self> {
-        let mut conn = get_connection();
-        self.save_in_transaction(&mut conn)
+    fn save(&self, db: &Database) -> DaoResult<Self> {
+        let conn = &mut db.try_get_connection()?;
+        self.try_save_in_transaction(&db.config().validation, db.keys().as_deref(), conn)
     }
 
-    /// Persist but it use default connection - uses persist_in_transaction()
+    /// Persist but it use connection and ValidationRules from provided Database - uses try_persist_in_transaction()
     /// It return saved value. MUTATE self
-    fn persist(&mut self) -> Option<Self> {
-        let mut conn = get_connection();
-        self.persist_in_transaction(&mut conn)
+    fn persist(&mut self, db: &Database) -> DaoResult<Self> {
+        let conn = &mut db.try_get_connection()?;
+        self.try_persist_in_transaction(&db.config().validation, db.keys().as_deref(), conn)
     }
 
-    /// Delete by ID but it use default connection - uses delete_with_conn()
-    fn delete_by_id(id_to_find: i32) -> Option<usize> {
-        let conn = &mut get_connection();
-        Self::delete_by_id_with_conn(id_to_find, conn)
+    /// Delete by ID but it use connection from provided Database - uses delete_with_conn()
+    fn delete_by_id(db: &Database, id_to_find: i32) -> DaoResult<Option<usize>> {
+        let conn = &mut db.try_get_connection()?;
+        Ok(Self::delete_by_id_with_conn(id_to_find, conn))
     }
 
-    /// Delete but it use default connection - uses delete_with_conn()
-    fn delete(&self) -> Option<usize> {
-        let conn = &mut get_connection();
-        self.delete_with_conn(conn)
+    /// Delete but it use connection from provided Database - uses delete_with_conn()
+    fn delete(&self, db: &Database) -> DaoResult<Option<usize>> {
+        let conn = &mut db.try_get_connection()?;
+        Ok(self.delete_with_conn(db.keys().as_deref(), conn))
     }
 }
 
+/// Error reported when versioned update/delete touched nothing while record still exists
+pub(crate) fn stale_version(id: i32, actual: Option<i32>) -> DaoError {
+    DaoError::StaleVersion {
+        id,
+        actual: actual.unwrap_or_default(),
+    }
+}
+
+/// Searches take keys (see Crud) too
 pub trait Searchable
 where
     Self: Sized,
 {
-    fn get_all() -> Vec<Self> {
-        let conn = &mut get_connection();
-        Self::get_all_with_connection(conn)
+    fn get_all(db: &Database) -> DaoResult<Vec<Self>> {
+        let conn = &mut db.try_get_connection()?;
+        Self::get_all_with_connection(db.keys().as_deref(), conn)
     }
-    fn search(s: &str) -> Vec<Self> {
-        let conn = &mut get_connection();
-        Self::search_with_connection(s, conn)
+    fn search(db: &Database, s: &str) -> DaoResult<Vec<Self>> {
+        let conn = &mut db.try_get_connection()?;
+        Self::search_with_connection(s, db.keys().as_deref(), conn)
     }
-    fn filter<P>(predicate: P) -> Vec<Self>
+    fn filter<P>(db: &Database, predicate: P) -> DaoResult<Vec<Self>>
     where
         P: FnMut(&Self) -> bool,
     {
-        let conn = &mut get_connection();
-        Self::filter_with_connection(predicate, conn)
+        let conn = &mut db.try_get_connection()?;
+        Self::filter_with_connection(predicate, db.keys().as_deref(), conn)
     }
 
-    fn get_all_with_connection(conn: &mut SqliteConnection) -> Vec<Self>;
+    fn get_all_with_connection(keys: Option<&KeyRing>, conn: &mut DbConnection) -> DaoResult<Vec<Self>>;
 
-    fn search_with_connection(s: &str, conn: &mut SqliteConnection) -> Vec<Self>;
+    fn search_with_connection(s: &str, keys: Option<&KeyRing>, conn: &mut DbConnection) -> DaoResult<Vec<Self>>;
 
-    fn filter_with_connection<P>(predicate: P, conn: &mut SqliteConnection) -> Vec<Self>
+    fn filter_with_connection<P>(predicate: P, keys: Option<&KeyRing>, conn: &mut DbConnection) -> DaoResult<Vec<Self>>
     where
         P: FnMut(&Self) -> bool,
     {
-        Self::get_all_with_connection(conn)
+        Ok(Self::get_all_with_connection(keys, conn)?
             .into_iter()
             .filter(predicate)
-            .collect()
+            .collect())
     }
 }
 
@@ -131,10 +207,34 @@ pub trait SearchableByParent
 where
     Self: Sized,
 {
-    fn search_by_parent(parent_id: i32) -> Vec<Self> {
-        let conn = &mut get_connection();
-        Self::search_by_parent_id_with_connection(parent_id, conn)
+    fn search_by_parent(db: &Database, parent_id: i32) -> DaoResult<Vec<Self>> {
+        let conn = &mut db.try_get_connection()?;
+        Self::search_by_parent_id_with_connection(parent_id, db.keys().as_deref(), conn)
+    }
+
+    fn search_by_parent_id_with_connection(
+        parent_id: i32,
+        keys: Option<&KeyRing>,
+        conn: &mut DbConnection,
+    ) -> DaoResult<Vec<Self>>;
+}
+
+/// Records valid in period (from_date - to_date, open-ended when to_date is empty) which belong to parent
+pub trait SearchableByDate
+where
+    Self: Sized,
+{
+    /// Record of parent valid on given date - when there are more (e.g. overlapping contacts)
+    /// the one which started last
+    fn effective_on(db: &Database, parent_id: i32, date: NaiveDate) -> DaoResult<Option<Self>> {
+        let conn = &mut db.try_get_connection()?;
+        Self::effective_on_with_connection(parent_id, date, db.keys().as_deref(), conn)
     }
 
-    fn search_by_parent_id_with_connection(parent_id: i32, conn: &mut SqliteConnection) -> Vec<Self>;
+    fn effective_on_with_connection(
+        parent_id: i32,
+        date: NaiveDate,
+        keys: Option<&KeyRing>,
+        conn: &mut DbConnection,
+    ) -> DaoResult<Option<Self>>;
 }
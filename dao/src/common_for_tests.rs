@@ -1,61 +1,88 @@
 use std::fmt::Debug;
-use std::io::stdout;
+use std::sync::OnceLock;
 
-#[cfg(test)]
 use diesel::dsl::*;
 use diesel::prelude::*;
-use diesel::sqlite::SqliteConnection;
-use diesel_migrations::{EmbeddedMigrations, MigrationHarness};
+use diesel_migrations::MigrationHarness;
+
 use crate::base_dao::{Crud, HaveId};
+use crate::connection::{Database, DbConnection, MIGRATIONS};
+use crate::crypto::{test_keys, KeyRing};
 use crate::schema::employees::dsl::id as employee_id;
 use crate::schema::employees::dsl::*;
 use crate::schema::users::dsl::id as user_id;
 use crate::schema::users::dsl::*;
 
+#[cfg(feature = "sqlite")]
 static TEST_DB_NAME: &str = ":memory:";
 
-pub const MIGRATIONS: EmbeddedMigrations = embed_migrations!("../migrations");
-
 pub fn initialize_log() {
     let _ = log4rs::init_file("log4rs.yml", Default::default());
 }
 
-pub fn initialize_db() -> SqliteConnection {
-    let mut conn = SqliteConnection::establish(TEST_DB_NAME)
-        .expect(&format!("Error connecting to {}", TEST_DB_NAME));
-    conn.run_pending_migrations(MIGRATIONS);
+/// Every test get fresh in-memory database
+#[cfg(feature = "sqlite")]
+pub fn initialize_db() -> DbConnection {
+    let mut conn = DbConnection::establish(TEST_DB_NAME)
+        .unwrap_or_else(|_| panic!("Error connecting to {}", TEST_DB_NAME));
+    crate::connection::configure_connection(&mut conn, &Default::default())
+        .expect("Fail to configure connection");
+    conn.run_pending_migrations(MIGRATIONS)
+        .expect("Fail to run migrations");
+    conn
+}
+
+/// Every test get its own set of tables in session scoped `pg_temp` schema of database
+/// pointed by TEST_DATABASE_URL - so tests are isolated and nothing left after them
+#[cfg(feature = "postgres")]
+pub fn initialize_db() -> DbConnection {
+    let test_db_url = std::env::var("TEST_DATABASE_URL")
+        .expect("TEST_DATABASE_URL have to point to PostgreSQL database used by tests");
+    let mut conn = DbConnection::establish(&test_db_url)
+        .unwrap_or_else(|_| panic!("Error connecting to {}", test_db_url));
+    diesel::sql_query("SET search_path TO pg_temp")
+        .execute(&mut conn)
+        .expect("Fail to set search_path");
+    conn.run_pending_migrations(MIGRATIONS)
+        .expect("Fail to run migrations");
     conn
 }
 
-pub fn initialize() -> SqliteConnection {
+pub fn initialize() -> DbConnection {
     initialize_log();
     initialize_db()
 }
 
-pub fn user_count(conn: &mut SqliteConnection) -> i64 {
+/// Keys of tests (see crypto::test_keys()) - tests encrypt and decrypt records with them
+pub fn keys() -> Option<&'static KeyRing> {
+    static KEYS: OnceLock<KeyRing> = OnceLock::new();
+    Some(KEYS.get_or_init(test_keys))
+}
+
+pub fn user_count(conn: &mut DbConnection) -> i64 {
     users.select(count(user_id)).first(conn).unwrap()
 }
 
-pub fn employee_count(conn: &mut SqliteConnection) -> i64 {
+pub fn employee_count(conn: &mut DbConnection) -> i64 {
     employees.select(count(employee_id)).first(conn).unwrap()
 }
 
-pub fn assert_user_count(expected: i64, conn: &mut SqliteConnection) {
+pub fn assert_user_count(expected: i64, conn: &mut DbConnection) {
     let user_count = user_count(conn);
     assert_eq!(user_count, expected);
 }
 
 #[allow(dead_code)]
-pub fn assert_employee_count(expected: i64, conn: &mut SqliteConnection) {
+pub fn assert_employee_count(expected: i64, conn: &mut DbConnection) {
     let employee_count = employee_count(conn);
     assert_eq!(employee_count, expected);
 }
 
 pub struct Assertions<T> {
-    pub saved: Option<fn(&T, &mut SqliteConnection)>,
-    pub get: Option<fn(&T, &mut SqliteConnection)>,
-    pub persisted: Option<fn(&T, &mut SqliteConnection)>,
-    pub deleted: Option<fn(&T, &mut SqliteConnection)>,
+    pub saved: Option<fn(&T, &mut DbConnection)>,
+    pub get: Option<fn(&T, &mut DbConnection)>,
+    pub persisted: Option<fn(&T, &mut DbConnection)>,
+    pub deleted: Option<fn(&T, &mut DbConnection)>,
 }
 
 impl<T> Assertions<T> {
@@ -68,22 +95,22 @@ impl<T> Assertions<T> {
         }
     }
     #[allow(dead_code)]
-    pub fn with_saved(mut self, f: fn(&T, &mut SqliteConnection)) -> Self {
+    pub fn with_saved(mut self, f: fn(&T, &mut DbConnection)) -> Self {
         self.saved = Some(f);
         self
     }
     #[allow(dead_code)]
-    pub fn with_get(mut self, f: fn(&T, &mut SqliteConnection)) -> Self {
+    pub fn with_get(mut self, f: fn(&T, &mut DbConnection)) -> Self {
         self.get = Some(f);
         self
     }
     #[allow(dead_code)]
-    pub fn with_persisted(mut self, f: fn(&T, &mut SqliteConnection)) -> Self {
+    pub fn with_persisted(mut self, f: fn(&T, &mut DbConnection)) -> Self {
         self.persisted = Some(f);
         self
     }
     #[allow(dead_code)]
-    pub fn with_deleted(mut self, f: fn(&T, &mut SqliteConnection)) -> Self {
+    pub fn with_deleted(mut self, f: fn(&T, &mut DbConnection)) -> Self {
         self.deleted = Some(f);
         self
     }
@@ -93,14 +120,18 @@ pub trait CrudTests
 where
     Self: Crud + HaveId + Debug,
 {
-    fn test(&mut self, conn: &mut SqliteConnection) {
+    /// Prepare deleted record to be persisted again - it is inserted as new one by default, soft deleted record
+    /// (see EmployeeDTO) has to forget its id for that
+    fn renew(&mut self) {}
+
+    fn test(&mut self, conn: &mut DbConnection) {
         Self::test_with_assertion(self, Assertions::new(), conn);
     }
 
-    fn test_with_assertion(&mut self, assertions: Assertions<Self>, conn: &mut SqliteConnection) {
+    fn test_with_assertion(&mut self, assertions: Assertions<Self>, conn: &mut DbConnection) {
         info!("About to test {:#?}", &self);
         // Save
-        let saved = self.save_in_transaction(conn);
+        let saved = self.save_in_transaction(keys(), conn);
         assert!(saved.is_some());
         let saved = saved.unwrap();
         let saved_id = saved.get_id();
@@ -110,7 +141,7 @@ where
             f(&saved, conn);
         }
         // Get
-        let saved = Self::get_with_conn(saved_id, conn);
+        let saved = Self::get_with_conn(saved_id, keys(), conn);
         assert!(saved.is_some());
         let saved = saved.unwrap();
         let saved_id2 = saved.get_id();
@@ -120,9 +151,11 @@ where
         if let Some(f) = assertions.get {
             f(&saved, conn);
         }
+        // Saved copy would overlap with persisted one (the same period)
+        assert_eq!(Self::delete_by_id_with_conn(saved_id, conn), Some(1));
         // Persist
         assert!(self.get_id().is_none());
-        let persisted = self.persist_in_transaction(conn);
+        let persisted = self.persist_in_transaction(keys(), conn);
         assert!(persisted.is_some());
         assert!(self.get_id().is_some());
         let persisted_id = persisted.unwrap().get_id();
@@ -134,43 +167,47 @@ where
             f(self, conn);
         }
         // Delete by id
-        let deleted = self.delete_with_conn(conn);
+        let deleted = self.delete_with_conn(keys(), conn);
         assert_eq!(deleted, Some(1));
-        let just_deleted = Self::get_with_conn(self_id, conn);
+        let just_deleted = Self::get_with_conn(self_id, keys(), conn);
         assert!(just_deleted.is_none());
         // Delete by self
-        self.persist_in_transaction(conn);
+        self.renew();
+        self.persist_in_transaction(keys(), conn);
         let self_id = self.get_id().unwrap();
-        let persisted = Self::get_with_conn(self_id, conn);
+        let persisted = Self::get_with_conn(self_id, keys(), conn);
         assert!(persisted.is_some());
-        let deleted = self.delete_with_conn(conn);
+        let deleted = self.delete_with_conn(keys(), conn);
         assert_eq!(deleted, Some(1));
-        let just_deleted = Self::get_with_conn(self_id, conn);
+        let just_deleted = Self::get_with_conn(self_id, keys(), conn);
         assert!(just_deleted.is_none());
         if let Some(f) = assertions.deleted {
             f(self, conn);
         }
     }
 
-    fn test_without_conn(&mut self) {
+    #[allow(dead_code)]
+    fn test_with_db(&mut self, db: &Database) {
         info!("About to test {:#?}", &self);
         // Save
-        let saved = self.save();
-        assert!(saved.is_some());
+        let saved = self.save(db);
+        assert!(saved.is_ok());
         let saved_id = saved.unwrap().get_id();
         assert!(saved_id.is_some());
         let saved_id = saved_id.unwrap();
         // Get
-        let saved = Self::get(saved_id);
+        let saved = Self::get(db, saved_id).unwrap();
         assert!(saved.is_some());
         let saved_id2 = saved.unwrap().get_id();
         assert!(saved_id2.is_some());
         let saved_id2 = saved_id2.unwrap();
         assert_eq!(saved_id, saved_id2);
+        // Saved copy would overlap with persisted one (the same period)
+        assert_eq!(Self::delete_by_id(db, saved_id).unwrap(), Some(1));
         // Persist
         assert!(self.get_id().is_none());
-        let persisted = self.persist();
-        assert!(persisted.is_some());
+        let persisted = self.persist(db);
+        assert!(persisted.is_ok());
         assert!(self.get_id().is_some());
         let persisted_id = persisted.unwrap().get_id();
         assert!(persisted_id.is_some());
@@ -178,18 +215,19 @@ where
         let self_id = self.get_id().unwrap();
         assert_eq!(self_id, persisted_id);
         // Delete by id
-        let deleted = self.delete();
+        let deleted = self.delete(db).unwrap();
         assert_eq!(deleted, Some(1));
-        let just_deleted = Self::get(self_id);
+        let just_deleted = Self::get(db, self_id).unwrap();
         assert!(just_deleted.is_none());
         // Delete by self
-        self.persist();
+        self.renew();
+        self.persist(db).unwrap();
         let self_id = self.get_id().unwrap();
-        let persisted = Self::get(self_id);
+        let persisted = Self::get(db, self_id).unwrap();
         assert!(persisted.is_some());
-        let deleted = self.delete();
+        let deleted = self.delete(db).unwrap();
         assert_eq!(deleted, Some(1));
-        let just_deleted = Self::get(self_id);
+        let just_deleted = Self::get(db, self_id).unwrap();
         assert!(just_deleted.is_none());
     }
 }
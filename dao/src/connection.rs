@@ -1,44 +1,809 @@
 use std::env;
+use std::fmt;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
 
-use diesel::r2d2::ConnectionManager;
-use diesel::r2d2::Pool;
-use diesel::sqlite::SqliteConnection;
+use chrono::Local;
+#[cfg(feature = "sqlite")]
+use diesel::connection::SimpleConnection;
+use diesel::r2d2::{ConnectionManager, CustomizeConnection, Pool};
+use diesel::{Connection, QueryResult};
 use diesel_migrations::{EmbeddedMigrations, MigrationHarness};
 use dotenv::dotenv;
 
-pub const MIGRATIONS: EmbeddedMigrations = embed_migrations!("../migrations");
-
-lazy_static! {
-    static ref POOL: Pool<ConnectionManager<SqliteConnection>> = create_connection_pool();
-}
-
-fn create_connection_pool() -> Pool<ConnectionManager<SqliteConnection>> {
-    dotenv().ok();
-    let database_url = match env::var("DATABASE_URL") {
-        Ok(url) => url,
-        Err(_) => String::from("rust_backend.sqlite3"),
-    };
-    let pool_size = match env::var("POOL_SIZE") {
-        Ok(pool_size) => pool_size.parse::<u32>().unwrap(),
-        Err(_) => 1,
-    };
-    info!(
-        "Initialize connection pool with DATABASE_URL='{}', POOL_SIZE='{}'",
-        database_url, pool_size
-    );
-    let manager = ConnectionManager::<SqliteConnection>::new(database_url);
-    Pool::builder()
-        .max_size(pool_size)
-        .build(manager)
-        .expect("Failed to create pool.")
-}
-
-pub fn initialize_db() {
-    let mut conn = get_connection();
-    info!("Initialize DB (if not exist), run migrations");
-    conn.run_pending_migrations(MIGRATIONS).expect("Fail to initiate DB");
-}
-
-pub fn get_connection() -> r2d2::PooledConnection<ConnectionManager<SqliteConnection>> {
-    POOL.get().unwrap()
+use crate::contacts_dao::{convert_legacy_contacts, reencrypt_contacts};
+use crate::crypto::{EncryptionKey, KeyRing, DEFAULT_KEY_ID};
+use crate::employee_number::convert_legacy_employees;
+use crate::employees_dao::{reencrypt_emergency_contacts, reencrypt_national_ids, EmployeeDTO};
+use crate::error::{ConfigError, DaoError, DaoResult};
+use crate::money::Currency;
+use crate::payroll_dao::reencrypt_payslips;
+use crate::salaries_dao::{convert_legacy_salaries, reencrypt_salaries};
+use crate::storage::{DocumentStorage, DocumentsConfig, S3Config, StorageConfig};
+use crate::validation::ValidationRules;
+
+#[cfg(all(feature = "sqlite", feature = "postgres"))]
+compile_error!("Features \"sqlite\" and \"postgres\" are mutually exclusive - use `--no-default-features --features postgres` for PostgreSQL");
+
+#[cfg(not(any(feature = "sqlite", feature = "postgres")))]
+compile_error!("One of features \"sqlite\" or \"postgres\" have to be enabled");
+
+/// Connection to the database backend selected by cargo feature
+#[cfg(feature = "sqlite")]
+pub type DbConnection = diesel::sqlite::SqliteConnection;
+#[cfg(feature = "postgres")]
+pub type DbConnection = diesel::pg::PgConnection;
+
+#[cfg(feature = "sqlite")]
+pub const MIGRATIONS: EmbeddedMigrations = embed_migrations!("../migrations/sqlite");
+#[cfg(feature = "postgres")]
+pub const MIGRATIONS: EmbeddedMigrations = embed_migrations!("../migrations/postgres");
+
+#[cfg(feature = "sqlite")]
+const DEFAULT_DATABASE_URL: &str = "rust_backend.sqlite3";
+#[cfg(feature = "postgres")]
+const DEFAULT_DATABASE_URL: &str = "postgres://localhost/rust_backend";
+
+/// Backend which should be used for given DATABASE_URL - `postgres://` and `postgresql://`
+/// schemes mean PostgreSQL, everything else is treated as SQLite file (or `:memory:`)
+pub fn backend_for_url(database_url: &str) -> &'static str {
+    if database_url.starts_with("postgres://") || database_url.starts_with("postgresql://") {
+        "postgres"
+    } else {
+        "sqlite"
+    }
+}
+
+/// Backend compiled in (by cargo feature)
+pub fn compiled_backend() -> &'static str {
+    if cfg!(feature = "postgres") {
+        "postgres"
+    } else {
+        "sqlite"
+    }
+}
+
+/// Raw queries are written with PostgreSQL `$n` parameters - SQLite understands them as `?n`
+pub(crate) fn sql(query: &str) -> String {
+    if cfg!(feature = "sqlite") {
+        query.replace('$', "?")
+    } else {
+        query.to_string()
+    }
+}
+
+pub type PooledConnection = r2d2::PooledConnection<ConnectionManager<DbConnection>>;
+
+/// SQLite settings applied by PRAGMA on every connection checked out from pool
+/// (foreign keys are always enforced)
+#[derive(Clone, Debug)]
+pub struct SqliteConfig {
+    pub busy_timeout: Duration,
+    pub journal_mode: String,
+    pub synchronous: String,
+}
+
+impl Default for SqliteConfig {
+    fn default() -> Self {
+        SqliteConfig {
+            busy_timeout: Duration::from_millis(5000),
+            journal_mode: "WAL".to_string(),
+            synchronous: "NORMAL".to_string(),
+        }
+    }
+}
+
+const JOURNAL_MODES: &[&str] = &["DELETE", "TRUNCATE", "PERSIST", "MEMORY", "WAL", "OFF"];
+const SYNCHRONOUS_MODES: &[&str] = &["OFF", "NORMAL", "FULL", "EXTRA"];
+
+/// Configuration of Database
+#[derive(Clone, Debug)]
+pub struct DbConfig {
+    pub database_url: String,
+    pub pool_size: u32,
+    /// Minimum idle connections kept in pool - None means pool_size
+    pub min_idle: Option<u32>,
+    /// How long wait for connection from pool before give up (and answer 503)
+    pub connection_timeout: Duration,
+    /// Connections older than that are closed - None means they live forever
+    pub max_lifetime: Option<Duration>,
+    pub sqlite: SqliteConfig,
+    pub validation: ValidationRules,
+    /// Currency given to salaries saved before salaries had currency (DEFAULT_CURRENCY)
+    pub default_currency: Currency,
+    /// Keys of encrypted columns (ENCRYPTION_KEY or ENCRYPTION_KEY_FILE) - without them national IDs can't be
+    /// stored nor read and salary and payslip amounts, phones and addresses are stored in plain text
+    pub encryption_keys: Option<KeyRing>,
+    /// Deleted employees are purged that many days after they were deleted (DELETED_RETENTION_DAYS) -
+    /// None means they are kept forever
+    pub retention_days: Option<u32>,
+    /// Where documents of employees are stored and which of them can be uploaded
+    pub documents: DocumentsConfig,
+}
+
+impl DbConfig {
+    pub fn new(database_url: &str) -> DbConfig {
+        DbConfig {
+            database_url: database_url.to_string(),
+            pool_size: 1,
+            min_idle: None,
+            connection_timeout: Duration::from_secs(5),
+            max_lifetime: None,
+            sqlite: Default::default(),
+            validation: Default::default(),
+            default_currency: Currency::PLN,
+            encryption_keys: None,
+            retention_days: None,
+            documents: Default::default(),
+        }
+    }
+
+    /// Read configuration from environment (also from `.env`):
+    /// DATABASE_URL, POOL_SIZE, POOL_MIN_IDLE, POOL_CONNECTION_TIMEOUT_MS, POOL_MAX_LIFETIME_SECS,
+    /// SQLITE_BUSY_TIMEOUT_MS, SQLITE_JOURNAL_MODE, SQLITE_SYNCHRONOUS, CONTACTS_ALLOW_OVERLAP,
+    /// DEFAULT_CURRENCY, DEFAULT_COUNTRY, EMPLOYEE_NUMBER_FORMAT, ENCRYPTION_KEY (or ENCRYPTION_KEY_FILE),
+    /// DELETED_RETENTION_DAYS, DOCUMENTS_DIR (or S3_ENDPOINT, S3_BUCKET, S3_REGION, S3_ACCESS_KEY and S3_SECRET_KEY),
+    /// DOCUMENT_MAX_SIZE and DOCUMENT_MIME_TYPES
+    pub fn from_env() -> Result<DbConfig, ConfigError> {
+        dotenv().ok();
+        DbConfig::from_lookup(|name| env::var(name).ok())
+    }
+
+    /// The same as from_env() but read variables by provided lookup function
+    pub fn from_lookup<F>(lookup: F) -> Result<DbConfig, ConfigError>
+    where
+        F: Fn(&str) -> Option<String>,
+    {
+        let database_url = lookup("DATABASE_URL").unwrap_or_else(|| DEFAULT_DATABASE_URL.to_string());
+        let mut config = DbConfig::new(&database_url);
+        if let Some(pool_size) = parse_var(&lookup, "POOL_SIZE")? {
+            config.pool_size = pool_size;
+        }
+        config.min_idle = parse_var(&lookup, "POOL_MIN_IDLE")?;
+        if let Some(timeout) = parse_var(&lookup, "POOL_CONNECTION_TIMEOUT_MS")? {
+            config.connection_timeout = Duration::from_millis(timeout);
+        }
+        config.max_lifetime = parse_var(&lookup, "POOL_MAX_LIFETIME_SECS")?.map(Duration::from_secs);
+        if let Some(busy_timeout) = parse_var(&lookup, "SQLITE_BUSY_TIMEOUT_MS")? {
+            config.sqlite.busy_timeout = Duration::from_millis(busy_timeout);
+        }
+        if let Some(journal_mode) = lookup("SQLITE_JOURNAL_MODE") {
+            config.sqlite.journal_mode = journal_mode.to_uppercase();
+        }
+        if let Some(synchronous) = lookup("SQLITE_SYNCHRONOUS") {
+            config.sqlite.synchronous = synchronous.to_uppercase();
+        }
+        if let Some(allow) = parse_var(&lookup, "CONTACTS_ALLOW_OVERLAP")? {
+            config.validation.allow_overlapping_contacts = allow;
+        }
+        if let Some(default_currency) = parse_var(&lookup, "DEFAULT_CURRENCY")? {
+            config.default_currency = default_currency;
+        }
+        if let Some(default_country) = parse_var(&lookup, "DEFAULT_COUNTRY")? {
+            config.validation.default_country = default_country;
+        }
+        if let Some(format) = parse_var(&lookup, "EMPLOYEE_NUMBER_FORMAT")? {
+            config.validation.employee_number_format = format;
+        }
+        config.encryption_keys = match (lookup("ENCRYPTION_KEY"), lookup("ENCRYPTION_KEY_FILE")) {
+            (Some(_), Some(path)) => {
+                return Err(ConfigError::invalid(
+                    "ENCRYPTION_KEY_FILE",
+                    path,
+                    "can't be used together with ENCRYPTION_KEY",
+                ));
+            }
+            (Some(keys), None) => Some(
+                keys.parse()
+                    .map_err(|e: String| ConfigError::invalid("ENCRYPTION_KEY", "***", &e))?,
+            ),
+            (None, Some(path)) => {
+                let keys = std::fs::read_to_string(&path)
+                    .map_err(|e| ConfigError::invalid("ENCRYPTION_KEY_FILE", &path, &e.to_string()))?;
+                Some(keys.parse().map_err(|e: String| ConfigError::invalid("ENCRYPTION_KEY_FILE", &path, &e))?)
+            }
+            (None, None) => None,
+        };
+        config.retention_days = parse_var(&lookup, "DELETED_RETENTION_DAYS")?;
+        match (lookup("DOCUMENTS_DIR"), lookup("S3_ENDPOINT")) {
+            (Some(_), Some(endpoint)) => {
+                return Err(ConfigError::invalid("S3_ENDPOINT", endpoint, "can't be used together with DOCUMENTS_DIR"));
+            }
+            (Some(dir), None) => config.documents.storage = StorageConfig::Local(PathBuf::from(dir)),
+            (None, Some(endpoint)) => {
+                let required = |name: &'static str| {
+                    lookup(name).ok_or_else(|| ConfigError::invalid(name, "", "is required together with S3_ENDPOINT"))
+                };
+                config.documents.storage = StorageConfig::S3(S3Config {
+                    endpoint,
+                    bucket: required("S3_BUCKET")?,
+                    region: lookup("S3_REGION").unwrap_or_else(|| "us-east-1".to_string()),
+                    access_key: required("S3_ACCESS_KEY")?,
+                    secret_key: required("S3_SECRET_KEY")?,
+                })
+            }
+            (None, None) => {}
+        }
+        if let Some(max_size) = parse_var(&lookup, "DOCUMENT_MAX_SIZE")? {
+            config.documents.max_size = max_size;
+        }
+        if let Some(mime_types) = lookup("DOCUMENT_MIME_TYPES") {
+            config.documents.mime_types = mime_types
+                .split(',')
+                .map(|t| t.trim().to_lowercase())
+                .filter(|t| !t.is_empty())
+                .collect();
+        }
+        config.validate()?;
+        Ok(config)
+    }
+
+    pub fn with_pool_size(mut self, pool_size: u32) -> DbConfig {
+        self.pool_size = pool_size;
+        self
+    }
+
+    /// Just one key - with DEFAULT_KEY_ID
+    pub fn with_encryption_key(mut self, encryption_key: EncryptionKey) -> DbConfig {
+        self.encryption_keys = Some(KeyRing::new(DEFAULT_KEY_ID, encryption_key));
+        self
+    }
+
+    pub fn with_encryption_keys(mut self, encryption_keys: KeyRing) -> DbConfig {
+        self.encryption_keys = Some(encryption_keys);
+        self
+    }
+
+    pub fn with_retention_days(mut self, retention_days: u32) -> DbConfig {
+        self.retention_days = Some(retention_days);
+        self
+    }
+
+    /// Documents are stored in local directory
+    pub fn with_documents_dir<P: Into<PathBuf>>(mut self, dir: P) -> DbConfig {
+        self.documents.storage = StorageConfig::Local(dir.into());
+        self
+    }
+
+    pub fn with_connection_timeout(mut self, connection_timeout: Duration) -> DbConfig {
+        self.connection_timeout = connection_timeout;
+        self
+    }
+
+    /// SQLite in-memory database exists only as long as its (single) connection
+    pub fn is_in_memory(&self) -> bool {
+        backend_for_url(&self.database_url) == "sqlite"
+            && (self.database_url == ":memory:" || self.database_url.contains("mode=memory"))
+    }
+
+    /// Check if configuration make sense - Database::new() do it before create pool
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let backend = backend_for_url(&self.database_url);
+        if backend != compiled_backend() {
+            return Err(ConfigError::BackendMismatch {
+                database_url: self.database_url.clone(),
+                required: backend,
+                compiled: compiled_backend(),
+            });
+        }
+        if self.pool_size == 0 {
+            return Err(ConfigError::invalid("POOL_SIZE", self.pool_size, "have to be greater than 0"));
+        }
+        if let Some(min_idle) = self.min_idle
+            && min_idle > self.pool_size
+        {
+            return Err(ConfigError::invalid(
+                "POOL_MIN_IDLE",
+                min_idle,
+                &format!("can't be greater than POOL_SIZE={}", self.pool_size),
+            ));
+        }
+        if self.connection_timeout.is_zero() {
+            return Err(ConfigError::invalid("POOL_CONNECTION_TIMEOUT_MS", 0, "have to be greater than 0"));
+        }
+        if self.is_in_memory() {
+            if self.pool_size > 1 {
+                return Err(ConfigError::invalid(
+                    "POOL_SIZE",
+                    self.pool_size,
+                    "in-memory SQLite database require POOL_SIZE=1 - every connection would get its own, empty database",
+                ));
+            }
+            if self.max_lifetime.is_some() || self.min_idle == Some(0) {
+                return Err(ConfigError::invalid(
+                    "POOL_MAX_LIFETIME_SECS/POOL_MIN_IDLE",
+                    self.database_url.clone(),
+                    "in-memory SQLite database is lost when its connection is closed",
+                ));
+            }
+        }
+        if !JOURNAL_MODES.contains(&self.sqlite.journal_mode.as_str()) {
+            return Err(ConfigError::invalid(
+                "SQLITE_JOURNAL_MODE",
+                self.sqlite.journal_mode.clone(),
+                &format!("should be one of {:?}", JOURNAL_MODES),
+            ));
+        }
+        if !SYNCHRONOUS_MODES.contains(&self.sqlite.synchronous.as_str()) {
+            return Err(ConfigError::invalid(
+                "SQLITE_SYNCHRONOUS",
+                self.sqlite.synchronous.clone(),
+                &format!("should be one of {:?}", SYNCHRONOUS_MODES),
+            ));
+        }
+        if let StorageConfig::S3(s3) = &self.documents.storage
+            && !(s3.endpoint.starts_with("http://") || s3.endpoint.starts_with("https://"))
+        {
+            return Err(ConfigError::invalid(
+                "S3_ENDPOINT",
+                s3.endpoint.clone(),
+                "should be http://host[:port] or https://host[:port]",
+            ));
+        }
+        if self.documents.max_size == 0 {
+            return Err(ConfigError::invalid("DOCUMENT_MAX_SIZE", 0, "have to be greater than 0"));
+        }
+        if let Some(invalid) = self.documents.mime_types.iter().find(|t| !t.contains('/')) {
+            return Err(ConfigError::invalid("DOCUMENT_MIME_TYPES", invalid, "is not MIME type (like application/pdf)"));
+        }
+        Ok(())
+    }
+}
+
+fn parse_var<F, T>(lookup: &F, name: &'static str) -> Result<Option<T>, ConfigError>
+where
+    F: Fn(&str) -> Option<String>,
+    T: FromStr,
+    T::Err: fmt::Display,
+{
+    match lookup(name) {
+        Some(value) => value
+            .trim()
+            .parse::<T>()
+            .map(Some)
+            .map_err(|e| ConfigError::invalid(name, value, &e.to_string())),
+        None => Ok(None),
+    }
+}
+
+/// Apply settings which have to be set on every new connection
+#[derive(Debug)]
+struct ConnectionCustomizer {
+    #[allow(dead_code)]
+    sqlite: SqliteConfig,
+}
+
+impl CustomizeConnection<DbConnection, diesel::r2d2::Error> for ConnectionCustomizer {
+    #[cfg(feature = "sqlite")]
+    fn on_acquire(&self, conn: &mut DbConnection) -> Result<(), diesel::r2d2::Error> {
+        configure_connection(conn, &self.sqlite).map_err(diesel::r2d2::Error::QueryError)
+    }
+}
+
+/// Set PRAGMAs on SQLite connection - busy_timeout go first so switching journal mode can wait for lock
+#[cfg(feature = "sqlite")]
+pub fn configure_connection(conn: &mut DbConnection, sqlite: &SqliteConfig) -> QueryResult<()> {
+    conn.batch_execute(&format!(
+        "PRAGMA busy_timeout = {}; PRAGMA journal_mode = {}; PRAGMA synchronous = {}; PRAGMA foreign_keys = ON;",
+        sqlite.busy_timeout.as_millis(),
+        sqlite.journal_mode,
+        sqlite.synchronous
+    ))
+}
+
+/// Handle to database - connection pool plus its configuration (and encryption keys and storage of documents).
+/// It is cheap to clone (pool is shared between clones) and every DAO operation take it
+/// (or connection checked out from it) as parameter, so several isolated databases can
+/// coexist in one process.
+#[derive(Clone)]
+pub struct Database {
+    pool: Pool<ConnectionManager<DbConnection>>,
+    config: DbConfig,
+    keys: Option<Arc<KeyRing>>,
+    storage: Arc<dyn DocumentStorage>,
+}
+
+impl Database {
+    pub fn new(config: DbConfig) -> Result<Database, ConfigError> {
+        config.validate()?;
+        info!("Initialize {} connection pool with {:?}", compiled_backend(), config);
+        let manager = ConnectionManager::<DbConnection>::new(config.database_url.clone());
+        let mut builder = Pool::builder()
+            .max_size(config.pool_size)
+            .min_idle(config.min_idle)
+            .connection_timeout(config.connection_timeout)
+            .max_lifetime(config.max_lifetime)
+            .connection_customizer(Box::new(ConnectionCustomizer {
+                sqlite: config.sqlite.clone(),
+            }));
+        if config.is_in_memory() {
+            builder = builder.idle_timeout(None);
+        }
+        let pool = builder.build(manager).map_err(ConfigError::Connect)?;
+        let storage = config.documents.open().map_err(|e| ConfigError::invalid("S3_ENDPOINT", "", &e.to_string()))?;
+        let keys = config.encryption_keys.clone().map(Arc::new);
+        Ok(Database { pool, config, keys, storage })
+    }
+
+    pub fn config(&self) -> &DbConfig {
+        &self.config
+    }
+
+    /// Keys of encrypted columns (see DbConfig::encryption_keys) every DAO operation which reads or writes them
+    /// takes - None when they are not configured
+    pub fn keys(&self) -> Option<Arc<KeyRing>> {
+        self.keys.clone()
+    }
+
+    /// Where content of documents is kept
+    pub fn storage(&self) -> &dyn DocumentStorage {
+        self.storage.as_ref()
+    }
+
+    /// Initialize DB (if not exist) - run pending migrations, convert rows saved before:
+    /// salaries had currency, contacts had structured details and employees had employee numbers
+    /// and purge employees deleted before retention period
+    pub fn initialize(&self) {
+        let mut conn = self.try_get_connection().expect("Fail to get connection to initiate DB");
+        info!("Initialize DB (if not exist), run migrations");
+        conn.run_pending_migrations(MIGRATIONS)
+            .expect("Fail to initiate DB");
+        let converted = convert_legacy_salaries(self.config.default_currency, &mut conn)
+            .expect("Fail to convert salaries to default currency");
+        if converted > 0 {
+            info!("{} salaries converted to {}", converted, self.config.default_currency);
+        }
+        let converted = convert_legacy_contacts(self.config.validation.default_country, &mut conn)
+            .expect("Fail to convert contact details");
+        if converted > 0 {
+            info!("{} phones and addresses converted to {}", converted, self.config.validation.default_country);
+        }
+        let converted = convert_legacy_employees(&self.config.validation.employee_number_format, &mut conn)
+            .expect("Fail to give employee numbers");
+        if converted > 0 {
+            info!("{} employees got employee number", converted);
+        }
+        self.purge_deleted().expect("Fail to purge deleted employees");
+    }
+
+    /// Purge employees deleted more than DELETED_RETENTION_DAYS ago - there is nothing to purge when
+    /// retention period is not configured. Return number of purged employees.
+    pub fn purge_deleted(&self) -> DaoResult<usize> {
+        let days = match self.config.retention_days {
+            Some(days) => days,
+            None => return Ok(0),
+        };
+        let cutoff = Local::now().naive_local() - chrono::Duration::days(days.into());
+        let mut conn = self.try_get_connection()?;
+        let purged = EmployeeDTO::purge_deleted_before_with_connection(cutoff, self.storage(), &mut conn)?;
+        if purged > 0 {
+            info!("{} employees deleted before {} purged", purged, cutoff);
+        }
+        Ok(purged)
+    }
+
+    /// Encrypt national IDs, salary and payslip amounts, phones and addresses which are in plain text or encrypted
+    /// by older key by the current key (see KeyRing) - older keys can be removed after that. Nothing is encrypted when
+    /// keys are not configured. Return number of re-encrypted rows.
+    pub fn reencrypt(&self) -> DaoResult<usize> {
+        let keys = match &self.config.encryption_keys {
+            Some(keys) => keys,
+            None => return Ok(0),
+        };
+        let mut conn = self.try_get_connection()?;
+        let reencrypted = conn.transaction(|conn| -> QueryResult<usize> {
+            let national_ids = reencrypt_national_ids(keys, conn)?;
+            let contacts = reencrypt_contacts(keys, conn)? + reencrypt_emergency_contacts(keys, conn)?;
+            Ok(national_ids + contacts + reencrypt_salaries(keys, conn)? + reencrypt_payslips(keys, conn)?)
+        })?;
+        if reencrypted > 0 {
+            info!("{} rows re-encrypted", reencrypted);
+        }
+        Ok(reencrypted)
+    }
+
+    /// Connection from pool - pool timeout is reported as DaoError::Pool (503 Service Unavailable in REST)
+    pub fn try_get_connection(&self) -> DaoResult<PooledConnection> {
+        self.pool.get().map_err(DaoError::from)
+    }
+}
+
+impl fmt::Debug for Database {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Database")
+            .field("config", &self.config)
+            .field("state", &self.pool.state())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::country::Country;
+
+    use super::*;
+
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn databases_are_isolated() {
+        use crate::users_dao::get_users;
+        use crate::{create_user, NewUser};
+
+        let db1 = Database::new(DbConfig::new(":memory:")).unwrap();
+        let db2 = Database::new(DbConfig::new(":memory:")).unwrap();
+        db1.initialize();
+        db2.initialize();
+        let new_user = NewUser {
+            username: "only_in_db1".to_string(),
+            password: "pass".to_string(),
+            is_admin: false,
+            employee_id: None,
+            privacy_officer: false,
+        };
+        create_user(&new_user, &mut db1.try_get_connection().unwrap()).unwrap();
+        assert_eq!(3, get_users(&mut db1.try_get_connection().unwrap()).len());
+        assert_eq!(2, get_users(&mut db2.try_get_connection().unwrap()).len());
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn databases_have_own_encryption_keys() {
+        use crate::crypto::test_keys;
+
+        let encrypted = Database::new(DbConfig::new(":memory:").with_encryption_keys(test_keys())).unwrap();
+        let plain = Database::new(DbConfig::new(":memory:")).unwrap();
+        assert_eq!(encrypted.keys().as_deref(), Some(&test_keys()));
+        assert!(plain.keys().is_none());
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn foreign_keys_are_enforced() {
+        use diesel::prelude::*;
+
+        let db = Database::new(DbConfig::new(":memory:")).unwrap();
+        db.initialize();
+        let inserted = diesel::sql_query(
+            "insert into salaries(employee_id, from_date, to_date, amount, search_string) values (42, '2020-01-01', '2020-12-31', 1, '')",
+        )
+        .execute(&mut db.try_get_connection().unwrap());
+        assert!(inserted.is_err(), "Salary of not existing employee should be rejected");
+    }
+
+    #[test]
+    fn data_is_reencrypted_by_current_key() {
+        use chrono::NaiveDate;
+        use diesel::prelude::*;
+
+        use crate::base_dao::Crud;
+        use crate::common_for_tests::initialize;
+        use crate::contacts_dao::{ContactDTO, PhoneDTO, PhoneKind};
+        use crate::crypto::{encrypt_by_old_test_key, test_keys};
+        use crate::money::{Money, PayPeriod};
+        use crate::salaries_dao::SalaryDTO;
+        use crate::schema::contact_phones::dsl as cp;
+        use crate::schema::emergency_contacts::dsl as ec;
+        use crate::schema::employees::dsl as e;
+        use crate::schema::salaries::dsl as s;
+        use crate::{EmergencyContactDTO, EmployeeSearch, EmployeeScope};
+
+        let conn = &mut initialize();
+        let keys = &test_keys();
+        let saved = EmployeeDTO {
+            first_name: "Jan".to_string(),
+            last_name: "Kowalski".to_string(),
+            national_id: Some("85010112345".to_string()),
+            salaries: vec![SalaryDTO {
+                id: None,
+                employee_id: None,
+                from_date: NaiveDate::from_ymd_opt(2021, 1, 1).unwrap(),
+                to_date: None,
+                amount: Money::new(500_000, Currency::PLN),
+                pay_period: PayPeriod::Monthly,
+                gross: true,
+                search_string: "".to_string(),
+                version: None,
+                contract_id: None,
+            }],
+            contacts: vec![ContactDTO {
+                id: None,
+                employee_id: None,
+                from_date: NaiveDate::from_ymd_opt(2021, 1, 1).unwrap(),
+                to_date: None,
+                emails: vec![],
+                phones: vec![PhoneDTO {
+                    kind: PhoneKind::Mobile,
+                    number: "+48602345678".to_string(),
+                }],
+                addresses: vec![],
+                search_string: "".to_string(),
+                version: None,
+            }],
+            emergency_contacts: vec![EmergencyContactDTO {
+                name: "Anna Kowalska".to_string(),
+                relationship: "wife".to_string(),
+                phone: "+48601234567".to_string(),
+                email: None,
+            }],
+            ..Default::default()
+        }
+        .save_in_transaction(Some(keys), conn)
+        .unwrap();
+        let e_id = saved.id.unwrap();
+        let stored: (i64, Option<String>) = s::salaries
+            .filter(s::employee_id.eq(e_id))
+            .select((s::amount, s::amount_encrypted))
+            .first(conn)
+            .unwrap();
+        assert_eq!(stored.0, 0, "Salary amount should be stored encrypted only");
+        assert!(stored.1.is_some());
+        let phone: String = ec::emergency_contacts.select(ec::phone).first(conn).unwrap();
+        assert!(phone.starts_with("0:"), "Phone of emergency contact should be stored encrypted");
+        assert_eq!(reencrypt_salaries(keys, conn).unwrap() + reencrypt_contacts(keys, conn).unwrap(), 0);
+        assert_eq!(reencrypt_emergency_contacts(keys, conn).unwrap(), 0);
+
+        // As it was before encryption of salaries and with national ID and phone encrypted by older key
+        diesel::update(s::salaries.filter(s::employee_id.eq(e_id)))
+            .set((s::amount.eq(500_000), s::amount_encrypted.eq(None::<String>)))
+            .execute(conn)
+            .unwrap();
+        diesel::update(e::employees.filter(e::id.eq(e_id)))
+            .set(e::national_id.eq(encrypt_by_old_test_key("85010112345")))
+            .execute(conn)
+            .unwrap();
+        diesel::update(cp::contact_phones)
+            .set((
+                cp::number.eq(encrypt_by_old_test_key("+48602345678")),
+                cp::number_index.eq(None::<String>),
+            ))
+            .execute(conn)
+            .unwrap();
+        diesel::update(ec::emergency_contacts)
+            .set((ec::phone.eq("+48601234567"), ec::encrypted.eq(false)))
+            .execute(conn)
+            .unwrap();
+        let by_phone = EmployeeSearch {
+            phone: Some("+48 602 345 678".to_string()),
+            ..Default::default()
+        };
+        let found =
+            EmployeeDTO::search_in_scope_with_connection(&by_phone, EmployeeScope::All, Some(keys), conn).unwrap();
+        assert!(found.is_empty(), "Phone without blind index can't be found");
+
+        let national_ids = reencrypt_national_ids(keys, conn).unwrap();
+        assert_eq!(national_ids + reencrypt_salaries(keys, conn).unwrap() + reencrypt_contacts(keys, conn).unwrap(), 3);
+        assert_eq!(reencrypt_emergency_contacts(keys, conn).unwrap(), 1);
+        let number: String = cp::contact_phones.select(cp::number).first(conn).unwrap();
+        assert!(number.starts_with("0:"), "Phone should be encrypted by the current key");
+        let read = EmployeeDTO::get_with_conn(e_id, Some(keys), conn).unwrap();
+        assert_eq!(read.national_id, saved.national_id);
+        assert_eq!(read.salaries[0].amount, Money::new(500_000, Currency::PLN));
+        assert_eq!(read.contacts[0].phones, saved.contacts[0].phones);
+        assert_eq!(read.emergency_contacts, saved.emergency_contacts);
+        let found =
+            EmployeeDTO::search_in_scope_with_connection(&by_phone, EmployeeScope::All, Some(keys), conn).unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(reencrypt_national_ids(keys, conn).unwrap(), 0, "Current data should not be re-encrypted");
+    }
+
+    #[test]
+    fn config_from_lookup() {
+        let vars = |name: &str| match name {
+            "DATABASE_URL" => Some(DEFAULT_DATABASE_URL.to_string()),
+            "POOL_SIZE" => Some("8".to_string()),
+            "POOL_MIN_IDLE" => Some("2".to_string()),
+            "POOL_CONNECTION_TIMEOUT_MS" => Some("250".to_string()),
+            "POOL_MAX_LIFETIME_SECS" => Some("3600".to_string()),
+            "SQLITE_BUSY_TIMEOUT_MS" => Some("1000".to_string()),
+            "SQLITE_JOURNAL_MODE" => Some("delete".to_string()),
+            "CONTACTS_ALLOW_OVERLAP" => Some("true".to_string()),
+            "DEFAULT_CURRENCY" => Some("EUR".to_string()),
+            "DEFAULT_COUNTRY" => Some("DE".to_string()),
+            "EMPLOYEE_NUMBER_FORMAT" => Some("HR-{seq:6}".to_string()),
+            "ENCRYPTION_KEY" => Some("BwcHBwcHBwcHBwcHBwcHBwcHBwcHBwcHBwcHBwcHBwc=".to_string()),
+            "DELETED_RETENTION_DAYS" => Some("3650".to_string()),
+            "DOCUMENTS_DIR" => Some("/var/lib/hr/documents".to_string()),
+            "DOCUMENT_MAX_SIZE" => Some("1048576".to_string()),
+            "DOCUMENT_MIME_TYPES" => Some("application/pdf, Image/PNG".to_string()),
+            _ => None,
+        };
+        let config = DbConfig::from_lookup(vars).unwrap();
+        assert_eq!(8, config.pool_size);
+        assert_eq!(Some(2), config.min_idle);
+        assert_eq!(Duration::from_millis(250), config.connection_timeout);
+        assert_eq!(Some(Duration::from_secs(3600)), config.max_lifetime);
+        assert_eq!(Duration::from_millis(1000), config.sqlite.busy_timeout);
+        assert_eq!("DELETE", config.sqlite.journal_mode);
+        assert_eq!("NORMAL", config.sqlite.synchronous);
+        assert!(config.validation.allow_overlapping_contacts);
+        assert_eq!(Currency::EUR, config.default_currency);
+        assert_eq!(Country::DE, config.validation.default_country);
+        assert_eq!("HR-{seq:6}", config.validation.employee_number_format.to_string());
+        assert_eq!(
+            Some(KeyRing::new(DEFAULT_KEY_ID, EncryptionKey::new([7; 32]))),
+            config.encryption_keys
+        );
+        assert_eq!(Some(3650), config.retention_days);
+        assert_eq!(StorageConfig::Local(PathBuf::from("/var/lib/hr/documents")), config.documents.storage);
+        assert_eq!(1048576, config.documents.max_size);
+        assert_eq!(vec!["application/pdf", "image/png"], config.documents.mime_types);
+
+        let s3_vars = |name: &str| match name {
+            "DATABASE_URL" => Some(DEFAULT_DATABASE_URL.to_string()),
+            "S3_ENDPOINT" => Some("http://minio:9000".to_string()),
+            "S3_BUCKET" => Some("documents".to_string()),
+            "S3_ACCESS_KEY" => Some("hr".to_string()),
+            "S3_SECRET_KEY" => Some("minio-s3cr3t".to_string()),
+            _ => None,
+        };
+        let config = DbConfig::from_lookup(s3_vars).unwrap();
+        match &config.documents.storage {
+            StorageConfig::S3(s3) => {
+                assert_eq!("us-east-1", s3.region);
+                assert!(!format!("{:?}", config).contains("minio-s3cr3t"), "Secret key should not be logged");
+            }
+            storage => panic!("Should be S3 storage and instead I got {:?}", storage),
+        }
+        let both = DbConfig::from_lookup(|name| s3_vars(name).or_else(|| (name == "DOCUMENTS_DIR").then(String::new)));
+        assert!(both.is_err());
+    }
+
+    #[test]
+    fn invalid_config_is_reported() {
+        let config_with = |name: &'static str, value: &'static str| {
+            DbConfig::from_lookup(move |n| {
+                if n == name {
+                    Some(value.to_string())
+                } else if n == "DATABASE_URL" {
+                    Some(DEFAULT_DATABASE_URL.to_string())
+                } else {
+                    None
+                }
+            })
+        };
+        let err = config_with("POOL_SIZE", "many").unwrap_err();
+        assert_eq!(
+            "Invalid POOL_SIZE='many': invalid digit found in string",
+            err.to_string()
+        );
+        assert!(config_with("POOL_SIZE", "0").is_err());
+        assert!(config_with("POOL_MIN_IDLE", "5").is_err());
+        assert!(config_with("POOL_CONNECTION_TIMEOUT_MS", "0").is_err());
+        assert!(config_with("SQLITE_JOURNAL_MODE", "fast").is_err());
+        assert!(config_with("SQLITE_SYNCHRONOUS", "always").is_err());
+        assert!(config_with("CONTACTS_ALLOW_OVERLAP", "maybe").is_err());
+        assert!(config_with("DEFAULT_CURRENCY", "zloty").is_err());
+        assert!(config_with("DEFAULT_COUNTRY", "Poland").is_err());
+        assert!(config_with("EMPLOYEE_NUMBER_FORMAT", "E-").is_err());
+        assert!(config_with("DELETED_RETENTION_DAYS", "-1").is_err());
+        assert!(config_with("S3_ENDPOINT", "http://minio:9000").is_err(), "S3_BUCKET and keys are required");
+        assert!(config_with("DOCUMENT_MAX_SIZE", "0").is_err());
+        assert!(config_with("DOCUMENT_MIME_TYPES", "pdf").is_err());
+        let mut ftp = DbConfig::new(DEFAULT_DATABASE_URL);
+        ftp.documents.storage = StorageConfig::S3(S3Config {
+            endpoint: "ftp://s3.amazonaws.com".to_string(),
+            bucket: "documents".to_string(),
+            region: "eu-central-1".to_string(),
+            access_key: "hr".to_string(),
+            secret_key: "secret".to_string(),
+        });
+        assert!(ftp.validate().is_err());
+        let err = config_with("ENCRYPTION_KEY", "c2hvcnQ=").unwrap_err();
+        assert_eq!("Invalid ENCRYPTION_KEY='***': has 5 bytes instead of 32", err.to_string());
+        let err = config_with("ENCRYPTION_KEY_FILE", "/not/existing/keys").unwrap_err();
+        assert!(err
+            .to_string()
+            .starts_with("Invalid ENCRYPTION_KEY_FILE='/not/existing/keys': "));
+        assert!(DbConfig::new(":memory:").with_pool_size(2).validate().is_err());
+    }
+
+    #[test]
+    fn backend_is_selected_by_url_scheme() {
+        assert_eq!("postgres", backend_for_url("postgres://localhost/rust_backend"));
+        assert_eq!("postgres", backend_for_url("postgresql://user:pass@db:5432/hr"));
+        assert_eq!("sqlite", backend_for_url("rust_backend.sqlite3"));
+        assert_eq!("sqlite", backend_for_url(":memory:"));
+    }
 }
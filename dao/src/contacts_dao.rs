@@ -1,26 +1,164 @@
+use std::collections::BTreeMap;
+
 use chrono::NaiveDate;
 use diesel::dsl::*;
 use diesel::prelude::*;
-use diesel::sqlite::SqliteConnection;
 
-use crate::base_dao::SearchableByParent;
-use crate::base_dao::{Crud, HaveId};
-use crate::models::{Contact, NewContact};
+use std::str::FromStr;
+
+use crate::base_dao::{SearchableByDate, SearchableByParent};
+use crate::base_dao::{stale_version, Crud, HaveId, HaveVersion};
+use crate::connection::DbConnection;
+use crate::country::{is_e164, Country};
+use crate::crypto::{self, KeyRing};
+use crate::employees_dao::not_deleted;
+use crate::error::DaoResult;
+use crate::validation::{check_no_overlap_with_existing, check_period, Errors, HavePeriod, ValidationRules};
+use crate::models::{
+    Contact, ContactAddress, ContactEmail, ContactPhone, NewContact, NewContactAddress, NewContactEmail,
+    NewContactPhone,
+};
 use crate::schema::contacts::dsl::id as contact_id;
 use crate::schema::contacts::dsl::*;
 use crate::Searchable;
 
+/// Whether email is for work or private matters
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum EmailKind {
+    Work,
+    Private,
+}
+
+impl EmailKind {
+    /// How it is stored in DB
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EmailKind::Work => "work",
+            EmailKind::Private => "private",
+        }
+    }
+}
+
+impl FromStr for EmailKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "work" => Ok(EmailKind::Work),
+            "private" => Ok(EmailKind::Private),
+            _ => Err(format!("unknown email kind '{}' - should be one of work, private", s)),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum PhoneKind {
+    Mobile,
+    Landline,
+}
+
+impl PhoneKind {
+    /// How it is stored in DB
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PhoneKind::Mobile => "mobile",
+            PhoneKind::Landline => "landline",
+        }
+    }
+}
+
+impl FromStr for PhoneKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "mobile" => Ok(PhoneKind::Mobile),
+            "landline" => Ok(PhoneKind::Landline),
+            _ => Err(format!("unknown phone kind '{}' - should be one of mobile, landline", s)),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum AddressKind {
+    Home,
+    Work,
+    /// Where letters should be sent when it is not home
+    Correspondence,
+}
+
+impl AddressKind {
+    /// How it is stored in DB
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AddressKind::Home => "home",
+            AddressKind::Work => "work",
+            AddressKind::Correspondence => "correspondence",
+        }
+    }
+}
+
+impl FromStr for AddressKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "home" => Ok(AddressKind::Home),
+            "work" => Ok(AddressKind::Work),
+            "correspondence" => Ok(AddressKind::Correspondence),
+            _ => Err(format!(
+                "unknown address kind '{}' - should be one of home, work, correspondence",
+                s
+            )),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct EmailDTO {
+    pub kind: EmailKind,
+    pub email: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct PhoneDTO {
+    pub kind: PhoneKind,
+    /// E.164 - number without `+` or `00` is normalized as number of DEFAULT_COUNTRY on save
+    pub number: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct AddressDTO {
+    pub kind: AddressKind,
+    pub street: String,
+    pub city: String,
+    /// In format of the country - "00-950" in PL
+    pub postal_code: String,
+    /// Two letter ISO-3166 code - "PL"
+    pub country: Country,
+}
+
+/// Contact details of employee valid in a period - emails, phones and addresses are kept in order they are given
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Hash)]
 pub struct ContactDTO {
     pub id: Option<i32>,
     pub employee_id: Option<i32>,
     pub from_date: NaiveDate,
-    pub to_date: NaiveDate,
-    pub phone: String,
-    pub address: Option<String>,
+    pub to_date: Option<NaiveDate>,
+    #[serde(default)]
+    pub emails: Vec<EmailDTO>,
+    #[serde(default)]
+    pub phones: Vec<PhoneDTO>,
+    #[serde(default)]
+    pub addresses: Vec<AddressDTO>,
     pub search_string: String,
+    pub version: Option<i32>,
 }
 
+/// Just contact row - emails, phones and addresses are loaded by with_details()
 impl From<Contact> for ContactDTO {
     fn from(c: Contact) -> Self {
         ContactDTO {
@@ -28,23 +166,11 @@ impl From<Contact> for ContactDTO {
             employee_id: Some(c.employee_id),
             from_date: c.from_date,
             to_date: c.to_date,
-            address: c.address,
-            phone: c.phone,
+            emails: vec![],
+            phones: vec![],
+            addresses: vec![],
             search_string: c.search_string,
-        }
-    }
-}
-
-impl From<&Contact> for ContactDTO {
-    fn from(c: &Contact) -> Self {
-        ContactDTO {
-            id: Some(c.id),
-            employee_id: Some(c.employee_id),
-            from_date: c.from_date,
-            to_date: c.to_date,
-            address: c.address.clone(),
-            phone: c.phone.clone(),
-            search_string: c.search_string.clone(),
+            version: Some(c.version),
         }
     }
 }
@@ -56,9 +182,8 @@ impl From<&ContactDTO> for Contact {
             employee_id: contact_dto.employee_id.unwrap(),
             from_date: contact_dto.from_date,
             to_date: contact_dto.to_date,
-            address: contact_dto.address.clone(),
-            phone: contact_dto.phone.clone(),
             search_string: contact_dto.search_string.clone(),
+            version: contact_dto.version.unwrap_or_default(),
         }
     }
 }
@@ -69,109 +194,868 @@ impl From<&ContactDTO> for NewContact {
             employee_id: contact_dto.employee_id.unwrap(),
             from_date: contact_dto.from_date,
             to_date: contact_dto.to_date,
-            address: contact_dto.address.clone(),
-            phone: contact_dto.phone.clone(),
             search_string: contact_dto.search_string.clone(),
         }
     }
 }
 
+/// Phone or address value as it is stored - encrypted when ENCRYPTION_KEY is configured
+pub(crate) fn seal(value: &str, keys: Option<&KeyRing>) -> String {
+    match keys {
+        Some(keys) => keys.encrypt(value).expect("AES-GCM encryption doesn't fail"),
+        None => value.to_string(),
+    }
+}
+
+/// Stored phone or address value of row `row_id` - encrypted one is decrypted, it can't be read without key
+pub(crate) fn unseal(
+    row_id: i32,
+    what: &str,
+    value: String,
+    encrypted: bool,
+    keys: Option<&KeyRing>,
+) -> QueryResult<String> {
+    if encrypted {
+        crypto::decrypt(keys, &value).map_err(|e| crypto::unreadable(row_id, what, e))
+    } else {
+        Ok(value)
+    }
+}
+
+/// Blind index of phone number - just encrypted numbers have it
+fn number_index(number: &str, keys: Option<&KeyRing>) -> Option<String> {
+    keys.map(|keys| keys.blind_index(number).expect("HMAC accepts key of any size"))
+}
+
+/// Contacts with their emails, phones and addresses
+fn with_details(found: Vec<Contact>, keys: Option<&KeyRing>, conn: &mut DbConnection) -> QueryResult<Vec<ContactDTO>> {
+    use crate::schema::contact_addresses::dsl as ca;
+    use crate::schema::contact_emails::dsl as ce;
+    use crate::schema::contact_phones::dsl as cp;
+
+    let emails = ContactEmail::belonging_to(&found)
+        .order(ce::position)
+        .load::<ContactEmail>(conn)?
+        .grouped_by(&found);
+    let phones = ContactPhone::belonging_to(&found)
+        .order(cp::position)
+        .load::<ContactPhone>(conn)?
+        .grouped_by(&found);
+    let addresses = ContactAddress::belonging_to(&found)
+        .order(ca::position)
+        .load::<ContactAddress>(conn)?
+        .grouped_by(&found);
+    found
+        .into_iter()
+        .zip(emails)
+        .zip(phones)
+        .zip(addresses)
+        .map(|(((c, emails), phones), addresses)| {
+            Ok(ContactDTO {
+                emails: emails
+                    .into_iter()
+                    .map(|e| EmailDTO {
+                        kind: e.kind.parse().expect("kind is checked by DB"),
+                        email: e.email,
+                    })
+                    .collect(),
+                phones: phones
+                    .into_iter()
+                    .map(|p| {
+                        Ok(PhoneDTO {
+                            kind: p.kind.parse().expect("kind is checked by DB"),
+                            number: unseal(p.id, "Phone", p.number, p.encrypted, keys)?,
+                        })
+                    })
+                    .collect::<QueryResult<_>>()?,
+                addresses: addresses
+                    .into_iter()
+                    .map(|a| {
+                        Ok(AddressDTO {
+                            kind: a.kind.parse().expect("kind is checked by DB"),
+                            street: unseal(a.id, "Address", a.street, a.encrypted, keys)?,
+                            city: unseal(a.id, "Address", a.city, a.encrypted, keys)?,
+                            postal_code: unseal(a.id, "Address", a.postal_code, a.encrypted, keys)?,
+                            country: a.country.parse().unwrap_or_else(|e| {
+                                panic!(
+                                    "Address id = {} has {} - rows from before addresses had country are converted by Database::initialize()",
+                                    a.id, e
+                                )
+                            }),
+                        })
+                    })
+                    .collect::<QueryResult<_>>()?,
+                ..ContactDTO::from(c)
+            })
+        })
+        .collect()
+}
+
+/// Replace emails, phones and addresses of contact by the ones in `c`
+fn save_details(c_id: i32, c: &ContactDTO, keys: Option<&KeyRing>, conn: &mut DbConnection) -> QueryResult<()> {
+    use crate::schema::contact_addresses::dsl as ca;
+    use crate::schema::contact_emails::dsl as ce;
+    use crate::schema::contact_phones::dsl as cp;
+
+    delete_details(&[c_id], conn)?;
+    let encrypted = keys.is_some();
+    let emails: Vec<NewContactEmail> = c
+        .emails
+        .iter()
+        .enumerate()
+        .map(|(i, e)| NewContactEmail {
+            contact_id: c_id,
+            position: i as i32,
+            kind: e.kind.as_str().to_string(),
+            email: e.email.clone(),
+        })
+        .collect();
+    insert_into(ce::contact_emails).values(&emails).execute(conn)?;
+    let phones: Vec<NewContactPhone> = c
+        .phones
+        .iter()
+        .enumerate()
+        .map(|(i, p)| NewContactPhone {
+            contact_id: c_id,
+            position: i as i32,
+            kind: p.kind.as_str().to_string(),
+            number: seal(&p.number, keys),
+            encrypted,
+            number_index: number_index(&p.number, keys),
+        })
+        .collect();
+    insert_into(cp::contact_phones).values(&phones).execute(conn)?;
+    let addresses: Vec<NewContactAddress> = c
+        .addresses
+        .iter()
+        .enumerate()
+        .map(|(i, a)| NewContactAddress {
+            contact_id: c_id,
+            position: i as i32,
+            kind: a.kind.as_str().to_string(),
+            street: seal(&a.street, keys),
+            city: seal(&a.city, keys),
+            postal_code: seal(&a.postal_code, keys),
+            country: a.country.code().to_string(),
+            encrypted,
+        })
+        .collect();
+    insert_into(ca::contact_addresses).values(&addresses).execute(conn)?;
+    Ok(())
+}
+
+fn delete_details(c_ids: &[i32], conn: &mut DbConnection) -> QueryResult<()> {
+    use crate::schema::contact_addresses::dsl as ca;
+    use crate::schema::contact_emails::dsl as ce;
+    use crate::schema::contact_phones::dsl as cp;
+
+    diesel::delete(ce::contact_emails.filter(ce::contact_id.eq_any(c_ids))).execute(conn)?;
+    diesel::delete(cp::contact_phones.filter(cp::contact_id.eq_any(c_ids))).execute(conn)?;
+    diesel::delete(ca::contact_addresses.filter(ca::contact_id.eq_any(c_ids))).execute(conn)?;
+    Ok(())
+}
+
+/// Contacts of deleted employee are deleted with it
+pub(crate) fn delete_contacts_of(e_id: i32, conn: &mut DbConnection) -> QueryResult<usize> {
+    let c_ids: Vec<i32> = contacts
+        .filter(employee_id.eq(e_id))
+        .select(contact_id)
+        .load(conn)?;
+    delete_details(&c_ids, conn)?;
+    diesel::delete(contacts.filter(employee_id.eq(e_id))).execute(conn)
+}
+
+/// Contacts of anonymized employee lose their emails, phones and addresses - just their periods are kept
+pub(crate) fn anonymize_contacts_of(e_id: i32, conn: &mut DbConnection) -> QueryResult<usize> {
+    let c_ids: Vec<i32> = contacts
+        .filter(employee_id.eq(e_id))
+        .select(contact_id)
+        .load(conn)?;
+    delete_details(&c_ids, conn)?;
+    diesel::update(contacts.filter(employee_id.eq(e_id)))
+        .set((search_string.eq(""), version.eq(version + 1)))
+        .execute(conn)
+}
+
+impl ContactDTO {
+    /// Emails trimmed (with domain in lower case), phones in E.164 (numbers without international prefix are
+    /// numbers of `default_country`) and postal codes in format of their country. Values which can't be
+    /// normalized are kept as they are - validate() reports them.
+    pub fn normalized(&self, default_country: Country) -> ContactDTO {
+        ContactDTO {
+            emails: self
+                .emails
+                .iter()
+                .map(|e| EmailDTO {
+                    email: match e.email.trim().rsplit_once('@') {
+                        Some((local, domain)) => format!("{}@{}", local, domain.to_lowercase()),
+                        None => e.email.trim().to_string(),
+                    },
+                    ..e.clone()
+                })
+                .collect(),
+            phones: self
+                .phones
+                .iter()
+                .map(|p| PhoneDTO {
+                    number: default_country
+                        .normalize_phone(&p.number)
+                        .unwrap_or_else(|_| p.number.clone()),
+                    ..p.clone()
+                })
+                .collect(),
+            addresses: self
+                .addresses
+                .iter()
+                .map(|a| AddressDTO {
+                    street: a.street.trim().to_string(),
+                    city: a.city.trim().to_string(),
+                    postal_code: a
+                        .country
+                        .normalize_postal_code(&a.postal_code)
+                        .unwrap_or_else(|_| a.postal_code.clone()),
+                    ..a.clone()
+                })
+                .collect(),
+            ..self.clone()
+        }
+    }
+}
+
+/// Local part and domain with at least two labels (`jan.kowalski@example.com`) - no quoted local parts
+pub(crate) fn is_valid_email(email: &str) -> bool {
+    let Some((local, domain)) = email.rsplit_once('@') else {
+        return false;
+    };
+    let local_ok = !local.is_empty()
+        && local.len() <= 64
+        && !local.starts_with('.')
+        && !local.ends_with('.')
+        && !local.contains("..")
+        && local
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || ".!#$%&'*+/=?^_`{|}~-".contains(c));
+    let labels: Vec<&str> = domain.split('.').collect();
+    let domain_ok = domain.len() <= 253
+        && labels.len() >= 2
+        && labels.iter().all(|l| {
+            (1..=63).contains(&l.len())
+                && !l.starts_with('-')
+                && !l.ends_with('-')
+                && l.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+        })
+        && labels
+            .last()
+            .is_some_and(|tld| tld.len() >= 2 && tld.chars().all(|c| c.is_ascii_alphabetic()));
+    local_ok && domain_ok
+}
+
+/// Emails have to be valid, phones in E.164 and addresses complete with postal code valid in their country -
+/// `c` should be normalized()
+pub(crate) fn check_contact_details(c: &ContactDTO, prefix: &str, errors: &mut Errors) {
+    for (i, e) in c.emails.iter().enumerate() {
+        if !is_valid_email(&e.email) {
+            errors.add(
+                prefix,
+                &format!("emails[{}].email", i),
+                format!("'{}' is not valid email", e.email),
+            );
+        }
+    }
+    for (i, p) in c.phones.iter().enumerate() {
+        if !is_e164(&p.number) {
+            errors.add(
+                prefix,
+                &format!("phones[{}].number", i),
+                format!("'{}' is not valid phone number", p.number),
+            );
+        }
+    }
+    for (i, a) in c.addresses.iter().enumerate() {
+        if a.street.is_empty() {
+            errors.add(prefix, &format!("addresses[{}].street", i), "can't be empty".to_string());
+        }
+        if a.city.is_empty() {
+            errors.add(prefix, &format!("addresses[{}].city", i), "can't be empty".to_string());
+        }
+        if let Err(e) = a.country.normalize_postal_code(&a.postal_code) {
+            errors.add(prefix, &format!("addresses[{}].postal_code", i), e);
+        }
+    }
+}
+
+/// Phones saved before they had to be in E.164 (not starting with `+`) are normalized as numbers of
+/// `default_country` and free-text addresses (marked by empty `country`) are split to street, postal code and
+/// city - `"ul. Marszałkowska 1, 00-950 Warszawa"`. Address which can't be split stays in street. Return number
+/// of converted phones and addresses.
+pub fn convert_legacy_contacts(default_country: Country, conn: &mut DbConnection) -> QueryResult<usize> {
+    use crate::schema::contact_addresses::dsl as ca;
+    use crate::schema::contact_phones::dsl as cp;
+
+    let mut converted = 0;
+    let legacy_phones: Vec<(i32, String)> = cp::contact_phones
+        .filter(cp::number.not_like("+%"))
+        .filter(cp::encrypted.eq(false))
+        .select((cp::id, cp::number))
+        .load(conn)?;
+    for (phone_id, raw) in legacy_phones {
+        match default_country.normalize_phone(&raw) {
+            Ok(normalized) => {
+                diesel::update(cp::contact_phones.filter(cp::id.eq(phone_id)))
+                    .set(cp::number.eq(normalized))
+                    .execute(conn)?;
+                converted += 1;
+            }
+            Err(e) => warn!("Phone id = {} is kept as it is: {}", phone_id, e),
+        }
+    }
+    let legacy_addresses: Vec<(i32, String)> = ca::contact_addresses
+        .filter(ca::country.eq(""))
+        .filter(ca::encrypted.eq(false))
+        .select((ca::id, ca::street))
+        .load(conn)?;
+    for (address_id, text) in legacy_addresses {
+        let split = text.rsplit_once(',').and_then(|(street, rest)| {
+            default_country
+                .split_postal_code(rest.trim())
+                .map(|(postal_code, city)| (street.trim(), postal_code, city))
+        });
+        let (street, postal_code, city) = split.unwrap_or((text.as_str(), String::new(), ""));
+        diesel::update(ca::contact_addresses.filter(ca::id.eq(address_id)))
+            .set((
+                ca::street.eq(street),
+                ca::postal_code.eq(postal_code),
+                ca::city.eq(city),
+                ca::country.eq(default_country.code()),
+            ))
+            .execute(conn)?;
+        converted += 1;
+    }
+    Ok(converted)
+}
+
+/// Phones and addresses in plain text or encrypted by other than the current key are encrypted by the current
+/// key - return number of such phones and addresses
+pub(crate) fn reencrypt_contacts(keys: &KeyRing, conn: &mut DbConnection) -> QueryResult<usize> {
+    use crate::schema::contact_addresses::dsl as ca;
+    use crate::schema::contact_phones::dsl as cp;
+
+    let mut reencrypted = 0;
+    let phones: Vec<(i32, String, bool)> = cp::contact_phones
+        .select((cp::id, cp::number, cp::encrypted))
+        .order(cp::id)
+        .load(conn)?;
+    for (phone_id, stored, encrypted) in phones {
+        if encrypted && keys.is_current(&stored) {
+            continue;
+        }
+        let number = if encrypted {
+            keys.decrypt(&stored).map_err(|e| crypto::unreadable(phone_id, "Phone", e))?
+        } else {
+            stored
+        };
+        diesel::update(cp::contact_phones.filter(cp::id.eq(phone_id)))
+            .set((
+                cp::number.eq(seal(&number, Some(keys))),
+                cp::encrypted.eq(true),
+                cp::number_index.eq(number_index(&number, Some(keys))),
+            ))
+            .execute(conn)?;
+        reencrypted += 1;
+    }
+    let addresses: Vec<(i32, String, String, String, bool)> = ca::contact_addresses
+        .select((ca::id, ca::street, ca::city, ca::postal_code, ca::encrypted))
+        .order(ca::id)
+        .load(conn)?;
+    for (address_id, street, city, postal_code, encrypted) in addresses {
+        if encrypted && keys.is_current(&street) {
+            continue;
+        }
+        let mut plain = [street, city, postal_code];
+        if encrypted {
+            for value in plain.iter_mut() {
+                *value = keys.decrypt(value).map_err(|e| crypto::unreadable(address_id, "Address", e))?;
+            }
+        }
+        diesel::update(ca::contact_addresses.filter(ca::id.eq(address_id)))
+            .set((
+                ca::street.eq(seal(&plain[0], Some(keys))),
+                ca::city.eq(seal(&plain[1], Some(keys))),
+                ca::postal_code.eq(seal(&plain[2], Some(keys))),
+                ca::encrypted.eq(true),
+            ))
+            .execute(conn)?;
+        reencrypted += 1;
+    }
+    Ok(reencrypted)
+}
+
+/// Employees with phone `number` (E.164) in any of their contacts - encrypted numbers are found by their blind
+/// index
+pub(crate) fn employees_with_phone(
+    number: &str,
+    keys: Option<&KeyRing>,
+    conn: &mut DbConnection,
+) -> QueryResult<Vec<i32>> {
+    use crate::schema::contact_phones::dsl as cp;
+
+    let indexes = match keys {
+        Some(keys) => keys.blind_indexes(number).expect("HMAC accepts key of any size"),
+        None => vec![],
+    };
+    let c_ids: Vec<i32> = cp::contact_phones
+        .filter(cp::encrypted.eq(false).and(cp::number.eq(number)).or(cp::number_index.eq_any(indexes)))
+        .select(cp::contact_id)
+        .load(conn)?;
+    contacts
+        .filter(contact_id.eq_any(c_ids))
+        .select(employee_id)
+        .distinct()
+        .load(conn)
+}
+
 impl HaveId for ContactDTO {
     fn get_id(&self) -> Option<i32> {
         self.id
     }
 }
 
+impl HavePeriod for ContactDTO {
+    fn period_start(&self) -> NaiveDate {
+        self.from_date
+    }
+
+    fn period_end(&self) -> Option<NaiveDate> {
+        self.to_date
+    }
+}
+
+impl HaveVersion for ContactDTO {
+    fn get_version(&self) -> Option<i32> {
+        self.version
+    }
+}
+
 impl Crud for ContactDTO {
     fn update(&mut self, persisted: &Self) {
         self.id = persisted.id;
+        self.version = persisted.version;
+    }
+
+    /// Period have to be valid and (unless allowed by rules) can't overlap with other contacts of the same employee.
+    /// Emails, phones and addresses are checked by check_contact_details().
+    fn validate(&self, rules: &ValidationRules, keys: Option<&KeyRing>, conn: &mut DbConnection) -> DaoResult<()> {
+        let mut errors = Errors::default();
+        check_period(self, "", &mut errors);
+        check_contact_details(self, "", &mut errors);
+        if let Some(parent_id) = self.employee_id
+            && !rules.allow_overlapping_contacts
+        {
+            let existing = Self::search_by_parent_id_with_connection(parent_id, keys, conn)?;
+            check_no_overlap_with_existing(self, &existing, "contact", &mut errors);
+        }
+        errors.into_result()
     }
 
-    fn get_simple(id_to_find: i32, conn: &mut SqliteConnection) -> QueryResult<ContactDTO> {
-        contacts
+    /// Phones and postal codes are normalized (with DEFAULT_COUNTRY for phones without international prefix)
+    /// before they are validated
+    fn try_save_in_transaction(
+        &self,
+        rules: &ValidationRules,
+        keys: Option<&KeyRing>,
+        conn: &mut DbConnection,
+    ) -> DaoResult<Self> {
+        let normalized = self.normalized(rules.default_country);
+        conn.transaction(|conn| {
+            normalized.validate(rules, keys, conn)?;
+            normalized.save_simple(keys, conn)
+        })
+    }
+
+    fn get_simple(id_to_find: i32, keys: Option<&KeyRing>, conn: &mut DbConnection) -> QueryResult<ContactDTO> {
+        let found = contacts
             .filter(contact_id.eq(id_to_find))
-            .first(conn)
-            .map(|c: Contact| ContactDTO::from(c))
+            .first::<Contact>(conn)?;
+        Ok(with_details(vec![found], keys, conn)?.remove(0))
     }
 
-    fn save_simple(&self, conn: &mut SqliteConnection) -> QueryResult<ContactDTO> {
-        fn insert(c: &ContactDTO, conn: &mut SqliteConnection) -> QueryResult<ContactDTO> {
-            insert_into(contacts)
+    fn save_simple(&self, keys: Option<&KeyRing>, conn: &mut DbConnection) -> DaoResult<ContactDTO> {
+        fn insert(c: &ContactDTO, keys: Option<&KeyRing>, conn: &mut DbConnection) -> QueryResult<ContactDTO> {
+            let c_id = insert_into(contacts)
                 .values(NewContact::from(c))
-                .execute(conn)
-                .and_then(|_| {
-                    contacts
-                        .order(contact_id.desc())
-                        .first(conn)
-                        .map(|c: Contact| ContactDTO::from(c))
-                })
+                .returning(contact_id)
+                .get_result::<i32>(conn)?;
+            save_details(c_id, c, keys, conn)?;
+            ContactDTO::get_simple(c_id, keys, conn)
         }
-        if self.id.is_some() {
-            let self_id = self.id.unwrap();
-            let updated = diesel::update(contacts.filter(contact_id.eq(self_id)))
-                .set(Contact::from(self))
-                .execute(conn)?;
+        if let Some(self_id) = self.id {
+            let updated = match self.version {
+                Some(self_version) => diesel::update(
+                    contacts
+                        .filter(contact_id.eq(self_id))
+                        .filter(version.eq(self_version)),
+                )
+                .set((Contact::from(self), version.eq(version + 1)))
+                .execute(conn)?,
+                None => 0,
+            };
             if updated == 0 {
-                insert(self, conn)
-            } else {
-                contacts
+                let current = contacts
                     .filter(contact_id.eq(self_id))
-                    .first(conn)
-                    .map(|c: Contact| ContactDTO::from(c))
+                    .select(version)
+                    .first::<i32>(conn)
+                    .optional()?;
+                match current {
+                    Some(current) => Err(stale_version(self_id, Some(current))),
+                    None => Ok(insert(self, keys, conn)?),
+                }
+            } else {
+                save_details(self_id, self, keys, conn)?;
+                Ok(Self::get_simple(self_id, keys, conn)?)
             }
         } else {
-            insert(self, conn)
+            Ok(insert(self, keys, conn)?)
         }
     }
 
-    fn delete_simple(id_to_find: i32, conn: &mut SqliteConnection) -> QueryResult<usize> {
+    fn delete_simple(id_to_find: i32, conn: &mut DbConnection) -> QueryResult<usize> {
+        delete_details(&[id_to_find], conn)?;
         diesel::delete(contacts.filter(contact_id.eq(id_to_find))).execute(conn)
     }
+
+    fn delete_versioned_simple(
+        id_to_find: i32,
+        version_to_find: i32,
+        conn: &mut DbConnection,
+    ) -> QueryResult<usize> {
+        let matching = contacts
+            .filter(contact_id.eq(id_to_find))
+            .filter(version.eq(version_to_find))
+            .count()
+            .get_result::<i64>(conn)?;
+        if matching == 0 {
+            return Ok(0);
+        }
+        delete_details(&[id_to_find], conn)?;
+        diesel::delete(
+            contacts
+                .filter(contact_id.eq(id_to_find))
+                .filter(version.eq(version_to_find)),
+        )
+        .execute(conn)
+    }
 }
 
+/// Records of deleted employees are left out
 impl Searchable for ContactDTO {
-    fn get_all_with_connection(conn: &mut SqliteConnection) -> Vec<Self> {
-        todo!()
+    fn get_all_with_connection(keys: Option<&KeyRing>, conn: &mut DbConnection) -> DaoResult<Vec<Self>> {
+        let found = contacts.filter(employee_id.eq_any(not_deleted())).load::<Contact>(conn)?;
+        Ok(with_details(found, keys, conn)?)
     }
 
-    fn search_with_connection(s: &str, conn: &mut SqliteConnection) -> Vec<Self> {
-        todo!()
+    fn search_with_connection(s: &str, keys: Option<&KeyRing>, conn: &mut DbConnection) -> DaoResult<Vec<Self>> {
+        let found = contacts
+            .filter(search_string.like(s))
+            .filter(employee_id.eq_any(not_deleted()))
+            .load::<Contact>(conn)?;
+        Ok(with_details(found, keys, conn)?)
     }
 }
 
 impl SearchableByParent for ContactDTO {
-    fn search_by_parent_id_with_connection(parent_id: i32, conn: &mut SqliteConnection) -> Vec<Self> {
-        todo!()
+    fn search_by_parent_id_with_connection(
+        parent_id: i32,
+        keys: Option<&KeyRing>,
+        conn: &mut DbConnection,
+    ) -> DaoResult<Vec<Self>> {
+        Ok(contacts_of(parent_id, keys, conn)?)
     }
 }
 
+impl SearchableByDate for ContactDTO {
+    fn effective_on_with_connection(
+        parent_id: i32,
+        date: NaiveDate,
+        keys: Option<&KeyRing>,
+        conn: &mut DbConnection,
+    ) -> DaoResult<Option<Self>> {
+        Ok(contact_on(parent_id, date, keys, conn)?)
+    }
+}
+
+/// Contacts of employee - see SearchableByParent
+pub(crate) fn contacts_of(
+    parent_id: i32,
+    keys: Option<&KeyRing>,
+    conn: &mut DbConnection,
+) -> QueryResult<Vec<ContactDTO>> {
+    contacts
+        .filter(employee_id.eq(parent_id))
+        .order(contact_id)
+        .load::<Contact>(conn)
+        .and_then(|found| with_details(found, keys, conn))
+}
+
+/// Contacts of employees by employee (ordered by id) - employees without contacts are left out
+pub(crate) fn contacts_of_many(
+    parent_ids: &[i32],
+    keys: Option<&KeyRing>,
+    conn: &mut DbConnection,
+) -> QueryResult<BTreeMap<i32, Vec<ContactDTO>>> {
+    let found = contacts
+        .filter(employee_id.eq_any(parent_ids))
+        .order(contact_id)
+        .load::<Contact>(conn)?;
+    let mut by_employee: BTreeMap<i32, Vec<ContactDTO>> = BTreeMap::new();
+    for contact in with_details(found, keys, conn)? {
+        by_employee
+            .entry(contact.employee_id.expect("saved contact has employee"))
+            .or_default()
+            .push(contact);
+    }
+    Ok(by_employee)
+}
+
+/// Contact of employee valid on date - see SearchableByDate
+pub(crate) fn contact_on(
+    parent_id: i32,
+    date: NaiveDate,
+    keys: Option<&KeyRing>,
+    conn: &mut DbConnection,
+) -> QueryResult<Option<ContactDTO>> {
+    contacts
+        .filter(employee_id.eq(parent_id))
+        .filter(from_date.le(date))
+        .filter(to_date.is_null().or(to_date.ge(date)))
+        .order(from_date.desc())
+        .first::<Contact>(conn)
+        .optional()
+        .and_then(|found| with_details(found.into_iter().collect(), keys, conn))
+        .map(|mut found| found.pop())
+}
+
 #[cfg(test)]
 mod tests {
     use diesel_migrations::{EmbeddedMigrations, MigrationHarness};
 
     use crate::common_for_tests::*;
+    use crate::error::DaoError;
 
     use super::*;
 
-    const MIGRATIONS: EmbeddedMigrations = embed_migrations!("test_data/contacts");
+    #[cfg(feature = "sqlite")]
+    const MIGRATIONS: EmbeddedMigrations = embed_migrations!("test_data/sqlite/contacts");
+    #[cfg(feature = "postgres")]
+    const MIGRATIONS: EmbeddedMigrations = embed_migrations!("test_data/postgres/contacts");
 
     impl CrudTests for ContactDTO {}
 
     #[test]
     fn crud_operations_on_contacts() {
         let conn = &mut initialize();
-        conn.run_pending_migrations(MIGRATIONS);
+        conn.run_pending_migrations(MIGRATIONS)
+            .expect("Fail to insert contacts test data into DB");
         let mut contact = ContactDTO {
             id: None,
             employee_id: Some(1),
             from_date: NaiveDate::from_ymd_opt(2015, 3, 14).unwrap(),
-            to_date: NaiveDate::from_ymd_opt(2020, 5, 23).unwrap(),
-            phone: "123456".to_string(),
-            address: Some("Some contact address".to_string()),
+            to_date: Some(NaiveDate::from_ymd_opt(2020, 5, 23).unwrap()),
+            emails: vec![EmailDTO {
+                kind: EmailKind::Work,
+                email: "jan.kowalski@example.com".to_string(),
+            }],
+            phones: vec![
+                PhoneDTO {
+                    kind: PhoneKind::Mobile,
+                    number: "+48601234567".to_string(),
+                },
+                PhoneDTO {
+                    kind: PhoneKind::Landline,
+                    number: "+48221234567".to_string(),
+                },
+            ],
+            addresses: vec![AddressDTO {
+                kind: AddressKind::Home,
+                street: "ul. Marszałkowska 1".to_string(),
+                city: "Warszawa".to_string(),
+                postal_code: "00-950".to_string(),
+                country: Country::PL,
+            }],
             search_string: "some search for contact".to_string(),
+            version: None,
+        };
+        // Details are kept in order they were given
+        let has_details = |c: &ContactDTO, _conn: &mut DbConnection| {
+            assert_eq!(c.emails[0].email, "jan.kowalski@example.com");
+            assert_eq!(
+                c.phones.iter().map(|p| p.kind).collect::<Vec<_>>(),
+                vec![PhoneKind::Mobile, PhoneKind::Landline]
+            );
+            assert_eq!(c.addresses[0].postal_code, "00-950");
         };
-        //salary.save_simple(conn).unwrap();
-        contact.test(conn);
-        //salary.test_without_conn();
+        contact.test_with_assertion(
+            Assertions::new().with_saved(has_details).with_get(has_details),
+            conn,
+        );
+    }
+
+    #[test]
+    fn overlapping_contacts_are_allowed_only_by_rules() {
+        let conn = &mut initialize();
+        conn.run_pending_migrations(MIGRATIONS)
+            .expect("Fail to insert contacts test data into DB");
+        let contact = ContactDTO {
+            id: None,
+            employee_id: Some(1),
+            from_date: NaiveDate::from_ymd_opt(2020, 1, 1).unwrap(),
+            to_date: None,
+            emails: vec![],
+            phones: vec![],
+            addresses: vec![],
+            search_string: "".to_string(),
+            version: None,
+        };
+        let strict = ValidationRules::default();
+        let lenient = ValidationRules {
+            allow_overlapping_contacts: true,
+            ..Default::default()
+        };
+        assert!(contact.try_save_in_transaction(&strict, keys(), conn).is_ok());
+        assert!(contact
+            .try_save_in_transaction(&strict, keys(), conn)
+            .unwrap_err()
+            .is_validation());
+        assert!(contact.try_save_in_transaction(&lenient, keys(), conn).is_ok());
+        assert_eq!(ContactDTO::search_by_parent_id_with_connection(1, keys(), conn).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn contact_details_are_normalized_and_validated() {
+        let conn = &mut initialize();
+        conn.run_pending_migrations(MIGRATIONS)
+            .expect("Fail to insert contacts test data into DB");
+        let contact = ContactDTO {
+            id: None,
+            employee_id: Some(1),
+            from_date: NaiveDate::from_ymd_opt(2020, 1, 1).unwrap(),
+            to_date: None,
+            emails: vec![EmailDTO {
+                kind: EmailKind::Private,
+                email: " Jan.Kowalski@Example.COM ".to_string(),
+            }],
+            phones: vec![PhoneDTO {
+                kind: PhoneKind::Landline,
+                number: "030 1234567".to_string(),
+            }],
+            addresses: vec![AddressDTO {
+                kind: AddressKind::Correspondence,
+                street: " Unter den Linden 1 ".to_string(),
+                city: "Berlin".to_string(),
+                postal_code: "10117".to_string(),
+                country: Country::DE,
+            }],
+            search_string: "".to_string(),
+            version: None,
+        };
+        let german = ValidationRules {
+            default_country: Country::DE,
+            ..Default::default()
+        };
+        let saved = contact.try_save_in_transaction(&german, keys(), conn).unwrap();
+        assert_eq!(saved.emails[0].email, "Jan.Kowalski@example.com");
+        assert_eq!(saved.phones[0].number, "+49301234567");
+        assert_eq!(saved.addresses[0].street, "Unter den Linden 1");
+
+        let mut invalid = saved.clone();
+        invalid.emails[0].email = "jan@localhost".to_string();
+        invalid.phones[0].number = "+0123".to_string();
+        invalid.addresses[0].city = " ".to_string();
+        invalid.addresses[0].postal_code = "00-950".to_string();
+        match invalid.try_save_in_transaction(&german, keys(), conn) {
+            Err(DaoError::Validation(errors)) => assert_eq!(
+                errors.iter().map(|e| e.field.as_str()).collect::<Vec<_>>(),
+                vec![
+                    "emails[0].email",
+                    "phones[0].number",
+                    "addresses[0].city",
+                    "addresses[0].postal_code"
+                ]
+            ),
+            result => panic!("Should report validation error and instead I got {:?}", result),
+        }
+    }
+
+    #[test]
+    fn legacy_phones_and_addresses_are_converted() {
+        use crate::schema::contact_addresses::dsl as ca;
+        use crate::schema::contact_phones::dsl as cp;
+
+        let conn = &mut initialize();
+        conn.run_pending_migrations(MIGRATIONS)
+            .expect("Fail to insert contacts test data into DB");
+        let c_id = ContactDTO {
+            id: None,
+            employee_id: Some(1),
+            from_date: NaiveDate::from_ymd_opt(2020, 1, 1).unwrap(),
+            to_date: None,
+            emails: vec![],
+            phones: vec![],
+            addresses: vec![],
+            search_string: "".to_string(),
+            version: None,
+        }
+        .save_simple(keys(), conn)
+        .unwrap()
+        .id
+        .unwrap();
+        // As left by migration from single phone and free-text address
+        for (position, number) in [(0, "601 234 567"), (1, "not a phone")] {
+            insert_into(cp::contact_phones)
+                .values(NewContactPhone {
+                    contact_id: c_id,
+                    position,
+                    kind: "mobile".to_string(),
+                    number: number.to_string(),
+                    encrypted: false,
+                    number_index: None,
+                })
+                .execute(conn)
+                .unwrap();
+        }
+        for (position, text) in [(0, "ul. Marszałkowska 1, 00-950 Warszawa"), (1, "Somewhere")] {
+            insert_into(ca::contact_addresses)
+                .values(NewContactAddress {
+                    contact_id: c_id,
+                    position,
+                    kind: "home".to_string(),
+                    street: text.to_string(),
+                    city: "".to_string(),
+                    postal_code: "".to_string(),
+                    country: "".to_string(),
+                    encrypted: false,
+                })
+                .execute(conn)
+                .unwrap();
+        }
+
+        assert_eq!(convert_legacy_contacts(Country::PL, conn).unwrap(), 3);
+        let converted = ContactDTO::get_simple(c_id, keys(), conn).unwrap();
+        assert_eq!(
+            converted.phones.iter().map(|p| p.number.as_str()).collect::<Vec<_>>(),
+            vec!["+48601234567", "not a phone"]
+        );
+        assert_eq!(
+            converted.addresses[0],
+            AddressDTO {
+                kind: AddressKind::Home,
+                street: "ul. Marszałkowska 1".to_string(),
+                city: "Warszawa".to_string(),
+                postal_code: "00-950".to_string(),
+                country: Country::PL,
+            }
+        );
+        assert_eq!(converted.addresses[1].street, "Somewhere");
+        assert_eq!(converted.addresses[1].country, Country::PL);
+        assert_eq!(convert_legacy_contacts(Country::PL, conn).unwrap(), 0);
     }
 }
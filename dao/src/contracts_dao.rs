@@ -0,0 +1,625 @@
+use std::fmt;
+use std::str::FromStr;
+
+use chrono::NaiveDate;
+use diesel::dsl::*;
+use diesel::prelude::*;
+
+use crate::base_dao::{SearchableByDate, SearchableByParent};
+use crate::base_dao::{stale_version, Crud, HaveId, HaveVersion};
+use crate::connection::DbConnection;
+use crate::crypto::KeyRing;
+use crate::employees_dao::not_deleted;
+use crate::error::DaoResult;
+use crate::models::{EmploymentContract, NewEmploymentContract};
+use crate::salaries_dao::SalaryDTO;
+use crate::schema::employment_contracts::dsl::id as contract_id;
+use crate::schema::employment_contracts::dsl::*;
+use crate::validation::{check_no_overlap_with_existing, check_period, Errors, HavePeriod, ValidationRules};
+use crate::Searchable;
+
+/// Kind of employment contract
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum ContractType {
+    Permanent,
+    /// Have to have `to_date`
+    FixedTerm,
+    /// Business to business - the employee is contractor
+    B2b,
+}
+
+impl ContractType {
+    /// How it is stored in DB
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ContractType::Permanent => "permanent",
+            ContractType::FixedTerm => "fixed_term",
+            ContractType::B2b => "b2b",
+        }
+    }
+}
+
+impl FromStr for ContractType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "permanent" => Ok(ContractType::Permanent),
+            "fixed_term" => Ok(ContractType::FixedTerm),
+            "b2b" => Ok(ContractType::B2b),
+            _ => Err(format!(
+                "unknown contract type '{}' - should be one of permanent, fixed_term, b2b",
+                s
+            )),
+        }
+    }
+}
+
+/// Fraction of full working time - `"1/2"` in JSON (`"1"` is full time)
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[serde(try_from = "String", into = "String")]
+pub struct WorkingTime {
+    pub numerator: i32,
+    pub denominator: i32,
+}
+
+impl WorkingTime {
+    pub const FULL: WorkingTime = WorkingTime {
+        numerator: 1,
+        denominator: 1,
+    };
+
+    pub fn new(numerator: i32, denominator: i32) -> Self {
+        WorkingTime {
+            numerator,
+            denominator,
+        }
+    }
+}
+
+impl Default for WorkingTime {
+    fn default() -> Self {
+        WorkingTime::FULL
+    }
+}
+
+impl fmt::Display for WorkingTime {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.denominator == 1 {
+            write!(f, "{}", self.numerator)
+        } else {
+            write!(f, "{}/{}", self.numerator, self.denominator)
+        }
+    }
+}
+
+impl FromStr for WorkingTime {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (n, d) = s.split_once('/').unwrap_or((s, "1"));
+        match (n.trim().parse::<i32>(), d.trim().parse::<i32>()) {
+            (Ok(n), Ok(d)) if d > 0 => Ok(WorkingTime::new(n, d)),
+            _ => Err(format!("working time '{}' should be fraction like 1/2", s)),
+        }
+    }
+}
+
+impl TryFrom<String> for WorkingTime {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl From<WorkingTime> for String {
+    fn from(w: WorkingTime) -> Self {
+        w.to_string()
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ContractDTO {
+    pub id: Option<i32>,
+    pub employee_id: Option<i32>,
+    pub position_id: Option<i32>,
+    pub contract_type: ContractType,
+    pub from_date: NaiveDate,
+    pub to_date: Option<NaiveDate>,
+    #[serde(default)]
+    pub working_time: WorkingTime,
+    pub search_string: String,
+    pub version: Option<i32>,
+}
+
+impl From<EmploymentContract> for ContractDTO {
+    fn from(c: EmploymentContract) -> Self {
+        ContractDTO {
+            id: Some(c.id),
+            employee_id: Some(c.employee_id),
+            position_id: c.position_id,
+            contract_type: c.contract_type.parse().expect("contract_type is checked by DB"),
+            from_date: c.from_date,
+            to_date: c.to_date,
+            working_time: WorkingTime::new(c.working_time_numerator, c.working_time_denominator),
+            search_string: c.search_string,
+            version: Some(c.version),
+        }
+    }
+}
+
+impl From<&ContractDTO> for EmploymentContract {
+    fn from(contract_dto: &ContractDTO) -> Self {
+        EmploymentContract {
+            id: contract_dto.id.unwrap(),
+            employee_id: contract_dto.employee_id.unwrap(),
+            position_id: contract_dto.position_id,
+            contract_type: contract_dto.contract_type.as_str().to_string(),
+            from_date: contract_dto.from_date,
+            to_date: contract_dto.to_date,
+            working_time_numerator: contract_dto.working_time.numerator,
+            working_time_denominator: contract_dto.working_time.denominator,
+            search_string: contract_dto.search_string.clone(),
+            version: contract_dto.version.unwrap_or_default(),
+        }
+    }
+}
+
+impl From<&ContractDTO> for NewEmploymentContract {
+    fn from(contract_dto: &ContractDTO) -> Self {
+        NewEmploymentContract {
+            employee_id: contract_dto.employee_id.unwrap(),
+            position_id: contract_dto.position_id,
+            contract_type: contract_dto.contract_type.as_str().to_string(),
+            from_date: contract_dto.from_date,
+            to_date: contract_dto.to_date,
+            working_time_numerator: contract_dto.working_time.numerator,
+            working_time_denominator: contract_dto.working_time.denominator,
+            search_string: contract_dto.search_string.clone(),
+        }
+    }
+}
+
+impl HaveId for ContractDTO {
+    fn get_id(&self) -> Option<i32> {
+        self.id
+    }
+}
+
+impl HavePeriod for ContractDTO {
+    fn period_start(&self) -> NaiveDate {
+        self.from_date
+    }
+
+    fn period_end(&self) -> Option<NaiveDate> {
+        self.to_date
+    }
+}
+
+impl HaveVersion for ContractDTO {
+    fn get_version(&self) -> Option<i32> {
+        self.version
+    }
+}
+
+/// Contract period have to be valid (and closed for fixed-term contract), working time have to be
+/// a fraction of full time and position have to exist
+pub(crate) fn check_contract(
+    c: &ContractDTO,
+    prefix: &str,
+    errors: &mut Errors,
+    conn: &mut DbConnection,
+) -> QueryResult<()> {
+    use crate::schema::positions::dsl as p;
+
+    check_period(c, prefix, errors);
+    if c.contract_type == ContractType::FixedTerm && c.to_date.is_none() {
+        errors.add(prefix, "to_date", "is required for fixed-term contract".to_string());
+    }
+    let WorkingTime {
+        numerator,
+        denominator,
+    } = c.working_time;
+    if numerator <= 0 || numerator > denominator {
+        errors.add(
+            prefix,
+            "working_time",
+            format!("{} should be more than 0 and at most full time (1)", c.working_time),
+        );
+    }
+    if let Some(position) = c.position_id {
+        let exists = p::positions
+            .filter(p::id.eq(position))
+            .select(p::id)
+            .first::<i32>(conn)
+            .optional()?;
+        if exists.is_none() {
+            errors.add(prefix, "position_id", format!("there is no position with id = {}", position));
+        }
+    }
+    Ok(())
+}
+
+/// Index of contract (in `contracts`) salary is paid under - the one with salary `contract_id`
+/// or (when salary has no contract yet) the one which period covers salary period
+pub(crate) fn contract_of(s: &SalaryDTO, contracts: &[ContractDTO]) -> Option<usize> {
+    match s.contract_id {
+        Some(c_id) => contracts.iter().position(|c| c.id == Some(c_id)),
+        None => contracts.iter().position(|c| c.covers(s)),
+    }
+}
+
+/// Salary have to be paid under (and within period of) contract of the employee - salaries of employee
+/// without contracts don't need one
+pub(crate) fn check_salary_contract(s: &SalaryDTO, contracts: &[ContractDTO], prefix: &str, errors: &mut Errors) {
+    if contracts.is_empty() && s.contract_id.is_none() {
+        return;
+    }
+    match (contract_of(s, contracts), s.contract_id) {
+        (Some(i), _) if contracts[i].covers(s) => {}
+        (Some(i), _) => errors.add(
+            prefix,
+            "contract_id",
+            format!(
+                "salary {} is not within contract {}",
+                s.describe_period(),
+                contracts[i].describe_period()
+            ),
+        ),
+        (None, Some(c_id)) => errors.add(
+            prefix,
+            "contract_id",
+            format!("there is no contract with id = {} of the employee", c_id),
+        ),
+        (None, None) => errors.add(
+            prefix,
+            "contract_id",
+            format!("salary {} is not within any contract of the employee", s.describe_period()),
+        ),
+    }
+}
+
+impl Crud for ContractDTO {
+    fn update(&mut self, persisted: &Self) {
+        self.id = persisted.id;
+        self.version = persisted.version;
+    }
+
+    /// Contract have to be valid, can't overlap with other contracts of the same employee
+    /// and salaries paid under it have to stay within its period
+    fn validate(&self, _rules: &ValidationRules, keys: Option<&KeyRing>, conn: &mut DbConnection) -> DaoResult<()> {
+        let mut errors = Errors::default();
+        check_contract(self, "", &mut errors, conn)?;
+        if let Some(parent_id) = self.employee_id {
+            let existing = Self::search_by_parent_id_with_connection(parent_id, keys, conn)?;
+            check_no_overlap_with_existing(self, &existing, "contract", &mut errors);
+        }
+        if let Some(self_id) = self.id {
+            for s in SalaryDTO::search_by_contract_id_with_connection(self_id, keys, conn)? {
+                if !self.covers(&s) {
+                    errors.add(
+                        "",
+                        "from_date",
+                        format!(
+                            "salary id = {} {} have to be within contract",
+                            s.id.unwrap_or_default(),
+                            s.describe_period()
+                        ),
+                    );
+                }
+            }
+        }
+        errors.into_result()
+    }
+
+    fn get_simple(id_to_find: i32, _keys: Option<&KeyRing>, conn: &mut DbConnection) -> QueryResult<ContractDTO> {
+        employment_contracts
+            .filter(contract_id.eq(id_to_find))
+            .first(conn)
+            .map(|c: EmploymentContract| ContractDTO::from(c))
+    }
+
+    fn save_simple(&self, keys: Option<&KeyRing>, conn: &mut DbConnection) -> DaoResult<ContractDTO> {
+        fn insert(c: &ContractDTO, conn: &mut DbConnection) -> QueryResult<ContractDTO> {
+            insert_into(employment_contracts)
+                .values(NewEmploymentContract::from(c))
+                .get_result(conn)
+                .map(|c: EmploymentContract| ContractDTO::from(c))
+        }
+        if let Some(self_id) = self.id {
+            let updated = match self.version {
+                Some(self_version) => diesel::update(
+                    employment_contracts
+                        .filter(contract_id.eq(self_id))
+                        .filter(version.eq(self_version)),
+                )
+                .set((EmploymentContract::from(self), version.eq(version + 1)))
+                .execute(conn)?,
+                None => 0,
+            };
+            if updated == 0 {
+                let current = employment_contracts
+                    .filter(contract_id.eq(self_id))
+                    .select(version)
+                    .first::<i32>(conn)
+                    .optional()?;
+                match current {
+                    Some(current) => Err(stale_version(self_id, Some(current))),
+                    None => Ok(insert(self, conn)?),
+                }
+            } else {
+                Ok(Self::get_simple(self_id, keys, conn)?)
+            }
+        } else {
+            Ok(insert(self, conn)?)
+        }
+    }
+
+    /// Salaries paid under deleted contract are left without contract
+    fn delete_simple(id_to_find: i32, conn: &mut DbConnection) -> QueryResult<usize> {
+        use crate::schema::salaries::dsl as s;
+
+        diesel::update(s::salaries.filter(s::contract_id.eq(id_to_find)))
+            .set((s::contract_id.eq(None::<i32>), s::version.eq(s::version + 1)))
+            .execute(conn)?;
+        diesel::delete(employment_contracts.filter(contract_id.eq(id_to_find))).execute(conn)
+    }
+
+    fn delete_versioned_simple(
+        id_to_find: i32,
+        version_to_find: i32,
+        conn: &mut DbConnection,
+    ) -> QueryResult<usize> {
+        // Bump version first - it check version and lock the row before salaries are changed
+        let locked = diesel::update(
+            employment_contracts
+                .filter(contract_id.eq(id_to_find))
+                .filter(version.eq(version_to_find)),
+        )
+        .set(version.eq(version + 1))
+        .execute(conn)?;
+        if locked == 0 {
+            return Ok(0);
+        }
+        Self::delete_simple(id_to_find, conn)
+    }
+}
+
+/// Records of deleted employees are left out
+impl Searchable for ContractDTO {
+    fn get_all_with_connection(_keys: Option<&KeyRing>, conn: &mut DbConnection) -> DaoResult<Vec<Self>> {
+        Ok(employment_contracts
+            .filter(employee_id.eq_any(not_deleted()))
+            .load::<EmploymentContract>(conn)?
+            .into_iter()
+            .map(Self::from)
+            .collect())
+    }
+
+    fn search_with_connection(s: &str, _keys: Option<&KeyRing>, conn: &mut DbConnection) -> DaoResult<Vec<Self>> {
+        Ok(employment_contracts
+            .filter(search_string.like(s))
+            .filter(employee_id.eq_any(not_deleted()))
+            .load::<EmploymentContract>(conn)?
+            .into_iter()
+            .map(Self::from)
+            .collect())
+    }
+}
+
+impl SearchableByParent for ContractDTO {
+    fn search_by_parent_id_with_connection(
+        parent_id: i32,
+        _keys: Option<&KeyRing>,
+        conn: &mut DbConnection,
+    ) -> DaoResult<Vec<Self>> {
+        Ok(employment_contracts
+            .filter(employee_id.eq(parent_id))
+            .order(from_date)
+            .load::<EmploymentContract>(conn)?
+            .into_iter()
+            .map(Self::from)
+            .collect())
+    }
+}
+
+impl SearchableByDate for ContractDTO {
+    fn effective_on_with_connection(
+        parent_id: i32,
+        date: NaiveDate,
+        _keys: Option<&KeyRing>,
+        conn: &mut DbConnection,
+    ) -> DaoResult<Option<Self>> {
+        Ok(employment_contracts
+            .filter(employee_id.eq(parent_id))
+            .filter(from_date.le(date))
+            .filter(to_date.is_null().or(to_date.ge(date)))
+            .order(from_date.desc())
+            .first::<EmploymentContract>(conn)
+            .optional()?
+            .map(Self::from))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::common_for_tests::*;
+    use crate::error::DaoError;
+    use crate::money::{Currency, Money, PayPeriod};
+    use crate::positions_dao::PositionDTO;
+    use crate::EmployeeDTO;
+
+    use super::*;
+
+    impl CrudTests for ContractDTO {}
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    fn contract(from: NaiveDate, to: Option<NaiveDate>, kind: ContractType) -> ContractDTO {
+        ContractDTO {
+            id: None,
+            employee_id: None,
+            position_id: None,
+            contract_type: kind,
+            from_date: from,
+            to_date: to,
+            working_time: WorkingTime::FULL,
+            search_string: "".to_string(),
+            version: None,
+        }
+    }
+
+    fn salary(from: NaiveDate, to: Option<NaiveDate>) -> SalaryDTO {
+        SalaryDTO {
+            id: None,
+            employee_id: None,
+            from_date: from,
+            to_date: to,
+            amount: Money::new(100000, Currency::PLN),
+            pay_period: PayPeriod::Monthly,
+            gross: true,
+            search_string: "".to_string(),
+            version: None,
+            contract_id: None,
+        }
+    }
+
+    fn employee(contracts: Vec<ContractDTO>, salaries: Vec<SalaryDTO>) -> EmployeeDTO {
+        EmployeeDTO {
+            id: None,
+            first_name: "Jan".to_string(),
+            last_name: "Kowalski".to_string(),
+            search_string: "".to_string(),
+            salaries,
+            contacts: vec![],
+            version: None,
+            department_id: None,
+            manager_id: None,
+            contracts,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn working_time_json() {
+        let half: WorkingTime = serde_json::from_str(r#""1/2""#).unwrap();
+        assert_eq!(half, WorkingTime::new(1, 2));
+        assert_eq!(serde_json::to_string(&half).unwrap(), r#""1/2""#);
+        assert_eq!(serde_json::to_string(&WorkingTime::FULL).unwrap(), r#""1""#);
+        assert!(serde_json::from_str::<WorkingTime>(r#""1/0""#).is_err());
+        assert!(serde_json::from_str::<WorkingTime>(r#""half""#).is_err());
+        assert_eq!(
+            serde_json::from_str::<ContractType>(r#""fixed_term""#).unwrap(),
+            ContractType::FixedTerm
+        );
+    }
+
+    #[test]
+    fn crud_operations_on_contract() {
+        let conn = &mut initialize();
+        let e = employee(vec![], vec![]).save_in_transaction(keys(), conn).unwrap();
+        let mut c = contract(date(2020, 1, 1), None, ContractType::Permanent);
+        c.employee_id = e.id;
+        c.test(conn);
+    }
+
+    #[test]
+    fn invalid_contracts_should_be_rejected() {
+        let conn = &mut initialize();
+        let rules = ValidationRules::default();
+        let mut fixed_term = contract(date(2020, 1, 1), None, ContractType::FixedTerm);
+        fixed_term.working_time = WorkingTime::new(3, 2);
+        fixed_term.position_id = Some(1000);
+        match employee(vec![fixed_term], vec![]).try_save_in_transaction(&rules, keys(), conn) {
+            Err(DaoError::Validation(errors)) => {
+                let fields: Vec<&str> = errors.iter().map(|e| e.field.as_str()).collect();
+                assert_eq!(
+                    fields,
+                    vec!["contracts[0].to_date", "contracts[0].working_time", "contracts[0].position_id"]
+                );
+            }
+            result => panic!("Should report validation errors and instead I got {:?}", result),
+        }
+
+        let contracts = vec![
+            contract(date(2020, 1, 1), Some(date(2020, 12, 31)), ContractType::FixedTerm),
+            contract(date(2020, 6, 1), None, ContractType::Permanent),
+        ];
+        match employee(contracts, vec![]).try_save_in_transaction(&rules, keys(), conn) {
+            Err(DaoError::Validation(errors)) => assert_eq!(errors[0].field, "contracts[1].from_date"),
+            result => panic!("Should report validation error and instead I got {:?}", result),
+        }
+    }
+
+    #[test]
+    fn salaries_are_paid_under_contracts() {
+        let conn = &mut initialize();
+        let rules = ValidationRules::default();
+        let developer = PositionDTO {
+            id: None,
+            name: "Developer".to_string(),
+            search_string: "".to_string(),
+            version: None,
+        }
+        .save_in_transaction(keys(), conn)
+        .unwrap();
+        let mut permanent = contract(date(2021, 1, 1), None, ContractType::Permanent);
+        permanent.position_id = developer.id;
+        permanent.working_time = WorkingTime::new(1, 2);
+        let contracts = vec![
+            contract(date(2020, 1, 1), Some(date(2020, 12, 31)), ContractType::FixedTerm),
+            permanent,
+        ];
+
+        // Salary have to be within a contract
+        let outside = employee(contracts.clone(), vec![salary(date(2020, 6, 1), None)]);
+        match outside.try_save_in_transaction(&rules, keys(), conn) {
+            Err(DaoError::Validation(errors)) => assert_eq!(errors[0].field, "salaries[0].contract_id"),
+            result => panic!("Should report validation error and instead I got {:?}", result),
+        }
+
+        // Salaries without contract_id are linked to contract covering them
+        let salaries = vec![
+            salary(date(2020, 1, 1), Some(date(2020, 12, 31))),
+            salary(date(2021, 1, 1), None),
+        ];
+        let saved = employee(contracts, salaries).try_save_in_transaction(&rules, keys(), conn).unwrap();
+        assert_eq!(saved.salaries[0].contract_id, saved.contracts[0].id);
+        assert_eq!(saved.salaries[1].contract_id, saved.contracts[1].id);
+        let read = EmployeeDTO::get_with_conn(saved.id.unwrap(), keys(), conn).unwrap();
+        assert_eq!(read.contracts, saved.contracts);
+        assert_eq!(read.contracts[1].working_time, WorkingTime::new(1, 2));
+        let on = EmployeeDTO::get_effective_on_with_conn(saved.id.unwrap(), date(2020, 3, 1), keys(), conn)
+            .unwrap()
+            .unwrap();
+        assert_eq!(on.contracts, vec![saved.contracts[0].clone()]);
+
+        // Contract can't be shortened so its salary falls out of it
+        let shortened = ContractDTO {
+            to_date: Some(date(2020, 6, 30)),
+            ..saved.contracts[0].clone()
+        };
+        match shortened.try_save_in_transaction(&rules, keys(), conn) {
+            Err(DaoError::Validation(errors)) => assert_eq!(errors[0].field, "from_date"),
+            result => panic!("Should report validation error and instead I got {:?}", result),
+        }
+
+        // Contracts removed from employee are deleted and salaries can be moved to other contract
+        let mut merged = read.clone();
+        merged.contracts[1].from_date = date(2020, 1, 1);
+        merged.contracts.remove(0);
+        merged.salaries[0].contract_id = merged.contracts[0].id;
+        let merged = merged.try_save_in_transaction(&rules, keys(), conn).unwrap();
+        assert_eq!(merged.contracts.len(), 1);
+        assert!(merged.salaries.iter().all(|s| s.contract_id == merged.contracts[0].id));
+
+        // Contracts of deleted position are left without position
+        assert_eq!(developer.try_delete_with_conn(keys(), conn).unwrap(), 1);
+        let contract = ContractDTO::get_with_conn(merged.contracts[0].id.unwrap(), keys(), conn).unwrap();
+        assert_eq!(contract.position_id, None);
+    }
+}
@@ -0,0 +1,199 @@
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// ISO-3166 country - knows how to normalize phone numbers and check postal codes of the country
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Country {
+    code: &'static str,
+    calling_code: &'static str,
+    /// Digit dialled before national number - dropped when number is written in international format
+    trunk_prefix: Option<char>,
+    /// `N` is digit, `A` letter - other characters have to be as they are
+    postal_codes: &'static [&'static str],
+}
+
+impl Country {
+    pub const PL: Country = Country::new("PL", "48", None, &["NN-NNN"]);
+    pub const DE: Country = Country::new("DE", "49", Some('0'), &["NNNNN"]);
+    pub const GB: Country = Country::new(
+        "GB",
+        "44",
+        Some('0'),
+        &["AN NAA", "ANN NAA", "ANA NAA", "AAN NAA", "AANN NAA", "AANA NAA"],
+    );
+    pub const US: Country = Country::new("US", "1", None, &["NNNNN", "NNNNN-NNNN"]);
+
+    const fn new(
+        code: &'static str,
+        calling_code: &'static str,
+        trunk_prefix: Option<char>,
+        postal_codes: &'static [&'static str],
+    ) -> Country {
+        Country {
+            code,
+            calling_code,
+            trunk_prefix,
+            postal_codes,
+        }
+    }
+
+    /// Two letter code - "PL"
+    pub fn code(&self) -> &'static str {
+        self.code
+    }
+
+    /// Phone number in E.164 format ("+48123456789") - numbers without international prefix (`+` or `00`)
+    /// are numbers of this country. Spaces, dashes, dots, slashes and parentheses are ignored.
+    pub fn normalize_phone(&self, number: &str) -> Result<String, String> {
+        let compact: String = number
+            .chars()
+            .filter(|c| !c.is_whitespace() && !"-./()".contains(*c))
+            .collect();
+        let normalized = if let Some(international) = compact.strip_prefix('+') {
+            format!("+{}", international)
+        } else if let Some(international) = compact.strip_prefix("00") {
+            format!("+{}", international)
+        } else {
+            let national = match self.trunk_prefix {
+                Some(trunk) => compact.strip_prefix(trunk).unwrap_or(&compact),
+                None => &compact,
+            };
+            format!("+{}{}", self.calling_code, national)
+        };
+        if is_e164(&normalized) {
+            Ok(normalized)
+        } else {
+            Err(format!("'{}' is not valid phone number", number))
+        }
+    }
+
+    /// Postal code in format of the country (upper case)
+    pub fn normalize_postal_code(&self, postal_code: &str) -> Result<String, String> {
+        let normalized = postal_code.trim().to_uppercase();
+        if self.postal_codes.iter().any(|p| matches_pattern(&normalized, p)) {
+            Ok(normalized)
+        } else {
+            Err(format!(
+                "'{}' is not valid postal code in {} - expected {}",
+                postal_code,
+                self.code,
+                self.postal_codes.join(" or ")
+            ))
+        }
+    }
+
+    /// Split `text` starting with postal code of the country to postal code and the rest
+    pub(crate) fn split_postal_code<'t>(&self, text: &'t str) -> Option<(String, &'t str)> {
+        self.postal_codes.iter().find_map(|p| {
+            let (code, rest) = text.split_at_checked(p.len())?;
+            if !(rest.is_empty() || rest.starts_with(' ')) {
+                return None;
+            }
+            self.normalize_postal_code(code).ok().map(|code| (code, rest.trim()))
+        })
+    }
+}
+
+/// `+`, country code and national number - 7 to 15 digits together
+pub(crate) fn is_e164(number: &str) -> bool {
+    number.strip_prefix('+').is_some_and(|digits| {
+        (7..=15).contains(&digits.len())
+            && !digits.starts_with('0')
+            && digits.bytes().all(|b| b.is_ascii_digit())
+    })
+}
+
+fn matches_pattern(text: &str, pattern: &str) -> bool {
+    text.len() == pattern.len()
+        && text.chars().zip(pattern.chars()).all(|(t, p)| match p {
+            'N' => t.is_ascii_digit(),
+            'A' => t.is_ascii_uppercase(),
+            _ => t == p,
+        })
+}
+
+/// Supported countries
+const COUNTRIES: &[Country] = &[
+    Country::PL,
+    Country::DE,
+    Country::GB,
+    Country::US,
+    Country::new("CZ", "420", None, &["NNN NN"]),
+    Country::new("FR", "33", Some('0'), &["NNNNN"]),
+    Country::new("NL", "31", Some('0'), &["NNNN AA"]),
+    Country::new("UA", "380", Some('0'), &["NNNNN"]),
+];
+
+impl FromStr for Country {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        COUNTRIES
+            .iter()
+            .find(|c| c.code == s)
+            .copied()
+            .ok_or_else(|| {
+                format!(
+                    "unknown country '{}' - should be one of {:?}",
+                    s,
+                    COUNTRIES.iter().map(|c| c.code).collect::<Vec<_>>()
+                )
+            })
+    }
+}
+
+impl fmt::Display for Country {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.code)
+    }
+}
+
+impl Serialize for Country {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.code)
+    }
+}
+
+impl<'de> Deserialize<'de> for Country {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let code = String::deserialize(deserializer)?;
+        code.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn phones_are_normalized_to_e164() {
+        let pl = Country::PL;
+        assert_eq!(pl.normalize_phone("601 234 567"), Ok("+48601234567".to_string()));
+        assert_eq!(pl.normalize_phone("+48 (22) 123-45-67"), Ok("+48221234567".to_string()));
+        assert_eq!(pl.normalize_phone("0049 30 1234567"), Ok("+49301234567".to_string()));
+        assert_eq!(Country::DE.normalize_phone("030 1234567"), Ok("+49301234567".to_string()));
+        assert_eq!(Country::GB.normalize_phone("020 7946 0018"), Ok("+442079460018".to_string()));
+        for invalid in ["", "12", "+0123456789", "601-234-abc", "+1234567890123456"] {
+            assert!(pl.normalize_phone(invalid).is_err(), "'{}' should be invalid", invalid);
+        }
+    }
+
+    #[test]
+    fn postal_codes_are_checked_per_country() {
+        assert_eq!(Country::PL.normalize_postal_code(" 00-950 "), Ok("00-950".to_string()));
+        assert!(Country::PL.normalize_postal_code("00950").is_err());
+        assert_eq!(Country::GB.normalize_postal_code("sw1a 1aa"), Ok("SW1A 1AA".to_string()));
+        assert!(Country::GB.normalize_postal_code("SW1A1AA").is_err());
+        assert!(Country::US.normalize_postal_code("12345-6789").is_ok());
+        assert!(Country::DE.normalize_postal_code("1234").is_err());
+        assert_eq!("CZ".parse::<Country>().unwrap().normalize_postal_code("110 00"), Ok("110 00".to_string()));
+        assert!("XX".parse::<Country>().is_err());
+        assert_eq!(
+            Country::PL.split_postal_code("00-950 Warszawa"),
+            Some(("00-950".to_string(), "Warszawa"))
+        );
+        assert_eq!(Country::PL.split_postal_code("Warszawa"), None);
+    }
+}
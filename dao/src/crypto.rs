@@ -0,0 +1,308 @@
+use std::fmt;
+use std::str::FromStr;
+
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use diesel::QueryResult;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+/// Length of AES-GCM nonce stored in front of ciphertext
+const NONCE_LEN: usize = 12;
+
+/// 256 bit key (ENCRYPTION_KEY - base64) of encrypted columns. It is never printed.
+#[derive(Clone, PartialEq, Eq)]
+pub struct EncryptionKey([u8; 32]);
+
+impl EncryptionKey {
+    pub fn new(bytes: [u8; 32]) -> EncryptionKey {
+        EncryptionKey(bytes)
+    }
+}
+
+impl FromStr for EncryptionKey {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let bytes = STANDARD
+            .decode(s.trim())
+            .map_err(|e| format!("is not base64: {}", e))?;
+        let bytes: [u8; 32] = bytes
+            .try_into()
+            .map_err(|b: Vec<u8>| format!("has {} bytes instead of 32", b.len()))?;
+        Ok(EncryptionKey(bytes))
+    }
+}
+
+impl fmt::Debug for EncryptionKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("EncryptionKey(***)")
+    }
+}
+
+/// Id of key given without one (`ENCRYPTION_KEY=<base64>`)
+pub const DEFAULT_KEY_ID: &str = "0";
+
+/// Encryption keys by id - the first one encrypts new values and all of them decrypt, so values encrypted by
+/// older keys are readable till they are re-encrypted (see Database::reencrypt()). It is parsed from `id:key`
+/// entries (key in base64) separated by commas or new lines, `#` starts comment: `"2022:<new key>, 2021:<old key>"`.
+/// Key without id has id DEFAULT_KEY_ID.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct KeyRing {
+    keys: Vec<(String, EncryptionKey)>,
+}
+
+impl KeyRing {
+    pub fn new(id: &str, key: EncryptionKey) -> KeyRing {
+        KeyRing {
+            keys: vec![(id.to_string(), key)],
+        }
+    }
+
+    /// Id of key new values are encrypted by
+    pub fn current_id(&self) -> &str {
+        &self.keys[0].0
+    }
+
+    fn current(&self) -> &EncryptionKey {
+        &self.keys[0].1
+    }
+
+    fn get(&self, id: &str) -> Option<&EncryptionKey> {
+        self.keys.iter().find(|(key_id, _)| key_id == id).map(|(_, key)| key)
+    }
+
+    /// Encrypted by the current key - `key id:base64 of random nonce followed by AES-256-GCM ciphertext (with tag)`
+    pub(crate) fn encrypt(&self, plain: &str) -> Result<String, String> {
+        Ok(format!("{}:{}", self.current_id(), seal(self.current(), plain)?))
+    }
+
+    /// Reverse of encrypt() - fail when value was encrypted by unknown key or was tampered with
+    pub(crate) fn decrypt(&self, stored: &str) -> Result<String, String> {
+        match stored.split_once(':') {
+            Some((id, sealed)) => {
+                let key = self.get(id).ok_or_else(|| format!("value is encrypted by unknown key '{}'", id))?;
+                open(key, sealed)
+            }
+            // Encrypted before keys had ids - the key is found by trying them
+            None => self
+                .keys
+                .iter()
+                .find_map(|(_, key)| open(key, stored).ok())
+                .ok_or_else(|| "can't decrypt value - wrong key or corrupted data".to_string()),
+        }
+    }
+
+    /// Whether value is encrypted by the current key - other values are re-encrypted by Database::reencrypt()
+    pub(crate) fn is_current(&self, stored: &str) -> bool {
+        stored.split_once(':').is_some_and(|(id, _)| id == self.current_id())
+    }
+
+    /// Keyed hash (HMAC-SHA256 with key derived from the current key) of value - equal values have equal
+    /// blind index, so encrypted column can be searched by exact value
+    pub(crate) fn blind_index(&self, value: &str) -> Result<String, String> {
+        index_with(self.current(), value)
+    }
+
+    /// Blind indexes of value by every key - rows not yet re-encrypted by the current key are found too
+    pub(crate) fn blind_indexes(&self, value: &str) -> Result<Vec<String>, String> {
+        self.keys.iter().map(|(_, key)| index_with(key, value)).collect()
+    }
+}
+
+impl FromStr for KeyRing {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut keys: Vec<(String, EncryptionKey)> = vec![];
+        for entry in s.split(['\n', ',']) {
+            let entry = entry.split('#').next().unwrap_or_default().trim();
+            if entry.is_empty() {
+                continue;
+            }
+            let (id, key) = match entry.split_once(':') {
+                Some((id, key)) => {
+                    let id = id.trim();
+                    let key = key.parse().map_err(|e| format!("key '{}' {}", id, e))?;
+                    (id, key)
+                }
+                None => (DEFAULT_KEY_ID, entry.parse()?),
+            };
+            if id.is_empty() || !id.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-') {
+                return Err(format!("'{}' is not valid key id - use letters, digits, '_' and '-'", id));
+            }
+            if keys.iter().any(|(other, _)| other == id) {
+                return Err(format!("key '{}' is given twice", id));
+            }
+            keys.push((id.to_string(), key));
+        }
+        if keys.is_empty() {
+            return Err("has no key".to_string());
+        }
+        Ok(KeyRing { keys })
+    }
+}
+
+/// Decrypted by keys - encrypted value can't be read without them
+pub(crate) fn decrypt(keys: Option<&KeyRing>, stored: &str) -> Result<String, String> {
+    keys.ok_or_else(|| "ENCRYPTION_KEY is not configured".to_string())?
+        .decrypt(stored)
+}
+
+/// Amount (in minor units) as it is stored - encrypted (with 0 in plain column) when there are keys
+pub(crate) fn seal_amount(minor_units: i64, keys: Option<&KeyRing>) -> (i64, Option<String>) {
+    match keys {
+        Some(keys) => {
+            let encrypted = keys.encrypt(&minor_units.to_string()).expect("AES-GCM encryption doesn't fail");
+            (0, Some(encrypted))
+        }
+        None => (minor_units, None),
+    }
+}
+
+/// Stored amount of row `id` - amount which can't be decrypted (no or unknown key) is error of query
+/// (500 Internal Server Error in REST)
+pub(crate) fn open_amount(
+    id: i32,
+    what: &str,
+    plain: i64,
+    encrypted: Option<&str>,
+    keys: Option<&KeyRing>,
+) -> QueryResult<i64> {
+    match encrypted {
+        Some(encrypted) => decrypt(keys, encrypted)
+            .and_then(|decrypted| decrypted.parse().map_err(|e| format!("'{}' is not amount: {}", decrypted, e)))
+            .map_err(|e| unreadable(id, what, e)),
+        None => Ok(plain),
+    }
+}
+
+fn seal(key: &EncryptionKey, plain: &str) -> Result<String, String> {
+    let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&key.0));
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+    let mut sealed = nonce.to_vec();
+    sealed.extend(
+        cipher
+            .encrypt(&nonce, plain.as_bytes())
+            .map_err(|e| format!("encryption failed: {}", e))?,
+    );
+    Ok(STANDARD.encode(sealed))
+}
+
+fn open(key: &EncryptionKey, sealed: &str) -> Result<String, String> {
+    let sealed = STANDARD
+        .decode(sealed)
+        .map_err(|e| format!("encrypted value is not base64: {}", e))?;
+    if sealed.len() < NONCE_LEN {
+        return Err("encrypted value is too short".to_string());
+    }
+    let (nonce, ciphertext) = sealed.split_at(NONCE_LEN);
+    let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&key.0));
+    let plain = cipher
+        .decrypt(Nonce::from_slice(nonce), ciphertext)
+        .map_err(|_| "can't decrypt value - wrong key or corrupted data".to_string())?;
+    String::from_utf8(plain).map_err(|e| e.to_string())
+}
+
+/// Error of reading `what` of row `id` which can't be decrypted (no or unknown key, tampered value)
+pub(crate) fn unreadable(id: i32, what: &str, reason: String) -> diesel::result::Error {
+    let message = format!("{} of id = {} can't be decrypted: {}", what, id, reason);
+    diesel::result::Error::DeserializationError(message.into())
+}
+
+fn index_with(key: &EncryptionKey, value: &str) -> Result<String, String> {
+    let mut derive = <Hmac<Sha256> as Mac>::new_from_slice(&key.0).map_err(|e| e.to_string())?;
+    derive.update(b"blind index");
+    let index_key = derive.finalize().into_bytes();
+    let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(&index_key).map_err(|e| e.to_string())?;
+    mac.update(value.as_bytes());
+    Ok(STANDARD.encode(mac.finalize().into_bytes()))
+}
+
+/// Key of tests is DEFAULT_KEY_ID and `"old"` key decrypts values of tests of re-encryption
+#[cfg(test)]
+pub(crate) fn test_keys() -> KeyRing {
+    KeyRing {
+        keys: vec![
+            (DEFAULT_KEY_ID.to_string(), EncryptionKey::new([7; 32])),
+            ("old".to_string(), EncryptionKey::new([8; 32])),
+        ],
+    }
+}
+
+/// Value encrypted by `"old"` key of tests
+#[cfg(test)]
+pub(crate) fn encrypt_by_old_test_key(plain: &str) -> String {
+    KeyRing::new("old", EncryptionKey::new([8; 32])).encrypt(plain).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encrypted_value_is_decrypted() {
+        let keys = test_keys();
+        let encrypted = keys.encrypt("85010112345").unwrap();
+        assert!(!encrypted.contains("85010112345"));
+        assert_ne!(encrypted, keys.encrypt("85010112345").unwrap(), "Nonce should be random");
+        assert_eq!(keys.decrypt(&encrypted), Ok("85010112345".to_string()));
+        assert!(keys.is_current(&encrypted));
+        let (key_id, sealed) = encrypted.split_once(':').unwrap();
+        let mut tampered = STANDARD.decode(sealed).unwrap();
+        tampered[NONCE_LEN] ^= 1;
+        assert!(keys.decrypt(&format!("{}:{}", key_id, STANDARD.encode(tampered))).is_err());
+        assert_eq!(keys.blind_index("85010112345"), keys.blind_index("85010112345"));
+        assert_ne!(keys.blind_index("85010112345"), keys.blind_index("85010112346"));
+        assert!(decrypt(None, &encrypted).is_err(), "Encrypted value can't be read without keys");
+    }
+
+    #[test]
+    fn values_encrypted_by_older_keys_are_decrypted() {
+        let keys = test_keys();
+        let by_old = encrypt_by_old_test_key("85010112345");
+        assert!(by_old.starts_with("old:"));
+        assert_eq!(keys.decrypt(&by_old), Ok("85010112345".to_string()));
+        assert!(keys.encrypt("85010112345").unwrap().starts_with("0:"));
+        let without_old = KeyRing::new(DEFAULT_KEY_ID, EncryptionKey::new([7; 32]));
+        assert_eq!(
+            without_old.decrypt(&by_old),
+            Err("value is encrypted by unknown key 'old'".to_string())
+        );
+        let before_key_ids = seal(&EncryptionKey::new([8; 32]), "85010112345").unwrap();
+        assert_eq!(keys.decrypt(&before_key_ids), Ok("85010112345".to_string()));
+        assert!(without_old.decrypt(&before_key_ids).is_err());
+    }
+
+    #[test]
+    fn key_ring_is_list_of_keys_with_ids() {
+        let (key7, key8) = (STANDARD.encode([7u8; 32]), STANDARD.encode([8u8; 32]));
+        let keys: KeyRing = format!("# rotated in 2022\n{}\n old:{} # before rotation\n", key7, key8)
+            .parse()
+            .unwrap();
+        assert_eq!(keys, test_keys());
+        assert_eq!(keys.current_id(), DEFAULT_KEY_ID);
+        let keys: KeyRing = format!("2022:{}, 2021:{}", key8, key7).parse().unwrap();
+        assert_eq!(keys.current_id(), "2022");
+        assert!(!format!("{:?}", keys).contains(&key8), "Keys should not be printed");
+        for invalid in [
+            "".to_string(),
+            format!("a:{}, a:{}", key7, key8),
+            format!("no key!:{}", key7),
+            "a:c2hvcnQ=".to_string(),
+        ] {
+            assert!(invalid.parse::<KeyRing>().is_err(), "'{}' should be invalid", invalid);
+        }
+    }
+
+    #[test]
+    fn key_is_base64_of_32_bytes() {
+        let key: EncryptionKey = STANDARD.encode([1u8; 32]).parse().unwrap();
+        assert_eq!(key, EncryptionKey::new([1; 32]));
+        assert_eq!(format!("{:?}", key), "EncryptionKey(***)");
+        assert!("not base64!".parse::<EncryptionKey>().is_err());
+        assert!(STANDARD.encode([1u8; 16]).parse::<EncryptionKey>().is_err());
+    }
+}
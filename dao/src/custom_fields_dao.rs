@@ -0,0 +1,666 @@
+use std::collections::BTreeMap;
+use std::str::FromStr;
+
+use chrono::NaiveDate;
+use diesel::dsl::*;
+use diesel::prelude::*;
+use serde_json::Value;
+
+use crate::base_dao::{stale_version, Crud, HaveId, HaveVersion, Searchable};
+use crate::connection::DbConnection;
+use crate::crypto::KeyRing;
+use crate::error::DaoResult;
+use crate::models::{CustomField, NewCustomField, NewCustomFieldValue};
+use crate::schema::custom_field_values::dsl as cv;
+use crate::schema::custom_fields::dsl::id as field_id;
+use crate::schema::custom_fields::dsl::*;
+use crate::validation::{Errors, ValidationRules};
+
+/// Type of custom field value - JSON string, number, `"YYYY-MM-DD"` string, one of options or bool
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum CustomFieldType {
+    String,
+    Number,
+    Date,
+    Enum,
+    Bool,
+}
+
+impl CustomFieldType {
+    /// How it is stored in DB
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CustomFieldType::String => "string",
+            CustomFieldType::Number => "number",
+            CustomFieldType::Date => "date",
+            CustomFieldType::Enum => "enum",
+            CustomFieldType::Bool => "bool",
+        }
+    }
+}
+
+impl FromStr for CustomFieldType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "string" => Ok(CustomFieldType::String),
+            "number" => Ok(CustomFieldType::Number),
+            "date" => Ok(CustomFieldType::Date),
+            "enum" => Ok(CustomFieldType::Enum),
+            "bool" => Ok(CustomFieldType::Bool),
+            _ => Err(format!(
+                "unknown field type '{}' - should be one of string, number, date, enum, bool",
+                s
+            )),
+        }
+    }
+}
+
+/// Validation rules of custom field - each of them applies just to some types
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct CustomFieldRules {
+    /// Maximal length (in characters) of string
+    #[serde(default)]
+    pub max_length: Option<usize>,
+    /// Range of number (both inclusive)
+    #[serde(default)]
+    pub min: Option<f64>,
+    #[serde(default)]
+    pub max: Option<f64>,
+    /// Allowed values of enum
+    #[serde(default)]
+    pub options: Vec<String>,
+}
+
+/// Field of employees defined at runtime - values are in `EmployeeDTO::custom_fields` under its name
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct CustomFieldDTO {
+    pub id: Option<i32>,
+    /// Key in `custom_fields` of employee - letters, digits and `_`
+    pub name: String,
+    pub field_type: CustomFieldType,
+    /// Employee can't be saved without value of required field
+    #[serde(default)]
+    pub required: bool,
+    #[serde(default)]
+    pub rules: CustomFieldRules,
+    pub search_string: String,
+    pub version: Option<i32>,
+}
+
+impl From<CustomField> for CustomFieldDTO {
+    fn from(f: CustomField) -> Self {
+        CustomFieldDTO {
+            id: Some(f.id),
+            name: f.name,
+            field_type: f.field_type.parse().expect("field_type is checked by DB"),
+            required: f.required,
+            rules: serde_json::from_str(&f.rules).expect("rules are saved as JSON"),
+            search_string: f.search_string,
+            version: Some(f.version),
+        }
+    }
+}
+
+impl From<&CustomFieldDTO> for CustomField {
+    fn from(field_dto: &CustomFieldDTO) -> Self {
+        CustomField {
+            id: field_dto.id.unwrap(),
+            name: field_dto.name.clone(),
+            field_type: field_dto.field_type.as_str().to_string(),
+            required: field_dto.required,
+            rules: serde_json::to_string(&field_dto.rules).expect("rules are serializable"),
+            search_string: field_dto.search_string.clone(),
+            version: field_dto.version.unwrap_or_default(),
+        }
+    }
+}
+
+impl From<&CustomFieldDTO> for NewCustomField {
+    fn from(field_dto: &CustomFieldDTO) -> Self {
+        NewCustomField {
+            name: field_dto.name.clone(),
+            field_type: field_dto.field_type.as_str().to_string(),
+            required: field_dto.required,
+            rules: serde_json::to_string(&field_dto.rules).expect("rules are serializable"),
+            search_string: field_dto.search_string.clone(),
+        }
+    }
+}
+
+/// Text of number as it is stored and searched - integers without fraction, so `5` and `5.0` are the same
+fn number_text(n: f64) -> String {
+    if n.fract() == 0.0 && n.abs() < i64::MAX as f64 {
+        (n as i64).to_string()
+    } else {
+        n.to_string()
+    }
+}
+
+impl CustomFieldDTO {
+    /// Value as it is stored (see custom_field_values) or why it is not valid value of this field
+    pub fn stored(&self, value: &Value) -> Result<String, String> {
+        let r = &self.rules;
+        match (self.field_type, value) {
+            (CustomFieldType::String, Value::String(s)) => match r.max_length {
+                Some(max) if s.chars().count() > max => Err(format!("is longer than {} characters", max)),
+                _ => Ok(s.clone()),
+            },
+            (CustomFieldType::Number, Value::Number(n)) => {
+                let n = n.as_f64().ok_or_else(|| format!("{} is not a number", n))?;
+                if let Some(min) = r.min
+                    && n < min
+                {
+                    return Err(format!("{} is less than {}", n, min));
+                }
+                if let Some(max) = r.max
+                    && n > max
+                {
+                    return Err(format!("{} is more than {}", n, max));
+                }
+                Ok(number_text(n))
+            }
+            (CustomFieldType::Date, Value::String(s)) => NaiveDate::parse_from_str(s, "%Y-%m-%d")
+                .map(|d| d.to_string())
+                .map_err(|_| format!("'{}' is not date in format YYYY-MM-DD", s)),
+            (CustomFieldType::Enum, Value::String(s)) if r.options.contains(s) => Ok(s.clone()),
+            (CustomFieldType::Enum, Value::String(s)) => {
+                Err(format!("'{}' is not one of {}", s, r.options.join(", ")))
+            }
+            (CustomFieldType::Bool, Value::Bool(b)) => Ok(b.to_string()),
+            (t, v) => Err(format!("{} is not {}", v, t.as_str())),
+        }
+    }
+
+    /// Reverse of stored()
+    fn value_of(&self, stored: String) -> Value {
+        match self.field_type {
+            CustomFieldType::Number => match stored.parse::<i64>() {
+                Ok(n) => Value::from(n),
+                Err(_) => Value::from(stored.parse::<f64>().expect("number is checked on save")),
+            },
+            CustomFieldType::Bool => Value::Bool(stored == "true"),
+            _ => Value::String(stored),
+        }
+    }
+
+    /// Value of field in template of employee - null for date and optional fields
+    fn template_value(&self) -> Value {
+        match self.field_type {
+            _ if !self.required => Value::Null,
+            CustomFieldType::String => Value::String("".to_string()),
+            CustomFieldType::Number => Value::from(0),
+            CustomFieldType::Enum => self.rules.options.first().cloned().map_or(Value::Null, Value::String),
+            CustomFieldType::Bool => Value::Bool(false),
+            CustomFieldType::Date => Value::Null,
+        }
+    }
+}
+
+impl HaveId for CustomFieldDTO {
+    fn get_id(&self) -> Option<i32> {
+        self.id
+    }
+}
+
+impl HaveVersion for CustomFieldDTO {
+    fn get_version(&self) -> Option<i32> {
+        self.version
+    }
+}
+
+impl Crud for CustomFieldDTO {
+    fn update(&mut self, persisted: &Self) {
+        self.id = persisted.id;
+        self.version = persisted.version;
+    }
+
+    /// Name is unique identifier, rules fit the type and type of field which has values can't be changed
+    fn validate(&self, _rules: &ValidationRules, _keys: Option<&KeyRing>, conn: &mut DbConnection) -> DaoResult<()> {
+        let mut errors = Errors::default();
+        if self.name.is_empty() || !self.name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+            errors.add("", "name", format!("'{}' is not valid name - use letters, digits and '_'", self.name));
+        }
+        let same_name = custom_fields
+            .filter(name.eq(&self.name))
+            .select(field_id)
+            .first::<i32>(conn)
+            .optional()?;
+        if let Some(other) = same_name
+            && Some(other) != self.id
+        {
+            errors.add("", "name", format!("'{}' is already name of custom field id = {}", self.name, other));
+        }
+        let r = &self.rules;
+        if r.max_length.is_some() && self.field_type != CustomFieldType::String {
+            errors.add("rules.", "max_length", "can be set just for string field".to_string());
+        }
+        if (r.min.is_some() || r.max.is_some()) && self.field_type != CustomFieldType::Number {
+            errors.add("rules.", "min", "min and max can be set just for number field".to_string());
+        }
+        if let (Some(min), Some(max)) = (r.min, r.max)
+            && max < min
+        {
+            errors.add("rules.", "max", format!("can't be less than min {}", min));
+        }
+        match self.field_type {
+            CustomFieldType::Enum if r.options.is_empty() => {
+                errors.add("rules.", "options", "enum field has to have options".to_string());
+            }
+            CustomFieldType::Enum => {
+                for (i, option) in r.options.iter().enumerate() {
+                    if r.options[..i].contains(option) {
+                        errors.add("rules.", "options", format!("'{}' is given twice", option));
+                    }
+                }
+            }
+            _ if !r.options.is_empty() => {
+                errors.add("rules.", "options", "can be set just for enum field".to_string());
+            }
+            _ => {}
+        }
+        if let Some(self_id) = self.id {
+            let current = custom_fields
+                .filter(field_id.eq(self_id))
+                .select(field_type)
+                .first::<String>(conn)
+                .optional()?;
+            let values = cv::custom_field_values
+                .filter(cv::custom_field_id.eq(self_id))
+                .count()
+                .get_result::<i64>(conn)?;
+            if current.is_some_and(|t| t != self.field_type.as_str()) && values > 0 {
+                errors.add("", "field_type", format!("can't be changed - {} employees have the field", values));
+            }
+        }
+        errors.into_result()
+    }
+
+    fn get_simple(id_to_find: i32, _keys: Option<&KeyRing>, conn: &mut DbConnection) -> QueryResult<Self> {
+        custom_fields
+            .filter(field_id.eq(id_to_find))
+            .first(conn)
+            .map(|f: CustomField| CustomFieldDTO::from(f))
+    }
+
+    fn save_simple(&self, keys: Option<&KeyRing>, conn: &mut DbConnection) -> DaoResult<Self> {
+        fn insert(f: &CustomFieldDTO, conn: &mut DbConnection) -> QueryResult<CustomFieldDTO> {
+            insert_into(custom_fields)
+                .values(NewCustomField::from(f))
+                .get_result(conn)
+                .map(|f: CustomField| CustomFieldDTO::from(f))
+        }
+        if let Some(self_id) = self.id {
+            let updated = match self.version {
+                Some(self_version) => diesel::update(
+                    custom_fields
+                        .filter(field_id.eq(self_id))
+                        .filter(version.eq(self_version)),
+                )
+                .set((CustomField::from(self), version.eq(version + 1)))
+                .execute(conn)?,
+                None => 0,
+            };
+            if updated == 0 {
+                let current = custom_fields
+                    .filter(field_id.eq(self_id))
+                    .select(version)
+                    .first::<i32>(conn)
+                    .optional()?;
+                match current {
+                    Some(current) => Err(stale_version(self_id, Some(current))),
+                    None => Ok(insert(self, conn)?),
+                }
+            } else {
+                Ok(Self::get_simple(self_id, keys, conn)?)
+            }
+        } else {
+            Ok(insert(self, conn)?)
+        }
+    }
+
+    /// Values of the field are deleted with it
+    fn delete_simple(id_to_find: i32, conn: &mut DbConnection) -> QueryResult<usize> {
+        diesel::delete(cv::custom_field_values.filter(cv::custom_field_id.eq(id_to_find))).execute(conn)?;
+        diesel::delete(custom_fields.filter(field_id.eq(id_to_find))).execute(conn)
+    }
+
+    fn delete_versioned_simple(
+        id_to_find: i32,
+        version_to_find: i32,
+        conn: &mut DbConnection,
+    ) -> QueryResult<usize> {
+        // Version is checked (and the row locked) by update before values of the field are deleted
+        let locked = diesel::update(
+            custom_fields
+                .filter(field_id.eq(id_to_find))
+                .filter(version.eq(version_to_find)),
+        )
+        .set(version.eq(version + 1))
+        .execute(conn)?;
+        if locked == 0 {
+            return Ok(0);
+        }
+        Self::delete_simple(id_to_find, conn)
+    }
+}
+
+impl Searchable for CustomFieldDTO {
+    fn get_all_with_connection(_keys: Option<&KeyRing>, conn: &mut DbConnection) -> DaoResult<Vec<Self>> {
+        Ok(defined_fields(conn)?)
+    }
+
+    fn search_with_connection(s: &str, _keys: Option<&KeyRing>, conn: &mut DbConnection) -> DaoResult<Vec<Self>> {
+        Ok(custom_fields
+            .filter(search_string.like(s))
+            .order(name)
+            .load::<CustomField>(conn)?
+            .into_iter()
+            .map(Self::from)
+            .collect())
+    }
+}
+
+/// Every custom field (ordered by name) - see Searchable
+fn defined_fields(conn: &mut DbConnection) -> QueryResult<Vec<CustomFieldDTO>> {
+    Ok(custom_fields
+        .order(name)
+        .load::<CustomField>(conn)?
+        .into_iter()
+        .map(CustomFieldDTO::from)
+        .collect())
+}
+
+/// Values of employee are checked against definitions of custom fields - unknown fields are rejected, null is
+/// the same as missing value
+pub(crate) fn check_custom_fields(
+    values: &BTreeMap<String, Value>,
+    errors: &mut Errors,
+    conn: &mut DbConnection,
+) -> QueryResult<()> {
+    let defined = defined_fields(conn)?;
+    for f in &defined {
+        match values.get(&f.name) {
+            None | Some(Value::Null) if f.required => {
+                errors.add("custom_fields.", &f.name, "is required".to_string());
+            }
+            None | Some(Value::Null) => {}
+            Some(Value::String(s)) if f.required && s.trim().is_empty() => {
+                errors.add("custom_fields.", &f.name, "is required".to_string());
+            }
+            Some(value) => {
+                if let Err(e) = f.stored(value) {
+                    errors.add("custom_fields.", &f.name, e);
+                }
+            }
+        }
+    }
+    for key in values.keys() {
+        if !defined.iter().any(|f| &f.name == key) {
+            errors.add("custom_fields.", key, "is not defined custom field".to_string());
+        }
+    }
+    Ok(())
+}
+
+/// Values are replaced as a whole - check_custom_fields() make sure they are valid
+pub(crate) fn save_custom_values(
+    e_id: i32,
+    values: &BTreeMap<String, Value>,
+    conn: &mut DbConnection,
+) -> QueryResult<()> {
+    delete_custom_values_of(e_id, conn)?;
+    let new_values: Vec<NewCustomFieldValue> = defined_fields(conn)?
+        .into_iter()
+        .filter_map(|f| {
+            let value = values.get(&f.name).filter(|v| !v.is_null())?;
+            Some(NewCustomFieldValue {
+                employee_id: e_id,
+                custom_field_id: f.id.unwrap(),
+                value: f.stored(value).expect("values are checked by check_custom_fields()"),
+            })
+        })
+        .collect();
+    insert_into(cv::custom_field_values)
+        .values(&new_values)
+        .execute(conn)?;
+    Ok(())
+}
+
+/// Values of custom fields of employee by name of field
+pub(crate) fn custom_values_of(e_id: i32, conn: &mut DbConnection) -> QueryResult<BTreeMap<String, Value>> {
+    Ok(cv::custom_field_values
+        .inner_join(custom_fields)
+        .filter(cv::employee_id.eq(e_id))
+        .select((custom_fields::all_columns(), cv::value))
+        .load::<(CustomField, String)>(conn)?
+        .into_iter()
+        .map(|(f, stored)| {
+            let f = CustomFieldDTO::from(f);
+            let value = f.value_of(stored);
+            (f.name, value)
+        })
+        .collect())
+}
+
+/// Custom field values of employees by employee - employees without values are left out
+pub(crate) fn custom_values_of_many(
+    e_ids: &[i32],
+    conn: &mut DbConnection,
+) -> QueryResult<BTreeMap<i32, BTreeMap<String, Value>>> {
+    let mut by_employee: BTreeMap<i32, BTreeMap<String, Value>> = BTreeMap::new();
+    for (f, e_id, stored) in cv::custom_field_values
+        .inner_join(custom_fields)
+        .filter(cv::employee_id.eq_any(e_ids))
+        .select((custom_fields::all_columns(), cv::employee_id, cv::value))
+        .load::<(CustomField, i32, String)>(conn)?
+    {
+        let f = CustomFieldDTO::from(f);
+        let value = f.value_of(stored);
+        by_employee.entry(e_id).or_default().insert(f.name, value);
+    }
+    Ok(by_employee)
+}
+
+pub(crate) fn delete_custom_values_of(e_id: i32, conn: &mut DbConnection) -> QueryResult<usize> {
+    diesel::delete(cv::custom_field_values.filter(cv::employee_id.eq(e_id))).execute(conn)
+}
+
+/// Employees with custom field filter `name:value` - string fields contain the value, others are equal to it
+pub(crate) fn employees_with_custom_value(filter: &str, conn: &mut DbConnection) -> DaoResult<Vec<i32>> {
+    let invalid = |message: String| {
+        let mut errors = Errors::default();
+        errors.add("", "custom_field", message);
+        errors.into_result().unwrap_err()
+    };
+    let (field_name, text) = filter
+        .split_once(':')
+        .ok_or_else(|| invalid(format!("'{}' should be name:value", filter)))?;
+    let f = custom_fields
+        .filter(name.eq(field_name))
+        .first::<CustomField>(conn)
+        .optional()?
+        .map(CustomFieldDTO::from)
+        .ok_or_else(|| invalid(format!("'{}' is not defined custom field", field_name)))?;
+    let query = cv::custom_field_values
+        .filter(cv::custom_field_id.eq(f.id.unwrap()))
+        .select(cv::employee_id)
+        .into_boxed();
+    let query = match f.field_type {
+        CustomFieldType::String => query.filter(cv::value.like(format!("%{}%", text))),
+        CustomFieldType::Number => {
+            let n = text.parse::<f64>().map_err(|_| invalid(format!("'{}' is not a number", text)))?;
+            query.filter(cv::value.eq(number_text(n)))
+        }
+        _ => query.filter(cv::value.eq(text)),
+    };
+    Ok(query.load(conn)?)
+}
+
+/// Custom fields of template of employee (`/employees/template`) - every defined field by name
+pub(crate) fn custom_fields_template_with_connection(conn: &mut DbConnection) -> QueryResult<BTreeMap<String, Value>> {
+    Ok(defined_fields(conn)?
+        .into_iter()
+        .map(|f| {
+            let value = f.template_value();
+            (f.name, value)
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::common_for_tests::*;
+    use crate::employees_dao::{EmployeeDTO, EmployeeSearch};
+    use crate::error::DaoError;
+    use crate::hierarchy::EmployeeScope;
+
+    // Glob of DSL shadows `rules` and `name` - local variables have to be named differently
+    use super::*;
+
+    impl CrudTests for CustomFieldDTO {}
+
+    pub(crate) fn custom_field(field_name: &str, t: CustomFieldType) -> CustomFieldDTO {
+        CustomFieldDTO {
+            id: None,
+            name: field_name.to_string(),
+            field_type: t,
+            required: false,
+            rules: Default::default(),
+            search_string: "".to_string(),
+            version: None,
+        }
+    }
+
+    #[test]
+    fn crud_operations_on_custom_field() {
+        let conn = &mut initialize();
+        custom_field("badge_number", CustomFieldType::String).test(conn);
+    }
+
+    #[test]
+    fn rules_have_to_fit_type_of_field() {
+        let conn = &mut initialize();
+        let validation_rules = ValidationRules::default();
+        let mut size = custom_field("t_shirt_size", CustomFieldType::Enum);
+        size.rules.options = vec!["S".to_string(), "M".to_string(), "L".to_string()];
+        let size = size.save_in_transaction(keys(), conn).unwrap();
+        let with_rules = |t, r: CustomFieldRules| CustomFieldDTO {
+            rules: r,
+            ..custom_field("field", t)
+        };
+        for (f, field) in [
+            (size.clone().with_id(None), "name"),
+            (custom_field("cost center", CustomFieldType::String), "name"),
+            (custom_field("size", CustomFieldType::Enum), "rules.options"),
+            (
+                with_rules(CustomFieldType::Date, CustomFieldRules { max_length: Some(5), ..Default::default() }),
+                "rules.max_length",
+            ),
+            (
+                with_rules(
+                    CustomFieldType::Number,
+                    CustomFieldRules { min: Some(5.0), max: Some(1.0), ..Default::default() },
+                ),
+                "rules.max",
+            ),
+        ] {
+            match f.try_save_in_transaction(&validation_rules, keys(), conn) {
+                Err(DaoError::Validation(errors)) => assert_eq!(errors[0].field, field),
+                result => panic!("Should report validation error and instead I got {:?}", result),
+            }
+        }
+
+        assert_eq!(size.stored(&json!("M")), Ok("M".to_string()));
+        assert!(size.stored(&json!("XXL")).is_err());
+        let mut number = custom_field("desk", CustomFieldType::Number);
+        number.rules.max = Some(100.0);
+        assert_eq!(number.stored(&json!(5.0)), Ok("5".to_string()));
+        assert!(number.stored(&json!(101)).is_err());
+        assert!(number.stored(&json!("5")).is_err());
+        let date = custom_field("badge_valid_to", CustomFieldType::Date);
+        assert_eq!(date.stored(&json!("2021-02-03")), Ok("2021-02-03".to_string()));
+        assert!(date.stored(&json!("03.02.2021")).is_err());
+    }
+
+    #[test]
+    fn custom_values_of_employee_are_validated_and_searchable() {
+        let conn = &mut initialize();
+        let validation_rules = ValidationRules::default();
+        let mut desk = custom_field("desk", CustomFieldType::Number);
+        desk.required = true;
+        let desk = desk.save_in_transaction(keys(), conn).unwrap();
+        custom_field("badge", CustomFieldType::String).save_in_transaction(keys(), conn).unwrap();
+        let employee = |values: Value| EmployeeDTO {
+            first_name: "Jan".to_string(),
+            last_name: "Kowalski".to_string(),
+            custom_fields: serde_json::from_value(values).unwrap(),
+            ..Default::default()
+        };
+        for (values, field) in [
+            (json!({}), "custom_fields.desk"),
+            (json!({"desk": null}), "custom_fields.desk"),
+            (json!({"desk": "12"}), "custom_fields.desk"),
+            (json!({"desk": 12, "floor": 3}), "custom_fields.floor"),
+        ] {
+            match employee(values.clone()).try_save_in_transaction(&validation_rules, keys(), conn) {
+                Err(DaoError::Validation(errors)) => assert_eq!(errors[0].field, field, "{}", values),
+                result => panic!("Should report validation error and instead I got {:?}", result),
+            }
+        }
+
+        let saved = employee(json!({"desk": 12.0, "badge": "B-1234", "ignored": null}))
+            .try_save_in_transaction(&validation_rules, keys(), conn)
+            .unwrap_err();
+        assert!(saved.is_validation(), "Unknown field should be rejected even when null");
+        let saved = employee(json!({"desk": 12.0, "badge": "B-1234"}))
+            .try_save_in_transaction(&validation_rules, keys(), conn)
+            .unwrap();
+        assert_eq!(saved.custom_fields["desk"], json!(12));
+        let found = EmployeeDTO::get_with_conn(saved.id.unwrap(), keys(), conn).unwrap();
+        assert_eq!(found.custom_fields, saved.custom_fields);
+        let template = EmployeeDTO::template_with_connection(conn).unwrap();
+        assert_eq!(template.custom_fields["desk"], json!(0));
+        assert_eq!(template.custom_fields["badge"], Value::Null);
+
+        let search = |q: Option<&str>, filter: Option<&str>, conn: &mut DbConnection| {
+            let search = EmployeeSearch {
+                q: q.map(str::to_string),
+                custom_field: filter.map(str::to_string),
+                ..Default::default()
+            };
+            EmployeeDTO::search_in_scope_with_connection(&search, EmployeeScope::All, keys(), conn)
+                .map(|found| found.len())
+        };
+        assert_eq!(search(Some("1234"), None, conn).unwrap(), 1);
+        assert_eq!(search(None, Some("badge:1234"), conn).unwrap(), 1);
+        assert_eq!(search(None, Some("desk:12.0"), conn).unwrap(), 1);
+        assert_eq!(search(None, Some("desk:1"), conn).unwrap(), 0);
+        assert!(search(None, Some("floor:1"), conn).unwrap_err().is_validation());
+        assert!(search(None, Some("desk"), conn).unwrap_err().is_validation());
+
+        // Type of field with values can't be changed, deleted field takes values with it
+        let changed = CustomFieldDTO {
+            field_type: CustomFieldType::String,
+            ..desk.clone()
+        };
+        assert!(changed.try_save_in_transaction(&validation_rules, keys(), conn).unwrap_err().is_validation());
+        desk.delete_with_conn(keys(), conn).unwrap();
+        let found = EmployeeDTO::get_with_conn(saved.id.unwrap(), keys(), conn).unwrap();
+        assert!(!found.custom_fields.contains_key("desk"));
+    }
+
+    impl CustomFieldDTO {
+        fn with_id(self, field_id_to_set: Option<i32>) -> Self {
+            CustomFieldDTO {
+                id: field_id_to_set,
+                version: None,
+                ..self
+            }
+        }
+    }
+}
@@ -0,0 +1,263 @@
+use diesel::dsl::*;
+use diesel::prelude::*;
+
+use crate::base_dao::{stale_version, Crud, HaveId, HaveVersion, Searchable};
+use crate::connection::{Database, DbConnection};
+use crate::crypto::KeyRing;
+use crate::error::DaoResult;
+use crate::hierarchy::{check_parent, descendants, Tree};
+use crate::models::{Department, NewDepartment};
+use crate::schema::departments::dsl::id as department_id;
+use crate::schema::departments::dsl::*;
+use crate::validation::{Errors, ValidationRules};
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct DepartmentDTO {
+    pub id: Option<i32>,
+    pub name: String,
+    /// Department this one is part of - None for top level department
+    pub parent_id: Option<i32>,
+    pub search_string: String,
+    pub version: Option<i32>,
+}
+
+impl From<Department> for DepartmentDTO {
+    fn from(d: Department) -> Self {
+        DepartmentDTO {
+            id: Some(d.id),
+            name: d.name,
+            parent_id: d.parent_id,
+            search_string: d.search_string,
+            version: Some(d.version),
+        }
+    }
+}
+
+impl From<&DepartmentDTO> for Department {
+    fn from(department_dto: &DepartmentDTO) -> Self {
+        Department {
+            id: department_dto.id.unwrap(),
+            name: department_dto.name.clone(),
+            parent_id: department_dto.parent_id,
+            search_string: department_dto.search_string.clone(),
+            version: department_dto.version.unwrap_or_default(),
+        }
+    }
+}
+
+impl From<&DepartmentDTO> for NewDepartment {
+    fn from(department_dto: &DepartmentDTO) -> Self {
+        NewDepartment {
+            name: department_dto.name.clone(),
+            parent_id: department_dto.parent_id,
+            search_string: department_dto.search_string.clone(),
+        }
+    }
+}
+
+impl HaveId for DepartmentDTO {
+    fn get_id(&self) -> Option<i32> {
+        self.id
+    }
+}
+
+impl HaveVersion for DepartmentDTO {
+    fn get_version(&self) -> Option<i32> {
+        self.version
+    }
+}
+
+impl Crud for DepartmentDTO {
+    fn update(&mut self, persisted: &Self) {
+        self.id = persisted.id;
+        self.version = persisted.version;
+    }
+
+    /// Name is required and parent have to exist and can't make a cycle
+    fn validate(&self, _rules: &ValidationRules, _keys: Option<&KeyRing>, conn: &mut DbConnection) -> DaoResult<()> {
+        let mut errors = Errors::default();
+        if self.name.trim().is_empty() {
+            errors.add("", "name", "can't be empty".to_string());
+        }
+        check_parent(Tree::Departments, self.id, self.parent_id, "parent_id", &mut errors, conn)?;
+        errors.into_result()
+    }
+
+    fn get_simple(id_to_find: i32, _keys: Option<&KeyRing>, conn: &mut DbConnection) -> QueryResult<Self> {
+        departments
+            .filter(department_id.eq(id_to_find))
+            .first(conn)
+            .map(|d: Department| DepartmentDTO::from(d))
+    }
+
+    fn save_simple(&self, keys: Option<&KeyRing>, conn: &mut DbConnection) -> DaoResult<Self> {
+        fn insert(d: &DepartmentDTO, conn: &mut DbConnection) -> QueryResult<DepartmentDTO> {
+            insert_into(departments)
+                .values(NewDepartment::from(d))
+                .get_result(conn)
+                .map(|d: Department| DepartmentDTO::from(d))
+        }
+        if let Some(self_id) = self.id {
+            let updated = match self.version {
+                Some(self_version) => diesel::update(
+                    departments
+                        .filter(department_id.eq(self_id))
+                        .filter(version.eq(self_version)),
+                )
+                .set((Department::from(self), version.eq(version + 1)))
+                .execute(conn)?,
+                None => 0,
+            };
+            if updated == 0 {
+                let current = departments
+                    .filter(department_id.eq(self_id))
+                    .select(version)
+                    .first::<i32>(conn)
+                    .optional()?;
+                match current {
+                    Some(current) => Err(stale_version(self_id, Some(current))),
+                    None => Ok(insert(self, conn)?),
+                }
+            } else {
+                Ok(Self::get_simple(self_id, keys, conn)?)
+            }
+        } else {
+            Ok(insert(self, conn)?)
+        }
+    }
+
+    /// Sub-departments and employees of deleted department are moved to its parent
+    fn delete_simple(id_to_find: i32, conn: &mut DbConnection) -> QueryResult<usize> {
+        use crate::schema::employees::dsl as e;
+
+        let parent = departments
+            .filter(department_id.eq(id_to_find))
+            .select(parent_id)
+            .first::<Option<i32>>(conn)
+            .optional()?
+            .flatten();
+        diesel::update(departments.filter(parent_id.eq(id_to_find)))
+            .set((parent_id.eq(parent), version.eq(version + 1)))
+            .execute(conn)?;
+        diesel::update(e::employees.filter(e::department_id.eq(id_to_find)))
+            .set((e::department_id.eq(parent), e::version.eq(e::version + 1)))
+            .execute(conn)?;
+        diesel::delete(departments.filter(department_id.eq(id_to_find))).execute(conn)
+    }
+
+    fn delete_versioned_simple(
+        id_to_find: i32,
+        version_to_find: i32,
+        conn: &mut DbConnection,
+    ) -> QueryResult<usize> {
+        // Bump version first - it check version and lock the row before children are moved
+        let locked = diesel::update(
+            departments
+                .filter(department_id.eq(id_to_find))
+                .filter(version.eq(version_to_find)),
+        )
+        .set(version.eq(version + 1))
+        .execute(conn)?;
+        if locked == 0 {
+            return Ok(0);
+        }
+        Self::delete_simple(id_to_find, conn)
+    }
+}
+
+impl Searchable for DepartmentDTO {
+    fn get_all_with_connection(_keys: Option<&KeyRing>, conn: &mut DbConnection) -> DaoResult<Vec<Self>> {
+        Ok(departments
+            .order(department_id)
+            .load::<Department>(conn)?
+            .into_iter()
+            .map(Self::from)
+            .collect())
+    }
+
+    fn search_with_connection(s: &str, _keys: Option<&KeyRing>, conn: &mut DbConnection) -> DaoResult<Vec<Self>> {
+        Ok(departments
+            .filter(search_string.like(s))
+            .load::<Department>(conn)?
+            .into_iter()
+            .map(Self::from)
+            .collect())
+    }
+}
+
+impl DepartmentDTO {
+    /// The department and all its sub-departments (recursively)
+    pub fn subtree(db: &Database, id_to_find: i32) -> DaoResult<Vec<Self>> {
+        let conn = &mut db.try_get_connection()?;
+        Self::subtree_with_connection(id_to_find, conn)
+    }
+
+    pub fn subtree_with_connection(id_to_find: i32, conn: &mut DbConnection) -> DaoResult<Vec<Self>> {
+        let ids = descendants(Tree::Departments, id_to_find, conn)?;
+        Ok(departments
+            .filter(department_id.eq_any(ids))
+            .order(department_id)
+            .load::<Department>(conn)?
+            .into_iter()
+            .map(Self::from)
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::common_for_tests::*;
+    use crate::error::DaoError;
+
+    use super::*;
+
+    impl CrudTests for DepartmentDTO {}
+
+    fn department(department_name: &str, parent: Option<i32>) -> DepartmentDTO {
+        DepartmentDTO {
+            id: None,
+            name: department_name.to_string(),
+            parent_id: parent,
+            search_string: "".to_string(),
+            version: None,
+        }
+    }
+
+    #[test]
+    fn crud_operations_on_department() {
+        let conn = &mut initialize();
+        department("IT", None).test(conn);
+    }
+
+    #[test]
+    fn department_tree_can_not_have_cycles() {
+        let conn = &mut initialize();
+        let rules = ValidationRules::default();
+        let company = department("Company", None).save_in_transaction(keys(), conn).unwrap();
+        let it = department("IT", company.id).save_in_transaction(keys(), conn).unwrap();
+        let dev = department("Development", it.id).save_in_transaction(keys(), conn).unwrap();
+        department("Sales", company.id).save_in_transaction(keys(), conn).unwrap();
+
+        let names = |ds: Vec<DepartmentDTO>| ds.into_iter().map(|d| d.name).collect::<Vec<_>>();
+        let subtree = DepartmentDTO::subtree_with_connection(it.id.unwrap(), conn).unwrap();
+        assert_eq!(names(subtree), vec!["IT", "Development"]);
+        assert_eq!(DepartmentDTO::subtree_with_connection(company.id.unwrap(), conn).unwrap().len(), 4);
+
+        for (parent, field) in [(dev.id, "parent_id"), (company.id, "parent_id"), (Some(1000), "parent_id")] {
+            let moved = DepartmentDTO {
+                parent_id: parent,
+                ..company.clone()
+            };
+            match moved.try_save_in_transaction(&rules, keys(), conn) {
+                Err(DaoError::Validation(errors)) => assert_eq!(errors[0].field, field),
+                result => panic!("Should report validation error and instead I got {:?}", result),
+            }
+        }
+
+        // Sub-departments of deleted department are moved to its parent
+        assert_eq!(it.try_delete_with_conn(keys(), conn).unwrap(), 1);
+        let dev = DepartmentDTO::get_with_conn(dev.id.unwrap(), keys(), conn).unwrap();
+        assert_eq!(dev.parent_id, company.id);
+        assert_eq!(dev.version, Some(2));
+    }
+}
@@ -0,0 +1,505 @@
+use std::str::FromStr;
+
+use aes_gcm::aead::rand_core::RngCore;
+use aes_gcm::aead::OsRng;
+use chrono::{Local, NaiveDate, NaiveDateTime};
+use diesel::dsl::*;
+use diesel::prelude::*;
+use sha2::{Digest, Sha256};
+
+use crate::connection::DbConnection;
+use crate::error::{DaoError, DaoResult};
+use crate::hierarchy::EmployeeScope;
+use crate::models::{Document, NewDocument, User};
+use crate::schema::documents::dsl::id as document_id;
+use crate::schema::documents::dsl::*;
+use crate::schema::employees::dsl as e;
+use crate::storage::{DocumentStorage, DocumentsConfig, StorageError};
+use crate::validation::Errors;
+
+/// Longest file name of document (in characters)
+const MAX_FILE_NAME_LENGTH: usize = 255;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DocumentType {
+    Contract,
+    Certificate,
+    IdScan,
+    Other,
+}
+
+impl DocumentType {
+    /// How it is stored in DB
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DocumentType::Contract => "contract",
+            DocumentType::Certificate => "certificate",
+            DocumentType::IdScan => "id_scan",
+            DocumentType::Other => "other",
+        }
+    }
+}
+
+impl FromStr for DocumentType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "contract" => Ok(DocumentType::Contract),
+            "certificate" => Ok(DocumentType::Certificate),
+            "id_scan" => Ok(DocumentType::IdScan),
+            "other" => Ok(DocumentType::Other),
+            _ => Err(format!(
+                "unknown document type '{}' - should be one of contract, certificate, id_scan, other",
+                s
+            )),
+        }
+    }
+}
+
+/// Who can read document - besides being able to see its employee (see EmployeeScope) like salaries
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Confidentiality {
+    /// Everybody who can see the employee
+    #[default]
+    Internal,
+    /// Just admins and the employee itself
+    Confidential,
+    /// Just admins
+    Restricted,
+}
+
+impl Confidentiality {
+    /// How it is stored in DB
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Confidentiality::Internal => "internal",
+            Confidentiality::Confidential => "confidential",
+            Confidentiality::Restricted => "restricted",
+        }
+    }
+}
+
+impl FromStr for Confidentiality {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "internal" => Ok(Confidentiality::Internal),
+            "confidential" => Ok(Confidentiality::Confidential),
+            "restricted" => Ok(Confidentiality::Restricted),
+            _ => Err(format!(
+                "unknown confidentiality '{}' - should be one of internal, confidential, restricted",
+                s
+            )),
+        }
+    }
+}
+
+/// Metadata of document attached to employee - content is in DocumentStorage
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct DocumentDTO {
+    pub id: i32,
+    pub employee_id: i32,
+    pub document_type: DocumentType,
+    pub file_name: String,
+    pub content_type: String,
+    /// In bytes
+    pub size: i64,
+    /// Hex of SHA-256 of content - it is checked every time content is read
+    pub sha256: String,
+    pub valid_from: Option<NaiveDate>,
+    pub valid_to: Option<NaiveDate>,
+    pub confidentiality: Confidentiality,
+    pub uploaded_by: Option<i32>,
+    pub uploaded_at: NaiveDateTime,
+    #[serde(skip)]
+    storage_key: String,
+}
+
+impl From<Document> for DocumentDTO {
+    fn from(d: Document) -> Self {
+        DocumentDTO {
+            id: d.id,
+            employee_id: d.employee_id,
+            document_type: d.document_type.parse().expect("document type is checked by DB"),
+            file_name: d.file_name,
+            content_type: d.content_type,
+            size: d.size,
+            sha256: d.sha256,
+            valid_from: d.valid_from,
+            valid_to: d.valid_to,
+            confidentiality: d.confidentiality.parse().expect("confidentiality is checked by DB"),
+            uploaded_by: d.uploaded_by,
+            uploaded_at: d.uploaded_at,
+            storage_key: d.storage_key,
+        }
+    }
+}
+
+/// Metadata given with uploaded content (`POST /employees/{id}/documents`)
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct DocumentUpload {
+    pub document_type: DocumentType,
+    pub file_name: String,
+    pub content_type: String,
+    #[serde(default)]
+    pub valid_from: Option<NaiveDate>,
+    #[serde(default)]
+    pub valid_to: Option<NaiveDate>,
+    #[serde(default)]
+    pub confidentiality: Confidentiality,
+    /// Hex of SHA-256 computed by client - upload is rejected when content doesn't match it
+    #[serde(default)]
+    pub sha256: Option<String>,
+}
+
+/// Type of content detected by its signature (magic bytes) - declared type has to match it. Declared type is
+/// trusted just for content without signature known to `infer` and `application/octet-stream` is replaced by
+/// the detected one.
+fn content_type_of(declared: &str, content: &[u8]) -> Result<String, String> {
+    match infer::get(content) {
+        Some(detected) if declared == detected.mime_type() || declared == "application/octet-stream" => {
+            Ok(detected.mime_type().to_string())
+        }
+        Some(detected) => Err(format!("content is {}, not {}", detected.mime_type(), declared)),
+        None if infer::is_mime_supported(declared) => Err(format!("content is not {}", declared)),
+        None => Ok(declared.to_string()),
+    }
+}
+
+fn random_key(e_id: i32) -> String {
+    let mut random = [0u8; 16];
+    OsRng.fill_bytes(&mut random);
+    let random: String = random.iter().map(|b| format!("{:02x}", b)).collect();
+    format!("{}/{}", e_id, random)
+}
+
+impl DocumentDTO {
+    /// Could the user read the document - its employee has to be in scope of the user (see EmployeeScope) first
+    pub fn readable_by(&self, user: &User, conn: &mut DbConnection) -> QueryResult<bool> {
+        if !EmployeeScope::of(user).contains(self.employee_id, conn)? {
+            return Ok(false);
+        }
+        Ok(match self.confidentiality {
+            Confidentiality::Internal => true,
+            Confidentiality::Confidential => user.is_admin || user.employee_id == Some(self.employee_id),
+            Confidentiality::Restricted => user.is_admin,
+        })
+    }
+
+    /// Documents of employee - the newest first
+    pub fn of_employee_with_connection(e_id: i32, conn: &mut DbConnection) -> QueryResult<Vec<Self>> {
+        Ok(documents
+            .filter(employee_id.eq(e_id))
+            .order(document_id.desc())
+            .load::<Document>(conn)?
+            .into_iter()
+            .map(Self::from)
+            .collect())
+    }
+
+    /// Document of employee - DaoError::not_found() when the employee has no such document
+    pub fn get_with_connection(e_id: i32, d_id: i32, conn: &mut DbConnection) -> QueryResult<Self> {
+        documents
+            .filter(document_id.eq(d_id))
+            .filter(employee_id.eq(e_id))
+            .first(conn)
+            .map(|d: Document| DocumentDTO::from(d))
+    }
+
+    /// Store content and its metadata - both or neither. DaoError::not_found() when there is no such (not deleted)
+    /// employee, validation error when content is too big, of not allowed type, doesn't match its type or checksum.
+    pub fn upload_with_connection(
+        e_id: i32,
+        upload: &DocumentUpload,
+        content: &[u8],
+        user_id: Option<i32>,
+        config: &DocumentsConfig,
+        storage: &dyn DocumentStorage,
+        conn: &mut DbConnection,
+    ) -> DaoResult<Self> {
+        let exists = e::employees
+            .filter(e::id.eq(e_id))
+            .filter(e::deleted_at.is_null())
+            .select(count(e::id))
+            .first::<i64>(conn)?;
+        if exists == 0 {
+            return Err(DaoError::not_found());
+        }
+        let checksum = format!("{:x}", Sha256::digest(content));
+        let mut errors = Errors::default();
+        let name = upload.file_name.trim();
+        if name.is_empty() {
+            errors.add("", "file_name", "can't be empty".to_string());
+        } else if name.chars().count() > MAX_FILE_NAME_LENGTH {
+            errors.add("", "file_name", format!("is longer than {} characters", MAX_FILE_NAME_LENGTH));
+        }
+        if content.is_empty() {
+            errors.add("", "file", "can't be empty".to_string());
+        } else if content.len() as u64 > config.max_size {
+            errors.add("", "file", format!("is bigger than {} bytes", config.max_size));
+        }
+        let declared = upload.content_type.trim().to_lowercase();
+        let mime_type = match content_type_of(&declared, content) {
+            Ok(mime_type) if !config.mime_types.contains(&mime_type) => {
+                errors.add(
+                    "",
+                    "content_type",
+                    format!("'{}' is not allowed - should be one of {}", mime_type, config.mime_types.join(", ")),
+                );
+                mime_type
+            }
+            Ok(mime_type) => mime_type,
+            Err(mismatch) => {
+                if !content.is_empty() {
+                    errors.add("", "content_type", mismatch);
+                }
+                declared
+            }
+        };
+        if let Some(expected) = &upload.sha256
+            && !expected.trim().eq_ignore_ascii_case(&checksum)
+        {
+            errors.add("", "sha256", "doesn't match uploaded content".to_string());
+        }
+        if let (Some(from), Some(to)) = (upload.valid_from, upload.valid_to)
+            && to < from
+        {
+            errors.add("", "valid_to", "can't be before valid_from".to_string());
+        }
+        errors.into_result()?;
+
+        let key = random_key(e_id);
+        conn.transaction(|conn| {
+            let saved = insert_into(documents)
+                .values(NewDocument {
+                    employee_id: e_id,
+                    document_type: upload.document_type.as_str().to_string(),
+                    file_name: name.to_string(),
+                    content_type: mime_type.clone(),
+                    size: content.len() as i64,
+                    sha256: checksum.clone(),
+                    storage_key: key.clone(),
+                    valid_from: upload.valid_from,
+                    valid_to: upload.valid_to,
+                    confidentiality: upload.confidentiality.as_str().to_string(),
+                    uploaded_by: user_id,
+                    uploaded_at: Local::now().naive_local(),
+                })
+                .get_result::<Document>(conn)
+                .map(DocumentDTO::from)?;
+            storage.put(&key, content)?;
+            Ok(saved)
+        })
+    }
+
+    /// Content of document - DaoError::Storage when it is missing or doesn't match its checksum
+    pub fn content(&self, storage: &dyn DocumentStorage) -> DaoResult<Vec<u8>> {
+        let content = storage.get(&self.storage_key)?;
+        if format!("{:x}", Sha256::digest(&content)) != self.sha256 {
+            return Err(StorageError::Corrupted(self.storage_key.clone()).into());
+        }
+        Ok(content)
+    }
+
+    /// Delete document with its content - DaoError::not_found() when the employee has no such document
+    pub fn delete_with_connection(
+        e_id: i32,
+        d_id: i32,
+        storage: &dyn DocumentStorage,
+        conn: &mut DbConnection,
+    ) -> DaoResult<usize> {
+        conn.transaction(|conn| {
+            let document = Self::get_with_connection(e_id, d_id, conn)?;
+            let deleted = diesel::delete(documents.filter(document_id.eq(d_id))).execute(conn)?;
+            storage.delete(&document.storage_key)?;
+            Ok(deleted)
+        })
+    }
+}
+
+/// Delete documents of employees - return storage keys of their content which should be deleted by
+/// delete_contents() when the transaction is committed
+pub(crate) fn delete_documents_of(e_ids: &[i32], conn: &mut DbConnection) -> QueryResult<Vec<String>> {
+    let keys = documents
+        .filter(employee_id.eq_any(e_ids))
+        .select(storage_key)
+        .load(conn)?;
+    diesel::delete(documents.filter(employee_id.eq_any(e_ids))).execute(conn)?;
+    Ok(keys)
+}
+
+/// Content (of documents or photos) which can't be deleted is just logged - its metadata is gone already
+pub(crate) fn delete_contents(keys: &[String], storage: &dyn DocumentStorage) {
+    for key in keys {
+        if let Err(e) = storage.delete(key) {
+            warn!("Deleted content '{}' is left in storage: {}", key, e);
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::path::PathBuf;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use crate::common_for_tests::*;
+    use crate::employees_dao::EmployeeDTO;
+    use crate::storage::LocalStorage;
+    use crate::Crud;
+
+    use super::*;
+
+    pub const PDF: &[u8] = b"%PDF-1.4\n%%EOF\n";
+
+    /// Storage in its own temporary directory
+    pub fn temporary_storage() -> (LocalStorage, PathBuf) {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+        let dir = std::env::temp_dir().join(format!(
+            "documents_test_{}_{}",
+            std::process::id(),
+            COUNTER.fetch_add(1, Ordering::SeqCst)
+        ));
+        (LocalStorage::new(&dir), dir)
+    }
+
+    pub fn contract() -> DocumentUpload {
+        DocumentUpload {
+            document_type: DocumentType::Contract,
+            file_name: "contract.pdf".to_string(),
+            content_type: "application/pdf".to_string(),
+            valid_from: NaiveDate::from_ymd_opt(2022, 1, 1),
+            valid_to: None,
+            confidentiality: Confidentiality::Confidential,
+            sha256: None,
+        }
+    }
+
+    fn user(is_admin: bool, e_id: Option<i32>) -> User {
+        User {
+            id: 1,
+            username: "user".to_string(),
+            password: "".to_string(),
+            is_admin,
+            employee_id: e_id,
+            version: 1,
+            privacy_officer: false,
+        }
+    }
+
+    #[test]
+    fn documents_are_uploaded_read_and_deleted() {
+        let conn = &mut initialize();
+        let (storage, dir) = temporary_storage();
+        let config = DocumentsConfig::default();
+        let e_id = EmployeeDTO {
+            first_name: "Jan".to_string(),
+            last_name: "Kowalski".to_string(),
+            ..Default::default()
+        }
+        .save_in_transaction(keys(), conn)
+        .unwrap()
+        .id
+        .unwrap();
+
+        let upload = DocumentUpload {
+            sha256: Some(format!("{:x}", Sha256::digest(PDF))),
+            ..contract()
+        };
+        let saved = DocumentDTO::upload_with_connection(e_id, &upload, PDF, Some(1), &config, &storage, conn).unwrap();
+        assert_eq!(saved.size, PDF.len() as i64);
+        assert_eq!(saved.document_type, DocumentType::Contract);
+        assert_eq!(DocumentDTO::of_employee_with_connection(e_id, conn).unwrap(), vec![saved.clone()]);
+        let read = DocumentDTO::get_with_connection(e_id, saved.id, conn).unwrap();
+        assert_eq!(read.content(&storage).unwrap(), PDF);
+        assert!(DocumentDTO::get_with_connection(e_id + 1, saved.id, conn).is_err());
+
+        // Content changed behind our back is reported
+        storage.put(&read.storage_key, b"%PDF-1.4 changed").unwrap();
+        assert!(matches!(read.content(&storage), Err(DaoError::Storage(StorageError::Corrupted(_)))));
+
+        for (invalid, content, field) in [
+            (contract(), &b""[..], "file"),
+            (contract(), &[b'%'; 100][..], "content_type"),
+            (DocumentUpload { content_type: "text/plain".to_string(), ..contract() }, PDF, "content_type"),
+            (DocumentUpload { content_type: "text/plain".to_string(), ..contract() }, &b"text"[..], "content_type"),
+            (DocumentUpload { sha256: Some("00".to_string()), ..contract() }, PDF, "sha256"),
+            (DocumentUpload { file_name: " ".to_string(), ..contract() }, PDF, "file_name"),
+            (DocumentUpload { valid_to: NaiveDate::from_ymd_opt(2021, 1, 1), ..contract() }, PDF, "valid_to"),
+        ] {
+            match DocumentDTO::upload_with_connection(e_id, &invalid, content, None, &config, &storage, conn) {
+                Err(DaoError::Validation(errors)) => assert_eq!(errors[0].field, field),
+                result => panic!("Should report validation error and instead I got {:?}", result),
+            }
+        }
+        let untyped = DocumentUpload {
+            content_type: "application/octet-stream".to_string(),
+            ..contract()
+        };
+        let detected = DocumentDTO::upload_with_connection(e_id, &untyped, PDF, None, &config, &storage, conn).unwrap();
+        assert_eq!(detected.content_type, "application/pdf", "Type should be detected by content");
+        DocumentDTO::delete_with_connection(e_id, detected.id, &storage, conn).unwrap();
+        let small = DocumentsConfig { max_size: 4, ..Default::default() };
+        match DocumentDTO::upload_with_connection(e_id, &contract(), PDF, None, &small, &storage, conn) {
+            Err(DaoError::Validation(errors)) => assert_eq!(errors[0].field, "file"),
+            result => panic!("Should report validation error and instead I got {:?}", result),
+        }
+        assert!(DocumentDTO::upload_with_connection(e_id + 1, &contract(), PDF, None, &config, &storage, conn)
+            .unwrap_err()
+            .is_not_found());
+
+        assert_eq!(DocumentDTO::delete_with_connection(e_id, saved.id, &storage, conn).unwrap(), 1);
+        assert!(matches!(storage.get(&read.storage_key), Err(StorageError::NotFound(_))));
+        assert!(DocumentDTO::of_employee_with_connection(e_id, conn).unwrap().is_empty());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn documents_are_readable_within_scope_by_confidentiality() {
+        let conn = &mut initialize();
+        let (storage, dir) = temporary_storage();
+        let config = DocumentsConfig::default();
+        let mut save = |last_name: &str, manager: Option<i32>| {
+            EmployeeDTO {
+                first_name: "Jan".to_string(),
+                last_name: last_name.to_string(),
+                manager_id: manager,
+                ..Default::default()
+            }
+            .save_in_transaction(keys(), conn)
+            .unwrap()
+            .id
+            .unwrap()
+        };
+        let manager = save("Manager", None);
+        let e_id = save("Kowalski", Some(manager));
+        let other = save("Other", None);
+
+        for (level, readers) in [
+            (Confidentiality::Internal, [true, true, true, false, true]),
+            (Confidentiality::Confidential, [true, false, true, false, false]),
+            (Confidentiality::Restricted, [true, false, false, false, false]),
+        ] {
+            let upload = DocumentUpload { confidentiality: level, ..contract() };
+            let document =
+                DocumentDTO::upload_with_connection(e_id, &upload, PDF, None, &config, &storage, conn).unwrap();
+            let users = [
+                user(true, None),
+                user(false, Some(manager)),
+                user(false, Some(e_id)),
+                user(false, Some(other)),
+                user(false, None),
+            ];
+            let readable: Vec<bool> = users.iter().map(|u| document.readable_by(u, conn).unwrap()).collect();
+            assert_eq!(
+                readable, readers,
+                "{:?} document is read by admin, manager, employee itself, other employee and not linked user",
+                level
+            );
+        }
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,306 @@
+use diesel::dsl::*;
+use diesel::prelude::*;
+
+use crate::base_dao::{stale_version, Crud, HaveId, HaveVersion, Searchable};
+use crate::connection::DbConnection;
+use crate::crypto::KeyRing;
+use crate::employees_dao::{EmployeeDTO, EmployeeSearch};
+use crate::error::{DaoError, DaoResult};
+use crate::hierarchy::EmployeeScope;
+use crate::models::{EmployeeGroup, NewEmployeeGroup};
+use crate::schema::employee_groups::dsl::id as group_id;
+use crate::schema::employee_groups::dsl::*;
+use crate::validation::{Errors, ValidationRules};
+
+/// Saved group of employees - `filter` is evaluated every time members are read, so they follow changes
+/// of employees (tags, status, department ...)
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct EmployeeGroupDTO {
+    pub id: Option<i32>,
+    pub name: String,
+    /// The same criteria as `GET /employees` has - `tag` is list of tags there
+    pub filter: EmployeeSearch,
+    pub search_string: String,
+    pub version: Option<i32>,
+}
+
+impl From<EmployeeGroup> for EmployeeGroupDTO {
+    fn from(g: EmployeeGroup) -> Self {
+        EmployeeGroupDTO {
+            id: Some(g.id),
+            name: g.name,
+            filter: serde_json::from_str(&g.filter).expect("filter is saved as JSON"),
+            search_string: g.search_string,
+            version: Some(g.version),
+        }
+    }
+}
+
+impl From<&EmployeeGroupDTO> for EmployeeGroup {
+    fn from(group_dto: &EmployeeGroupDTO) -> Self {
+        EmployeeGroup {
+            id: group_dto.id.unwrap(),
+            name: group_dto.name.clone(),
+            filter: serde_json::to_string(&group_dto.filter).expect("filter is serializable"),
+            search_string: group_dto.search_string.clone(),
+            version: group_dto.version.unwrap_or_default(),
+        }
+    }
+}
+
+impl From<&EmployeeGroupDTO> for NewEmployeeGroup {
+    fn from(group_dto: &EmployeeGroupDTO) -> Self {
+        NewEmployeeGroup {
+            name: group_dto.name.clone(),
+            filter: serde_json::to_string(&group_dto.filter).expect("filter is serializable"),
+            search_string: group_dto.search_string.clone(),
+        }
+    }
+}
+
+impl HaveId for EmployeeGroupDTO {
+    fn get_id(&self) -> Option<i32> {
+        self.id
+    }
+}
+
+impl HaveVersion for EmployeeGroupDTO {
+    fn get_version(&self) -> Option<i32> {
+        self.version
+    }
+}
+
+impl Crud for EmployeeGroupDTO {
+    fn update(&mut self, persisted: &Self) {
+        self.id = persisted.id;
+        self.version = persisted.version;
+    }
+
+    /// Name is unique and filter can be evaluated - group is never of deleted employees
+    fn validate(&self, _rules: &ValidationRules, keys: Option<&KeyRing>, conn: &mut DbConnection) -> DaoResult<()> {
+        let mut errors = Errors::default();
+        if self.name.trim().is_empty() {
+            errors.add("", "name", "can't be empty".to_string());
+        }
+        let same_name = employee_groups
+            .filter(name.eq(&self.name))
+            .select(group_id)
+            .first::<i32>(conn)
+            .optional()?;
+        if let Some(other) = same_name
+            && Some(other) != self.id
+        {
+            errors.add("", "name", format!("'{}' is already name of group id = {}", self.name, other));
+        }
+        if self.filter.include_deleted {
+            errors.add("filter.", "include_deleted", "group can't have deleted employees".to_string());
+        }
+        match EmployeeDTO::search_ids_in_scope_with_connection(&self.filter, EmployeeScope::All, keys, conn) {
+            Err(DaoError::Validation(filter_errors)) => {
+                for e in filter_errors {
+                    errors.add("filter.", &e.field, e.message);
+                }
+            }
+            Err(e) => return Err(e),
+            Ok(_) => {}
+        }
+        errors.into_result()
+    }
+
+    fn get_simple(id_to_find: i32, _keys: Option<&KeyRing>, conn: &mut DbConnection) -> QueryResult<Self> {
+        employee_groups
+            .filter(group_id.eq(id_to_find))
+            .first(conn)
+            .map(|g: EmployeeGroup| EmployeeGroupDTO::from(g))
+    }
+
+    fn save_simple(&self, keys: Option<&KeyRing>, conn: &mut DbConnection) -> DaoResult<Self> {
+        fn insert(g: &EmployeeGroupDTO, conn: &mut DbConnection) -> QueryResult<EmployeeGroupDTO> {
+            insert_into(employee_groups)
+                .values(NewEmployeeGroup::from(g))
+                .get_result(conn)
+                .map(|g: EmployeeGroup| EmployeeGroupDTO::from(g))
+        }
+        if let Some(self_id) = self.id {
+            let updated = match self.version {
+                Some(self_version) => diesel::update(
+                    employee_groups
+                        .filter(group_id.eq(self_id))
+                        .filter(version.eq(self_version)),
+                )
+                .set((EmployeeGroup::from(self), version.eq(version + 1)))
+                .execute(conn)?,
+                None => 0,
+            };
+            if updated == 0 {
+                let current = employee_groups
+                    .filter(group_id.eq(self_id))
+                    .select(version)
+                    .first::<i32>(conn)
+                    .optional()?;
+                match current {
+                    Some(current) => Err(stale_version(self_id, Some(current))),
+                    None => Ok(insert(self, conn)?),
+                }
+            } else {
+                Ok(Self::get_simple(self_id, keys, conn)?)
+            }
+        } else {
+            Ok(insert(self, conn)?)
+        }
+    }
+
+    fn delete_simple(id_to_find: i32, conn: &mut DbConnection) -> QueryResult<usize> {
+        diesel::delete(employee_groups.filter(group_id.eq(id_to_find))).execute(conn)
+    }
+
+    fn delete_versioned_simple(
+        id_to_find: i32,
+        version_to_find: i32,
+        conn: &mut DbConnection,
+    ) -> QueryResult<usize> {
+        diesel::delete(
+            employee_groups
+                .filter(group_id.eq(id_to_find))
+                .filter(version.eq(version_to_find)),
+        )
+        .execute(conn)
+    }
+}
+
+impl Searchable for EmployeeGroupDTO {
+    fn get_all_with_connection(_keys: Option<&KeyRing>, conn: &mut DbConnection) -> DaoResult<Vec<Self>> {
+        Ok(employee_groups
+            .order(name)
+            .load::<EmployeeGroup>(conn)?
+            .into_iter()
+            .map(Self::from)
+            .collect())
+    }
+
+    fn search_with_connection(s: &str, _keys: Option<&KeyRing>, conn: &mut DbConnection) -> DaoResult<Vec<Self>> {
+        Ok(employee_groups
+            .filter(search_string.like(s))
+            .order(name)
+            .load::<EmployeeGroup>(conn)?
+            .into_iter()
+            .map(Self::from)
+            .collect())
+    }
+}
+
+impl EmployeeGroupDTO {
+    /// Current members of group in scope - DaoError::not_found() when there is no such group
+    pub fn members_with_connection(
+        id_to_find: i32,
+        scope: EmployeeScope,
+        keys: Option<&KeyRing>,
+        conn: &mut DbConnection,
+    ) -> DaoResult<Vec<EmployeeDTO>> {
+        let group = Self::get_simple(id_to_find, keys, conn)?;
+        EmployeeDTO::search_in_scope_with_connection(&group.filter, scope, keys, conn)
+    }
+
+    /// Ids of members_with_connection() - for reports and exports of group
+    pub fn member_ids_with_connection(
+        id_to_find: i32,
+        scope: EmployeeScope,
+        keys: Option<&KeyRing>,
+        conn: &mut DbConnection,
+    ) -> DaoResult<Vec<i32>> {
+        let group = Self::get_simple(id_to_find, keys, conn)?;
+        EmployeeDTO::search_ids_in_scope_with_connection(&group.filter, scope, keys, conn)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::common_for_tests::*;
+    use crate::employees_dao::{EmployeeDTO, EmployeeSearch, EmploymentStatus};
+    use crate::error::DaoError;
+    use crate::hierarchy::EmployeeScope;
+    use crate::tags_dao::{change_tags_with_connection, TagsChange};
+    use crate::validation::ValidationRules;
+    use crate::Crud;
+
+    use super::EmployeeGroupDTO;
+
+    impl CrudTests for EmployeeGroupDTO {}
+
+    fn group(group_name: &str, filter: EmployeeSearch) -> EmployeeGroupDTO {
+        EmployeeGroupDTO {
+            id: None,
+            name: group_name.to_string(),
+            filter,
+            search_string: "".to_string(),
+            version: None,
+        }
+    }
+
+    fn remote() -> EmployeeSearch {
+        EmployeeSearch {
+            tag: vec!["remote".to_string()],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn crud_operations_on_employee_group() {
+        let conn = &mut initialize();
+        group("Remote", remote()).test(conn);
+    }
+
+    #[test]
+    fn members_of_group_are_evaluated_on_read() {
+        let conn = &mut initialize();
+        let rules = ValidationRules::default();
+        let saved = group("Remote", remote()).try_save_in_transaction(&rules, keys(), conn).unwrap();
+        let g_id = saved.id.unwrap();
+        assert!(EmployeeGroupDTO::members_with_connection(g_id, EmployeeScope::All, keys(), conn).unwrap().is_empty());
+
+        let jan = EmployeeDTO {
+            first_name: "Jan".to_string(),
+            last_name: "Kowalski".to_string(),
+            ..Default::default()
+        }
+        .save_in_transaction(keys(), conn)
+        .unwrap();
+        let change = TagsChange {
+            employee_ids: vec![jan.id.unwrap()],
+            add: vec!["remote".to_string()],
+            remove: vec![],
+        };
+        change_tags_with_connection(&change, conn).unwrap();
+        let members = EmployeeGroupDTO::members_with_connection(g_id, EmployeeScope::All, keys(), conn).unwrap();
+        assert_eq!(members.len(), 1);
+        assert_eq!(members[0].tags, vec!["remote"]);
+        let out_of_scope = EmployeeScope::Subtree(jan.id.unwrap() + 1);
+        let members = EmployeeGroupDTO::member_ids_with_connection(g_id, out_of_scope, keys(), conn).unwrap();
+        assert!(members.is_empty(), "Members out of scope should be left out");
+        assert!(EmployeeGroupDTO::members_with_connection(g_id + 1, EmployeeScope::All, keys(), conn)
+            .unwrap_err()
+            .is_not_found());
+
+        for (invalid, field) in [
+            (group("Remote", EmployeeSearch::default()), "name"),
+            (
+                group("Deleted", EmployeeSearch { include_deleted: true, ..Default::default() }),
+                "filter.include_deleted",
+            ),
+            (
+                group("Shoe size", EmployeeSearch { custom_field: Some("shoe:42".to_string()), ..Default::default() }),
+                "filter.custom_field",
+            ),
+        ] {
+            match invalid.try_save_in_transaction(&rules, keys(), conn) {
+                Err(DaoError::Validation(errors)) => assert_eq!(errors[0].field, field),
+                result => panic!("Should report validation error and instead I got {:?}", result),
+            }
+        }
+        let active = EmployeeSearch {
+            status: Some(EmploymentStatus::Active),
+            ..Default::default()
+        };
+        assert!(group("Active", active).try_save_in_transaction(&rules, keys(), conn).is_ok());
+    }
+}
@@ -0,0 +1,145 @@
+use std::fmt;
+use std::str::FromStr;
+
+use chrono::{Datelike, Local};
+use diesel::prelude::*;
+
+use crate::connection::DbConnection;
+use crate::schema::employees::dsl as e;
+use crate::schema::sequences::dsl::*;
+
+const SEQUENCE: &str = "employee_number";
+
+/// Format of generated employee numbers (EMPLOYEE_NUMBER_FORMAT) - `{seq}` is replaced by next number of
+/// sequence (`{seq:5}` pads it with zeros to 5 digits) and `{year}` by current year: `"E{year}-{seq:4}"`
+/// gives `"E2021-0042"`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EmployeeNumberFormat {
+    prefix: String,
+    width: usize,
+    suffix: String,
+}
+
+impl Default for EmployeeNumberFormat {
+    fn default() -> Self {
+        "E{seq:5}".parse().expect("default format is valid")
+    }
+}
+
+impl FromStr for EmployeeNumberFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let start = s
+            .find("{seq")
+            .ok_or_else(|| "has to contain {seq} or {seq:width}".to_string())?;
+        let end = start
+            + s[start..]
+                .find('}')
+                .ok_or_else(|| "{seq is not closed".to_string())?;
+        let width = match &s[start + 4..end] {
+            "" => 0,
+            w => w
+                .strip_prefix(':')
+                .and_then(|w| w.parse::<usize>().ok())
+                .filter(|w| (1..=12).contains(w))
+                .ok_or_else(|| format!("'{}' is not valid width - should be {{seq:1}} to {{seq:12}}", w))?,
+        };
+        let (prefix, suffix) = (&s[..start], &s[end + 1..]);
+        if prefix.contains("{seq") || suffix.contains("{seq") {
+            return Err("can contain just one {seq}".to_string());
+        }
+        Ok(EmployeeNumberFormat {
+            prefix: prefix.to_string(),
+            width,
+            suffix: suffix.to_string(),
+        })
+    }
+}
+
+impl fmt::Display for EmployeeNumberFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.width {
+            0 => write!(f, "{}{{seq}}{}", self.prefix, self.suffix),
+            w => write!(f, "{}{{seq:{}}}{}", self.prefix, w, self.suffix),
+        }
+    }
+}
+
+impl EmployeeNumberFormat {
+    /// Number `seq` in this format
+    pub fn format(&self, seq: i32, year: i32) -> String {
+        let year = year.to_string();
+        format!(
+            "{}{:0width$}{}",
+            self.prefix.replace("{year}", &year),
+            seq,
+            self.suffix.replace("{year}", &year),
+            width = self.width
+        )
+    }
+}
+
+/// Next value of named sequence - row of the sequence is locked (by update) till end of transaction
+pub(crate) fn next_in_sequence(sequence: &str, conn: &mut DbConnection) -> QueryResult<i32> {
+    diesel::update(sequences.filter(name.eq(sequence)))
+        .set(value.eq(value + 1))
+        .execute(conn)?;
+    sequences.filter(name.eq(sequence)).select(value).first(conn)
+}
+
+/// Next employee number not used yet - numbers given by hand are skipped
+pub(crate) fn next_employee_number(format: &EmployeeNumberFormat, conn: &mut DbConnection) -> QueryResult<String> {
+    loop {
+        let number = format.format(next_in_sequence(SEQUENCE, conn)?, Local::now().year());
+        let used = e::employees
+            .filter(e::employee_number.eq(&number))
+            .count()
+            .get_result::<i64>(conn)?;
+        if used == 0 {
+            return Ok(number);
+        }
+    }
+}
+
+/// Employees saved before they had employee numbers get them in `format` (in order of id). Return number
+/// of such employees.
+pub fn convert_legacy_employees(format: &EmployeeNumberFormat, conn: &mut DbConnection) -> QueryResult<usize> {
+    conn.transaction(|conn| {
+        let without_number: Vec<i32> = e::employees
+            .filter(e::employee_number.is_null())
+            .order(e::id)
+            .select(e::id)
+            .load(conn)?;
+        for employee_id in &without_number {
+            let number = next_employee_number(format, conn)?;
+            diesel::update(e::employees.filter(e::id.eq(employee_id)))
+                .set(e::employee_number.eq(number))
+                .execute(conn)?;
+        }
+        Ok(without_number.len())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::common_for_tests::*;
+
+    use super::*;
+
+    #[test]
+    fn employee_numbers_follow_format() {
+        let format: EmployeeNumberFormat = "E{year}-{seq:4}".parse().unwrap();
+        assert_eq!(format.format(42, 2021), "E2021-0042");
+        assert_eq!(format.to_string(), "E{year}-{seq:4}");
+        assert_eq!("{seq}".parse::<EmployeeNumberFormat>().unwrap().format(42, 2021), "42");
+        assert_eq!(EmployeeNumberFormat::default().format(7, 2021), "E00007");
+        for invalid in ["E", "E{seq", "E{seq:0}", "E{seq:x}", "{seq}-{seq}"] {
+            assert!(invalid.parse::<EmployeeNumberFormat>().is_err(), "'{}' should be invalid", invalid);
+        }
+
+        let conn = &mut initialize();
+        assert_eq!(next_employee_number(&"N{seq}".parse().unwrap(), conn).unwrap(), "N1");
+        assert_eq!(next_employee_number(&"N{seq}".parse().unwrap(), conn).unwrap(), "N2");
+    }
+}
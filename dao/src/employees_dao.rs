@@ -1,17 +1,142 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+
+use chrono::{Local, NaiveDate, NaiveDateTime};
 use diesel::dsl::*;
 use diesel::prelude::*;
-use diesel::sqlite::SqliteConnection;
 
-use crate::base_dao::{Crud, HaveId, Searchable};
-use crate::contacts_dao::ContactDTO;
-use crate::models::{Contact, Employee, NewEmployee, Salary};
-use crate::salaries_dao::SalaryDTO;
+use crate::base_dao::{
+    stale_version, Crud, HaveId, HaveVersion, Searchable, SearchableByDate, SearchableByParent,
+};
+use crate::connection::{Database, DbConnection};
+use crate::error::{DaoError, DaoResult};
+use crate::hierarchy::{check_parent, descendants, EmployeeScope, Tree};
+use crate::validation::{check_amount, check_no_overlaps, check_period, Errors, ValidationRules};
+use crate::contacts_dao::{
+    check_contact_details, contact_on, contacts_of_many, is_valid_email, seal, unseal, ContactDTO,
+};
+use crate::country::is_e164;
+use crate::crypto::{self, KeyRing};
+use crate::custom_fields_dao::{
+    check_custom_fields, custom_fields_template_with_connection, custom_values_of, custom_values_of_many,
+    delete_custom_values_of, employees_with_custom_value, save_custom_values,
+};
+use crate::employee_number::{next_employee_number, EmployeeNumberFormat};
+use crate::contracts_dao::{check_contract, check_salary_contract, contract_of, ContractDTO};
+use crate::models::{EmergencyContact, Employee, EmploymentContract, NewEmergencyContact, NewEmployee, Salary};
+use crate::schema::custom_field_values::dsl as cv;
+use crate::schema::emergency_contacts::dsl as ec;
+use crate::salaries_dao::{from_rows, salary_on, SalaryDTO};
+use crate::storage::DocumentStorage;
+use crate::photos_dao::PhotoDTO;
+use crate::tags_dao::{employees_tagged, tags_of, tags_of_many};
 use crate::schema::contacts::dsl::contacts;
 use crate::schema::employees::dsl::id as employee_id;
+use crate::schema::employees::dsl::version as employee_version;
 use crate::schema::employees::dsl::*;
 use crate::schema::salaries::dsl::*;
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
+/// Lifecycle of employee - see can_become() for allowed transitions
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum EmploymentStatus {
+    Candidate,
+    #[default]
+    Active,
+    OnLeave,
+    Terminated,
+}
+
+impl EmploymentStatus {
+    /// How it is stored in DB
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EmploymentStatus::Candidate => "candidate",
+            EmploymentStatus::Active => "active",
+            EmploymentStatus::OnLeave => "on_leave",
+            EmploymentStatus::Terminated => "terminated",
+        }
+    }
+
+    /// Candidate is hired (or not), active employee can go on leave and back, anybody but candidate can be
+    /// terminated. Terminated is final - employee hired again is new employee.
+    pub fn can_become(&self, next: EmploymentStatus) -> bool {
+        use EmploymentStatus::*;
+
+        *self == next
+            || matches!(
+                (self, next),
+                (Candidate, Active)
+                    | (Candidate, Terminated)
+                    | (Active, OnLeave)
+                    | (Active, Terminated)
+                    | (OnLeave, Active)
+                    | (OnLeave, Terminated)
+            )
+    }
+}
+
+impl FromStr for EmploymentStatus {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "candidate" => Ok(EmploymentStatus::Candidate),
+            "active" => Ok(EmploymentStatus::Active),
+            "on_leave" => Ok(EmploymentStatus::OnLeave),
+            "terminated" => Ok(EmploymentStatus::Terminated),
+            _ => Err(format!(
+                "unknown employment status '{}' - should be one of candidate, active, on_leave, terminated",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for EmploymentStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Criteria of employee search (`GET /employees?q=&status=...`) - all given have to match
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct EmployeeSearch {
+    /// Part of first or last name, search string or employee number
+    pub q: Option<String>,
+    pub status: Option<EmploymentStatus>,
+    pub born_on: Option<NaiveDate>,
+    pub hired_from: Option<NaiveDate>,
+    pub hired_to: Option<NaiveDate>,
+    /// Whole national ID - it is found by its blind index
+    pub national_id: Option<String>,
+    /// Whole phone number in E.164 (`+48601234567`) from any contact of employee - encrypted phones are found
+    /// by their blind index
+    pub phone: Option<String>,
+    /// Value of custom field as `name:value` - part of value for string fields, whole value for others
+    pub custom_field: Option<String>,
+    /// Employee has to have every tag - except ones starting with `!` it mustn't have (`?tag=remote&tag=!contractor`)
+    #[serde(default)]
+    pub tag: Vec<String>,
+    /// Deleted employees too - just for admins
+    #[serde(default)]
+    pub include_deleted: bool,
+}
+
+/// Person to call when something happens to employee
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct EmergencyContactDTO {
+    pub name: String,
+    /// "wife", "father" ...
+    pub relationship: String,
+    /// E.164 - number without `+` or `00` is normalized as number of DEFAULT_COUNTRY on save
+    pub phone: String,
+    #[serde(default)]
+    pub email: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
 pub struct EmployeeDTO {
     pub id: Option<i32>,
     pub first_name: String,
@@ -19,10 +144,82 @@ pub struct EmployeeDTO {
     pub search_string: String,
     pub salaries: Vec<SalaryDTO>,
     pub contacts: Vec<ContactDTO>,
+    pub version: Option<i32>,
+    #[serde(default)]
+    pub department_id: Option<i32>,
+    /// Employee this one reports to - None for top of organization
+    #[serde(default)]
+    pub manager_id: Option<i32>,
+    /// Employment contracts - salaries are paid under them
+    #[serde(default)]
+    pub contracts: Vec<ContractDTO>,
+    /// Unique - generated in EMPLOYEE_NUMBER_FORMAT when not given
+    #[serde(default)]
+    pub employee_number: Option<String>,
+    #[serde(default)]
+    pub date_of_birth: Option<NaiveDate>,
+    /// PESEL, SSN ... - stored encrypted (ENCRYPTION_KEY)
+    #[serde(default)]
+    pub national_id: Option<String>,
+    #[serde(default)]
+    pub hire_date: Option<NaiveDate>,
+    /// Set just for terminated employee
+    #[serde(default)]
+    pub termination_date: Option<NaiveDate>,
+    #[serde(default)]
+    pub status: EmploymentStatus,
+    #[serde(default)]
+    pub emergency_contacts: Vec<EmergencyContactDTO>,
+    /// When and by whom (user id) employee was deleted - read-only, deleted employee can be just restored
+    #[serde(default)]
+    pub deleted_at: Option<NaiveDateTime>,
+    #[serde(default)]
+    pub deleted_by: Option<i32>,
+    /// When personal data of employee were anonymized - read-only
+    #[serde(default)]
+    pub anonymized_at: Option<NaiveDateTime>,
+    /// Values of custom fields (see CustomFieldDTO) by their name - missing and null is the same
+    #[serde(default)]
+    pub custom_fields: BTreeMap<String, serde_json::Value>,
+    /// Tags ordered by name - read-only, they are changed in bulk (see TagsChange)
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Photo with URLs of its thumbnails - read-only, it is changed by `PUT /employees/{id}/photo`
+    #[serde(default)]
+    pub photo: Option<PhotoDTO>,
 }
 
-impl From<Employee> for EmployeeDTO {
-    fn from(e: Employee) -> Self {
+/// National ID as it is stored and searched - upper case without spaces and dashes
+fn normalize_national_id(national: &str) -> String {
+    national
+        .chars()
+        .filter(|c| !c.is_whitespace() && *c != '-')
+        .flat_map(char::to_uppercase)
+        .collect()
+}
+
+/// Encrypted national ID and its blind index - validate() make sure there are keys to encrypt it
+fn encrypt_national_id(national: Option<&str>, keys: Option<&KeyRing>) -> (Option<String>, Option<String>) {
+    match national {
+        Some(national) => {
+            let keys = keys.expect("encryption key is checked by validate()");
+            (
+                Some(keys.encrypt(national).expect("AES-GCM encryption doesn't fail")),
+                Some(keys.blind_index(national).expect("HMAC accepts key of any size")),
+            )
+        }
+        None => (None, None),
+    }
+}
+
+impl EmployeeDTO {
+    /// Employee without associations - national ID which can't be decrypted (no or other key) is left out
+    fn from_row(e: Employee, keys: Option<&KeyRing>) -> Self {
+        let national = e.national_id.and_then(|encrypted| {
+            crypto::decrypt(keys, &encrypted)
+                .map_err(|err| warn!("National ID of employee id = {} is not readable: {}", e.id, err))
+                .ok()
+        });
         EmployeeDTO {
             id: Some(e.id),
             first_name: e.first_name,
@@ -30,27 +227,65 @@ impl From<Employee> for EmployeeDTO {
             search_string: e.search_string,
             salaries: Default::default(),
             contacts: Default::default(),
+            version: Some(e.version),
+            department_id: e.department_id,
+            manager_id: e.manager_id,
+            contracts: Default::default(),
+            employee_number: e.employee_number,
+            date_of_birth: e.date_of_birth,
+            national_id: national,
+            hire_date: e.hire_date,
+            termination_date: e.termination_date,
+            status: e.status.parse().expect("status is checked by DB"),
+            emergency_contacts: Default::default(),
+            deleted_at: e.deleted_at,
+            deleted_by: e.deleted_by,
+            anonymized_at: e.anonymized_at,
+            custom_fields: Default::default(),
+            tags: Default::default(),
+            photo: None,
         }
     }
-}
 
-impl From<&EmployeeDTO> for Employee {
-    fn from(employee_dto: &EmployeeDTO) -> Self {
+    /// Row of employee - its national ID is encrypted by `keys`
+    fn to_row(&self, keys: Option<&KeyRing>) -> Employee {
+        let (encrypted, index) = encrypt_national_id(self.national_id.as_deref(), keys);
         Employee {
-            id: employee_dto.id.unwrap(),
-            first_name: employee_dto.first_name.clone(),
-            last_name: employee_dto.last_name.clone(),
-            search_string: employee_dto.search_string.clone(),
+            id: self.id.unwrap(),
+            first_name: self.first_name.clone(),
+            last_name: self.last_name.clone(),
+            search_string: self.search_string.clone(),
+            version: self.version.unwrap_or_default(),
+            department_id: self.department_id,
+            manager_id: self.manager_id,
+            employee_number: self.employee_number.clone(),
+            date_of_birth: self.date_of_birth,
+            national_id: encrypted,
+            national_id_hash: index,
+            hire_date: self.hire_date,
+            termination_date: self.termination_date,
+            status: self.status.as_str().to_string(),
+            deleted_at: self.deleted_at,
+            deleted_by: self.deleted_by,
+            anonymized_at: self.anonymized_at,
         }
     }
-}
 
-impl From<&EmployeeDTO> for NewEmployee {
-    fn from(employee_dto: &EmployeeDTO) -> Self {
+    fn to_new_row(&self, keys: Option<&KeyRing>) -> NewEmployee {
+        let (encrypted, index) = encrypt_national_id(self.national_id.as_deref(), keys);
         NewEmployee {
-            first_name: employee_dto.first_name.clone(),
-            last_name: employee_dto.last_name.clone(),
-            search_string: employee_dto.search_string.clone(),
+            first_name: self.first_name.clone(),
+            last_name: self.last_name.clone(),
+            search_string: self.search_string.clone(),
+            department_id: self.department_id,
+            manager_id: self.manager_id,
+            employee_number: self.employee_number.clone(),
+            date_of_birth: self.date_of_birth,
+            national_id: encrypted,
+            national_id_hash: index,
+            hire_date: self.hire_date,
+            termination_date: self.termination_date,
+            status: self.status.as_str().to_string(),
         }
     }
 }
@@ -61,132 +296,928 @@ impl HaveId for EmployeeDTO {
     }
 }
 
-fn delete_associations(e_id: i32, conn: &mut SqliteConnection) -> QueryResult<usize> {
-    use crate::schema::contacts::columns::employee_id as contacts_employee_id;
+impl HaveVersion for EmployeeDTO {
+    fn get_version(&self) -> Option<i32> {
+        self.version
+    }
+}
+
+/// Ids of employees which are not deleted
+type NotDeleted = Select<Filter<employees, IsNull<deleted_at>>, employee_id>;
+
+/// Records of deleted employees (salaries, contacts ...) are left out of searches by `employee_id.eq_any(not_deleted())`
+pub(crate) fn not_deleted() -> NotDeleted {
+    employees.filter(deleted_at.is_null()).select(employee_id)
+}
+
+/// Mark employee as deleted - nothing else changes, so restore_with_connection() undoes it. Its reports keep
+/// it as manager_id and users stay linked to it, but it is left out of organization chart and scopes (see
+/// hierarchy) and nobody can log in as it (see validate_user()).
+fn soft_delete(id_to_find: i32, by: Option<i32>, conn: &mut DbConnection) -> QueryResult<usize> {
+    diesel::update(employees.filter(employee_id.eq(id_to_find)).filter(deleted_at.is_null()))
+        .set((
+            deleted_at.eq(Some(Local::now().naive_local())),
+            deleted_by.eq(by),
+            employee_version.eq(employee_version + 1),
+        ))
+        .execute(conn)
+}
+
+/// Before employee is deleted for good its reports are moved to its manager and users linked to it are unlinked
+fn detach(e_id: i32, conn: &mut DbConnection) -> QueryResult<()> {
+    use crate::schema::users::dsl as u;
+
+    let manager = employees
+        .filter(employee_id.eq(e_id))
+        .select(manager_id)
+        .first::<Option<i32>>(conn)?;
+    diesel::update(employees.filter(manager_id.eq(e_id)))
+        .set((manager_id.eq(manager), employee_version.eq(employee_version + 1)))
+        .execute(conn)?;
+    diesel::update(u::users.filter(u::employee_id.eq(e_id)))
+        .set((u::employee_id.eq(None::<i32>), u::version.eq(u::version + 1)))
+        .execute(conn)?;
+    Ok(())
+}
+
+/// Bump version of not deleted employee - it check version and lock the row before it is deleted
+fn lock_version(id_to_find: i32, version_to_find: i32, conn: &mut DbConnection) -> QueryResult<usize> {
+    diesel::update(
+        employees
+            .filter(employee_id.eq(id_to_find))
+            .filter(employee_version.eq(version_to_find))
+            .filter(deleted_at.is_null()),
+    )
+    .set(employee_version.eq(employee_version + 1))
+    .execute(conn)
+}
+
+fn delete_associations(e_id: i32, conn: &mut DbConnection) -> QueryResult<usize> {
+    use crate::schema::employment_contracts::dsl as c;
     use crate::schema::salaries::columns::employee_id as salaries_employee_id;
 
+    crate::absences_dao::delete_absences_of(e_id, conn)?;
+    crate::timesheets_dao::delete_timesheet_of(e_id, conn)?;
     diesel::delete(salaries)
         .filter(salaries_employee_id.eq(e_id))
         .execute(conn)?;
-    diesel::delete(contacts)
-        .filter(contacts_employee_id.eq(e_id))
-        .execute(conn)
+    diesel::delete(c::employment_contracts)
+        .filter(c::employee_id.eq(e_id))
+        .execute(conn)?;
+    diesel::delete(ec::emergency_contacts)
+        .filter(ec::employee_id.eq(e_id))
+        .execute(conn)?;
+    delete_custom_values_of(e_id, conn)?;
+    crate::tags_dao::delete_tags_of(e_id, conn)?;
+    crate::contacts_dao::delete_contacts_of(e_id, conn)
+}
+
+/// Emergency contacts of employees (in order they were given) - grouped as `found`, phones are decrypted
+fn emergency_contacts_of(
+    found: &[Employee],
+    keys: Option<&KeyRing>,
+    conn: &mut DbConnection,
+) -> QueryResult<Vec<Vec<EmergencyContactDTO>>> {
+    EmergencyContact::belonging_to(found)
+        .order(ec::position)
+        .load::<EmergencyContact>(conn)?
+        .grouped_by(found)
+        .into_iter()
+        .map(|group| {
+            group
+                .into_iter()
+                .map(|c| {
+                    Ok(EmergencyContactDTO {
+                        phone: unseal(c.id, "Phone of emergency contact", c.phone, c.encrypted, keys)?,
+                        name: c.name,
+                        relationship: c.relationship,
+                        email: c.email,
+                    })
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// Emergency contacts are replaced as a whole - they have no identity of their own
+fn save_emergency_contacts(
+    e_id: i32,
+    to_save: &[EmergencyContactDTO],
+    keys: Option<&KeyRing>,
+    conn: &mut DbConnection,
+) -> QueryResult<()> {
+    diesel::delete(ec::emergency_contacts)
+        .filter(ec::employee_id.eq(e_id))
+        .execute(conn)?;
+    let new_contacts: Vec<NewEmergencyContact> = to_save
+        .iter()
+        .enumerate()
+        .map(|(i, c)| NewEmergencyContact {
+            employee_id: e_id,
+            position: i as i32,
+            name: c.name.clone(),
+            relationship: c.relationship.clone(),
+            phone: seal(&c.phone, keys),
+            email: c.email.clone(),
+            encrypted: keys.is_some(),
+        })
+        .collect();
+    insert_into(ec::emergency_contacts)
+        .values(&new_contacts)
+        .execute(conn)?;
+    Ok(())
+}
+
+/// Employee number of saved employee stays - new one get next number in `format`
+fn assign_employee_number(
+    e_dto: &EmployeeDTO,
+    format: &EmployeeNumberFormat,
+    conn: &mut DbConnection,
+) -> QueryResult<String> {
+    if let Some(number) = &e_dto.employee_number {
+        return Ok(number.clone());
+    }
+    let current = match e_dto.id {
+        Some(self_id) => employees
+            .filter(employee_id.eq(self_id))
+            .filter(deleted_at.is_null())
+            .select(employee_number)
+            .first::<Option<String>>(conn)
+            .optional()?
+            .flatten(),
+        None => None,
+    };
+    match current {
+        Some(number) => Ok(number),
+        None => next_employee_number(format, conn),
+    }
+}
+
+/// Employee number is unique, status changes just as allowed by EmploymentStatus::can_become(),
+/// dates make sense together and national ID can be encrypted
+fn check_profile(
+    e_dto: &EmployeeDTO,
+    errors: &mut Errors,
+    keys: Option<&KeyRing>,
+    conn: &mut DbConnection,
+) -> QueryResult<()> {
+    if let Some(number) = &e_dto.employee_number {
+        if number.trim().is_empty() {
+            errors.add("", "employee_number", "can't be empty".to_string());
+        }
+        let same_number = employees
+            .filter(employee_number.eq(number))
+            .select(employee_id)
+            .first::<i32>(conn)
+            .optional()?;
+        if let Some(other) = same_number
+            && Some(other) != e_dto.id
+        {
+            errors.add(
+                "",
+                "employee_number",
+                format!("'{}' is already number of employee id = {}", number, other),
+            );
+        }
+    }
+    if let Some(self_id) = e_dto.id {
+        let current = employees
+            .filter(employee_id.eq(self_id))
+            .select(status)
+            .first::<String>(conn)
+            .optional()?
+            .map(|s| s.parse::<EmploymentStatus>().expect("status is checked by DB"));
+        if let Some(current) = current
+            && !current.can_become(e_dto.status)
+        {
+            errors.add("", "status", format!("can't change from {} to {}", current, e_dto.status));
+        }
+    }
+    match (e_dto.status, e_dto.termination_date) {
+        (EmploymentStatus::Terminated, None) => {
+            errors.add("", "termination_date", "is required for terminated employee".to_string());
+        }
+        (EmploymentStatus::Terminated, _) | (_, None) => {}
+        (_, Some(_)) => {
+            errors.add("", "termination_date", "can be set just for terminated employee".to_string());
+        }
+    }
+    if let (Some(hired), Some(terminated)) = (e_dto.hire_date, e_dto.termination_date)
+        && terminated < hired
+    {
+        errors.add("", "termination_date", format!("can't be before hire_date {}", hired));
+    }
+    if let Some(born) = e_dto.date_of_birth {
+        if born > Local::now().date_naive() {
+            errors.add("", "date_of_birth", "can't be in the future".to_string());
+        }
+        if let Some(hired) = e_dto.hire_date
+            && hired <= born
+        {
+            errors.add("", "hire_date", format!("has to be after date_of_birth {}", born));
+        }
+    }
+    if let Some(national) = &e_dto.national_id {
+        if national.is_empty() {
+            errors.add("", "national_id", "can't be empty".to_string());
+        } else if keys.is_none() {
+            errors.add("", "national_id", "can't be stored - ENCRYPTION_KEY is not configured".to_string());
+        }
+    }
+    for (i, c) in e_dto.emergency_contacts.iter().enumerate() {
+        let prefix = format!("emergency_contacts[{}].", i);
+        if c.name.trim().is_empty() {
+            errors.add(&prefix, "name", "can't be empty".to_string());
+        }
+        if c.relationship.trim().is_empty() {
+            errors.add(&prefix, "relationship", "can't be empty".to_string());
+        }
+        if !is_e164(&c.phone) {
+            errors.add(&prefix, "phone", format!("'{}' is not valid phone number", c.phone));
+        }
+        if let Some(email) = &c.email
+            && !is_valid_email(email)
+        {
+            errors.add(&prefix, "email", format!("'{}' is not valid email", email));
+        }
+    }
+    Ok(())
+}
+
+/// Bring associations (salaries or contacts) of employee in line with `to_save`: records which
+/// already belong to the employee are updated (with version check), the rest is inserted
+/// and records missing in `to_save` are deleted
+fn save_associations<T>(e_id: i32, to_save: &[T], keys: Option<&KeyRing>, conn: &mut DbConnection) -> DaoResult<Vec<T>>
+where
+    T: Crud + SearchableByParent + Clone,
+    T: AssociatedWithEmployee,
+{
+    let existing: Vec<i32> = T::search_by_parent_id_with_connection(e_id, keys, conn)?
+        .iter()
+        .filter_map(HaveId::get_id)
+        .collect();
+    let mut saved = Vec::with_capacity(to_save.len());
+    for a in to_save {
+        let mut a = a.clone();
+        a.set_employee_id(e_id);
+        if !a.get_id().is_some_and(|a_id| existing.contains(&a_id)) {
+            a.set_new();
+        }
+        saved.push(a.save_simple(keys, conn)?);
+    }
+    for a_id in existing {
+        if !saved.iter().any(|a| a.get_id() == Some(a_id)) {
+            T::delete_simple(a_id, conn)?;
+        }
+    }
+    Ok(saved)
+}
+
+/// Salaries, contacts and contracts belong to employee
+trait AssociatedWithEmployee {
+    fn set_employee_id(&mut self, e_id: i32);
+    /// Forget id and version so record is inserted as new one
+    fn set_new(&mut self);
+}
+
+impl AssociatedWithEmployee for SalaryDTO {
+    fn set_employee_id(&mut self, e_id: i32) {
+        self.employee_id = Some(e_id);
+    }
+
+    fn set_new(&mut self) {
+        self.id = None;
+        self.version = None;
+    }
+}
+
+impl AssociatedWithEmployee for ContractDTO {
+    fn set_employee_id(&mut self, e_id: i32) {
+        self.employee_id = Some(e_id);
+    }
+
+    fn set_new(&mut self) {
+        self.id = None;
+        self.version = None;
+    }
+}
+
+impl AssociatedWithEmployee for ContactDTO {
+    fn set_employee_id(&mut self, e_id: i32) {
+        self.employee_id = Some(e_id);
+    }
+
+    fn set_new(&mut self) {
+        self.id = None;
+        self.version = None;
+    }
 }
 
 impl Crud for EmployeeDTO {
     fn update(&mut self, persisted: &Self) {
         self.id = persisted.id;
+        self.version = persisted.version;
         self.salaries = persisted.salaries.clone();
         self.contacts = persisted.contacts.clone();
+        self.contracts = persisted.contracts.clone();
     }
 
-    fn get_simple(id_to_find: i32, conn: &mut SqliteConnection) -> QueryResult<Self> {
+    /// Salaries, contacts and contracts are validated as they are in DTO - they replace saved ones.
+    /// Department and manager have to exist and employee can't (even indirectly) report to itself.
+    /// Salaries have to be within contracts (when there are any). Profile is checked by check_profile() and
+    /// custom fields by check_custom_fields().
+    fn validate(&self, rules: &ValidationRules, keys: Option<&KeyRing>, conn: &mut DbConnection) -> DaoResult<()> {
+        let mut errors = Errors::default();
+        check_profile(self, &mut errors, keys, conn)?;
+        check_custom_fields(&self.custom_fields, &mut errors, conn)?;
+        check_parent(Tree::Departments, None, self.department_id, "department_id", &mut errors, conn)?;
+        check_parent(Tree::Employees, self.id, self.manager_id, "manager_id", &mut errors, conn)?;
+        for (i, s) in self.salaries.iter().enumerate() {
+            check_period(s, &format!("salaries[{}].", i), &mut errors);
+            check_amount(&s.amount, &format!("salaries[{}].", i), &mut errors);
+            check_salary_contract(s, &self.contracts, &format!("salaries[{}].", i), &mut errors);
+        }
+        for (i, c) in self.contracts.iter().enumerate() {
+            check_contract(c, &format!("contracts[{}].", i), &mut errors, conn)?;
+        }
+        for (i, c) in self.contacts.iter().enumerate() {
+            check_period(c, &format!("contacts[{}].", i), &mut errors);
+            check_contact_details(c, &format!("contacts[{}].", i), &mut errors);
+        }
+        check_no_overlaps(&self.salaries, "salary", "salaries", &mut errors);
+        check_no_overlaps(&self.contracts, "contract", "contracts", &mut errors);
+        if !rules.allow_overlapping_contacts {
+            check_no_overlaps(&self.contacts, "contact", "contacts", &mut errors);
+        }
+        errors.into_result()
+    }
+
+    /// Contacts are normalized (see ContactDTO::normalized()) and so are phones of emergency contacts and
+    /// national ID before they are validated. New employee get number in EMPLOYEE_NUMBER_FORMAT.
+    fn try_save_in_transaction(
+        &self,
+        rules: &ValidationRules,
+        keys: Option<&KeyRing>,
+        conn: &mut DbConnection,
+    ) -> DaoResult<Self> {
+        let mut normalized = EmployeeDTO {
+            contacts: self
+                .contacts
+                .iter()
+                .map(|c| c.normalized(rules.default_country))
+                .collect(),
+            national_id: self.national_id.as_deref().map(normalize_national_id),
+            emergency_contacts: self
+                .emergency_contacts
+                .iter()
+                .map(|c| EmergencyContactDTO {
+                    phone: rules
+                        .default_country
+                        .normalize_phone(&c.phone)
+                        .unwrap_or_else(|_| c.phone.clone()),
+                    email: c.email.as_ref().map(|e| e.trim().to_string()),
+                    ..c.clone()
+                })
+                .collect(),
+            ..self.clone()
+        };
+        conn.transaction(|conn| {
+            normalized.employee_number = Some(assign_employee_number(
+                &normalized,
+                &rules.employee_number_format,
+                conn,
+            )?);
+            normalized.validate(rules, keys, conn)?;
+            normalized.save_simple(keys, conn)
+        })
+    }
+
+    /// Deleted employee is not found - see get_including_deleted_with_connection()
+    fn get_simple(id_to_find: i32, keys: Option<&KeyRing>, conn: &mut DbConnection) -> QueryResult<Self> {
         employees
             .filter(employee_id.eq(id_to_find))
+            .filter(deleted_at.is_null())
             .first(conn)
-            .map(|e: Employee| into_dto_with_associations(e, conn))
+            .and_then(|e: Employee| into_dto_with_associations(e, keys, conn))
     }
 
-    fn save_simple(&self, conn: &mut SqliteConnection) -> QueryResult<Self> {
-        let employee_to_dto_with_associations = |e: Employee,
-                                                 salaries_to_save: &Vec<SalaryDTO>,
-                                                 contacts_to_save: &Vec<ContactDTO>,
-                                                 conn: &mut SqliteConnection|
-         -> EmployeeDTO {
-            let e_id = e.id;
-            let mut e_dto = EmployeeDTO::from(e);
-            for s in salaries_to_save {
-                let mut new_s = s.clone();
-                new_s.employee_id = Some(e_id);
-                e_dto.salaries.push(new_s.save_simple(conn).unwrap());
-            }
-            for c in contacts_to_save {
-                let mut new_c = c.clone();
-                new_c.employee_id = Some(e_id);
-                e_dto.contacts.push(new_c.save_simple(conn).unwrap());
+    fn save_simple(&self, keys: Option<&KeyRing>, conn: &mut DbConnection) -> DaoResult<Self> {
+        fn insert(e_dto: &EmployeeDTO, keys: Option<&KeyRing>, conn: &mut DbConnection) -> QueryResult<Employee> {
+            insert_into(employees)
+                .values(e_dto.to_new_row(keys))
+                .get_result(conn)
+        }
+
+        // Saved without try_save_in_transaction() - number in default format
+        if self.employee_number.is_none() {
+            let numbered = EmployeeDTO {
+                employee_number: Some(assign_employee_number(self, &Default::default(), conn)?),
+                ..self.clone()
+            };
+            return numbered.save_simple(keys, conn);
+        }
+        // Employee row is updated (and its version incremented) on every save - even when
+        // just salaries or contacts changed - so its version cover whole EmployeeDTO.
+        // Deleted employee has to be restored (see restore_with_connection()) before it is saved.
+        let e = if let Some(self_id) = self.id {
+            let updated = match self.version {
+                Some(self_version) => diesel::update(
+                    employees
+                        .filter(employee_id.eq(self_id))
+                        .filter(employee_version.eq(self_version))
+                        .filter(deleted_at.is_null()),
+                )
+                .set((self.to_row(keys), employee_version.eq(employee_version + 1)))
+                .execute(conn)?,
+                None => 0,
+            };
+            if updated == 0 {
+                let current = employees
+                    .filter(employee_id.eq(self_id))
+                    .select((employee_version, deleted_at))
+                    .first::<(i32, Option<NaiveDateTime>)>(conn)
+                    .optional()?;
+                match current {
+                    Some((current, None)) => return Err(stale_version(self_id, Some(current))),
+                    Some((_, Some(_))) => return Err(DaoError::not_found()),
+                    None => insert(self, keys, conn)?,
+                }
+            } else {
+                employees.filter(employee_id.eq(self_id)).first(conn)?
             }
-            e_dto
+        } else {
+            insert(self, keys, conn)?
         };
-        let insert =
-            |e_dto: &EmployeeDTO, conn: &mut SqliteConnection| -> QueryResult<EmployeeDTO> {
-                insert_into(employees)
-                    .values(NewEmployee::from(e_dto))
-                    .execute(conn)
-                    .and_then(|_| {
-                        employees
-                            .order(employee_id.desc())
-                            .first(conn)
-                            .map(|e: Employee| {
-                                employee_to_dto_with_associations(e, &self.salaries, &self.contacts, conn)
-                            })
-                    })
-            };
+        let e_id = e.id;
+        let mut e_dto = EmployeeDTO::from_row(e, keys);
+        // Salaries are unlinked so contracts missing in DTO can be deleted - they are linked again
+        // to contracts they are (by id or period) paid under
+        {
+            use crate::schema::salaries::columns::employee_id as salaries_employee_id;
 
-        if self.id.is_some() {
-            let self_id = self.id.unwrap();
-            diesel::update(employees.filter(employee_id.eq(self_id)))
-                .set(Employee::from(self))
-                .execute(conn)
-                .and_then(|updated| {
-                    if updated == 0 {
-                        insert(self, conn)
-                    } else {
-                        employees
-                            .filter(employee_id.eq(self_id))
-                            .first(conn)
-                            .map(|e: Employee| {
-                                let _ = delete_associations(e.id, conn);
-                                employee_to_dto_with_associations(e, &self.salaries, &self.contacts, conn)
-                            })
-                    }
-                })
-        } else {
-            insert(self, conn)
+            diesel::update(salaries.filter(salaries_employee_id.eq(e_id)))
+                .set(contract_id.eq(None::<i32>))
+                .execute(conn)?;
         }
+        e_dto.contracts = save_associations(e_id, &self.contracts, keys, conn)?;
+        let linked: Vec<SalaryDTO> = self
+            .salaries
+            .iter()
+            .map(|s| SalaryDTO {
+                contract_id: contract_of(s, &self.contracts).and_then(|i| e_dto.contracts[i].id),
+                ..s.clone()
+            })
+            .collect();
+        e_dto.salaries = save_associations(e_id, &linked, keys, conn)?;
+        e_dto.contacts = save_associations(e_id, &self.contacts, keys, conn)?;
+        save_emergency_contacts(e_id, &self.emergency_contacts, keys, conn)?;
+        e_dto.emergency_contacts = self.emergency_contacts.clone();
+        save_custom_values(e_id, &self.custom_fields, conn)?;
+        e_dto.custom_fields = custom_values_of(e_id, conn)?;
+        e_dto.tags = tags_of(e_id, conn)?;
+        e_dto.photo = PhotoDTO::of_employee_with_connection(e_id, conn)?;
+        Ok(e_dto)
     }
 
-    fn delete_simple(id_to_find: i32, conn: &mut SqliteConnection) -> QueryResult<usize> {
-        let _ = delete_associations(id_to_find, conn);
-        diesel::delete(employees)
-            .filter(employee_id.eq(id_to_find))
-            .execute(conn)
+    /// Soft delete - employee is kept (see soft_delete()) till purge_deleted_before_with_connection()
+    fn delete_simple(id_to_find: i32, conn: &mut DbConnection) -> QueryResult<usize> {
+        soft_delete(id_to_find, None, conn)
+    }
+
+    fn delete_versioned_simple(
+        id_to_find: i32,
+        version_to_find: i32,
+        conn: &mut DbConnection,
+    ) -> QueryResult<usize> {
+        if lock_version(id_to_find, version_to_find, conn)? == 0 {
+            return Ok(0);
+        }
+        soft_delete(id_to_find, None, conn)
     }
 }
 
 impl Searchable for EmployeeDTO {
-    fn get_all_with_connection(conn: &mut SqliteConnection) -> Vec<Self> {
-        employees
-            .load::<Employee>(conn)
-            .expect("Load employees failed")
+    fn get_all_with_connection(keys: Option<&KeyRing>, conn: &mut DbConnection) -> DaoResult<Vec<Self>> {
+        let found = employees.filter(deleted_at.is_null()).load::<Employee>(conn)?;
+        Ok(with_associations(found, keys, conn)?)
+    }
+
+    /// Match search string or employee number
+    fn search_with_connection(s: &str, keys: Option<&KeyRing>, conn: &mut DbConnection) -> DaoResult<Vec<Self>> {
+        use crate::schema::employees::columns::search_string;
+
+        Ok(employees
+            .filter(search_string.like(s).or(employee_number.like(s)))
+            .filter(deleted_at.is_null())
+            .load::<Employee>(conn)?
             .into_iter()
-            .map(|e| into_dto_with_associations(e, conn))
-            .collect()
+            .map(|e| Self::from_row(e, keys))
+            .collect())
+    }
+}
+
+impl EmployeeDTO {
+    /// New employee to be filled in - with every custom field (default value of required ones, null of others)
+    pub fn template(db: &Database) -> DaoResult<Self> {
+        let conn = &mut db.try_get_connection()?;
+        Self::template_with_connection(conn)
+    }
+
+    pub fn template_with_connection(conn: &mut DbConnection) -> DaoResult<Self> {
+        Ok(EmployeeDTO {
+            custom_fields: custom_fields_template_with_connection(conn)?,
+            ..Default::default()
+        })
+    }
+
+    /// Employee with just salary and contact valid on given date (if any)
+    pub fn get_effective_on(db: &Database, id_to_find: i32, date: NaiveDate) -> DaoResult<Option<Self>> {
+        let conn = &mut db.try_get_connection()?;
+        Self::get_effective_on_with_conn(id_to_find, date, db.keys().as_deref(), conn)
+    }
+
+    pub fn get_effective_on_with_conn(
+        id_to_find: i32,
+        date: NaiveDate,
+        keys: Option<&KeyRing>,
+        conn: &mut DbConnection,
+    ) -> DaoResult<Option<Self>> {
+        let e: Employee = match employees
+            .filter(employee_id.eq(id_to_find))
+            .filter(deleted_at.is_null())
+            .first(conn)
+            .optional()?
+        {
+            Some(e) => e,
+            None => return Ok(None),
+        };
+        let mut e_dto = EmployeeDTO::from_row(e.clone(), keys);
+        e_dto.salaries = salary_on(id_to_find, date, keys, conn)?.into_iter().collect();
+        e_dto.contacts = contact_on(id_to_find, date, keys, conn)?.into_iter().collect();
+        e_dto.contracts = ContractDTO::effective_on_with_connection(id_to_find, date, keys, conn)?
+            .into_iter()
+            .collect();
+        e_dto.emergency_contacts = emergency_contacts_of(&[e], keys, conn)?.remove(0);
+        e_dto.custom_fields = custom_values_of(id_to_find, conn)?;
+        e_dto.tags = tags_of(id_to_find, conn)?;
+        e_dto.photo = PhotoDTO::of_employee_with_connection(id_to_find, conn)?;
+        Ok(Some(e_dto))
+    }
+
+    /// Employees in scope matching all criteria of `search` - deleted ones just when `search.include_deleted`
+    pub fn search_in_scope(db: &Database, search: &EmployeeSearch, scope: EmployeeScope) -> DaoResult<Vec<Self>> {
+        let conn = &mut db.try_get_connection()?;
+        Self::search_in_scope_with_connection(search, scope, db.keys().as_deref(), conn)
     }
 
-    fn search_with_connection(s: &str, conn: &mut SqliteConnection) -> Vec<Self> {
+    pub fn search_in_scope_with_connection(
+        search: &EmployeeSearch,
+        scope: EmployeeScope,
+        keys: Option<&KeyRing>,
+        conn: &mut DbConnection,
+    ) -> DaoResult<Vec<Self>> {
+        let found = Self::search_ids_in_scope_with_connection(search, scope, keys, conn)?;
+        Self::get_many_with_connection(&found, keys, conn)
+    }
+
+    /// Employees (ordered by id) with given ids - used to read big results in batches of ids
+    pub fn get_many_with_connection(
+        ids: &[i32],
+        keys: Option<&KeyRing>,
+        conn: &mut DbConnection,
+    ) -> DaoResult<Vec<Self>> {
+        Ok(employees
+            .filter(employee_id.eq_any(ids))
+            .order(employee_id)
+            .load::<Employee>(conn)
+            .and_then(|found| with_associations(found, keys, conn))?)
+    }
+
+    /// Ids (ordered) of search_in_scope_with_connection()
+    pub fn search_ids_in_scope_with_connection(
+        search: &EmployeeSearch,
+        scope: EmployeeScope,
+        keys: Option<&KeyRing>,
+        conn: &mut DbConnection,
+    ) -> DaoResult<Vec<i32>> {
         use crate::schema::employees::columns::search_string;
 
-        employees
-            .filter(search_string.like(s))
+        let mut query = employees.order(employee_id).into_boxed();
+        if let Some(ids) = scope.employee_ids(conn)? {
+            query = query.filter(employee_id.eq_any(ids));
+        }
+        if !search.include_deleted {
+            query = query.filter(deleted_at.is_null());
+        }
+        if let Some(text) = &search.q {
+            let pattern = format!("%{}%", text);
+            query = query.filter(
+                search_string
+                    .like(pattern.clone())
+                    .or(first_name.like(pattern.clone()))
+                    .or(last_name.like(pattern.clone()))
+                    .or(employee_number.like(pattern.clone()))
+                    .or(employee_id.eq_any(
+                        cv::custom_field_values
+                            .filter(cv::value.like(pattern))
+                            .select(cv::employee_id),
+                    )),
+            );
+        }
+        if let Some(s) = search.status {
+            query = query.filter(status.eq(s.as_str()));
+        }
+        if let Some(born) = search.born_on {
+            query = query.filter(date_of_birth.eq(born));
+        }
+        if let Some(from) = search.hired_from {
+            query = query.filter(hire_date.ge(from));
+        }
+        if let Some(to) = search.hired_to {
+            query = query.filter(hire_date.le(to));
+        }
+        if let Some(national) = &search.national_id {
+            let indexes = match keys {
+                Some(keys) => keys
+                    .blind_indexes(&normalize_national_id(national))
+                    .expect("HMAC accepts key of any size"),
+                None => {
+                    let mut errors = Errors::default();
+                    errors.add("", "national_id", "ENCRYPTION_KEY is not configured".to_string());
+                    return Err(errors.into_result().unwrap_err());
+                }
+            };
+            query = query.filter(national_id_hash.eq_any(indexes));
+        }
+        if let Some(number) = &search.phone {
+            let number: String = number.chars().filter(|c| !c.is_whitespace()).collect();
+            query = query.filter(employee_id.eq_any(crate::contacts_dao::employees_with_phone(&number, keys, conn)?));
+        }
+        if let Some(filter) = &search.custom_field {
+            query = query.filter(employee_id.eq_any(employees_with_custom_value(filter, conn)?));
+        }
+        for tag in &search.tag {
+            query = match tag.trim().strip_prefix('!') {
+                Some(excluded) => query.filter(employee_id.ne_all(employees_tagged(excluded.trim(), conn)?)),
+                None => query.filter(employee_id.eq_any(employees_tagged(tag.trim(), conn)?)),
+            };
+        }
+        Ok(query.select(employee_id).load(conn)?)
+    }
+
+    /// Employees in scope
+    pub fn get_all_in_scope(db: &Database, scope: EmployeeScope) -> DaoResult<Vec<Self>> {
+        let conn = &mut db.try_get_connection()?;
+        Self::get_all_in_scope_with_connection(scope, db.keys().as_deref(), conn)
+    }
+
+    pub fn get_all_in_scope_with_connection(
+        scope: EmployeeScope,
+        keys: Option<&KeyRing>,
+        conn: &mut DbConnection,
+    ) -> DaoResult<Vec<Self>> {
+        let mut query = employees.filter(deleted_at.is_null()).order(employee_id).into_boxed();
+        if let Some(ids) = scope.employee_ids(conn)? {
+            query = query.filter(employee_id.eq_any(ids));
+        }
+        Ok(query
             .load::<Employee>(conn)
-            .expect("Search employees failed ")
-            .into_iter()
-            .map(Self::from)
-            .collect()
+            .and_then(|found| with_associations(found, keys, conn))?)
+    }
+
+    /// All employees reporting to manager - directly or through other managers (without the manager)
+    pub fn reports_of(db: &Database, manager: i32) -> DaoResult<Vec<Self>> {
+        let conn = &mut db.try_get_connection()?;
+        Self::reports_of_with_connection(manager, db.keys().as_deref(), conn)
+    }
+
+    pub fn reports_of_with_connection(
+        manager: i32,
+        keys: Option<&KeyRing>,
+        conn: &mut DbConnection,
+    ) -> DaoResult<Vec<Self>> {
+        let ids = descendants(Tree::Employees, manager, conn)?;
+        Ok(employees
+            .filter(employee_id.eq_any(ids))
+            .filter(employee_id.ne(manager))
+            .filter(deleted_at.is_null())
+            .order(employee_id)
+            .load::<Employee>(conn)
+            .and_then(|found| with_associations(found, keys, conn))?)
+    }
+
+    /// Employees in scope which have no contact valid on given date
+    pub fn without_contact_on(db: &Database, date: NaiveDate, scope: EmployeeScope) -> DaoResult<Vec<Self>> {
+        let conn = &mut db.try_get_connection()?;
+        Self::without_contact_on_with_connection(date, scope, db.keys().as_deref(), conn)
+    }
+
+    pub fn without_contact_on_with_connection(
+        date: NaiveDate,
+        scope: EmployeeScope,
+        keys: Option<&KeyRing>,
+        conn: &mut DbConnection,
+    ) -> DaoResult<Vec<Self>> {
+        use crate::schema::contacts::columns::employee_id as contacts_employee_id;
+        use crate::schema::contacts::columns::from_date as contacts_from_date;
+        use crate::schema::contacts::columns::to_date as contacts_to_date;
+
+        let valid_contacts = contacts
+            .filter(contacts_employee_id.eq(employee_id))
+            .filter(contacts_from_date.le(date))
+            .filter(contacts_to_date.is_null().or(contacts_to_date.ge(date)));
+        let mut query = employees
+            .filter(not(exists(valid_contacts)))
+            .filter(deleted_at.is_null())
+            .order(employee_id)
+            .into_boxed();
+        if let Some(ids) = scope.employee_ids(conn)? {
+            query = query.filter(employee_id.eq_any(ids));
+        }
+        Ok(query
+            .load::<Employee>(conn)
+            .and_then(|found| with_associations(found, keys, conn))?)
+    }
+
+    /// Employee even when it is deleted
+    pub fn get_including_deleted_with_connection(
+        id_to_find: i32,
+        keys: Option<&KeyRing>,
+        conn: &mut DbConnection,
+    ) -> QueryResult<Self> {
+        employees
+            .filter(employee_id.eq(id_to_find))
+            .first(conn)
+            .and_then(|e: Employee| into_dto_with_associations(e, keys, conn))
+    }
+
+    /// Soft delete (see Crud::delete_simple()) which record deleting user - DaoError::StaleVersion when
+    /// employee is not in `expected_version`
+    pub fn delete_by_with_connection(
+        id_to_find: i32,
+        expected_version: i32,
+        user_id: i32,
+        keys: Option<&KeyRing>,
+        conn: &mut DbConnection,
+    ) -> DaoResult<usize> {
+        conn.transaction(|conn| {
+            if lock_version(id_to_find, expected_version, conn)? == 0 {
+                let current = Self::get_simple(id_to_find, keys, conn)?;
+                return Err(stale_version(id_to_find, current.version));
+            }
+            Ok(soft_delete(id_to_find, Some(user_id), conn)?)
+        })
+    }
+
+    /// Deleted employee is back with its reports and users - DaoError::NotFound when it is not deleted
+    pub fn restore_with_connection(
+        id_to_find: i32,
+        expected_version: i32,
+        keys: Option<&KeyRing>,
+        conn: &mut DbConnection,
+    ) -> DaoResult<Self> {
+        conn.transaction(|conn| {
+            let current = employees
+                .filter(employee_id.eq(id_to_find))
+                .filter(deleted_at.is_not_null())
+                .select(employee_version)
+                .first::<i32>(conn)
+                .optional()?
+                .ok_or_else(DaoError::not_found)?;
+            if current != expected_version {
+                return Err(stale_version(id_to_find, Some(current)));
+            }
+            diesel::update(employees.filter(employee_id.eq(id_to_find)))
+                .set((
+                    deleted_at.eq(None::<NaiveDateTime>),
+                    deleted_by.eq(None::<i32>),
+                    employee_version.eq(employee_version + 1),
+                ))
+                .execute(conn)?;
+            Ok(Self::get_simple(id_to_find, keys, conn)?)
+        })
+    }
+
+    /// Employees deleted before `cutoff` are deleted for good - with their salaries, contacts, contracts,
+    /// absences, timesheets, documents and photos (their content is deleted from `storage` after that). Their reports
+    /// are moved to their managers and users are unlinked. Return number of purged employees.
+    pub fn purge_deleted_before_with_connection(
+        cutoff: NaiveDateTime,
+        storage: &dyn DocumentStorage,
+        conn: &mut DbConnection,
+    ) -> QueryResult<usize> {
+        let (purged, keys) = conn.transaction(|conn| {
+            let expired: Vec<i32> = employees
+                .filter(deleted_at.lt(cutoff))
+                .select(employee_id)
+                .load(conn)?;
+            let mut keys = crate::documents_dao::delete_documents_of(&expired, conn)?;
+            keys.extend(crate::photos_dao::delete_photos_of(&expired, conn)?);
+            for e_id in &expired {
+                detach(*e_id, conn)?;
+                delete_associations(*e_id, conn)?;
+            }
+            let purged = diesel::delete(employees.filter(employee_id.eq_any(&expired))).execute(conn)?;
+            QueryResult::Ok((purged, keys))
+        })?;
+        crate::documents_dao::delete_contents(&keys, storage);
+        Ok(purged)
     }
 }
 
-fn into_dto_with_associations(e: Employee, conn: &mut SqliteConnection) -> EmployeeDTO {
-    let sv: Vec<Salary> = Salary::belonging_to(&e).load(conn).unwrap();
-    let cv: Vec<Contact> = Contact::belonging_to(&e).load(conn).unwrap();
-    let mut e_dto = EmployeeDTO::from(e);
-    for s in sv {
-        e_dto.salaries.push(SalaryDTO::from(s));
+/// National IDs encrypted by other than the current key are encrypted (and indexed) by the current key - return
+/// number of such employees
+pub(crate) fn reencrypt_national_ids(keys: &KeyRing, conn: &mut DbConnection) -> QueryResult<usize> {
+    let stored: Vec<(i32, String)> = employees
+        .filter(national_id.is_not_null())
+        .select((employee_id, national_id.assume_not_null()))
+        .order(employee_id)
+        .load(conn)?;
+    let mut reencrypted = 0;
+    for (e_id, encrypted) in stored {
+        if keys.is_current(&encrypted) {
+            continue;
+        }
+        let national = keys.decrypt(&encrypted).map_err(|e| crypto::unreadable(e_id, "National ID", e))?;
+        let (encrypted, index) = encrypt_national_id(Some(&national), Some(keys));
+        diesel::update(employees.filter(employee_id.eq(e_id)))
+            .set((national_id.eq(encrypted), national_id_hash.eq(index)))
+            .execute(conn)?;
+        reencrypted += 1;
     }
-    for c in cv {
-        e_dto.contacts.push(ContactDTO::from(c));
+    Ok(reencrypted)
+}
+
+/// Phones of emergency contacts in plain text or encrypted by other than the current key are encrypted by the
+/// current key - return number of such contacts
+pub(crate) fn reencrypt_emergency_contacts(keys: &KeyRing, conn: &mut DbConnection) -> QueryResult<usize> {
+    let stored: Vec<(i32, String, bool)> = ec::emergency_contacts
+        .select((ec::id, ec::phone, ec::encrypted))
+        .order(ec::id)
+        .load(conn)?;
+    let mut reencrypted = 0;
+    for (contact_id, stored, encrypted) in stored {
+        if encrypted && keys.is_current(&stored) {
+            continue;
+        }
+        let phone = unseal(contact_id, "Phone of emergency contact", stored, encrypted, Some(keys))?;
+        diesel::update(ec::emergency_contacts.filter(ec::id.eq(contact_id)))
+            .set((ec::phone.eq(seal(&phone, Some(keys))), ec::encrypted.eq(true)))
+            .execute(conn)?;
+        reencrypted += 1;
     }
-    e_dto
+    Ok(reencrypted)
+}
+
+/// Employees are read with their associations in batches of that many - every association is one query per batch
+const ASSOCIATIONS_BATCH_SIZE: usize = 100;
+
+/// Employees (in order of `found`) with salaries, contracts, contacts, emergency contacts, custom fields, tags and
+/// photo - associations are loaded for ASSOCIATIONS_BATCH_SIZE employees at once
+fn with_associations(
+    found: Vec<Employee>,
+    keys: Option<&KeyRing>,
+    conn: &mut DbConnection,
+) -> QueryResult<Vec<EmployeeDTO>> {
+    use crate::schema::employment_contracts::columns::from_date as contract_from_date;
+
+    let mut with_associations = Vec::with_capacity(found.len());
+    for batch in found.chunks(ASSOCIATIONS_BATCH_SIZE) {
+        let ids: Vec<i32> = batch.iter().map(|e| e.id).collect();
+        let salary_rows = Salary::belonging_to(batch).load::<Salary>(conn)?.grouped_by(batch);
+        let contract_rows = EmploymentContract::belonging_to(batch)
+            .order(contract_from_date)
+            .load::<EmploymentContract>(conn)?
+            .grouped_by(batch);
+        let emergency = emergency_contacts_of(batch, keys, conn)?;
+        let mut contacts_by_employee = contacts_of_many(&ids, keys, conn)?;
+        let mut custom_by_employee = custom_values_of_many(&ids, conn)?;
+        let mut tags_by_employee = tags_of_many(&ids, conn)?;
+        let mut photos = PhotoDTO::of_employees_with_connection(&ids, conn)?;
+        for (((e, salary_rows), contract_rows), emergency) in
+            batch.iter().cloned().zip(salary_rows).zip(contract_rows).zip(emergency)
+        {
+            let e_id = e.id;
+            let mut e_dto = EmployeeDTO::from_row(e, keys);
+            e_dto.salaries = from_rows(Ok(salary_rows), keys)?;
+            e_dto.contracts = contract_rows.into_iter().map(ContractDTO::from).collect();
+            e_dto.contacts = contacts_by_employee.remove(&e_id).unwrap_or_default();
+            e_dto.emergency_contacts = emergency;
+            e_dto.custom_fields = custom_by_employee.remove(&e_id).unwrap_or_default();
+            e_dto.tags = tags_by_employee.remove(&e_id).unwrap_or_default();
+            e_dto.photo = photos.remove(&e_id);
+            with_associations.push(e_dto);
+        }
+    }
+    Ok(with_associations)
+}
+
+fn into_dto_with_associations(
+    e: Employee,
+    keys: Option<&KeyRing>,
+    conn: &mut DbConnection,
+) -> QueryResult<EmployeeDTO> {
+    with_associations(vec![e], keys, conn).map(|mut found| found.remove(0))
 }
 
 #[cfg(test)]
@@ -194,10 +1225,22 @@ mod tests {
     use chrono::NaiveDate;
 
     use crate::common_for_tests::*;
+    use crate::error::DaoError;
+    use crate::money::{Currency, Money, PayPeriod};
+    use crate::contacts_dao::{AddressDTO, AddressKind, PhoneDTO, PhoneKind};
+    use crate::documents_dao::tests::{contract, temporary_storage, PDF};
+    use crate::documents_dao::DocumentDTO;
+    use crate::schema::employees::dsl::national_id;
+    use crate::country::Country;
 
     use super::*;
 
-    impl CrudTests for EmployeeDTO {}
+    impl CrudTests for EmployeeDTO {
+        fn renew(&mut self) {
+            self.id = None;
+            self.version = None;
+        }
+    }
 
     #[test]
     fn crud_operations_on_employee() {
@@ -212,17 +1255,25 @@ mod tests {
                     id: None,
                     employee_id: None,
                     from_date: NaiveDate::from_ymd_opt(2015, 3, 14).unwrap(),
-                    to_date: NaiveDate::from_ymd_opt(2015, 3, 15).unwrap(),
-                    amount: 1,
+                    to_date: Some(NaiveDate::from_ymd_opt(2015, 3, 15).unwrap()),
+                    amount: Money::new(1, Currency::PLN),
+                    pay_period: PayPeriod::Monthly,
+                    gross: true,
                     search_string: "".to_string(),
+                    version: None,
+                    contract_id: None,
                 },
                 SalaryDTO {
                     id: None,
                     employee_id: None,
                     from_date: NaiveDate::from_ymd_opt(2015, 3, 16).unwrap(),
-                    to_date: NaiveDate::from_ymd_opt(2015, 3, 17).unwrap(),
-                    amount: 2,
+                    to_date: Some(NaiveDate::from_ymd_opt(2015, 3, 17).unwrap()),
+                    amount: Money::new(2, Currency::PLN),
+                    pay_period: PayPeriod::Monthly,
+                    gross: true,
                     search_string: "".to_string(),
+                    version: None,
+                    contract_id: None,
                 },
             ],
             contacts: vec![
@@ -230,23 +1281,38 @@ mod tests {
                     id: None,
                     employee_id: None,
                     from_date: NaiveDate::from_ymd_opt(2015, 3, 14).unwrap(),
-                    to_date: NaiveDate::from_ymd_opt(2015, 3, 15).unwrap(),
-                    phone: "123456".to_string(),
-                    address: Some("Address 1".to_string()),
+                    to_date: Some(NaiveDate::from_ymd_opt(2015, 3, 15).unwrap()),
+                    phones: vec![PhoneDTO {
+                        kind: PhoneKind::Mobile,
+                        number: "+48601123456".to_string(),
+                    }],
+                    emails: vec![],
+                    addresses: vec![],
                     search_string: "".to_string(),
+                    version: None,
                 },
                 ContactDTO {
                     id: None,
                     employee_id: None,
                     from_date: NaiveDate::from_ymd_opt(2015, 3, 16).unwrap(),
-                    to_date: NaiveDate::from_ymd_opt(2015, 3, 17).unwrap(),
-                    phone: "234567".to_string(),
-                    address: Some("Address 2".to_string()),
+                    to_date: Some(NaiveDate::from_ymd_opt(2015, 3, 17).unwrap()),
+                    phones: vec![PhoneDTO {
+                        kind: PhoneKind::Mobile,
+                        number: "+48601234567".to_string(),
+                    }],
+                    emails: vec![],
+                    addresses: vec![],
                     search_string: "".to_string(),
+                    version: None,
                 },
             ],
+            version: None,
+            department_id: None,
+            manager_id: None,
+            contracts: vec![],
+            ..Default::default()
         };
-        let common_assertions = |e: &EmployeeDTO, _conn: &mut SqliteConnection| {
+        let common_assertions = |e: &EmployeeDTO, _conn: &mut DbConnection| {
             assert_eq!(e.salaries.len(), 2);
             assert_eq!(e.contacts.len(), 2);
             for s in &e.salaries {
@@ -266,4 +1332,362 @@ mod tests {
 
         employee.test_with_assertion(assertions, conn);
     }
+
+    #[test]
+    fn stale_employee_should_not_be_saved_nor_deleted() {
+        let conn = &mut initialize();
+        let employee = EmployeeDTO {
+            id: None,
+            first_name: "Jan".to_string(),
+            last_name: "Kowalski".to_string(),
+            search_string: "".to_string(),
+            salaries: vec![SalaryDTO {
+                id: None,
+                employee_id: None,
+                from_date: NaiveDate::from_ymd_opt(2015, 3, 14).unwrap(),
+                to_date: Some(NaiveDate::from_ymd_opt(2015, 3, 15).unwrap()),
+                amount: Money::new(1, Currency::PLN),
+                pay_period: PayPeriod::Monthly,
+                gross: true,
+                search_string: "".to_string(),
+                version: None,
+                contract_id: None,
+            }],
+            contacts: vec![],
+            version: None,
+            department_id: None,
+            manager_id: None,
+            contracts: vec![],
+            ..Default::default()
+        };
+        let saved = employee.save_in_transaction(keys(), conn).unwrap();
+        assert_eq!(saved.version, Some(1));
+        assert_eq!(saved.salaries[0].version, Some(1));
+
+        // Salary is updated in place - it keeps its id and get new version
+        let mut changed = saved.clone();
+        changed.salaries[0].amount = Money::new(2, Currency::PLN);
+        let updated = changed.try_save_in_transaction(&Default::default(), keys(), conn).unwrap();
+        assert_eq!(updated.version, Some(2));
+        assert_eq!(updated.salaries[0].id, saved.salaries[0].id);
+        assert_eq!(updated.salaries[0].version, Some(2));
+
+        // `saved` is stale now
+        match saved.try_save_in_transaction(&Default::default(), keys(), conn) {
+            Err(DaoError::StaleVersion { actual: 2, .. }) => {}
+            result => panic!("Should report stale version and instead I got {:?}", result),
+        }
+        assert!(saved.try_delete_with_conn(keys(), conn).unwrap_err().is_stale_version());
+        let read = EmployeeDTO::get_with_conn(saved.id.unwrap(), keys(), conn).unwrap();
+        assert_eq!(read.salaries[0].amount.minor_units, 2);
+
+        // Stale salary in otherwise current employee is reported too
+        let mut with_stale_salary = updated.clone();
+        with_stale_salary.salaries[0].version = Some(1);
+        assert!(with_stale_salary
+            .try_save_in_transaction(&Default::default(), keys(), conn)
+            .unwrap_err()
+            .is_stale_version());
+
+        assert_eq!(updated.try_delete_with_conn(keys(), conn).unwrap(), 1);
+        assert!(updated.try_delete_with_conn(keys(), conn).unwrap_err().is_not_found());
+    }
+
+    #[test]
+    fn salaries_and_contacts_effective_on_date() {
+        let conn = &mut initialize();
+        let date = |y, m, d| NaiveDate::from_ymd_opt(y, m, d).unwrap();
+        let salary = |from, to, salary_amount| SalaryDTO {
+            id: None,
+            employee_id: None,
+            from_date: from,
+            to_date: to,
+            amount: Money::new(salary_amount, Currency::PLN),
+            pay_period: PayPeriod::Monthly,
+            gross: true,
+            search_string: "".to_string(),
+            version: None,
+            contract_id: None,
+        };
+        let employee = |name: &str, employee_contacts| EmployeeDTO {
+            id: None,
+            first_name: "Jan".to_string(),
+            last_name: name.to_string(),
+            search_string: "".to_string(),
+            salaries: vec![
+                salary(date(2020, 1, 1), Some(date(2020, 12, 31)), 1000),
+                salary(date(2021, 1, 1), None, 2000),
+            ],
+            contacts: employee_contacts,
+            version: None,
+            department_id: None,
+            manager_id: None,
+            contracts: vec![],
+            ..Default::default()
+        };
+        let with_contact = employee(
+            "Kowalski",
+            vec![ContactDTO {
+                id: None,
+                employee_id: None,
+                from_date: date(2020, 1, 1),
+                to_date: Some(date(2020, 12, 31)),
+                emails: vec![],
+                phones: vec![],
+                addresses: vec![AddressDTO {
+                    kind: AddressKind::Home,
+                    street: "Old street 1".to_string(),
+                    city: "Warszawa".to_string(),
+                    postal_code: "00-950".to_string(),
+                    country: Country::PL,
+                }],
+                search_string: "".to_string(),
+                version: None,
+            }],
+        )
+        .save_in_transaction(keys(), conn)
+        .unwrap();
+        let without_contact = employee("Nowak", vec![]).save_in_transaction(keys(), conn).unwrap();
+        let e_id = with_contact.id.unwrap();
+
+        let salary_on = |d, conn: &mut DbConnection| {
+            SalaryDTO::effective_on_with_connection(e_id, d, keys(), conn).unwrap().map(|s| s.amount.minor_units)
+        };
+        assert_eq!(salary_on(date(2019, 12, 31), conn), None);
+        assert_eq!(salary_on(date(2020, 12, 31), conn), Some(1000));
+        assert_eq!(salary_on(date(2021, 1, 1), conn), Some(2000));
+        assert_eq!(salary_on(date(2030, 1, 1), conn), Some(2000));
+
+        let on_2020 = EmployeeDTO::get_effective_on_with_conn(e_id, date(2020, 6, 30), keys(), conn).unwrap().unwrap();
+        assert_eq!(on_2020.salaries.len(), 1);
+        assert_eq!(on_2020.salaries[0].amount.minor_units, 1000);
+        assert_eq!(on_2020.contacts[0].addresses[0].street, "Old street 1");
+        let on_2022 = EmployeeDTO::get_effective_on_with_conn(e_id, date(2022, 1, 1), keys(), conn).unwrap().unwrap();
+        assert_eq!(on_2022.salaries[0].amount.minor_units, 2000);
+        assert!(on_2022.contacts.is_empty());
+        assert!(EmployeeDTO::get_effective_on_with_conn(e_id + 100, date(2022, 1, 1), keys(), conn).unwrap().is_none());
+
+        let ids = |v: Vec<EmployeeDTO>| v.into_iter().map(|e| e.id).collect::<Vec<_>>();
+        let all = EmployeeScope::All;
+        assert_eq!(
+            ids(EmployeeDTO::without_contact_on_with_connection(date(2020, 6, 30), all, keys(), conn).unwrap()),
+            vec![without_contact.id]
+        );
+        assert_eq!(
+            ids(EmployeeDTO::without_contact_on_with_connection(date(2022, 1, 1), all, keys(), conn).unwrap()),
+            vec![with_contact.id, without_contact.id]
+        );
+    }
+
+    #[test]
+    fn employee_profile_is_validated() {
+        let conn = &mut initialize();
+        let rules = ValidationRules {
+            employee_number_format: "HR-{seq:3}".parse().unwrap(),
+            ..Default::default()
+        };
+        let date = |y, m, d| NaiveDate::from_ymd_opt(y, m, d).unwrap();
+        let candidate = EmployeeDTO {
+            first_name: "Jan".to_string(),
+            last_name: "Kowalski".to_string(),
+            date_of_birth: Some(date(1985, 1, 1)),
+            national_id: Some(" 850101-12345 ".to_string()),
+            status: EmploymentStatus::Candidate,
+            emergency_contacts: vec![EmergencyContactDTO {
+                name: "Anna Kowalska".to_string(),
+                relationship: "wife".to_string(),
+                phone: "602 345 678".to_string(),
+                email: Some("anna@example.com".to_string()),
+            }],
+            ..Default::default()
+        };
+        let saved = candidate.try_save_in_transaction(&rules, keys(), conn).unwrap();
+        assert_eq!(saved.employee_number, Some("HR-001".to_string()));
+        assert_eq!(saved.national_id, Some("85010112345".to_string()));
+        assert_eq!(saved.emergency_contacts[0].phone, "+48602345678");
+        let stored: Option<String> = employees
+            .filter(employee_id.eq(saved.id.unwrap()))
+            .select(national_id)
+            .first(conn)
+            .unwrap();
+        assert!(!stored.unwrap().contains("85010112345"), "National ID should be encrypted");
+        let found = EmployeeDTO::search_in_scope_with_connection(
+            &EmployeeSearch {
+                national_id: Some("85010112345".to_string()),
+                ..Default::default()
+            },
+            EmployeeScope::All,
+            keys(),
+            conn,
+        )
+        .unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].emergency_contacts, saved.emergency_contacts);
+
+        let hired = EmployeeDTO {
+            status: EmploymentStatus::Active,
+            hire_date: Some(date(2020, 1, 1)),
+            ..saved.clone()
+        }
+        .try_save_in_transaction(&rules, keys(), conn)
+        .unwrap();
+        assert_eq!(hired.employee_number, saved.employee_number);
+        let invalid = |e: EmployeeDTO, field: &str, conn: &mut DbConnection| {
+            match e.try_save_in_transaction(&rules, keys(), conn) {
+                Err(DaoError::Validation(errors)) => assert_eq!(errors[0].field, field),
+                result => panic!("Should report validation error and instead I got {:?}", result),
+            }
+        };
+        invalid(
+            EmployeeDTO {
+                status: EmploymentStatus::Candidate,
+                ..hired.clone()
+            },
+            "status",
+            conn,
+        );
+        invalid(
+            EmployeeDTO {
+                status: EmploymentStatus::Terminated,
+                ..hired.clone()
+            },
+            "termination_date",
+            conn,
+        );
+        invalid(
+            EmployeeDTO {
+                status: EmploymentStatus::Terminated,
+                termination_date: Some(date(2019, 12, 31)),
+                ..hired.clone()
+            },
+            "termination_date",
+            conn,
+        );
+        invalid(
+            EmployeeDTO {
+                hire_date: Some(date(1984, 1, 1)),
+                ..hired.clone()
+            },
+            "hire_date",
+            conn,
+        );
+        invalid(
+            EmployeeDTO {
+                id: None,
+                version: None,
+                ..hired.clone()
+            },
+            "employee_number",
+            conn,
+        );
+        let mut wrong_phone = hired.clone();
+        wrong_phone.emergency_contacts[0].phone = "12".to_string();
+        invalid(wrong_phone, "emergency_contacts[0].phone", conn);
+    }
+
+    #[test]
+    fn deleted_employee_is_kept_till_purged() {
+        let conn = &mut initialize();
+        let salary = SalaryDTO {
+            id: None,
+            employee_id: None,
+            from_date: NaiveDate::from_ymd_opt(2020, 1, 1).unwrap(),
+            to_date: None,
+            amount: Money::new(1000, Currency::PLN),
+            pay_period: PayPeriod::Monthly,
+            gross: true,
+            search_string: "".to_string(),
+            version: None,
+            contract_id: None,
+        };
+        let manager = EmployeeDTO {
+            first_name: "Anna".to_string(),
+            last_name: "Nowak".to_string(),
+            salaries: vec![salary.clone()],
+            ..Default::default()
+        }
+        .save_in_transaction(keys(), conn)
+        .unwrap();
+        let boss_id = manager.id.unwrap();
+        let report = EmployeeDTO {
+            first_name: "Jan".to_string(),
+            last_name: "Kowalski".to_string(),
+            manager_id: Some(boss_id),
+            salaries: vec![salary],
+            ..Default::default()
+        }
+        .save_in_transaction(keys(), conn)
+        .unwrap();
+
+        assert!(EmployeeDTO::delete_by_with_connection(boss_id, 0, 1, keys(), conn)
+            .unwrap_err()
+            .is_stale_version());
+        assert_eq!(EmployeeDTO::delete_by_with_connection(boss_id, 1, 1, keys(), conn).unwrap(), 1);
+        assert!(EmployeeDTO::get_with_conn(boss_id, keys(), conn).is_none());
+        assert_eq!(EmployeeDTO::get_all_with_connection(keys(), conn).unwrap().len(), 1);
+        assert_eq!(SalaryDTO::get_all_with_connection(keys(), conn).unwrap().len(), 1);
+        let reported = EmployeeDTO::get_with_conn(report.id.unwrap(), keys(), conn).unwrap();
+        assert_eq!(reported.manager_id, Some(boss_id), "Report should keep its deleted manager");
+        assert_eq!(reported.version, report.version, "Report shouldn't be changed");
+        let deleted = EmployeeDTO::get_including_deleted_with_connection(boss_id, keys(), conn).unwrap();
+        assert!(deleted.deleted_at.is_some());
+        assert_eq!(deleted.deleted_by, Some(1));
+        assert_eq!(deleted.salaries.len(), 1, "Records of deleted employee should be kept");
+        let search = EmployeeSearch {
+            include_deleted: true,
+            ..Default::default()
+        };
+        let found = EmployeeDTO::search_in_scope_with_connection(&search, EmployeeScope::All, keys(), conn).unwrap();
+        assert_eq!(found.len(), 2);
+        assert!(
+            deleted.try_save_in_transaction(&Default::default(), keys(), conn).unwrap_err().is_not_found(),
+            "Deleted employee should be restored before it is saved"
+        );
+        assert_eq!(employee_count(conn), 2, "Deleted employee shouldn't be saved as new one");
+
+        assert!(EmployeeDTO::restore_with_connection(boss_id, 1, keys(), conn)
+            .unwrap_err()
+            .is_stale_version());
+        let restored = EmployeeDTO::restore_with_connection(boss_id, deleted.version.unwrap(), keys(), conn).unwrap();
+        assert_eq!(restored.deleted_at, None);
+        assert_eq!(restored.employee_number, manager.employee_number);
+        let reports = EmployeeDTO::reports_of_with_connection(boss_id, keys(), conn).unwrap();
+        assert_eq!(reports[0].id, report.id, "Restored employee should manage its reports again");
+        assert!(EmployeeDTO::restore_with_connection(boss_id, restored.version.unwrap(), keys(), conn)
+            .unwrap_err()
+            .is_not_found());
+
+        let (storage, dir) = temporary_storage();
+        let document = DocumentDTO::upload_with_connection(
+            boss_id,
+            &contract(),
+            PDF,
+            None,
+            &Default::default(),
+            &storage,
+            conn,
+        )
+        .unwrap();
+        assert_eq!(EmployeeDTO::delete_by_id_with_conn(boss_id, conn), Some(1));
+        let moment = Local::now().naive_local();
+        assert_eq!(
+            EmployeeDTO::purge_deleted_before_with_connection(moment - chrono::Duration::days(1), &storage, conn)
+                .unwrap(),
+            0
+        );
+        assert_eq!(
+            EmployeeDTO::purge_deleted_before_with_connection(moment + chrono::Duration::days(1), &storage, conn)
+                .unwrap(),
+            1
+        );
+        assert!(document.content(&storage).is_err(), "Content of purged document should be deleted");
+        std::fs::remove_dir_all(dir).unwrap();
+        assert!(EmployeeDTO::get_including_deleted_with_connection(boss_id, keys(), conn)
+            .optional()
+            .unwrap()
+            .is_none());
+        assert_eq!(SalaryDTO::search_by_parent_id_with_connection(boss_id, keys(), conn).unwrap().len(), 0);
+        let reported = EmployeeDTO::get_with_conn(report.id.unwrap(), keys(), conn).unwrap();
+        assert_eq!(reported.manager_id, None, "Reports of purged employee should be moved to its manager");
+    }
 }
@@ -0,0 +1,155 @@
+use std::error::Error;
+use std::fmt;
+
+use crate::storage::StorageError;
+use crate::validation::FieldError;
+
+/// Errors reported by DAO when caller need to know what exactly went wrong
+#[derive(Debug)]
+pub enum DaoError {
+    /// Can't check out connection from pool (timeout, pool exhausted or DB not reachable)
+    Pool(r2d2::Error),
+    /// Query failed
+    Query(diesel::result::Error),
+    /// Record was modified by someone else since it was read (optimistic locking)
+    StaleVersion { id: i32, actual: i32 },
+    /// Record violates business rules - nothing was saved
+    Validation(Vec<FieldError>),
+    /// Content of document can't be stored or read
+    Storage(StorageError),
+}
+
+pub type DaoResult<T> = Result<T, DaoError>;
+
+impl DaoError {
+    pub fn not_found() -> DaoError {
+        DaoError::Query(diesel::result::Error::NotFound)
+    }
+
+    pub fn is_not_found(&self) -> bool {
+        matches!(self, DaoError::Query(diesel::result::Error::NotFound))
+    }
+
+    pub fn is_validation(&self) -> bool {
+        matches!(self, DaoError::Validation(_))
+    }
+
+    pub fn is_stale_version(&self) -> bool {
+        matches!(self, DaoError::StaleVersion { .. })
+    }
+
+    /// Record can't be deleted because other records refer to it
+    pub fn is_foreign_key_violation(&self) -> bool {
+        matches!(
+            self,
+            DaoError::Query(diesel::result::Error::DatabaseError(
+                diesel::result::DatabaseErrorKind::ForeignKeyViolation,
+                _
+            ))
+        )
+    }
+}
+
+impl fmt::Display for DaoError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DaoError::Pool(e) => write!(f, "Can't get DB connection: {}", e),
+            DaoError::Query(e) => write!(f, "Query failed: {}", e),
+            DaoError::StaleVersion { id, actual } => write!(
+                f,
+                "Record with id = {} was modified in the meantime - current version is {}",
+                id, actual
+            ),
+            DaoError::Validation(errors) => write!(
+                f,
+                "Validation failed: {}",
+                errors
+                    .iter()
+                    .map(FieldError::to_string)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            DaoError::Storage(e) => write!(f, "Document storage failed: {}", e),
+        }
+    }
+}
+
+impl Error for DaoError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            DaoError::Pool(e) => Some(e),
+            DaoError::Query(e) => Some(e),
+            DaoError::Storage(e) => Some(e),
+            DaoError::StaleVersion { .. } | DaoError::Validation(_) => None,
+        }
+    }
+}
+
+impl From<r2d2::Error> for DaoError {
+    fn from(e: r2d2::Error) -> Self {
+        DaoError::Pool(e)
+    }
+}
+
+impl From<diesel::result::Error> for DaoError {
+    fn from(e: diesel::result::Error) -> Self {
+        DaoError::Query(e)
+    }
+}
+
+impl From<StorageError> for DaoError {
+    fn from(e: StorageError) -> Self {
+        DaoError::Storage(e)
+    }
+}
+
+/// Invalid Database configuration - reported on startup
+#[derive(Debug)]
+pub enum ConfigError {
+    InvalidValue {
+        name: &'static str,
+        value: String,
+        reason: String,
+    },
+    BackendMismatch {
+        database_url: String,
+        required: &'static str,
+        compiled: &'static str,
+    },
+    /// Pool can't establish initial connections
+    Connect(r2d2::Error),
+}
+
+impl ConfigError {
+    pub fn invalid<V: ToString>(name: &'static str, value: V, reason: &str) -> ConfigError {
+        ConfigError::InvalidValue {
+            name,
+            value: value.to_string(),
+            reason: reason.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::InvalidValue {
+                name,
+                value,
+                reason,
+            } => write!(f, "Invalid {}='{}': {}", name, value, reason),
+            ConfigError::BackendMismatch {
+                database_url,
+                required,
+                compiled,
+            } => write!(
+                f,
+                "DATABASE_URL='{}' requires '{}' backend but application is compiled with '{}' - rebuild with `--no-default-features --features {}`",
+                database_url, required, compiled, required
+            ),
+            ConfigError::Connect(e) => write!(f, "Can't connect to database: {}", e),
+        }
+    }
+}
+
+impl Error for ConfigError {}
@@ -0,0 +1,390 @@
+use std::collections::HashMap;
+
+use diesel::prelude::*;
+use diesel::sql_types::{Integer, Nullable, Text};
+
+use crate::connection::{sql, Database, DbConnection};
+use crate::error::DaoResult;
+use crate::models::User;
+use crate::validation::Errors;
+
+/// Tables which rows form a tree by reference to their parent
+#[derive(Clone, Copy, Debug)]
+pub(crate) enum Tree {
+    /// Departments by parent_id
+    Departments,
+    /// Employees by manager_id
+    Employees,
+}
+
+impl Tree {
+    fn table(&self) -> &'static str {
+        match self {
+            Tree::Departments => "departments",
+            Tree::Employees => "employees",
+        }
+    }
+
+    fn parent_column(&self) -> &'static str {
+        match self {
+            Tree::Departments => "parent_id",
+            Tree::Employees => "manager_id",
+        }
+    }
+
+    /// Condition (on `alias`) of rows which are part of the tree - deleted employees are not, but the tree is
+    /// walked through them so their reports (which keep their manager_id) are still part of it
+    fn present(&self, alias: &str) -> String {
+        match self {
+            Tree::Departments => String::new(),
+            Tree::Employees => format!(" AND {}deleted_at IS NULL", alias),
+        }
+    }
+}
+
+#[derive(QueryableByName)]
+struct Id {
+    #[diesel(sql_type = Integer)]
+    id: i32,
+}
+
+fn load_ids(query: String, start: i32, conn: &mut DbConnection) -> QueryResult<Vec<i32>> {
+    let ids: Vec<Id> = diesel::sql_query(sql(&query))
+        .bind::<Integer, _>(start)
+        .load(conn)?;
+    Ok(ids.into_iter().map(|i| i.id).collect())
+}
+
+/// `start` and all its ancestors (parent, parent of parent...) - empty when `start` doesn't exist (or it is
+/// deleted employee). Deleted employees are skipped.
+/// UNION (not UNION ALL) stops recursion even if there is a cycle.
+pub(crate) fn ancestors(tree: Tree, start: i32, conn: &mut DbConnection) -> QueryResult<Vec<i32>> {
+    let query = format!(
+        "WITH RECURSIVE chain(id, parent) AS ( \
+             SELECT id, {parent} FROM {table} WHERE id = $1{present} \
+             UNION \
+             SELECT t.id, t.{parent} FROM {table} t JOIN chain ON t.id = chain.parent \
+         ) \
+         SELECT chain.id AS id FROM chain JOIN {table} t ON t.id = chain.id{t_present}",
+        table = tree.table(),
+        parent = tree.parent_column(),
+        present = tree.present(""),
+        t_present = tree.present("t."),
+    );
+    load_ids(query, start, conn)
+}
+
+/// `start` and all its descendants (children, children of children...) ordered by id -
+/// empty when `start` doesn't exist (or it is deleted employee). Deleted employees are skipped.
+pub(crate) fn descendants(tree: Tree, start: i32, conn: &mut DbConnection) -> QueryResult<Vec<i32>> {
+    let query = format!(
+        "WITH RECURSIVE subtree(id) AS ( \
+             SELECT id FROM {table} WHERE id = $1{present} \
+             UNION \
+             SELECT t.id FROM {table} t JOIN subtree ON t.{parent} = subtree.id \
+         ) \
+         SELECT subtree.id AS id FROM subtree JOIN {table} t ON t.id = subtree.id{t_present} ORDER BY subtree.id",
+        table = tree.table(),
+        parent = tree.parent_column(),
+        present = tree.present(""),
+        t_present = tree.present("t."),
+    );
+    load_ids(query, start, conn)
+}
+
+/// Parent of record have to exist and can't be the record itself nor any of its descendants
+pub(crate) fn check_parent(
+    tree: Tree,
+    id: Option<i32>,
+    parent: Option<i32>,
+    field: &str,
+    errors: &mut Errors,
+    conn: &mut DbConnection,
+) -> QueryResult<()> {
+    if let Some(parent) = parent {
+        let chain = ancestors(tree, parent, conn)?;
+        if chain.is_empty() {
+            errors.add("", field, format!("there is no record with id = {}", parent));
+        } else if let Some(id) = id
+            && chain.contains(&id)
+        {
+            errors.add(
+                "",
+                field,
+                format!("{} would make a cycle - it is the record itself or one of its descendants", parent),
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Which employees user can see
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EmployeeScope {
+    /// Admins and users not linked to employee see everybody
+    All,
+    /// User linked to employee see the employee and its (recursive) reports
+    Subtree(i32),
+}
+
+impl EmployeeScope {
+    /// Scope of user - None when there is no such user
+    pub fn for_user(user_id: i32, conn: &mut DbConnection) -> Option<EmployeeScope> {
+        crate::users_dao::get_user(user_id, conn).as_ref().map(EmployeeScope::of)
+    }
+
+    pub fn of(user: &User) -> EmployeeScope {
+        match user.employee_id {
+            Some(e_id) if !user.is_admin => EmployeeScope::Subtree(e_id),
+            _ => EmployeeScope::All,
+        }
+    }
+
+    /// Ids of employees in scope - None means all employees
+    pub fn employee_ids(&self, conn: &mut DbConnection) -> QueryResult<Option<Vec<i32>>> {
+        match self {
+            EmployeeScope::All => Ok(None),
+            EmployeeScope::Subtree(root) => descendants(Tree::Employees, *root, conn).map(Some),
+        }
+    }
+
+    pub fn contains(&self, e_id: i32, conn: &mut DbConnection) -> QueryResult<bool> {
+        match self {
+            EmployeeScope::All => Ok(true),
+            EmployeeScope::Subtree(root) => Ok(ancestors(Tree::Employees, e_id, conn)?.contains(root)),
+        }
+    }
+}
+
+/// Admin can approve requests (absences, timesheets) of anybody, user linked to employee requests of its
+/// (recursive) reports - but not its own ones
+pub fn can_approve(user_id: i32, e_id: i32, conn: &mut DbConnection) -> QueryResult<bool> {
+    match crate::users_dao::get_user(user_id, conn) {
+        Some(user) if user.is_admin => Ok(true),
+        Some(user) => match user.employee_id {
+            Some(manager) if manager != e_id => Ok(ancestors(Tree::Employees, e_id, conn)?.contains(&manager)),
+            _ => Ok(false),
+        },
+        None => Ok(false),
+    }
+}
+
+/// Employee in organization chart with all employees reporting to it
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct OrgChartNode {
+    pub id: i32,
+    pub first_name: String,
+    pub last_name: String,
+    pub department_id: Option<i32>,
+    pub department: Option<String>,
+    pub reports: Vec<OrgChartNode>,
+}
+
+#[derive(QueryableByName)]
+struct OrgChartRow {
+    #[diesel(sql_type = Integer)]
+    id: i32,
+    #[diesel(sql_type = Text)]
+    first_name: String,
+    #[diesel(sql_type = Text)]
+    last_name: String,
+    #[diesel(sql_type = Nullable<Integer>)]
+    manager_id: Option<i32>,
+    #[diesel(sql_type = Nullable<Integer>)]
+    department_id: Option<i32>,
+    #[diesel(sql_type = Nullable<Text>)]
+    department: Option<String>,
+}
+
+/// Organization chart (employees by their managers) - for EmployeeScope::All there is a tree for every
+/// employee without manager, for EmployeeScope::Subtree just one tree. Deleted employees are left out - their
+/// reports are shown under the nearest manager which is not deleted. Employees are ordered by name.
+pub fn org_chart_with_connection(scope: EmployeeScope, conn: &mut DbConnection) -> QueryResult<Vec<OrgChartNode>> {
+    // Root of the chart has NULL manager_id (for Subtree the manager is out of scope)
+    let (start, root) = match scope {
+        EmployeeScope::All => ("manager_id IS NULL AND $1 IS NULL", None),
+        EmployeeScope::Subtree(root) => ("id = $1 AND deleted_at IS NULL", Some(root)),
+    };
+    // `manager_id` of chart is the nearest manager which is not deleted
+    let query = format!(
+        "WITH RECURSIVE chart(id, manager_id, present) AS ( \
+             SELECT id, CAST(NULL AS INTEGER), CASE WHEN deleted_at IS NULL THEN 1 ELSE 0 END \
+             FROM employees WHERE {start} \
+             UNION \
+             SELECT e.id, CASE WHEN chart.present = 1 THEN chart.id ELSE chart.manager_id END, \
+                 CASE WHEN e.deleted_at IS NULL THEN 1 ELSE 0 END \
+             FROM employees e JOIN chart ON e.manager_id = chart.id \
+         ) \
+         SELECT e.id, e.first_name, e.last_name, chart.manager_id, e.department_id, d.name AS department \
+         FROM chart \
+         JOIN employees e ON e.id = chart.id \
+         LEFT JOIN departments d ON d.id = e.department_id \
+         WHERE chart.present = 1 \
+         ORDER BY e.last_name, e.first_name, e.id",
+        start = start,
+    );
+    let rows: Vec<OrgChartRow> = diesel::sql_query(sql(&query))
+        .bind::<Nullable<Integer>, _>(root)
+        .load(conn)?;
+
+    let mut reports: HashMap<Option<i32>, Vec<OrgChartRow>> = HashMap::new();
+    for row in rows {
+        reports.entry(row.manager_id).or_default().push(row);
+    }
+    fn build(manager: Option<i32>, reports: &mut HashMap<Option<i32>, Vec<OrgChartRow>>) -> Vec<OrgChartNode> {
+        reports
+            .remove(&manager)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|row| OrgChartNode {
+                reports: build(Some(row.id), reports),
+                id: row.id,
+                first_name: row.first_name,
+                last_name: row.last_name,
+                department_id: row.department_id,
+                department: row.department,
+            })
+            .collect()
+    }
+    Ok(build(None, &mut reports))
+}
+
+pub fn org_chart(db: &Database, scope: EmployeeScope) -> DaoResult<Vec<OrgChartNode>> {
+    let conn = &mut db.try_get_connection()?;
+    Ok(org_chart_with_connection(scope, conn)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::base_dao::Crud;
+    use crate::common_for_tests::*;
+    use crate::error::DaoError;
+    use crate::models::NewUser;
+    use crate::users_dao::{create_user, get_user, validate_user};
+    use crate::{DepartmentDTO, EmployeeDTO};
+
+    use super::*;
+
+    fn save_employee(last_name: &str, manager: Option<&EmployeeDTO>, conn: &mut DbConnection) -> EmployeeDTO {
+        EmployeeDTO {
+            id: None,
+            first_name: "Jan".to_string(),
+            last_name: last_name.to_string(),
+            search_string: "".to_string(),
+            salaries: vec![],
+            contacts: vec![],
+            version: None,
+            department_id: None,
+            manager_id: manager.and_then(|m| m.id),
+            contracts: vec![],
+            ..Default::default()
+        }
+        .save_in_transaction(keys(), conn)
+        .unwrap()
+    }
+
+    fn names(employees: Vec<EmployeeDTO>) -> Vec<String> {
+        employees.into_iter().map(|e| e.last_name).collect()
+    }
+
+    #[test]
+    fn employee_hierarchy() {
+        let conn = &mut initialize();
+        let ceo = save_employee("Ceo", None, conn);
+        let cto = save_employee("Cto", Some(&ceo), conn);
+        let dev = save_employee("Dev", Some(&cto), conn);
+        save_employee("Sales", Some(&ceo), conn);
+
+        let reports = EmployeeDTO::reports_of_with_connection(ceo.id.unwrap(), keys(), conn).unwrap();
+        assert_eq!(names(reports), vec!["Cto", "Dev", "Sales"]);
+        assert_eq!(names(EmployeeDTO::reports_of_with_connection(cto.id.unwrap(), keys(), conn).unwrap()), vec!["Dev"]);
+        assert!(EmployeeDTO::reports_of_with_connection(dev.id.unwrap(), keys(), conn).unwrap().is_empty());
+
+        for manager in [dev.id, ceo.id, Some(1000)] {
+            let moved = EmployeeDTO {
+                manager_id: manager,
+                ..ceo.clone()
+            };
+            match moved.try_save_in_transaction(&Default::default(), keys(), conn) {
+                Err(DaoError::Validation(errors)) => assert_eq!(errors[0].field, "manager_id"),
+                result => panic!("Should report validation error and instead I got {:?}", result),
+            }
+        }
+
+        // User linked to employee see just its subtree, admin see everybody
+        let manager = create_user(
+            &NewUser {
+                username: "cto".to_string(),
+                password: "".to_string(),
+                is_admin: false,
+                employee_id: cto.id,
+                privacy_officer: false,
+            },
+            conn,
+        )
+        .unwrap();
+        let scope = EmployeeScope::for_user(manager.id, conn).unwrap();
+        assert_eq!(scope, EmployeeScope::Subtree(cto.id.unwrap()));
+        let in_scope = EmployeeDTO::get_all_in_scope_with_connection(scope, keys(), conn).unwrap();
+        assert_eq!(names(in_scope), vec!["Cto", "Dev"]);
+        assert!(scope.contains(dev.id.unwrap(), conn).unwrap());
+        assert!(!scope.contains(ceo.id.unwrap(), conn).unwrap());
+        assert_eq!(EmployeeScope::for_user(2, conn), Some(EmployeeScope::All));
+        assert_eq!(EmployeeScope::for_user(1000, conn), None);
+
+        let chart = org_chart_with_connection(EmployeeScope::All, conn).unwrap();
+        assert_eq!(chart.len(), 1);
+        assert_eq!(chart[0].last_name, "Ceo");
+        let reports: Vec<&str> = chart[0].reports.iter().map(|r| r.last_name.as_str()).collect();
+        assert_eq!(reports, vec!["Cto", "Sales"]);
+        assert_eq!(chart[0].reports[0].reports[0].last_name, "Dev");
+        let chart = org_chart_with_connection(scope, conn).unwrap();
+        assert_eq!(chart.len(), 1);
+        assert_eq!(chart[0].last_name, "Cto");
+        assert_eq!(chart[0].reports.len(), 1);
+
+        // Deleted employee keeps its reports and users - it is just left out of the chart and scopes
+        assert!(validate_user(&"cto".to_string(), &"".to_string(), conn).is_some());
+        let cto = EmployeeDTO::get_with_conn(cto.id.unwrap(), keys(), conn).unwrap();
+        assert_eq!(cto.try_delete_with_conn(keys(), conn).unwrap(), 1);
+        assert_eq!(EmployeeDTO::get_with_conn(dev.id.unwrap(), keys(), conn).unwrap().manager_id, cto.id);
+        assert_eq!(get_user(manager.id, conn).unwrap().employee_id, cto.id);
+        let chart = org_chart_with_connection(EmployeeScope::All, conn).unwrap();
+        let reports: Vec<&str> = chart[0].reports.iter().map(|r| r.last_name.as_str()).collect();
+        assert_eq!(reports, vec!["Dev", "Sales"], "Reports of deleted employee are under its manager");
+        let reports = EmployeeDTO::reports_of_with_connection(ceo.id.unwrap(), keys(), conn).unwrap();
+        assert_eq!(names(reports), vec!["Dev", "Sales"]);
+        assert!(EmployeeDTO::get_all_in_scope_with_connection(scope, keys(), conn).unwrap().is_empty());
+        assert!(org_chart_with_connection(scope, conn).unwrap().is_empty());
+        assert!(can_approve(2, dev.id.unwrap(), conn).unwrap());
+        assert!(validate_user(&"cto".to_string(), &"".to_string(), conn).is_none(), "User of deleted can't log in");
+    }
+
+    #[test]
+    fn employee_department_has_to_exist() {
+        let conn = &mut initialize();
+        let it = DepartmentDTO {
+            id: None,
+            name: "IT".to_string(),
+            parent_id: None,
+            search_string: "".to_string(),
+            version: None,
+        }
+        .save_in_transaction(keys(), conn)
+        .unwrap();
+        let mut employee = save_employee("Kowalski", None, conn);
+        employee.department_id = Some(1000);
+        match employee.try_save_in_transaction(&Default::default(), keys(), conn) {
+            Err(DaoError::Validation(errors)) => assert_eq!(errors[0].field, "department_id"),
+            result => panic!("Should report validation error and instead I got {:?}", result),
+        }
+        employee.department_id = it.id;
+        let employee = employee.try_save_in_transaction(&Default::default(), keys(), conn).unwrap();
+        let chart = org_chart_with_connection(EmployeeScope::All, conn).unwrap();
+        assert_eq!(chart[0].department, Some("IT".to_string()));
+
+        // Employees of deleted department are moved to its parent (none here)
+        assert_eq!(it.try_delete_with_conn(keys(), conn).unwrap(), 1);
+        assert_eq!(EmployeeDTO::get_with_conn(employee.id.unwrap(), keys(), conn).unwrap().department_id, None);
+    }
+}
@@ -0,0 +1,629 @@
+use std::collections::BTreeMap;
+
+use chrono::{Days, NaiveDate};
+use diesel::prelude::*;
+
+use crate::base_dao::Crud;
+use crate::connection::DbConnection;
+use crate::contacts_dao::{AddressDTO, AddressKind, ContactDTO, EmailDTO, EmailKind, PhoneDTO, PhoneKind};
+use crate::crypto::KeyRing;
+use crate::employees_dao::EmployeeDTO;
+use crate::error::{DaoError, DaoResult};
+use crate::money::{Currency, Money, PayPeriod};
+use crate::salaries_dao::SalaryDTO;
+use crate::schema::employees::dsl as e;
+use crate::validation::{FieldError, ValidationRules};
+
+/// Fields row of import can have - columns named the same are mapped to them unless mapping says otherwise.
+/// Salary (`salary_*`) and contact (`contact_*`, `email`, `phone`, `street` ...) periods start on `hire_date` when
+/// `salary_from` (`contact_from`) is not given.
+pub const IMPORT_FIELDS: [&str; 25] = [
+    "employee_number",
+    "first_name",
+    "last_name",
+    "date_of_birth",
+    "national_id",
+    "hire_date",
+    "termination_date",
+    "status",
+    "department_id",
+    "manager_id",
+    "manager_number",
+    "salary_from",
+    "salary_to",
+    "salary_amount",
+    "salary_currency",
+    "salary_pay_period",
+    "salary_gross",
+    "contact_from",
+    "contact_to",
+    "email",
+    "phone",
+    "street",
+    "city",
+    "postal_code",
+    "country",
+];
+
+/// Fields which make contact - contact is imported when any of them is given
+const CONTACT_DETAILS: [&str; 6] = ["email", "phone", "street", "city", "postal_code", "country"];
+
+/// How rows are imported
+#[derive(Clone, Debug)]
+pub struct ImportOptions {
+    /// Rows are just validated - nothing is saved
+    pub dry_run: bool,
+    /// All rows or none - nothing is saved when any row fails
+    pub atomic: bool,
+    /// Rows saved in one transaction (when not atomic) - failed row doesn't affect others
+    pub chunk_size: usize,
+    /// chrono format of dates - Excel serial numbers (days since 1899-12-30) are accepted too
+    pub date_format: String,
+    /// Currency of salaries without `salary_currency`
+    pub default_currency: Currency,
+}
+
+impl Default for ImportOptions {
+    fn default() -> Self {
+        ImportOptions {
+            dry_run: false,
+            atomic: false,
+            chunk_size: 100,
+            date_format: "%Y-%m-%d".to_string(),
+            default_currency: Currency::PLN,
+        }
+    }
+}
+
+/// Values of row by field (see IMPORT_FIELDS) - `row` is its number in imported file (header is row 1)
+#[derive(Clone, Debug, Default)]
+pub struct ImportRow {
+    pub row: usize,
+    pub values: BTreeMap<String, String>,
+}
+
+/// Why row was not imported
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ImportRowError {
+    pub row: usize,
+    pub employee_number: Option<String>,
+    pub errors: Vec<FieldError>,
+}
+
+/// What was (or would be when `dry_run`) done - employees are updated when their employee number is found
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct ImportResult {
+    pub dry_run: bool,
+    pub created: usize,
+    pub updated: usize,
+    pub failed: usize,
+    pub errors: Vec<ImportRowError>,
+    /// Columns which are not mapped to any field
+    #[serde(default)]
+    pub ignored_columns: Vec<String>,
+}
+
+/// Non-empty (trimmed) values of row and errors of their parsing
+struct RowParser<'a> {
+    row: &'a ImportRow,
+    options: &'a ImportOptions,
+    errors: Vec<FieldError>,
+}
+
+impl<'a> RowParser<'a> {
+    fn text(&self, field: &str) -> Option<String> {
+        self.row
+            .values
+            .get(field)
+            .map(|v| v.trim())
+            .filter(|v| !v.is_empty())
+            .map(str::to_string)
+    }
+
+    fn error(&mut self, field: &str, message: String) {
+        self.errors.push(FieldError {
+            field: field.to_string(),
+            message,
+        });
+    }
+
+    fn parse<T>(&mut self, field: &str, parse: impl FnOnce(&str) -> Result<T, String>) -> Option<T> {
+        let value = self.text(field)?;
+        parse(&value).map_err(|message| self.error(field, message)).ok()
+    }
+
+    fn date(&mut self, field: &str) -> Option<NaiveDate> {
+        let format = self.options.date_format.clone();
+        self.parse(field, |value| {
+            NaiveDate::parse_from_str(value, &format)
+                .ok()
+                .or_else(|| {
+                    let serial = value.parse::<f64>().ok().filter(|s| s.is_finite() && *s >= 1.0)?;
+                    NaiveDate::from_ymd_opt(1899, 12, 30)?.checked_add_days(Days::new(serial as u64))
+                })
+                .ok_or_else(|| format!("'{}' is not date in format {}", value, format))
+        })
+    }
+
+    fn id(&mut self, field: &str) -> Option<i32> {
+        self.parse(field, |value| value.parse().map_err(|_| format!("'{}' is not id", value)))
+    }
+
+    fn bool(&mut self, field: &str) -> Option<bool> {
+        self.parse(field, |value| match value.to_lowercase().as_str() {
+            "true" | "yes" | "y" | "1" => Ok(true),
+            "false" | "no" | "n" | "0" => Ok(false),
+            _ => Err(format!("'{}' is not true or false", value)),
+        })
+    }
+
+    fn given(&self, fields: &[&str]) -> bool {
+        fields.iter().any(|f| self.text(f).is_some())
+    }
+}
+
+/// Employee number of not deleted employee - None when there is none
+fn employee_with_number(number: &str, conn: &mut DbConnection) -> QueryResult<Option<i32>> {
+    e::employees
+        .filter(e::employee_number.eq(number))
+        .filter(e::deleted_at.is_null())
+        .select(e::id)
+        .first(conn)
+        .optional()
+}
+
+/// Row applied to `employee` (existing one or default) - salary and contact replace ones starting the same day
+fn apply_row(
+    row: &ImportRow,
+    employee: &mut EmployeeDTO,
+    options: &ImportOptions,
+    rules: &ValidationRules,
+    conn: &mut DbConnection,
+) -> DaoResult<Vec<FieldError>> {
+    let mut p = RowParser {
+        row,
+        options,
+        errors: vec![],
+    };
+    if let Some(number) = p.text("employee_number") {
+        employee.employee_number = Some(number);
+    }
+    if let Some(name) = p.text("first_name") {
+        employee.first_name = name;
+    }
+    if let Some(name) = p.text("last_name") {
+        employee.last_name = name;
+    }
+    for (field, name) in [("first_name", &employee.first_name), ("last_name", &employee.last_name)] {
+        if name.is_empty() {
+            p.error(field, "can't be empty".to_string());
+        }
+    }
+    if let Some(date) = p.date("date_of_birth") {
+        employee.date_of_birth = Some(date);
+    }
+    if let Some(national) = p.text("national_id") {
+        employee.national_id = Some(national);
+    }
+    if let Some(date) = p.date("hire_date") {
+        employee.hire_date = Some(date);
+    }
+    if let Some(date) = p.date("termination_date") {
+        employee.termination_date = Some(date);
+    }
+    if let Some(status) = p.parse("status", |s| s.to_lowercase().parse()) {
+        employee.status = status;
+    }
+    if let Some(department) = p.id("department_id") {
+        employee.department_id = Some(department);
+    }
+    if let Some(manager) = p.id("manager_id") {
+        employee.manager_id = Some(manager);
+    }
+    if let Some(number) = p.text("manager_number") {
+        match employee_with_number(&number, conn)? {
+            Some(manager) => employee.manager_id = Some(manager),
+            None => p.error("manager_number", format!("there is no employee with number '{}'", number)),
+        }
+    }
+
+    if p.given(&["salary_from", "salary_to", "salary_amount"]) {
+        let currency = p
+            .parse("salary_currency", |c| c.to_uppercase().parse())
+            .unwrap_or(options.default_currency);
+        let amount = p.parse("salary_amount", |a| Money::parse(a, currency));
+        let pay_period = p
+            .parse("salary_pay_period", |s| s.to_lowercase().parse())
+            .unwrap_or(PayPeriod::Monthly);
+        let gross = p.bool("salary_gross").unwrap_or(true);
+        let from = p.date("salary_from").or(employee.hire_date);
+        let to = p.date("salary_to");
+        if p.text("salary_amount").is_none() {
+            p.error("salary_amount", "can't be empty".to_string());
+        }
+        if from.is_none() && p.text("salary_from").is_none() {
+            p.error("salary_from", "can't be empty when there is no hire_date".to_string());
+        }
+        if let (Some(amount), Some(from)) = (amount, from) {
+            let salary = SalaryDTO {
+                id: None,
+                employee_id: employee.id,
+                from_date: from,
+                to_date: to,
+                amount,
+                pay_period,
+                gross,
+                search_string: String::new(),
+                version: None,
+                contract_id: None,
+            };
+            match employee.salaries.iter_mut().find(|s| s.from_date == from) {
+                Some(existing) => {
+                    *existing = SalaryDTO {
+                        id: existing.id,
+                        version: existing.version,
+                        contract_id: existing.contract_id,
+                        search_string: existing.search_string.clone(),
+                        ..salary
+                    }
+                }
+                None => employee.salaries.push(salary),
+            }
+        }
+    }
+
+    if p.given(&CONTACT_DETAILS) {
+        let from = p.date("contact_from").or(employee.hire_date);
+        let to = p.date("contact_to");
+        if from.is_none() && p.text("contact_from").is_none() {
+            p.error("contact_from", "can't be empty when there is no hire_date".to_string());
+        }
+        let country = p
+            .parse("country", |c| c.to_uppercase().parse())
+            .unwrap_or(rules.default_country);
+        let emails: Vec<EmailDTO> = p
+            .text("email")
+            .map(|email| EmailDTO {
+                kind: EmailKind::Work,
+                email,
+            })
+            .into_iter()
+            .collect();
+        let phones: Vec<PhoneDTO> = p
+            .text("phone")
+            .map(|number| PhoneDTO {
+                kind: PhoneKind::Mobile,
+                number,
+            })
+            .into_iter()
+            .collect();
+        let addresses = if p.given(&["street", "city", "postal_code"]) {
+            vec![AddressDTO {
+                kind: AddressKind::Home,
+                street: p.text("street").unwrap_or_default(),
+                city: p.text("city").unwrap_or_default(),
+                postal_code: p.text("postal_code").unwrap_or_default(),
+                country,
+            }]
+        } else {
+            vec![]
+        };
+        if let Some(from) = from {
+            match employee.contacts.iter_mut().find(|c| c.from_date == from) {
+                Some(existing) => {
+                    existing.to_date = to.or(existing.to_date);
+                    existing.emails = emails;
+                    existing.phones = phones;
+                    existing.addresses = addresses;
+                }
+                None => employee.contacts.push(ContactDTO {
+                    id: None,
+                    employee_id: employee.id,
+                    from_date: from,
+                    to_date: to,
+                    emails,
+                    phones,
+                    addresses,
+                    search_string: String::new(),
+                    version: None,
+                }),
+            }
+        }
+    }
+    Ok(p.errors)
+}
+
+/// Import (or with `dry_run` just validate) one row - Ok(true) when employee was created, Ok(false) when updated
+fn import_row(
+    row: &ImportRow,
+    options: &ImportOptions,
+    rules: &ValidationRules,
+    keys: Option<&KeyRing>,
+    conn: &mut DbConnection,
+) -> DaoResult<bool> {
+    let existing = match row.values.get("employee_number").map(|n| n.trim()).filter(|n| !n.is_empty()) {
+        Some(number) => employee_with_number(number, conn)?,
+        None => None,
+    };
+    let mut employee = match existing {
+        Some(e_id) => EmployeeDTO::get_simple(e_id, keys, conn)?,
+        None => EmployeeDTO::default(),
+    };
+    let errors = apply_row(row, &mut employee, options, rules, conn)?;
+    if !errors.is_empty() {
+        return Err(DaoError::Validation(errors));
+    }
+    employee.try_save_in_transaction(rules, keys, conn)?;
+    Ok(existing.is_none())
+}
+
+/// Import rows as employees with their salary and contact - they are saved in transactions of `chunk_size` rows
+/// (all rows in one when `atomic` or `dry_run`) and every row in its own nested one, so failed row is just reported.
+/// Nothing is saved when `dry_run` or when `atomic` and any row failed.
+pub fn import_employees_with_connection(
+    rows: &[ImportRow],
+    options: &ImportOptions,
+    rules: &ValidationRules,
+    keys: Option<&KeyRing>,
+    conn: &mut DbConnection,
+) -> DaoResult<ImportResult> {
+    let mut result = ImportResult {
+        dry_run: options.dry_run,
+        ..Default::default()
+    };
+    let chunk_size = if options.atomic || options.dry_run {
+        rows.len().max(1)
+    } else {
+        options.chunk_size.max(1)
+    };
+    for chunk in rows.chunks(chunk_size) {
+        let mut chunk_result = ImportResult::default();
+        let saved = conn.transaction(|conn| {
+            for row in chunk {
+                match import_row(row, options, rules, keys, conn) {
+                    Ok(true) => chunk_result.created += 1,
+                    Ok(false) => chunk_result.updated += 1,
+                    Err(e @ (DaoError::Pool(_) | DaoError::Storage(_))) => return Err(e),
+                    Err(e) => {
+                        let errors = match e {
+                            DaoError::Validation(errors) => errors,
+                            e => vec![FieldError {
+                                field: String::new(),
+                                message: e.to_string(),
+                            }],
+                        };
+                        chunk_result.failed += 1;
+                        chunk_result.errors.push(ImportRowError {
+                            row: row.row,
+                            employee_number: row
+                                .values
+                                .get("employee_number")
+                                .map(|n| n.trim().to_string())
+                                .filter(|n| !n.is_empty()),
+                            errors,
+                        });
+                    }
+                }
+            }
+            if options.dry_run || (options.atomic && chunk_result.failed > 0) {
+                return Err(DaoError::Query(diesel::result::Error::RollbackTransaction));
+            }
+            Ok(())
+        });
+        match saved {
+            Ok(()) | Err(DaoError::Query(diesel::result::Error::RollbackTransaction)) => {}
+            Err(e) => return Err(e),
+        }
+        if options.atomic && !options.dry_run && chunk_result.failed > 0 {
+            chunk_result.created = 0;
+            chunk_result.updated = 0;
+        }
+        result.created += chunk_result.created;
+        result.updated += chunk_result.updated;
+        result.failed += chunk_result.failed;
+        result.errors.append(&mut chunk_result.errors);
+    }
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::common_for_tests::*;
+    use crate::Searchable;
+
+    use super::*;
+
+    fn row(number: usize, values: &[(&str, &str)]) -> ImportRow {
+        ImportRow {
+            row: number,
+            values: values.iter().map(|(f, v)| (f.to_string(), v.to_string())).collect(),
+        }
+    }
+
+    fn kowalski(number: usize, amount: &str) -> ImportRow {
+        row(
+            number,
+            &[
+                ("employee_number", "S-1"),
+                ("first_name", "Jan"),
+                ("last_name", "Kowalski"),
+                ("hire_date", "2022-01-01"),
+                ("salary_amount", amount),
+                ("email", "jan@example.com"),
+                ("phone", "601 234 567"),
+            ],
+        )
+    }
+
+    #[test]
+    fn rows_are_imported_as_employees() {
+        let conn = &mut initialize();
+        let rules = ValidationRules::default();
+        let rows = vec![
+            kowalski(2, "5000"),
+            row(
+                3,
+                &[
+                    ("employee_number", "S-2"),
+                    ("first_name", "Anna"),
+                    ("last_name", "Nowak"),
+                    ("manager_number", "S-1"),
+                    ("salary_amount", "6000.50"),
+                    ("salary_from", "44927"),
+                    ("salary_currency", "eur"),
+                    ("street", "Marszałkowska 1"),
+                    ("city", "Warszawa"),
+                    ("postal_code", "00-950"),
+                    ("contact_from", "2023-01-01"),
+                ],
+            ),
+            row(4, &[("first_name", "Bez"), ("hire_date", "01.01.2022"), ("status", "retired")]),
+        ];
+
+        let dry_run = ImportOptions {
+            dry_run: true,
+            ..Default::default()
+        };
+        let result = import_employees_with_connection(&rows, &dry_run, &rules, keys(), conn).unwrap();
+        assert_eq!((result.created, result.updated, result.failed), (2, 0, 1));
+        assert!(result.dry_run);
+        assert!(
+            EmployeeDTO::get_all_with_connection(keys(), conn).unwrap().is_empty(),
+            "Dry run shouldn't save anything"
+        );
+        assert_eq!(result.errors[0].row, 4);
+        let fields: Vec<&str> = result.errors[0].errors.iter().map(|e| e.field.as_str()).collect();
+        assert_eq!(fields, vec!["last_name", "hire_date", "status"]);
+
+        let atomic = ImportOptions {
+            atomic: true,
+            ..Default::default()
+        };
+        let result = import_employees_with_connection(&rows, &atomic, &rules, keys(), conn).unwrap();
+        assert_eq!((result.created, result.updated, result.failed), (0, 0, 1));
+        let employees = EmployeeDTO::get_all_with_connection(keys(), conn).unwrap();
+        assert!(employees.is_empty(), "Failed row should roll back all of them");
+
+        let chunked = ImportOptions {
+            chunk_size: 1,
+            ..Default::default()
+        };
+        let result = import_employees_with_connection(&rows, &chunked, &rules, keys(), conn).unwrap();
+        assert_eq!((result.created, result.updated, result.failed), (2, 0, 1));
+        let employees = EmployeeDTO::get_all_with_connection(keys(), conn).unwrap();
+        assert_eq!(employees.len(), 2);
+        let jan = EmployeeDTO::get_simple(employees[0].id.unwrap(), keys(), conn).unwrap();
+        assert_eq!(jan.salaries[0].amount, Money::new(500000, Currency::PLN));
+        assert_eq!(jan.salaries[0].from_date, NaiveDate::from_ymd_opt(2022, 1, 1).unwrap());
+        assert_eq!(jan.contacts[0].phones[0].number, "+48601234567");
+        let anna = EmployeeDTO::get_simple(employees[1].id.unwrap(), keys(), conn).unwrap();
+        assert_eq!(anna.manager_id, jan.id);
+        assert_eq!(anna.salaries[0].amount, Money::new(600050, Currency::EUR));
+        assert_eq!(anna.salaries[0].from_date, NaiveDate::from_ymd_opt(2023, 1, 1).unwrap());
+        assert_eq!(anna.contacts[0].addresses[0].city, "Warszawa");
+
+        // The same employee number updates employee - salary starting the same day is replaced
+        let result = import_employees_with_connection(&[kowalski(2, "5500")], &Default::default(), &rules, keys(), conn)
+            .unwrap();
+        assert_eq!((result.created, result.updated, result.failed), (0, 1, 0));
+        let updated = EmployeeDTO::get_simple(jan.id.unwrap(), keys(), conn).unwrap();
+        assert_eq!(updated.salaries.len(), 1);
+        assert_eq!(updated.salaries[0].amount, Money::new(550000, Currency::PLN));
+        assert_eq!(updated.contacts.len(), 1);
+
+        let invalid_email = row(2, &[("employee_number", "S-1"), ("email", "not an email")]);
+        let result =
+            import_employees_with_connection(&[invalid_email], &Default::default(), &rules, keys(), conn).unwrap();
+        assert_eq!(result.failed, 1);
+        assert_eq!(result.errors[0].employee_number, Some("S-1".to_string()));
+        assert_eq!(result.errors[0].errors[0].field, "contacts[0].emails[0].email");
+    }
+
+    #[test]
+    fn dry_run_writes_nothing() {
+        use crate::schema::sequences::dsl as s;
+
+        let conn = &mut initialize();
+        let rules = ValidationRules::default();
+        import_employees_with_connection(&[kowalski(2, "5000")], &Default::default(), &rules, keys(), conn).unwrap();
+        let sequence = || s::sequences.filter(s::name.eq("employee_number")).select(s::value);
+        let jan_id = EmployeeDTO::get_all_with_connection(keys(), conn).unwrap()[0].id.unwrap();
+        let jan = serde_json::to_value(EmployeeDTO::get_simple(jan_id, keys(), conn).unwrap()).unwrap();
+        let number: Option<i32> = sequence().first(conn).optional().unwrap();
+
+        let rows = vec![
+            row(2, &[("employee_number", "S-1"), ("last_name", "Kowalczyk"), ("salary_amount", "9000")]),
+            row(
+                3,
+                &[
+                    ("first_name", "Anna"),
+                    ("last_name", "Nowak"),
+                    ("hire_date", "2023-01-01"),
+                    ("manager_number", "S-1"),
+                    ("email", "anna@example.com"),
+                ],
+            ),
+        ];
+        let dry_run = ImportOptions {
+            dry_run: true,
+            ..Default::default()
+        };
+        let result = import_employees_with_connection(&rows, &dry_run, &rules, keys(), conn).unwrap();
+        assert_eq!((result.created, result.updated, result.failed), (1, 1, 0));
+
+        assert_eq!(EmployeeDTO::get_all_with_connection(keys(), conn).unwrap().len(), 1);
+        let after = serde_json::to_value(EmployeeDTO::get_simple(jan_id, keys(), conn).unwrap()).unwrap();
+        assert_eq!(after, jan, "Updated employee should be kept as it was");
+        assert_eq!(
+            sequence().first(conn).optional().unwrap(),
+            number,
+            "Employee number shouldn't be used up"
+        );
+    }
+
+    #[test]
+    fn failed_row_is_rolled_back_without_the_rest_of_its_chunk() {
+        let conn = &mut initialize();
+        let rules = ValidationRules::default();
+        let employee = |number: usize, last_name: &str, manager: &str| {
+            let employee_number = format!("S-{}", number);
+            let mut values = vec![
+                ("employee_number", employee_number.as_str()),
+                ("first_name", "Jan"),
+                ("last_name", last_name),
+                ("hire_date", "2022-01-01"),
+                ("salary_amount", "5000"),
+            ];
+            if !manager.is_empty() {
+                values.push(("manager_number", manager));
+            }
+            row(number + 1, &values)
+        };
+        // Chunks: S-1, S-2 | S-3 (no last name), S-4 | S-5 (reporting to S-3), S-6
+        let rows = vec![
+            employee(1, "Kowalski", ""),
+            employee(2, "Nowak", "S-1"),
+            employee(3, "", ""),
+            employee(4, "Wiśniewski", "S-2"),
+            employee(5, "Zieliński", "S-3"),
+            employee(6, "Wójcik", "S-4"),
+        ];
+        let chunked = ImportOptions {
+            chunk_size: 2,
+            ..Default::default()
+        };
+        let result = import_employees_with_connection(&rows, &chunked, &rules, keys(), conn).unwrap();
+        assert_eq!((result.created, result.updated, result.failed), (4, 0, 2));
+        let failed: Vec<usize> = result.errors.iter().map(|e| e.row).collect();
+        assert_eq!(failed, vec![4, 6]);
+        assert_eq!(result.errors[1].errors[0].field, "manager_number", "Failed row shouldn't be saved");
+
+        let employees = EmployeeDTO::get_all_with_connection(keys(), conn).unwrap();
+        let numbers: Vec<&str> = employees.iter().filter_map(|e| e.employee_number.as_deref()).collect();
+        assert_eq!(numbers, vec!["S-1", "S-2", "S-4", "S-6"]);
+        for e in &employees {
+            let saved = EmployeeDTO::get_simple(e.id.unwrap(), keys(), conn).unwrap();
+            assert_eq!(saved.salaries.len(), 1, "Salary of {:?} should be saved", e.employee_number);
+        }
+    }
+}
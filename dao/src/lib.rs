@@ -5,8 +5,6 @@ extern crate diesel;
 extern crate diesel_migrations;
 extern crate dotenv;
 #[macro_use]
-extern crate lazy_static;
-#[macro_use]
 extern crate log;
 extern crate log4rs;
 extern crate monitor;
@@ -16,50 +14,87 @@ extern crate serde;
 extern crate serde_derive;
 extern crate sha3;
 
-use diesel::QueryResult;
-
-pub use base_dao::{Crud, Searchable, SearchableByParent};
-pub use connection::{get_connection, initialize_db, MIGRATIONS};
-pub use employees_dao::EmployeeDTO;
+pub use absence_types_dao::AbsenceTypeDTO;
+pub use absences_dao::{calendar, calendar_with_connection, working_days, AbsenceBalance, AbsenceDTO, AbsenceStatus, CalendarEntry};
+pub use audit_dao::{AuditAction, AuditEntryDTO};
+pub use base_dao::{Crud, Searchable, SearchableByDate, SearchableByParent};
+pub use connection::{Database, DbConfig, DbConnection, PooledConnection, SqliteConfig, MIGRATIONS};
+pub use contacts_dao::{AddressDTO, AddressKind, ContactDTO, EmailDTO, EmailKind, PhoneDTO, PhoneKind};
+pub use country::Country;
+pub use custom_fields_dao::{CustomFieldDTO, CustomFieldRules, CustomFieldType};
+pub use contracts_dao::{ContractDTO, ContractType, WorkingTime};
+pub use departments_dao::DepartmentDTO;
+pub use documents_dao::{Confidentiality, DocumentDTO, DocumentType, DocumentUpload};
+pub use crypto::{EncryptionKey, KeyRing, DEFAULT_KEY_ID};
+pub use employee_groups_dao::EmployeeGroupDTO;
+pub use employee_number::EmployeeNumberFormat;
+pub use employees_dao::{EmergencyContactDTO, EmployeeDTO, EmployeeSearch, EmploymentStatus};
+pub use error::{ConfigError, DaoError, DaoResult};
+pub use hierarchy::{can_approve, org_chart, org_chart_with_connection, EmployeeScope, OrgChartNode};
+pub use import_dao::{
+    import_employees_with_connection, ImportOptions, ImportResult, ImportRow, ImportRowError, IMPORT_FIELDS,
+};
 pub use models::*;
+pub use money::{Currency, Money, PayPeriod};
+pub use page::{Page, PageRequest};
+pub use payroll_dao::{
+    apply_rules, calculate_payslips, LineKind, PayrollRule, PayrollRunDTO, PayrollVerification, PayslipDTO,
+    PayslipLineDTO, RunStatus,
+};
+pub use payroll_rules_dao::{PayrollRuleDTO, RuleKind};
+pub use personal_data::{
+    anonymize_employee_with_connection, personal_data_export_with_connection, PersonalDataExport, ANONYMIZED_NAME,
+};
+pub use photos_dao::{PhotoDTO, THUMBNAIL_SIZES};
+pub use positions_dao::PositionDTO;
+pub use reports_dao::{
+    salary_report, salary_report_with_connection, MonthlyCost, PayRaise, ReportGroup, ReportGrouping, SalaryReport,
+    SalaryReportParams, SalaryStatistics,
+};
+pub use salaries_dao::SalaryDTO;
+pub use storage::{DocumentStorage, DocumentsConfig, LocalStorage, S3Config, StorageConfig, StorageError};
+pub use tags_dao::{change_tags_with_connection, get_tags, get_tags_with_connection, TagDTO, TagsChange, MAX_TAG_LENGTH};
+pub use timesheets_dao::{
+    monthly_summary, monthly_summary_with_connection, timesheet_rows, timesheet_rows_with_connection, week_start,
+    CategoryHours, Hours, TimesheetEntryDTO, TimesheetRow, TimesheetSummary, TimesheetWeekDTO, WeekStatus,
+};
+pub use users_dao::{create_user, delete_user, get_user, get_users, update_user, validate_user};
+pub use validation::{FieldError, ValidationRules};
 
+mod absence_types_dao;
+mod absences_dao;
+mod audit_dao;
 mod base_dao;
 #[cfg(test)]
 mod common_for_tests;
 mod connection;
 mod contacts_dao;
+mod contracts_dao;
+mod country;
+mod crypto;
+mod custom_fields_dao;
+mod departments_dao;
+mod documents_dao;
+mod employee_groups_dao;
+mod employee_number;
 mod employees_dao;
+mod error;
+mod hierarchy;
+mod import_dao;
 mod models;
+mod money;
+mod page;
+mod payroll_dao;
+mod payroll_rules_dao;
+mod personal_data;
+mod photos_dao;
+mod positions_dao;
+mod reports_dao;
+mod s3_storage;
 mod salaries_dao;
 mod schema;
+mod storage;
+mod tags_dao;
+mod timesheets_dao;
 mod users_dao;
-
-pub fn create_user(new_user: &NewUser) -> QueryResult<User> {
-    let mut conn = get_connection();
-    users_dao::create_user(new_user, &mut conn)
-}
-
-pub fn update_user(user: &User) -> QueryResult<User> {
-    let mut conn = get_connection();
-    users_dao::update_user(user, &mut conn)
-}
-
-pub fn delete_user(user: &User) -> QueryResult<usize> {
-    let mut conn = get_connection();
-    users_dao::delete_user(user, &mut conn)
-}
-
-pub fn get_users() -> Vec<User> {
-    let mut conn = get_connection();
-    users_dao::get_users(&mut conn)
-}
-
-pub fn get_user(id: i32) -> Option<User> {
-    let mut conn = get_connection();
-    users_dao::get_user(id, &mut conn)
-}
-
-pub fn validate_user(username: &String, password: &String) -> Option<User> {
-    let mut conn = get_connection();
-    users_dao::validate_user(username, password, &mut conn)
-}
+mod validation;
@@ -1,81 +1,664 @@
-use chrono::NaiveDate;
+use chrono::{NaiveDate, NaiveDateTime};
 
-use crate::schema::{contacts, employees, salaries, users};
+use crate::schema::{
+    absence_types, absences, audit_log, contact_addresses, contact_emails, contact_phones, contacts, custom_field_values, custom_fields, departments, documents, emergency_contacts, employee_groups, employee_photos, employee_tags, employees, employment_contracts, payroll_rules, payroll_runs,
+    payslip_lines, payslips, positions, salaries, tags, timesheet_entries, timesheet_weeks, users,
+};
 
 #[derive(Queryable, AsChangeset, Debug, Serialize, Clone)]
+#[diesel(treat_none_as_null = true)]
 pub struct User {
     pub id: i32,
     pub username: String,
     pub password: String,
     pub is_admin: bool,
+    #[diesel(skip_update)]
+    pub version: i32,
+    /// Employee the user is - such user see only the employee and its (recursive) reports
+    pub employee_id: Option<i32>,
+    /// Can export and anonymize personal data of employees (GDPR)
+    pub privacy_officer: bool,
 }
 
 #[derive(Insertable, Debug, Clone)]
-#[table_name = "users"]
+#[diesel(table_name = users)]
 pub struct NewUser {
     pub username: String,
     pub password: String,
     pub is_admin: bool,
+    pub employee_id: Option<i32>,
+    pub privacy_officer: bool,
 }
 
 #[derive(Queryable, AsChangeset, Debug, Serialize, Identifiable, Clone)]
+#[diesel(table_name = departments, treat_none_as_null = true)]
+pub struct Department {
+    pub id: i32,
+    pub name: String,
+    pub parent_id: Option<i32>,
+    pub search_string: String,
+    #[diesel(skip_update)]
+    pub version: i32,
+}
+
+#[derive(Insertable, Debug, Clone)]
+#[diesel(table_name = departments)]
+pub struct NewDepartment {
+    pub name: String,
+    pub parent_id: Option<i32>,
+    pub search_string: String,
+}
+
+#[derive(Queryable, AsChangeset, Debug, Serialize, Identifiable, Clone)]
+#[diesel(treat_none_as_null = true)]
 pub struct Employee {
     pub id: i32,
     pub first_name: String,
     pub last_name: String,
     pub search_string: String,
+    #[diesel(skip_update)]
+    pub version: i32,
+    pub department_id: Option<i32>,
+    pub manager_id: Option<i32>,
+    pub employee_number: Option<String>,
+    pub date_of_birth: Option<NaiveDate>,
+    /// Encrypted (see crypto::encrypt())
+    pub national_id: Option<String>,
+    /// Blind index of national ID - search by it without decrypting
+    pub national_id_hash: Option<String>,
+    pub hire_date: Option<NaiveDate>,
+    pub termination_date: Option<NaiveDate>,
+    pub status: String,
+    /// Set by soft delete - row is purged once retention period passes
+    #[diesel(skip_update)]
+    pub deleted_at: Option<NaiveDateTime>,
+    #[diesel(skip_update)]
+    pub deleted_by: Option<i32>,
+    /// Set once personal data of employee are anonymized
+    #[diesel(skip_update)]
+    pub anonymized_at: Option<NaiveDateTime>,
 }
 
 #[derive(Insertable, Debug, Clone)]
-#[table_name = "employees"]
+#[diesel(table_name = employees)]
 pub struct NewEmployee {
     pub first_name: String,
     pub last_name: String,
     pub search_string: String,
+    pub department_id: Option<i32>,
+    pub manager_id: Option<i32>,
+    pub employee_number: Option<String>,
+    pub date_of_birth: Option<NaiveDate>,
+    pub national_id: Option<String>,
+    pub national_id_hash: Option<String>,
+    pub hire_date: Option<NaiveDate>,
+    pub termination_date: Option<NaiveDate>,
+    pub status: String,
+}
+
+#[derive(Queryable, Debug, Serialize, Associations, Identifiable, Clone)]
+#[diesel(belongs_to(Employee))]
+#[diesel(table_name = emergency_contacts)]
+pub struct EmergencyContact {
+    pub id: i32,
+    pub employee_id: i32,
+    pub position: i32,
+    pub name: String,
+    pub relationship: String,
+    /// E.164 - "+48601234567" (encrypted when `encrypted`)
+    pub phone: String,
+    pub email: Option<String>,
+    pub encrypted: bool,
+}
+
+#[derive(Insertable, Debug, Clone)]
+#[diesel(table_name = emergency_contacts)]
+pub struct NewEmergencyContact {
+    pub employee_id: i32,
+    pub position: i32,
+    pub name: String,
+    pub relationship: String,
+    pub phone: String,
+    pub email: Option<String>,
+    pub encrypted: bool,
+}
+
+#[derive(Queryable, AsChangeset, Debug, Serialize, Identifiable, Clone)]
+#[diesel(table_name = positions)]
+pub struct Position {
+    pub id: i32,
+    pub name: String,
+    pub search_string: String,
+    #[diesel(skip_update)]
+    pub version: i32,
+}
+
+#[derive(Insertable, Debug, Clone)]
+#[diesel(table_name = positions)]
+pub struct NewPosition {
+    pub name: String,
+    pub search_string: String,
 }
 
 #[derive(Queryable, AsChangeset, Debug, Serialize, Associations, Identifiable, Clone)]
-#[belongs_to(Employee)]
-#[table_name = "salaries"]
+#[diesel(belongs_to(Employee))]
+#[diesel(table_name = employment_contracts, treat_none_as_null = true)]
+pub struct EmploymentContract {
+    pub id: i32,
+    pub employee_id: i32,
+    pub position_id: Option<i32>,
+    pub contract_type: String,
+    pub from_date: NaiveDate,
+    pub to_date: Option<NaiveDate>,
+    pub working_time_numerator: i32,
+    pub working_time_denominator: i32,
+    pub search_string: String,
+    #[diesel(skip_update)]
+    pub version: i32,
+}
+
+#[derive(Insertable, Debug, Clone)]
+#[diesel(table_name = employment_contracts)]
+pub struct NewEmploymentContract {
+    pub employee_id: i32,
+    pub position_id: Option<i32>,
+    pub contract_type: String,
+    pub from_date: NaiveDate,
+    pub to_date: Option<NaiveDate>,
+    pub working_time_numerator: i32,
+    pub working_time_denominator: i32,
+    pub search_string: String,
+}
+
+#[derive(Queryable, AsChangeset, Debug, Serialize, Associations, Identifiable, Clone)]
+#[diesel(belongs_to(Employee))]
+#[diesel(table_name = salaries, treat_none_as_null = true)]
 pub struct Salary {
     pub id: i32,
     pub employee_id: i32,
     pub from_date: NaiveDate,
-    pub to_date: NaiveDate,
+    pub to_date: Option<NaiveDate>,
+    /// In minor units of currency
     pub amount: i64,
     pub search_string: String,
+    #[diesel(skip_update)]
+    pub version: i32,
+    pub currency: String,
+    pub pay_period: String,
+    pub gross: bool,
+    /// Contract the salary is paid under - None for salaries from before contracts
+    pub contract_id: Option<i32>,
+    /// Amount encrypted (see crypto::encrypt()) - `amount` is 0 then
+    pub amount_encrypted: Option<String>,
 }
 
 #[derive(Insertable, Debug, Clone)]
-#[table_name = "salaries"]
+#[diesel(table_name = salaries)]
 pub struct NewSalary {
     pub employee_id: i32,
     pub from_date: NaiveDate,
-    pub to_date: NaiveDate,
+    pub to_date: Option<NaiveDate>,
     pub amount: i64,
     pub search_string: String,
+    pub currency: String,
+    pub pay_period: String,
+    pub gross: bool,
+    /// Contract the salary is paid under - None for salaries from before contracts
+    pub contract_id: Option<i32>,
+    /// Amount encrypted (see crypto::encrypt()) - `amount` is 0 then
+    pub amount_encrypted: Option<String>,
 }
 
 #[derive(Queryable, AsChangeset, Debug, Serialize, Associations, Identifiable, Clone)]
-#[belongs_to(Employee)]
-#[table_name = "contacts"]
+#[diesel(belongs_to(Employee))]
+#[diesel(table_name = contacts, treat_none_as_null = true)]
 pub struct Contact {
     pub id: i32,
     pub employee_id: i32,
     pub from_date: NaiveDate,
-    pub to_date: NaiveDate,
-    pub phone: String,
-    pub address: Option<String>,
+    pub to_date: Option<NaiveDate>,
     pub search_string: String,
+    #[diesel(skip_update)]
+    pub version: i32,
 }
 
 #[derive(Insertable, Debug, Clone)]
-#[table_name = "contacts"]
+#[diesel(table_name = contacts)]
 pub struct NewContact {
     pub employee_id: i32,
     pub from_date: NaiveDate,
+    pub to_date: Option<NaiveDate>,
+    pub search_string: String,
+}
+
+#[derive(Queryable, Debug, Serialize, Associations, Identifiable, Clone)]
+#[diesel(belongs_to(Contact))]
+#[diesel(table_name = contact_emails)]
+pub struct ContactEmail {
+    pub id: i32,
+    pub contact_id: i32,
+    /// Order of email in contact
+    pub position: i32,
+    pub kind: String,
+    pub email: String,
+}
+
+#[derive(Insertable, Debug, Clone)]
+#[diesel(table_name = contact_emails)]
+pub struct NewContactEmail {
+    pub contact_id: i32,
+    pub position: i32,
+    pub kind: String,
+    pub email: String,
+}
+
+#[derive(Queryable, Debug, Serialize, Associations, Identifiable, Clone)]
+#[diesel(belongs_to(Contact))]
+#[diesel(table_name = contact_phones)]
+pub struct ContactPhone {
+    pub id: i32,
+    pub contact_id: i32,
+    pub position: i32,
+    pub kind: String,
+    /// E.164 - "+48601234567" or encrypted (see crypto::encrypt())
+    pub number: String,
+    pub encrypted: bool,
+    /// Blind index of encrypted number - search by it without decrypting
+    pub number_index: Option<String>,
+}
+
+#[derive(Insertable, Debug, Clone)]
+#[diesel(table_name = contact_phones)]
+pub struct NewContactPhone {
+    pub contact_id: i32,
+    pub position: i32,
+    pub kind: String,
+    pub number: String,
+    pub encrypted: bool,
+    pub number_index: Option<String>,
+}
+
+#[derive(Queryable, Debug, Serialize, Associations, Identifiable, Clone)]
+#[diesel(belongs_to(Contact))]
+#[diesel(table_name = contact_addresses)]
+pub struct ContactAddress {
+    pub id: i32,
+    pub contact_id: i32,
+    pub position: i32,
+    pub kind: String,
+    pub street: String,
+    pub city: String,
+    pub postal_code: String,
+    /// Empty for addresses not yet converted from free text (see convert_legacy_contacts())
+    pub country: String,
+    /// Street, city and postal code are encrypted (see crypto::encrypt())
+    pub encrypted: bool,
+}
+
+#[derive(Insertable, Debug, Clone)]
+#[diesel(table_name = contact_addresses)]
+pub struct NewContactAddress {
+    pub contact_id: i32,
+    pub position: i32,
+    pub kind: String,
+    pub street: String,
+    pub city: String,
+    pub postal_code: String,
+    pub country: String,
+    pub encrypted: bool,
+}
+
+#[derive(Queryable, AsChangeset, Debug, Serialize, Identifiable, Clone)]
+#[diesel(table_name = absence_types, treat_none_as_null = true)]
+pub struct AbsenceType {
+    pub id: i32,
+    pub name: String,
+    pub yearly_entitlement: Option<i32>,
+    pub carry_over_limit: i32,
+    pub search_string: String,
+    #[diesel(skip_update)]
+    pub version: i32,
+}
+
+#[derive(Insertable, Debug, Clone)]
+#[diesel(table_name = absence_types)]
+pub struct NewAbsenceType {
+    pub name: String,
+    pub yearly_entitlement: Option<i32>,
+    pub carry_over_limit: i32,
+    pub search_string: String,
+}
+
+#[derive(Queryable, AsChangeset, Debug, Serialize, Associations, Identifiable, Clone)]
+#[diesel(belongs_to(Employee))]
+#[diesel(table_name = absences, treat_none_as_null = true)]
+pub struct Absence {
+    pub id: i32,
+    pub employee_id: i32,
+    pub absence_type_id: i32,
+    pub from_date: NaiveDate,
     pub to_date: NaiveDate,
-    pub phone: String,
-    pub address: Option<String>,
+    /// Working days (Monday - Friday) of absence
+    pub days: i32,
+    pub status: String,
+    pub decided_by: Option<i32>,
+    pub comment: Option<String>,
+    pub search_string: String,
+    #[diesel(skip_update)]
+    pub version: i32,
+}
+
+#[derive(Insertable, Debug, Clone)]
+#[diesel(table_name = absences)]
+pub struct NewAbsence {
+    pub employee_id: i32,
+    pub absence_type_id: i32,
+    pub from_date: NaiveDate,
+    pub to_date: NaiveDate,
+    pub days: i32,
+    pub status: String,
+    pub decided_by: Option<i32>,
+    pub comment: Option<String>,
+    pub search_string: String,
+}
+
+#[derive(Queryable, AsChangeset, Debug, Serialize, Associations, Identifiable, Clone)]
+#[diesel(belongs_to(Employee))]
+#[diesel(table_name = timesheet_entries, treat_none_as_null = true)]
+pub struct TimesheetEntry {
+    pub id: i32,
+    pub employee_id: i32,
+    pub work_date: NaiveDate,
+    /// Worked time in minutes
+    pub minutes: i32,
+    pub category: String,
+    pub note: Option<String>,
+    pub search_string: String,
+    #[diesel(skip_update)]
+    pub version: i32,
+}
+
+#[derive(Insertable, Debug, Clone)]
+#[diesel(table_name = timesheet_entries)]
+pub struct NewTimesheetEntry {
+    pub employee_id: i32,
+    pub work_date: NaiveDate,
+    pub minutes: i32,
+    pub category: String,
+    pub note: Option<String>,
+    pub search_string: String,
+}
+
+#[derive(Queryable, Debug, Serialize, Identifiable, Clone)]
+#[diesel(table_name = timesheet_weeks)]
+pub struct TimesheetWeek {
+    pub id: i32,
+    pub employee_id: i32,
+    /// Monday of the week
+    pub week_start: NaiveDate,
+    pub status: String,
+    pub decided_by: Option<i32>,
+    pub version: i32,
+}
+
+#[derive(Queryable, AsChangeset, Debug, Serialize, Identifiable, Clone)]
+#[diesel(table_name = payroll_rules, treat_none_as_null = true)]
+pub struct PayrollRule {
+    pub id: i32,
+    pub name: String,
+    pub kind: String,
+    /// In basis points (1/100 of percent)
+    pub percent: Option<i32>,
+    /// In minor units of currency
+    pub amount: Option<i64>,
+    pub currency: Option<String>,
+    pub active: bool,
+    pub search_string: String,
+    #[diesel(skip_update)]
+    pub version: i32,
+}
+
+#[derive(Insertable, Debug, Clone)]
+#[diesel(table_name = payroll_rules)]
+pub struct NewPayrollRule {
+    pub name: String,
+    pub kind: String,
+    pub percent: Option<i32>,
+    pub amount: Option<i64>,
+    pub currency: Option<String>,
+    pub active: bool,
+    pub search_string: String,
+}
+
+#[derive(Queryable, Debug, Serialize, Identifiable, Clone)]
+#[diesel(table_name = payroll_runs)]
+pub struct PayrollRun {
+    pub id: i32,
+    pub year: i32,
+    pub month: i32,
+    pub status: String,
+    pub approved_by: Option<i32>,
+    /// JSON snapshot of rules the run was calculated with
+    pub rules: String,
+    pub version: i32,
+}
+
+#[derive(Queryable, Debug, Serialize, Associations, Identifiable, Clone)]
+#[diesel(belongs_to(PayrollRun))]
+#[diesel(table_name = payslips)]
+pub struct Payslip {
+    pub id: i32,
+    pub payroll_run_id: i32,
+    pub employee_id: i32,
+    pub first_name: String,
+    pub last_name: String,
+    pub currency: String,
+    /// In minor units of currency
+    pub gross: i64,
+    pub deductions: i64,
+    pub net: i64,
+    /// Amounts encrypted (see crypto::encrypt()) - plain columns are 0 then
+    pub gross_encrypted: Option<String>,
+    pub deductions_encrypted: Option<String>,
+    pub net_encrypted: Option<String>,
+}
+
+#[derive(Insertable, Debug, Clone)]
+#[diesel(table_name = payslips)]
+pub struct NewPayslip {
+    pub payroll_run_id: i32,
+    pub employee_id: i32,
+    pub first_name: String,
+    pub last_name: String,
+    pub currency: String,
+    pub gross: i64,
+    pub deductions: i64,
+    pub net: i64,
+    pub gross_encrypted: Option<String>,
+    pub deductions_encrypted: Option<String>,
+    pub net_encrypted: Option<String>,
+}
+
+#[derive(Queryable, Debug, Serialize, Associations, Identifiable, Clone)]
+#[diesel(belongs_to(Payslip))]
+#[diesel(table_name = payslip_lines)]
+pub struct PayslipLine {
+    pub id: i32,
+    pub payslip_id: i32,
+    /// Order of line in payslip
+    pub position: i32,
+    pub kind: String,
+    pub description: String,
+    pub amount: i64,
+    pub salary_id: Option<i32>,
+    pub gross: bool,
+    /// Amount encrypted (see crypto::encrypt()) - `amount` is 0 then
+    pub amount_encrypted: Option<String>,
+}
+
+#[derive(Insertable, Debug, Clone)]
+#[diesel(table_name = payslip_lines)]
+pub struct NewPayslipLine {
+    pub payslip_id: i32,
+    pub position: i32,
+    pub kind: String,
+    pub description: String,
+    pub amount: i64,
+    pub salary_id: Option<i32>,
+    pub gross: bool,
+    pub amount_encrypted: Option<String>,
+}
+
+#[derive(Queryable, Debug, Serialize, Clone)]
+#[diesel(table_name = audit_log)]
+pub struct AuditEntry {
+    pub id: i32,
+    pub at: NaiveDateTime,
+    pub user_id: Option<i32>,
+    pub employee_id: Option<i32>,
+    pub action: String,
+    pub details: String,
+}
+
+#[derive(Insertable, Debug, Clone)]
+#[diesel(table_name = audit_log)]
+pub struct NewAuditEntry {
+    pub at: NaiveDateTime,
+    pub user_id: Option<i32>,
+    pub employee_id: Option<i32>,
+    pub action: String,
+    pub details: String,
+}
+
+#[derive(Queryable, AsChangeset, Debug, Serialize, Identifiable, Clone)]
+#[diesel(table_name = custom_fields)]
+pub struct CustomField {
+    pub id: i32,
+    pub name: String,
+    pub field_type: String,
+    pub required: bool,
+    /// JSON of CustomFieldRules
+    pub rules: String,
+    pub search_string: String,
+    #[diesel(skip_update)]
+    pub version: i32,
+}
+
+#[derive(Insertable, Debug, Clone)]
+#[diesel(table_name = custom_fields)]
+pub struct NewCustomField {
+    pub name: String,
+    pub field_type: String,
+    pub required: bool,
+    pub rules: String,
     pub search_string: String,
 }
+
+#[derive(Insertable, Debug, Clone)]
+#[diesel(table_name = custom_field_values)]
+pub struct NewCustomFieldValue {
+    pub employee_id: i32,
+    pub custom_field_id: i32,
+    /// Normalized text of value - see CustomFieldDTO::stored()
+    pub value: String,
+}
+
+#[derive(Queryable, Identifiable, Debug, Clone)]
+#[diesel(table_name = tags)]
+pub struct Tag {
+    pub id: i32,
+    pub name: String,
+}
+
+#[derive(Insertable, Debug, Clone)]
+#[diesel(table_name = tags)]
+pub struct NewTag {
+    pub name: String,
+}
+
+#[derive(Insertable, Debug, Clone)]
+#[diesel(table_name = employee_tags)]
+pub struct NewEmployeeTag {
+    pub employee_id: i32,
+    pub tag_id: i32,
+}
+
+#[derive(Queryable, AsChangeset, Debug, Serialize, Identifiable, Clone)]
+#[diesel(table_name = employee_groups)]
+pub struct EmployeeGroup {
+    pub id: i32,
+    pub name: String,
+    /// JSON of EmployeeSearch
+    pub filter: String,
+    pub search_string: String,
+    #[diesel(skip_update)]
+    pub version: i32,
+}
+
+#[derive(Insertable, Debug, Clone)]
+#[diesel(table_name = employee_groups)]
+pub struct NewEmployeeGroup {
+    pub name: String,
+    pub filter: String,
+    pub search_string: String,
+}
+
+#[derive(Queryable, Debug, Serialize, Associations, Identifiable, Clone)]
+#[diesel(belongs_to(Employee))]
+pub struct Document {
+    pub id: i32,
+    pub employee_id: i32,
+    pub document_type: String,
+    pub file_name: String,
+    pub content_type: String,
+    pub size: i64,
+    pub sha256: String,
+    pub storage_key: String,
+    pub valid_from: Option<NaiveDate>,
+    pub valid_to: Option<NaiveDate>,
+    pub confidentiality: String,
+    pub uploaded_by: Option<i32>,
+    pub uploaded_at: NaiveDateTime,
+}
+
+#[derive(Insertable, Debug, Clone)]
+#[diesel(table_name = documents)]
+pub struct NewDocument {
+    pub employee_id: i32,
+    pub document_type: String,
+    pub file_name: String,
+    pub content_type: String,
+    pub size: i64,
+    pub sha256: String,
+    pub storage_key: String,
+    pub valid_from: Option<NaiveDate>,
+    pub valid_to: Option<NaiveDate>,
+    pub confidentiality: String,
+    pub uploaded_by: Option<i32>,
+    pub uploaded_at: NaiveDateTime,
+}
+
+#[derive(Queryable, Debug, Serialize, Associations, Identifiable, Clone)]
+#[diesel(belongs_to(Employee))]
+pub struct EmployeePhoto {
+    pub id: i32,
+    pub employee_id: i32,
+    pub content_type: String,
+    pub width: i32,
+    pub height: i32,
+    pub sha256: String,
+    pub uploaded_by: Option<i32>,
+    pub uploaded_at: NaiveDateTime,
+}
+
+#[derive(Insertable, Debug, Clone)]
+#[diesel(table_name = employee_photos)]
+pub struct NewEmployeePhoto {
+    pub employee_id: i32,
+    pub content_type: String,
+    pub width: i32,
+    pub height: i32,
+    pub sha256: String,
+    pub uploaded_by: Option<i32>,
+    pub uploaded_at: NaiveDateTime,
+}
@@ -0,0 +1,257 @@
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// ISO-4217 currency - amounts in this currency are kept as integer number of its minor units
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Currency {
+    code: &'static str,
+    minor_digits: u32,
+}
+
+impl Currency {
+    pub const PLN: Currency = Currency::new("PLN", 2);
+    pub const EUR: Currency = Currency::new("EUR", 2);
+    pub const USD: Currency = Currency::new("USD", 2);
+
+    const fn new(code: &'static str, minor_digits: u32) -> Currency {
+        Currency { code, minor_digits }
+    }
+
+    pub fn code(&self) -> &'static str {
+        self.code
+    }
+
+    /// Number of digits after decimal point (2 for PLN - 1 zloty is 100 grosz)
+    pub fn minor_digits(&self) -> u32 {
+        self.minor_digits
+    }
+
+    /// How many minor units make one major unit
+    pub fn minor_units_per_unit(&self) -> i64 {
+        10i64.pow(self.minor_digits)
+    }
+}
+
+/// Supported currencies
+const CURRENCIES: &[Currency] = &[
+    Currency::PLN,
+    Currency::EUR,
+    Currency::USD,
+    Currency::new("GBP", 2),
+    Currency::new("CHF", 2),
+    Currency::new("CZK", 2),
+    Currency::new("DKK", 2),
+    Currency::new("HUF", 2),
+    Currency::new("NOK", 2),
+    Currency::new("SEK", 2),
+    Currency::new("UAH", 2),
+    Currency::new("JPY", 0),
+];
+
+impl FromStr for Currency {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        CURRENCIES
+            .iter()
+            .find(|c| c.code == s)
+            .copied()
+            .ok_or_else(|| {
+                format!(
+                    "unknown currency '{}' - should be one of {:?}",
+                    s,
+                    CURRENCIES.iter().map(|c| c.code).collect::<Vec<_>>()
+                )
+            })
+    }
+}
+
+impl fmt::Display for Currency {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.code)
+    }
+}
+
+impl Serialize for Currency {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.code)
+    }
+}
+
+impl<'de> Deserialize<'de> for Currency {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let code = String::deserialize(deserializer)?;
+        code.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+/// Exact amount of money - integer number of minor units (e.g. grosz) of currency.
+/// In JSON it is `"amount": "1234.56", "currency": "PLN"` - amount is decimal string so it
+/// never goes through floating point.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[serde(try_from = "RawMoney", into = "RawMoney")]
+pub struct Money {
+    pub minor_units: i64,
+    pub currency: Currency,
+}
+
+impl Money {
+    pub fn new(minor_units: i64, currency: Currency) -> Money {
+        Money {
+            minor_units,
+            currency,
+        }
+    }
+
+    /// Parse decimal string like "-1234.5" - it can't have more fraction digits than currency allow
+    pub fn parse(amount: &str, currency: Currency) -> Result<Money, String> {
+        let invalid = || {
+            format!(
+                "invalid amount '{}' - expected decimal number with at most {} digits after '.'",
+                amount,
+                currency.minor_digits()
+            )
+        };
+        let (negative, unsigned) = match amount.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, amount),
+        };
+        let (units, fraction) = unsigned.split_once('.').unwrap_or((unsigned, ""));
+        let all_digits = |s: &str| s.bytes().all(|b| b.is_ascii_digit());
+        if units.is_empty()
+            || !all_digits(units)
+            || !all_digits(fraction)
+            || (unsigned.contains('.') && fraction.is_empty())
+            || fraction.len() > currency.minor_digits() as usize
+        {
+            return Err(invalid());
+        }
+        let fraction = format!("{:0<width$}", fraction, width = currency.minor_digits() as usize);
+        let minor_units = units
+            .parse::<i64>()
+            .ok()
+            .and_then(|u| u.checked_mul(currency.minor_units_per_unit()))
+            .and_then(|u| u.checked_add(fraction.parse::<i64>().unwrap_or(0)))
+            .ok_or_else(invalid)?;
+        Ok(Money::new(if negative { -minor_units } else { minor_units }, currency))
+    }
+
+    /// Amount as decimal string (without currency) - "1234.56"
+    pub fn amount(&self) -> String {
+        let digits = self.currency.minor_digits() as usize;
+        let sign = if self.minor_units < 0 { "-" } else { "" };
+        let abs = self.minor_units.unsigned_abs();
+        if digits == 0 {
+            return format!("{}{}", sign, abs);
+        }
+        let per_unit = self.currency.minor_units_per_unit() as u64;
+        format!("{}{}.{:0width$}", sign, abs / per_unit, abs % per_unit, width = digits)
+    }
+}
+
+impl fmt::Display for Money {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.amount(), self.currency)
+    }
+}
+
+/// JSON form of Money
+#[derive(Serialize, Deserialize)]
+struct RawMoney {
+    amount: String,
+    currency: Currency,
+}
+
+impl TryFrom<RawMoney> for Money {
+    type Error = String;
+
+    fn try_from(raw: RawMoney) -> Result<Self, Self::Error> {
+        Money::parse(&raw.amount, raw.currency)
+    }
+}
+
+impl From<Money> for RawMoney {
+    fn from(m: Money) -> Self {
+        RawMoney {
+            amount: m.amount(),
+            currency: m.currency,
+        }
+    }
+}
+
+/// Period salary amount is paid for
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum PayPeriod {
+    Hourly,
+    Monthly,
+    Yearly,
+}
+
+impl PayPeriod {
+    /// How it is stored in DB
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PayPeriod::Hourly => "hourly",
+            PayPeriod::Monthly => "monthly",
+            PayPeriod::Yearly => "yearly",
+        }
+    }
+}
+
+impl FromStr for PayPeriod {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "hourly" => Ok(PayPeriod::Hourly),
+            "monthly" => Ok(PayPeriod::Monthly),
+            "yearly" => Ok(PayPeriod::Yearly),
+            _ => Err(format!(
+                "unknown pay period '{}' - should be one of hourly, monthly, yearly",
+                s
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn amount_is_exact_decimal_string() {
+        let pln = |s: &str| Money::parse(s, Currency::PLN).map(|m| m.minor_units);
+        assert_eq!(pln("1234.56"), Ok(123456));
+        assert_eq!(pln("1234.5"), Ok(123450));
+        assert_eq!(pln("1234"), Ok(123400));
+        assert_eq!(pln("-0.01"), Ok(-1));
+        assert_eq!(pln("0.10"), Ok(10));
+        for invalid in ["", "-", "1.", ".5", "1.234", "1,5", "1e3", "+1", "12a", "99999999999999999999"] {
+            assert!(pln(invalid).is_err(), "'{}' should be rejected", invalid);
+        }
+        let jpy: Currency = "JPY".parse().unwrap();
+        assert!(Money::parse("10.5", jpy).is_err());
+        assert_eq!(Money::new(1050, jpy).amount(), "1050");
+
+        assert_eq!(Money::new(123456, Currency::PLN).amount(), "1234.56");
+        assert_eq!(Money::new(-5, Currency::EUR).amount(), "-0.05");
+        assert_eq!(Money::new(100, Currency::USD).to_string(), "1.00 USD");
+    }
+
+    #[test]
+    fn money_json() {
+        let money = Money::new(123456, Currency::PLN);
+        let json = serde_json::to_string(&money).unwrap();
+        assert_eq!(json, r#"{"amount":"1234.56","currency":"PLN"}"#);
+        assert_eq!(serde_json::from_str::<Money>(&json).unwrap(), money);
+        assert!(serde_json::from_str::<Money>(r#"{"amount":"1.5","currency":"XYZ"}"#).is_err());
+        assert!(serde_json::from_str::<Money>(r#"{"amount":1.5,"currency":"PLN"}"#).is_err());
+        assert_eq!(
+            serde_json::from_str::<PayPeriod>(r#""yearly""#).unwrap(),
+            PayPeriod::Yearly
+        );
+    }
+}
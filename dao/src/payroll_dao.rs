@@ -0,0 +1,985 @@
+use std::collections::BTreeMap;
+use std::str::FromStr;
+
+use chrono::{Datelike, Days, Months, NaiveDate};
+use diesel::dsl::*;
+use diesel::prelude::*;
+
+use crate::base_dao::stale_version;
+use crate::connection::DbConnection;
+use crate::crypto::{self, KeyRing};
+use crate::error::{DaoError, DaoResult};
+use crate::hierarchy::EmployeeScope;
+use crate::models::{NewPayslip, NewPayslipLine, PayrollRun, Payslip, PayslipLine, Salary};
+use crate::money::{Currency, Money, PayPeriod};
+use crate::payroll_rules_dao::{PayrollRuleDTO, RuleKind};
+use crate::salaries_dao::{from_rows, SalaryDTO};
+use crate::timesheets_dao::{timesheet_rows_with_connection, WeekStatus};
+use crate::validation::Errors;
+
+/// Bonus or deduction applied to payslips of payroll run. Rules are applied one by one (bonuses before
+/// deductions) and every rule sees lines added by rules applied before it.
+pub trait PayrollRule {
+    fn kind(&self) -> RuleKind;
+
+    /// Line to add to `payslip` - None when the rule doesn't apply to it
+    fn apply(&self, payslip: &PayslipDTO) -> Option<PayslipLineDTO>;
+}
+
+impl PayrollRule for PayrollRuleDTO {
+    fn kind(&self) -> RuleKind {
+        self.kind
+    }
+
+    /// Percent is taken from gross lines (gross salaries and bonuses), fixed amount applies just to payslips
+    /// in its currency. Deduction never takes more than what is left of net pay.
+    fn apply(&self, payslip: &PayslipDTO) -> Option<PayslipLineDTO> {
+        let currency = payslip.net.currency;
+        let mut amount = match (self.percent, self.amount) {
+            (Some(p), _) => div_round(payslip.gross_basis() as i128 * p as i128, 10000),
+            (None, Some(a)) if a.currency == currency => a.minor_units,
+            _ => return None,
+        };
+        if self.kind == RuleKind::Deduction {
+            amount = amount.min(payslip.net.minor_units);
+        }
+        if amount <= 0 {
+            return None;
+        }
+        Some(PayslipLineDTO {
+            kind: match self.kind {
+                RuleKind::Bonus => LineKind::Bonus,
+                RuleKind::Deduction => LineKind::Deduction,
+            },
+            description: self.name.clone(),
+            amount: Money::new(amount, currency),
+            salary_id: None,
+            gross: self.kind == RuleKind::Bonus,
+        })
+    }
+}
+
+/// Rounded half away from zero - `d` have to be positive
+fn div_round(n: i128, d: i128) -> i64 {
+    let q = (n.abs() * 2 + d) / (2 * d);
+    (if n < 0 { -q } else { q }) as i64
+}
+
+/// What line of payslip is for
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum LineKind {
+    Salary,
+    Bonus,
+    Deduction,
+}
+
+impl LineKind {
+    /// How it is stored in DB
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LineKind::Salary => "salary",
+            LineKind::Bonus => "bonus",
+            LineKind::Deduction => "deduction",
+        }
+    }
+}
+
+impl FromStr for LineKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "salary" => Ok(LineKind::Salary),
+            "bonus" => Ok(LineKind::Bonus),
+            "deduction" => Ok(LineKind::Deduction),
+            _ => Err(format!("unknown line kind '{}' - should be one of salary, bonus, deduction", s)),
+        }
+    }
+}
+
+/// Line of payslip - amount is always positive, deductions are subtracted by kind
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct PayslipLineDTO {
+    pub kind: LineKind,
+    pub description: String,
+    pub amount: Money,
+    /// Salary the line pays - just for salary lines
+    pub salary_id: Option<i32>,
+    /// Whether percent rules are taken from the line
+    pub gross: bool,
+}
+
+impl PayslipLineDTO {
+    /// Line of row - its amount is decrypted by `keys` and has `currency` of its payslip
+    fn from_row(l: PayslipLine, currency: Currency, keys: Option<&KeyRing>) -> QueryResult<Self> {
+        let encrypted = l.amount_encrypted.as_deref();
+        let amount = crypto::open_amount(l.id, "Amount of payslip line", l.amount, encrypted, keys)?;
+        Ok(PayslipLineDTO {
+            kind: l.kind.parse().expect("kind is checked by DB"),
+            description: l.description,
+            amount: Money::new(amount, currency),
+            salary_id: l.salary_id,
+            gross: l.gross,
+        })
+    }
+}
+
+/// Pay of employee in one currency - employee is copied so payslip survives deletion of the employee
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct PayslipDTO {
+    pub id: Option<i32>,
+    pub employee_id: i32,
+    pub first_name: String,
+    pub last_name: String,
+    /// Salaries and bonuses
+    pub gross: Money,
+    pub deductions: Money,
+    /// Gross minus deductions
+    pub net: Money,
+    pub lines: Vec<PayslipLineDTO>,
+}
+
+impl PayslipDTO {
+    fn new(employee: &(i32, String, String), currency: Currency) -> PayslipDTO {
+        PayslipDTO {
+            id: None,
+            employee_id: employee.0,
+            first_name: employee.1.clone(),
+            last_name: employee.2.clone(),
+            gross: Money::new(0, currency),
+            deductions: Money::new(0, currency),
+            net: Money::new(0, currency),
+            lines: vec![],
+        }
+    }
+
+    /// Add line and update totals
+    pub fn push(&mut self, line: PayslipLineDTO) {
+        match line.kind {
+            LineKind::Deduction => self.deductions.minor_units += line.amount.minor_units,
+            LineKind::Salary | LineKind::Bonus => self.gross.minor_units += line.amount.minor_units,
+        }
+        self.net.minor_units = self.gross.minor_units - self.deductions.minor_units;
+        self.lines.push(line);
+    }
+
+    /// Sum of gross lines - base of percent rules
+    pub fn gross_basis(&self) -> i64 {
+        self.lines
+            .iter()
+            .filter(|l| l.gross && l.kind != LineKind::Deduction)
+            .map(|l| l.amount.minor_units)
+            .sum()
+    }
+
+    /// The same payslip with just salary lines and `rules` applied again
+    fn recalculated(&self, rules: &[PayrollRuleDTO]) -> PayslipDTO {
+        let mut payslip = PayslipDTO {
+            lines: vec![],
+            ..PayslipDTO::new(&(self.employee_id, self.first_name.clone(), self.last_name.clone()), self.net.currency)
+        };
+        payslip.id = self.id;
+        for line in self.lines.iter().filter(|l| l.kind == LineKind::Salary) {
+            payslip.push(line.clone());
+        }
+        apply_rules(&mut payslip, rules);
+        payslip
+    }
+}
+
+/// Bonuses first, then deductions - each group in given order
+pub fn apply_rules<R: PayrollRule>(payslip: &mut PayslipDTO, rules: &[R]) {
+    for kind in [RuleKind::Bonus, RuleKind::Deduction] {
+        for rule in rules.iter().filter(|r| r.kind() == kind) {
+            if let Some(line) = rule.apply(payslip) {
+                payslip.push(line);
+            }
+        }
+    }
+}
+
+/// Payslips of (not deleted) employees with salaries in `month` of `year` - one per employee and currency,
+/// ordered by employee and currency. Monthly and yearly salaries are pro-rated by calendar days they cover, hourly ones
+/// are paid for approved timesheet hours.
+pub fn calculate_payslips<R: PayrollRule>(
+    year: i32,
+    month: u32,
+    rules: &[R],
+    keys: Option<&KeyRing>,
+    conn: &mut DbConnection,
+) -> DaoResult<Vec<PayslipDTO>> {
+    use crate::schema::employees::dsl as e;
+    use crate::schema::salaries::dsl as s;
+
+    let (first, last) = month_range(year, month)?;
+    let days_in_month = last.day() as i128;
+    let found = s::salaries
+        .filter(s::from_date.le(last))
+        .filter(s::to_date.is_null().or(s::to_date.ge(first)))
+        .order((s::employee_id, s::from_date, s::id))
+        .load::<Salary>(conn);
+    let salaries: Vec<SalaryDTO> = from_rows(found, keys)?;
+    let employee_ids: Vec<i32> = salaries.iter().filter_map(|s| s.employee_id).collect();
+    let employees: Vec<(i32, String, String)> = e::employees
+        .filter(e::id.eq_any(&employee_ids))
+        .filter(e::deleted_at.is_null())
+        .select((e::id, e::first_name, e::last_name))
+        .order((e::last_name, e::first_name, e::id))
+        .load(conn)?;
+    let approved: Vec<(i32, NaiveDate, i32)> = timesheet_rows_with_connection(first, last, None, EmployeeScope::All, conn)?
+        .into_iter()
+        .filter(|r| r.status == Some(WeekStatus::Approved))
+        .map(|r| (r.employee_id, r.work_date, r.hours.minutes))
+        .collect();
+
+    let mut payslips = vec![];
+    for employee in &employees {
+        let mut by_currency: BTreeMap<&str, PayslipDTO> = BTreeMap::new();
+        for salary in salaries.iter().filter(|s| s.employee_id == Some(employee.0)) {
+            let from = salary.from_date.max(first);
+            let to = salary.to_date.map_or(last, |to| to.min(last));
+            let days = (to - from).num_days() as i128 + 1;
+            let amount = salary.amount.minor_units as i128;
+            let (pay, description) = match salary.pay_period {
+                PayPeriod::Monthly | PayPeriod::Yearly => {
+                    let per_month = if salary.pay_period == PayPeriod::Yearly { 12 } else { 1 };
+                    (
+                        div_round(amount * days, days_in_month * per_month),
+                        format!(
+                            "{} salary {} - {} ({}/{} days)",
+                            salary.pay_period.as_str(),
+                            from,
+                            to,
+                            days,
+                            days_in_month
+                        ),
+                    )
+                }
+                PayPeriod::Hourly => {
+                    let worked: i32 = approved
+                        .iter()
+                        .filter(|(e_id, day, _)| *e_id == employee.0 && *day >= from && *day <= to)
+                        .map(|(_, _, worked)| worked)
+                        .sum();
+                    if worked == 0 {
+                        continue;
+                    }
+                    (
+                        div_round(amount * worked as i128, 60),
+                        format!("hourly salary {} - {} ({:.2} h)", from, to, worked as f64 / 60.0),
+                    )
+                }
+            };
+            let currency = salary.amount.currency;
+            by_currency
+                .entry(currency.code())
+                .or_insert_with(|| PayslipDTO::new(employee, currency))
+                .push(PayslipLineDTO {
+                    kind: LineKind::Salary,
+                    description,
+                    amount: Money::new(pay, currency),
+                    salary_id: salary.id,
+                    gross: salary.gross,
+                });
+        }
+        for (_, mut payslip) in by_currency {
+            apply_rules(&mut payslip, rules);
+            payslips.push(payslip);
+        }
+    }
+    Ok(payslips)
+}
+
+/// First and last day of month
+fn month_range(year: i32, month: u32) -> DaoResult<(NaiveDate, NaiveDate)> {
+    let mut errors = Errors::default();
+    let first = NaiveDate::from_ymd_opt(year, month, 1);
+    if first.is_none() {
+        errors.add("", "month", format!("{}-{} is not valid month", year, month));
+    }
+    errors.into_result()?;
+    let first = first.unwrap();
+    Ok((first, first + Months::new(1) - Days::new(1)))
+}
+
+/// Where payroll run is in draft/approve/lock workflow
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum RunStatus {
+    /// Can be recalculated or deleted
+    Draft,
+    /// Waiting to be locked
+    Approved,
+    /// Final - can't be changed anymore
+    Locked,
+}
+
+impl RunStatus {
+    /// How it is stored in DB
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RunStatus::Draft => "draft",
+            RunStatus::Approved => "approved",
+            RunStatus::Locked => "locked",
+        }
+    }
+}
+
+impl FromStr for RunStatus {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "draft" => Ok(RunStatus::Draft),
+            "approved" => Ok(RunStatus::Approved),
+            "locked" => Ok(RunStatus::Locked),
+            _ => Err(format!("unknown run status '{}' - should be one of draft, approved, locked", s)),
+        }
+    }
+}
+
+/// Payroll of one month - payslips are snapshot made when the run was calculated together with rules
+/// applied to them
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct PayrollRunDTO {
+    pub id: i32,
+    pub year: i32,
+    pub month: u32,
+    pub status: RunStatus,
+    /// User who approved the run
+    pub approved_by: Option<i32>,
+    /// Rules as they were when the run was calculated
+    pub rules: Vec<PayrollRuleDTO>,
+    /// Ordered by employee and currency
+    pub payslips: Vec<PayslipDTO>,
+    pub version: i32,
+}
+
+/// Result of recalculating payroll run from its snapshot
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct PayrollVerification {
+    pub payroll_run_id: i32,
+    pub reproducible: bool,
+    /// Ids of payslips which differ from the recalculated ones
+    pub mismatched_payslips: Vec<i32>,
+}
+
+/// DTOs of `slips` with their lines (in order of `slips`)
+fn payslips_with_lines(
+    slips: Vec<Payslip>,
+    keys: Option<&KeyRing>,
+    conn: &mut DbConnection,
+) -> QueryResult<Vec<PayslipDTO>> {
+    use crate::schema::payslip_lines::dsl as l;
+
+    let lines = PayslipLine::belonging_to(&slips)
+        .order(l::position)
+        .load::<PayslipLine>(conn)?
+        .grouped_by(&slips);
+    slips
+        .into_iter()
+        .zip(lines)
+        .map(|(slip, lines)| {
+            let currency: Currency = slip.currency.parse().expect("currency is checked on save");
+            let money = |plain: i64, encrypted: &Option<String>| {
+                let amount = crypto::open_amount(slip.id, "Amount of payslip", plain, encrypted.as_deref(), keys)?;
+                Ok::<_, diesel::result::Error>(Money::new(amount, currency))
+            };
+            Ok(PayslipDTO {
+                id: Some(slip.id),
+                employee_id: slip.employee_id,
+                gross: money(slip.gross, &slip.gross_encrypted)?,
+                deductions: money(slip.deductions, &slip.deductions_encrypted)?,
+                net: money(slip.net, &slip.net_encrypted)?,
+                first_name: slip.first_name,
+                last_name: slip.last_name,
+                lines: lines
+                    .into_iter()
+                    .map(|l| PayslipLineDTO::from_row(l, currency, keys))
+                    .collect::<QueryResult<_>>()?,
+            })
+        })
+        .collect()
+}
+
+/// Payslips of employee from all payroll runs - the oldest first
+pub(crate) fn payslips_of(e_id: i32, keys: Option<&KeyRing>, conn: &mut DbConnection) -> QueryResult<Vec<PayslipDTO>> {
+    use crate::schema::payslips::dsl as p;
+
+    let slips = p::payslips
+        .filter(p::employee_id.eq(e_id))
+        .order(p::id)
+        .load::<Payslip>(conn)?;
+    payslips_with_lines(slips, keys, conn)
+}
+
+/// Payslips of anonymized employee get its new name - amounts stay as they were, so runs can still be
+/// verified
+pub(crate) fn anonymize_payslips_of(e_id: i32, name: (&str, &str), conn: &mut DbConnection) -> QueryResult<usize> {
+    use crate::schema::payslips::dsl as p;
+
+    diesel::update(p::payslips.filter(p::employee_id.eq(e_id)))
+        .set((p::first_name.eq(name.0), p::last_name.eq(name.1)))
+        .execute(conn)
+}
+
+impl PayrollRunDTO {
+    fn from_model(run: PayrollRun, keys: Option<&KeyRing>, conn: &mut DbConnection) -> QueryResult<Self> {
+        use crate::schema::payslips::dsl as p;
+
+        let slips = Payslip::belonging_to(&run).order(p::id).load::<Payslip>(conn)?;
+        let payslips = payslips_with_lines(slips, keys, conn)?;
+        Ok(PayrollRunDTO {
+            id: run.id,
+            year: run.year,
+            month: run.month as u32,
+            status: run.status.parse().expect("status is checked by DB"),
+            approved_by: run.approved_by,
+            rules: serde_json::from_str(&run.rules).expect("rules are saved as JSON"),
+            payslips,
+            version: run.version,
+        })
+    }
+
+    pub fn get_with_connection(
+        id_to_find: i32,
+        keys: Option<&KeyRing>,
+        conn: &mut DbConnection,
+    ) -> QueryResult<Option<Self>> {
+        use crate::schema::payroll_runs::dsl as r;
+
+        match r::payroll_runs.filter(r::id.eq(id_to_find)).first::<PayrollRun>(conn).optional()? {
+            Some(run) => Ok(Some(Self::from_model(run, keys, conn)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// All runs - the latest month first
+    pub fn get_all_with_connection(keys: Option<&KeyRing>, conn: &mut DbConnection) -> QueryResult<Vec<Self>> {
+        use crate::schema::payroll_runs::dsl as r;
+
+        r::payroll_runs
+            .order((r::year.desc(), r::month.desc()))
+            .load::<PayrollRun>(conn)?
+            .into_iter()
+            .map(|run| Self::from_model(run, keys, conn))
+            .collect()
+    }
+
+    /// Calculate draft run of month with active rules - there can be just one run per month
+    pub fn create_with_connection(
+        year: i32,
+        month: u32,
+        keys: Option<&KeyRing>,
+        conn: &mut DbConnection,
+    ) -> DaoResult<Self> {
+        use crate::schema::payroll_runs::dsl as r;
+
+        conn.transaction(|conn| {
+            month_range(year, month)?;
+            let existing = r::payroll_runs
+                .filter(r::year.eq(year))
+                .filter(r::month.eq(month as i32))
+                .select(r::id)
+                .first::<i32>(conn)
+                .optional()?;
+            if let Some(existing) = existing {
+                let mut errors = Errors::default();
+                errors.add(
+                    "",
+                    "month",
+                    format!("payroll run of {}-{:02} already exists (id = {})", year, month, existing),
+                );
+                errors.into_result()?;
+            }
+            let rules = PayrollRuleDTO::active_with_connection(conn)?;
+            let payslips = calculate_payslips(year, month, &rules, keys, conn)?;
+            let run_id = insert_into(r::payroll_runs)
+                .values((
+                    r::year.eq(year),
+                    r::month.eq(month as i32),
+                    r::status.eq(RunStatus::Draft.as_str()),
+                    r::rules.eq(serde_json::to_string(&rules).expect("rules are serializable")),
+                ))
+                .returning(r::id)
+                .get_result::<i32>(conn)?;
+            save_payslips(run_id, &payslips, keys, conn)?;
+            Ok(Self::get_with_connection(run_id, keys, conn)?.expect("run was just saved"))
+        })
+    }
+
+    /// Run with `expected_version` in `status` - or why it isn't
+    fn expect(
+        id_to_find: i32,
+        expected_version: i32,
+        status: RunStatus,
+        keys: Option<&KeyRing>,
+        conn: &mut DbConnection,
+    ) -> DaoResult<Self> {
+        let run = Self::get_with_connection(id_to_find, keys, conn)?.ok_or_else(DaoError::not_found)?;
+        if run.version != expected_version {
+            return Err(stale_version(run.id, Some(run.version)));
+        }
+        let mut errors = Errors::default();
+        if run.status != status {
+            errors.add(
+                "",
+                "status",
+                format!("payroll run is {} and should be {}", run.status.as_str(), status.as_str()),
+            );
+        }
+        errors.into_result()?;
+        Ok(run)
+    }
+
+    /// Calculate draft run again with current salaries, timesheets and active rules
+    pub fn recalculate_with_connection(
+        id_to_find: i32,
+        expected_version: i32,
+        keys: Option<&KeyRing>,
+        conn: &mut DbConnection,
+    ) -> DaoResult<Self> {
+        use crate::schema::payroll_runs::dsl as r;
+
+        conn.transaction(|conn| {
+            let run = Self::expect(id_to_find, expected_version, RunStatus::Draft, keys, conn)?;
+            delete_payslips(run.id, conn)?;
+            let rules = PayrollRuleDTO::active_with_connection(conn)?;
+            let payslips = calculate_payslips(run.year, run.month, &rules, keys, conn)?;
+            diesel::update(r::payroll_runs.filter(r::id.eq(run.id)))
+                .set((
+                    r::rules.eq(serde_json::to_string(&rules).expect("rules are serializable")),
+                    r::version.eq(r::version + 1),
+                ))
+                .execute(conn)?;
+            save_payslips(run.id, &payslips, keys, conn)?;
+            Ok(Self::get_with_connection(run.id, keys, conn)?.expect("run was just saved"))
+        })
+    }
+
+    /// Draft run is approved by `user_id`
+    pub fn approve_with_connection(
+        id_to_find: i32,
+        expected_version: i32,
+        user_id: i32,
+        keys: Option<&KeyRing>,
+        conn: &mut DbConnection,
+    ) -> DaoResult<Self> {
+        use crate::schema::payroll_runs::dsl as r;
+
+        conn.transaction(|conn| {
+            let run = Self::expect(id_to_find, expected_version, RunStatus::Draft, keys, conn)?;
+            diesel::update(r::payroll_runs.filter(r::id.eq(run.id)))
+                .set((
+                    r::status.eq(RunStatus::Approved.as_str()),
+                    r::approved_by.eq(Some(user_id)),
+                    r::version.eq(r::version + 1),
+                ))
+                .execute(conn)?;
+            Ok(Self::get_with_connection(run.id, keys, conn)?.expect("run was just saved"))
+        })
+    }
+
+    /// Approved run is locked - just when it can be reproduced from its snapshot (see verify_with_connection())
+    pub fn lock_with_connection(
+        id_to_find: i32,
+        expected_version: i32,
+        keys: Option<&KeyRing>,
+        conn: &mut DbConnection,
+    ) -> DaoResult<Self> {
+        use crate::schema::payroll_runs::dsl as r;
+
+        conn.transaction(|conn| {
+            let run = Self::expect(id_to_find, expected_version, RunStatus::Approved, keys, conn)?;
+            let verification = run.verify();
+            if !verification.reproducible {
+                let mut errors = Errors::default();
+                errors.add(
+                    "",
+                    "payslips",
+                    format!("payslips {:?} can't be reproduced from the run", verification.mismatched_payslips),
+                );
+                errors.into_result()?;
+            }
+            diesel::update(r::payroll_runs.filter(r::id.eq(run.id)))
+                .set((r::status.eq(RunStatus::Locked.as_str()), r::version.eq(r::version + 1)))
+                .execute(conn)?;
+            Ok(Self::get_with_connection(run.id, keys, conn)?.expect("run was just saved"))
+        })
+    }
+
+    /// Just draft run can be deleted
+    pub fn delete_with_connection(
+        id_to_find: i32,
+        expected_version: i32,
+        keys: Option<&KeyRing>,
+        conn: &mut DbConnection,
+    ) -> DaoResult<usize> {
+        use crate::schema::payroll_runs::dsl as r;
+
+        conn.transaction(|conn| {
+            let run = Self::expect(id_to_find, expected_version, RunStatus::Draft, keys, conn)?;
+            delete_payslips(run.id, conn)?;
+            Ok(diesel::delete(r::payroll_runs.filter(r::id.eq(run.id))).execute(conn)?)
+        })
+    }
+
+    /// Apply rules of the run again to salary lines of its payslips and compare with stored payslips
+    pub fn verify(&self) -> PayrollVerification {
+        let mismatched_payslips: Vec<i32> = self
+            .payslips
+            .iter()
+            .filter(|p| p.recalculated(&self.rules) != **p)
+            .filter_map(|p| p.id)
+            .collect();
+        PayrollVerification {
+            payroll_run_id: self.id,
+            reproducible: mismatched_payslips.is_empty(),
+            mismatched_payslips,
+        }
+    }
+}
+
+fn save_payslips(
+    run_id: i32,
+    payslips: &[PayslipDTO],
+    keys: Option<&KeyRing>,
+    conn: &mut DbConnection,
+) -> QueryResult<()> {
+    use crate::schema::payslip_lines::dsl as l;
+    use crate::schema::payslips::dsl as p;
+
+    for payslip in payslips {
+        let (gross, gross_encrypted) = crypto::seal_amount(payslip.gross.minor_units, keys);
+        let (deductions, deductions_encrypted) = crypto::seal_amount(payslip.deductions.minor_units, keys);
+        let (net, net_encrypted) = crypto::seal_amount(payslip.net.minor_units, keys);
+        let slip_id = insert_into(p::payslips)
+            .values(NewPayslip {
+                payroll_run_id: run_id,
+                employee_id: payslip.employee_id,
+                first_name: payslip.first_name.clone(),
+                last_name: payslip.last_name.clone(),
+                currency: payslip.net.currency.code().to_string(),
+                gross,
+                deductions,
+                net,
+                gross_encrypted,
+                deductions_encrypted,
+                net_encrypted,
+            })
+            .returning(p::id)
+            .get_result::<i32>(conn)?;
+        let lines: Vec<NewPayslipLine> = payslip
+            .lines
+            .iter()
+            .enumerate()
+            .map(|(i, line)| {
+                let (amount, amount_encrypted) = crypto::seal_amount(line.amount.minor_units, keys);
+                NewPayslipLine {
+                    payslip_id: slip_id,
+                    position: i as i32,
+                    kind: line.kind.as_str().to_string(),
+                    description: line.description.clone(),
+                    amount,
+                    salary_id: line.salary_id,
+                    gross: line.gross,
+                    amount_encrypted,
+                }
+            })
+            .collect();
+        insert_into(l::payslip_lines).values(&lines).execute(conn)?;
+    }
+    Ok(())
+}
+
+/// Amounts of payslips and their lines in plain text or encrypted by other than the current key are encrypted by
+/// the current key - return number of such payslips and lines
+pub(crate) fn reencrypt_payslips(keys: &KeyRing, conn: &mut DbConnection) -> QueryResult<usize> {
+    use crate::schema::payslip_lines::dsl as l;
+    use crate::schema::payslips::dsl as p;
+
+    let is_current = |encrypted: &Option<String>| encrypted.as_deref().is_some_and(|stored| keys.is_current(stored));
+    let mut reencrypted = 0;
+    for slip in p::payslips.order(p::id).load::<Payslip>(conn)? {
+        let encrypted = [&slip.gross_encrypted, &slip.deductions_encrypted, &slip.net_encrypted];
+        if encrypted.into_iter().all(is_current) {
+            continue;
+        }
+        let seal = |plain: i64, encrypted: &Option<String>| {
+            let minor_units =
+                crypto::open_amount(slip.id, "Amount of payslip", plain, encrypted.as_deref(), Some(keys))?;
+            Ok::<_, diesel::result::Error>(crypto::seal_amount(minor_units, Some(keys)))
+        };
+        let (gross, gross_encrypted) = seal(slip.gross, &slip.gross_encrypted)?;
+        let (deductions, deductions_encrypted) = seal(slip.deductions, &slip.deductions_encrypted)?;
+        let (net, net_encrypted) = seal(slip.net, &slip.net_encrypted)?;
+        diesel::update(p::payslips.filter(p::id.eq(slip.id)))
+            .set((
+                p::gross.eq(gross),
+                p::deductions.eq(deductions),
+                p::net.eq(net),
+                p::gross_encrypted.eq(gross_encrypted),
+                p::deductions_encrypted.eq(deductions_encrypted),
+                p::net_encrypted.eq(net_encrypted),
+            ))
+            .execute(conn)?;
+        reencrypted += 1;
+    }
+    let lines: Vec<(i32, i64, Option<String>)> = l::payslip_lines
+        .select((l::id, l::amount, l::amount_encrypted))
+        .order(l::id)
+        .load(conn)?;
+    for (line_id, plain, encrypted) in lines {
+        if is_current(&encrypted) {
+            continue;
+        }
+        let minor_units =
+            crypto::open_amount(line_id, "Amount of payslip line", plain, encrypted.as_deref(), Some(keys))?;
+        let (plain, encrypted) = crypto::seal_amount(minor_units, Some(keys));
+        diesel::update(l::payslip_lines.filter(l::id.eq(line_id)))
+            .set((l::amount.eq(plain), l::amount_encrypted.eq(encrypted)))
+            .execute(conn)?;
+        reencrypted += 1;
+    }
+    Ok(reencrypted)
+}
+
+fn delete_payslips(run_id: i32, conn: &mut DbConnection) -> QueryResult<usize> {
+    use crate::schema::payslip_lines::dsl as l;
+    use crate::schema::payslips::dsl as p;
+
+    let slip_ids = p::payslips.filter(p::payroll_run_id.eq(run_id)).select(p::id);
+    diesel::delete(l::payslip_lines.filter(l::payslip_id.eq_any(slip_ids))).execute(conn)?;
+    diesel::delete(p::payslips.filter(p::payroll_run_id.eq(run_id))).execute(conn)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::common_for_tests::*;
+    use crate::timesheets_dao::{Hours, TimesheetEntryDTO, TimesheetWeekDTO};
+    use crate::{Crud, EmployeeDTO};
+
+    use super::*;
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    fn salary(from: NaiveDate, to: Option<NaiveDate>, amount: Money, period: PayPeriod) -> SalaryDTO {
+        SalaryDTO {
+            id: None,
+            employee_id: None,
+            from_date: from,
+            to_date: to,
+            amount,
+            pay_period: period,
+            gross: true,
+            search_string: "".to_string(),
+            version: None,
+            contract_id: None,
+        }
+    }
+
+    fn save_employee(last: &str, salaries: Vec<SalaryDTO>, conn: &mut DbConnection) -> i32 {
+        EmployeeDTO {
+            id: None,
+            first_name: "Jan".to_string(),
+            last_name: last.to_string(),
+            search_string: "".to_string(),
+            salaries,
+            contacts: vec![],
+            version: None,
+            department_id: None,
+            manager_id: None,
+            contracts: vec![],
+            ..Default::default()
+        }
+        .save_in_transaction(keys(), conn)
+        .unwrap()
+        .id
+        .unwrap()
+    }
+
+    fn save_rule(rule_name: &str, kind: RuleKind, percent: Option<i32>, amount: Option<Money>, conn: &mut DbConnection) {
+        PayrollRuleDTO {
+            id: None,
+            name: rule_name.to_string(),
+            kind,
+            percent,
+            amount,
+            active: true,
+            search_string: "".to_string(),
+            version: None,
+        }
+        .save_in_transaction(keys(), conn)
+        .unwrap();
+    }
+
+    fn amounts(payslip: &PayslipDTO) -> Vec<(LineKind, i64)> {
+        payslip.lines.iter().map(|l| (l.kind, l.amount.minor_units)).collect()
+    }
+
+    #[test]
+    fn salaries_are_pro_rated_and_rules_applied() {
+        let conn = &mut initialize();
+        let pln = |a| Money::new(a, Currency::PLN);
+        // Raise on 16th of March (31 days): 15 days of 3100.00 and 16 days of 6200.00
+        let a = save_employee(
+            "A",
+            vec![
+                salary(date(2021, 1, 1), Some(date(2021, 3, 15)), pln(310000), PayPeriod::Monthly),
+                salary(date(2021, 3, 16), None, pln(620000), PayPeriod::Monthly),
+            ],
+            conn,
+        );
+        // Yearly salary in EUR for 14 days and then hourly one in PLN - two payslips
+        let b = save_employee(
+            "B",
+            vec![
+                salary(date(2021, 1, 1), Some(date(2021, 3, 14)), Money::new(1200000, Currency::EUR), PayPeriod::Yearly),
+                salary(date(2021, 3, 15), None, pln(5000), PayPeriod::Hourly),
+            ],
+            conn,
+        );
+        // Salary of other month only - no payslip
+        save_employee(
+            "C",
+            vec![salary(date(2021, 1, 1), Some(date(2021, 2, 28)), pln(100000), PayPeriod::Monthly)],
+            conn,
+        );
+        TimesheetEntryDTO {
+            id: None,
+            employee_id: Some(b),
+            work_date: date(2021, 3, 15),
+            hours: Hours::parse("7:30").unwrap(),
+            category: "Project X".to_string(),
+            note: None,
+            search_string: "".to_string(),
+            version: None,
+        }
+        .save_in_transaction(keys(), conn)
+        .unwrap();
+        TimesheetWeekDTO::submit_with_connection(b, date(2021, 3, 15), conn).unwrap();
+        TimesheetWeekDTO::decide_with_connection(b, date(2021, 3, 15), 1, WeekStatus::Approved, 2, conn).unwrap();
+        save_rule("Social security", RuleKind::Deduction, Some(1000), None, conn);
+        save_rule("Christmas bonus", RuleKind::Bonus, None, Some(pln(10000)), conn);
+
+        let run = PayrollRunDTO::create_with_connection(2021, 3, keys(), conn).unwrap();
+        assert_eq!(run.status, RunStatus::Draft);
+        assert_eq!(run.rules.len(), 2);
+        let payslips = &run.payslips;
+        assert_eq!(
+            payslips.iter().map(|p| (p.employee_id, p.net.currency)).collect::<Vec<_>>(),
+            vec![(a, Currency::PLN), (b, Currency::EUR), (b, Currency::PLN)]
+        );
+        assert_eq!(
+            amounts(&payslips[0]),
+            vec![
+                (LineKind::Salary, 150000),
+                (LineKind::Salary, 320000),
+                (LineKind::Bonus, 10000),
+                (LineKind::Deduction, 48000)
+            ]
+        );
+        assert_eq!((payslips[0].gross, payslips[0].net), (pln(480000), pln(432000)));
+        // Bonus is in PLN so EUR payslip gets just deduction
+        assert_eq!(amounts(&payslips[1]), vec![(LineKind::Salary, 45161), (LineKind::Deduction, 4516)]);
+        assert_eq!(
+            amounts(&payslips[2]),
+            vec![(LineKind::Salary, 37500), (LineKind::Bonus, 10000), (LineKind::Deduction, 4750)]
+        );
+
+        match PayrollRunDTO::create_with_connection(2021, 3, keys(), conn) {
+            Err(DaoError::Validation(errors)) => assert_eq!(errors[0].field, "month"),
+            result => panic!("Should report validation error and instead I got {:?}", result),
+        }
+        assert!(PayrollRunDTO::lock_with_connection(run.id, 1, keys(), conn).unwrap_err().is_validation());
+        assert!(PayrollRunDTO::approve_with_connection(run.id, 5, 2, keys(), conn).unwrap_err().is_stale_version());
+
+        save_rule("Canteen", RuleKind::Deduction, None, Some(pln(2000)), conn);
+        let run = PayrollRunDTO::recalculate_with_connection(run.id, 1, keys(), conn).unwrap();
+        assert_eq!(run.version, 2);
+        assert_eq!(run.payslips[0].net, pln(430000));
+
+        let run = PayrollRunDTO::approve_with_connection(run.id, 2, 2, keys(), conn).unwrap();
+        assert_eq!((run.status, run.approved_by), (RunStatus::Approved, Some(2)));
+        assert!(PayrollRunDTO::recalculate_with_connection(run.id, 3, keys(), conn).unwrap_err().is_validation());
+        assert!(PayrollRunDTO::delete_with_connection(run.id, 3, keys(), conn).unwrap_err().is_validation());
+
+        // Locked run doesn't change with rules and is reproducible from its snapshot
+        let locked = PayrollRunDTO::lock_with_connection(run.id, 3, keys(), conn).unwrap();
+        assert_eq!(locked.status, RunStatus::Locked);
+        save_rule("Union", RuleKind::Deduction, Some(100), None, conn);
+        let again = PayrollRunDTO::get_with_connection(run.id, keys(), conn).unwrap().unwrap();
+        assert_eq!(again.payslips, locked.payslips);
+        assert!(again.verify().reproducible);
+
+        let mut tampered = again.clone();
+        tampered.payslips[1].lines[1].amount.minor_units += 1;
+        let verification = tampered.verify();
+        assert!(!verification.reproducible);
+        assert_eq!(verification.mismatched_payslips, vec![again.payslips[1].id.unwrap()]);
+    }
+
+    #[test]
+    fn draft_run_can_be_deleted() {
+        let conn = &mut initialize();
+        save_employee(
+            "A",
+            vec![salary(date(2021, 1, 1), None, Money::new(100000, Currency::PLN), PayPeriod::Monthly)],
+            conn,
+        );
+        let run = PayrollRunDTO::create_with_connection(2021, 2, keys(), conn).unwrap();
+        assert_eq!(run.payslips[0].net, Money::new(100000, Currency::PLN));
+        assert_eq!(PayrollRunDTO::delete_with_connection(run.id, 1, keys(), conn).unwrap(), 1);
+        assert_eq!(PayrollRunDTO::get_with_connection(run.id, keys(), conn).unwrap(), None);
+        assert!(PayrollRunDTO::create_with_connection(2021, 13, keys(), conn).unwrap_err().is_validation());
+    }
+
+    #[test]
+    fn payslip_amounts_are_stored_encrypted() {
+        use crate::crypto::test_keys;
+        use crate::schema::payslip_lines::dsl as l;
+        use crate::schema::payslips::dsl as p;
+
+        let conn = &mut initialize();
+        save_employee(
+            "A",
+            vec![salary(date(2021, 1, 1), None, Money::new(100000, Currency::PLN), PayPeriod::Monthly)],
+            conn,
+        );
+        let run = PayrollRunDTO::create_with_connection(2021, 2, keys(), conn).unwrap();
+        let stored: (i64, Option<String>) = p::payslips.select((p::net, p::net_encrypted)).first(conn).unwrap();
+        assert_eq!(stored.0, 0, "Net should be stored encrypted only");
+        assert!(stored.1.is_some());
+        let keys = &test_keys();
+        assert_eq!(reencrypt_payslips(keys, conn).unwrap(), 0);
+
+        // As it was before encryption of payslips
+        diesel::update(p::payslips)
+            .set((
+                p::gross.eq(100000),
+                p::net.eq(100000),
+                p::gross_encrypted.eq(None::<String>),
+                p::net_encrypted.eq(None::<String>),
+            ))
+            .execute(conn)
+            .unwrap();
+        diesel::update(l::payslip_lines)
+            .set((l::amount.eq(100000), l::amount_encrypted.eq(None::<String>)))
+            .execute(conn)
+            .unwrap();
+        assert_eq!(PayrollRunDTO::get_with_connection(run.id, Some(keys), conn).unwrap().unwrap(), run);
+        assert_eq!(reencrypt_payslips(keys, conn).unwrap(), 2);
+        let lines: Vec<i64> = l::payslip_lines.select(l::amount).load(conn).unwrap();
+        assert_eq!(lines, vec![0]);
+        assert_eq!(PayrollRunDTO::get_with_connection(run.id, Some(keys), conn).unwrap().unwrap(), run);
+
+        diesel::update(l::payslip_lines)
+            .set(l::amount_encrypted.eq("garbage"))
+            .execute(conn)
+            .unwrap();
+        assert!(PayrollRunDTO::get_with_connection(run.id, Some(keys), conn).is_err());
+    }
+}
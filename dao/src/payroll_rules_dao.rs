@@ -0,0 +1,313 @@
+use std::str::FromStr;
+
+use diesel::dsl::*;
+use diesel::prelude::*;
+
+use crate::base_dao::{stale_version, Crud, HaveId, HaveVersion, Searchable};
+use crate::connection::DbConnection;
+use crate::crypto::KeyRing;
+use crate::error::DaoResult;
+use crate::models::{NewPayrollRule, PayrollRule};
+use crate::money::Money;
+use crate::schema::payroll_rules::dsl::id as rule_id;
+use crate::schema::payroll_rules::dsl::*;
+use crate::validation::{Errors, ValidationRules};
+
+/// Whether rule adds to pay or takes from it
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum RuleKind {
+    Bonus,
+    Deduction,
+}
+
+impl RuleKind {
+    /// How it is stored in DB
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RuleKind::Bonus => "bonus",
+            RuleKind::Deduction => "deduction",
+        }
+    }
+}
+
+impl FromStr for RuleKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "bonus" => Ok(RuleKind::Bonus),
+            "deduction" => Ok(RuleKind::Deduction),
+            _ => Err(format!("unknown rule kind '{}' - should be one of bonus, deduction", s)),
+        }
+    }
+}
+
+/// Bonus or deduction applied to every payslip of payroll run - either percent of gross pay or fixed amount
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct PayrollRuleDTO {
+    pub id: Option<i32>,
+    pub name: String,
+    pub kind: RuleKind,
+    /// Percent of gross pay in basis points - 1371 is 13.71%
+    #[serde(default)]
+    pub percent: Option<i32>,
+    /// Fixed amount - applied just to payslips in the same currency
+    #[serde(default)]
+    pub amount: Option<Money>,
+    /// Inactive rules are not applied to new payroll runs
+    #[serde(default = "active_by_default")]
+    pub active: bool,
+    pub search_string: String,
+    pub version: Option<i32>,
+}
+
+fn active_by_default() -> bool {
+    true
+}
+
+impl From<PayrollRule> for PayrollRuleDTO {
+    fn from(r: PayrollRule) -> Self {
+        PayrollRuleDTO {
+            id: Some(r.id),
+            name: r.name,
+            kind: r.kind.parse().expect("kind is checked by DB"),
+            percent: r.percent,
+            amount: r
+                .amount
+                .zip(r.currency)
+                .map(|(a, c)| Money::new(a, c.parse().expect("currency is checked on save"))),
+            active: r.active,
+            search_string: r.search_string,
+            version: Some(r.version),
+        }
+    }
+}
+
+impl From<&PayrollRuleDTO> for PayrollRule {
+    fn from(rule_dto: &PayrollRuleDTO) -> Self {
+        PayrollRule {
+            id: rule_dto.id.unwrap(),
+            name: rule_dto.name.clone(),
+            kind: rule_dto.kind.as_str().to_string(),
+            percent: rule_dto.percent,
+            amount: rule_dto.amount.map(|a| a.minor_units),
+            currency: rule_dto.amount.map(|a| a.currency.code().to_string()),
+            active: rule_dto.active,
+            search_string: rule_dto.search_string.clone(),
+            version: rule_dto.version.unwrap_or_default(),
+        }
+    }
+}
+
+impl From<&PayrollRuleDTO> for NewPayrollRule {
+    fn from(rule_dto: &PayrollRuleDTO) -> Self {
+        NewPayrollRule {
+            name: rule_dto.name.clone(),
+            kind: rule_dto.kind.as_str().to_string(),
+            percent: rule_dto.percent,
+            amount: rule_dto.amount.map(|a| a.minor_units),
+            currency: rule_dto.amount.map(|a| a.currency.code().to_string()),
+            active: rule_dto.active,
+            search_string: rule_dto.search_string.clone(),
+        }
+    }
+}
+
+impl HaveId for PayrollRuleDTO {
+    fn get_id(&self) -> Option<i32> {
+        self.id
+    }
+}
+
+impl HaveVersion for PayrollRuleDTO {
+    fn get_version(&self) -> Option<i32> {
+        self.version
+    }
+}
+
+impl Crud for PayrollRuleDTO {
+    fn update(&mut self, persisted: &Self) {
+        self.id = persisted.id;
+        self.version = persisted.version;
+    }
+
+    /// Name is required and unique, rule has either percent (0 - 100%) or amount (not negative)
+    fn validate(&self, _rules: &ValidationRules, _keys: Option<&KeyRing>, conn: &mut DbConnection) -> DaoResult<()> {
+        let mut errors = Errors::default();
+        if self.name.trim().is_empty() {
+            errors.add("", "name", "can't be empty".to_string());
+        }
+        let same_name = payroll_rules
+            .filter(name.eq(&self.name))
+            .select(rule_id)
+            .first::<i32>(conn)
+            .optional()?;
+        if let Some(other) = same_name
+            && Some(other) != self.id
+        {
+            errors.add("", "name", format!("'{}' is already name of payroll rule id = {}", self.name, other));
+        }
+        match (self.percent, self.amount) {
+            (Some(p), None) if !(0..=10000).contains(&p) => {
+                errors.add("", "percent", format!("{} is not between 0 and 10000 (100%)", p));
+            }
+            (None, Some(a)) if a.minor_units < 0 => {
+                errors.add("", "amount", "can't be negative".to_string());
+            }
+            (Some(_), None) | (None, Some(_)) => {}
+            _ => errors.add("", "percent", "either percent or amount has to be set".to_string()),
+        }
+        errors.into_result()
+    }
+
+    fn get_simple(id_to_find: i32, _keys: Option<&KeyRing>, conn: &mut DbConnection) -> QueryResult<Self> {
+        payroll_rules
+            .filter(rule_id.eq(id_to_find))
+            .first(conn)
+            .map(|r: PayrollRule| PayrollRuleDTO::from(r))
+    }
+
+    fn save_simple(&self, keys: Option<&KeyRing>, conn: &mut DbConnection) -> DaoResult<Self> {
+        fn insert(r: &PayrollRuleDTO, conn: &mut DbConnection) -> QueryResult<PayrollRuleDTO> {
+            insert_into(payroll_rules)
+                .values(NewPayrollRule::from(r))
+                .get_result(conn)
+                .map(|r: PayrollRule| PayrollRuleDTO::from(r))
+        }
+        if let Some(self_id) = self.id {
+            let updated = match self.version {
+                Some(self_version) => diesel::update(
+                    payroll_rules
+                        .filter(rule_id.eq(self_id))
+                        .filter(version.eq(self_version)),
+                )
+                .set((PayrollRule::from(self), version.eq(version + 1)))
+                .execute(conn)?,
+                None => 0,
+            };
+            if updated == 0 {
+                let current = payroll_rules
+                    .filter(rule_id.eq(self_id))
+                    .select(version)
+                    .first::<i32>(conn)
+                    .optional()?;
+                match current {
+                    Some(current) => Err(stale_version(self_id, Some(current))),
+                    None => Ok(insert(self, conn)?),
+                }
+            } else {
+                Ok(Self::get_simple(self_id, keys, conn)?)
+            }
+        } else {
+            Ok(insert(self, conn)?)
+        }
+    }
+
+    /// Payroll runs keep their own copy of rules so deleted rule doesn't change them
+    fn delete_simple(id_to_find: i32, conn: &mut DbConnection) -> QueryResult<usize> {
+        diesel::delete(payroll_rules.filter(rule_id.eq(id_to_find))).execute(conn)
+    }
+
+    fn delete_versioned_simple(
+        id_to_find: i32,
+        version_to_find: i32,
+        conn: &mut DbConnection,
+    ) -> QueryResult<usize> {
+        diesel::delete(
+            payroll_rules
+                .filter(rule_id.eq(id_to_find))
+                .filter(version.eq(version_to_find)),
+        )
+        .execute(conn)
+    }
+}
+
+impl Searchable for PayrollRuleDTO {
+    /// In order they are applied (by id)
+    fn get_all_with_connection(_keys: Option<&KeyRing>, conn: &mut DbConnection) -> DaoResult<Vec<Self>> {
+        Ok(payroll_rules
+            .order(rule_id)
+            .load::<PayrollRule>(conn)?
+            .into_iter()
+            .map(Self::from)
+            .collect())
+    }
+
+    fn search_with_connection(s: &str, _keys: Option<&KeyRing>, conn: &mut DbConnection) -> DaoResult<Vec<Self>> {
+        Ok(payroll_rules
+            .filter(search_string.like(s))
+            .order(rule_id)
+            .load::<PayrollRule>(conn)?
+            .into_iter()
+            .map(Self::from)
+            .collect())
+    }
+}
+
+impl PayrollRuleDTO {
+    /// Rules applied to new payroll runs - in order they are applied (by id)
+    pub fn active_with_connection(conn: &mut DbConnection) -> QueryResult<Vec<Self>> {
+        Ok(payroll_rules
+            .filter(active.eq(true))
+            .order(rule_id)
+            .load::<PayrollRule>(conn)?
+            .into_iter()
+            .map(Self::from)
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::common_for_tests::*;
+    use crate::error::DaoError;
+    use crate::money::Currency;
+    use crate::Crud;
+
+    // Not `super::*` - glob of DSL would shadow `kind` used by assert_eq!()
+    use super::{Money, PayrollRuleDTO, RuleKind, ValidationRules};
+
+    impl CrudTests for PayrollRuleDTO {}
+
+    fn rule(rule_name: &str, p: Option<i32>, a: Option<Money>) -> PayrollRuleDTO {
+        PayrollRuleDTO {
+            id: None,
+            name: rule_name.to_string(),
+            kind: RuleKind::Deduction,
+            percent: p,
+            amount: a,
+            active: true,
+            search_string: "".to_string(),
+            version: None,
+        }
+    }
+
+    #[test]
+    fn crud_operations_on_payroll_rule() {
+        let conn = &mut initialize();
+        rule("Social security", Some(1371), None).test(conn);
+        rule("Canteen", None, Some(Money::new(10000, Currency::PLN))).test(conn);
+    }
+
+    #[test]
+    fn payroll_rule_has_percent_or_amount() {
+        let conn = &mut initialize();
+        let rules = ValidationRules::default();
+        rule("Social security", Some(1371), None)
+            .try_save_in_transaction(&rules, keys(), conn)
+            .unwrap();
+        for (r, field) in [
+            (rule("Social security", Some(100), None), "name"),
+            (rule("Both", Some(100), Some(Money::new(100, Currency::PLN))), "percent"),
+            (rule("None", None, None), "percent"),
+            (rule("Too much", Some(10001), None), "percent"),
+            (rule("Negative", None, Some(Money::new(-100, Currency::PLN))), "amount"),
+        ] {
+            match r.try_save_in_transaction(&rules, keys(), conn) {
+                Err(DaoError::Validation(errors)) => assert_eq!(errors[0].field, field),
+                result => panic!("Should report validation error and instead I got {:?}", result),
+            }
+        }
+    }
+}
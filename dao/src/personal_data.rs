@@ -0,0 +1,210 @@
+use chrono::{Local, NaiveDate, NaiveDateTime};
+use diesel::prelude::*;
+
+use crate::absences_dao::{anonymize_absences_of, AbsenceDTO};
+use crate::audit_dao::{record, AuditAction, AuditEntryDTO};
+use crate::base_dao::{stale_version, SearchableByParent};
+use crate::connection::DbConnection;
+use crate::contacts_dao::anonymize_contacts_of;
+use crate::crypto::KeyRing;
+use crate::documents_dao::{delete_contents, delete_documents_of, DocumentDTO};
+use crate::photos_dao::delete_photos_of;
+use crate::employees_dao::EmployeeDTO;
+use crate::error::{DaoError, DaoResult};
+use crate::payroll_dao::{anonymize_payslips_of, payslips_of, PayslipDTO};
+use crate::schema::employees::dsl::*;
+use crate::storage::DocumentStorage;
+use crate::timesheets_dao::{anonymize_timesheet_of, TimesheetEntryDTO};
+use crate::validation::Errors;
+
+/// First name of anonymized employee - last name is `#<id>` so anonymized employees can be told apart
+pub const ANONYMIZED_NAME: &str = "Anonymized";
+
+/// What anonymization replaces - it is recorded in audit log
+const ANONYMIZED_DATA: &str = "name, employee number, date of birth, national id, emergency contacts, \
+custom fields, contact details, absence comments, timesheet notes, names on payslips, documents, photo";
+
+/// All personal data kept about employee (deleted one too) - contacts, salaries, contracts and emergency
+/// contacts are in the employee
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct PersonalDataExport {
+    pub exported_at: NaiveDateTime,
+    pub employee: EmployeeDTO,
+    pub absences: Vec<AbsenceDTO>,
+    pub timesheet_entries: Vec<TimesheetEntryDTO>,
+    pub payslips: Vec<PayslipDTO>,
+    /// Metadata of documents - their content is downloaded separately
+    pub documents: Vec<DocumentDTO>,
+    /// Who exported or anonymized the data and when - this export included
+    pub audit_entries: Vec<AuditEntryDTO>,
+}
+
+/// Export personal data of employee for `user_id` - the export is audited
+pub fn personal_data_export_with_connection(
+    e_id: i32,
+    user_id: i32,
+    keys: Option<&KeyRing>,
+    conn: &mut DbConnection,
+) -> DaoResult<PersonalDataExport> {
+    conn.transaction(|conn| {
+        let employee = EmployeeDTO::get_including_deleted_with_connection(e_id, keys, conn)?;
+        record(user_id, e_id, AuditAction::PersonalDataExport, "", conn)?;
+        Ok(PersonalDataExport {
+            exported_at: Local::now().naive_local(),
+            employee,
+            absences: AbsenceDTO::search_by_parent_id_with_connection(e_id, keys, conn)?,
+            timesheet_entries: TimesheetEntryDTO::search_by_parent_id_with_connection(e_id, keys, conn)?,
+            payslips: payslips_of(e_id, keys, conn)?,
+            documents: DocumentDTO::of_employee_with_connection(e_id, conn)?,
+            audit_entries: AuditEntryDTO::of_employee_with_connection(e_id, conn)?,
+        })
+    })
+}
+
+/// Irreversibly replace personal data of employee (deleted one too) - salaries, contracts, absences and
+/// timesheet minutes are kept, so reports and payroll still count with them. Documents are deleted (their content
+/// from `storage` too). DaoError::StaleVersion when employee is not in `expected_version`, validation error when it
+/// is already anonymized.
+pub fn anonymize_employee_with_connection(
+    e_id: i32,
+    expected_version: i32,
+    user_id: i32,
+    storage: &dyn DocumentStorage,
+    keys: Option<&KeyRing>,
+    conn: &mut DbConnection,
+) -> DaoResult<EmployeeDTO> {
+    use crate::schema::emergency_contacts::dsl as ec;
+    use crate::schema::users::dsl as u;
+
+    let (anonymized_employee, contents) = conn.transaction(|conn| {
+        let (current, anonymized) = employees
+            .filter(id.eq(e_id))
+            .select((version, anonymized_at))
+            .first::<(i32, Option<NaiveDateTime>)>(conn)
+            .optional()?
+            .ok_or_else(DaoError::not_found)?;
+        if current != expected_version {
+            return Err(stale_version(e_id, Some(current)));
+        }
+        let mut errors = Errors::default();
+        if anonymized.is_some() {
+            errors.add("", "anonymized_at", "employee is already anonymized".to_string());
+        }
+        errors.into_result()?;
+
+        let last = format!("#{}", e_id);
+        diesel::update(employees.filter(id.eq(e_id)))
+            .set((
+                first_name.eq(ANONYMIZED_NAME),
+                last_name.eq(&last),
+                search_string.eq(""),
+                employee_number.eq(format!("ANON-{}", e_id)),
+                date_of_birth.eq(None::<NaiveDate>),
+                national_id.eq(None::<String>),
+                national_id_hash.eq(None::<String>),
+                anonymized_at.eq(Some(Local::now().naive_local())),
+                version.eq(version + 1),
+            ))
+            .execute(conn)?;
+        diesel::delete(ec::emergency_contacts.filter(ec::employee_id.eq(e_id))).execute(conn)?;
+        crate::custom_fields_dao::delete_custom_values_of(e_id, conn)?;
+        diesel::update(u::users.filter(u::employee_id.eq(e_id)))
+            .set((u::employee_id.eq(None::<i32>), u::version.eq(u::version + 1)))
+            .execute(conn)?;
+        anonymize_contacts_of(e_id, conn)?;
+        anonymize_absences_of(e_id, conn)?;
+        anonymize_timesheet_of(e_id, conn)?;
+        anonymize_payslips_of(e_id, (ANONYMIZED_NAME, &last), conn)?;
+        let mut contents = delete_documents_of(&[e_id], conn)?;
+        contents.extend(delete_photos_of(&[e_id], conn)?);
+        record(user_id, e_id, AuditAction::Anonymization, ANONYMIZED_DATA, conn)?;
+        DaoResult::Ok((EmployeeDTO::get_including_deleted_with_connection(e_id, keys, conn)?, contents))
+    })?;
+    delete_contents(&contents, storage);
+    Ok(anonymized_employee)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::common_for_tests::*;
+    use crate::contacts_dao::{ContactDTO, PhoneDTO, PhoneKind};
+    use crate::documents_dao::tests::{contract, temporary_storage, PDF};
+    use crate::employees_dao::EmergencyContactDTO;
+    use crate::photos_dao::tests::png;
+    use crate::photos_dao::PhotoDTO;
+    use crate::Crud;
+
+    use super::*;
+
+    #[test]
+    fn anonymized_employee_keeps_no_personal_data() {
+        let conn = &mut initialize();
+        let saved = EmployeeDTO {
+            first_name: "Jan".to_string(),
+            last_name: "Kowalski".to_string(),
+            national_id: Some("85010112345".to_string()),
+            contacts: vec![ContactDTO {
+                id: None,
+                employee_id: None,
+                from_date: NaiveDate::from_ymd_opt(2020, 1, 1).unwrap(),
+                to_date: None,
+                emails: vec![],
+                phones: vec![PhoneDTO {
+                    kind: PhoneKind::Mobile,
+                    number: "+48602345678".to_string(),
+                }],
+                addresses: vec![],
+                search_string: "".to_string(),
+                version: None,
+            }],
+            emergency_contacts: vec![EmergencyContactDTO {
+                name: "Anna Kowalska".to_string(),
+                relationship: "wife".to_string(),
+                phone: "+48602345679".to_string(),
+                email: None,
+            }],
+            ..Default::default()
+        }
+        .save_in_transaction(keys(), conn)
+        .unwrap();
+        let e_id = saved.id.unwrap();
+        let (storage, dir) = temporary_storage();
+        let document =
+            DocumentDTO::upload_with_connection(e_id, &contract(), PDF, None, &Default::default(), &storage, conn)
+                .unwrap();
+        let photo = PhotoDTO::save_with_connection(e_id, &png(), None, 1024 * 1024, &storage, conn).unwrap();
+
+        let export = personal_data_export_with_connection(e_id, 1, keys(), conn).unwrap();
+        assert_eq!(export.employee.national_id, Some("85010112345".to_string()));
+        assert_eq!(export.employee.contacts[0].phones.len(), 1);
+        assert_eq!(export.documents, vec![document.clone()]);
+        assert_eq!(export.audit_entries.len(), 1, "The export itself should be audited");
+        assert_eq!(export.audit_entries[0].action, AuditAction::PersonalDataExport);
+        assert!(personal_data_export_with_connection(e_id + 1, 1, keys(), conn).unwrap_err().is_not_found());
+
+        assert!(anonymize_employee_with_connection(e_id, 0, 1, &storage, keys(), conn)
+            .unwrap_err()
+            .is_stale_version());
+        let anonymized =
+            anonymize_employee_with_connection(e_id, saved.version.unwrap(), 1, &storage, keys(), conn).unwrap();
+        assert_eq!(anonymized.first_name, ANONYMIZED_NAME);
+        assert_eq!(anonymized.last_name, format!("#{}", e_id));
+        assert_eq!(anonymized.national_id, None);
+        assert!(anonymized.emergency_contacts.is_empty());
+        assert_eq!(anonymized.contacts.len(), 1, "Contact periods should be kept");
+        assert!(anonymized.contacts[0].phones.is_empty());
+        assert!(anonymized.anonymized_at.is_some());
+        assert!(DocumentDTO::of_employee_with_connection(e_id, conn).unwrap().is_empty());
+        assert!(document.content(&storage).is_err(), "Content of document should be deleted");
+        assert_eq!(anonymized.photo, None);
+        assert!(photo.content(e_id, Some(64), &storage).is_err(), "Thumbnails should be deleted");
+        assert!(anonymize_employee_with_connection(e_id, anonymized.version.unwrap(), 1, &storage, keys(), conn)
+            .unwrap_err()
+            .is_validation());
+        let audit = AuditEntryDTO::of_employee_with_connection(e_id, conn).unwrap();
+        assert_eq!(audit.len(), 2);
+        assert_eq!(audit[1].action, AuditAction::Anonymization);
+        assert!(!audit[1].details.contains("Kowalski"));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,422 @@
+use std::collections::BTreeMap;
+use std::io::Cursor;
+
+use chrono::{Local, NaiveDateTime};
+use diesel::dsl::*;
+use diesel::prelude::*;
+use image::codecs::jpeg::JpegEncoder;
+use image::imageops::FilterType;
+use image::{DynamicImage, ImageDecoder, ImageFormat, ImageReader, Limits};
+use sha2::{Digest, Sha256};
+
+use crate::connection::DbConnection;
+use crate::error::{DaoError, DaoResult};
+use crate::models::{EmployeePhoto, NewEmployeePhoto};
+use crate::schema::employee_photos::dsl::*;
+use crate::schema::employees::dsl as e;
+use crate::storage::DocumentStorage;
+use crate::validation::FieldError;
+
+/// Width and height (in pixels) of square thumbnails generated for every photo
+pub const THUMBNAIL_SIZES: [u32; 3] = [64, 128, 256];
+
+/// Photos with bigger width or height are rejected before they are decoded
+const MAX_DIMENSION: u32 = 8192;
+
+const JPEG_QUALITY: u8 = 90;
+
+/// Photo of employee - its original and thumbnails are served by `GET /employees/{id}/photo[?size=...]`
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct PhotoDTO {
+    pub content_type: String,
+    /// Of original (after it is rotated as its EXIF orientation say)
+    pub width: i32,
+    pub height: i32,
+    /// Hex of SHA-256 of original - it changes with every new photo, so it is its ETag
+    pub sha256: String,
+    pub uploaded_by: Option<i32>,
+    pub uploaded_at: NaiveDateTime,
+    /// URL of original
+    pub url: String,
+    /// URLs of thumbnails by their size
+    pub thumbnails: BTreeMap<u32, String>,
+}
+
+impl From<EmployeePhoto> for PhotoDTO {
+    fn from(p: EmployeePhoto) -> Self {
+        let url = format!("/employees/{}/photo", p.employee_id);
+        PhotoDTO {
+            thumbnails: THUMBNAIL_SIZES
+                .iter()
+                .map(|size| (*size, format!("{}?size={}&v={}", url, size, p.sha256)))
+                .collect(),
+            url: format!("{}?v={}", url, p.sha256),
+            content_type: p.content_type,
+            width: p.width,
+            height: p.height,
+            sha256: p.sha256,
+            uploaded_by: p.uploaded_by,
+            uploaded_at: p.uploaded_at,
+        }
+    }
+}
+
+/// Photo decoded, rotated and encoded again (without any metadata) with its thumbnails
+struct ProcessedPhoto {
+    format: ImageFormat,
+    width: u32,
+    height: u32,
+    original: Vec<u8>,
+    thumbnails: Vec<(u32, Vec<u8>)>,
+}
+
+fn encode(image: &DynamicImage, format: ImageFormat) -> image::ImageResult<Vec<u8>> {
+    let mut encoded = Vec::new();
+    match format {
+        // JPEG has no alpha channel
+        ImageFormat::Jpeg => DynamicImage::ImageRgb8(image.to_rgb8())
+            .write_with_encoder(JpegEncoder::new_with_quality(&mut encoded, JPEG_QUALITY))?,
+        _ => image.write_to(Cursor::new(&mut encoded), format)?,
+    }
+    Ok(encoded)
+}
+
+/// JPEG, PNG or WebP (recognized by content) - Err with description of problem otherwise
+fn process(content: &[u8]) -> Result<ProcessedPhoto, String> {
+    let reader = ImageReader::new(Cursor::new(content))
+        .with_guessed_format()
+        .map_err(|e| e.to_string())?;
+    let format = match reader.format() {
+        Some(format @ (ImageFormat::Jpeg | ImageFormat::Png | ImageFormat::WebP)) => format,
+        _ => return Err("is not JPEG, PNG or WebP image".to_string()),
+    };
+    let mut limits = Limits::default();
+    limits.max_image_width = Some(MAX_DIMENSION);
+    limits.max_image_height = Some(MAX_DIMENSION);
+    let mut decoder = reader.into_decoder().map_err(|e| format!("can't be read: {}", e))?;
+    decoder.set_limits(limits).map_err(|e| format!("can't be read: {}", e))?;
+    let orientation = decoder.orientation().map_err(|e| format!("can't be read: {}", e))?;
+    let mut image = DynamicImage::from_decoder(decoder).map_err(|e| format!("can't be read: {}", e))?;
+    image.apply_orientation(orientation);
+    let encoding_failed = |e: image::ImageError| format!("can't be encoded: {}", e);
+    let thumbnails = THUMBNAIL_SIZES
+        .iter()
+        .map(|size| {
+            let thumbnail = image.resize_to_fill(*size, *size, FilterType::Lanczos3);
+            encode(&thumbnail, format).map(|encoded| (*size, encoded))
+        })
+        .collect::<Result<_, _>>()
+        .map_err(encoding_failed)?;
+    Ok(ProcessedPhoto {
+        format,
+        width: image.width(),
+        height: image.height(),
+        original: encode(&image, format).map_err(encoding_failed)?,
+        thumbnails,
+    })
+}
+
+/// Where original (size None) or thumbnail of photo is stored
+fn storage_key(e_id: i32, checksum: &str, size: Option<u32>) -> String {
+    match size {
+        Some(size) => format!("photos/{}/{}/{}", e_id, checksum, size),
+        None => format!("photos/{}/{}/original", e_id, checksum),
+    }
+}
+
+fn storage_keys(e_id: i32, checksum: &str) -> Vec<String> {
+    std::iter::once(None)
+        .chain(THUMBNAIL_SIZES.iter().copied().map(Some))
+        .map(|size| storage_key(e_id, checksum, size))
+        .collect()
+}
+
+impl PhotoDTO {
+    /// Photo of employee - None when it has none
+    pub fn of_employee_with_connection(e_id: i32, conn: &mut DbConnection) -> QueryResult<Option<Self>> {
+        employee_photos
+            .filter(employee_id.eq(e_id))
+            .first::<EmployeePhoto>(conn)
+            .optional()
+            .map(|p| p.map(Self::from))
+    }
+
+    /// Photos of employees by employee - employees without photo are left out
+    pub(crate) fn of_employees_with_connection(
+        e_ids: &[i32],
+        conn: &mut DbConnection,
+    ) -> QueryResult<BTreeMap<i32, Self>> {
+        Ok(employee_photos
+            .filter(employee_id.eq_any(e_ids))
+            .load::<EmployeePhoto>(conn)?
+            .into_iter()
+            .map(|p| (p.employee_id, Self::from(p)))
+            .collect())
+    }
+
+    /// Replace photo of employee (old one is deleted from `storage`) - it is stored without metadata (EXIF ...) and
+    /// rotated as its EXIF orientation say. DaoError::not_found() when there is no such (not deleted) employee,
+    /// validation error when content is too big or it is not JPEG, PNG or WebP image.
+    pub fn save_with_connection(
+        e_id: i32,
+        content: &[u8],
+        user_id: Option<i32>,
+        max_size: u64,
+        storage: &dyn DocumentStorage,
+        conn: &mut DbConnection,
+    ) -> DaoResult<Self> {
+        let exists = e::employees
+            .filter(e::id.eq(e_id))
+            .filter(e::deleted_at.is_null())
+            .select(count(e::id))
+            .first::<i64>(conn)?;
+        if exists == 0 {
+            return Err(DaoError::not_found());
+        }
+        let processed = if content.is_empty() {
+            Err("can't be empty".to_string())
+        } else if content.len() as u64 > max_size {
+            Err(format!("is bigger than {} bytes", max_size))
+        } else {
+            // Nothing bigger than max_size is stored (and read back) - even when it grows by encoding
+            process(content).and_then(|photo| {
+                if photo.original.len() as u64 > max_size {
+                    Err(format!("is bigger than {} bytes once encoded", max_size))
+                } else {
+                    Ok(photo)
+                }
+            })
+        };
+        let photo = processed.map_err(|message| {
+            DaoError::Validation(vec![FieldError {
+                field: "photo".to_string(),
+                message,
+            }])
+        })?;
+
+        let checksum = format!("{:x}", Sha256::digest(&photo.original));
+        let (saved, replaced) = conn.transaction(|conn| {
+            let replaced = Self::of_employee_with_connection(e_id, conn)?;
+            diesel::delete(employee_photos.filter(employee_id.eq(e_id))).execute(conn)?;
+            insert_into(employee_photos)
+                .values(NewEmployeePhoto {
+                    employee_id: e_id,
+                    content_type: photo.format.to_mime_type().to_string(),
+                    width: photo.width as i32,
+                    height: photo.height as i32,
+                    sha256: checksum.clone(),
+                    uploaded_by: user_id,
+                    uploaded_at: Local::now().naive_local(),
+                })
+                .execute(conn)?;
+            storage.put(&storage_key(e_id, &checksum, None), &photo.original)?;
+            for (size, thumbnail) in &photo.thumbnails {
+                storage.put(&storage_key(e_id, &checksum, Some(*size)), thumbnail)?;
+            }
+            let saved = Self::of_employee_with_connection(e_id, conn)?.ok_or_else(DaoError::not_found)?;
+            DaoResult::Ok((saved, replaced))
+        })?;
+        if let Some(replaced) = replaced.filter(|r| r.sha256 != checksum) {
+            crate::documents_dao::delete_contents(&storage_keys(e_id, &replaced.sha256), storage);
+        }
+        Ok(saved)
+    }
+
+    /// Original (size None) or thumbnail of photo - validation error when there is no thumbnail of such size
+    pub fn content(&self, e_id: i32, size: Option<u32>, storage: &dyn DocumentStorage) -> DaoResult<Vec<u8>> {
+        if let Some(size) = size
+            && !THUMBNAIL_SIZES.contains(&size)
+        {
+            let sizes: Vec<String> = THUMBNAIL_SIZES.iter().map(u32::to_string).collect();
+            return Err(DaoError::Validation(vec![FieldError {
+                field: "size".to_string(),
+                message: format!("should be one of {}", sizes.join(", ")),
+            }]));
+        }
+        Ok(storage.get(&storage_key(e_id, &self.sha256, size))?)
+    }
+
+    /// Delete photo of employee - DaoError::not_found() when it has none
+    pub fn delete_with_connection(
+        e_id: i32,
+        storage: &dyn DocumentStorage,
+        conn: &mut DbConnection,
+    ) -> DaoResult<usize> {
+        let keys = delete_photos_of(&[e_id], conn)?;
+        if keys.is_empty() {
+            return Err(DaoError::not_found());
+        }
+        crate::documents_dao::delete_contents(&keys, storage);
+        Ok(1)
+    }
+}
+
+/// Delete photos of employees - return storage keys of their originals and thumbnails which should be deleted by
+/// delete_contents() when the transaction is committed
+pub(crate) fn delete_photos_of(e_ids: &[i32], conn: &mut DbConnection) -> QueryResult<Vec<String>> {
+    let photos: Vec<(i32, String)> = employee_photos
+        .filter(employee_id.eq_any(e_ids))
+        .select((employee_id, sha256))
+        .load(conn)?;
+    diesel::delete(employee_photos.filter(employee_id.eq_any(e_ids))).execute(conn)?;
+    Ok(photos
+        .iter()
+        .flat_map(|(e_id, checksum)| storage_keys(*e_id, checksum))
+        .collect())
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use image::{Rgb, RgbImage, Rgba, RgbaImage};
+
+    use crate::common_for_tests::*;
+    use crate::documents_dao::tests::temporary_storage;
+    use crate::employees_dao::EmployeeDTO;
+    use crate::storage::StorageError;
+    use crate::Crud;
+
+    use super::*;
+
+    /// JPEG with EXIF saying it should be rotated by 90 degrees clockwise
+    fn rotated_jpeg(w: u32, h: u32) -> Vec<u8> {
+        jpeg_with_metadata(w, h, b"")
+    }
+
+    /// JPEG with EXIF saying it should be rotated by 90 degrees clockwise - `private` is put after EXIF entries and
+    /// in comment of JPEG
+    fn jpeg_with_metadata(w: u32, h: u32, private: &[u8]) -> Vec<u8> {
+        let image = DynamicImage::ImageRgb8(RgbImage::from_pixel(w, h, Rgb([200, 30, 30])));
+        let jpeg = encode(&image, ImageFormat::Jpeg).unwrap();
+        let tiff: &[u8] = &[
+            b'I', b'I', 0x2A, 0, 8, 0, 0, 0, 1, 0, 0x12, 0x01, 3, 0, 1, 0, 0, 0, 6, 0, 0, 0, 0, 0, 0, 0,
+        ];
+        let mut exif = vec![0xFF, 0xE1];
+        exif.extend_from_slice(&((2 + 6 + tiff.len() + private.len()) as u16).to_be_bytes());
+        exif.extend_from_slice(b"Exif\0\0");
+        exif.extend_from_slice(tiff);
+        exif.extend_from_slice(private);
+        let mut comment = vec![];
+        if !private.is_empty() {
+            comment.extend_from_slice(&[0xFF, 0xFE]);
+            comment.extend_from_slice(&((2 + private.len()) as u16).to_be_bytes());
+            comment.extend_from_slice(private);
+        }
+        [&jpeg[..2], &exif, &comment, &jpeg[2..]].concat()
+    }
+
+    pub fn png() -> Vec<u8> {
+        let image = DynamicImage::ImageRgba8(RgbaImage::from_pixel(40, 30, Rgba([0, 0, 255, 128])));
+        encode(&image, ImageFormat::Png).unwrap()
+    }
+
+    #[test]
+    fn photos_are_processed_replaced_and_deleted() {
+        let conn = &mut initialize();
+        let (storage, dir) = temporary_storage();
+        let e_id = EmployeeDTO {
+            first_name: "Jan".to_string(),
+            last_name: "Kowalski".to_string(),
+            ..Default::default()
+        }
+        .save_in_transaction(keys(), conn)
+        .unwrap()
+        .id
+        .unwrap();
+        assert_eq!(PhotoDTO::of_employee_with_connection(e_id, conn).unwrap(), None);
+
+        let jpeg = rotated_jpeg(300, 200);
+        assert!(jpeg.windows(4).any(|w| w == b"Exif"));
+        let saved = PhotoDTO::save_with_connection(e_id, &jpeg, Some(1), 1024 * 1024, &storage, conn).unwrap();
+        assert_eq!((saved.content_type.as_str(), saved.width, saved.height), ("image/jpeg", 200, 300));
+        assert_eq!(saved.url, format!("/employees/{}/photo?v={}", e_id, saved.sha256));
+        assert_eq!(
+            saved.thumbnails[&64],
+            format!("/employees/{}/photo?size=64&v={}", e_id, saved.sha256)
+        );
+        let original = saved.content(e_id, None, &storage).unwrap();
+        assert!(!original.windows(4).any(|w| w == b"Exif"), "Metadata should be stripped");
+        assert_eq!(format!("{:x}", Sha256::digest(&original)), saved.sha256);
+        for size in THUMBNAIL_SIZES {
+            let thumbnail = image::load_from_memory(&saved.content(e_id, Some(size), &storage).unwrap()).unwrap();
+            assert_eq!((thumbnail.width(), thumbnail.height()), (size, size));
+        }
+        match saved.content(e_id, Some(100), &storage) {
+            Err(DaoError::Validation(errors)) => assert_eq!(errors[0].field, "size"),
+            result => panic!("Should report validation error and instead I got {:?}", result.map(|c| c.len())),
+        }
+        let employee = EmployeeDTO::get_with_conn(e_id, keys(), conn).unwrap();
+        assert_eq!(employee.photo, Some(saved.clone()));
+
+        // New photo replaces the old one together with its thumbnails
+        let replaced = PhotoDTO::save_with_connection(e_id, &png(), None, 1024 * 1024, &storage, conn).unwrap();
+        assert_eq!((replaced.content_type.as_str(), replaced.width, replaced.height), ("image/png", 40, 30));
+        assert!(matches!(saved.content(e_id, None, &storage), Err(DaoError::Storage(StorageError::NotFound(_)))));
+        assert!(matches!(
+            saved.content(e_id, Some(64), &storage),
+            Err(DaoError::Storage(StorageError::NotFound(_)))
+        ));
+
+        let png = png();
+        for (content, max_size) in [
+            (&b""[..], 1024),
+            (&b"%PDF-1.4\n%%EOF\n"[..], 1024),
+            (&png[..png.len() / 2], 1024 * 1024),
+            (&jpeg[..], 100),
+        ] {
+            match PhotoDTO::save_with_connection(e_id, content, None, max_size, &storage, conn) {
+                Err(DaoError::Validation(errors)) => assert_eq!(errors[0].field, "photo"),
+                result => panic!("Should report validation error and instead I got {:?}", result),
+            }
+        }
+        assert!(PhotoDTO::save_with_connection(e_id + 1, &png, None, 1024, &storage, conn)
+            .unwrap_err()
+            .is_not_found());
+
+        assert_eq!(PhotoDTO::delete_with_connection(e_id, &storage, conn).unwrap(), 1);
+        assert!(replaced.content(e_id, None, &storage).is_err());
+        assert_eq!(PhotoDTO::of_employee_with_connection(e_id, conn).unwrap(), None);
+        assert!(PhotoDTO::delete_with_connection(e_id, &storage, conn).unwrap_err().is_not_found());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn metadata_is_stripped_from_photo_and_its_thumbnails() {
+        let conn = &mut initialize();
+        let (storage, dir) = temporary_storage();
+        let e_id = EmployeeDTO {
+            first_name: "Jan".to_string(),
+            last_name: "Kowalski".to_string(),
+            ..Default::default()
+        }
+        .save_in_transaction(keys(), conn)
+        .unwrap()
+        .id
+        .unwrap();
+        let private = b"GPS 52.2297N 21.0122E, camera serial 0042";
+        let contains = |content: &[u8], part: &[u8]| content.windows(part.len()).any(|w| w == part);
+
+        let jpeg = jpeg_with_metadata(300, 120, private);
+        assert!(contains(&jpeg, b"Exif") && contains(&jpeg, private));
+        let saved = PhotoDTO::save_with_connection(e_id, &jpeg, None, 1024 * 1024, &storage, conn).unwrap();
+        assert_eq!((saved.width, saved.height), (120, 300), "Photo should be rotated as EXIF say");
+        let original = saved.content(e_id, None, &storage).unwrap();
+        assert!(!contains(&original, b"Exif") && !contains(&original, private));
+        let decoded = image::load_from_memory(&original).unwrap();
+        assert_eq!((decoded.width(), decoded.height()), (120, 300));
+        for size in THUMBNAIL_SIZES {
+            let thumbnail = saved.content(e_id, Some(size), &storage).unwrap();
+            let stripped = !contains(&thumbnail, b"Exif") && !contains(&thumbnail, private);
+            assert!(stripped, "Thumbnail {} should be without metadata", size);
+            let decoded = image::load_from_memory(&thumbnail).unwrap();
+            assert_eq!((decoded.width(), decoded.height()), (size, size), "Thumbnail is cropped to square");
+        }
+
+        // Thumbnails of photo smaller than them are enlarged
+        let small = PhotoDTO::save_with_connection(e_id, &png(), None, 1024 * 1024, &storage, conn).unwrap();
+        for size in THUMBNAIL_SIZES {
+            let thumbnail = image::load_from_memory(&small.content(e_id, Some(size), &storage).unwrap()).unwrap();
+            assert_eq!((thumbnail.width(), thumbnail.height()), (size, size));
+        }
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,221 @@
+use diesel::dsl::*;
+use diesel::prelude::*;
+
+use crate::base_dao::{stale_version, Crud, HaveId, HaveVersion, Searchable};
+use crate::connection::DbConnection;
+use crate::crypto::KeyRing;
+use crate::error::DaoResult;
+use crate::models::{NewPosition, Position};
+use crate::schema::positions::dsl::id as position_id;
+use crate::schema::positions::dsl::*;
+use crate::validation::{Errors, ValidationRules};
+
+/// Position (job title) from catalog - employment contracts are for position
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct PositionDTO {
+    pub id: Option<i32>,
+    pub name: String,
+    pub search_string: String,
+    pub version: Option<i32>,
+}
+
+impl From<Position> for PositionDTO {
+    fn from(p: Position) -> Self {
+        PositionDTO {
+            id: Some(p.id),
+            name: p.name,
+            search_string: p.search_string,
+            version: Some(p.version),
+        }
+    }
+}
+
+impl From<&PositionDTO> for Position {
+    fn from(position_dto: &PositionDTO) -> Self {
+        Position {
+            id: position_dto.id.unwrap(),
+            name: position_dto.name.clone(),
+            search_string: position_dto.search_string.clone(),
+            version: position_dto.version.unwrap_or_default(),
+        }
+    }
+}
+
+impl From<&PositionDTO> for NewPosition {
+    fn from(position_dto: &PositionDTO) -> Self {
+        NewPosition {
+            name: position_dto.name.clone(),
+            search_string: position_dto.search_string.clone(),
+        }
+    }
+}
+
+impl HaveId for PositionDTO {
+    fn get_id(&self) -> Option<i32> {
+        self.id
+    }
+}
+
+impl HaveVersion for PositionDTO {
+    fn get_version(&self) -> Option<i32> {
+        self.version
+    }
+}
+
+impl Crud for PositionDTO {
+    fn update(&mut self, persisted: &Self) {
+        self.id = persisted.id;
+        self.version = persisted.version;
+    }
+
+    /// Name is required and unique
+    fn validate(&self, _rules: &ValidationRules, _keys: Option<&KeyRing>, conn: &mut DbConnection) -> DaoResult<()> {
+        let mut errors = Errors::default();
+        if self.name.trim().is_empty() {
+            errors.add("", "name", "can't be empty".to_string());
+        }
+        let same_name = positions
+            .filter(name.eq(&self.name))
+            .select(position_id)
+            .first::<i32>(conn)
+            .optional()?;
+        if let Some(other) = same_name
+            && Some(other) != self.id
+        {
+            errors.add("", "name", format!("'{}' is already name of position id = {}", self.name, other));
+        }
+        errors.into_result()
+    }
+
+    fn get_simple(id_to_find: i32, _keys: Option<&KeyRing>, conn: &mut DbConnection) -> QueryResult<Self> {
+        positions
+            .filter(position_id.eq(id_to_find))
+            .first(conn)
+            .map(|p: Position| PositionDTO::from(p))
+    }
+
+    fn save_simple(&self, keys: Option<&KeyRing>, conn: &mut DbConnection) -> DaoResult<Self> {
+        fn insert(p: &PositionDTO, conn: &mut DbConnection) -> QueryResult<PositionDTO> {
+            insert_into(positions)
+                .values(NewPosition::from(p))
+                .get_result(conn)
+                .map(|p: Position| PositionDTO::from(p))
+        }
+        if let Some(self_id) = self.id {
+            let updated = match self.version {
+                Some(self_version) => diesel::update(
+                    positions
+                        .filter(position_id.eq(self_id))
+                        .filter(version.eq(self_version)),
+                )
+                .set((Position::from(self), version.eq(version + 1)))
+                .execute(conn)?,
+                None => 0,
+            };
+            if updated == 0 {
+                let current = positions
+                    .filter(position_id.eq(self_id))
+                    .select(version)
+                    .first::<i32>(conn)
+                    .optional()?;
+                match current {
+                    Some(current) => Err(stale_version(self_id, Some(current))),
+                    None => Ok(insert(self, conn)?),
+                }
+            } else {
+                Ok(Self::get_simple(self_id, keys, conn)?)
+            }
+        } else {
+            Ok(insert(self, conn)?)
+        }
+    }
+
+    /// Contracts for deleted position are left without position
+    fn delete_simple(id_to_find: i32, conn: &mut DbConnection) -> QueryResult<usize> {
+        use crate::schema::employment_contracts::dsl as c;
+
+        diesel::update(c::employment_contracts.filter(c::position_id.eq(id_to_find)))
+            .set((c::position_id.eq(None::<i32>), c::version.eq(c::version + 1)))
+            .execute(conn)?;
+        diesel::delete(positions.filter(position_id.eq(id_to_find))).execute(conn)
+    }
+
+    fn delete_versioned_simple(
+        id_to_find: i32,
+        version_to_find: i32,
+        conn: &mut DbConnection,
+    ) -> QueryResult<usize> {
+        // Bump version first - it check version and lock the row before contracts are changed
+        let locked = diesel::update(
+            positions
+                .filter(position_id.eq(id_to_find))
+                .filter(version.eq(version_to_find)),
+        )
+        .set(version.eq(version + 1))
+        .execute(conn)?;
+        if locked == 0 {
+            return Ok(0);
+        }
+        Self::delete_simple(id_to_find, conn)
+    }
+}
+
+impl Searchable for PositionDTO {
+    fn get_all_with_connection(_keys: Option<&KeyRing>, conn: &mut DbConnection) -> DaoResult<Vec<Self>> {
+        Ok(positions
+            .order(name)
+            .load::<Position>(conn)?
+            .into_iter()
+            .map(Self::from)
+            .collect())
+    }
+
+    fn search_with_connection(s: &str, _keys: Option<&KeyRing>, conn: &mut DbConnection) -> DaoResult<Vec<Self>> {
+        Ok(positions
+            .filter(search_string.like(s))
+            .load::<Position>(conn)?
+            .into_iter()
+            .map(Self::from)
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::common_for_tests::*;
+    use crate::error::DaoError;
+
+    use super::*;
+
+    impl CrudTests for PositionDTO {}
+
+    fn position(position_name: &str) -> PositionDTO {
+        PositionDTO {
+            id: None,
+            name: position_name.to_string(),
+            search_string: "".to_string(),
+            version: None,
+        }
+    }
+
+    #[test]
+    fn crud_operations_on_position() {
+        let conn = &mut initialize();
+        position("Developer").test(conn);
+    }
+
+    #[test]
+    fn position_name_is_unique() {
+        let conn = &mut initialize();
+        let rules = ValidationRules::default();
+        let developer = position("Developer").save_in_transaction(keys(), conn).unwrap();
+        for (p, field) in [(position("Developer"), "name"), (position(" "), "name")] {
+            match p.try_save_in_transaction(&rules, keys(), conn) {
+                Err(DaoError::Validation(errors)) => assert_eq!(errors[0].field, field),
+                result => panic!("Should report validation error and instead I got {:?}", result),
+            }
+        }
+        // Position can be saved with its own name
+        assert!(developer.try_save_in_transaction(&rules, keys(), conn).is_ok());
+    }
+}
@@ -0,0 +1,1171 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use chrono::{Datelike, Days, Months, NaiveDate};
+use diesel::prelude::*;
+use diesel::sql_types::{BigInt, Bool, Date, Integer, Nullable, Text};
+
+use crate::connection::{sql, Database, DbConnection};
+use crate::crypto::{self, KeyRing};
+use crate::employee_groups_dao::EmployeeGroupDTO;
+use crate::error::DaoResult;
+use crate::hierarchy::EmployeeScope;
+use crate::money::{Currency, Money, PayPeriod};
+use crate::salaries_dao::amount_of;
+use crate::schema::salaries;
+use crate::validation::Errors;
+
+/// Hourly salaries are counted as `HOURS_PER_MONTH` hours of work in month
+pub const HOURS_PER_MONTH: i64 = 168;
+
+/// Salary amount per month - yearly salaries are divided by 12, hourly multiplied by HOURS_PER_MONTH
+fn monthly_amount(amount: i64, pay_period: PayPeriod) -> i64 {
+    match pay_period {
+        PayPeriod::Yearly => amount / 12,
+        PayPeriod::Hourly => amount * HOURS_PER_MONTH,
+        PayPeriod::Monthly => amount,
+    }
+}
+
+/// SQL expression of salary amount per month - the same as monthly_amount()
+fn monthly_amount_sql() -> String {
+    format!(
+        "CASE s.pay_period WHEN 'yearly' THEN s.amount / 12 WHEN 'hourly' THEN s.amount * {} ELSE s.amount END",
+        HOURS_PER_MONTH
+    )
+}
+
+/// First day of month of `from` ($1)
+#[cfg(feature = "sqlite")]
+const FIRST_MONTH: &str = "date($1, 'start of month')";
+#[cfg(feature = "postgres")]
+const FIRST_MONTH: &str = "CAST(date_trunc('month', CAST($1 AS DATE)) AS DATE)";
+#[cfg(feature = "sqlite")]
+const NEXT_MONTH: &str = "date(month_start, '+1 month')";
+#[cfg(feature = "postgres")]
+const NEXT_MONTH: &str = "CAST(month_start + INTERVAL '1 month' AS DATE)";
+#[cfg(feature = "sqlite")]
+const MONTH_END: &str = "date(m.month_start, '+1 month', '-1 day')";
+#[cfg(feature = "postgres")]
+const MONTH_END: &str = "CAST(m.month_start + INTERVAL '1 month' - INTERVAL '1 day' AS DATE)";
+
+/// Salaries (`s`) of deleted employees are not reported
+const NOT_DELETED: &str = "s.employee_id IN (SELECT id FROM employees WHERE deleted_at IS NULL)";
+
+/// What (besides currency) salary costs and statistics are broken down by
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ReportGrouping {
+    /// Department of employee
+    Department,
+    /// Position of contract salary is paid under
+    Position,
+}
+
+impl ReportGrouping {
+    /// Joins (to salary `s`) of group `g` and its id and name columns
+    fn sql(grouping: Option<ReportGrouping>) -> (&'static str, &'static str, &'static str) {
+        match grouping {
+            None => ("", "CAST(NULL AS INTEGER)", "CAST(NULL AS TEXT)"),
+            Some(ReportGrouping::Department) => (
+                "JOIN employees ge ON ge.id = s.employee_id LEFT JOIN departments g ON g.id = ge.department_id",
+                "g.id",
+                "g.name",
+            ),
+            Some(ReportGrouping::Position) => (
+                "LEFT JOIN employment_contracts gc ON gc.id = s.contract_id LEFT JOIN positions g ON g.id = gc.position_id",
+                "g.id",
+                "g.name",
+            ),
+        }
+    }
+}
+
+/// Department or position salaries are reported for - `id` and `name` are None for salaries without one
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct ReportGroup {
+    pub id: Option<i32>,
+    pub name: Option<String>,
+}
+
+/// What salaries are reported
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct SalaryReportParams {
+    pub from: NaiveDate,
+    pub to: NaiveDate,
+    /// Only salaries in this currency - all currencies (each reported separately) by default
+    pub currency: Option<Currency>,
+    /// Gross or net salaries - they are never mixed
+    pub gross: bool,
+    /// Monthly costs and statistics are reported per group too
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub group_by: Option<ReportGrouping>,
+    /// Only salaries of members of saved group (see EmployeeGroupDTO)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub employee_group: Option<i32>,
+}
+
+/// Cost of salaries active in given month (at least one day) - one per month and currency,
+/// months without salaries are omitted
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct MonthlyCost {
+    /// First day of month
+    pub month: NaiveDate,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub group: Option<ReportGroup>,
+    pub currency: Currency,
+    pub total: Money,
+    pub headcount: i64,
+}
+
+/// Statistics of monthly salaries of employees active in report period - the latest salary of every employee
+/// is counted. Percentiles use nearest-rank method.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct SalaryStatistics {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub group: Option<ReportGroup>,
+    pub currency: Currency,
+    pub headcount: i64,
+    pub total: Money,
+    pub average: Money,
+    pub minimum: Money,
+    pub p25: Money,
+    pub median: Money,
+    pub p75: Money,
+    pub p90: Money,
+    pub maximum: Money,
+}
+
+/// Change of monthly salary of employee started in report period
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct PayRaise {
+    pub employee_id: i32,
+    pub first_name: String,
+    pub last_name: String,
+    pub from_date: NaiveDate,
+    pub previous: Money,
+    pub current: Money,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct SalaryReport {
+    #[serde(flatten)]
+    pub params: SalaryReportParams,
+    pub monthly: Vec<MonthlyCost>,
+    pub statistics: Vec<SalaryStatistics>,
+    pub raises: Vec<PayRaise>,
+}
+
+#[derive(QueryableByName)]
+struct MonthlyCostRow {
+    #[diesel(sql_type = Date)]
+    month: NaiveDate,
+    #[diesel(sql_type = Nullable<Integer>)]
+    group_id: Option<i32>,
+    #[diesel(sql_type = Nullable<Text>)]
+    group_name: Option<String>,
+    #[diesel(sql_type = Text)]
+    currency: String,
+    #[diesel(sql_type = BigInt)]
+    total: i64,
+    #[diesel(sql_type = BigInt)]
+    headcount: i64,
+}
+
+#[derive(QueryableByName)]
+struct StatisticsRow {
+    #[diesel(sql_type = Nullable<Integer>)]
+    group_id: Option<i32>,
+    #[diesel(sql_type = Nullable<Text>)]
+    group_name: Option<String>,
+    #[diesel(sql_type = Text)]
+    currency: String,
+    #[diesel(sql_type = BigInt)]
+    headcount: i64,
+    #[diesel(sql_type = BigInt)]
+    total: i64,
+    #[diesel(sql_type = BigInt)]
+    minimum: i64,
+    #[diesel(sql_type = Nullable<BigInt>)]
+    p25: Option<i64>,
+    #[diesel(sql_type = Nullable<BigInt>)]
+    median: Option<i64>,
+    #[diesel(sql_type = Nullable<BigInt>)]
+    p75: Option<i64>,
+    #[diesel(sql_type = Nullable<BigInt>)]
+    p90: Option<i64>,
+    #[diesel(sql_type = BigInt)]
+    maximum: i64,
+}
+
+#[derive(QueryableByName)]
+struct PayRaiseRow {
+    #[diesel(sql_type = Integer)]
+    employee_id: i32,
+    #[diesel(sql_type = Text)]
+    first_name: String,
+    #[diesel(sql_type = Text)]
+    last_name: String,
+    #[diesel(sql_type = Date)]
+    from_date: NaiveDate,
+    #[diesel(sql_type = Text)]
+    currency: String,
+    #[diesel(sql_type = BigInt)]
+    previous_amount: i64,
+    #[diesel(sql_type = BigInt)]
+    monthly_amount: i64,
+}
+
+#[derive(QueryableByName)]
+struct SalaryRow {
+    #[diesel(sql_type = Integer)]
+    id: i32,
+    #[diesel(sql_type = Integer)]
+    employee_id: i32,
+    #[diesel(sql_type = Date)]
+    from_date: NaiveDate,
+    #[diesel(sql_type = Nullable<Date>)]
+    to_date: Option<NaiveDate>,
+    #[diesel(sql_type = BigInt)]
+    amount: i64,
+    #[diesel(sql_type = Nullable<Text>)]
+    amount_encrypted: Option<String>,
+    #[diesel(sql_type = Text)]
+    pay_period: String,
+    #[diesel(sql_type = Text)]
+    currency: String,
+    #[diesel(sql_type = Nullable<Integer>)]
+    group_id: Option<i32>,
+    #[diesel(sql_type = Nullable<Text>)]
+    group_name: Option<String>,
+}
+
+/// Salary as it is reported - with monthly amount and group
+struct ReportedSalary {
+    employee_id: i32,
+    from_date: NaiveDate,
+    to_date: Option<NaiveDate>,
+    monthly: i64,
+    currency: Currency,
+    group_id: Option<i32>,
+    group_name: Option<String>,
+}
+
+impl ReportedSalary {
+    fn is_active(&self, from: NaiveDate, to: NaiveDate) -> bool {
+        self.from_date <= to && self.to_date.is_none_or(|end| end >= from)
+    }
+
+    fn in_currency(&self, params: &SalaryReportParams) -> bool {
+        params.currency.is_none_or(|c| c == self.currency)
+    }
+
+    /// Rows of report are ordered by group name (salaries without group last) and currency
+    fn order_key(&self) -> (bool, Option<String>, Option<i32>, &'static str) {
+        (self.group_name.is_none(), self.group_name.clone(), self.group_id, self.currency.code())
+    }
+}
+
+/// Currency of salary - saved salaries always have valid one
+fn currency_of(code: &str) -> Currency {
+    code.parse().expect("currency of salary is checked on save")
+}
+
+/// SQL condition on salary `s` limiting it to members of employee group - always true without group
+fn members_sql(members: Option<&BTreeSet<i32>>) -> String {
+    match members {
+        None => "1 = 1".to_string(),
+        Some(members) if members.is_empty() => "1 = 0".to_string(),
+        Some(members) => format!(
+            "s.employee_id IN ({})",
+            members.iter().map(i32::to_string).collect::<Vec<_>>().join(", ")
+        ),
+    }
+}
+
+/// Every query take the same parameters: $1 - from, $2 - to, $3 - gross, $4 - currency (or NULL)
+macro_rules! bind_params {
+    ($query:expr, $params:expr) => {
+        diesel::sql_query(sql($query))
+            .bind::<Date, _>($params.from)
+            .bind::<Date, _>($params.to)
+            .bind::<Bool, _>($params.gross)
+            .bind::<Nullable<Text>, _>($params.currency.map(|c| c.code()))
+    };
+}
+
+/// Group of report row - None when report is not grouped
+fn group_of(params: &SalaryReportParams, id: Option<i32>, name: Option<String>) -> Option<ReportGroup> {
+    params.group_by.map(|_| ReportGroup { id, name })
+}
+
+fn monthly_costs_in_db(
+    params: &SalaryReportParams,
+    members: Option<&BTreeSet<i32>>,
+    conn: &mut DbConnection,
+) -> QueryResult<Vec<MonthlyCost>> {
+    let (group_joins, group_id, group_name) = ReportGrouping::sql(params.group_by);
+    let query = format!(
+        "WITH RECURSIVE months(month_start) AS ( \
+             SELECT {first_month} \
+             UNION ALL \
+             SELECT {next_month} FROM months WHERE {next_month} <= $2 \
+         ) \
+         SELECT m.month_start AS month, {group_id} AS group_id, {group_name} AS group_name, \
+             s.currency AS currency, \
+             CAST(SUM({monthly}) AS BIGINT) AS total, COUNT(DISTINCT s.employee_id) AS headcount \
+         FROM months m \
+         JOIN salaries s ON s.from_date <= {month_end} AND (s.to_date IS NULL OR s.to_date >= m.month_start) \
+         {group_joins} \
+         WHERE s.gross = $3 AND ($4 IS NULL OR s.currency = $4) AND {not_deleted} AND {members} \
+         GROUP BY m.month_start, {group_id}, {group_name}, s.currency \
+         ORDER BY m.month_start, {group_name} IS NULL, {group_name}, s.currency",
+        group_joins = group_joins,
+        group_id = group_id,
+        group_name = group_name,
+        first_month = FIRST_MONTH,
+        next_month = NEXT_MONTH,
+        month_end = MONTH_END,
+        monthly = monthly_amount_sql(),
+        not_deleted = NOT_DELETED,
+        members = members_sql(members),
+    );
+    let rows: Vec<MonthlyCostRow> = bind_params!(&query, params).load(conn)?;
+    Ok(rows
+        .into_iter()
+        .map(|r| {
+            let c = currency_of(&r.currency);
+            MonthlyCost {
+                month: r.month,
+                group: group_of(params, r.group_id, r.group_name),
+                currency: c,
+                total: Money::new(r.total, c),
+                headcount: r.headcount,
+            }
+        })
+        .collect())
+}
+
+fn statistics_in_db(
+    params: &SalaryReportParams,
+    members: Option<&BTreeSet<i32>>,
+    conn: &mut DbConnection,
+) -> QueryResult<Vec<SalaryStatistics>> {
+    let percentile = |p: u32, name: &str| {
+        format!(
+            "MAX(CASE WHEN amount_rank = ({} * n + 99) / 100 THEN monthly_amount END) AS {}",
+            p, name
+        )
+    };
+    let (group_joins, group_id, group_name) = ReportGrouping::sql(params.group_by);
+    let query = format!(
+        "WITH latest AS ( \
+             SELECT {group_id} AS group_id, {group_name} AS group_name, s.currency AS currency, \
+                 {monthly} AS monthly_amount, \
+                 ROW_NUMBER() OVER (PARTITION BY s.employee_id ORDER BY s.from_date DESC) AS latest_rank \
+             FROM salaries s \
+             {group_joins} \
+             WHERE s.from_date <= $2 AND (s.to_date IS NULL OR s.to_date >= $1) \
+                 AND s.gross = $3 AND ($4 IS NULL OR s.currency = $4) AND {not_deleted} AND {members} \
+         ), ranked AS ( \
+             SELECT group_id, group_name, currency, monthly_amount, \
+                 ROW_NUMBER() OVER (PARTITION BY group_id, currency ORDER BY monthly_amount) AS amount_rank, \
+                 COUNT(*) OVER (PARTITION BY group_id, currency) AS n \
+             FROM latest WHERE latest_rank = 1 \
+         ) \
+         SELECT group_id, group_name, currency, COUNT(*) AS headcount, \
+             CAST(SUM(monthly_amount) AS BIGINT) AS total, \
+             MIN(monthly_amount) AS minimum, {p25}, {median}, {p75}, {p90}, MAX(monthly_amount) AS maximum \
+         FROM ranked \
+         GROUP BY group_id, group_name, currency \
+         ORDER BY group_name IS NULL, group_name, currency",
+        group_joins = group_joins,
+        group_id = group_id,
+        group_name = group_name,
+        monthly = monthly_amount_sql(),
+        not_deleted = NOT_DELETED,
+        members = members_sql(members),
+        p25 = percentile(25, "p25"),
+        median = percentile(50, "median"),
+        p75 = percentile(75, "p75"),
+        p90 = percentile(90, "p90"),
+    );
+    let rows: Vec<StatisticsRow> = bind_params!(&query, params).load(conn)?;
+    Ok(rows
+        .into_iter()
+        .map(|r| {
+            let c = currency_of(&r.currency);
+            let money = |minor_units: Option<i64>| Money::new(minor_units.unwrap_or(r.minimum), c);
+            SalaryStatistics {
+                group: group_of(params, r.group_id, r.group_name),
+                currency: c,
+                headcount: r.headcount,
+                total: Money::new(r.total, c),
+                // Amounts are not negative so it is rounding half up
+                average: Money::new((r.total + r.headcount / 2) / r.headcount, c),
+                minimum: Money::new(r.minimum, c),
+                p25: money(r.p25),
+                median: money(r.median),
+                p75: money(r.p75),
+                p90: money(r.p90),
+                maximum: Money::new(r.maximum, c),
+            }
+        })
+        .collect())
+}
+
+fn raises_in_db(
+    params: &SalaryReportParams,
+    members: Option<&BTreeSet<i32>>,
+    conn: &mut DbConnection,
+) -> QueryResult<Vec<PayRaise>> {
+    let query = format!(
+        "WITH history AS ( \
+             SELECT s.employee_id AS employee_id, s.from_date AS from_date, s.currency AS currency, \
+                 {monthly} AS monthly_amount, \
+                 LAG({monthly}) OVER (PARTITION BY s.employee_id ORDER BY s.from_date) AS previous_amount, \
+                 LAG(s.currency) OVER (PARTITION BY s.employee_id ORDER BY s.from_date) AS previous_currency \
+             FROM salaries s \
+             WHERE s.gross = $3 AND {not_deleted} AND {members} \
+         ) \
+         SELECT h.employee_id, e.first_name, e.last_name, h.from_date, h.currency, \
+             h.previous_amount, h.monthly_amount \
+         FROM history h \
+         JOIN employees e ON e.id = h.employee_id \
+         WHERE h.from_date >= $1 AND h.from_date <= $2 AND ($4 IS NULL OR h.currency = $4) \
+             AND h.previous_currency = h.currency AND h.previous_amount <> h.monthly_amount \
+         ORDER BY e.last_name, e.first_name, h.employee_id, h.from_date",
+        monthly = monthly_amount_sql(),
+        not_deleted = NOT_DELETED,
+        members = members_sql(members),
+    );
+    let rows: Vec<PayRaiseRow> = bind_params!(&query, params).load(conn)?;
+    Ok(rows
+        .into_iter()
+        .map(|r| {
+            let c = currency_of(&r.currency);
+            PayRaise {
+                employee_id: r.employee_id,
+                first_name: r.first_name,
+                last_name: r.last_name,
+                from_date: r.from_date,
+                previous: Money::new(r.previous_amount, c),
+                current: Money::new(r.monthly_amount, c),
+            }
+        })
+        .collect())
+}
+
+/// Gross (or net) salaries of not deleted group members active in report period and the salaries just before
+/// those started in it (for raises) - ordered by employee and date. Only these amounts are decrypted.
+fn reported_salaries(
+    params: &SalaryReportParams,
+    members: Option<&BTreeSet<i32>>,
+    keys: &KeyRing,
+    conn: &mut DbConnection,
+) -> QueryResult<Vec<ReportedSalary>> {
+    let (group_joins, group_id, group_name) = ReportGrouping::sql(params.group_by);
+    let query = format!(
+        "WITH history AS ( \
+             SELECT s.id, s.employee_id, s.contract_id, s.from_date, s.to_date, s.amount, s.amount_encrypted, \
+                 s.pay_period, s.currency, \
+                 LEAD(s.from_date) OVER (PARTITION BY s.employee_id ORDER BY s.from_date) AS next_from \
+             FROM salaries s \
+             WHERE s.gross = $3 AND {not_deleted} AND {members} \
+         ) \
+         SELECT s.id AS id, s.employee_id AS employee_id, s.from_date AS from_date, s.to_date AS to_date, \
+             s.amount AS amount, s.amount_encrypted AS amount_encrypted, s.pay_period AS pay_period, \
+             s.currency AS currency, {group_id} AS group_id, {group_name} AS group_name \
+         FROM history s \
+         {group_joins} \
+         WHERE (s.from_date <= $2 AND (s.to_date IS NULL OR s.to_date >= $1)) \
+             OR (s.next_from >= $1 AND s.next_from <= $2) \
+         ORDER BY s.employee_id, s.from_date, s.id",
+        group_joins = group_joins,
+        group_id = group_id,
+        group_name = group_name,
+        not_deleted = NOT_DELETED,
+        members = members_sql(members),
+    );
+    // Currency is checked here (not by DB) as salary before raise may be in other one
+    let rows: Vec<SalaryRow> = diesel::sql_query(sql(&query))
+        .bind::<Date, _>(params.from)
+        .bind::<Date, _>(params.to)
+        .bind::<Bool, _>(params.gross)
+        .load(conn)?;
+    rows.into_iter()
+        .map(|r| {
+            let amount = amount_of(r.id, r.amount, r.amount_encrypted.as_deref(), Some(keys))?;
+            Ok(ReportedSalary {
+                employee_id: r.employee_id,
+                from_date: r.from_date,
+                to_date: r.to_date,
+                monthly: monthly_amount(amount, r.pay_period.parse().expect("pay_period is checked by DB")),
+                currency: currency_of(&r.currency),
+                group_id: r.group_id,
+                group_name: r.group_name,
+            })
+        })
+        .collect()
+}
+
+fn monthly_costs(params: &SalaryReportParams, found: &[ReportedSalary]) -> Vec<MonthlyCost> {
+    let mut monthly = vec![];
+    let mut month = params.from.with_day(1).expect("every month has the first day");
+    while month <= params.to {
+        let month_end = month + Months::new(1) - Days::new(1);
+        // Total and employees by group and currency
+        let mut costs: BTreeMap<_, (&ReportedSalary, i64, BTreeSet<i32>)> = BTreeMap::new();
+        for s in found.iter().filter(|s| s.is_active(month, month_end) && s.in_currency(params)) {
+            let cost = costs.entry(s.order_key()).or_insert((s, 0, BTreeSet::new()));
+            cost.1 += s.monthly;
+            cost.2.insert(s.employee_id);
+        }
+        monthly.extend(costs.into_values().map(|(s, total, employees)| MonthlyCost {
+            month,
+            group: group_of(params, s.group_id, s.group_name.clone()),
+            currency: s.currency,
+            total: Money::new(total, s.currency),
+            headcount: employees.len() as i64,
+        }));
+        month = month + Months::new(1);
+    }
+    monthly
+}
+
+fn statistics(params: &SalaryReportParams, found: &[ReportedSalary]) -> Vec<SalaryStatistics> {
+    // The latest salary of every employee - salaries are ordered by employee and date
+    let mut latest: BTreeMap<i32, &ReportedSalary> = BTreeMap::new();
+    for s in found.iter().filter(|s| s.is_active(params.from, params.to) && s.in_currency(params)) {
+        latest.insert(s.employee_id, s);
+    }
+    let mut groups: BTreeMap<_, (&ReportedSalary, Vec<i64>)> = BTreeMap::new();
+    for s in latest.into_values() {
+        groups.entry(s.order_key()).or_insert((s, vec![])).1.push(s.monthly);
+    }
+    groups
+        .into_values()
+        .map(|(s, mut amounts)| {
+            amounts.sort_unstable();
+            let c = s.currency;
+            let n = amounts.len();
+            let total: i64 = amounts.iter().sum();
+            // Nearest-rank method
+            let percentile = |p: usize| Money::new(amounts[(p * n).div_ceil(100).max(1) - 1], c);
+            SalaryStatistics {
+                group: group_of(params, s.group_id, s.group_name.clone()),
+                currency: c,
+                headcount: n as i64,
+                total: Money::new(total, c),
+                // Amounts are not negative so it is rounding half up
+                average: Money::new((total + n as i64 / 2) / n as i64, c),
+                minimum: Money::new(amounts[0], c),
+                p25: percentile(25),
+                median: percentile(50),
+                p75: percentile(75),
+                p90: percentile(90),
+                maximum: Money::new(amounts[n - 1], c),
+            }
+        })
+        .collect()
+}
+
+fn raises(
+    params: &SalaryReportParams,
+    found: &[ReportedSalary],
+    conn: &mut DbConnection,
+) -> QueryResult<Vec<PayRaise>> {
+    use crate::schema::employees::dsl as e;
+
+    let changes: Vec<(&ReportedSalary, &ReportedSalary)> = found
+        .windows(2)
+        .map(|pair| (&pair[0], &pair[1]))
+        .filter(|(previous, current)| {
+            previous.employee_id == current.employee_id
+                && current.from_date >= params.from
+                && current.from_date <= params.to
+                && current.in_currency(params)
+                && previous.currency == current.currency
+                && previous.monthly != current.monthly
+        })
+        .collect();
+    let employee_ids: Vec<i32> = changes.iter().map(|(_, current)| current.employee_id).collect();
+    let names: BTreeMap<i32, (String, String)> = e::employees
+        .filter(e::id.eq_any(&employee_ids))
+        .select((e::id, e::first_name, e::last_name))
+        .load::<(i32, String, String)>(conn)?
+        .into_iter()
+        .map(|(e_id, first, last)| (e_id, (first, last)))
+        .collect();
+    let mut raises: Vec<PayRaise> = changes
+        .into_iter()
+        .map(|(previous, current)| {
+            let (first_name, last_name) = names[&current.employee_id].clone();
+            PayRaise {
+                employee_id: current.employee_id,
+                first_name,
+                last_name,
+                from_date: current.from_date,
+                previous: Money::new(previous.monthly, previous.currency),
+                current: Money::new(current.monthly, current.currency),
+            }
+        })
+        .collect();
+    raises.sort_by(|a, b| {
+        (&a.last_name, &a.first_name, a.employee_id, a.from_date).cmp(&(
+            &b.last_name,
+            &b.first_name,
+            b.employee_id,
+            b.from_date,
+        ))
+    });
+    Ok(raises)
+}
+
+/// Report of salaries in period from `params.from` to `params.to` (both inclusive) - amounts are monthly
+/// (see monthly_amount()) and never summed across currencies
+pub fn salary_report_with_connection(
+    params: &SalaryReportParams,
+    keys: Option<&KeyRing>,
+    conn: &mut DbConnection,
+) -> DaoResult<SalaryReport> {
+    let mut errors = Errors::default();
+    if params.to < params.from {
+        errors.add("", "to", format!("can't be before from {}", params.from));
+    }
+    let members = match params.employee_group {
+        Some(g_id) => match EmployeeGroupDTO::member_ids_with_connection(g_id, EmployeeScope::All, keys, conn) {
+            Ok(members) => Some(members.into_iter().collect::<BTreeSet<i32>>()),
+            Err(e) if e.is_not_found() => {
+                errors.add("", "employee_group", format!("there is no group with id = {}", g_id));
+                None
+            }
+            Err(e) => return Err(e),
+        },
+        None => None,
+    };
+    errors.into_result()?;
+    let Some(keys) = keys else {
+        // Amounts are in plain text so everything is computed by DB
+        if let Some((s_id, _)) = salaries::table
+            .filter(salaries::amount_encrypted.is_not_null())
+            .select((salaries::id, salaries::amount_encrypted))
+            .first::<(i32, Option<String>)>(conn)
+            .optional()?
+        {
+            let reason = "ENCRYPTION_KEY is not configured".to_string();
+            return Err(crypto::unreadable(s_id, "Amount of salary", reason).into());
+        }
+        return Ok(SalaryReport {
+            params: params.clone(),
+            monthly: monthly_costs_in_db(params, members.as_ref(), conn)?,
+            statistics: statistics_in_db(params, members.as_ref(), conn)?,
+            raises: raises_in_db(params, members.as_ref(), conn)?,
+        });
+    };
+    // Encrypted amounts can't be aggregated by DB - salaries of report period are decrypted and aggregated here
+    let found = reported_salaries(params, members.as_ref(), keys, conn)?;
+    Ok(SalaryReport {
+        params: params.clone(),
+        monthly: monthly_costs(params, &found),
+        statistics: statistics(params, &found),
+        raises: raises(params, &found, conn)?,
+    })
+}
+
+pub fn salary_report(db: &Database, params: &SalaryReportParams) -> DaoResult<SalaryReport> {
+    let mut conn = db.try_get_connection()?;
+    salary_report_with_connection(params, db.keys().as_deref(), &mut conn)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::base_dao::Crud;
+    use crate::common_for_tests::*;
+    use crate::error::DaoError;
+    use crate::money::PayPeriod;
+    use crate::tags_dao::{change_tags_with_connection, TagsChange};
+    use crate::{
+        ContractDTO, ContractType, DepartmentDTO, EmployeeDTO, EmployeeSearch, PositionDTO, SalaryDTO, WorkingTime,
+    };
+
+    use super::*;
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    fn salary(from: NaiveDate, to: Option<NaiveDate>, amount: Money, pay_period: PayPeriod) -> SalaryDTO {
+        SalaryDTO {
+            id: None,
+            employee_id: None,
+            from_date: from,
+            to_date: to,
+            amount,
+            pay_period,
+            gross: true,
+            search_string: "".to_string(),
+            version: None,
+            contract_id: None,
+        }
+    }
+
+    fn save_employee(
+        name: &str,
+        salaries: Vec<SalaryDTO>,
+        keys: Option<&KeyRing>,
+        conn: &mut DbConnection,
+    ) -> EmployeeDTO {
+        EmployeeDTO {
+            id: None,
+            first_name: "Jan".to_string(),
+            last_name: name.to_string(),
+            search_string: "".to_string(),
+            salaries,
+            contacts: vec![],
+            version: None,
+            department_id: None,
+            manager_id: None,
+            contracts: vec![],
+            ..Default::default()
+        }
+        .save_in_transaction(keys, conn)
+        .unwrap()
+    }
+
+    #[test]
+    fn salary_report_is_computed_per_currency() {
+        salary_report_is_computed_per_currency_in(keys(), &mut initialize());
+    }
+
+    #[test]
+    fn salary_report_is_computed_by_db_without_encryption_key() {
+        salary_report_is_computed_per_currency_in(None, &mut initialize());
+    }
+
+    fn salary_report_is_computed_per_currency_in(keys: Option<&KeyRing>, conn: &mut DbConnection) {
+        let pln = |units: i64| Money::new(units * 100, Currency::PLN);
+        let kowalski = save_employee(
+            "Kowalski",
+            vec![
+                salary(date(2020, 1, 1), Some(date(2020, 1, 31)), pln(1000), PayPeriod::Monthly),
+                salary(date(2020, 2, 1), None, pln(1200), PayPeriod::Monthly),
+            ],
+            keys,
+            conn,
+        );
+        save_employee(
+            "Nowak",
+            vec![salary(date(2020, 1, 15), None, pln(24000), PayPeriod::Yearly)],
+            keys,
+            conn,
+        );
+        save_employee(
+            "Wisniewski",
+            vec![salary(date(2019, 1, 1), None, pln(10), PayPeriod::Hourly)],
+            keys,
+            conn,
+        );
+        save_employee(
+            "Smith",
+            vec![salary(date(2020, 2, 10), None, Money::new(300000, Currency::EUR), PayPeriod::Monthly)],
+            keys,
+            conn,
+        );
+        let mut net = salary(date(2020, 1, 1), None, pln(5000), PayPeriod::Monthly);
+        net.gross = false;
+        save_employee("Net", vec![net], keys, conn);
+
+        let params = SalaryReportParams {
+            from: date(2020, 1, 10),
+            to: date(2020, 2, 29),
+            currency: None,
+            gross: true,
+            group_by: None,
+            employee_group: None,
+        };
+        let report = salary_report_with_connection(&params, keys, conn).unwrap();
+
+        let monthly: Vec<(NaiveDate, &str, String, i64)> = report
+            .monthly
+            .iter()
+            .map(|m| (m.month, m.currency.code(), m.total.amount(), m.headcount))
+            .collect();
+        assert_eq!(
+            monthly,
+            vec![
+                (date(2020, 1, 1), "PLN", "4680.00".to_string(), 3),
+                (date(2020, 2, 1), "EUR", "3000.00".to_string(), 1),
+                (date(2020, 2, 1), "PLN", "4880.00".to_string(), 3),
+            ]
+        );
+
+        assert_eq!(report.statistics.len(), 2);
+        let pln_stats = &report.statistics[1];
+        assert_eq!(pln_stats.currency, Currency::PLN);
+        assert_eq!(pln_stats.headcount, 3);
+        assert_eq!(pln_stats.total, pln(4880));
+        assert_eq!(pln_stats.average, Money::new(162667, Currency::PLN));
+        assert_eq!(pln_stats.minimum, pln(1200));
+        assert_eq!(pln_stats.median, pln(1680));
+        assert_eq!(pln_stats.p90, pln(2000));
+        assert_eq!(pln_stats.maximum, pln(2000));
+
+        assert_eq!(report.raises.len(), 1);
+        assert_eq!(report.raises[0].employee_id, kowalski.id.unwrap());
+        assert_eq!(report.raises[0].from_date, date(2020, 2, 1));
+        assert_eq!(report.raises[0].previous, pln(1000));
+        assert_eq!(report.raises[0].current, pln(1200));
+
+        let eur_only = SalaryReportParams {
+            currency: Some(Currency::EUR),
+            ..params.clone()
+        };
+        let report = salary_report_with_connection(&eur_only, keys, conn).unwrap();
+        assert_eq!(report.monthly.len(), 1);
+        assert_eq!(report.statistics[0].median, Money::new(300000, Currency::EUR));
+        assert!(report.raises.is_empty());
+
+        let net_only = SalaryReportParams {
+            gross: false,
+            ..params.clone()
+        };
+        let report = salary_report_with_connection(&net_only, keys, conn).unwrap();
+        assert_eq!(report.statistics[0].total, pln(5000));
+
+        let change = TagsChange {
+            employee_ids: vec![kowalski.id.unwrap()],
+            add: vec!["remote".to_string()],
+            remove: vec![],
+        };
+        change_tags_with_connection(&change, conn).unwrap();
+        let remote = EmployeeGroupDTO {
+            id: None,
+            name: "Remote".to_string(),
+            filter: EmployeeSearch {
+                tag: vec!["remote".to_string()],
+                ..Default::default()
+            },
+            search_string: "".to_string(),
+            version: None,
+        }
+        .save_in_transaction(keys, conn)
+        .unwrap();
+        let remote_only = SalaryReportParams {
+            employee_group: remote.id,
+            ..params.clone()
+        };
+        let report = salary_report_with_connection(&remote_only, keys, conn).unwrap();
+        assert_eq!(report.statistics.len(), 1);
+        assert_eq!(report.statistics[0].headcount, 1);
+        assert_eq!(report.statistics[0].total, pln(1200));
+        let unknown_group = SalaryReportParams {
+            employee_group: remote.id.map(|g_id| g_id + 1),
+            ..params.clone()
+        };
+        assert!(salary_report_with_connection(&unknown_group, keys, conn).unwrap_err().is_validation());
+
+        let backwards = SalaryReportParams {
+            from: params.to,
+            to: params.from,
+            ..params
+        };
+        match salary_report_with_connection(&backwards, keys, conn) {
+            Err(DaoError::Validation(errors)) => assert_eq!(errors[0].field, "to"),
+            result => panic!("Should report validation error and instead I got {:?}", result),
+        }
+    }
+
+    #[test]
+    fn salary_report_is_grouped_by_department_or_position() {
+        let conn = &mut initialize();
+        let pln = |units: i64| Money::new(units * 100, Currency::PLN);
+        let it = DepartmentDTO {
+            id: None,
+            name: "IT".to_string(),
+            parent_id: None,
+            search_string: "".to_string(),
+            version: None,
+        }
+        .save_in_transaction(keys(), conn)
+        .unwrap();
+        let developer = PositionDTO {
+            id: None,
+            name: "Developer".to_string(),
+            search_string: "".to_string(),
+            version: None,
+        }
+        .save_in_transaction(keys(), conn)
+        .unwrap();
+        EmployeeDTO {
+            department_id: it.id,
+            contracts: vec![ContractDTO {
+                id: None,
+                employee_id: None,
+                position_id: developer.id,
+                contract_type: ContractType::Permanent,
+                from_date: date(2020, 1, 1),
+                to_date: None,
+                working_time: WorkingTime::FULL,
+                search_string: "".to_string(),
+                version: None,
+            }],
+            ..save_employee(
+                "Kowalski",
+                vec![salary(date(2020, 1, 1), None, pln(1000), PayPeriod::Monthly)],
+                keys(),
+                conn,
+            )
+        }
+        .save_in_transaction(keys(), conn)
+        .unwrap();
+        save_employee(
+            "Nowak",
+            vec![salary(date(2020, 1, 1), None, pln(2000), PayPeriod::Monthly)],
+            keys(),
+            conn,
+        );
+
+        for (grouping, group_name) in [(ReportGrouping::Department, "IT"), (ReportGrouping::Position, "Developer")] {
+            let params = SalaryReportParams {
+                from: date(2020, 1, 1),
+                to: date(2020, 1, 31),
+                currency: None,
+                gross: true,
+                group_by: Some(grouping),
+                employee_group: None,
+            };
+            let report = salary_report_with_connection(&params, keys(), conn).unwrap();
+            let monthly: Vec<(Option<String>, Money, i64)> = report
+                .monthly
+                .iter()
+                .map(|m| (m.group.clone().unwrap().name, m.total, m.headcount))
+                .collect();
+            assert_eq!(
+                monthly,
+                vec![(Some(group_name.to_string()), pln(1000), 1), (None, pln(2000), 1)]
+            );
+            let statistics: Vec<(Option<String>, Money)> = report
+                .statistics
+                .iter()
+                .map(|s| (s.group.clone().unwrap().name, s.median))
+                .collect();
+            assert_eq!(statistics, vec![(Some(group_name.to_string()), pln(1000)), (None, pln(2000))]);
+        }
+    }
+
+    #[test]
+    fn only_salaries_of_report_period_are_decrypted() {
+        let conn = &mut initialize();
+        let pln = |units: i64| Money::new(units * 100, Currency::PLN);
+        let kowalski = save_employee(
+            "Kowalski",
+            vec![
+                salary(date(2018, 1, 1), Some(date(2018, 12, 31)), pln(800), PayPeriod::Monthly),
+                salary(date(2019, 1, 1), Some(date(2019, 12, 31)), pln(1000), PayPeriod::Monthly),
+                salary(date(2020, 2, 1), None, pln(1200), PayPeriod::Monthly),
+            ],
+            keys(),
+            conn,
+        );
+        diesel::update(salaries::table.filter(salaries::from_date.eq(date(2018, 1, 1))))
+            .set(salaries::amount_encrypted.eq("garbage"))
+            .execute(conn)
+            .unwrap();
+        let params = SalaryReportParams {
+            from: date(2020, 1, 1),
+            to: date(2020, 12, 31),
+            currency: None,
+            gross: true,
+            group_by: None,
+            employee_group: None,
+        };
+
+        let report = salary_report_with_connection(&params, keys(), conn).unwrap();
+        assert_eq!(report.statistics[0].total, pln(1200));
+        assert_eq!(report.raises.len(), 1, "Salary before the period is read for raise");
+        assert_eq!(report.raises[0].employee_id, kowalski.id.unwrap());
+        assert_eq!(report.raises[0].previous, pln(1000));
+        let in_2018 = SalaryReportParams {
+            from: date(2018, 1, 1),
+            to: date(2018, 12, 31),
+            ..params.clone()
+        };
+        assert!(salary_report_with_connection(&in_2018, keys(), conn).is_err());
+
+        assert!(
+            salary_report_with_connection(&params, None, conn).is_err(),
+            "Encrypted amounts can't be reported without key"
+        );
+    }
+
+    /// The same salary history saved encrypted (with keys) or in plain text (without them) - salaries start and end
+    /// in the middle of months, some employees have department and position, one is deleted
+    fn save_salary_history(keys: Option<&KeyRing>, conn: &mut DbConnection) {
+        let pln = |minor_units: i64| Money::new(minor_units, Currency::PLN);
+        let it = DepartmentDTO {
+            id: None,
+            name: "IT".to_string(),
+            parent_id: None,
+            search_string: "".to_string(),
+            version: None,
+        }
+        .save_in_transaction(keys, conn)
+        .unwrap();
+        let developer = PositionDTO {
+            id: None,
+            name: "Developer".to_string(),
+            search_string: "".to_string(),
+            version: None,
+        }
+        .save_in_transaction(keys, conn)
+        .unwrap();
+        let contract = ContractDTO {
+            id: None,
+            employee_id: None,
+            position_id: developer.id,
+            contract_type: ContractType::Permanent,
+            from_date: date(2019, 6, 1),
+            to_date: None,
+            working_time: WorkingTime::FULL,
+            search_string: "".to_string(),
+            version: None,
+        };
+        EmployeeDTO {
+            department_id: it.id,
+            contracts: vec![contract],
+            ..save_employee(
+                "Kowalski",
+                vec![
+                    salary(date(2019, 6, 1), Some(date(2020, 2, 14)), pln(100000), PayPeriod::Monthly),
+                    salary(date(2020, 2, 15), Some(date(2020, 7, 20)), pln(125050), PayPeriod::Monthly),
+                    salary(date(2020, 7, 21), None, pln(1600007), PayPeriod::Yearly),
+                ],
+                keys,
+                conn,
+            )
+        }
+        .save_in_transaction(keys, conn)
+        .unwrap();
+        EmployeeDTO {
+            department_id: it.id,
+            ..save_employee(
+                "Nowak",
+                vec![
+                    salary(date(2020, 1, 20), Some(date(2020, 3, 9)), pln(4550), PayPeriod::Hourly),
+                    salary(date(2020, 3, 10), None, pln(4550), PayPeriod::Hourly),
+                ],
+                keys,
+                conn,
+            )
+        }
+        .save_in_transaction(keys, conn)
+        .unwrap();
+        save_employee(
+            "Wisniewski",
+            vec![
+                salary(date(2019, 1, 1), Some(date(2020, 4, 30)), pln(200000), PayPeriod::Monthly),
+                salary(date(2020, 5, 1), None, Money::new(60000, Currency::EUR), PayPeriod::Monthly),
+            ],
+            keys,
+            conn,
+        );
+        save_employee(
+            "Smith",
+            vec![salary(
+                date(2020, 2, 3),
+                Some(date(2020, 2, 25)),
+                Money::new(300000, Currency::EUR),
+                PayPeriod::Monthly,
+            )],
+            keys,
+            conn,
+        );
+        save_employee(
+            "Zielinski",
+            vec![salary(date(2020, 1, 1), None, pln(150000), PayPeriod::Monthly)],
+            keys,
+            conn,
+        );
+        let mut net = salary(date(2020, 1, 11), None, pln(500000), PayPeriod::Monthly);
+        net.gross = false;
+        let mut raised = salary(date(2020, 6, 16), None, pln(550000), PayPeriod::Monthly);
+        raised.gross = false;
+        net.to_date = Some(date(2020, 6, 15));
+        save_employee("Net", vec![net, raised], keys, conn);
+        let deleted = save_employee(
+            "Deleted",
+            vec![salary(date(2020, 1, 1), None, pln(900000), PayPeriod::Monthly)],
+            keys,
+            conn,
+        );
+        EmployeeDTO::delete_by_with_connection(deleted.id.unwrap(), deleted.version.unwrap(), 1, keys, conn).unwrap();
+    }
+
+    #[test]
+    fn salary_report_computed_by_db_is_the_same_as_computed_from_decrypted_salaries() {
+        let encrypted = &mut initialize();
+        save_salary_history(keys(), encrypted);
+        let plain = &mut initialize_db();
+        save_salary_history(None, plain);
+
+        let periods = [
+            (date(2020, 1, 15), date(2020, 3, 10)),
+            (date(2020, 2, 10), date(2020, 2, 20)),
+            (date(2019, 12, 1), date(2020, 12, 31)),
+        ];
+        let groupings = [None, Some(ReportGrouping::Department), Some(ReportGrouping::Position)];
+        let salaries = [(None, true), (Some(Currency::PLN), true), (Some(Currency::EUR), true), (None, false)];
+        for (from, to) in periods {
+            for group_by in groupings {
+                for (currency, gross) in salaries {
+                    let params = SalaryReportParams {
+                        from,
+                        to,
+                        currency,
+                        gross,
+                        group_by,
+                        employee_group: None,
+                    };
+                    let decrypted = salary_report_with_connection(&params, keys(), encrypted).unwrap();
+                    let by_db = salary_report_with_connection(&params, None, plain).unwrap();
+                    assert_eq!(decrypted, by_db, "Reports of {:?} should be the same", params);
+                }
+            }
+        }
+
+        // Salaries of partial months are counted in full - monthly amounts are not pro-rated
+        let params = SalaryReportParams {
+            from: date(2020, 2, 10),
+            to: date(2020, 2, 20),
+            currency: Some(Currency::PLN),
+            gross: true,
+            group_by: None,
+            employee_group: None,
+        };
+        let report = salary_report_with_connection(&params, keys(), encrypted).unwrap();
+        assert_eq!(report.monthly.len(), 1);
+        assert_eq!(report.monthly[0].headcount, 4);
+        assert_eq!(
+            report.monthly[0].total,
+            Money::new(100000 + 125050 + 4550 * HOURS_PER_MONTH + 200000 + 150000, Currency::PLN)
+        );
+        assert_eq!(report.statistics[0].headcount, 4);
+        assert_eq!(report.raises.len(), 1);
+        assert_eq!(report.raises[0].from_date, date(2020, 2, 15));
+        let year = SalaryReportParams {
+            from: date(2020, 1, 1),
+            to: date(2020, 12, 31),
+            ..params
+        };
+        let raises: Vec<(String, NaiveDate)> = salary_report_with_connection(&year, None, plain)
+            .unwrap()
+            .raises
+            .into_iter()
+            .map(|r| (r.last_name, r.from_date))
+            .collect();
+        assert_eq!(
+            raises,
+            vec![("Kowalski".to_string(), date(2020, 2, 15)), ("Kowalski".to_string(), date(2020, 7, 21))]
+        );
+    }
+}
@@ -0,0 +1,289 @@
+use std::error::Error;
+use std::future::Future;
+use std::io;
+use std::sync::Arc;
+use std::time::Duration;
+
+use aws_sdk_s3::config::timeout::TimeoutConfig;
+use aws_sdk_s3::config::{
+    BehaviorVersion, Credentials, Region, RequestChecksumCalculation, ResponseChecksumValidation,
+};
+use aws_sdk_s3::error::{DisplayErrorContext, ProvideErrorMetadata, SdkError};
+use aws_sdk_s3::primitives::ByteStream;
+use aws_sdk_s3::Client;
+use tokio::runtime::Runtime;
+
+use crate::storage::{DocumentStorage, S3Config, StorageError};
+
+/// How long wait for store before request fails
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Current-thread runtime requests are blocked on (by thread of caller) - it is shut down in background when dropped,
+/// as blocking shutdown panics when the last storage is dropped inside other runtime (e.g. of actix)
+struct BlockingRuntime(Option<Runtime>);
+
+impl BlockingRuntime {
+    fn block_on<F: Future>(&self, future: F) -> F::Output {
+        self.0.as_ref().expect("Runtime is shut down just when dropped").block_on(future)
+    }
+}
+
+impl Drop for BlockingRuntime {
+    fn drop(&mut self) {
+        if let Some(runtime) = self.0.take() {
+            runtime.shutdown_background();
+        }
+    }
+}
+
+/// Client of S3 compatible store (plain HTTP or HTTPS) - DocumentStorage is blocking, so requests of AWS SDK are
+/// run on its own runtime
+pub struct S3Storage {
+    client: Client,
+    bucket: String,
+    /// The biggest object which is read - nothing bigger is stored, so bigger answer of store is reported as error
+    max_size: u64,
+    runtime: Arc<BlockingRuntime>,
+}
+
+impl S3Storage {
+    pub fn new(config: &S3Config, max_size: u64) -> io::Result<S3Storage> {
+        let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build()?;
+        let sdk_config = aws_sdk_s3::Config::builder()
+            .behavior_version(BehaviorVersion::latest())
+            .endpoint_url(config.endpoint.trim_end_matches('/'))
+            .region(Region::new(config.region.clone()))
+            .credentials_provider(Credentials::new(&config.access_key, &config.secret_key, None, None, "S3_ACCESS_KEY"))
+            .force_path_style(true)
+            // Checksums are sent and checked just when the operation requires them - not every S3 compatible store
+            // supports the newer ones (content is checked by its SHA-256 anyway)
+            .request_checksum_calculation(RequestChecksumCalculation::WhenRequired)
+            .response_checksum_validation(ResponseChecksumValidation::WhenRequired)
+            .timeout_config(TimeoutConfig::builder().operation_timeout(TIMEOUT).build())
+            .build();
+        Ok(S3Storage {
+            client: Client::from_conf(sdk_config),
+            bucket: config.bucket.clone(),
+            max_size,
+            runtime: Arc::new(BlockingRuntime(Some(runtime))),
+        })
+    }
+
+    /// Body of object read till max_size
+    async fn read(&self, key: &str) -> Result<Vec<u8>, StorageError> {
+        let object = self
+            .client
+            .get_object()
+            .bucket(&self.bucket)
+            .key(key)
+            .send()
+            .await
+            .map_err(|e| failed(key, e))?;
+        if object.content_length().is_some_and(|length| length as u64 > self.max_size) {
+            return Err(self.too_big(key));
+        }
+        let mut body: ByteStream = object.body;
+        let mut content = Vec::new();
+        while let Some(chunk) = body.try_next().await.map_err(io::Error::from)? {
+            if (content.len() + chunk.len()) as u64 > self.max_size {
+                return Err(self.too_big(key));
+            }
+            content.extend_from_slice(&chunk);
+        }
+        Ok(content)
+    }
+
+    fn too_big(&self, key: &str) -> StorageError {
+        StorageError::TooBig {
+            key: key.to_string(),
+            max_size: self.max_size,
+        }
+    }
+}
+
+impl DocumentStorage for S3Storage {
+    fn put(&self, key: &str, content: &[u8]) -> Result<(), StorageError> {
+        let request = self
+            .client
+            .put_object()
+            .bucket(&self.bucket)
+            .key(key)
+            .body(ByteStream::from(content.to_vec()))
+            .send();
+        self.runtime.block_on(request).map_err(|e| failed(key, e))?;
+        Ok(())
+    }
+
+    fn get(&self, key: &str) -> Result<Vec<u8>, StorageError> {
+        self.runtime.block_on(self.read(key))
+    }
+
+    fn delete(&self, key: &str) -> Result<(), StorageError> {
+        let request = self.client.delete_object().bucket(&self.bucket).key(key).send();
+        match self.runtime.block_on(request).map_err(|e| failed(key, e)) {
+            Err(StorageError::NotFound(_)) => Ok(()),
+            result => result.map(|_| ()),
+        }
+    }
+}
+
+/// Store answered by error (404 is StorageError::NotFound) or it was not reached at all (StorageError::Io)
+fn failed<E>(key: &str, e: SdkError<E>) -> StorageError
+where
+    E: ProvideErrorMetadata + Error + Send + Sync + 'static,
+{
+    match &e {
+        SdkError::ServiceError(context) => match context.raw().status().as_u16() {
+            404 => StorageError::NotFound(key.to_string()),
+            status => {
+                let error = context.err();
+                let message = match (error.code(), error.message()) {
+                    (Some(code), Some(message)) => format!("{}: {}", code, message),
+                    (Some(code), None) => code.to_string(),
+                    (None, _) => DisplayErrorContext(error).to_string(),
+                };
+                StorageError::Remote { status, message }
+            }
+        },
+        _ => StorageError::Io(io::Error::other(DisplayErrorContext(&e).to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+    use std::sync::{Arc, Mutex};
+    use std::thread;
+
+    use sha2::{Digest, Sha256};
+
+    use super::*;
+
+    /// Objects of stub by their path
+    type Objects = Arc<Mutex<HashMap<String, Vec<u8>>>>;
+
+    /// In-memory stand-in of S3 compatible store - it answers PUT, GET and DELETE of signed requests
+    fn start_stub() -> (String, Objects) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let endpoint = format!("http://{}", listener.local_addr().unwrap());
+        let objects = Arc::new(Mutex::new(HashMap::new()));
+        let stored = objects.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut request_line = String::new();
+                reader.read_line(&mut request_line).unwrap();
+                let mut headers = HashMap::new();
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    match line.trim_end().split_once(": ") {
+                        Some((name, value)) => headers.insert(name.to_lowercase(), value.to_string()),
+                        None => break,
+                    };
+                }
+                let length = headers.get("content-length").map_or(0, |l| l.parse().unwrap());
+                let mut body = vec![0; length];
+                reader.read_exact(&mut body).unwrap();
+                let parts: Vec<&str> = request_line.split(' ').collect();
+                let path = parts[1].split('?').next().unwrap();
+                let signed = headers["authorization"].starts_with("AWS4-HMAC-SHA256 Credential=minio/")
+                    && headers["x-amz-content-sha256"] == format!("{:x}", Sha256::digest(&body));
+                let error = |code: &str| format!("<Error><Code>{}</Code></Error>", code).into_bytes();
+                let mut objects = stored.lock().unwrap();
+                let (status, answer) = match (parts[0], signed) {
+                    (_, false) => ("403 Forbidden", error("SignatureDoesNotMatch")),
+                    ("PUT", _) => {
+                        objects.insert(path.to_string(), body);
+                        ("200 OK", vec![])
+                    }
+                    ("GET", _) => match objects.get(path) {
+                        Some(object) => ("200 OK", object.clone()),
+                        None => ("404 Not Found", error("NoSuchKey")),
+                    },
+                    ("DELETE", _) => {
+                        objects.remove(path);
+                        ("204 No Content", vec![])
+                    }
+                    _ => ("405 Method Not Allowed", vec![]),
+                };
+                let head = format!(
+                    "HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                    status,
+                    answer.len()
+                );
+                stream.write_all(head.as_bytes()).unwrap();
+                stream.write_all(&answer).unwrap();
+            }
+        });
+        (endpoint, objects)
+    }
+
+    fn config(endpoint: &str, access_key: &str) -> S3Config {
+        S3Config {
+            endpoint: endpoint.to_string(),
+            bucket: "documents".to_string(),
+            region: "us-east-1".to_string(),
+            access_key: access_key.to_string(),
+            secret_key: "minio-secret".to_string(),
+        }
+    }
+
+    #[test]
+    fn documents_are_stored_in_s3_compatible_store() {
+        let (endpoint, objects) = start_stub();
+        let storage = S3Storage::new(&config(&endpoint, "minio"), 1024).unwrap();
+        storage.put("1/contract with space", b"%PDF-1.4").unwrap();
+        assert!(objects.lock().unwrap().contains_key("/documents/1/contract%20with%20space"));
+        assert_eq!(storage.get("1/contract with space").unwrap(), b"%PDF-1.4");
+
+        storage.delete("1/contract with space").unwrap();
+        assert!(matches!(storage.get("1/contract with space"), Err(StorageError::NotFound(_))));
+        let unknown = S3Storage::new(&config(&endpoint, "intruder"), 1024).unwrap();
+        match unknown.put("1/contract", b"%PDF-1.4") {
+            Err(StorageError::Remote { status, message }) => {
+                assert_eq!(status, 403);
+                assert_eq!(message, "SignatureDoesNotMatch");
+            }
+            result => panic!("Should be rejected by store and instead I got {:?}", result),
+        }
+    }
+
+    #[test]
+    fn storage_is_used_by_many_threads_and_dropped_inside_runtime() {
+        let (endpoint, _) = start_stub();
+        let storage = Arc::new(S3Storage::new(&config(&endpoint, "minio"), 1024).unwrap());
+        let threads: Vec<_> = (0..4)
+            .map(|i| {
+                let storage = storage.clone();
+                thread::spawn(move || {
+                    let key = format!("{}/contract", i);
+                    storage.put(&key, b"%PDF-1.4").unwrap();
+                    storage.get(&key).unwrap()
+                })
+            })
+            .collect();
+        for thread in threads {
+            assert_eq!(thread.join().unwrap(), b"%PDF-1.4");
+        }
+        let other = tokio::runtime::Builder::new_current_thread().build().unwrap();
+        other.block_on(async move { drop(storage) });
+    }
+
+    #[test]
+    fn object_bigger_than_max_size_is_not_read() {
+        let (endpoint, objects) = start_stub();
+        let storage = S3Storage::new(&config(&endpoint, "minio"), 4).unwrap();
+        objects.lock().unwrap().insert("/documents/1/contract".to_string(), b"%PDF-1.4".to_vec());
+        match storage.get("1/contract") {
+            Err(StorageError::TooBig { key, max_size }) => {
+                assert_eq!(key, "1/contract");
+                assert_eq!(max_size, 4);
+            }
+            result => panic!("Should be too big and instead I got {:?}", result),
+        }
+    }
+}
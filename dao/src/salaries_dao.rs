@@ -1,11 +1,19 @@
 use chrono::NaiveDate;
 use diesel::dsl::*;
 use diesel::prelude::*;
-use diesel::sqlite::SqliteConnection;
 
-use crate::base_dao::SearchableByParent;
-use crate::base_dao::{Crud, HaveId};
+use crate::base_dao::{SearchableByDate, SearchableByParent};
+use crate::base_dao::{stale_version, Crud, HaveId, HaveVersion};
+use crate::connection::DbConnection;
+use crate::contracts_dao::{check_salary_contract, contract_of, ContractDTO};
+use crate::crypto::{self, KeyRing};
+use crate::employees_dao::not_deleted;
+use crate::error::DaoResult;
+use crate::validation::{
+    check_amount, check_no_overlap_with_existing, check_period, Errors, HavePeriod, ValidationRules,
+};
 use crate::models::{NewSalary, Salary};
+use crate::money::{Currency, Money, PayPeriod};
 use crate::schema::salaries::dsl::id as salary_id;
 use crate::schema::salaries::dsl::*;
 use crate::Searchable;
@@ -15,60 +23,120 @@ pub struct SalaryDTO {
     pub id: Option<i32>,
     pub employee_id: Option<i32>,
     pub from_date: NaiveDate,
-    pub to_date: NaiveDate,
-    pub amount: i64,
+    pub to_date: Option<NaiveDate>,
+    /// `"amount": "1234.56", "currency": "PLN"` in JSON
+    #[serde(flatten)]
+    pub amount: Money,
+    pub pay_period: PayPeriod,
+    pub gross: bool,
     pub search_string: String,
+    pub version: Option<i32>,
+    /// Employment contract the salary is paid under - salary period have to be within the contract
+    #[serde(default)]
+    pub contract_id: Option<i32>,
 }
 
-impl From<Salary> for SalaryDTO {
-    fn from(s: Salary) -> Self {
-        SalaryDTO {
+impl SalaryDTO {
+    /// Salary of row - its amount is decrypted by `keys`
+    pub(crate) fn from_row(s: &Salary, keys: Option<&KeyRing>) -> QueryResult<SalaryDTO> {
+        let salary_currency: Currency = s.currency.parse().unwrap_or_else(|e| {
+            panic!(
+                "Salary id = {} has {} - rows from before currencies are converted by Database::initialize()",
+                s.id, e
+            )
+        });
+        Ok(SalaryDTO {
             id: Some(s.id),
             employee_id: Some(s.employee_id),
             from_date: s.from_date,
             to_date: s.to_date,
-            amount: s.amount,
-            search_string: s.search_string,
-        }
+            amount: Money::new(amount_of(s.id, s.amount, s.amount_encrypted.as_deref(), keys)?, salary_currency),
+            pay_period: s.pay_period.parse().expect("pay_period is checked by DB"),
+            gross: s.gross,
+            search_string: s.search_string.clone(),
+            version: Some(s.version),
+            contract_id: s.contract_id,
+        })
     }
-}
 
-impl From<&Salary> for SalaryDTO {
-    fn from(s: &Salary) -> Self {
-        SalaryDTO {
-            id: Some(s.id),
-            employee_id: Some(s.employee_id),
-            from_date: s.from_date,
-            to_date: s.to_date,
-            amount: s.amount,
-            search_string: s.search_string.clone(),
+    /// Row of salary - its amount is encrypted when there are `keys`
+    fn to_row(&self, keys: Option<&KeyRing>) -> Salary {
+        let (plain, encrypted) = crypto::seal_amount(self.amount.minor_units, keys);
+        Salary {
+            id: self.id.unwrap(),
+            employee_id: self.employee_id.unwrap(),
+            from_date: self.from_date,
+            to_date: self.to_date,
+            amount: plain,
+            search_string: self.search_string.clone(),
+            version: self.version.unwrap_or_default(),
+            currency: self.amount.currency.code().to_string(),
+            pay_period: self.pay_period.as_str().to_string(),
+            gross: self.gross,
+            contract_id: self.contract_id,
+            amount_encrypted: encrypted,
         }
     }
-}
 
-impl From<&SalaryDTO> for Salary {
-    fn from(salary_dto: &SalaryDTO) -> Self {
-        Salary {
-            id: salary_dto.id.unwrap(),
-            employee_id: salary_dto.employee_id.unwrap(),
-            from_date: salary_dto.from_date,
-            to_date: salary_dto.to_date,
-            amount: salary_dto.amount,
-            search_string: salary_dto.search_string.clone(),
+    fn to_new_row(&self, keys: Option<&KeyRing>) -> NewSalary {
+        let (plain, encrypted) = crypto::seal_amount(self.amount.minor_units, keys);
+        NewSalary {
+            employee_id: self.employee_id.unwrap(),
+            from_date: self.from_date,
+            to_date: self.to_date,
+            amount: plain,
+            search_string: self.search_string.clone(),
+            currency: self.amount.currency.code().to_string(),
+            pay_period: self.pay_period.as_str().to_string(),
+            gross: self.gross,
+            contract_id: self.contract_id,
+            amount_encrypted: encrypted,
         }
     }
 }
 
-impl From<&SalaryDTO> for NewSalary {
-    fn from(salary_dto: &SalaryDTO) -> Self {
-        NewSalary {
-            employee_id: salary_dto.employee_id.unwrap(),
-            from_date: salary_dto.from_date,
-            to_date: salary_dto.to_date,
-            amount: salary_dto.amount,
-            search_string: salary_dto.search_string.clone(),
+/// Salaries of rows - their amounts are decrypted by keys
+pub(crate) fn from_rows(rows: QueryResult<Vec<Salary>>, keys: Option<&KeyRing>) -> QueryResult<Vec<SalaryDTO>> {
+    rows?.iter().map(|s| SalaryDTO::from_row(s, keys)).collect()
+}
+
+/// Amount of salary row `s_id` - amount which can't be decrypted (no or unknown key) is error of query
+/// (500 Internal Server Error in REST) as salaries are never read without their amount
+pub(crate) fn amount_of(s_id: i32, plain: i64, encrypted: Option<&str>, keys: Option<&KeyRing>) -> QueryResult<i64> {
+    crypto::open_amount(s_id, "Amount of salary", plain, encrypted, keys)
+}
+
+/// Salaries saved before they got currency (marked by empty `currency`) had amount in whole units -
+/// they get `default_currency` and amount in its minor units. Return number of converted salaries.
+pub fn convert_legacy_salaries(default_currency: Currency, conn: &mut DbConnection) -> QueryResult<usize> {
+    diesel::update(salaries.filter(currency.eq("")))
+        .set((
+            currency.eq(default_currency.code()),
+            amount.eq(amount * default_currency.minor_units_per_unit()),
+        ))
+        .execute(conn)
+}
+
+/// Amounts in plain text or encrypted by other than the current key are encrypted by the current key - return
+/// number of such salaries
+pub(crate) fn reencrypt_salaries(keys: &KeyRing, conn: &mut DbConnection) -> QueryResult<usize> {
+    let stored: Vec<(i32, i64, Option<String>)> = salaries
+        .select((salary_id, amount, amount_encrypted))
+        .order(salary_id)
+        .load(conn)?;
+    let mut reencrypted = 0;
+    for (s_id, plain, encrypted) in stored {
+        if encrypted.as_deref().is_some_and(|stored| keys.is_current(stored)) {
+            continue;
         }
+        let minor_units = amount_of(s_id, plain, encrypted.as_deref(), Some(keys))?;
+        let (plain, encrypted) = crypto::seal_amount(minor_units, Some(keys));
+        diesel::update(salaries.filter(salary_id.eq(s_id)))
+            .set((amount.eq(plain), amount_encrypted.eq(encrypted)))
+            .execute(conn)?;
+        reencrypted += 1;
     }
+    Ok(reencrypted)
 }
 
 impl HaveId for SalaryDTO {
@@ -77,80 +145,204 @@ impl HaveId for SalaryDTO {
     }
 }
 
+impl HavePeriod for SalaryDTO {
+    fn period_start(&self) -> NaiveDate {
+        self.from_date
+    }
+
+    fn period_end(&self) -> Option<NaiveDate> {
+        self.to_date
+    }
+}
+
+impl HaveVersion for SalaryDTO {
+    fn get_version(&self) -> Option<i32> {
+        self.version
+    }
+}
+
 impl Crud for SalaryDTO {
     fn update(&mut self, persisted: &Self) {
         self.id = persisted.id;
+        self.version = persisted.version;
     }
 
-    fn get_simple(id_to_find: i32, conn: &mut SqliteConnection) -> QueryResult<SalaryDTO> {
-        salaries
-            .filter(salary_id.eq(id_to_find))
-            .first(conn)
-            .map(|s: Salary| SalaryDTO::from(s))
+    /// Amount can't be negative, period have to be valid, can't overlap with other salaries of the same employee
+    /// and have to be within contract of the employee (if it has any)
+    fn validate(&self, _rules: &ValidationRules, keys: Option<&KeyRing>, conn: &mut DbConnection) -> DaoResult<()> {
+        let mut errors = Errors::default();
+        check_period(self, "", &mut errors);
+        check_amount(&self.amount, "", &mut errors);
+        if let Some(parent_id) = self.employee_id
+        {
+            let existing = salaries_of(parent_id, keys, conn)?;
+            check_no_overlap_with_existing(self, &existing, "salary", &mut errors);
+            let contracts = ContractDTO::search_by_parent_id_with_connection(parent_id, keys, conn)?;
+            check_salary_contract(self, &contracts, "", &mut errors);
+        }
+        errors.into_result()
     }
 
-    fn save_simple(&self, conn: &mut SqliteConnection) -> QueryResult<SalaryDTO> {
-        fn insert(s: &SalaryDTO, conn: &mut SqliteConnection) -> QueryResult<SalaryDTO> {
-            insert_into(salaries)
-                .values(NewSalary::from(s))
-                .execute(conn)
-                .and_then(|_| {
-                    salaries
-                        .order(salary_id.desc())
-                        .first(conn)
-                        .map(|s: Salary| SalaryDTO::from(s))
-                })
+    fn get_simple(id_to_find: i32, keys: Option<&KeyRing>, conn: &mut DbConnection) -> QueryResult<SalaryDTO> {
+        let found: Salary = salaries.filter(salary_id.eq(id_to_find)).first(conn)?;
+        SalaryDTO::from_row(&found, keys)
+    }
+
+    /// Salary without contract is linked to contract of the employee covering it (if there is one)
+    fn save_simple(&self, keys: Option<&KeyRing>, conn: &mut DbConnection) -> DaoResult<SalaryDTO> {
+        if self.contract_id.is_none()
+            && let Some(parent_id) = self.employee_id
+        {
+            let contracts = ContractDTO::search_by_parent_id_with_connection(parent_id, keys, conn)?;
+            if let Some(i) = contract_of(self, &contracts) {
+                let linked = SalaryDTO {
+                    contract_id: contracts[i].id,
+                    ..self.clone()
+                };
+                return linked.save_simple(keys, conn);
+            }
         }
-        if self.id.is_some() {
-            let self_id = self.id.unwrap();
-            let updated = diesel::update(salaries.filter(salary_id.eq(self_id)))
-                .set(Salary::from(self))
-                .execute(conn)?;
+        fn insert(s: &SalaryDTO, keys: Option<&KeyRing>, conn: &mut DbConnection) -> QueryResult<SalaryDTO> {
+            let inserted: Salary = insert_into(salaries)
+                .values(s.to_new_row(keys))
+                .get_result(conn)?;
+            SalaryDTO::from_row(&inserted, keys)
+        }
+        if let Some(self_id) = self.id {
+            let updated = match self.version {
+                Some(self_version) => diesel::update(
+                    salaries
+                        .filter(salary_id.eq(self_id))
+                        .filter(version.eq(self_version)),
+                )
+                .set((self.to_row(keys), version.eq(version + 1)))
+                .execute(conn)?,
+                None => 0,
+            };
             if updated == 0 {
-                insert(self, conn)
-            } else {
-                salaries
+                let current = salaries
                     .filter(salary_id.eq(self_id))
-                    .first(conn)
-                    .map(|s: Salary| SalaryDTO::from(s))
+                    .select(version)
+                    .first::<i32>(conn)
+                    .optional()?;
+                match current {
+                    Some(current) => Err(stale_version(self_id, Some(current))),
+                    None => Ok(insert(self, keys, conn)?),
+                }
+            } else {
+                Ok(Self::get_simple(self_id, keys, conn)?)
             }
         } else {
-            insert(self, conn)
+            Ok(insert(self, keys, conn)?)
         }
     }
 
-    fn delete_simple(id_to_find: i32, conn: &mut SqliteConnection) -> QueryResult<usize> {
+    fn delete_simple(id_to_find: i32, conn: &mut DbConnection) -> QueryResult<usize> {
         diesel::delete(salaries.filter(salary_id.eq(id_to_find))).execute(conn)
     }
+
+    fn delete_versioned_simple(
+        id_to_find: i32,
+        version_to_find: i32,
+        conn: &mut DbConnection,
+    ) -> QueryResult<usize> {
+        diesel::delete(
+            salaries
+                .filter(salary_id.eq(id_to_find))
+                .filter(version.eq(version_to_find)),
+        )
+        .execute(conn)
+    }
 }
 
+/// Records of deleted employees are left out
 impl Searchable for SalaryDTO {
-    fn get_all_with_connection(conn: &mut SqliteConnection) -> Vec<Self> {
-        todo!()
+    fn get_all_with_connection(keys: Option<&KeyRing>, conn: &mut DbConnection) -> DaoResult<Vec<Self>> {
+        let found = salaries.filter(employee_id.eq_any(not_deleted())).load::<Salary>(conn);
+        Ok(from_rows(found, keys)?)
     }
 
-    fn search_with_connection(s: &str, conn: &mut SqliteConnection) -> Vec<Self> {
-        todo!()
+    fn search_with_connection(s: &str, keys: Option<&KeyRing>, conn: &mut DbConnection) -> DaoResult<Vec<Self>> {
+        let found = salaries
+            .filter(search_string.like(s))
+            .filter(employee_id.eq_any(not_deleted()))
+            .load::<Salary>(conn);
+        Ok(from_rows(found, keys)?)
     }
 }
 
 impl SearchableByParent for SalaryDTO {
     fn search_by_parent_id_with_connection(
         parent_id: i32,
-        conn: &mut SqliteConnection,
-    ) -> Vec<Self> {
-        todo!()
+        keys: Option<&KeyRing>,
+        conn: &mut DbConnection,
+    ) -> DaoResult<Vec<Self>> {
+        Ok(salaries_of(parent_id, keys, conn)?)
+    }
+}
+
+impl SalaryDTO {
+    /// Salaries paid under contract
+    pub fn search_by_contract_id_with_connection(
+        c_id: i32,
+        keys: Option<&KeyRing>,
+        conn: &mut DbConnection,
+    ) -> DaoResult<Vec<Self>> {
+        let found = salaries.filter(contract_id.eq(c_id)).order(from_date).load::<Salary>(conn);
+        Ok(from_rows(found, keys)?)
+    }
+}
+
+/// Salaries of employee - see SearchableByParent
+pub(crate) fn salaries_of(
+    parent_id: i32,
+    keys: Option<&KeyRing>,
+    conn: &mut DbConnection,
+) -> QueryResult<Vec<SalaryDTO>> {
+    let found = salaries.filter(employee_id.eq(parent_id)).load::<Salary>(conn);
+    from_rows(found, keys)
+}
+
+/// Salary of employee valid on date - see SearchableByDate
+pub(crate) fn salary_on(
+    parent_id: i32,
+    date: NaiveDate,
+    keys: Option<&KeyRing>,
+    conn: &mut DbConnection,
+) -> QueryResult<Option<SalaryDTO>> {
+    let found = salaries
+        .filter(employee_id.eq(parent_id))
+        .filter(from_date.le(date))
+        .filter(to_date.is_null().or(to_date.ge(date)))
+        .order(from_date.desc())
+        .first::<Salary>(conn)
+        .optional()?;
+    found.map(|s| SalaryDTO::from_row(&s, keys)).transpose()
+}
+
+impl SearchableByDate for SalaryDTO {
+    fn effective_on_with_connection(
+        parent_id: i32,
+        date: NaiveDate,
+        keys: Option<&KeyRing>,
+        conn: &mut DbConnection,
+    ) -> DaoResult<Option<Self>> {
+        Ok(salary_on(parent_id, date, keys, conn)?)
     }
 }
 
 #[cfg(test)]
 mod tests {
     use crate::common_for_tests::*;
+    use crate::error::DaoError;
     use diesel_migrations::{EmbeddedMigrations, MigrationHarness};
 
     use super::*;
 
-    const SALARIES_TEST_DATA: EmbeddedMigrations = embed_migrations!("test_data/salaries");
+    #[cfg(feature = "sqlite")]
+    const SALARIES_TEST_DATA: EmbeddedMigrations = embed_migrations!("test_data/sqlite/salaries");
+    #[cfg(feature = "postgres")]
+    const SALARIES_TEST_DATA: EmbeddedMigrations = embed_migrations!("test_data/postgres/salaries");
 
     impl CrudTests for SalaryDTO {}
 
@@ -162,13 +354,127 @@ mod tests {
         let mut salary = SalaryDTO {
             id: None,
             employee_id: Some(1),
-            from_date: NaiveDate::from_ymd(2015, 3, 14),
-            to_date: NaiveDate::from_ymd(2020, 5, 23),
-            amount: 0,
+            from_date: NaiveDate::from_ymd_opt(2015, 3, 14).unwrap(),
+            to_date: Some(NaiveDate::from_ymd_opt(2020, 5, 23).unwrap()),
+            amount: Money::new(0, Currency::PLN),
+            pay_period: PayPeriod::Monthly,
+            gross: true,
             search_string: "some search".to_string(),
+            version: None,
+            contract_id: None,
         };
-        //salary.save_simple(conn).unwrap();
+        //salary.save_simple(keys(), conn).unwrap();
         salary.test(conn);
-        //salary.test_without_conn();
+        //salary.test_with_db(&db);
+    }
+
+    #[test]
+    fn invalid_salary_periods_should_be_rejected() {
+        let conn = &mut initialize();
+        conn.run_pending_migrations(SALARIES_TEST_DATA)
+            .expect("Fail to insert salaries test data into DB");
+        let rules = ValidationRules::default();
+        let open_ended = SalaryDTO {
+            id: None,
+            employee_id: Some(1),
+            from_date: NaiveDate::from_ymd_opt(2020, 1, 1).unwrap(),
+            to_date: None,
+            amount: Money::new(100, Currency::PLN),
+            pay_period: PayPeriod::Monthly,
+            gross: true,
+            search_string: "".to_string(),
+            version: None,
+            contract_id: None,
+        };
+        let saved = open_ended.try_save_in_transaction(&rules, keys(), conn).unwrap();
+        assert_eq!(saved.to_date, None);
+
+        let ends_before_start = SalaryDTO {
+            from_date: NaiveDate::from_ymd_opt(2019, 1, 1).unwrap(),
+            to_date: Some(NaiveDate::from_ymd_opt(2018, 1, 1).unwrap()),
+            ..open_ended.clone()
+        };
+        let overlapping = SalaryDTO {
+            from_date: NaiveDate::from_ymd_opt(2019, 1, 1).unwrap(),
+            to_date: Some(NaiveDate::from_ymd_opt(2020, 1, 1).unwrap()),
+            ..open_ended.clone()
+        };
+        for (salary, field) in [(ends_before_start, "to_date"), (overlapping, "from_date")] {
+            match salary.try_save_in_transaction(&rules, keys(), conn) {
+                Err(DaoError::Validation(errors)) => assert_eq!(errors[0].field, field),
+                result => panic!("Should report validation error and instead I got {:?}", result),
+            }
+        }
+
+        // Closing the period of saved salary doesn't overlap with itself
+        let closed = SalaryDTO {
+            to_date: Some(NaiveDate::from_ymd_opt(2020, 12, 31).unwrap()),
+            ..saved
+        };
+        assert!(closed.try_save_in_transaction(&rules, keys(), conn).is_ok());
+        assert_eq!(SalaryDTO::search_by_parent_id_with_connection(1, keys(), conn).unwrap().len(), 1);
+
+        let negative = SalaryDTO {
+            from_date: NaiveDate::from_ymd_opt(2021, 1, 1).unwrap(),
+            amount: Money::new(-1, Currency::PLN),
+            ..open_ended
+        };
+        match negative.try_save_in_transaction(&rules, keys(), conn) {
+            Err(DaoError::Validation(errors)) => assert_eq!(errors[0].field, "amount"),
+            result => panic!("Should report validation error and instead I got {:?}", result),
+        }
+    }
+
+    #[test]
+    fn legacy_salaries_get_default_currency() {
+        let conn = &mut initialize();
+        conn.run_pending_migrations(SALARIES_TEST_DATA)
+            .expect("Fail to insert salaries test data into DB");
+        diesel::sql_query(
+            "insert into salaries(employee_id, from_date, to_date, amount, search_string, currency) \
+             values (1, '2020-01-01', NULL, 1234, '', '')",
+        )
+        .execute(conn)
+        .unwrap();
+        assert_eq!(convert_legacy_salaries(Currency::EUR, conn).unwrap(), 1);
+        assert_eq!(convert_legacy_salaries(Currency::EUR, conn).unwrap(), 0);
+        let salary = &SalaryDTO::search_by_parent_id_with_connection(1, keys(), conn).unwrap()[0];
+        assert_eq!(salary.amount, Money::new(123400, Currency::EUR));
+        assert_eq!(salary.pay_period, PayPeriod::Monthly);
+        assert!(salary.gross);
+        assert_eq!(
+            serde_json::to_value(salary).unwrap()["amount"],
+            serde_json::json!("1234.00")
+        );
+    }
+
+    #[test]
+    fn salary_which_cant_be_decrypted_is_error() {
+        let conn = &mut initialize();
+        conn.run_pending_migrations(SALARIES_TEST_DATA)
+            .expect("Fail to insert salaries test data into DB");
+        let saved = SalaryDTO {
+            id: None,
+            employee_id: Some(1),
+            from_date: NaiveDate::from_ymd_opt(2020, 1, 1).unwrap(),
+            to_date: None,
+            amount: Money::new(100, Currency::PLN),
+            pay_period: PayPeriod::Monthly,
+            gross: true,
+            search_string: "".to_string(),
+            version: None,
+            contract_id: None,
+        }
+        .save_simple(keys(), conn)
+        .unwrap();
+        let s_id = saved.id.unwrap();
+        match SalaryDTO::get_simple(s_id, None, conn) {
+            Err(diesel::result::Error::DeserializationError(e)) => assert_eq!(
+                e.to_string(),
+                format!("Amount of salary of id = {} can't be decrypted: ENCRYPTION_KEY is not configured", s_id)
+            ),
+            result => panic!("Should report decryption error and instead I got {:?}", result),
+        }
+        assert!(DaoError::from(salaries_of(1, None, conn).unwrap_err()).to_string().contains("can't be decrypted"));
     }
 }
@@ -1,12 +1,178 @@
 table! {
-    contacts (id) {
+    absence_types (id) {
+        id -> Integer,
+        name -> Text,
+        yearly_entitlement -> Nullable<Integer>,
+        carry_over_limit -> Integer,
+        search_string -> Text,
+        version -> Integer,
+    }
+}
+
+table! {
+    absences (id) {
         id -> Integer,
         employee_id -> Integer,
+        absence_type_id -> Integer,
         from_date -> Date,
         to_date -> Date,
+        days -> Integer,
+        status -> Text,
+        decided_by -> Nullable<Integer>,
+        comment -> Nullable<Text>,
+        search_string -> Text,
+        version -> Integer,
+    }
+}
+
+table! {
+    audit_log (id) {
+        id -> Integer,
+        at -> Timestamp,
+        user_id -> Nullable<Integer>,
+        employee_id -> Nullable<Integer>,
+        action -> Text,
+        details -> Text,
+    }
+}
+
+table! {
+    contact_addresses (id) {
+        id -> Integer,
+        contact_id -> Integer,
+        position -> Integer,
+        kind -> Text,
+        street -> Text,
+        city -> Text,
+        postal_code -> Text,
+        country -> Text,
+        encrypted -> Bool,
+    }
+}
+
+table! {
+    contact_emails (id) {
+        id -> Integer,
+        contact_id -> Integer,
+        position -> Integer,
+        kind -> Text,
+        email -> Text,
+    }
+}
+
+table! {
+    contact_phones (id) {
+        id -> Integer,
+        contact_id -> Integer,
+        position -> Integer,
+        kind -> Text,
+        number -> Text,
+        encrypted -> Bool,
+        number_index -> Nullable<Text>,
+    }
+}
+
+table! {
+    contacts (id) {
+        id -> Integer,
+        employee_id -> Integer,
+        from_date -> Date,
+        to_date -> Nullable<Date>,
+        search_string -> Text,
+        version -> Integer,
+    }
+}
+
+table! {
+    custom_field_values (id) {
+        id -> Integer,
+        employee_id -> Integer,
+        custom_field_id -> Integer,
+        value -> Text,
+    }
+}
+
+table! {
+    custom_fields (id) {
+        id -> Integer,
+        name -> Text,
+        field_type -> Text,
+        required -> Bool,
+        rules -> Text,
+        search_string -> Text,
+        version -> Integer,
+    }
+}
+
+table! {
+    departments (id) {
+        id -> Integer,
+        name -> Text,
+        parent_id -> Nullable<Integer>,
+        search_string -> Text,
+        version -> Integer,
+    }
+}
+
+table! {
+    documents (id) {
+        id -> Integer,
+        employee_id -> Integer,
+        document_type -> Text,
+        file_name -> Text,
+        content_type -> Text,
+        size -> BigInt,
+        sha256 -> Text,
+        storage_key -> Text,
+        valid_from -> Nullable<Date>,
+        valid_to -> Nullable<Date>,
+        confidentiality -> Text,
+        uploaded_by -> Nullable<Integer>,
+        uploaded_at -> Timestamp,
+    }
+}
+
+table! {
+    emergency_contacts (id) {
+        id -> Integer,
+        employee_id -> Integer,
+        position -> Integer,
+        name -> Text,
+        relationship -> Text,
         phone -> Text,
-        address -> Nullable<Text>,
+        email -> Nullable<Text>,
+        encrypted -> Bool,
+    }
+}
+
+table! {
+    employee_groups (id) {
+        id -> Integer,
+        name -> Text,
+        filter -> Text,
         search_string -> Text,
+        version -> Integer,
+    }
+}
+
+table! {
+    employee_photos (id) {
+        id -> Integer,
+        employee_id -> Integer,
+        content_type -> Text,
+        width -> Integer,
+        height -> Integer,
+        sha256 -> Text,
+        uploaded_by -> Nullable<Integer>,
+        uploaded_at -> Timestamp,
+    }
+}
+
+table! {
+    employee_tags (id) {
+        id -> Integer,
+        employee_id -> Integer,
+        tag_id -> Integer,
     }
 }
 
@@ -16,6 +182,100 @@ table! {
         first_name -> Text,
         last_name -> Text,
         search_string -> Text,
+        version -> Integer,
+        department_id -> Nullable<Integer>,
+        manager_id -> Nullable<Integer>,
+        employee_number -> Nullable<Text>,
+        date_of_birth -> Nullable<Date>,
+        national_id -> Nullable<Text>,
+        national_id_hash -> Nullable<Text>,
+        hire_date -> Nullable<Date>,
+        termination_date -> Nullable<Date>,
+        status -> Text,
+        deleted_at -> Nullable<Timestamp>,
+        deleted_by -> Nullable<Integer>,
+        anonymized_at -> Nullable<Timestamp>,
+    }
+}
+
+table! {
+    employment_contracts (id) {
+        id -> Integer,
+        employee_id -> Integer,
+        position_id -> Nullable<Integer>,
+        contract_type -> Text,
+        from_date -> Date,
+        to_date -> Nullable<Date>,
+        working_time_numerator -> Integer,
+        working_time_denominator -> Integer,
+        search_string -> Text,
+        version -> Integer,
+    }
+}
+
+table! {
+    payroll_rules (id) {
+        id -> Integer,
+        name -> Text,
+        kind -> Text,
+        percent -> Nullable<Integer>,
+        amount -> Nullable<BigInt>,
+        currency -> Nullable<Text>,
+        active -> Bool,
+        search_string -> Text,
+        version -> Integer,
+    }
+}
+
+table! {
+    payroll_runs (id) {
+        id -> Integer,
+        year -> Integer,
+        month -> Integer,
+        status -> Text,
+        approved_by -> Nullable<Integer>,
+        rules -> Text,
+        version -> Integer,
+    }
+}
+
+table! {
+    payslip_lines (id) {
+        id -> Integer,
+        payslip_id -> Integer,
+        position -> Integer,
+        kind -> Text,
+        description -> Text,
+        amount -> BigInt,
+        salary_id -> Nullable<Integer>,
+        gross -> Bool,
+        amount_encrypted -> Nullable<Text>,
+    }
+}
+
+table! {
+    payslips (id) {
+        id -> Integer,
+        payroll_run_id -> Integer,
+        employee_id -> Integer,
+        first_name -> Text,
+        last_name -> Text,
+        currency -> Text,
+        gross -> BigInt,
+        deductions -> BigInt,
+        net -> BigInt,
+        gross_encrypted -> Nullable<Text>,
+        deductions_encrypted -> Nullable<Text>,
+        net_encrypted -> Nullable<Text>,
+    }
+}
+
+table! {
+    positions (id) {
+        id -> Integer,
+        name -> Text,
+        search_string -> Text,
+        version -> Integer,
     }
 }
 
@@ -24,9 +284,53 @@ table! {
         id -> Integer,
         employee_id -> Integer,
         from_date -> Date,
-        to_date -> Date,
+        to_date -> Nullable<Date>,
         amount -> BigInt,
         search_string -> Text,
+        version -> Integer,
+        currency -> Text,
+        pay_period -> Text,
+        gross -> Bool,
+        contract_id -> Nullable<Integer>,
+        amount_encrypted -> Nullable<Text>,
+    }
+}
+
+table! {
+    sequences (name) {
+        name -> Text,
+        value -> Integer,
+    }
+}
+
+table! {
+    tags (id) {
+        id -> Integer,
+        name -> Text,
+    }
+}
+
+table! {
+    timesheet_entries (id) {
+        id -> Integer,
+        employee_id -> Integer,
+        work_date -> Date,
+        minutes -> Integer,
+        category -> Text,
+        note -> Nullable<Text>,
+        search_string -> Text,
+        version -> Integer,
+    }
+}
+
+table! {
+    timesheet_weeks (id) {
+        id -> Integer,
+        employee_id -> Integer,
+        week_start -> Date,
+        status -> Text,
+        decided_by -> Nullable<Integer>,
+        version -> Integer,
     }
 }
 
@@ -36,10 +340,68 @@ table! {
         username -> Text,
         password -> Text,
         is_admin -> Bool,
+        version -> Integer,
+        employee_id -> Nullable<Integer>,
+        privacy_officer -> Bool,
     }
 }
 
+joinable!(absences -> absence_types (absence_type_id));
+joinable!(absences -> employees (employee_id));
+joinable!(absences -> users (decided_by));
+joinable!(audit_log -> users (user_id));
+joinable!(contact_addresses -> contacts (contact_id));
+joinable!(contact_emails -> contacts (contact_id));
+joinable!(contact_phones -> contacts (contact_id));
 joinable!(contacts -> employees (employee_id));
+joinable!(custom_field_values -> custom_fields (custom_field_id));
+joinable!(custom_field_values -> employees (employee_id));
+joinable!(documents -> employees (employee_id));
+joinable!(documents -> users (uploaded_by));
+joinable!(emergency_contacts -> employees (employee_id));
+joinable!(employee_photos -> employees (employee_id));
+joinable!(employee_photos -> users (uploaded_by));
+joinable!(employee_tags -> employees (employee_id));
+joinable!(employee_tags -> tags (tag_id));
+joinable!(employees -> departments (department_id));
+joinable!(employment_contracts -> employees (employee_id));
+joinable!(employment_contracts -> positions (position_id));
+joinable!(payroll_runs -> users (approved_by));
+joinable!(payslip_lines -> payslips (payslip_id));
+joinable!(payslips -> payroll_runs (payroll_run_id));
 joinable!(salaries -> employees (employee_id));
+joinable!(salaries -> employment_contracts (contract_id));
+joinable!(timesheet_entries -> employees (employee_id));
+joinable!(timesheet_weeks -> employees (employee_id));
+joinable!(timesheet_weeks -> users (decided_by));
 
-allow_tables_to_appear_in_same_query!(contacts, employees, salaries, users,);
+allow_tables_to_appear_in_same_query!(
+    absence_types,
+    absences,
+    audit_log,
+    contact_addresses,
+    contact_emails,
+    contact_phones,
+    contacts,
+    custom_field_values,
+    custom_fields,
+    departments,
+    documents,
+    emergency_contacts,
+    employee_groups,
+    employee_photos,
+    employee_tags,
+    employees,
+    employment_contracts,
+    payroll_rules,
+    payroll_runs,
+    payslip_lines,
+    payslips,
+    positions,
+    salaries,
+    sequences,
+    tags,
+    timesheet_entries,
+    timesheet_weeks,
+    users,
+);
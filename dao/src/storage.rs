@@ -0,0 +1,212 @@
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::s3_storage::S3Storage;
+
+/// Where content of documents is kept - their metadata (with `storage_key`) is in `documents` table
+pub trait DocumentStorage: Send + Sync {
+    fn put(&self, key: &str, content: &[u8]) -> Result<(), StorageError>;
+
+    /// StorageError::NotFound when there is nothing under the key
+    fn get(&self, key: &str) -> Result<Vec<u8>, StorageError>;
+
+    /// Deleting what is not stored is not an error
+    fn delete(&self, key: &str) -> Result<(), StorageError>;
+}
+
+#[derive(Debug)]
+pub enum StorageError {
+    /// Local file system or connection to remote store failed
+    Io(io::Error),
+    /// There is nothing stored under the key
+    NotFound(String),
+    /// Remote store answered by error
+    Remote { status: u16, message: String },
+    /// Stored content doesn't match checksum it was stored with
+    Corrupted(String),
+    /// Remote store answered by more than the biggest content which is stored
+    TooBig { key: String, max_size: u64 },
+}
+
+impl fmt::Display for StorageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StorageError::Io(e) => write!(f, "{}", e),
+            StorageError::NotFound(key) => write!(f, "Nothing is stored under '{}'", key),
+            StorageError::Remote { status, message } => write!(f, "Store answered {}: {}", status, message),
+            StorageError::Corrupted(key) => write!(f, "Content under '{}' doesn't match its checksum", key),
+            StorageError::TooBig { key, max_size } => {
+                write!(f, "Content under '{}' is bigger than {} bytes", key, max_size)
+            }
+        }
+    }
+}
+
+impl Error for StorageError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            StorageError::Io(e) => Some(e),
+            StorageError::NotFound(_)
+            | StorageError::Remote { .. }
+            | StorageError::Corrupted(_)
+            | StorageError::TooBig { .. } => None,
+        }
+    }
+}
+
+impl From<io::Error> for StorageError {
+    fn from(e: io::Error) -> Self {
+        StorageError::Io(e)
+    }
+}
+
+/// S3 compatible store (AWS S3, MinIO ...) - objects are addressed in path style (`<endpoint>/<bucket>/<key>`)
+#[derive(Clone, PartialEq)]
+pub struct S3Config {
+    /// `http://host[:port]` or `https://host[:port]`
+    pub endpoint: String,
+    pub bucket: String,
+    pub region: String,
+    pub access_key: String,
+    pub secret_key: String,
+}
+
+/// Secret key is never logged
+impl fmt::Debug for S3Config {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("S3Config")
+            .field("endpoint", &self.endpoint)
+            .field("bucket", &self.bucket)
+            .field("region", &self.region)
+            .field("access_key", &self.access_key)
+            .field("secret_key", &"***")
+            .finish()
+    }
+}
+
+/// Backend of DocumentStorage - local directory (DOCUMENTS_DIR) or S3 compatible store (S3_ENDPOINT ...)
+#[derive(Clone, Debug, PartialEq)]
+pub enum StorageConfig {
+    Local(PathBuf),
+    S3(S3Config),
+}
+
+/// Which documents can be uploaded and where they are kept
+#[derive(Clone, Debug, PartialEq)]
+pub struct DocumentsConfig {
+    pub storage: StorageConfig,
+    /// The biggest document in bytes (DOCUMENT_MAX_SIZE)
+    pub max_size: u64,
+    /// Allowed content types (DOCUMENT_MIME_TYPES - separated by comma)
+    pub mime_types: Vec<String>,
+}
+
+impl DocumentsConfig {
+    /// Content bigger than max_size is never stored, so it is never read from S3 compatible store either
+    pub fn open(&self) -> io::Result<Arc<dyn DocumentStorage>> {
+        Ok(match &self.storage {
+            StorageConfig::Local(dir) => Arc::new(LocalStorage::new(dir)),
+            StorageConfig::S3(config) => Arc::new(S3Storage::new(config, self.max_size)?),
+        })
+    }
+}
+
+impl Default for DocumentsConfig {
+    fn default() -> Self {
+        DocumentsConfig {
+            storage: StorageConfig::Local(PathBuf::from("documents")),
+            max_size: 10 * 1024 * 1024,
+            mime_types: vec![
+                "application/pdf".to_string(),
+                "image/png".to_string(),
+                "image/jpeg".to_string(),
+            ],
+        }
+    }
+}
+
+/// Every document is file in directory - key is its path relative to the directory
+pub struct LocalStorage {
+    dir: PathBuf,
+}
+
+impl LocalStorage {
+    /// Directory is created with the first document
+    pub fn new<P: AsRef<Path>>(dir: P) -> LocalStorage {
+        LocalStorage {
+            dir: dir.as_ref().to_path_buf(),
+        }
+    }
+
+    /// Key can't point out of the directory
+    fn path(&self, key: &str) -> Result<PathBuf, StorageError> {
+        let valid = !key.is_empty()
+            && key
+                .split('/')
+                .all(|part| !part.is_empty() && part != "." && part != ".." && !part.contains('\\'));
+        if !valid {
+            return Err(StorageError::Io(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("'{}' is not valid storage key", key),
+            )));
+        }
+        Ok(self.dir.join(key))
+    }
+}
+
+impl DocumentStorage for LocalStorage {
+    /// Content is written to temporary file first, so half written document is never read
+    fn put(&self, key: &str, content: &[u8]) -> Result<(), StorageError> {
+        let path = self.path(key)?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut temporary = path.clone().into_os_string();
+        temporary.push(".part");
+        fs::write(&temporary, content)?;
+        fs::rename(&temporary, &path)?;
+        Ok(())
+    }
+
+    fn get(&self, key: &str) -> Result<Vec<u8>, StorageError> {
+        match fs::read(self.path(key)?) {
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Err(StorageError::NotFound(key.to_string())),
+            result => Ok(result?),
+        }
+    }
+
+    fn delete(&self, key: &str) -> Result<(), StorageError> {
+        match fs::remove_file(self.path(key)?) {
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+            result => Ok(result?),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn local_storage_keeps_documents_in_directory() {
+        let dir = std::env::temp_dir().join(format!("local_storage_test_{}", std::process::id()));
+        let storage = LocalStorage::new(&dir);
+        storage.put("1/contract", b"%PDF-1.4").unwrap();
+        assert_eq!(storage.get("1/contract").unwrap(), b"%PDF-1.4");
+        assert!(dir.join("1").join("contract").is_file());
+        storage.put("1/contract", b"%PDF-1.7").unwrap();
+        assert_eq!(storage.get("1/contract").unwrap(), b"%PDF-1.7");
+
+        storage.delete("1/contract").unwrap();
+        assert!(matches!(storage.get("1/contract"), Err(StorageError::NotFound(_))));
+        storage.delete("1/contract").unwrap();
+        for invalid in ["", "../passwd", "1//contract", "1/./contract", "/etc/passwd"] {
+            assert!(storage.put(invalid, b"").is_err(), "Key '{}' should be rejected", invalid);
+        }
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
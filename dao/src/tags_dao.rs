@@ -0,0 +1,317 @@
+use std::collections::BTreeMap;
+
+use diesel::dsl::*;
+use diesel::prelude::*;
+
+use crate::connection::{Database, DbConnection};
+use crate::error::DaoResult;
+use crate::models::{NewEmployeeTag, NewTag};
+use crate::schema::employee_tags::dsl as et;
+use crate::schema::employees::dsl as e;
+use crate::schema::tags::dsl::id as tag_id;
+use crate::schema::tags::dsl::*;
+use crate::validation::Errors;
+
+/// Longest name of tag (in characters)
+pub const MAX_TAG_LENGTH: usize = 50;
+
+/// Tag with number of employees which have it
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct TagDTO {
+    pub name: String,
+    pub employees: i64,
+}
+
+/// Bulk change of tags (`POST /employees/tags`) - tags in `add` are added to every employee (unknown tags are
+/// created), then tags in `remove` are removed from them
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct TagsChange {
+    pub employee_ids: Vec<i32>,
+    #[serde(default)]
+    pub add: Vec<String>,
+    #[serde(default)]
+    pub remove: Vec<String>,
+}
+
+/// Tag is free text - just `!` at the beginning is reserved for excluding tag in search (`?tag=!contractor`)
+fn check_tag(tag: &str, field: &str, errors: &mut Errors) {
+    let tag = tag.trim();
+    if tag.is_empty() {
+        errors.add("", field, "can't be empty".to_string());
+    } else if tag.starts_with('!') {
+        errors.add("", field, format!("'{}' can't start with '!'", tag));
+    } else if tag.chars().count() > MAX_TAG_LENGTH {
+        errors.add("", field, format!("is longer than {} characters", MAX_TAG_LENGTH));
+    }
+}
+
+/// All tags ordered by name
+pub fn get_tags(db: &Database) -> DaoResult<Vec<TagDTO>> {
+    let conn = &mut db.try_get_connection()?;
+    Ok(get_tags_with_connection(conn)?)
+}
+
+pub fn get_tags_with_connection(conn: &mut DbConnection) -> QueryResult<Vec<TagDTO>> {
+    Ok(tags
+        .left_join(et::employee_tags)
+        .group_by((tag_id, name))
+        .select((name, count(et::id.nullable())))
+        .order(name)
+        .load::<(String, i64)>(conn)?
+        .into_iter()
+        .map(|(tag_name, employees)| TagDTO { name: tag_name, employees })
+        .collect())
+}
+
+/// Apply `change` in one transaction - employees have to exist (and not be deleted). Return tags of changed
+/// employees by their id.
+pub fn change_tags_with_connection(
+    change: &TagsChange,
+    conn: &mut DbConnection,
+) -> DaoResult<BTreeMap<i32, Vec<String>>> {
+    conn.transaction(|conn| {
+        let mut errors = Errors::default();
+        if change.employee_ids.is_empty() {
+            errors.add("", "employee_ids", "at least one employee has to be given".to_string());
+        }
+        let existing: Vec<i32> = e::employees
+            .filter(e::id.eq_any(&change.employee_ids))
+            .filter(e::deleted_at.is_null())
+            .select(e::id)
+            .load(conn)?;
+        for (i, e_id) in change.employee_ids.iter().enumerate() {
+            if !existing.contains(e_id) {
+                errors.add("", &format!("employee_ids[{}]", i), format!("there is no employee with id = {}", e_id));
+            }
+        }
+        for (i, tag) in change.add.iter().enumerate() {
+            check_tag(tag, &format!("add[{}]", i), &mut errors);
+        }
+        errors.into_result()?;
+
+        for tag in &change.add {
+            let t_id = tag_id_of(tag.trim(), conn)?;
+            let tagged: Vec<i32> = et::employee_tags
+                .filter(et::tag_id.eq(t_id))
+                .filter(et::employee_id.eq_any(&existing))
+                .select(et::employee_id)
+                .load(conn)?;
+            let new_tags: Vec<NewEmployeeTag> = existing
+                .iter()
+                .filter(|e_id| !tagged.contains(e_id))
+                .map(|&e_id| NewEmployeeTag { employee_id: e_id, tag_id: t_id })
+                .collect();
+            insert_into(et::employee_tags).values(&new_tags).execute(conn)?;
+        }
+        if !change.remove.is_empty() {
+            let removed: Vec<String> = change.remove.iter().map(|t| t.trim().to_string()).collect();
+            diesel::delete(
+                et::employee_tags
+                    .filter(et::employee_id.eq_any(&existing))
+                    .filter(et::tag_id.eq_any(tags.filter(name.eq_any(removed)).select(tag_id))),
+            )
+            .execute(conn)?;
+            delete_unused_tags(conn)?;
+        }
+        let mut changed = BTreeMap::new();
+        for e_id in existing {
+            changed.insert(e_id, tags_of(e_id, conn)?);
+        }
+        Ok(changed)
+    })
+}
+
+/// Id of tag - it is created when it doesn't exist
+fn tag_id_of(tag: &str, conn: &mut DbConnection) -> QueryResult<i32> {
+    let found = tags.filter(name.eq(tag)).select(tag_id).first::<i32>(conn).optional()?;
+    match found {
+        Some(t_id) => Ok(t_id),
+        None => {
+            insert_into(tags).values(NewTag { name: tag.to_string() }).returning(tag_id).get_result(conn)
+        }
+    }
+}
+
+/// Tags nobody has are not kept
+fn delete_unused_tags(conn: &mut DbConnection) -> QueryResult<usize> {
+    diesel::delete(tags.filter(tag_id.ne_all(et::employee_tags.select(et::tag_id)))).execute(conn)
+}
+
+/// Tags of employee ordered by name
+pub(crate) fn tags_of(e_id: i32, conn: &mut DbConnection) -> QueryResult<Vec<String>> {
+    et::employee_tags
+        .inner_join(tags)
+        .filter(et::employee_id.eq(e_id))
+        .select(name)
+        .order(name)
+        .load(conn)
+}
+
+/// Tags (ordered by name) of employees by employee - employees without tags are left out
+pub(crate) fn tags_of_many(e_ids: &[i32], conn: &mut DbConnection) -> QueryResult<BTreeMap<i32, Vec<String>>> {
+    let mut by_employee: BTreeMap<i32, Vec<String>> = BTreeMap::new();
+    for (e_id, tag) in et::employee_tags
+        .inner_join(tags)
+        .filter(et::employee_id.eq_any(e_ids))
+        .select((et::employee_id, name))
+        .order(name)
+        .load::<(i32, String)>(conn)?
+    {
+        by_employee.entry(e_id).or_default().push(tag);
+    }
+    Ok(by_employee)
+}
+
+pub(crate) fn delete_tags_of(e_id: i32, conn: &mut DbConnection) -> QueryResult<usize> {
+    let deleted = diesel::delete(et::employee_tags.filter(et::employee_id.eq(e_id))).execute(conn)?;
+    delete_unused_tags(conn)?;
+    Ok(deleted)
+}
+
+/// Employees (deleted too) which have the tag
+pub(crate) fn employees_tagged(tag: &str, conn: &mut DbConnection) -> QueryResult<Vec<i32>> {
+    et::employee_tags
+        .inner_join(tags)
+        .filter(name.eq(tag))
+        .select(et::employee_id)
+        .load(conn)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::common_for_tests::*;
+    use crate::employees_dao::{EmployeeDTO, EmployeeSearch};
+    use crate::hierarchy::EmployeeScope;
+    use crate::Crud;
+
+    use super::{change_tags_with_connection, get_tags_with_connection, TagDTO, TagsChange};
+
+    fn employee(first_name: &str, conn: &mut crate::connection::DbConnection) -> i32 {
+        EmployeeDTO {
+            first_name: first_name.to_string(),
+            last_name: "Kowalski".to_string(),
+            ..Default::default()
+        }
+        .save_in_transaction(keys(), conn)
+        .unwrap()
+        .id
+        .unwrap()
+    }
+
+    fn change(employee_ids: &[i32], add: &[&str], remove: &[&str]) -> TagsChange {
+        TagsChange {
+            employee_ids: employee_ids.to_vec(),
+            add: add.iter().map(|t| t.to_string()).collect(),
+            remove: remove.iter().map(|t| t.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn tags_are_changed_in_bulk_and_searchable() {
+        let conn = &mut initialize();
+        let jan = employee("Jan", conn);
+        let anna = employee("Anna", conn);
+
+        let both = change(&[jan, anna], &["remote", " on-call "], &[]);
+        let changed = change_tags_with_connection(&both, conn).unwrap();
+        assert_eq!(changed[&jan], vec!["on-call", "remote"]);
+        change_tags_with_connection(&change(&[anna], &["contractor"], &["on-call"]), conn).unwrap();
+        assert_eq!(EmployeeDTO::get_with_conn(anna, keys(), conn).unwrap().tags, vec!["contractor", "remote"]);
+        assert_eq!(
+            get_tags_with_connection(conn).unwrap(),
+            vec![
+                TagDTO { name: "contractor".to_string(), employees: 1 },
+                TagDTO { name: "on-call".to_string(), employees: 1 },
+                TagDTO { name: "remote".to_string(), employees: 2 },
+            ]
+        );
+
+        let search = |tag: &[&str], conn: &mut crate::connection::DbConnection| {
+            let search = EmployeeSearch {
+                tag: tag.iter().map(|t| t.to_string()).collect(),
+                ..Default::default()
+            };
+            EmployeeDTO::search_in_scope_with_connection(&search, EmployeeScope::All, keys(), conn)
+                .unwrap()
+                .into_iter()
+                .map(|e| e.id.unwrap())
+                .collect::<Vec<i32>>()
+        };
+        assert_eq!(search(&["remote"], conn), vec![jan, anna]);
+        assert_eq!(search(&["remote", "!contractor"], conn), vec![jan]);
+        assert_eq!(search(&["!nobody-has-it"], conn), vec![jan, anna]);
+        assert!(search(&["nobody-has-it"], conn).is_empty());
+
+        // Tag nobody has is deleted
+        change_tags_with_connection(&change(&[jan], &[], &["on-call"]), conn).unwrap();
+        assert_eq!(get_tags_with_connection(conn).unwrap().len(), 2);
+
+        for (invalid, field) in [
+            (change(&[], &["remote"], &[]), "employee_ids"),
+            (change(&[jan, anna + 1], &["remote"], &[]), "employee_ids[1]"),
+            (change(&[jan], &["remote", " "], &[]), "add[1]"),
+            (change(&[jan], &["!remote"], &[]), "add[0]"),
+        ] {
+            match change_tags_with_connection(&invalid, conn) {
+                Err(crate::DaoError::Validation(errors)) => assert_eq!(errors[0].field, field),
+                result => panic!("Should report validation error and instead I got {:?}", result),
+            }
+        }
+    }
+
+    #[test]
+    fn invalid_bulk_change_changes_nothing() {
+        let conn = &mut initialize();
+        let jan = employee("Jan", conn);
+        let anna = employee("Anna", conn);
+        change_tags_with_connection(&change(&[jan], &["remote"], &[]), conn).unwrap();
+
+        let invalid = change(&[jan, anna], &["contractor", "!remote"], &["remote"]);
+        assert!(change_tags_with_connection(&invalid, conn).unwrap_err().is_validation());
+        assert_eq!(EmployeeDTO::get_with_conn(jan, keys(), conn).unwrap().tags, vec!["remote"]);
+        assert!(EmployeeDTO::get_with_conn(anna, keys(), conn).unwrap().tags.is_empty());
+        let remote = TagDTO { name: "remote".to_string(), employees: 1 };
+        assert_eq!(get_tags_with_connection(conn).unwrap(), vec![remote]);
+
+        // Tag added twice (or to employee who has it already) is kept once
+        let twice = change(&[jan, anna], &["remote", "remote "], &[]);
+        let changed = change_tags_with_connection(&twice, conn).unwrap();
+        assert_eq!(changed[&jan], vec!["remote"]);
+        assert_eq!(changed[&anna], vec!["remote"]);
+        // Removing tag nobody has is not an error
+        let changed = change_tags_with_connection(&change(&[anna], &[], &["contractor"]), conn).unwrap();
+        assert_eq!(changed[&anna], vec!["remote"]);
+    }
+
+    #[test]
+    fn tag_filter_is_combined_with_other_filters() {
+        let conn = &mut initialize();
+        let jan = employee("Jan", conn);
+        let anna = employee("Anna", conn);
+        let piotr = employee("Piotr", conn);
+        change_tags_with_connection(&change(&[jan, anna, piotr], &["remote"], &[]), conn).unwrap();
+        change_tags_with_connection(&change(&[anna], &["contractor"], &[]), conn).unwrap();
+        let deleted = EmployeeDTO::get_with_conn(piotr, keys(), conn).unwrap();
+        EmployeeDTO::delete_by_with_connection(piotr, deleted.version.unwrap(), 1, keys(), conn).unwrap();
+
+        let search = |search: EmployeeSearch, conn: &mut crate::connection::DbConnection| {
+            EmployeeDTO::search_in_scope_with_connection(&search, EmployeeScope::All, keys(), conn)
+                .unwrap()
+                .into_iter()
+                .map(|e| e.id.unwrap())
+                .collect::<Vec<i32>>()
+        };
+        let tagged = |tag: &[&str]| EmployeeSearch {
+            tag: tag.iter().map(|t| t.to_string()).collect(),
+            ..Default::default()
+        };
+        assert_eq!(search(tagged(&["remote"]), conn), vec![jan, anna]);
+        let with_deleted = EmployeeSearch { include_deleted: true, ..tagged(&["remote"]) };
+        assert_eq!(search(with_deleted, conn), vec![jan, anna, piotr]);
+        let named = |q: &str, tag: &[&str]| EmployeeSearch { q: Some(q.to_string()), ..tagged(tag) };
+        assert_eq!(search(named("Anna", &["remote"]), conn), vec![anna]);
+        assert!(search(named("Jan", &["contractor"]), conn).is_empty());
+        assert_eq!(search(tagged(&["remote", "contractor"]), conn), vec![anna]);
+        assert_eq!(search(tagged(&[" ! contractor "]), conn), vec![jan]);
+    }
+}
@@ -0,0 +1,986 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::str::FromStr;
+
+use chrono::{Datelike, Days, Months, NaiveDate};
+use diesel::dsl::*;
+use diesel::prelude::*;
+
+use crate::base_dao::{stale_version, Crud, HaveId, HaveVersion, SearchableByParent};
+use crate::connection::{Database, DbConnection};
+use crate::crypto::KeyRing;
+use crate::error::DaoResult;
+use crate::hierarchy::EmployeeScope;
+use crate::models::{NewTimesheetEntry, TimesheetEntry, TimesheetWeek};
+use crate::money::{Currency, Money, PayPeriod};
+use crate::page::{Page, PageRequest};
+use crate::salaries_dao::amount_of;
+use crate::schema::timesheet_entries::dsl::id as entry_id;
+use crate::schema::timesheet_entries::dsl::*;
+use crate::validation::{Errors, ValidationRules};
+
+/// Employee can't work more than 24 hours a day
+pub const MAX_MINUTES_PER_DAY: i32 = 24 * 60;
+
+/// Exact worked time - whole minutes. In JSON it is `"7:30"` (decimal `"7.5"` is accepted too) so it never
+/// goes through floating point.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[serde(try_from = "String", into = "String")]
+pub struct Hours {
+    pub minutes: i32,
+}
+
+impl Hours {
+    pub fn from_minutes(total: i32) -> Hours {
+        Hours { minutes: total }
+    }
+
+    /// Parse `"7:30"` or decimal `"7.5"` - decimal have to be whole number of minutes
+    pub fn parse(s: &str) -> Result<Hours, String> {
+        let invalid = || format!("invalid hours '{}' - expected H:MM (like 7:30) or decimal hours (like 7.5)", s);
+        let all_digits = |s: &str| !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit());
+        let total = if let Some((h, m)) = s.split_once(':') {
+            if !all_digits(h) || m.len() != 2 || !all_digits(m) {
+                return Err(invalid());
+            }
+            let m: i32 = m.parse().map_err(|_| invalid())?;
+            if m >= 60 {
+                return Err(invalid());
+            }
+            h.parse::<i32>()
+                .ok()
+                .and_then(|h| h.checked_mul(60))
+                .and_then(|h| h.checked_add(m))
+        } else {
+            let (h, fraction) = s.split_once('.').unwrap_or((s, ""));
+            if !all_digits(h) || (s.contains('.') && !all_digits(fraction)) || fraction.len() > 4 {
+                return Err(invalid());
+            }
+            // fraction in 1/10000 of hour - 60 minutes is 10000
+            let fraction: i32 = format!("{:0<4}", fraction).parse().map_err(|_| invalid())?;
+            if fraction * 60 % 10000 != 0 {
+                return Err(format!("{} - it is not whole number of minutes", invalid()));
+            }
+            h.parse::<i32>()
+                .ok()
+                .and_then(|h| h.checked_mul(60))
+                .and_then(|h| h.checked_add(fraction * 60 / 10000))
+        };
+        total.map(Hours::from_minutes).ok_or_else(invalid)
+    }
+
+    /// Decimal hours rounded to 2 digits - "7.50"
+    pub fn decimal(&self) -> String {
+        let hundredths = (self.minutes as i64 * 100 + 30) / 60;
+        format!("{}.{:02}", hundredths / 100, hundredths % 100)
+    }
+}
+
+impl fmt::Display for Hours {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{:02}", self.minutes / 60, self.minutes % 60)
+    }
+}
+
+impl TryFrom<String> for Hours {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        Hours::parse(&s)
+    }
+}
+
+impl From<Hours> for String {
+    fn from(h: Hours) -> Self {
+        h.to_string()
+    }
+}
+
+impl std::ops::Add for Hours {
+    type Output = Hours;
+
+    fn add(self, other: Hours) -> Hours {
+        Hours::from_minutes(self.minutes + other.minutes)
+    }
+}
+
+/// Monday of the week `date` belongs to
+pub fn week_start(date: NaiveDate) -> NaiveDate {
+    date - Days::new(date.weekday().num_days_from_monday() as u64)
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct TimesheetEntryDTO {
+    pub id: Option<i32>,
+    pub employee_id: Option<i32>,
+    pub work_date: NaiveDate,
+    pub hours: Hours,
+    /// Project or kind of work
+    pub category: String,
+    #[serde(default)]
+    pub note: Option<String>,
+    pub search_string: String,
+    pub version: Option<i32>,
+}
+
+impl From<TimesheetEntry> for TimesheetEntryDTO {
+    fn from(e: TimesheetEntry) -> Self {
+        TimesheetEntryDTO {
+            id: Some(e.id),
+            employee_id: Some(e.employee_id),
+            work_date: e.work_date,
+            hours: Hours::from_minutes(e.minutes),
+            category: e.category,
+            note: e.note,
+            search_string: e.search_string,
+            version: Some(e.version),
+        }
+    }
+}
+
+impl From<&TimesheetEntryDTO> for TimesheetEntry {
+    fn from(entry_dto: &TimesheetEntryDTO) -> Self {
+        TimesheetEntry {
+            id: entry_dto.id.unwrap(),
+            employee_id: entry_dto.employee_id.unwrap(),
+            work_date: entry_dto.work_date,
+            minutes: entry_dto.hours.minutes,
+            category: entry_dto.category.clone(),
+            note: entry_dto.note.clone(),
+            search_string: entry_dto.search_string.clone(),
+            version: entry_dto.version.unwrap_or_default(),
+        }
+    }
+}
+
+impl From<&TimesheetEntryDTO> for NewTimesheetEntry {
+    fn from(entry_dto: &TimesheetEntryDTO) -> Self {
+        NewTimesheetEntry {
+            employee_id: entry_dto.employee_id.unwrap(),
+            work_date: entry_dto.work_date,
+            minutes: entry_dto.hours.minutes,
+            category: entry_dto.category.clone(),
+            note: entry_dto.note.clone(),
+            search_string: entry_dto.search_string.clone(),
+        }
+    }
+}
+
+impl HaveId for TimesheetEntryDTO {
+    fn get_id(&self) -> Option<i32> {
+        self.id
+    }
+}
+
+impl HaveVersion for TimesheetEntryDTO {
+    fn get_version(&self) -> Option<i32> {
+        self.version
+    }
+}
+
+impl Crud for TimesheetEntryDTO {
+    fn update(&mut self, persisted: &Self) {
+        self.id = persisted.id;
+        self.version = persisted.version;
+    }
+
+    /// Hours have to be positive and at most 24 a day (with other entries of the day), entry can't be in
+    /// week which is already submitted or approved nor in day of approved leave
+    fn validate(&self, _rules: &ValidationRules, keys: Option<&KeyRing>, conn: &mut DbConnection) -> DaoResult<()> {
+        use crate::schema::absences::dsl as a;
+
+        let mut errors = Errors::default();
+        if self.hours.minutes <= 0 {
+            errors.add("", "hours", "has to be positive".to_string());
+        }
+        if self.category.trim().is_empty() {
+            errors.add("", "category", "can't be empty".to_string());
+        }
+        if let Some(e_id) = self.employee_id {
+            let mut dates = vec![self.work_date];
+            if let Some(self_id) = self.id
+                && let Some(persisted) = Self::get_simple(self_id, keys, conn).optional()?
+                && persisted.work_date != self.work_date
+            {
+                dates.push(persisted.work_date);
+            }
+            for date in dates {
+                if let Some(locked) = TimesheetWeekDTO::locked(e_id, date, conn)? {
+                    errors.add(
+                        "",
+                        "work_date",
+                        format!(
+                            "week starting {} is already {} - its entries can't be changed",
+                            locked.week_start,
+                            locked.status.as_str()
+                        ),
+                    );
+                }
+            }
+            let on_leave = a::absences
+                .filter(a::employee_id.eq(e_id))
+                .filter(a::status.eq(crate::AbsenceStatus::Approved.as_str()))
+                .filter(a::from_date.le(self.work_date))
+                .filter(a::to_date.ge(self.work_date))
+                .select(count_star())
+                .first::<i64>(conn)?;
+            if on_leave > 0 {
+                errors.add(
+                    "",
+                    "work_date",
+                    format!("employee is on approved leave on {}", self.work_date),
+                );
+            }
+            let others: Option<i64> = timesheet_entries
+                .filter(employee_id.eq(e_id))
+                .filter(work_date.eq(self.work_date))
+                .filter(entry_id.ne(self.id.unwrap_or_default()))
+                .select(sum(minutes))
+                .first(conn)?;
+            let day_total = others.unwrap_or_default() + self.hours.minutes as i64;
+            if day_total > MAX_MINUTES_PER_DAY as i64 {
+                errors.add(
+                    "",
+                    "hours",
+                    format!(
+                        "{} worked on {} - it is more than 24 hours",
+                        Hours::from_minutes(day_total as i32),
+                        self.work_date
+                    ),
+                );
+            }
+        }
+        errors.into_result()
+    }
+
+    fn get_simple(id_to_find: i32, _keys: Option<&KeyRing>, conn: &mut DbConnection) -> QueryResult<TimesheetEntryDTO> {
+        timesheet_entries
+            .filter(entry_id.eq(id_to_find))
+            .first(conn)
+            .map(|e: TimesheetEntry| TimesheetEntryDTO::from(e))
+    }
+
+    fn save_simple(&self, keys: Option<&KeyRing>, conn: &mut DbConnection) -> DaoResult<TimesheetEntryDTO> {
+        fn insert(e: &TimesheetEntryDTO, conn: &mut DbConnection) -> QueryResult<TimesheetEntryDTO> {
+            insert_into(timesheet_entries)
+                .values(NewTimesheetEntry::from(e))
+                .get_result(conn)
+                .map(|e: TimesheetEntry| TimesheetEntryDTO::from(e))
+        }
+        if let Some(self_id) = self.id {
+            let updated = match self.version {
+                Some(self_version) => diesel::update(
+                    timesheet_entries
+                        .filter(entry_id.eq(self_id))
+                        .filter(version.eq(self_version)),
+                )
+                .set((TimesheetEntry::from(self), version.eq(version + 1)))
+                .execute(conn)?,
+                None => 0,
+            };
+            if updated == 0 {
+                let current = timesheet_entries
+                    .filter(entry_id.eq(self_id))
+                    .select(version)
+                    .first::<i32>(conn)
+                    .optional()?;
+                match current {
+                    Some(current) => Err(stale_version(self_id, Some(current))),
+                    None => Ok(insert(self, conn)?),
+                }
+            } else {
+                Ok(Self::get_simple(self_id, keys, conn)?)
+            }
+        } else {
+            Ok(insert(self, conn)?)
+        }
+    }
+
+    fn delete_simple(id_to_find: i32, conn: &mut DbConnection) -> QueryResult<usize> {
+        diesel::delete(timesheet_entries.filter(entry_id.eq(id_to_find))).execute(conn)
+    }
+
+    fn delete_versioned_simple(
+        id_to_find: i32,
+        version_to_find: i32,
+        conn: &mut DbConnection,
+    ) -> QueryResult<usize> {
+        diesel::delete(
+            timesheet_entries
+                .filter(entry_id.eq(id_to_find))
+                .filter(version.eq(version_to_find)),
+        )
+        .execute(conn)
+    }
+}
+
+impl SearchableByParent for TimesheetEntryDTO {
+    fn search_by_parent_id_with_connection(
+        parent_id: i32,
+        _keys: Option<&KeyRing>,
+        conn: &mut DbConnection,
+    ) -> DaoResult<Vec<Self>> {
+        Ok(timesheet_entries
+            .filter(employee_id.eq(parent_id))
+            .order((work_date, entry_id))
+            .load::<TimesheetEntry>(conn)?
+            .into_iter()
+            .map(Self::from)
+            .collect())
+    }
+}
+
+impl TimesheetEntryDTO {
+    /// Entries of employee ordered by date - optionally just from `from` to `to` (both inclusive)
+    pub fn page_by_employee_with_connection(
+        e_id: i32,
+        from: Option<NaiveDate>,
+        to: Option<NaiveDate>,
+        page: PageRequest,
+        conn: &mut DbConnection,
+    ) -> QueryResult<Page<TimesheetEntryDTO>> {
+        let filtered = || {
+            let mut query = timesheet_entries.filter(employee_id.eq(e_id)).into_boxed();
+            if let Some(from) = from {
+                query = query.filter(work_date.ge(from));
+            }
+            if let Some(to) = to {
+                query = query.filter(work_date.le(to));
+            }
+            query
+        };
+        let total = filtered().select(count_star()).first::<i64>(conn)?;
+        let items = filtered()
+            .order((work_date, entry_id))
+            .offset(page.offset())
+            .limit(page.per_page)
+            .load::<TimesheetEntry>(conn)?
+            .into_iter()
+            .map(Self::from)
+            .collect();
+        Ok(Page::new(items, page, total))
+    }
+
+    /// Entry can be deleted just when its week is not submitted nor approved - DaoError::Validation otherwise
+    pub fn check_deletable(&self, conn: &mut DbConnection) -> DaoResult<()> {
+        let mut errors = Errors::default();
+        if let Some(e_id) = self.employee_id
+            && let Some(locked) = TimesheetWeekDTO::locked(e_id, self.work_date, conn)?
+        {
+            errors.add(
+                "",
+                "work_date",
+                format!(
+                    "week starting {} is already {} - its entries can't be deleted",
+                    locked.week_start,
+                    locked.status.as_str()
+                ),
+            );
+        }
+        errors.into_result()
+    }
+}
+
+/// Where week of timesheet is in submit/approve/reject workflow - weeks which were never submitted are drafts
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum WeekStatus {
+    /// Waiting for decision of manager or admin
+    Submitted,
+    Approved,
+    /// Entries can be changed and week submitted again
+    Rejected,
+}
+
+impl WeekStatus {
+    /// How it is stored in DB
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            WeekStatus::Submitted => "submitted",
+            WeekStatus::Approved => "approved",
+            WeekStatus::Rejected => "rejected",
+        }
+    }
+}
+
+impl FromStr for WeekStatus {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "submitted" => Ok(WeekStatus::Submitted),
+            "approved" => Ok(WeekStatus::Approved),
+            "rejected" => Ok(WeekStatus::Rejected),
+            _ => Err(format!(
+                "unknown week status '{}' - should be one of submitted, approved, rejected",
+                s
+            )),
+        }
+    }
+}
+
+/// Submitted week of timesheet of employee
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct TimesheetWeekDTO {
+    pub id: i32,
+    pub employee_id: i32,
+    /// Monday of the week
+    pub week_start: NaiveDate,
+    pub status: WeekStatus,
+    /// User who approved or rejected the week
+    pub decided_by: Option<i32>,
+    pub version: i32,
+}
+
+impl From<TimesheetWeek> for TimesheetWeekDTO {
+    fn from(w: TimesheetWeek) -> Self {
+        TimesheetWeekDTO {
+            id: w.id,
+            employee_id: w.employee_id,
+            week_start: w.week_start,
+            status: w.status.parse().expect("status is checked by DB"),
+            decided_by: w.decided_by,
+            version: w.version,
+        }
+    }
+}
+
+impl TimesheetWeekDTO {
+    /// Week of employee starting on Monday `start` - None when it was never submitted
+    pub fn get_with_connection(e_id: i32, start: NaiveDate, conn: &mut DbConnection) -> QueryResult<Option<Self>> {
+        use crate::schema::timesheet_weeks::dsl as w;
+
+        w::timesheet_weeks
+            .filter(w::employee_id.eq(e_id))
+            .filter(w::week_start.eq(start))
+            .first::<TimesheetWeek>(conn)
+            .optional()
+            .map(|w| w.map(Self::from))
+    }
+
+    /// Week of `date` when it is submitted or approved (so its entries can't be changed)
+    fn locked(e_id: i32, date: NaiveDate, conn: &mut DbConnection) -> QueryResult<Option<Self>> {
+        Ok(Self::get_with_connection(e_id, week_start(date), conn)?.filter(|w| w.status != WeekStatus::Rejected))
+    }
+
+    /// Submit week (again when it was rejected) for approval - `start` have to be Monday
+    pub fn submit_with_connection(e_id: i32, start: NaiveDate, conn: &mut DbConnection) -> DaoResult<Self> {
+        use crate::schema::timesheet_weeks::dsl as w;
+
+        conn.transaction(|conn| {
+            let mut errors = Errors::default();
+            if week_start(start) != start {
+                errors.add("", "week_start", format!("{} is not Monday", start));
+            }
+            let current = Self::get_with_connection(e_id, start, conn)?;
+            if let Some(current) = &current
+                && current.status != WeekStatus::Rejected
+            {
+                errors.add(
+                    "",
+                    "status",
+                    format!("week is already {}", current.status.as_str()),
+                );
+            }
+            errors.into_result()?;
+            match current {
+                Some(current) => {
+                    diesel::update(w::timesheet_weeks.filter(w::id.eq(current.id)))
+                        .set((
+                            w::status.eq(WeekStatus::Submitted.as_str()),
+                            w::decided_by.eq(None::<i32>),
+                            w::version.eq(w::version + 1),
+                        ))
+                        .execute(conn)?;
+                }
+                None => {
+                    insert_into(w::timesheet_weeks)
+                        .values((
+                            w::employee_id.eq(e_id),
+                            w::week_start.eq(start),
+                            w::status.eq(WeekStatus::Submitted.as_str()),
+                        ))
+                        .execute(conn)?;
+                }
+            }
+            Ok(Self::get_with_connection(e_id, start, conn)?.expect("week was just saved"))
+        })
+    }
+
+    /// Approve or reject submitted week - `user_id` is deciding user (see can_approve()) and `expected_version`
+    /// version of week the decision is based on
+    pub fn decide_with_connection(
+        e_id: i32,
+        start: NaiveDate,
+        expected_version: i32,
+        decision: WeekStatus,
+        user_id: i32,
+        conn: &mut DbConnection,
+    ) -> DaoResult<Self> {
+        use crate::schema::timesheet_weeks::dsl as w;
+
+        conn.transaction(|conn| {
+            let week = Self::get_with_connection(e_id, start, conn)?.ok_or_else(crate::DaoError::not_found)?;
+            if week.version != expected_version {
+                return Err(stale_version(week.id, Some(week.version)));
+            }
+            let mut errors = Errors::default();
+            if week.status != WeekStatus::Submitted {
+                errors.add("", "status", format!("week is already {}", week.status.as_str()));
+            }
+            if decision == WeekStatus::Submitted {
+                errors.add("", "status", "decision should be approved or rejected".to_string());
+            }
+            errors.into_result()?;
+            diesel::update(w::timesheet_weeks.filter(w::id.eq(week.id)))
+                .set((
+                    w::status.eq(decision.as_str()),
+                    w::decided_by.eq(Some(user_id)),
+                    w::version.eq(w::version + 1),
+                ))
+                .execute(conn)?;
+            Ok(Self::get_with_connection(e_id, start, conn)?.expect("week was just saved"))
+        })
+    }
+}
+
+/// Hours worked on category (project or kind of work)
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct CategoryHours {
+    pub category: String,
+    pub hours: Hours,
+}
+
+/// Worked hours of employee in a month
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct TimesheetSummary {
+    pub employee_id: i32,
+    pub first_name: String,
+    pub last_name: String,
+    pub year: i32,
+    pub month: u32,
+    /// All entries of the month
+    pub hours: Hours,
+    /// Entries of approved weeks
+    pub approved_hours: Hours,
+    /// All entries by category - ordered by category
+    pub categories: Vec<CategoryHours>,
+    /// Approved hours paid by hourly salary effective on the day - per currency (empty when employee has no
+    /// hourly salary)
+    pub pay: Vec<Money>,
+}
+
+/// Entry of timesheet with employee and status of its week
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct TimesheetRow {
+    pub entry_id: i32,
+    pub employee_id: i32,
+    pub first_name: String,
+    pub last_name: String,
+    pub work_date: NaiveDate,
+    pub hours: Hours,
+    pub category: String,
+    pub note: Option<String>,
+    /// None when the week was never submitted (draft)
+    pub status: Option<WeekStatus>,
+}
+
+/// Entries (with employee and status of its week) of employees in scope from `from` to `to` (both inclusive) -
+/// optionally just of one employee. Deleted employees are left out. Ordered by employee and date.
+pub fn timesheet_rows_with_connection(
+    from: NaiveDate,
+    to: NaiveDate,
+    employee: Option<i32>,
+    scope: EmployeeScope,
+    conn: &mut DbConnection,
+) -> DaoResult<Vec<TimesheetRow>> {
+    use crate::schema::employees::dsl as e;
+    use crate::schema::timesheet_weeks::dsl as w;
+
+    let mut errors = Errors::default();
+    if to < from {
+        errors.add("", "to", format!("can't be before from {}", from));
+    }
+    errors.into_result()?;
+    let mut query = timesheet_entries
+        .inner_join(e::employees)
+        .filter(work_date.ge(from))
+        .filter(work_date.le(to))
+        .filter(e::deleted_at.is_null())
+        .select((entry_id, e::id, e::first_name, e::last_name, work_date, minutes, category, note))
+        .order((e::last_name, e::first_name, e::id, work_date, entry_id))
+        .into_boxed();
+    if let Some(ids) = scope.employee_ids(conn)? {
+        query = query.filter(e::id.eq_any(ids));
+    }
+    if let Some(employee) = employee {
+        query = query.filter(e::id.eq(employee));
+    }
+    type Row = (i32, i32, String, String, NaiveDate, i32, String, Option<String>);
+    let rows = query.load::<Row>(conn)?;
+    let weeks: HashMap<(i32, NaiveDate), WeekStatus> = w::timesheet_weeks
+        .filter(w::week_start.ge(week_start(from)))
+        .filter(w::week_start.le(to))
+        .load::<TimesheetWeek>(conn)?
+        .into_iter()
+        .map(|w| {
+            let w = TimesheetWeekDTO::from(w);
+            ((w.employee_id, w.week_start), w.status)
+        })
+        .collect();
+    Ok(rows
+        .into_iter()
+        .map(|r| TimesheetRow {
+            entry_id: r.0,
+            employee_id: r.1,
+            first_name: r.2,
+            last_name: r.3,
+            work_date: r.4,
+            hours: Hours::from_minutes(r.5),
+            category: r.6,
+            note: r.7,
+            status: weeks.get(&(r.1, week_start(r.4))).copied(),
+        })
+        .collect())
+}
+
+pub fn timesheet_rows(
+    db: &Database,
+    from: NaiveDate,
+    to: NaiveDate,
+    employee: Option<i32>,
+    scope: EmployeeScope,
+) -> DaoResult<Vec<TimesheetRow>> {
+    let mut conn = db.try_get_connection()?;
+    timesheet_rows_with_connection(from, to, employee, scope, &mut conn)
+}
+
+/// Worked hours in `month` of `year` per employee in scope (just employees with entries in the month) -
+/// ordered by employee
+pub fn monthly_summary_with_connection(
+    year: i32,
+    month: u32,
+    scope: EmployeeScope,
+    keys: Option<&KeyRing>,
+    conn: &mut DbConnection,
+) -> DaoResult<Vec<TimesheetSummary>> {
+    use crate::schema::salaries::dsl as s;
+
+    let mut errors = Errors::default();
+    let first = NaiveDate::from_ymd_opt(year, month, 1);
+    if first.is_none() {
+        errors.add("", "month", format!("{}-{} is not valid month", year, month));
+    }
+    errors.into_result()?;
+    let first = first.unwrap();
+    let last = first + Months::new(1) - Days::new(1);
+    let rows = timesheet_rows_with_connection(first, last, None, scope, conn)?;
+    let employee_ids: Vec<i32> = rows.iter().map(|r| r.employee_id).collect();
+    let hourly: Vec<(i32, NaiveDate, Option<NaiveDate>, i64, String)> = s::salaries
+        .filter(s::employee_id.eq_any(&employee_ids))
+        .filter(s::pay_period.eq(PayPeriod::Hourly.as_str()))
+        .filter(s::from_date.le(last))
+        .filter(s::to_date.is_null().or(s::to_date.ge(first)))
+        .select((s::id, s::employee_id, s::from_date, s::to_date, s::amount, s::amount_encrypted, s::currency))
+        .load::<(i32, i32, NaiveDate, Option<NaiveDate>, i64, Option<String>, String)>(conn)?
+        .into_iter()
+        .map(|(s_id, e_id, from, to, plain, encrypted, currency)| {
+            Ok((e_id, from, to, amount_of(s_id, plain, encrypted.as_deref(), keys)?, currency))
+        })
+        .collect::<QueryResult<_>>()?;
+
+    let mut summaries: Vec<TimesheetSummary> = vec![];
+    for row in &rows {
+        if summaries.last().is_none_or(|s| s.employee_id != row.employee_id) {
+            summaries.push(TimesheetSummary {
+                employee_id: row.employee_id,
+                first_name: row.first_name.clone(),
+                last_name: row.last_name.clone(),
+                year,
+                month,
+                hours: Hours::default(),
+                approved_hours: Hours::default(),
+                categories: vec![],
+                pay: vec![],
+            });
+        }
+        let summary = summaries.last_mut().unwrap();
+        summary.hours = summary.hours + row.hours;
+        match summary.categories.iter_mut().find(|c| c.category == row.category) {
+            Some(c) => c.hours = c.hours + row.hours,
+            None => summary.categories.push(CategoryHours {
+                category: row.category.clone(),
+                hours: row.hours,
+            }),
+        }
+        if row.status == Some(WeekStatus::Approved) {
+            summary.approved_hours = summary.approved_hours + row.hours;
+        }
+    }
+    // Pay is summed in minor units times minutes and rounded once per currency
+    let mut pay: HashMap<i32, BTreeMap<&str, (Currency, i64)>> = HashMap::new();
+    for row in rows.iter().filter(|r| r.status == Some(WeekStatus::Approved)) {
+        let salary = hourly.iter().find(|(e_id, from, to, _, _)| {
+            *e_id == row.employee_id && *from <= row.work_date && to.is_none_or(|to| to >= row.work_date)
+        });
+        if let Some((_, _, _, amount, currency)) = salary {
+            let currency: Currency = currency.parse().expect("currency is checked by DB");
+            pay.entry(row.employee_id)
+                .or_default()
+                .entry(currency.code())
+                .or_insert((currency, 0))
+                .1 += amount * row.hours.minutes as i64;
+        }
+    }
+    for summary in summaries.iter_mut() {
+        summary.categories.sort_by(|a, b| a.category.cmp(&b.category));
+        if let Some(pay) = pay.get(&summary.employee_id) {
+            summary.pay = pay
+                .values()
+                .map(|(currency, minor_minutes)| Money::new((minor_minutes + 30) / 60, *currency))
+                .collect();
+        }
+    }
+    Ok(summaries)
+}
+
+pub fn monthly_summary(db: &Database, year: i32, month: u32, scope: EmployeeScope) -> DaoResult<Vec<TimesheetSummary>> {
+    let mut conn = db.try_get_connection()?;
+    monthly_summary_with_connection(year, month, scope, db.keys().as_deref(), &mut conn)
+}
+
+/// Timesheet of deleted employee is deleted with it
+pub(crate) fn delete_timesheet_of(e_id: i32, conn: &mut DbConnection) -> QueryResult<usize> {
+    use crate::schema::timesheet_weeks::dsl as w;
+
+    diesel::delete(w::timesheet_weeks.filter(w::employee_id.eq(e_id))).execute(conn)?;
+    diesel::delete(timesheet_entries.filter(employee_id.eq(e_id))).execute(conn)
+}
+
+/// Notes of timesheet entries of anonymized employee are dropped - minutes are kept for summaries
+pub(crate) fn anonymize_timesheet_of(e_id: i32, conn: &mut DbConnection) -> QueryResult<usize> {
+    diesel::update(timesheet_entries.filter(employee_id.eq(e_id)))
+        .set((note.eq(None::<String>), search_string.eq(""), version.eq(version + 1)))
+        .execute(conn)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::common_for_tests::*;
+    use crate::error::DaoError;
+    use crate::{AbsenceDTO, AbsenceStatus, EmployeeDTO, SalaryDTO};
+
+    use super::*;
+
+    impl CrudTests for TimesheetEntryDTO {}
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    fn save_employee(hourly_rate: Option<i64>, conn: &mut DbConnection) -> i32 {
+        EmployeeDTO {
+            id: None,
+            first_name: "Jan".to_string(),
+            last_name: "Kowalski".to_string(),
+            search_string: "".to_string(),
+            salaries: hourly_rate
+                .map(|rate| SalaryDTO {
+                    id: None,
+                    employee_id: None,
+                    from_date: date(2021, 1, 1),
+                    to_date: None,
+                    amount: Money::new(rate, Currency::PLN),
+                    pay_period: PayPeriod::Hourly,
+                    gross: true,
+                    search_string: "".to_string(),
+                    version: None,
+                    contract_id: None,
+                })
+                .into_iter()
+                .collect(),
+            contacts: vec![],
+            version: None,
+            department_id: None,
+            manager_id: None,
+            contracts: vec![],
+            ..Default::default()
+        }
+        .save_in_transaction(keys(), conn)
+        .unwrap()
+        .id
+        .unwrap()
+    }
+
+    fn entry(e_id: i32, day: NaiveDate, hours: &str) -> TimesheetEntryDTO {
+        TimesheetEntryDTO {
+            id: None,
+            employee_id: Some(e_id),
+            work_date: day,
+            hours: Hours::parse(hours).unwrap(),
+            category: "Project X".to_string(),
+            note: None,
+            search_string: "".to_string(),
+            version: None,
+        }
+    }
+
+    fn validation_error(e: &TimesheetEntryDTO, conn: &mut DbConnection) -> String {
+        match e.try_save_in_transaction(&Default::default(), keys(), conn) {
+            Err(DaoError::Validation(errors)) => errors[0].field.clone(),
+            result => panic!("Should report validation error and instead I got {:?}", result),
+        }
+    }
+
+    #[test]
+    fn hours_are_parsed_and_formatted() {
+        assert_eq!(Hours::parse("7:30"), Ok(Hours::from_minutes(450)));
+        assert_eq!(Hours::parse("7.5"), Ok(Hours::from_minutes(450)));
+        assert_eq!(Hours::parse("8"), Ok(Hours::from_minutes(480)));
+        assert_eq!(Hours::parse("0.25"), Ok(Hours::from_minutes(15)));
+        for invalid in ["", "7.33", "7:60", "7:5", "-1", "a:30", "7."] {
+            assert!(Hours::parse(invalid).is_err(), "'{}' should be invalid", invalid);
+        }
+        assert_eq!(Hours::from_minutes(450).to_string(), "7:30");
+        assert_eq!(Hours::from_minutes(20).decimal(), "0.33");
+        assert_eq!(serde_json::to_string(&Hours::from_minutes(65)).unwrap(), "\"1:05\"");
+        assert_eq!(week_start(date(2021, 3, 7)), date(2021, 3, 1));
+        assert_eq!(week_start(date(2021, 3, 1)), date(2021, 3, 1));
+    }
+
+    #[test]
+    fn crud_operations_on_timesheet_entry() {
+        let conn = &mut initialize();
+        let e_id = save_employee(None, conn);
+        entry(e_id, date(2021, 3, 1), "8:00").test(conn);
+    }
+
+    #[test]
+    fn timesheet_rules_are_checked() {
+        let conn = &mut initialize();
+        let e_id = save_employee(None, conn);
+        let monday = date(2021, 3, 1);
+
+        let first = entry(e_id, monday, "16:00")
+            .try_save_in_transaction(&Default::default(), keys(), conn)
+            .unwrap();
+        assert_eq!(validation_error(&entry(e_id, monday, "8:01"), conn), "hours");
+        assert_eq!(validation_error(&entry(e_id, monday, "0"), conn), "hours");
+        // Entry itself is not counted twice when it is updated
+        assert!(TimesheetEntryDTO {
+            hours: Hours::parse("24").unwrap(),
+            ..first.clone()
+        }
+        .try_save_in_transaction(&Default::default(), keys(), conn)
+        .is_ok());
+
+        let leave = AbsenceDTO {
+            id: None,
+            employee_id: Some(e_id),
+            absence_type_id: 2,
+            from_date: date(2021, 3, 3),
+            to_date: date(2021, 3, 3),
+            days: 0,
+            status: AbsenceStatus::Requested,
+            decided_by: None,
+            comment: None,
+            search_string: "".to_string(),
+            version: None,
+        }
+        .try_save_in_transaction(&Default::default(), keys(), conn)
+        .unwrap();
+        assert!(entry(e_id, date(2021, 3, 3), "1")
+            .try_save_in_transaction(&Default::default(), keys(), conn)
+            .is_ok());
+        AbsenceDTO::decide_with_connection(leave.id.unwrap(), 1, AbsenceStatus::Approved, 2, conn).unwrap();
+        assert_eq!(validation_error(&entry(e_id, date(2021, 3, 3), "1"), conn), "work_date");
+
+        // Entries of submitted week are locked - until the week is rejected
+        let submitted = TimesheetWeekDTO::submit_with_connection(e_id, monday, conn).unwrap();
+        assert_eq!(submitted.status, WeekStatus::Submitted);
+        assert_eq!(validation_error(&entry(e_id, date(2021, 3, 2), "1"), conn), "work_date");
+        assert!(first.check_deletable(conn).is_err());
+        match TimesheetWeekDTO::submit_with_connection(e_id, date(2021, 3, 2), conn) {
+            Err(DaoError::Validation(errors)) => {
+                let fields: Vec<&str> = errors.iter().map(|e| e.field.as_str()).collect();
+                assert_eq!(fields, vec!["week_start"]);
+            }
+            result => panic!("Should report validation error and instead I got {:?}", result),
+        }
+        assert!(TimesheetWeekDTO::decide_with_connection(e_id, monday, 5, WeekStatus::Rejected, 2, conn)
+            .unwrap_err()
+            .is_stale_version());
+        let rejected = TimesheetWeekDTO::decide_with_connection(e_id, monday, 1, WeekStatus::Rejected, 2, conn).unwrap();
+        assert_eq!(rejected.decided_by, Some(2));
+        assert!(first.check_deletable(conn).is_ok());
+        let resubmitted = TimesheetWeekDTO::submit_with_connection(e_id, monday, conn).unwrap();
+        assert_eq!((resubmitted.status, resubmitted.decided_by, resubmitted.version), (WeekStatus::Submitted, None, 3));
+    }
+
+    #[test]
+    fn monthly_summary_counts_hours_and_hourly_pay() {
+        let conn = &mut initialize();
+        // 50.00 PLN per hour
+        let e_id = save_employee(Some(5000), conn);
+        let other = save_employee(None, conn);
+        for (e, day, hours, kind) in [
+            (e_id, date(2021, 3, 1), "8", "Project X"),
+            (e_id, date(2021, 3, 2), "7:30", "Support"),
+            (e_id, date(2021, 3, 2), "0:20", "Project X"),
+            (e_id, date(2021, 3, 8), "2", "Project X"),
+            (e_id, date(2021, 4, 1), "8", "Project X"),
+            (other, date(2021, 3, 1), "4", "Support"),
+        ] {
+            TimesheetEntryDTO {
+                category: kind.to_string(),
+                ..entry(e, day, hours)
+            }
+            .try_save_in_transaction(&Default::default(), keys(), conn)
+            .unwrap();
+        }
+        TimesheetWeekDTO::submit_with_connection(e_id, date(2021, 3, 1), conn).unwrap();
+        TimesheetWeekDTO::decide_with_connection(e_id, date(2021, 3, 1), 1, WeekStatus::Approved, 2, conn).unwrap();
+        TimesheetWeekDTO::submit_with_connection(e_id, date(2021, 3, 8), conn).unwrap();
+
+        let summaries = monthly_summary_with_connection(2021, 3, EmployeeScope::All, keys(), conn).unwrap();
+        assert_eq!(summaries.len(), 2);
+        let summary = summaries.iter().find(|s| s.employee_id == e_id).unwrap();
+        assert_eq!(summary.hours, Hours::parse("17:50").unwrap());
+        assert_eq!(summary.approved_hours, Hours::parse("15:50").unwrap());
+        assert_eq!(
+            summary.categories,
+            vec![
+                CategoryHours {
+                    category: "Project X".to_string(),
+                    hours: Hours::parse("10:20").unwrap()
+                },
+                CategoryHours {
+                    category: "Support".to_string(),
+                    hours: Hours::parse("7:30").unwrap()
+                }
+            ]
+        );
+        // 15:50 * 50.00 = 791.67 (rounded)
+        assert_eq!(summary.pay, vec![Money::new(79167, Currency::PLN)]);
+        let other_summary = summaries.iter().find(|s| s.employee_id == other).unwrap();
+        assert!(other_summary.pay.is_empty());
+
+        let just_other = monthly_summary_with_connection(2021, 3, EmployeeScope::Subtree(other), keys(), conn).unwrap();
+        assert_eq!(just_other.len(), 1);
+        assert!(monthly_summary_with_connection(2021, 13, EmployeeScope::All, keys(), conn)
+            .unwrap_err()
+            .is_validation());
+
+        let page = TimesheetEntryDTO::page_by_employee_with_connection(
+            e_id,
+            Some(date(2021, 3, 1)),
+            None,
+            PageRequest::new(Some(2), Some(2)),
+            conn,
+        )
+        .unwrap();
+        assert_eq!(page.total, 5);
+        let days: Vec<NaiveDate> = page.items.iter().map(|e| e.work_date).collect();
+        assert_eq!(days, vec![date(2021, 3, 2), date(2021, 3, 8)]);
+    }
+}
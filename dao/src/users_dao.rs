@@ -1,37 +1,92 @@
 use diesel::dsl::*;
 use diesel::prelude::*;
-use diesel::sqlite::SqliteConnection;
 
+use crate::base_dao::stale_version;
+use crate::connection::DbConnection;
+use crate::error::{DaoError, DaoResult};
 use crate::models::{NewUser, User};
 use crate::schema::users::dsl::*;
 
-pub fn create_user(new_user: &NewUser, conn: &mut SqliteConnection) -> QueryResult<User> {
+pub fn create_user(new_user: &NewUser, conn: &mut DbConnection) -> QueryResult<User> {
     conn.transaction(|conn| {
         insert_into(users)
             .values(new_user)
-            .execute(conn)
-            .and_then(|_| users.order(id.desc()).first(conn))
+            .get_result(conn)
     })
 }
 
-pub fn update_user(user: &User, conn: &mut SqliteConnection) -> QueryResult<User> {
+/// Update user only when it is in the same version as `user` - DaoError::StaleVersion otherwise
+pub fn update_user(user: &User, conn: &mut DbConnection) -> DaoResult<User> {
     conn.transaction(|conn| {
-        diesel::update(users.filter(id.eq(user.id)))
-            .set(user)
-            .execute(conn)
-            .and_then(|_| users.filter(id.eq(user.id)).first(conn))
+        let updated = diesel::update(users.filter(id.eq(user.id)).filter(version.eq(user.version)))
+            .set((user, version.eq(version + 1)))
+            .execute(conn)?;
+        if updated == 0 {
+            return Err(stale_or_not_found(user.id, conn));
+        }
+        Ok(users.filter(id.eq(user.id)).first(conn)?)
     })
 }
 
-pub fn delete_user(user: &User, conn: &mut SqliteConnection) -> QueryResult<usize> {
-    conn.transaction(|conn| diesel::delete(users.filter(id.eq(user.id))).execute(conn))
+/// Delete user only when it is in the same version as `user` - DaoError::StaleVersion otherwise.
+/// Absences and timesheet weeks decided by the user (and employees deleted, documents and photos uploaded by it) are
+/// kept (without the user).
+pub fn delete_user(user: &User, conn: &mut DbConnection) -> DaoResult<usize> {
+    use crate::schema::absences::dsl as a;
+    use crate::schema::audit_log::dsl as l;
+    use crate::schema::documents::dsl as d;
+    use crate::schema::employee_photos::dsl as ep;
+    use crate::schema::employees::dsl as e;
+    use crate::schema::payroll_runs::dsl as r;
+    use crate::schema::timesheet_weeks::dsl as w;
+
+    conn.transaction(|conn| {
+        diesel::update(a::absences.filter(a::decided_by.eq(user.id)))
+            .set((a::decided_by.eq(None::<i32>), a::version.eq(a::version + 1)))
+            .execute(conn)?;
+        diesel::update(w::timesheet_weeks.filter(w::decided_by.eq(user.id)))
+            .set((w::decided_by.eq(None::<i32>), w::version.eq(w::version + 1)))
+            .execute(conn)?;
+        diesel::update(r::payroll_runs.filter(r::approved_by.eq(user.id)))
+            .set((r::approved_by.eq(None::<i32>), r::version.eq(r::version + 1)))
+            .execute(conn)?;
+        diesel::update(e::employees.filter(e::deleted_by.eq(user.id)))
+            .set(e::deleted_by.eq(None::<i32>))
+            .execute(conn)?;
+        diesel::update(d::documents.filter(d::uploaded_by.eq(user.id)))
+            .set(d::uploaded_by.eq(None::<i32>))
+            .execute(conn)?;
+        diesel::update(ep::employee_photos.filter(ep::uploaded_by.eq(user.id)))
+            .set(ep::uploaded_by.eq(None::<i32>))
+            .execute(conn)?;
+        diesel::update(l::audit_log.filter(l::user_id.eq(user.id)))
+            .set(l::user_id.eq(None::<i32>))
+            .execute(conn)?;
+        let deleted = diesel::delete(users.filter(id.eq(user.id)).filter(version.eq(user.version)))
+            .execute(conn)?;
+        if deleted == 0 {
+            return Err(stale_or_not_found(user.id, conn));
+        }
+        Ok(deleted)
+    })
+}
+
+fn stale_or_not_found(id_to_find: i32, conn: &mut DbConnection) -> DaoError {
+    match users
+        .filter(id.eq(id_to_find))
+        .select(version)
+        .first::<i32>(conn)
+    {
+        Ok(current) => stale_version(id_to_find, Some(current)),
+        Err(e) => DaoError::Query(e),
+    }
 }
 
-pub fn get_users(conn: &mut SqliteConnection) -> Vec<User> {
+pub fn get_users(conn: &mut DbConnection) -> Vec<User> {
     users.load::<User>(conn).expect("Load users failed")
 }
 
-pub fn get_user(id_to_find: i32, conn: &mut SqliteConnection) -> Option<User> {
+pub fn get_user(id_to_find: i32, conn: &mut DbConnection) -> Option<User> {
     users
         .filter(id.eq(id_to_find))
         .first(conn)
@@ -39,17 +94,22 @@ pub fn get_user(id_to_find: i32, conn: &mut SqliteConnection) -> Option<User> {
         .unwrap_or(None)
 }
 
+/// User with given name and password - users linked to deleted employee can't log in
 pub fn validate_user(
     username_p: &String,
     password_p: &String,
-    conn: &mut SqliteConnection,
+    conn: &mut DbConnection,
 ) -> Option<User> {
     info!(
         "Validate user '{}' with password '{}'",
         username_p, password_p
     );
+    use crate::schema::employees::dsl as e;
+
+    let not_deleted = e::employees.filter(e::deleted_at.is_null()).select(e::id.nullable());
     users
         .filter(username.eq(username_p).and(password.eq(password_p)))
+        .filter(employee_id.is_null().or(employee_id.eq_any(not_deleted)))
         .first(conn)
         .optional()
         .unwrap_or(None)
@@ -80,6 +140,8 @@ mod tests {
             username: test_user.to_string(),
             password: test_pass.to_string(),
             is_admin: true,
+            employee_id: None,
+            privacy_officer: false,
         };
         let rows_inserted = insert_into(users).values(&new_user).execute(conn);
         assert_eq!(Ok(1), rows_inserted);
@@ -165,11 +227,19 @@ mod tests {
             username: "admin".to_string(),
             password: "not_important".to_string(),
             is_admin: true,
+            employee_id: None,
+            privacy_officer: false,
         };
         let rows_inserted = insert_into(users).values(&new_user).execute(conn);
         match rows_inserted {
             Err(DatabaseError(UniqueViolation, msg)) => {
-                assert_eq!(msg.message(), "UNIQUE constraint failed: users.username")
+                #[cfg(feature = "sqlite")]
+                assert_eq!(msg.message(), "UNIQUE constraint failed: users.username");
+                #[cfg(feature = "postgres")]
+                assert_eq!(
+                    msg.message(),
+                    "duplicate key value violates unique constraint \"unique_user_username_idx\""
+                );
             }
             _ => panic!(
                 "Should report: UNIQUE constraint failed: users.username and instead I got {:?}",
@@ -212,6 +282,8 @@ mod tests {
             username: "new_username".to_string(),
             password: "new_password".to_string(),
             is_admin: false,
+            employee_id: None,
+            privacy_officer: false,
         };
         let created_user = create_user(&new_user, conn).unwrap();
         assert_eq!(3, created_user.id);
@@ -231,6 +303,31 @@ mod tests {
         assert!(admin_in_db.is_none());
     }
 
+    #[test]
+    fn stale_user_should_not_be_updated_nor_deleted() {
+        let conn = &mut initialize();
+
+        let mut admin_in_db = get_user(2, conn).unwrap();
+        assert_eq!(1, admin_in_db.version);
+        admin_in_db.password = "new_password".to_string();
+        let updated_user = update_user(&admin_in_db, conn).unwrap();
+        assert_eq!(2, updated_user.version);
+
+        // admin_in_db still have version 1
+        admin_in_db.password = "lost_update".to_string();
+        match update_user(&admin_in_db, conn) {
+            Err(DaoError::StaleVersion { id: 2, actual: 2 }) => {}
+            result => panic!("Should report stale version and instead I got {:?}", result),
+        }
+        assert!(delete_user(&admin_in_db, conn)
+            .unwrap_err()
+            .is_stale_version());
+        assert_eq!("new_password", get_user(2, conn).unwrap().password);
+
+        assert_eq!(1, delete_user(&updated_user, conn).unwrap());
+        assert!(delete_user(&updated_user, conn).unwrap_err().is_not_found());
+    }
+
     fn hash(text: &String) -> String {
         format!("{:x}", Sha3_256::digest(text.as_bytes()))
     }
@@ -0,0 +1,222 @@
+use std::fmt;
+
+use chrono::NaiveDate;
+
+use crate::base_dao::HaveId;
+use crate::country::Country;
+use crate::employee_number::EmployeeNumberFormat;
+use crate::error::{DaoError, DaoResult};
+use crate::money::Money;
+
+/// Business rules which can be configured (see DbConfig)
+#[derive(Clone, Debug)]
+pub struct ValidationRules {
+    /// Employee can have more contacts valid in the same time (CONTACTS_ALLOW_OVERLAP)
+    pub allow_overlapping_contacts: bool,
+    /// Country of phone numbers given without international prefix (DEFAULT_COUNTRY)
+    pub default_country: Country,
+    /// Format of generated employee numbers (EMPLOYEE_NUMBER_FORMAT)
+    pub employee_number_format: EmployeeNumberFormat,
+}
+
+impl Default for ValidationRules {
+    fn default() -> Self {
+        ValidationRules {
+            allow_overlapping_contacts: false,
+            default_country: Country::PL,
+            employee_number_format: Default::default(),
+        }
+    }
+}
+
+/// What is wrong with which field of saved record - `field` is path like `salaries[1].to_date`
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct FieldError {
+    pub field: String,
+    pub message: String,
+}
+
+impl fmt::Display for FieldError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.field, self.message)
+    }
+}
+
+/// Collect field errors of one record - `prefix` is path of the record in validated DTO
+#[derive(Default)]
+pub struct Errors {
+    errors: Vec<FieldError>,
+}
+
+impl Errors {
+    pub fn add(&mut self, prefix: &str, field: &str, message: String) {
+        self.errors.push(FieldError {
+            field: format!("{}{}", prefix, field),
+            message,
+        });
+    }
+
+    /// DaoError::Validation when there is at least one error
+    pub fn into_result(self) -> DaoResult<()> {
+        if self.errors.is_empty() {
+            Ok(())
+        } else {
+            Err(DaoError::Validation(self.errors))
+        }
+    }
+}
+
+/// Period from `from` to `to` (both inclusive) - `to` is None for open-ended period
+pub trait HavePeriod {
+    fn period_start(&self) -> NaiveDate;
+    fn period_end(&self) -> Option<NaiveDate>;
+
+    fn overlaps<P: HavePeriod>(&self, other: &P) -> bool {
+        self.period_end().is_none_or(|to| other.period_start() <= to)
+            && other.period_end().is_none_or(|to| self.period_start() <= to)
+    }
+
+    /// Whole `other` period is within this one
+    fn covers<P: HavePeriod>(&self, other: &P) -> bool {
+        self.period_start() <= other.period_start()
+            && self
+                .period_end()
+                .is_none_or(|to| other.period_end().is_some_and(|other_to| other_to <= to))
+    }
+
+    fn describe_period(&self) -> String {
+        match self.period_end() {
+            Some(to) => format!("{} - {}", self.period_start(), to),
+            None => format!("{} - (open-ended)", self.period_start()),
+        }
+    }
+}
+
+/// Period have to start before it ends
+pub fn check_period<P: HavePeriod>(p: &P, prefix: &str, errors: &mut Errors) {
+    if let Some(to) = p.period_end()
+        && to < p.period_start()
+    {
+        errors.add(
+            prefix,
+            "to_date",
+            format!("can't be before from_date {}", p.period_start()),
+        );
+    }
+}
+
+/// Amount of money can't be negative
+pub fn check_amount(amount: &Money, prefix: &str, errors: &mut Errors) {
+    if amount.minor_units < 0 {
+        errors.add(prefix, "amount", format!("can't be negative ({})", amount));
+    }
+}
+
+/// Periods in `periods` can't overlap - every period overlapping earlier one is reported.
+/// `what` and `path` name the list in messages and field paths (e.g. "salary", "salaries").
+pub fn check_no_overlaps<P: HavePeriod>(periods: &[P], what: &str, path: &str, errors: &mut Errors) {
+    for (i, p) in periods.iter().enumerate() {
+        if let Some(j) = periods[..i].iter().position(|earlier| earlier.overlaps(p)) {
+            errors.add(
+                &format!("{}[{}].", path, i),
+                "from_date",
+                format!(
+                    "{} {} overlaps with {}[{}] {}",
+                    what,
+                    p.describe_period(),
+                    path,
+                    j,
+                    periods[j].describe_period()
+                ),
+            );
+        }
+    }
+}
+
+/// Period of record can't overlap with periods of `existing` (already saved) records
+pub fn check_no_overlap_with_existing<P>(p: &P, existing: &[P], what: &str, errors: &mut Errors)
+where
+    P: HavePeriod + HaveId,
+{
+    for e in existing
+        .iter()
+        .filter(|e| e.get_id() != p.get_id() && e.overlaps(p))
+    {
+        errors.add(
+            "",
+            "from_date",
+            format!(
+                "{} {} overlaps with {} id = {} {}",
+                what,
+                p.describe_period(),
+                what,
+                e.get_id().unwrap_or_default(),
+                e.describe_period()
+            ),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Period(NaiveDate, Option<NaiveDate>);
+
+    impl HavePeriod for Period {
+        fn period_start(&self) -> NaiveDate {
+            self.0
+        }
+        fn period_end(&self) -> Option<NaiveDate> {
+            self.1
+        }
+    }
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    #[test]
+    fn overlapping_periods() {
+        let closed = Period(date(2020, 1, 1), Some(date(2020, 12, 31)));
+        let next = Period(date(2021, 1, 1), None);
+        let last_day = Period(date(2020, 12, 31), None);
+        let before = Period(date(2019, 1, 1), Some(date(2019, 12, 31)));
+        let open = Period(date(2018, 1, 1), None);
+
+        assert!(!closed.overlaps(&next));
+        assert!(!next.overlaps(&closed));
+        assert!(closed.overlaps(&last_day));
+        assert!(next.overlaps(&last_day));
+        assert!(!closed.overlaps(&before));
+        assert!(open.overlaps(&closed));
+        assert!(open.overlaps(&next));
+
+        assert!(open.covers(&closed));
+        assert!(open.covers(&next));
+        assert!(!closed.covers(&last_day));
+        assert!(!next.covers(&open));
+        assert!(closed.covers(&Period(date(2020, 3, 1), Some(date(2020, 12, 31)))));
+    }
+
+    #[test]
+    fn errors_are_reported_per_field() {
+        let periods = vec![
+            Period(date(2020, 1, 1), Some(date(2020, 12, 31))),
+            Period(date(2021, 1, 1), Some(date(2020, 1, 1))),
+            Period(date(2020, 6, 1), None),
+        ];
+        let mut errors = Errors::default();
+        for (i, p) in periods.iter().enumerate() {
+            check_period(p, &format!("salaries[{}].", i), &mut errors);
+        }
+        check_no_overlaps(&periods, "salary", "salaries", &mut errors);
+        match errors.into_result() {
+            Err(DaoError::Validation(errors)) => {
+                let fields: Vec<&str> = errors.iter().map(|e| e.field.as_str()).collect();
+                assert_eq!(fields, vec!["salaries[1].to_date", "salaries[2].from_date"]);
+            }
+            result => panic!("Should report validation errors and instead I got {:?}", result),
+        }
+    }
+}
@@ -1,4 +1,9 @@
 #!/bin/bash
 
 export RUST_BACKTRACE=1
-cargo test -- --nocapture --test-threads 1
+cargo test -- --nocapture
+
+# PostgreSQL tests run only when TEST_DATABASE_URL point to disposable database
+if [ -n "$TEST_DATABASE_URL" ]; then
+  cargo test --no-default-features --features postgres -- --nocapture
+fi
@@ -0,0 +1,3 @@
+insert into employees(id, first_name, last_name, search_string)
+values (1, 'Jan', 'Kowalski', 'JanKowalski');
+select setval(pg_get_serial_sequence('employees', 'id'), (select max(id) from employees));
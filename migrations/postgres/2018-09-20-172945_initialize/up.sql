@@ -0,0 +1,8 @@
+-- Your SQL goes here
+CREATE TABLE users
+(
+    id       SERIAL PRIMARY KEY NOT NULL,
+    username TEXT               NOT NULL,
+    password TEXT               NOT NULL,
+    is_admin BOOLEAN            NOT NULL DEFAULT FALSE
+)
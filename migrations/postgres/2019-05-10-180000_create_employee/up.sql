@@ -0,0 +1,8 @@
+-- Your SQL goes here
+CREATE TABLE employees
+(
+    id            SERIAL PRIMARY KEY NOT NULL,
+    first_name    TEXT               NOT NULL,
+    last_name     TEXT               NOT NULL,
+    search_string TEXT               NOT NULL
+)
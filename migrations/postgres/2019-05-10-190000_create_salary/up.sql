@@ -0,0 +1,11 @@
+-- Your SQL goes here
+CREATE TABLE salaries
+(
+     id            SERIAL PRIMARY KEY NOT NULL
+    ,employee_id   INTEGER            NOT NULL
+    ,from_date     DATE               NOT NULL
+    ,to_date       DATE               NOT NULL
+    ,amount        BIGINT             NOT NULL
+    ,search_string TEXT               NOT NULL
+    ,FOREIGN KEY (employee_id) REFERENCES employees (id)
+);
@@ -0,0 +1,5 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE contacts DROP COLUMN version;
+ALTER TABLE salaries DROP COLUMN version;
+ALTER TABLE employees DROP COLUMN version;
+ALTER TABLE users DROP COLUMN version;
@@ -0,0 +1,5 @@
+-- Row version for optimistic concurrency control - incremented on every update
+ALTER TABLE users ADD COLUMN version INTEGER NOT NULL DEFAULT 1;
+ALTER TABLE employees ADD COLUMN version INTEGER NOT NULL DEFAULT 1;
+ALTER TABLE salaries ADD COLUMN version INTEGER NOT NULL DEFAULT 1;
+ALTER TABLE contacts ADD COLUMN version INTEGER NOT NULL DEFAULT 1;
@@ -0,0 +1,7 @@
+-- This file should undo anything in `up.sql` - open-ended periods are closed at the end of time
+ALTER TABLE contacts DROP CONSTRAINT contacts_period_check;
+UPDATE contacts SET to_date = '9999-12-31' WHERE to_date IS NULL;
+ALTER TABLE contacts ALTER COLUMN to_date SET NOT NULL;
+ALTER TABLE salaries DROP CONSTRAINT salaries_period_check;
+UPDATE salaries SET to_date = '9999-12-31' WHERE to_date IS NULL;
+ALTER TABLE salaries ALTER COLUMN to_date SET NOT NULL;
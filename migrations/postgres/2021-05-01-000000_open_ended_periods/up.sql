@@ -0,0 +1,5 @@
+-- Open-ended periods: to_date is NULL when period has no end yet
+ALTER TABLE salaries ALTER COLUMN to_date DROP NOT NULL;
+ALTER TABLE salaries ADD CONSTRAINT salaries_period_check CHECK (to_date IS NULL OR from_date <= to_date);
+ALTER TABLE contacts ALTER COLUMN to_date DROP NOT NULL;
+ALTER TABLE contacts ADD CONSTRAINT contacts_period_check CHECK (to_date IS NULL OR from_date <= to_date);
@@ -0,0 +1,6 @@
+-- Salary amount is integer number of minor units (grosz, cent) of currency, paid per pay_period, gross or net.
+-- Existing rows get empty currency - on startup they are converted (amount * minor units) to DEFAULT_CURRENCY.
+ALTER TABLE salaries ADD COLUMN currency TEXT NOT NULL DEFAULT '';
+ALTER TABLE salaries ADD COLUMN pay_period TEXT NOT NULL DEFAULT 'monthly'
+    CHECK (pay_period IN ('hourly', 'monthly', 'yearly'));
+ALTER TABLE salaries ADD COLUMN gross BOOLEAN NOT NULL DEFAULT TRUE;
@@ -0,0 +1,8 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE users DROP COLUMN employee_id;
+DROP INDEX employees_manager_id;
+DROP INDEX employees_department_id;
+ALTER TABLE employees DROP COLUMN manager_id;
+ALTER TABLE employees DROP COLUMN department_id;
+DROP INDEX departments_parent_id;
+DROP TABLE departments;
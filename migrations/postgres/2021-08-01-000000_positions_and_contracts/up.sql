@@ -0,0 +1,26 @@
+-- Catalog of positions and employment contracts of employees. Working time is a fraction of full time
+-- (numerator / denominator, e.g. 1/2). Salaries are paid under a contract - older ones have no contract.
+CREATE TABLE positions
+(
+    id            SERIAL PRIMARY KEY NOT NULL,
+    name          TEXT    NOT NULL UNIQUE,
+    search_string TEXT    NOT NULL DEFAULT '',
+    version       INTEGER NOT NULL DEFAULT 1
+);
+CREATE TABLE employment_contracts
+(
+    id                       SERIAL PRIMARY KEY NOT NULL,
+    employee_id              INTEGER NOT NULL REFERENCES employees (id),
+    position_id              INTEGER REFERENCES positions (id),
+    contract_type            TEXT    NOT NULL CHECK (contract_type IN ('permanent', 'fixed_term', 'b2b')),
+    from_date                DATE    NOT NULL,
+    to_date                  DATE,
+    working_time_numerator   INTEGER NOT NULL DEFAULT 1,
+    working_time_denominator INTEGER NOT NULL DEFAULT 1,
+    search_string            TEXT    NOT NULL DEFAULT '',
+    version                  INTEGER NOT NULL DEFAULT 1
+);
+CREATE INDEX employment_contracts_employee_id ON employment_contracts (employee_id);
+CREATE INDEX employment_contracts_position_id ON employment_contracts (position_id);
+ALTER TABLE salaries ADD COLUMN contract_id INTEGER REFERENCES employment_contracts (id);
+CREATE INDEX salaries_contract_id ON salaries (contract_id);
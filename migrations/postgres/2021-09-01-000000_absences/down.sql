@@ -0,0 +1,6 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX absences_from_date;
+DROP INDEX absences_absence_type_id;
+DROP INDEX absences_employee_id;
+DROP TABLE absences;
+DROP TABLE absence_types;
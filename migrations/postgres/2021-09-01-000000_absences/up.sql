@@ -0,0 +1,31 @@
+-- Absence types with yearly entitlement in working days (NULL - not limited) and how many unused days
+-- can be carried over to next year. Absences are requested and then approved or rejected by manager or admin.
+CREATE TABLE absence_types
+(
+    id                 SERIAL PRIMARY KEY NOT NULL,
+    name               TEXT    NOT NULL UNIQUE,
+    yearly_entitlement INTEGER,
+    carry_over_limit   INTEGER NOT NULL DEFAULT 0,
+    search_string      TEXT    NOT NULL DEFAULT '',
+    version            INTEGER NOT NULL DEFAULT 1
+);
+INSERT INTO absence_types (name, yearly_entitlement, carry_over_limit) VALUES ('Vacation', 26, 26);
+INSERT INTO absence_types (name, yearly_entitlement, carry_over_limit) VALUES ('Sick leave', NULL, 0);
+INSERT INTO absence_types (name, yearly_entitlement, carry_over_limit) VALUES ('Unpaid leave', NULL, 0);
+CREATE TABLE absences
+(
+    id              SERIAL PRIMARY KEY NOT NULL,
+    employee_id     INTEGER NOT NULL REFERENCES employees (id),
+    absence_type_id INTEGER NOT NULL REFERENCES absence_types (id),
+    from_date       DATE    NOT NULL,
+    to_date         DATE    NOT NULL,
+    days            INTEGER NOT NULL,
+    status          TEXT    NOT NULL DEFAULT 'requested' CHECK (status IN ('requested', 'approved', 'rejected')),
+    decided_by      INTEGER REFERENCES users (id),
+    comment         TEXT,
+    search_string   TEXT    NOT NULL DEFAULT '',
+    version         INTEGER NOT NULL DEFAULT 1
+);
+CREATE INDEX absences_employee_id ON absences (employee_id);
+CREATE INDEX absences_absence_type_id ON absences (absence_type_id);
+CREATE INDEX absences_from_date ON absences (from_date);
@@ -0,0 +1,39 @@
+-- Contact holds any number of typed emails, phones (E.164) and postal addresses - in order given by position.
+CREATE TABLE contact_emails
+(
+    id         SERIAL PRIMARY KEY NOT NULL,
+    contact_id INTEGER NOT NULL REFERENCES contacts (id),
+    position   INTEGER NOT NULL,
+    kind       TEXT    NOT NULL CHECK (kind IN ('work', 'private')),
+    email      TEXT    NOT NULL
+);
+CREATE INDEX contact_emails_contact_id ON contact_emails (contact_id);
+CREATE TABLE contact_phones
+(
+    id         SERIAL PRIMARY KEY NOT NULL,
+    contact_id INTEGER NOT NULL REFERENCES contacts (id),
+    position   INTEGER NOT NULL,
+    kind       TEXT    NOT NULL CHECK (kind IN ('mobile', 'landline')),
+    number     TEXT    NOT NULL
+);
+CREATE INDEX contact_phones_contact_id ON contact_phones (contact_id);
+CREATE TABLE contact_addresses
+(
+    id          SERIAL PRIMARY KEY NOT NULL,
+    contact_id  INTEGER NOT NULL REFERENCES contacts (id),
+    position    INTEGER NOT NULL,
+    kind        TEXT    NOT NULL CHECK (kind IN ('home', 'work', 'correspondence')),
+    street      TEXT    NOT NULL,
+    city        TEXT    NOT NULL,
+    postal_code TEXT    NOT NULL,
+    country     TEXT    NOT NULL
+);
+CREATE INDEX contact_addresses_contact_id ON contact_addresses (contact_id);
+-- Existing phone and free-text address are moved as they are - on startup phones without '+' are normalized
+-- and addresses without country split to street, postal code and city of DEFAULT_COUNTRY.
+INSERT INTO contact_phones (contact_id, position, kind, number)
+SELECT id, 0, 'mobile', TRIM(phone) FROM contacts WHERE TRIM(phone) <> '';
+INSERT INTO contact_addresses (contact_id, position, kind, street, city, postal_code, country)
+SELECT id, 0, 'home', TRIM(address), '', '', '' FROM contacts WHERE address IS NOT NULL AND TRIM(address) <> '';
+ALTER TABLE contacts DROP COLUMN phone;
+ALTER TABLE contacts DROP COLUMN address;
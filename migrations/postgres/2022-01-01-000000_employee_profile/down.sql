@@ -0,0 +1,12 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE emergency_contacts;
+DROP TABLE sequences;
+DROP INDEX employees_national_id_hash;
+DROP INDEX employees_employee_number;
+ALTER TABLE employees DROP COLUMN status;
+ALTER TABLE employees DROP COLUMN termination_date;
+ALTER TABLE employees DROP COLUMN hire_date;
+ALTER TABLE employees DROP COLUMN national_id_hash;
+ALTER TABLE employees DROP COLUMN national_id;
+ALTER TABLE employees DROP COLUMN date_of_birth;
+ALTER TABLE employees DROP COLUMN employee_number;
@@ -0,0 +1,31 @@
+-- HR profile of employee. Employee number is unique - numbers of existing employees are generated on start
+-- (Database::initialize()) in configured format. National ID is encrypted, national_id_hash is its blind index.
+ALTER TABLE employees ADD COLUMN employee_number TEXT;
+ALTER TABLE employees ADD COLUMN date_of_birth DATE;
+ALTER TABLE employees ADD COLUMN national_id TEXT;
+ALTER TABLE employees ADD COLUMN national_id_hash TEXT;
+ALTER TABLE employees ADD COLUMN hire_date DATE;
+ALTER TABLE employees ADD COLUMN termination_date DATE;
+ALTER TABLE employees ADD COLUMN status TEXT NOT NULL DEFAULT 'active'
+    CHECK (status IN ('candidate', 'active', 'on_leave', 'terminated'));
+CREATE UNIQUE INDEX employees_employee_number ON employees (employee_number);
+CREATE INDEX employees_national_id_hash ON employees (national_id_hash);
+-- Last value given by named sequence
+CREATE TABLE sequences
+(
+    name  TEXT PRIMARY KEY NOT NULL,
+    value INTEGER          NOT NULL
+);
+INSERT INTO sequences(name, value)
+VALUES ('employee_number', 0);
+CREATE TABLE emergency_contacts
+(
+    id           SERIAL PRIMARY KEY NOT NULL,
+    employee_id  INTEGER NOT NULL REFERENCES employees (id),
+    position     INTEGER NOT NULL,
+    name         TEXT    NOT NULL,
+    relationship TEXT    NOT NULL,
+    phone        TEXT    NOT NULL,
+    email        TEXT
+);
+CREATE INDEX emergency_contacts_employee_id ON emergency_contacts (employee_id);
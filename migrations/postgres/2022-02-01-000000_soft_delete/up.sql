@@ -0,0 +1,4 @@
+-- Deleted employees are kept (soft delete) till retention period passes - then they are purged
+ALTER TABLE employees ADD COLUMN deleted_at TIMESTAMP;
+ALTER TABLE employees ADD COLUMN deleted_by INTEGER REFERENCES users (id);
+CREATE INDEX employees_deleted_at ON employees (deleted_at);
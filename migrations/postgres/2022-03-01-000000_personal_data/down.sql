@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql` - anonymized employees stay anonymized
+DROP TABLE audit_log;
+ALTER TABLE employees DROP COLUMN anonymized_at;
+ALTER TABLE users DROP COLUMN privacy_officer;
@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE custom_field_values;
+DROP TABLE custom_fields;
@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE employee_groups;
+DROP TABLE employee_tags;
+DROP TABLE tags;
@@ -0,0 +1,23 @@
+-- Free-form tags of employees ("remote", "on-call" ...) - tag is deleted when no employee has it
+CREATE TABLE tags
+(
+    id   SERIAL PRIMARY KEY NOT NULL,
+    name TEXT NOT NULL UNIQUE
+);
+CREATE TABLE employee_tags
+(
+    id          SERIAL PRIMARY KEY NOT NULL,
+    employee_id INTEGER NOT NULL REFERENCES employees (id),
+    tag_id      INTEGER NOT NULL REFERENCES tags (id),
+    UNIQUE (employee_id, tag_id)
+);
+CREATE INDEX employee_tags_tag_id ON employee_tags (tag_id);
+-- Saved groups of employees - filter is JSON of employee search evaluated every time group is read
+CREATE TABLE employee_groups
+(
+    id            SERIAL PRIMARY KEY NOT NULL,
+    name          TEXT    NOT NULL UNIQUE,
+    filter        TEXT    NOT NULL,
+    search_string TEXT    NOT NULL DEFAULT '',
+    version       INTEGER NOT NULL DEFAULT 1
+);
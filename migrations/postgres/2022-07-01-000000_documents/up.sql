@@ -0,0 +1,18 @@
+-- Documents attached to employees - content is kept in document storage (local directory or S3) under storage_key
+CREATE TABLE documents
+(
+    id              SERIAL PRIMARY KEY NOT NULL,
+    employee_id     INTEGER   NOT NULL REFERENCES employees (id),
+    document_type   TEXT      NOT NULL CHECK (document_type IN ('contract', 'certificate', 'id_scan', 'other')),
+    file_name       TEXT      NOT NULL,
+    content_type    TEXT      NOT NULL,
+    size            BIGINT    NOT NULL,
+    sha256          TEXT      NOT NULL,
+    storage_key     TEXT      NOT NULL UNIQUE,
+    valid_from      DATE      NULL,
+    valid_to        DATE      NULL CHECK (valid_to >= valid_from),
+    confidentiality TEXT      NOT NULL DEFAULT 'internal' CHECK (confidentiality IN ('internal', 'confidential', 'restricted')),
+    uploaded_by     INTEGER   NULL REFERENCES users (id),
+    uploaded_at     TIMESTAMP NOT NULL
+);
+CREATE INDEX documents_employee_id ON documents (employee_id);
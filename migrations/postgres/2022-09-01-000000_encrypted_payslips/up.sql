@@ -0,0 +1,7 @@
+-- Payslip amounts and phones of emergency contacts are encrypted when ENCRYPTION_KEY is configured (rows saved
+-- before are encrypted by `rust_backend reencrypt`). Encrypted amount is in <column>_encrypted and <column> is 0.
+ALTER TABLE payslips ADD COLUMN gross_encrypted TEXT;
+ALTER TABLE payslips ADD COLUMN deductions_encrypted TEXT;
+ALTER TABLE payslips ADD COLUMN net_encrypted TEXT;
+ALTER TABLE payslip_lines ADD COLUMN amount_encrypted TEXT;
+ALTER TABLE emergency_contacts ADD COLUMN encrypted BOOLEAN NOT NULL DEFAULT FALSE;
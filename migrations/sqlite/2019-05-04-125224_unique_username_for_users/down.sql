@@ -0,0 +1,2 @@
+-- This file should undo anything in `up.sql`
+drop index if exists unique_user_username_idx;
@@ -0,0 +1,2 @@
+-- Your SQL goes here
+create unique index unique_user_username_idx on users (username);
@@ -0,0 +1,33 @@
+-- This file should undo anything in `up.sql` - open-ended periods are closed at the end of time
+CREATE TABLE salaries_old
+(
+     id            INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL
+    ,employee_id   INTEGER                           NOT NULL
+    ,from_date     DATE                              NOT NULL
+    ,to_date       DATE                              NOT NULL
+    ,amount        INTEGER                           NOT NULL
+    ,search_string TEXT                              NOT NULL
+    ,version       INTEGER                           NOT NULL DEFAULT 1
+    ,FOREIGN KEY (employee_id) REFERENCES employees (id)
+);
+INSERT INTO salaries_old (id, employee_id, from_date, to_date, amount, search_string, version)
+SELECT id, employee_id, from_date, COALESCE(to_date, '9999-12-31'), amount, search_string, version FROM salaries;
+DROP TABLE salaries;
+ALTER TABLE salaries_old RENAME TO salaries;
+
+CREATE TABLE contacts_old
+(
+    id            INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    employee_id   INTEGER                           NOT NULL,
+    from_date     DATE                              NOT NULL,
+    to_date       DATE                              NOT NULL,
+    phone         TEXT                              NOT NULL,
+    address       TEXT,
+    search_string TEXT                              NOT NULL,
+    version       INTEGER                           NOT NULL DEFAULT 1,
+    FOREIGN KEY (employee_id) REFERENCES employees (id)
+);
+INSERT INTO contacts_old (id, employee_id, from_date, to_date, phone, address, search_string, version)
+SELECT id, employee_id, from_date, COALESCE(to_date, '9999-12-31'), phone, address, search_string, version FROM contacts;
+DROP TABLE contacts;
+ALTER TABLE contacts_old RENAME TO contacts;
@@ -0,0 +1,36 @@
+-- Open-ended periods: to_date is NULL when period has no end yet.
+-- SQLite can't alter column so tables are rebuilt.
+CREATE TABLE salaries_new
+(
+     id            INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL
+    ,employee_id   INTEGER                           NOT NULL
+    ,from_date     DATE                              NOT NULL
+    ,to_date       DATE
+    ,amount        INTEGER                           NOT NULL
+    ,search_string TEXT                              NOT NULL
+    ,version       INTEGER                           NOT NULL DEFAULT 1
+    ,FOREIGN KEY (employee_id) REFERENCES employees (id)
+    ,CHECK (to_date IS NULL OR from_date <= to_date)
+);
+INSERT INTO salaries_new (id, employee_id, from_date, to_date, amount, search_string, version)
+SELECT id, employee_id, from_date, to_date, amount, search_string, version FROM salaries;
+DROP TABLE salaries;
+ALTER TABLE salaries_new RENAME TO salaries;
+
+CREATE TABLE contacts_new
+(
+    id            INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    employee_id   INTEGER                           NOT NULL,
+    from_date     DATE                              NOT NULL,
+    to_date       DATE,
+    phone         TEXT                              NOT NULL,
+    address       TEXT,
+    search_string TEXT                              NOT NULL,
+    version       INTEGER                           NOT NULL DEFAULT 1,
+    FOREIGN KEY (employee_id) REFERENCES employees (id),
+    CHECK (to_date IS NULL OR from_date <= to_date)
+);
+INSERT INTO contacts_new (id, employee_id, from_date, to_date, phone, address, search_string, version)
+SELECT id, employee_id, from_date, to_date, phone, address, search_string, version FROM contacts;
+DROP TABLE contacts;
+ALTER TABLE contacts_new RENAME TO contacts;
@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql` - amounts stay in minor units
+ALTER TABLE salaries DROP COLUMN gross;
+ALTER TABLE salaries DROP COLUMN pay_period;
+ALTER TABLE salaries DROP COLUMN currency;
@@ -0,0 +1,16 @@
+-- Departments form a tree (parent_id is NULL for top level ones), employees belong to department
+-- and report to manager. User linked to employee see just the employee and its (recursive) reports.
+CREATE TABLE departments
+(
+    id            INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    name          TEXT    NOT NULL,
+    parent_id     INTEGER REFERENCES departments (id),
+    search_string TEXT    NOT NULL DEFAULT '',
+    version       INTEGER NOT NULL DEFAULT 1
+);
+CREATE INDEX departments_parent_id ON departments (parent_id);
+ALTER TABLE employees ADD COLUMN department_id INTEGER REFERENCES departments (id);
+ALTER TABLE employees ADD COLUMN manager_id INTEGER REFERENCES employees (id);
+CREATE INDEX employees_department_id ON employees (department_id);
+CREATE INDEX employees_manager_id ON employees (manager_id);
+ALTER TABLE users ADD COLUMN employee_id INTEGER REFERENCES employees (id);
@@ -0,0 +1,7 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX salaries_contract_id;
+ALTER TABLE salaries DROP COLUMN contract_id;
+DROP INDEX employment_contracts_position_id;
+DROP INDEX employment_contracts_employee_id;
+DROP TABLE employment_contracts;
+DROP TABLE positions;
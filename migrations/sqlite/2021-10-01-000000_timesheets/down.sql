@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE timesheet_weeks;
+DROP INDEX timesheet_entries_employee_id_work_date;
+DROP TABLE timesheet_entries;
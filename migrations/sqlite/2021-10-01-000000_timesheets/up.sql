@@ -0,0 +1,24 @@
+-- Worked time of employees - entries are grouped by weeks (starting on Monday) which are submitted and then
+-- approved or rejected by manager or admin. Entries of submitted or approved week can't be changed.
+CREATE TABLE timesheet_entries
+(
+    id            INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    employee_id   INTEGER NOT NULL REFERENCES employees (id),
+    work_date     DATE    NOT NULL,
+    minutes       INTEGER NOT NULL CHECK (minutes > 0),
+    category      TEXT    NOT NULL,
+    note          TEXT,
+    search_string TEXT    NOT NULL DEFAULT '',
+    version       INTEGER NOT NULL DEFAULT 1
+);
+CREATE INDEX timesheet_entries_employee_id_work_date ON timesheet_entries (employee_id, work_date);
+CREATE TABLE timesheet_weeks
+(
+    id          INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    employee_id INTEGER NOT NULL REFERENCES employees (id),
+    week_start  DATE    NOT NULL,
+    status      TEXT    NOT NULL DEFAULT 'submitted' CHECK (status IN ('submitted', 'approved', 'rejected')),
+    decided_by  INTEGER REFERENCES users (id),
+    version     INTEGER NOT NULL DEFAULT 1,
+    UNIQUE (employee_id, week_start)
+);
@@ -0,0 +1,7 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX payslip_lines_payslip_id;
+DROP TABLE payslip_lines;
+DROP INDEX payslips_payroll_run_id;
+DROP TABLE payslips;
+DROP TABLE payroll_runs;
+DROP TABLE payroll_rules;
@@ -0,0 +1,52 @@
+-- Bonus and deduction rules applied to payslips - percent (in basis points, 1371 is 13.71%) of gross pay
+-- or fixed amount (in minor units of currency).
+CREATE TABLE payroll_rules
+(
+    id            INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    name          TEXT    NOT NULL UNIQUE,
+    kind          TEXT    NOT NULL CHECK (kind IN ('bonus', 'deduction')),
+    percent       INTEGER,
+    amount        BIGINT,
+    currency      TEXT,
+    active        BOOLEAN NOT NULL DEFAULT 1,
+    search_string TEXT    NOT NULL DEFAULT '',
+    version       INTEGER NOT NULL DEFAULT 1
+);
+-- Monthly payroll run with snapshot (JSON) of rules it was calculated with. Payslips are snapshot too - names
+-- of employees and salaries are copied so locked run doesn't change when employees or salaries do.
+CREATE TABLE payroll_runs
+(
+    id          INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    year        INTEGER NOT NULL,
+    month       INTEGER NOT NULL CHECK (month BETWEEN 1 AND 12),
+    status      TEXT    NOT NULL DEFAULT 'draft' CHECK (status IN ('draft', 'approved', 'locked')),
+    approved_by INTEGER REFERENCES users (id),
+    rules       TEXT    NOT NULL,
+    version     INTEGER NOT NULL DEFAULT 1,
+    UNIQUE (year, month)
+);
+CREATE TABLE payslips
+(
+    id             INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    payroll_run_id INTEGER NOT NULL REFERENCES payroll_runs (id),
+    employee_id    INTEGER NOT NULL,
+    first_name     TEXT    NOT NULL,
+    last_name      TEXT    NOT NULL,
+    currency       TEXT    NOT NULL,
+    gross          BIGINT  NOT NULL,
+    deductions     BIGINT  NOT NULL,
+    net            BIGINT  NOT NULL
+);
+CREATE INDEX payslips_payroll_run_id ON payslips (payroll_run_id);
+CREATE TABLE payslip_lines
+(
+    id          INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    payslip_id  INTEGER NOT NULL REFERENCES payslips (id),
+    position    INTEGER NOT NULL,
+    kind        TEXT    NOT NULL CHECK (kind IN ('salary', 'bonus', 'deduction')),
+    description TEXT    NOT NULL,
+    amount      BIGINT  NOT NULL,
+    salary_id   INTEGER,
+    gross       BOOLEAN NOT NULL
+);
+CREATE INDEX payslip_lines_payslip_id ON payslip_lines (payslip_id);
@@ -0,0 +1,12 @@
+-- This file should undo anything in `up.sql` - just the first phone and address of contact are kept
+ALTER TABLE contacts ADD COLUMN phone TEXT NOT NULL DEFAULT '';
+ALTER TABLE contacts ADD COLUMN address TEXT;
+UPDATE contacts
+SET phone   = COALESCE((SELECT p.number FROM contact_phones p WHERE p.contact_id = contacts.id
+                        ORDER BY p.position LIMIT 1), ''),
+    address = (SELECT CASE WHEN a.postal_code = '' AND a.city = '' THEN a.street
+                    ELSE a.street || ', ' || TRIM(a.postal_code || ' ' || a.city) END FROM contact_addresses a
+                 WHERE a.contact_id = contacts.id ORDER BY a.position LIMIT 1);
+DROP TABLE contact_addresses;
+DROP TABLE contact_phones;
+DROP TABLE contact_emails;
@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql` - employees deleted so far become visible again
+DROP INDEX employees_deleted_at;
+ALTER TABLE employees DROP COLUMN deleted_by;
+ALTER TABLE employees DROP COLUMN deleted_at;
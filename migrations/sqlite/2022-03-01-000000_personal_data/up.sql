@@ -0,0 +1,15 @@
+-- Privacy officer can export and anonymize personal data of employees (GDPR) - admin rights are not enough
+ALTER TABLE users ADD COLUMN privacy_officer BOOLEAN NOT NULL DEFAULT 0;
+ALTER TABLE employees ADD COLUMN anonymized_at TIMESTAMP;
+-- Who did what with personal data of which employee - employee_id has no reference so entries outlive
+-- purged employees
+CREATE TABLE audit_log
+(
+    id          INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    at          TIMESTAMP NOT NULL,
+    user_id     INTEGER REFERENCES users (id),
+    employee_id INTEGER,
+    action      TEXT      NOT NULL CHECK (action IN ('personal_data_export', 'anonymization')),
+    details     TEXT      NOT NULL DEFAULT ''
+);
+CREATE INDEX audit_log_employee_id ON audit_log (employee_id);
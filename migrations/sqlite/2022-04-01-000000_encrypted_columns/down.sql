@@ -0,0 +1,7 @@
+-- This file should undo anything in `up.sql` - encrypted values can't be decrypted by SQL, so amounts of
+-- encrypted salaries are lost (0) and encrypted phones and addresses are kept unreadable
+DROP INDEX contact_phones_number_index;
+ALTER TABLE contact_addresses DROP COLUMN encrypted;
+ALTER TABLE contact_phones DROP COLUMN number_index;
+ALTER TABLE contact_phones DROP COLUMN encrypted;
+ALTER TABLE salaries DROP COLUMN amount_encrypted;
@@ -0,0 +1,9 @@
+-- Salary amounts, phones and addresses are encrypted when ENCRYPTION_KEY is configured (rows saved before are
+-- encrypted by `rust_backend reencrypt`). Encrypted amount is in amount_encrypted and amount is 0.
+ALTER TABLE salaries ADD COLUMN amount_encrypted TEXT;
+ALTER TABLE contact_phones ADD COLUMN encrypted BOOLEAN NOT NULL DEFAULT 0;
+-- Blind index of encrypted number - phones are searched by it
+ALTER TABLE contact_phones ADD COLUMN number_index TEXT;
+CREATE INDEX contact_phones_number_index ON contact_phones (number_index);
+-- Street, city and postal code are encrypted
+ALTER TABLE contact_addresses ADD COLUMN encrypted BOOLEAN NOT NULL DEFAULT 0;
@@ -0,0 +1,23 @@
+-- Fields of employees defined by admins at runtime. Validation rules (max length, min / max, enum options) are
+-- kept as JSON.
+CREATE TABLE custom_fields
+(
+    id            INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    name          TEXT    NOT NULL UNIQUE,
+    field_type    TEXT    NOT NULL CHECK (field_type IN ('string', 'number', 'date', 'enum', 'bool')),
+    required      BOOLEAN NOT NULL DEFAULT 0,
+    rules         TEXT    NOT NULL DEFAULT '{}',
+    search_string TEXT    NOT NULL DEFAULT '',
+    version       INTEGER NOT NULL DEFAULT 1
+);
+-- Value of custom field as text - numbers, dates (YYYY-MM-DD) and bools ('true' / 'false') normalized so they
+-- can be compared
+CREATE TABLE custom_field_values
+(
+    id              INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    employee_id     INTEGER NOT NULL REFERENCES employees (id),
+    custom_field_id INTEGER NOT NULL REFERENCES custom_fields (id),
+    value           TEXT    NOT NULL,
+    UNIQUE (employee_id, custom_field_id)
+);
+CREATE INDEX custom_field_values_custom_field_id ON custom_field_values (custom_field_id, value);
@@ -0,0 +1,13 @@
+-- Photo of employee - original (re-encoded without metadata) and its thumbnails are kept in document storage
+-- under keys derived from employee_id and sha256 of the original
+CREATE TABLE employee_photos
+(
+    id           INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    employee_id  INTEGER   NOT NULL UNIQUE REFERENCES employees (id),
+    content_type TEXT      NOT NULL CHECK (content_type IN ('image/jpeg', 'image/png', 'image/webp')),
+    width        INTEGER   NOT NULL,
+    height       INTEGER   NOT NULL,
+    sha256       TEXT      NOT NULL,
+    uploaded_by  INTEGER   NULL REFERENCES users (id),
+    uploaded_at  TIMESTAMP NOT NULL
+);
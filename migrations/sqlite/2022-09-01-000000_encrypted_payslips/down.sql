@@ -0,0 +1,7 @@
+-- This file should undo anything in `up.sql` - encrypted values can't be decrypted by SQL, so encrypted amounts
+-- are lost (0) and encrypted phones are kept unreadable
+ALTER TABLE emergency_contacts DROP COLUMN encrypted;
+ALTER TABLE payslip_lines DROP COLUMN amount_encrypted;
+ALTER TABLE payslips DROP COLUMN net_encrypted;
+ALTER TABLE payslips DROP COLUMN deductions_encrypted;
+ALTER TABLE payslips DROP COLUMN gross_encrypted;
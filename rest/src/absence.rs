@@ -0,0 +1,345 @@
+use actix_web::error::{ErrorConflict, ErrorForbidden, ErrorInternalServerError, ErrorNotFound};
+use actix_web::http::Method;
+use actix_web::web::Json;
+use actix_web::{web, Error, HttpRequest, HttpResponse};
+use chrono::{Datelike, Local, NaiveDate};
+use dao::{
+    calendar_with_connection, can_approve, AbsenceDTO, AbsenceStatus, Crud, DaoError, DaoResult, Database, DbConnection,
+    EmployeeDTO, SearchableByParent,
+};
+
+use crate::db;
+use crate::employee::{logged_user, scope};
+use crate::etag;
+use crate::session::LoggedGuard::Logged;
+
+/// Absence of employee - None when there is no such absence of the employee or the employee is out of scope
+/// of logged user
+fn absence_of_employee(
+    user_id: i32,
+    e_id: i32,
+    a_id: i32,
+    conn: &mut DbConnection,
+) -> DaoResult<Option<AbsenceDTO>> {
+    if !scope(user_id, conn)?.contains(e_id, conn)? {
+        return Ok(None);
+    }
+    Ok(AbsenceDTO::get_with_conn(a_id, None, conn).filter(|a| a.employee_id == Some(e_id)))
+}
+
+/// Absences of employee - employee out of scope of logged user is reported as not found
+async fn get_absences(
+    req: HttpRequest,
+    db: web::Data<Database>,
+    path: web::Path<String>,
+) -> Result<HttpResponse, Error> {
+    let e_id: i32 = path.parse().unwrap();
+    let user_id = logged_user(&req)?;
+    let keys = db.keys();
+    let absences = db::try_block(&db, move |conn| {
+        if !scope(user_id, conn)?.contains(e_id, conn)?
+            || EmployeeDTO::get_with_conn(e_id, keys.as_deref(), conn).is_none()
+        {
+            return Ok(None);
+        }
+        AbsenceDTO::search_by_parent_id_with_connection(e_id, keys.as_deref(), conn).map(Some)
+    })
+    .await?;
+    match absences {
+        Some(absences) => {
+            let body = serde_json::to_string(&absences)?;
+            Ok(HttpResponse::Ok()
+                .content_type("application/json")
+                .body(body))
+        }
+        None => Err(ErrorNotFound(format!(
+            "Can't find employee with id = {}",
+            e_id
+        ))),
+    }
+}
+
+async fn get_absence(
+    req: HttpRequest,
+    db: web::Data<Database>,
+    path: web::Path<(String, String)>,
+) -> Result<HttpResponse, Error> {
+    let e_id: i32 = path.0.parse().unwrap();
+    let a_id: i32 = path.1.parse().unwrap();
+    let user_id = logged_user(&req)?;
+    match db::try_block(&db, move |conn| absence_of_employee(user_id, e_id, a_id, conn)).await? {
+        Some(absence) => etag::ok(&absence, absence.version.unwrap_or_default()),
+        None => Err(ErrorNotFound(format!(
+            "Can't find absence with id = {} of employee with id = {}",
+            a_id, e_id
+        ))),
+    }
+}
+
+/// 412 with current state of absence
+async fn absence_precondition_failed(db: &Database, id: i32) -> Result<HttpResponse, Error> {
+    let keys = db.keys();
+    let current = db::try_block(db, move |conn| Ok(AbsenceDTO::get_simple(id, keys.as_deref(), conn)?)).await?;
+    etag::precondition_failed(&current, current.version.unwrap_or_default())
+}
+
+/// Request absence (without id) or change absence still waiting for decision. Change require If-Match
+/// with ETag of absence it is based on - and so does every PUT. Status is changed just by approve and reject.
+async fn update_absence(
+    req: HttpRequest,
+    db: web::Data<Database>,
+    path: web::Path<String>,
+    absence_json: Json<AbsenceDTO>,
+) -> Result<HttpResponse, Error> {
+    let e_id: i32 = path.parse().unwrap();
+    let user_id = logged_user(&req)?;
+    let mut absence = absence_json.into_inner();
+    absence.employee_id = Some(e_id);
+    absence.status = AbsenceStatus::Requested;
+    absence.decided_by = None;
+    let if_match = if req.method() == Method::PUT || absence.id.is_some() {
+        Some(etag::if_match(&req)?)
+    } else {
+        None
+    };
+    let rules = db.config().validation.clone();
+    let keys = db.keys();
+    let saved = db::block(&db, move |conn| {
+        if !scope(user_id, conn)?.contains(e_id, conn)? {
+            return Err(DaoError::not_found());
+        }
+        EmployeeDTO::get_simple(e_id, keys.as_deref(), conn)?;
+        if let (Some(if_match), Some(id)) = (&if_match, absence.id) {
+            let current = absence_of_employee(user_id, e_id, id, conn)?.ok_or_else(DaoError::not_found)?;
+            absence.status = current.status;
+            absence.decided_by = current.decided_by;
+            absence.version = Some(etag::expected_version(
+                if_match,
+                id,
+                current.version.unwrap_or_default(),
+            )?);
+        }
+        absence.try_persist_in_transaction(&rules, keys.as_deref(), conn)
+    })
+    .await?;
+    match saved {
+        Ok(absence) => etag::ok(&absence, absence.version.unwrap_or_default()),
+        Err(DaoError::StaleVersion { id, .. }) => absence_precondition_failed(&db, id).await,
+        Err(e) if e.is_not_found() => Err(ErrorNotFound(format!(
+            "Can't find employee with id = {} or its absence",
+            e_id
+        ))),
+        Err(e) => Err(db::dao_error(e)),
+    }
+}
+
+/// Withdraw absence - just while it waits for decision (409 Conflict once it is decided)
+async fn delete_absence(
+    req: HttpRequest,
+    db: web::Data<Database>,
+    path: web::Path<(String, String)>,
+) -> Result<HttpResponse, Error> {
+    let e_id: i32 = path.0.parse().unwrap();
+    let a_id: i32 = path.1.parse().unwrap();
+    let user_id = logged_user(&req)?;
+    let if_match = etag::if_match(&req)?;
+    let keys = db.keys();
+    let deleted = db::block(&db, move |conn| {
+        let mut absence = absence_of_employee(user_id, e_id, a_id, conn)?.ok_or_else(DaoError::not_found)?;
+        absence.version = Some(etag::expected_version(
+            &if_match,
+            a_id,
+            absence.version.unwrap_or_default(),
+        )?);
+        if absence.status != AbsenceStatus::Requested {
+            return Ok(None);
+        }
+        absence.try_delete_with_conn(keys.as_deref(), conn).map(Some)
+    })
+    .await?;
+    match deleted {
+        Ok(Some(1)) => Ok(HttpResponse::Ok()
+            .content_type("application/json")
+            .body(format!("Removed absence with id = {}", a_id))),
+        Ok(Some(n)) => Err(ErrorInternalServerError(format!(
+            "Removed {} absences with id = {}",
+            n, a_id
+        ))),
+        Ok(None) => Err(ErrorConflict(format!(
+            "Absence with id = {} is already decided - it can't be removed",
+            a_id
+        ))),
+        Err(DaoError::StaleVersion { .. }) => absence_precondition_failed(&db, a_id).await,
+        Err(e) if e.is_not_found() => Err(ErrorNotFound(format!(
+            "Can't find absence with id = {} of employee with id = {}",
+            a_id, e_id
+        ))),
+        Err(e) => Err(db::dao_error(e)),
+    }
+}
+
+/// Approve or reject absence - allowed to admins and (recursive) managers of the employee. Require If-Match
+/// with ETag of absence the decision is based on.
+async fn decide_absence(
+    req: HttpRequest,
+    db: web::Data<Database>,
+    path: web::Path<(String, String)>,
+    decision: AbsenceStatus,
+) -> Result<HttpResponse, Error> {
+    let e_id: i32 = path.0.parse().unwrap();
+    let a_id: i32 = path.1.parse().unwrap();
+    let user_id = logged_user(&req)?;
+    let if_match = etag::if_match(&req)?;
+    let decided = db::block(&db, move |conn| {
+        let absence = absence_of_employee(user_id, e_id, a_id, conn)?.ok_or_else(DaoError::not_found)?;
+        if !can_approve(user_id, e_id, conn)? {
+            return Ok(None);
+        }
+        let expected = etag::expected_version(&if_match, a_id, absence.version.unwrap_or_default())?;
+        AbsenceDTO::decide_with_connection(a_id, expected, decision, user_id, conn).map(Some)
+    })
+    .await?;
+    match decided {
+        Ok(Some(absence)) => etag::ok(&absence, absence.version.unwrap_or_default()),
+        Ok(None) => Err(ErrorForbidden(format!(
+            "You can't decide about absences of employee with id = {}",
+            e_id
+        ))),
+        Err(DaoError::StaleVersion { .. }) => absence_precondition_failed(&db, a_id).await,
+        Err(e) if e.is_not_found() => Err(ErrorNotFound(format!(
+            "Can't find absence with id = {} of employee with id = {}",
+            a_id, e_id
+        ))),
+        Err(e) => Err(db::dao_error(e)),
+    }
+}
+
+async fn approve_absence(
+    req: HttpRequest,
+    db: web::Data<Database>,
+    path: web::Path<(String, String)>,
+) -> Result<HttpResponse, Error> {
+    decide_absence(req, db, path, AbsenceStatus::Approved).await
+}
+
+async fn reject_absence(
+    req: HttpRequest,
+    db: web::Data<Database>,
+    path: web::Path<(String, String)>,
+) -> Result<HttpResponse, Error> {
+    decide_absence(req, db, path, AbsenceStatus::Rejected).await
+}
+
+/// `?year=2021` - current year by default
+#[derive(Deserialize, Debug)]
+pub struct BalanceQuery {
+    pub year: Option<i32>,
+}
+
+/// Entitlement, carried over, used and remaining days per absence type
+async fn get_balance(
+    req: HttpRequest,
+    db: web::Data<Database>,
+    path: web::Path<String>,
+    query: web::Query<BalanceQuery>,
+) -> Result<HttpResponse, Error> {
+    let e_id: i32 = path.parse().unwrap();
+    let user_id = logged_user(&req)?;
+    let year = query.year.unwrap_or_else(|| Local::now().year());
+    let keys = db.keys();
+    let balance = db::try_block(&db, move |conn| {
+        if !scope(user_id, conn)?.contains(e_id, conn)?
+            || EmployeeDTO::get_with_conn(e_id, keys.as_deref(), conn).is_none()
+        {
+            return Ok(None);
+        }
+        AbsenceDTO::balance_with_connection(e_id, year, conn).map(Some)
+    })
+    .await?;
+    match balance {
+        Some(balance) => {
+            let body = serde_json::to_string(&balance)?;
+            Ok(HttpResponse::Ok()
+                .content_type("application/json")
+                .body(body))
+        }
+        None => Err(ErrorNotFound(format!(
+            "Can't find employee with id = {}",
+            e_id
+        ))),
+    }
+}
+
+/// `?from=YYYY-MM-DD&to=YYYY-MM-DD&department_id=1` - current month and all departments by default
+#[derive(Deserialize, Debug)]
+pub struct CalendarQuery {
+    pub from: Option<NaiveDate>,
+    pub to: Option<NaiveDate>,
+    pub department_id: Option<i32>,
+}
+
+/// Team calendar - requested and approved absences of employees logged user can see
+async fn get_calendar(
+    req: HttpRequest,
+    db: web::Data<Database>,
+    query: web::Query<CalendarQuery>,
+) -> Result<HttpResponse, Error> {
+    let user_id = logged_user(&req)?;
+    let today = Local::now().date_naive();
+    let from = query.from.unwrap_or_else(|| today.with_day(1).unwrap());
+    let to = query.to.unwrap_or_else(|| {
+        let next_month = from.with_day(1).unwrap() + chrono::Months::new(1);
+        next_month.pred_opt().unwrap()
+    });
+    let department = query.department_id;
+    let entries = db::try_block(&db, move |conn| {
+        let scope = scope(user_id, conn)?;
+        calendar_with_connection(from, to, department, scope, conn)
+    })
+    .await?;
+    let body = serde_json::to_string(&entries)?;
+    Ok(HttpResponse::Ok()
+        .content_type("application/json")
+        .body(body))
+}
+
+/// Absences as sub-resource of employees - `prefix` is prefix of employees
+pub fn config(cfg: &mut web::ServiceConfig, prefix: &str) {
+    cfg.service(
+        web::resource(format!("{}{}", prefix, "/{id}/absences"))
+            .wrap(Logged)
+            .route(web::get().to(get_absences))
+            .route(web::put().to(update_absence))
+            .route(web::post().to(update_absence)),
+    );
+    cfg.service(
+        web::resource(format!("{}{}", prefix, "/{id}/absences/balance"))
+            .wrap(Logged)
+            .route(web::get().to(get_balance)),
+    );
+    cfg.service(
+        web::resource(format!("{}{}", prefix, "/{id}/absences/{absence_id}"))
+            .wrap(Logged)
+            .route(web::get().to(get_absence))
+            .route(web::delete().to(delete_absence)),
+    );
+    cfg.service(
+        web::resource(format!("{}{}", prefix, "/{id}/absences/{absence_id}/approve"))
+            .wrap(Logged)
+            .route(web::post().to(approve_absence)),
+    );
+    cfg.service(
+        web::resource(format!("{}{}", prefix, "/{id}/absences/{absence_id}/reject"))
+            .wrap(Logged)
+            .route(web::post().to(reject_absence)),
+    );
+}
+
+/// Team calendar - `prefix` is prefix of absences
+pub fn config_calendar(cfg: &mut web::ServiceConfig, prefix: &str) {
+    cfg.service(
+        web::resource(format!("{}{}", prefix, "/calendar"))
+            .wrap(Logged)
+            .route(web::get().to(get_calendar)),
+    );
+}
@@ -0,0 +1,124 @@
+use actix_web::error::{ErrorInternalServerError, ErrorNotFound};
+use actix_web::http::Method;
+use actix_web::web::Json;
+use actix_web::{web, Error, HttpRequest, HttpResponse};
+use dao::{Crud, DaoError, Database, AbsenceTypeDTO, Searchable};
+
+use crate::db;
+use crate::etag;
+use crate::session::LoggedGuard::LoggedAsAdmin;
+
+async fn get_absence_types(db: web::Data<Database>) -> Result<HttpResponse, Error> {
+    let keys = db.keys();
+    let absence_types: Vec<AbsenceTypeDTO> =
+        db::try_block(&db, move |conn| AbsenceTypeDTO::get_all_with_connection(keys.as_deref(), conn)).await?;
+    let body = serde_json::to_string(&absence_types)?;
+    Ok(HttpResponse::Ok()
+        .content_type("application/json")
+        .body(body))
+}
+
+async fn get_absence_type(db: web::Data<Database>, path: web::Path<String>) -> Result<HttpResponse, Error> {
+    let id: i32 = path.parse().unwrap();
+    let keys = db.keys();
+    match db::block(&db, move |conn| AbsenceTypeDTO::get_with_conn(id, keys.as_deref(), conn)).await? {
+        Some(absence_type) => etag::ok(&absence_type, absence_type.version.unwrap_or_default()),
+        None => Err(ErrorNotFound(format!(
+            "Can't find absence type with id = {}",
+            id
+        ))),
+    }
+}
+
+/// 412 with current state of absence type
+async fn absence_type_precondition_failed(db: &Database, id: i32) -> Result<HttpResponse, Error> {
+    let keys = db.keys();
+    let current = db::try_block(db, move |conn| Ok(AbsenceTypeDTO::get_simple(id, keys.as_deref(), conn)?)).await?;
+    etag::precondition_failed(&current, current.version.unwrap_or_default())
+}
+
+/// Create absence type (without id) or update existing one. Update require If-Match with ETag
+/// of absence type it is based on - and so does every PUT.
+async fn update_absence_type(
+    req: HttpRequest,
+    db: web::Data<Database>,
+    absence_type_json: Json<AbsenceTypeDTO>,
+) -> Result<HttpResponse, Error> {
+    let mut absence_type = absence_type_json.into_inner();
+    let if_match = if req.method() == Method::PUT || absence_type.id.is_some() {
+        Some(etag::if_match(&req)?)
+    } else {
+        None
+    };
+    let rules = db.config().validation.clone();
+    let keys = db.keys();
+    let saved = db::block(&db, move |conn| {
+        if let (Some(if_match), Some(id)) = (&if_match, absence_type.id) {
+            let current = AbsenceTypeDTO::get_simple(id, keys.as_deref(), conn)?;
+            absence_type.version = Some(etag::expected_version(
+                if_match,
+                id,
+                current.version.unwrap_or_default(),
+            )?);
+        }
+        absence_type.try_persist_in_transaction(&rules, keys.as_deref(), conn)
+    })
+    .await?;
+    match saved {
+        Ok(absence_type) => etag::ok(&absence_type, absence_type.version.unwrap_or_default()),
+        Err(DaoError::StaleVersion { id, .. }) => absence_type_precondition_failed(&db, id).await,
+        Err(e) => Err(db::dao_error(e)),
+    }
+}
+
+/// Absence type which is used by absences can't be deleted - 409 Conflict
+async fn delete_absence_type(
+    req: HttpRequest,
+    db: web::Data<Database>,
+    path: web::Path<String>,
+) -> Result<HttpResponse, Error> {
+    let id: i32 = path.parse().unwrap();
+    let if_match = etag::if_match(&req)?;
+    let keys = db.keys();
+    let deleted = db::block(&db, move |conn| {
+        let mut absence_type = AbsenceTypeDTO::get_simple(id, keys.as_deref(), conn)?;
+        absence_type.version = Some(etag::expected_version(
+            &if_match,
+            id,
+            absence_type.version.unwrap_or_default(),
+        )?);
+        absence_type.try_delete_with_conn(keys.as_deref(), conn)
+    })
+    .await?;
+    match deleted {
+        Ok(1) => Ok(HttpResponse::Ok()
+            .content_type("application/json")
+            .body(format!("Removed absence type with id = {}", id))),
+        Ok(n) => Err(ErrorInternalServerError(format!(
+            "Removed {} absence types with id = {}",
+            n, id
+        ))),
+        Err(DaoError::StaleVersion { .. }) => absence_type_precondition_failed(&db, id).await,
+        Err(e) if e.is_not_found() => Err(ErrorNotFound(format!(
+            "Not found absence type with id = {}",
+            id
+        ))),
+        Err(e) => Err(db::dao_error(e)),
+    }
+}
+
+pub fn config(cfg: &mut web::ServiceConfig, prefix: &str) {
+    cfg.service(
+        web::resource(prefix)
+            .wrap(LoggedAsAdmin(&[Method::PUT, Method::POST]))
+            .route(web::get().to(get_absence_types))
+            .route(web::put().to(update_absence_type))
+            .route(web::post().to(update_absence_type)),
+    );
+    cfg.service(
+        web::resource(format!("{}{}", prefix, "/{id}"))
+            .wrap(LoggedAsAdmin(&[Method::DELETE]))
+            .route(web::get().to(get_absence_type))
+            .route(web::delete().to(delete_absence_type)),
+    );
+}
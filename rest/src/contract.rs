@@ -0,0 +1,174 @@
+use actix_web::error::{ErrorInternalServerError, ErrorNotFound};
+use actix_web::http::Method;
+use actix_web::web::Json;
+use actix_web::{web, Error, HttpRequest, HttpResponse};
+use dao::{ContractDTO, Crud, DaoError, DaoResult, Database, DbConnection, EmployeeDTO, SearchableByParent};
+
+use crate::db;
+use crate::employee::{logged_user, scope};
+use crate::etag;
+use crate::session::LoggedGuard::LoggedAsAdmin;
+
+/// Contract of employee - None when there is no such contract of the employee
+fn contract_of_employee(e_id: i32, c_id: i32, conn: &mut DbConnection) -> DaoResult<Option<ContractDTO>> {
+    Ok(ContractDTO::get_with_conn(c_id, None, conn).filter(|c| c.employee_id == Some(e_id)))
+}
+
+/// Contracts of employee - employee out of scope of logged user is reported as not found
+async fn get_contracts(
+    req: HttpRequest,
+    db: web::Data<Database>,
+    path: web::Path<String>,
+) -> Result<HttpResponse, Error> {
+    let e_id: i32 = path.parse().unwrap();
+    let user_id = logged_user(&req)?;
+    let keys = db.keys();
+    let contracts = db::try_block(&db, move |conn| {
+        if !scope(user_id, conn)?.contains(e_id, conn)?
+            || EmployeeDTO::get_with_conn(e_id, keys.as_deref(), conn).is_none()
+        {
+            return Ok(None);
+        }
+        ContractDTO::search_by_parent_id_with_connection(e_id, keys.as_deref(), conn).map(Some)
+    })
+    .await?;
+    match contracts {
+        Some(contracts) => {
+            let body = serde_json::to_string(&contracts)?;
+            Ok(HttpResponse::Ok()
+                .content_type("application/json")
+                .body(body))
+        }
+        None => Err(ErrorNotFound(format!(
+            "Can't find employee with id = {}",
+            e_id
+        ))),
+    }
+}
+
+async fn get_contract(
+    req: HttpRequest,
+    db: web::Data<Database>,
+    path: web::Path<(String, String)>,
+) -> Result<HttpResponse, Error> {
+    let e_id: i32 = path.0.parse().unwrap();
+    let c_id: i32 = path.1.parse().unwrap();
+    let user_id = logged_user(&req)?;
+    let contract = db::try_block(&db, move |conn| {
+        if !scope(user_id, conn)?.contains(e_id, conn)? {
+            return Ok(None);
+        }
+        contract_of_employee(e_id, c_id, conn)
+    })
+    .await?;
+    match contract {
+        Some(contract) => etag::ok(&contract, contract.version.unwrap_or_default()),
+        None => Err(ErrorNotFound(format!(
+            "Can't find contract with id = {} of employee with id = {}",
+            c_id, e_id
+        ))),
+    }
+}
+
+/// 412 with current state of contract
+async fn contract_precondition_failed(db: &Database, id: i32) -> Result<HttpResponse, Error> {
+    let keys = db.keys();
+    let current = db::try_block(db, move |conn| Ok(ContractDTO::get_simple(id, keys.as_deref(), conn)?)).await?;
+    etag::precondition_failed(&current, current.version.unwrap_or_default())
+}
+
+/// Create contract of employee (without id) or update existing one. Update require If-Match with ETag
+/// of contract it is based on - and so does every PUT.
+async fn update_contract(
+    req: HttpRequest,
+    db: web::Data<Database>,
+    path: web::Path<String>,
+    contract_json: Json<ContractDTO>,
+) -> Result<HttpResponse, Error> {
+    let e_id: i32 = path.parse().unwrap();
+    let mut contract = contract_json.into_inner();
+    contract.employee_id = Some(e_id);
+    let if_match = if req.method() == Method::PUT || contract.id.is_some() {
+        Some(etag::if_match(&req)?)
+    } else {
+        None
+    };
+    let rules = db.config().validation.clone();
+    let keys = db.keys();
+    let saved = db::block(&db, move |conn| {
+        EmployeeDTO::get_simple(e_id, keys.as_deref(), conn)?;
+        if let (Some(if_match), Some(id)) = (&if_match, contract.id) {
+            let current = contract_of_employee(e_id, id, conn)?.ok_or_else(DaoError::not_found)?;
+            contract.version = Some(etag::expected_version(
+                if_match,
+                id,
+                current.version.unwrap_or_default(),
+            )?);
+        }
+        contract.try_persist_in_transaction(&rules, keys.as_deref(), conn)
+    })
+    .await?;
+    match saved {
+        Ok(contract) => etag::ok(&contract, contract.version.unwrap_or_default()),
+        Err(DaoError::StaleVersion { id, .. }) => contract_precondition_failed(&db, id).await,
+        Err(e) if e.is_not_found() => Err(ErrorNotFound(format!(
+            "Can't find employee with id = {} or its contract",
+            e_id
+        ))),
+        Err(e) => Err(db::dao_error(e)),
+    }
+}
+
+/// Salaries paid under deleted contract are left without contract
+async fn delete_contract(
+    req: HttpRequest,
+    db: web::Data<Database>,
+    path: web::Path<(String, String)>,
+) -> Result<HttpResponse, Error> {
+    let e_id: i32 = path.0.parse().unwrap();
+    let c_id: i32 = path.1.parse().unwrap();
+    let if_match = etag::if_match(&req)?;
+    let keys = db.keys();
+    let deleted = db::block(&db, move |conn| {
+        let mut contract = contract_of_employee(e_id, c_id, conn)?.ok_or_else(DaoError::not_found)?;
+        contract.version = Some(etag::expected_version(
+            &if_match,
+            c_id,
+            contract.version.unwrap_or_default(),
+        )?);
+        contract.try_delete_with_conn(keys.as_deref(), conn)
+    })
+    .await?;
+    match deleted {
+        Ok(1) => Ok(HttpResponse::Ok()
+            .content_type("application/json")
+            .body(format!("Removed contract with id = {}", c_id))),
+        Ok(n) => Err(ErrorInternalServerError(format!(
+            "Removed {} contracts with id = {}",
+            n, c_id
+        ))),
+        Err(DaoError::StaleVersion { .. }) => contract_precondition_failed(&db, c_id).await,
+        Err(e) if e.is_not_found() => Err(ErrorNotFound(format!(
+            "Can't find contract with id = {} of employee with id = {}",
+            c_id, e_id
+        ))),
+        Err(e) => Err(db::dao_error(e)),
+    }
+}
+
+/// Contracts as sub-resource of employees - `prefix` is prefix of employees
+pub fn config(cfg: &mut web::ServiceConfig, prefix: &str) {
+    cfg.service(
+        web::resource(format!("{}{}", prefix, "/{id}/contracts"))
+            .wrap(LoggedAsAdmin(&[Method::PUT, Method::POST]))
+            .route(web::get().to(get_contracts))
+            .route(web::put().to(update_contract))
+            .route(web::post().to(update_contract)),
+    );
+    cfg.service(
+        web::resource(format!("{}{}", prefix, "/{id}/contracts/{contract_id}"))
+            .wrap(LoggedAsAdmin(&[Method::DELETE]))
+            .route(web::get().to(get_contract))
+            .route(web::delete().to(delete_contract)),
+    );
+}
@@ -0,0 +1,124 @@
+use actix_web::error::{ErrorInternalServerError, ErrorNotFound};
+use actix_web::http::Method;
+use actix_web::web::Json;
+use actix_web::{web, Error, HttpRequest, HttpResponse};
+use dao::{Crud, DaoError, Database, CustomFieldDTO, Searchable};
+
+use crate::db;
+use crate::etag;
+use crate::session::LoggedGuard::LoggedAsAdmin;
+
+async fn get_custom_fields(db: web::Data<Database>) -> Result<HttpResponse, Error> {
+    let keys = db.keys();
+    let custom_fields: Vec<CustomFieldDTO> =
+        db::try_block(&db, move |conn| CustomFieldDTO::get_all_with_connection(keys.as_deref(), conn)).await?;
+    let body = serde_json::to_string(&custom_fields)?;
+    Ok(HttpResponse::Ok()
+        .content_type("application/json")
+        .body(body))
+}
+
+async fn get_custom_field(db: web::Data<Database>, path: web::Path<String>) -> Result<HttpResponse, Error> {
+    let id: i32 = path.parse().unwrap();
+    let keys = db.keys();
+    match db::block(&db, move |conn| CustomFieldDTO::get_with_conn(id, keys.as_deref(), conn)).await? {
+        Some(custom_field) => etag::ok(&custom_field, custom_field.version.unwrap_or_default()),
+        None => Err(ErrorNotFound(format!(
+            "Can't find custom field with id = {}",
+            id
+        ))),
+    }
+}
+
+/// 412 with current state of custom field
+async fn custom_field_precondition_failed(db: &Database, id: i32) -> Result<HttpResponse, Error> {
+    let keys = db.keys();
+    let current = db::try_block(db, move |conn| Ok(CustomFieldDTO::get_simple(id, keys.as_deref(), conn)?)).await?;
+    etag::precondition_failed(&current, current.version.unwrap_or_default())
+}
+
+/// Create custom field (without id) or update existing one. Update require If-Match with ETag
+/// of custom field it is based on - and so does every PUT.
+async fn update_custom_field(
+    req: HttpRequest,
+    db: web::Data<Database>,
+    custom_field_json: Json<CustomFieldDTO>,
+) -> Result<HttpResponse, Error> {
+    let mut custom_field = custom_field_json.into_inner();
+    let if_match = if req.method() == Method::PUT || custom_field.id.is_some() {
+        Some(etag::if_match(&req)?)
+    } else {
+        None
+    };
+    let rules = db.config().validation.clone();
+    let keys = db.keys();
+    let saved = db::block(&db, move |conn| {
+        if let (Some(if_match), Some(id)) = (&if_match, custom_field.id) {
+            let current = CustomFieldDTO::get_simple(id, keys.as_deref(), conn)?;
+            custom_field.version = Some(etag::expected_version(
+                if_match,
+                id,
+                current.version.unwrap_or_default(),
+            )?);
+        }
+        custom_field.try_persist_in_transaction(&rules, keys.as_deref(), conn)
+    })
+    .await?;
+    match saved {
+        Ok(custom_field) => etag::ok(&custom_field, custom_field.version.unwrap_or_default()),
+        Err(DaoError::StaleVersion { id, .. }) => custom_field_precondition_failed(&db, id).await,
+        Err(e) => Err(db::dao_error(e)),
+    }
+}
+
+/// Values of employees are deleted with the field
+async fn delete_custom_field(
+    req: HttpRequest,
+    db: web::Data<Database>,
+    path: web::Path<String>,
+) -> Result<HttpResponse, Error> {
+    let id: i32 = path.parse().unwrap();
+    let if_match = etag::if_match(&req)?;
+    let keys = db.keys();
+    let deleted = db::block(&db, move |conn| {
+        let mut custom_field = CustomFieldDTO::get_simple(id, keys.as_deref(), conn)?;
+        custom_field.version = Some(etag::expected_version(
+            &if_match,
+            id,
+            custom_field.version.unwrap_or_default(),
+        )?);
+        custom_field.try_delete_with_conn(keys.as_deref(), conn)
+    })
+    .await?;
+    match deleted {
+        Ok(1) => Ok(HttpResponse::Ok()
+            .content_type("application/json")
+            .body(format!("Removed custom field with id = {}", id))),
+        Ok(n) => Err(ErrorInternalServerError(format!(
+            "Removed {} custom fields with id = {}",
+            n, id
+        ))),
+        Err(DaoError::StaleVersion { .. }) => custom_field_precondition_failed(&db, id).await,
+        Err(e) if e.is_not_found() => Err(ErrorNotFound(format!(
+            "Not found custom field with id = {}",
+            id
+        ))),
+        Err(e) => Err(db::dao_error(e)),
+    }
+}
+
+pub fn config(cfg: &mut web::ServiceConfig, prefix: &str) {
+    cfg.service(
+        web::resource(prefix)
+            .wrap(LoggedAsAdmin(&[Method::PUT, Method::POST]))
+            .route(web::get().to(get_custom_fields))
+            .route(web::put().to(update_custom_field))
+            .route(web::post().to(update_custom_field)),
+    );
+    cfg.service(
+        web::resource(format!("{}{}", prefix, "/{id}"))
+            .wrap(LoggedAsAdmin(&[Method::DELETE]))
+            .route(web::get().to(get_custom_field))
+            .route(web::delete().to(delete_custom_field)),
+    );
+}
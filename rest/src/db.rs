@@ -0,0 +1,60 @@
+use actix_web::error::{ErrorConflict, ErrorInternalServerError, ErrorNotFound, ErrorPreconditionFailed, InternalError};
+use actix_web::http::header::RETRY_AFTER;
+use actix_web::{web, Error, HttpResponse};
+use dao::{DaoError, DaoResult, Database, DbConnection};
+
+/// Run blocking DAO code on actix blocking thread pool so it does not stall async worker.
+/// Connection is checked out from db pool inside blocking thread - when pool can't give
+/// connection in time it is reported as 503 Service Unavailable.
+pub async fn block<F, R>(db: &Database, f: F) -> Result<R, Error>
+where
+    F: FnOnce(&mut DbConnection) -> R + Send + 'static,
+    R: Send + 'static,
+{
+    try_block(db, move |conn| Ok(f(conn))).await
+}
+
+/// The same as block() but for DAO code which can fail
+pub async fn try_block<F, R>(db: &Database, f: F) -> Result<R, Error>
+where
+    F: FnOnce(&mut DbConnection) -> DaoResult<R> + Send + 'static,
+    R: Send + 'static,
+{
+    let db = db.clone();
+    web::block(move || {
+        let mut conn = db.try_get_connection()?;
+        f(&mut conn)
+    })
+    .await?
+    .map_err(dao_error)
+}
+
+/// Map DaoError to HTTP error - validation errors are reported as 422 with field errors in body:
+/// `{"errors": [{"field": "salaries[0].to_date", "message": "..."}]}`, deleting record which is still
+/// referred to as 409 Conflict
+pub fn dao_error(e: DaoError) -> Error {
+    match e {
+        DaoError::Pool(_) => {
+            error!("{}", e);
+            InternalError::from_response(
+                e,
+                HttpResponse::ServiceUnavailable()
+                    .insert_header((RETRY_AFTER, "1"))
+                    .content_type("text/plain")
+                    .body("Database is busy - try again later"),
+            )
+            .into()
+        }
+        DaoError::Validation(ref errors) => {
+            let body = serde_json::json!({ "errors": errors });
+            InternalError::from_response(e, HttpResponse::UnprocessableEntity().json(body)).into()
+        }
+        e if e.is_not_found() => ErrorNotFound(e),
+        e if e.is_stale_version() => ErrorPreconditionFailed(e),
+        e if e.is_foreign_key_violation() => ErrorConflict(e),
+        e => {
+            error!("{}", e);
+            ErrorInternalServerError(e)
+        }
+    }
+}
@@ -0,0 +1,145 @@
+use actix_web::error::{ErrorInternalServerError, ErrorNotFound};
+use actix_web::http::Method;
+use actix_web::web::Json;
+use actix_web::{web, Error, HttpRequest, HttpResponse};
+use dao::{Crud, DaoError, Database, DepartmentDTO, Searchable};
+
+use crate::db;
+use crate::etag;
+use crate::session::LoggedGuard::{Logged, LoggedAsAdmin};
+
+async fn get_departments(db: web::Data<Database>) -> Result<HttpResponse, Error> {
+    let keys = db.keys();
+    let departments: Vec<DepartmentDTO> =
+        db::try_block(&db, move |conn| DepartmentDTO::get_all_with_connection(keys.as_deref(), conn)).await?;
+    let body = serde_json::to_string(&departments)?;
+    Ok(HttpResponse::Ok()
+        .content_type("application/json")
+        .body(body))
+}
+
+async fn get_department(db: web::Data<Database>, path: web::Path<String>) -> Result<HttpResponse, Error> {
+    let id: i32 = path.parse().unwrap();
+    let keys = db.keys();
+    match db::block(&db, move |conn| DepartmentDTO::get_with_conn(id, keys.as_deref(), conn)).await? {
+        Some(department) => etag::ok(&department, department.version.unwrap_or_default()),
+        None => Err(ErrorNotFound(format!(
+            "Can't find department with id = {}",
+            id
+        ))),
+    }
+}
+
+/// The department and all its sub-departments (recursively)
+async fn get_department_subtree(db: web::Data<Database>, path: web::Path<String>) -> Result<HttpResponse, Error> {
+    let id: i32 = path.parse().unwrap();
+    let departments = db::try_block(&db, move |conn| DepartmentDTO::subtree_with_connection(id, conn)).await?;
+    if departments.is_empty() {
+        return Err(ErrorNotFound(format!(
+            "Can't find department with id = {}",
+            id
+        )));
+    }
+    let body = serde_json::to_string(&departments)?;
+    Ok(HttpResponse::Ok()
+        .content_type("application/json")
+        .body(body))
+}
+
+/// 412 with current state of department
+async fn department_precondition_failed(db: &Database, id: i32) -> Result<HttpResponse, Error> {
+    let keys = db.keys();
+    let current = db::try_block(db, move |conn| Ok(DepartmentDTO::get_simple(id, keys.as_deref(), conn)?)).await?;
+    etag::precondition_failed(&current, current.version.unwrap_or_default())
+}
+
+/// Create department (without id) or update existing one. Update require If-Match with ETag
+/// of department it is based on - and so does every PUT.
+async fn update_department(
+    req: HttpRequest,
+    db: web::Data<Database>,
+    department_json: Json<DepartmentDTO>,
+) -> Result<HttpResponse, Error> {
+    let mut department = department_json.into_inner();
+    let if_match = if req.method() == Method::PUT || department.id.is_some() {
+        Some(etag::if_match(&req)?)
+    } else {
+        None
+    };
+    let rules = db.config().validation.clone();
+    let keys = db.keys();
+    let saved = db::block(&db, move |conn| {
+        if let (Some(if_match), Some(id)) = (&if_match, department.id) {
+            let current = DepartmentDTO::get_simple(id, keys.as_deref(), conn)?;
+            department.version = Some(etag::expected_version(
+                if_match,
+                id,
+                current.version.unwrap_or_default(),
+            )?);
+        }
+        department.try_persist_in_transaction(&rules, keys.as_deref(), conn)
+    })
+    .await?;
+    match saved {
+        Ok(department) => etag::ok(&department, department.version.unwrap_or_default()),
+        Err(DaoError::StaleVersion { id, .. }) => department_precondition_failed(&db, id).await,
+        Err(e) => Err(db::dao_error(e)),
+    }
+}
+
+/// Sub-departments and employees of deleted department are moved to its parent
+async fn delete_department(
+    req: HttpRequest,
+    db: web::Data<Database>,
+    path: web::Path<String>,
+) -> Result<HttpResponse, Error> {
+    let id: i32 = path.parse().unwrap();
+    let if_match = etag::if_match(&req)?;
+    let keys = db.keys();
+    let deleted = db::block(&db, move |conn| {
+        let mut department = DepartmentDTO::get_simple(id, keys.as_deref(), conn)?;
+        department.version = Some(etag::expected_version(
+            &if_match,
+            id,
+            department.version.unwrap_or_default(),
+        )?);
+        department.try_delete_with_conn(keys.as_deref(), conn)
+    })
+    .await?;
+    match deleted {
+        Ok(1) => Ok(HttpResponse::Ok()
+            .content_type("application/json")
+            .body(format!("Removed department with id = {}", id))),
+        Ok(n) => Err(ErrorInternalServerError(format!(
+            "Removed {} departments with id = {}",
+            n, id
+        ))),
+        Err(DaoError::StaleVersion { .. }) => department_precondition_failed(&db, id).await,
+        Err(e) if e.is_not_found() => Err(ErrorNotFound(format!(
+            "Not found department with id = {}",
+            id
+        ))),
+        Err(e) => Err(db::dao_error(e)),
+    }
+}
+
+pub fn config(cfg: &mut web::ServiceConfig, prefix: &str) {
+    cfg.service(
+        web::resource(prefix)
+            .wrap(LoggedAsAdmin(&[Method::PUT, Method::POST]))
+            .route(web::get().to(get_departments))
+            .route(web::put().to(update_department))
+            .route(web::post().to(update_department)),
+    );
+    cfg.service(
+        web::resource(format!("{}{}", prefix, "/{id}/subtree"))
+            .wrap(Logged)
+            .route(web::get().to(get_department_subtree)),
+    );
+    cfg.service(
+        web::resource(format!("{}{}", prefix, "/{id}"))
+            .wrap(LoggedAsAdmin(&[Method::DELETE]))
+            .route(web::get().to(get_department))
+            .route(web::delete().to(delete_department)),
+    );
+}
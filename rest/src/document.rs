@@ -0,0 +1,306 @@
+use actix_multipart::Multipart;
+use actix_web::error::{ErrorBadRequest, ErrorInternalServerError, ErrorNotFound, ErrorPayloadTooLarge};
+use actix_web::http::header::ContentDisposition;
+use actix_web::http::Method;
+use actix_web::{web, Error, HttpRequest, HttpResponse};
+use dao::{get_user, Crud, DaoResult, Database, DbConnection, DocumentDTO, DocumentUpload, EmployeeDTO};
+use futures::TryStreamExt;
+
+use crate::db;
+use crate::employee::{logged_user, scope};
+use crate::session::LoggedGuard::{Logged, LoggedAsAdmin};
+
+/// The biggest metadata of document - all parts but `file` together
+const MAX_METADATA_SIZE: usize = 64 * 1024;
+
+/// Field of `multipart/form-data` body
+#[derive(Debug, PartialEq)]
+struct Part {
+    name: String,
+    file_name: Option<String>,
+    content_type: Option<String>,
+    data: Vec<u8>,
+}
+
+/// Parts of `multipart/form-data` body read as they come - `file` part up to `max_size` bytes and other parts
+/// together up to MAX_METADATA_SIZE, so too big upload is rejected without being read whole
+async fn read_parts(mut multipart: Multipart, max_size: u64) -> Result<Vec<Part>, Error> {
+    let mut parts: Vec<Part> = Vec::new();
+    let mut metadata_size = 0;
+    while let Some(mut field) = multipart.try_next().await? {
+        let name = field.name().ok_or_else(|| ErrorBadRequest("Part without name"))?.to_string();
+        if name == "file" && parts.iter().any(|p| p.name == "file") {
+            return Err(ErrorBadRequest("There is more than one file part"));
+        }
+        let mut part = Part {
+            file_name: field
+                .content_disposition()
+                .and_then(|d| d.get_filename())
+                .map(str::to_string),
+            content_type: field.content_type().map(|t| t.to_string()),
+            name,
+            data: vec![],
+        };
+        while let Some(chunk) = field.try_next().await? {
+            if part.name == "file" {
+                if (part.data.len() + chunk.len()) as u64 > max_size {
+                    return Err(ErrorPayloadTooLarge(format!(
+                        "Document can't be bigger than {} bytes",
+                        max_size
+                    )));
+                }
+            } else {
+                metadata_size += chunk.len();
+                if metadata_size > MAX_METADATA_SIZE {
+                    return Err(ErrorPayloadTooLarge(format!(
+                        "Metadata of document can't be bigger than {} bytes",
+                        MAX_METADATA_SIZE
+                    )));
+                }
+            }
+            part.data.extend_from_slice(&chunk);
+        }
+        parts.push(part);
+    }
+    Ok(parts)
+}
+
+/// DocumentUpload from `file` part (its file name and content type) and other parts as metadata fields
+fn document_upload(parts: Vec<Part>) -> Result<(DocumentUpload, Vec<u8>), Error> {
+    let mut fields = serde_json::Map::new();
+    let mut file = None;
+    for part in parts {
+        if part.name == "file" {
+            fields.insert(
+                "content_type".to_string(),
+                part.content_type.clone().unwrap_or_else(|| "application/octet-stream".to_string()).into(),
+            );
+            if let Some(file_name) = &part.file_name {
+                fields.entry("file_name").or_insert_with(|| file_name.clone().into());
+            }
+            file = Some(part.data);
+        } else {
+            let value = String::from_utf8(part.data)
+                .map_err(|_| ErrorBadRequest(format!("{} is not UTF-8", part.name)))?;
+            fields.insert(part.name, value.into());
+        }
+    }
+    let file = file.ok_or_else(|| ErrorBadRequest("There is no file part"))?;
+    let upload = serde_json::from_value(fields.into()).map_err(|e| ErrorBadRequest(e.to_string()))?;
+    Ok((upload, file))
+}
+
+/// Document of employee logged user can read - None when the employee is out of scope, there is no such document
+/// or it is too confidential for the user
+fn readable_document(user_id: i32, e_id: i32, d_id: i32, conn: &mut DbConnection) -> DaoResult<Option<DocumentDTO>> {
+    let user = match get_user(user_id, conn) {
+        Some(user) => user,
+        None => return Ok(None),
+    };
+    match DocumentDTO::get_with_connection(e_id, d_id, conn) {
+        Ok(document) if document.readable_by(&user, conn)? => Ok(Some(document)),
+        _ => Ok(None),
+    }
+}
+
+fn document_not_found(e_id: i32, d_id: i32) -> Error {
+    ErrorNotFound(format!(
+        "Can't find document with id = {} of employee with id = {}",
+        d_id, e_id
+    ))
+}
+
+/// Documents of employee logged user can read - employee out of scope is reported as not found
+async fn get_documents(
+    req: HttpRequest,
+    db: web::Data<Database>,
+    path: web::Path<String>,
+) -> Result<HttpResponse, Error> {
+    let e_id: i32 = path.parse().unwrap();
+    let user_id = logged_user(&req)?;
+    let keys = db.keys();
+    let documents = db::try_block(&db, move |conn| {
+        let user = match get_user(user_id, conn) {
+            Some(user) => user,
+            None => return Ok(None),
+        };
+        if !scope(user_id, conn)?.contains(e_id, conn)?
+            || EmployeeDTO::get_with_conn(e_id, keys.as_deref(), conn).is_none()
+        {
+            return Ok(None);
+        }
+        let mut readable = vec![];
+        for document in DocumentDTO::of_employee_with_connection(e_id, conn)? {
+            if document.readable_by(&user, conn)? {
+                readable.push(document);
+            }
+        }
+        Ok(Some(readable))
+    })
+    .await?;
+    match documents {
+        Some(documents) => {
+            let body = serde_json::to_string(&documents)?;
+            Ok(HttpResponse::Ok()
+                .content_type("application/json")
+                .body(body))
+        }
+        None => Err(ErrorNotFound(format!(
+            "Can't find employee with id = {}",
+            e_id
+        ))),
+    }
+}
+
+async fn get_document(
+    req: HttpRequest,
+    db: web::Data<Database>,
+    path: web::Path<(String, String)>,
+) -> Result<HttpResponse, Error> {
+    let e_id: i32 = path.0.parse().unwrap();
+    let d_id: i32 = path.1.parse().unwrap();
+    let user_id = logged_user(&req)?;
+    let document = db::try_block(&db, move |conn| readable_document(user_id, e_id, d_id, conn)).await?;
+    let document = document.ok_or_else(|| document_not_found(e_id, d_id))?;
+    let body = serde_json::to_string(&document)?;
+    Ok(HttpResponse::Ok()
+        .content_type("application/json")
+        .body(body))
+}
+
+/// Content of document as attachment - its checksum is verified before it is sent
+async fn get_document_content(
+    req: HttpRequest,
+    db: web::Data<Database>,
+    path: web::Path<(String, String)>,
+) -> Result<HttpResponse, Error> {
+    let e_id: i32 = path.0.parse().unwrap();
+    let d_id: i32 = path.1.parse().unwrap();
+    let user_id = logged_user(&req)?;
+    let database = db.get_ref().clone();
+    let document = db::try_block(&db, move |conn| {
+        readable_document(user_id, e_id, d_id, conn)?
+            .map(|d| d.content(database.storage()).map(|content| (d, content)))
+            .transpose()
+    })
+    .await?;
+    let (document, content) = document.ok_or_else(|| document_not_found(e_id, d_id))?;
+    Ok(HttpResponse::Ok()
+        .content_type(document.content_type.as_str())
+        .insert_header(ContentDisposition::attachment(document.file_name))
+        .body(content))
+}
+
+/// Upload document as `multipart/form-data` - `file` part with content (its file name and Content-Type are used)
+/// and `document_type`, `valid_from`, `valid_to`, `confidentiality`, `sha256` (and `file_name` to override name
+/// of file) parts with metadata (see DocumentUpload)
+async fn upload_document(
+    req: HttpRequest,
+    db: web::Data<Database>,
+    path: web::Path<String>,
+    multipart: Multipart,
+) -> Result<HttpResponse, Error> {
+    let e_id: i32 = path.parse().unwrap();
+    let user_id = logged_user(&req)?;
+    let parts = read_parts(multipart, db.config().documents.max_size).await?;
+    let (upload, content) = document_upload(parts)?;
+    let database = db.get_ref().clone();
+    let saved = db::block(&db, move |conn| {
+        let documents = &database.config().documents;
+        DocumentDTO::upload_with_connection(e_id, &upload, &content, Some(user_id), documents, database.storage(), conn)
+    })
+    .await?;
+    match saved {
+        Ok(document) => {
+            let body = serde_json::to_string(&document)?;
+            Ok(HttpResponse::Created()
+                .content_type("application/json")
+                .body(body))
+        }
+        Err(e) if e.is_not_found() => Err(ErrorNotFound(format!(
+            "Can't find employee with id = {}",
+            e_id
+        ))),
+        Err(e) => Err(db::dao_error(e)),
+    }
+}
+
+/// Delete document with its content
+async fn delete_document(
+    db: web::Data<Database>,
+    path: web::Path<(String, String)>,
+) -> Result<HttpResponse, Error> {
+    let e_id: i32 = path.0.parse().unwrap();
+    let d_id: i32 = path.1.parse().unwrap();
+    let database = db.get_ref().clone();
+    let deleted = db::block(&db, move |conn| DocumentDTO::delete_with_connection(e_id, d_id, database.storage(), conn))
+        .await?;
+    match deleted {
+        Ok(1) => Ok(HttpResponse::Ok()
+            .content_type("application/json")
+            .body(format!("Removed document with id = {}", d_id))),
+        Ok(n) => Err(ErrorInternalServerError(format!(
+            "Removed {} documents with id = {}",
+            n, d_id
+        ))),
+        Err(e) if e.is_not_found() => Err(document_not_found(e_id, d_id)),
+        Err(e) => Err(db::dao_error(e)),
+    }
+}
+
+/// Documents as sub-resource of employees - `prefix` is prefix of employees. Upload and delete are just for admins,
+/// documents can be read by users who see their employee (and confidentiality of document allows it).
+pub fn config(cfg: &mut web::ServiceConfig, prefix: &str) {
+    cfg.service(
+        web::resource(format!("{}{}", prefix, "/{id}/documents"))
+            .wrap(LoggedAsAdmin(&[Method::POST]))
+            .route(web::get().to(get_documents))
+            .route(web::post().to(upload_document)),
+    );
+    cfg.service(
+        web::resource(format!("{}{}", prefix, "/{id}/documents/{document_id}"))
+            .wrap(LoggedAsAdmin(&[Method::DELETE]))
+            .route(web::get().to(get_document))
+            .route(web::delete().to(delete_document)),
+    );
+    cfg.service(
+        web::resource(format!("{}{}", prefix, "/{id}/documents/{document_id}/content"))
+            .wrap(Logged)
+            .route(web::get().to(get_document_content)),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn upload_is_made_of_parts() {
+        let parts = vec![
+            Part {
+                name: "document_type".to_string(),
+                file_name: None,
+                content_type: None,
+                data: b"contract".to_vec(),
+            },
+            Part {
+                name: "file".to_string(),
+                file_name: Some("umowa \"A\".pdf".to_string()),
+                content_type: Some("application/pdf".to_string()),
+                data: b"%PDF-1.4\r\n--Xy".to_vec(),
+            },
+        ];
+        let (upload, content) = document_upload(parts).unwrap();
+        assert_eq!(upload.file_name, "umowa \"A\".pdf");
+        assert_eq!(upload.content_type, "application/pdf");
+        assert_eq!(content, b"%PDF-1.4\r\n--Xy");
+
+        let without_file = vec![Part {
+            name: "document_type".to_string(),
+            file_name: None,
+            content_type: None,
+            data: b"contract".to_vec(),
+        }];
+        assert!(document_upload(without_file).is_err());
+    }
+}
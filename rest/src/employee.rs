@@ -1,28 +1,130 @@
 use actix_web::web::Json;
-use actix_web::{web, Error, HttpResponse};
-use actix_web::error::{ErrorInternalServerError, ErrorNotFound};
+use actix_web::{web, Error, HttpRequest, HttpResponse};
+use actix_web::error::{ErrorForbidden, ErrorInternalServerError, ErrorNotFound, ErrorUnauthorized};
 use actix_web::http::Method;
-use dao::{Crud, EmployeeDTO, Searchable};
+use chrono::{Local, NaiveDate};
+use dao::{
+    anonymize_employee_with_connection, get_user, personal_data_export_with_connection, Crud, DaoError, DaoResult,
+    Database, DbConnection, EmployeeDTO, EmployeeGroupDTO, EmployeeScope, EmployeeSearch, KeyRing,
+};
 
+use crate::db;
+use crate::etag;
+use crate::session::logged_user_id;
 use crate::session::LoggedGuard::{Logged, LoggedAsAdmin};
 
-async fn get_employees() -> Result<HttpResponse, Error> {
-    let employees: Vec<EmployeeDTO> = EmployeeDTO::get_all();
+/// Id of logged user - 401 when session is gone
+pub fn logged_user(req: &HttpRequest) -> Result<i32, Error> {
+    logged_user_id(req).ok_or_else(|| ErrorUnauthorized("Not logged in"))
+}
+
+/// Employees logged user can see - DaoError::not_found() when the user doesn't exist anymore
+pub fn scope(user_id: i32, conn: &mut DbConnection) -> DaoResult<EmployeeScope> {
+    EmployeeScope::for_user(user_id, conn).ok_or_else(DaoError::not_found)
+}
+
+/// Members (in scope) of saved group reports and exports are limited to - None when no group is given,
+/// DaoError::not_found() when there is no such group
+pub fn group_members(
+    group: Option<i32>,
+    scope: EmployeeScope,
+    keys: Option<&KeyRing>,
+    conn: &mut DbConnection,
+) -> DaoResult<Option<Vec<i32>>> {
+    group
+        .map(|g_id| EmployeeGroupDTO::member_ids_with_connection(g_id, scope, keys, conn))
+        .transpose()
+}
+
+/// Salaries are shown just to admins - employees are stripped of them for other users. Every response with
+/// employee (412 with current state too) goes through it.
+pub fn strip_salaries<'a>(
+    user_id: i32,
+    employees: impl IntoIterator<Item = &'a mut EmployeeDTO>,
+    conn: &mut DbConnection,
+) {
+    if !get_user(user_id, conn).is_some_and(|u| u.is_admin) {
+        employees.into_iter().for_each(|e| e.salaries.clear());
+    }
+}
+
+/// EmployeeSearch from query string - web::Query can't read repeated `tag`, so tags are read separately
+pub fn employee_search(query: &str) -> Result<EmployeeSearch, Error> {
+    let (tags, others): (Vec<&str>, Vec<&str>) = query.split('&').partition(|p| p.starts_with("tag="));
+    let mut search = web::Query::<EmployeeSearch>::from_query(&others.join("&"))?.into_inner();
+    search.tag = web::Query::<Vec<(String, String)>>::from_query(&tags.join("&"))?
+        .into_inner()
+        .into_iter()
+        .map(|(_, tag)| tag)
+        .collect();
+    Ok(search)
+}
+
+/// Employees in scope of logged user - `?q=&status=&born_on=&hired_from=&hired_to=&national_id=&phone=&custom_field=`
+/// and `&tag=` (repeated, `!` excludes tag) narrow them (see EmployeeSearch). Deleted employees are listed (with
+/// `?include_deleted=true`) just to admins. Salaries are listed just to admins too (as they are exported) - other users
+/// get employees without them.
+async fn get_employees(req: HttpRequest, db: web::Data<Database>) -> Result<HttpResponse, Error> {
+    let user_id = logged_user(&req)?;
+    let search = employee_search(req.query_string())?;
+    let keys = db.keys();
+    let employees: Option<Vec<EmployeeDTO>> = db::try_block(&db, move |conn| {
+        let is_admin = get_user(user_id, conn).is_some_and(|u| u.is_admin);
+        if search.include_deleted && !is_admin {
+            return Ok(None);
+        }
+        let scope = scope(user_id, conn)?;
+        let mut found = EmployeeDTO::search_in_scope_with_connection(&search, scope, keys.as_deref(), conn)?;
+        strip_salaries(user_id, &mut found, conn);
+        Ok(Some(found))
+    })
+    .await?;
+    let employees = employees.ok_or_else(|| ErrorForbidden("Just admin can list deleted employees"))?;
     let body = serde_json::to_string(&employees)?;
     Ok(HttpResponse::Ok()
         .content_type("application/json")
         .body(body))
 }
 
-async fn get_employee(path: web::Path<String>) -> Result<HttpResponse, Error> {
+/// `?on=YYYY-MM-DD` - "as of date" query
+#[derive(Deserialize, Debug)]
+pub struct OnDate {
+    pub on: Option<NaiveDate>,
+}
+
+/// Whole employee or (with `?on=`) employee with just salary and contact valid on given date.
+/// The latter is read-only view - it has no ETag so it can't be used to update employee.
+/// Employee out of scope of logged user is reported as not found, salaries are shown just to admins.
+async fn get_employee(
+    req: HttpRequest,
+    db: web::Data<Database>,
+    path: web::Path<String>,
+    query: web::Query<OnDate>,
+) -> Result<HttpResponse, Error> {
     let id: i32 = path.parse().unwrap();
-    match EmployeeDTO::get(id) {
-        Some(employee) => {
+    let on = query.on;
+    let user_id = logged_user(&req)?;
+    let keys = db.keys();
+    let employee = db::try_block(&db, move |conn| {
+        if !scope(user_id, conn)?.contains(id, conn)? {
+            return Ok(None);
+        }
+        let mut employee = match on {
+            Some(on) => EmployeeDTO::get_effective_on_with_conn(id, on, keys.as_deref(), conn)?,
+            None => EmployeeDTO::try_get_with_conn(id, keys.as_deref(), conn)?,
+        };
+        strip_salaries(user_id, &mut employee, conn);
+        Ok(employee)
+    })
+    .await?;
+    match employee {
+        Some(employee) if on.is_some() => {
             let body = serde_json::to_string(&employee)?;
             Ok(HttpResponse::Ok()
                 .content_type("application/json")
                 .body(body))
         }
+        Some(employee) => etag::ok(&employee, employee.version.unwrap_or_default()),
         None => Err(ErrorNotFound(format!(
             "Can't find employee with id = {}",
             id
@@ -30,45 +132,237 @@ async fn get_employee(path: web::Path<String>) -> Result<HttpResponse, Error> {
     }
 }
 
-async fn update_employee(employee_json: Json<EmployeeDTO>) -> Result<HttpResponse, Error> {
-    let mut employee = employee_json.clone();
-    match employee.persist() {
-        Some(employee) => {
-            let body = serde_json::to_string(&employee)?;
+/// All employees reporting (directly or not) to employee
+async fn get_employee_reports(
+    req: HttpRequest,
+    db: web::Data<Database>,
+    path: web::Path<String>,
+) -> Result<HttpResponse, Error> {
+    let id: i32 = path.parse().unwrap();
+    let user_id = logged_user(&req)?;
+    let keys = db.keys();
+    let reports = db::try_block(&db, move |conn| {
+        if !scope(user_id, conn)?.contains(id, conn)?
+            || EmployeeDTO::try_get_with_conn(id, keys.as_deref(), conn)?.is_none()
+        {
+            return Ok(None);
+        }
+        let mut reports = EmployeeDTO::reports_of_with_connection(id, keys.as_deref(), conn)?;
+        strip_salaries(user_id, &mut reports, conn);
+        Ok(Some(reports))
+    })
+    .await?;
+    match reports {
+        Some(reports) => {
+            let body = serde_json::to_string(&reports)?;
             Ok(HttpResponse::Ok()
                 .content_type("application/json")
                 .body(body))
         }
-        None => Err(ErrorInternalServerError(format!(
-            "Failed to update employee {:?}",
-            employee_json
+        None => Err(ErrorNotFound(format!(
+            "Can't find employee with id = {}",
+            id
         ))),
     }
 }
 
-async fn delete_employee(path: web::Path<String>) -> Result<HttpResponse, Error> {
+/// Employees without contact valid on `?on=` date (today by default)
+async fn get_employees_without_contact(
+    req: HttpRequest,
+    db: web::Data<Database>,
+    query: web::Query<OnDate>,
+) -> Result<HttpResponse, Error> {
+    let on = query.on.unwrap_or_else(|| Local::now().date_naive());
+    let user_id = logged_user(&req)?;
+    let keys = db.keys();
+    let employees: Vec<EmployeeDTO> = db::try_block(&db, move |conn| {
+        let scope = scope(user_id, conn)?;
+        let mut employees = EmployeeDTO::without_contact_on_with_connection(on, scope, keys.as_deref(), conn)?;
+        strip_salaries(user_id, &mut employees, conn);
+        Ok(employees)
+    })
+    .await?;
+    let body = serde_json::to_string(&employees)?;
+    Ok(HttpResponse::Ok()
+        .content_type("application/json")
+        .body(body))
+}
+
+/// 412 with current state of employee (deleted one too)
+async fn employee_precondition_failed(db: &Database, id: i32, user_id: i32) -> Result<HttpResponse, Error> {
+    let keys = db.keys();
+    let current = db::try_block(db, move |conn| {
+        let mut current = EmployeeDTO::get_including_deleted_with_connection(id, keys.as_deref(), conn)?;
+        strip_salaries(user_id, [&mut current], conn);
+        Ok(current)
+    })
+    .await?;
+    etag::precondition_failed(&current, current.version.unwrap_or_default())
+}
+
+/// Create employee (without id) or update existing one. Update require If-Match with ETag
+/// of employee it is based on - and so does every PUT.
+async fn update_employee(
+    req: HttpRequest,
+    db: web::Data<Database>,
+    employee_json: Json<EmployeeDTO>,
+) -> Result<HttpResponse, Error> {
+    let user_id = logged_user(&req)?;
+    let mut employee = employee_json.into_inner();
+    let if_match = if req.method() == Method::PUT || employee.id.is_some() {
+        Some(etag::if_match(&req)?)
+    } else {
+        None
+    };
+    let rules = db.config().validation.clone();
+    let keys = db.keys();
+    let saved = db::block(&db, move |conn| {
+        if let (Some(if_match), Some(id)) = (&if_match, employee.id) {
+            let current = EmployeeDTO::get_simple(id, keys.as_deref(), conn)?;
+            employee.version = Some(etag::expected_version(
+                if_match,
+                id,
+                current.version.unwrap_or_default(),
+            )?);
+        }
+        let mut saved = employee.try_persist_in_transaction(&rules, keys.as_deref(), conn);
+        strip_salaries(user_id, &mut saved, conn);
+        saved
+    })
+    .await?;
+    match saved {
+        Ok(employee) => etag::ok(&employee, employee.version.unwrap_or_default()),
+        Err(DaoError::StaleVersion { id, .. }) => employee_precondition_failed(&db, id, user_id).await,
+        Err(e) => Err(db::dao_error(e)),
+    }
+}
+
+/// Soft delete - employee is kept (and can be restored) till DELETED_RETENTION_DAYS pass
+async fn delete_employee(
+    req: HttpRequest,
+    db: web::Data<Database>,
+    path: web::Path<String>,
+) -> Result<HttpResponse, Error> {
     let id: i32 = path.parse().unwrap();
-    let employee = EmployeeDTO::get(id);
-    match employee {
-        Some(e) => match e.delete() {
-            Some(n) if n == 1 => Ok(HttpResponse::Ok()
-                .content_type("application/json")
-                .body(format!("Removed employee with id = {}", id))),
-            Some(n) => Err(ErrorInternalServerError(format!(
-                "Removed {} employees with id = {}",
-                n, id
-            ))),
-            None => Err(ErrorInternalServerError("???")),
-        },
-        None => Err(ErrorNotFound(format!(
+    let user_id = logged_user(&req)?;
+    let if_match = etag::if_match(&req)?;
+    let keys = db.keys();
+    let deleted = db::block(&db, move |conn| {
+        let employee = EmployeeDTO::get_simple(id, keys.as_deref(), conn)?;
+        let expected = etag::expected_version(&if_match, id, employee.version.unwrap_or_default())?;
+        EmployeeDTO::delete_by_with_connection(id, expected, user_id, keys.as_deref(), conn)
+    })
+    .await?;
+    match deleted {
+        Ok(1) => Ok(HttpResponse::Ok()
+            .content_type("application/json")
+            .body(format!("Removed employee with id = {}", id))),
+        Ok(n) => Err(ErrorInternalServerError(format!(
+            "Removed {} employees with id = {}",
+            n, id
+        ))),
+        Err(DaoError::StaleVersion { .. }) => employee_precondition_failed(&db, id, user_id).await,
+        Err(e) if e.is_not_found() => Err(ErrorNotFound(format!(
             "Not found employee with id = {}",
             id
         ))),
+        Err(e) => Err(db::dao_error(e)),
+    }
+}
+
+/// Bring deleted employee back - require If-Match with ETag of the deleted employee (see
+/// `GET /employees?include_deleted=true`). Employee which is not deleted is reported as not found.
+async fn restore_employee(
+    req: HttpRequest,
+    db: web::Data<Database>,
+    path: web::Path<String>,
+) -> Result<HttpResponse, Error> {
+    let id: i32 = path.parse().unwrap();
+    let user_id = logged_user(&req)?;
+    let if_match = etag::if_match(&req)?;
+    let keys = db.keys();
+    let restored = db::block(&db, move |conn| {
+        let employee = EmployeeDTO::get_including_deleted_with_connection(id, keys.as_deref(), conn)?;
+        let expected = etag::expected_version(&if_match, id, employee.version.unwrap_or_default())?;
+        let mut restored = EmployeeDTO::restore_with_connection(id, expected, keys.as_deref(), conn);
+        strip_salaries(user_id, &mut restored, conn);
+        restored
+    })
+    .await?;
+    match restored {
+        Ok(employee) => etag::ok(&employee, employee.version.unwrap_or_default()),
+        Err(DaoError::StaleVersion { .. }) => employee_precondition_failed(&db, id, user_id).await,
+        Err(e) if e.is_not_found() => Err(ErrorNotFound(format!(
+            "Not found deleted employee with id = {}",
+            id
+        ))),
+        Err(e) => Err(db::dao_error(e)),
+    }
+}
+
+/// Whether the user may export and anonymize personal data - admin alone may not
+fn is_privacy_officer(user_id: i32, conn: &mut DbConnection) -> bool {
+    get_user(user_id, conn).is_some_and(|u| u.privacy_officer)
+}
+
+/// All personal data of employee (deleted one too) - just for privacy officers, the export is audited
+async fn get_personal_data_export(
+    req: HttpRequest,
+    db: web::Data<Database>,
+    path: web::Path<String>,
+) -> Result<HttpResponse, Error> {
+    let user_id = logged_user(&req)?;
+    let id: i32 = path.parse().unwrap();
+    let keys = db.keys();
+    let export = db::try_block(&db, move |conn| {
+        if !is_privacy_officer(user_id, conn) {
+            return Ok(None);
+        }
+        personal_data_export_with_connection(id, user_id, keys.as_deref(), conn).map(Some)
+    })
+    .await?;
+    let export = export.ok_or_else(|| ErrorForbidden("Just privacy officer can export personal data"))?;
+    let body = serde_json::to_string(&export)?;
+    Ok(HttpResponse::Ok()
+        .content_type("application/json")
+        .body(body))
+}
+
+/// Irreversibly replace personal data of employee in version given by If-Match - just for privacy officers,
+/// the anonymization is audited
+async fn anonymize_employee(
+    req: HttpRequest,
+    db: web::Data<Database>,
+    path: web::Path<String>,
+) -> Result<HttpResponse, Error> {
+    let user_id = logged_user(&req)?;
+    let id: i32 = path.parse().unwrap();
+    let if_match = etag::if_match(&req)?;
+    if !db::block(&db, move |conn| is_privacy_officer(user_id, conn)).await? {
+        return Err(ErrorForbidden("Just privacy officer can anonymize employee"));
+    }
+    let database = db.get_ref().clone();
+    let keys = db.keys();
+    let anonymized = db::block(&db, move |conn| {
+        let employee = EmployeeDTO::get_including_deleted_with_connection(id, keys.as_deref(), conn)?;
+        let expected = etag::expected_version(&if_match, id, employee.version.unwrap_or_default())?;
+        let mut anonymized =
+            anonymize_employee_with_connection(id, expected, user_id, database.storage(), keys.as_deref(), conn);
+        strip_salaries(user_id, &mut anonymized, conn);
+        anonymized
+    })
+    .await?;
+    match anonymized {
+        Ok(employee) => etag::ok(&employee, employee.version.unwrap_or_default()),
+        Err(DaoError::StaleVersion { .. }) => employee_precondition_failed(&db, id, user_id).await,
+        Err(e) => Err(db::dao_error(e)),
     }
 }
 
-async fn get_employee_template() -> Result<HttpResponse, Error> {
-    let body = "NOT YET IMPLEMENTED".to_string();
+/// Empty employee with every custom field defined by admin (see EmployeeDTO::template())
+async fn get_employee_template(db: web::Data<Database>) -> Result<HttpResponse, Error> {
+    let template = db::try_block(&db, EmployeeDTO::template_with_connection).await?;
+    let body = serde_json::to_string(&template)?;
     Ok(HttpResponse::Ok()
         .content_type("application/json")
         .body(body))
@@ -87,6 +381,31 @@ pub fn config(cfg: &mut web::ServiceConfig, prefix: &str) {
             .wrap(Logged)
             .route(web::get().to(get_employee_template)),
     );
+    cfg.service(
+        web::resource(format!("{}{}", prefix, "/without-contact"))
+            .wrap(Logged)
+            .route(web::get().to(get_employees_without_contact)),
+    );
+    cfg.service(
+        web::resource(format!("{}{}", prefix, "/{id}/reports"))
+            .wrap(Logged)
+            .route(web::get().to(get_employee_reports)),
+    );
+    cfg.service(
+        web::resource(format!("{}{}", prefix, "/{id}/restore"))
+            .wrap(LoggedAsAdmin(&[Method::POST]))
+            .route(web::post().to(restore_employee)),
+    );
+    cfg.service(
+        web::resource(format!("{}{}", prefix, "/{id}/personal-data-export"))
+            .wrap(Logged)
+            .route(web::get().to(get_personal_data_export)),
+    );
+    cfg.service(
+        web::resource(format!("{}{}", prefix, "/{id}/anonymize"))
+            .wrap(Logged)
+            .route(web::post().to(anonymize_employee)),
+    );
     cfg.service(
         web::resource(format!("{}{}", prefix, "/{id}"))
             .wrap(LoggedAsAdmin(&[Method::DELETE]))
@@ -0,0 +1,151 @@
+use actix_web::error::{ErrorInternalServerError, ErrorNotFound};
+use actix_web::http::Method;
+use actix_web::web::Json;
+use actix_web::{web, Error, HttpRequest, HttpResponse};
+use dao::{Crud, DaoError, Database, EmployeeGroupDTO, Searchable};
+
+use crate::db;
+use crate::employee::{logged_user, scope, strip_salaries};
+use crate::etag;
+use crate::session::LoggedGuard::{Logged, LoggedAsAdmin};
+
+async fn get_employee_groups(db: web::Data<Database>) -> Result<HttpResponse, Error> {
+    let keys = db.keys();
+    let employee_groups: Vec<EmployeeGroupDTO> =
+        db::try_block(&db, move |conn| EmployeeGroupDTO::get_all_with_connection(keys.as_deref(), conn)).await?;
+    let body = serde_json::to_string(&employee_groups)?;
+    Ok(HttpResponse::Ok()
+        .content_type("application/json")
+        .body(body))
+}
+
+async fn get_employee_group(db: web::Data<Database>, path: web::Path<String>) -> Result<HttpResponse, Error> {
+    let id: i32 = path.parse().unwrap();
+    let keys = db.keys();
+    match db::block(&db, move |conn| EmployeeGroupDTO::get_with_conn(id, keys.as_deref(), conn)).await? {
+        Some(employee_group) => etag::ok(&employee_group, employee_group.version.unwrap_or_default()),
+        None => Err(ErrorNotFound(format!(
+            "Can't find employee group with id = {}",
+            id
+        ))),
+    }
+}
+
+/// 412 with current state of employee group
+async fn employee_group_precondition_failed(db: &Database, id: i32) -> Result<HttpResponse, Error> {
+    let keys = db.keys();
+    let current = db::try_block(db, move |conn| Ok(EmployeeGroupDTO::get_simple(id, keys.as_deref(), conn)?)).await?;
+    etag::precondition_failed(&current, current.version.unwrap_or_default())
+}
+
+/// Create employee group (without id) or update existing one. Update require If-Match with ETag
+/// of employee group it is based on - and so does every PUT.
+async fn update_employee_group(
+    req: HttpRequest,
+    db: web::Data<Database>,
+    employee_group_json: Json<EmployeeGroupDTO>,
+) -> Result<HttpResponse, Error> {
+    let mut employee_group = employee_group_json.into_inner();
+    let if_match = if req.method() == Method::PUT || employee_group.id.is_some() {
+        Some(etag::if_match(&req)?)
+    } else {
+        None
+    };
+    let rules = db.config().validation.clone();
+    let keys = db.keys();
+    let saved = db::block(&db, move |conn| {
+        if let (Some(if_match), Some(id)) = (&if_match, employee_group.id) {
+            let current = EmployeeGroupDTO::get_simple(id, keys.as_deref(), conn)?;
+            employee_group.version = Some(etag::expected_version(
+                if_match,
+                id,
+                current.version.unwrap_or_default(),
+            )?);
+        }
+        employee_group.try_persist_in_transaction(&rules, keys.as_deref(), conn)
+    })
+    .await?;
+    match saved {
+        Ok(employee_group) => etag::ok(&employee_group, employee_group.version.unwrap_or_default()),
+        Err(DaoError::StaleVersion { id, .. }) => employee_group_precondition_failed(&db, id).await,
+        Err(e) => Err(db::dao_error(e)),
+    }
+}
+
+/// Employees of deleted group are not changed
+async fn delete_employee_group(
+    req: HttpRequest,
+    db: web::Data<Database>,
+    path: web::Path<String>,
+) -> Result<HttpResponse, Error> {
+    let id: i32 = path.parse().unwrap();
+    let if_match = etag::if_match(&req)?;
+    let keys = db.keys();
+    let deleted = db::block(&db, move |conn| {
+        let mut employee_group = EmployeeGroupDTO::get_simple(id, keys.as_deref(), conn)?;
+        employee_group.version = Some(etag::expected_version(
+            &if_match,
+            id,
+            employee_group.version.unwrap_or_default(),
+        )?);
+        employee_group.try_delete_with_conn(keys.as_deref(), conn)
+    })
+    .await?;
+    match deleted {
+        Ok(1) => Ok(HttpResponse::Ok()
+            .content_type("application/json")
+            .body(format!("Removed employee group with id = {}", id))),
+        Ok(n) => Err(ErrorInternalServerError(format!(
+            "Removed {} employee groups with id = {}",
+            n, id
+        ))),
+        Err(DaoError::StaleVersion { .. }) => employee_group_precondition_failed(&db, id).await,
+        Err(e) if e.is_not_found() => Err(ErrorNotFound(format!(
+            "Not found employee group with id = {}",
+            id
+        ))),
+        Err(e) => Err(db::dao_error(e)),
+    }
+}
+
+/// Current members of group logged user can see - salaries are shown just to admins
+async fn get_members(
+    req: HttpRequest,
+    db: web::Data<Database>,
+    path: web::Path<String>,
+) -> Result<HttpResponse, Error> {
+    let user_id = logged_user(&req)?;
+    let id: i32 = path.parse().unwrap();
+    let keys = db.keys();
+    let members = db::try_block(&db, move |conn| {
+        let scope = scope(user_id, conn)?;
+        let mut members = EmployeeGroupDTO::members_with_connection(id, scope, keys.as_deref(), conn)?;
+        strip_salaries(user_id, &mut members, conn);
+        Ok(members)
+    })
+    .await?;
+    let body = serde_json::to_string(&members)?;
+    Ok(HttpResponse::Ok()
+        .content_type("application/json")
+        .body(body))
+}
+
+pub fn config(cfg: &mut web::ServiceConfig, prefix: &str) {
+    cfg.service(
+        web::resource(prefix)
+            .wrap(LoggedAsAdmin(&[Method::PUT, Method::POST]))
+            .route(web::get().to(get_employee_groups))
+            .route(web::put().to(update_employee_group))
+            .route(web::post().to(update_employee_group)),
+    );
+    cfg.service(
+        web::resource(format!("{}{}", prefix, "/{id}"))
+            .wrap(LoggedAsAdmin(&[Method::DELETE]))
+            .route(web::get().to(get_employee_group))
+            .route(web::delete().to(delete_employee_group)),
+    );    cfg.service(
+        web::resource(format!("{}{}", prefix, "/{id}/employees"))
+            .wrap(Logged)
+            .route(web::get().to(get_members)),
+    );
+}
@@ -0,0 +1,53 @@
+use actix_web::error::{ErrorBadRequest, ErrorPreconditionRequired};
+use actix_web::http::header::{EntityTag, ETag, Header, IfMatch, IF_MATCH};
+use actix_web::{Error, HttpRequest, HttpResponse};
+use dao::{DaoError, DaoResult};
+use serde::Serialize;
+
+/// ETag of record is its row version
+pub fn etag(version: i32) -> ETag {
+    ETag(EntityTag::new_strong(version.to_string()))
+}
+
+/// Requests modifying existing record have to carry If-Match with ETag of the record they are based on -
+/// 428 Precondition Required when it is missing
+pub fn if_match(req: &HttpRequest) -> Result<IfMatch, Error> {
+    if !req.headers().contains_key(IF_MATCH) {
+        return Err(ErrorPreconditionRequired(
+            "If-Match header with ETag of modified record is required",
+        ));
+    }
+    IfMatch::parse(req).map_err(ErrorBadRequest)
+}
+
+/// Version which should be written when If-Match match current version of record -
+/// DaoError::StaleVersion otherwise. DAO check it again when record is written.
+pub fn expected_version(if_match: &IfMatch, id: i32, current: i32) -> DaoResult<i32> {
+    let matched = match if_match {
+        IfMatch::Any => true,
+        IfMatch::Items(tags) => tags.iter().any(|tag| tag.strong_eq(&etag(current))),
+    };
+    if matched {
+        Ok(current)
+    } else {
+        Err(DaoError::StaleVersion { id, actual: current })
+    }
+}
+
+/// 200 OK with record as JSON and its ETag
+pub fn ok<T: Serialize>(record: &T, version: i32) -> Result<HttpResponse, Error> {
+    let body = serde_json::to_string(record)?;
+    Ok(HttpResponse::Ok()
+        .content_type("application/json")
+        .insert_header(etag(version))
+        .body(body))
+}
+
+/// 412 Precondition Failed with current record as JSON and its ETag - so client can merge changes and retry
+pub fn precondition_failed<T: Serialize>(current: &T, version: i32) -> Result<HttpResponse, Error> {
+    let body = serde_json::to_string(current)?;
+    Ok(HttpResponse::PreconditionFailed()
+        .content_type("application/json")
+        .insert_header(etag(version))
+        .body(body))
+}
@@ -0,0 +1,527 @@
+use actix_web::error::{ErrorBadRequest, ErrorForbidden, ErrorInternalServerError};
+use actix_web::http::header::{ContentDisposition, DispositionParam, DispositionType};
+use actix_web::{web, Error, HttpRequest, HttpResponse};
+use chrono::format::{Item, StrftimeItems};
+use chrono::{Local, NaiveDate};
+use dao::{get_user, ContactDTO, Database, EmployeeDTO, SalaryDTO};
+
+use crate::db;
+use crate::employee::{employee_search, group_members, logged_user, scope};
+use crate::session::LoggedGuard::Logged;
+use crate::xlsx::{Cell, XlsxError, XlsxWriter};
+
+/// Employees read from database at once - export is streamed batch by batch
+const EXPORT_BATCH_SIZE: usize = 100;
+
+/// Columns of employees - salary and contact ones are of salary and contact valid on `on` date. They are named like
+/// fields of import (see dao::IMPORT_FIELDS), so exported file can be imported back.
+const EMPLOYEE_COLUMNS: [&str; 25] = [
+    "id",
+    "employee_number",
+    "first_name",
+    "last_name",
+    "date_of_birth",
+    "hire_date",
+    "termination_date",
+    "status",
+    "department_id",
+    "manager_id",
+    "tags",
+    "salary_from",
+    "salary_to",
+    "salary_amount",
+    "salary_currency",
+    "salary_pay_period",
+    "salary_gross",
+    "contact_from",
+    "contact_to",
+    "email",
+    "phone",
+    "street",
+    "city",
+    "postal_code",
+    "country",
+];
+
+const SALARY_COLUMNS: [&str; 11] = [
+    "employee_id",
+    "employee_number",
+    "first_name",
+    "last_name",
+    "salary_from",
+    "salary_to",
+    "salary_amount",
+    "salary_currency",
+    "salary_pay_period",
+    "salary_gross",
+    "contract_id",
+];
+
+const CONTACT_COLUMNS: [&str; 12] = [
+    "employee_id",
+    "employee_number",
+    "first_name",
+    "last_name",
+    "contact_from",
+    "contact_to",
+    "email",
+    "phone",
+    "street",
+    "city",
+    "postal_code",
+    "country",
+];
+
+/// Columns which are exported just for users who can read salaries
+fn is_salary_column(column: &str) -> bool {
+    column.starts_with("salary_")
+}
+
+/// What is exported - one row per employee, salary or contact
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Dataset {
+    Employees,
+    Salaries,
+    Contacts,
+}
+
+impl Dataset {
+    fn name(&self) -> &'static str {
+        match self {
+            Dataset::Employees => "employees",
+            Dataset::Salaries => "salaries",
+            Dataset::Contacts => "contacts",
+        }
+    }
+
+    fn columns(&self) -> &'static [&'static str] {
+        match self {
+            Dataset::Employees => &EMPLOYEE_COLUMNS,
+            Dataset::Salaries => &SALARY_COLUMNS,
+            Dataset::Contacts => &CONTACT_COLUMNS,
+        }
+    }
+}
+
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    #[default]
+    Csv,
+    Xlsx,
+    /// JSON Lines - one JSON object per row
+    Jsonl,
+}
+
+/// `?format=csv|xlsx|jsonl&columns=first_name,last_name&date_format=%d.%m.%Y&on=YYYY-MM-DD&employee_group=1` - filters
+/// of employees are the same as of `GET /employees` (see EmployeeSearch)
+#[derive(Deserialize, Debug, Default)]
+#[serde(default)]
+pub struct ExportQuery {
+    format: ExportFormat,
+    /// Columns separated by `,` in order they are exported - all columns logged user can read when missing
+    columns: Option<String>,
+    /// chrono format of dates - `%Y-%m-%d` when missing
+    date_format: Option<String>,
+    /// Date salary and contact of exported employee are valid on - today when missing
+    on: Option<NaiveDate>,
+    employee_group: Option<i32>,
+}
+
+/// Value of exported cell
+enum Value {
+    Empty,
+    Text(String),
+    Number(String),
+    Date(NaiveDate),
+}
+
+impl<T: ToString> From<Option<T>> for Value {
+    fn from(value: Option<T>) -> Self {
+        value.map_or(Value::Empty, |v| Value::Text(v.to_string()))
+    }
+}
+
+/// First characters spreadsheets read formula from
+pub const FORMULA_PREFIXES: [char; 4] = ['=', '+', '-', '@'];
+
+/// Text which spreadsheet opening CSV file doesn't take for formula - it is prefixed with `'` (which is dropped by
+/// import) when it starts like formula
+fn csv_text(text: String) -> String {
+    if text.starts_with(FORMULA_PREFIXES) {
+        format!("'{}", text)
+    } else {
+        text
+    }
+}
+
+fn is_valid_on(from: NaiveDate, to: Option<NaiveDate>, on: NaiveDate) -> bool {
+    from <= on && to.is_none_or(|to| on <= to)
+}
+
+/// Value of column of row - of employee and (depending on dataset) its salary or contact
+fn value(column: &str, employee: &EmployeeDTO, salary: Option<&SalaryDTO>, contact: Option<&ContactDTO>) -> Value {
+    let address = contact.and_then(|c| c.addresses.first());
+    let joined = |values: Vec<&str>| Some(values.join("; ")).filter(|v| !v.is_empty());
+    match column {
+        "id" | "employee_id" => employee.id.map_or(Value::Empty, |id| Value::Number(id.to_string())),
+        "employee_number" => employee.employee_number.clone().into(),
+        "first_name" => Value::Text(employee.first_name.clone()),
+        "last_name" => Value::Text(employee.last_name.clone()),
+        "date_of_birth" => employee.date_of_birth.map_or(Value::Empty, Value::Date),
+        "hire_date" => employee.hire_date.map_or(Value::Empty, Value::Date),
+        "termination_date" => employee.termination_date.map_or(Value::Empty, Value::Date),
+        "status" => Value::Text(employee.status.to_string()),
+        "department_id" => employee.department_id.map_or(Value::Empty, |id| Value::Number(id.to_string())),
+        "manager_id" => employee.manager_id.map_or(Value::Empty, |id| Value::Number(id.to_string())),
+        "tags" => joined(employee.tags.iter().map(String::as_str).collect()).into(),
+        "salary_from" => salary.map_or(Value::Empty, |s| Value::Date(s.from_date)),
+        "salary_to" => salary.and_then(|s| s.to_date).map_or(Value::Empty, Value::Date),
+        "salary_amount" => salary.map_or(Value::Empty, |s| Value::Number(s.amount.amount())),
+        "salary_currency" => salary.map(|s| s.amount.currency).into(),
+        "salary_pay_period" => salary.map(|s| s.pay_period.as_str()).into(),
+        "salary_gross" => salary.map(|s| s.gross).into(),
+        "contract_id" => salary.and_then(|s| s.contract_id).map_or(Value::Empty, |id| Value::Number(id.to_string())),
+        "contact_from" => contact.map_or(Value::Empty, |c| Value::Date(c.from_date)),
+        "contact_to" => contact.and_then(|c| c.to_date).map_or(Value::Empty, Value::Date),
+        "email" => contact.and_then(|c| joined(c.emails.iter().map(|e| e.email.as_str()).collect())).into(),
+        "phone" => contact.and_then(|c| joined(c.phones.iter().map(|p| p.number.as_str()).collect())).into(),
+        "street" => address.map(|a| &a.street).into(),
+        "city" => address.map(|a| &a.city).into(),
+        "postal_code" => address.map(|a| &a.postal_code).into(),
+        "country" => address.map(|a| a.country.code()).into(),
+        _ => Value::Empty,
+    }
+}
+
+/// Exported file written row by row - what is written so far is taken by take() (XLSX workbook is taken whole by
+/// finish())
+enum TableWriter {
+    /// CSV rows are written by csv::Writer of every row
+    Csv(Vec<u8>),
+    Xlsx(Box<XlsxWriter>),
+    Jsonl(Vec<u8>),
+}
+
+struct Export {
+    writer: TableWriter,
+    dataset: Dataset,
+    columns: Vec<&'static str>,
+    date_format: String,
+    on: NaiveDate,
+}
+
+impl Export {
+    /// Export with header of columns already written (there is no header in JSON Lines)
+    fn new(
+        format: ExportFormat,
+        dataset: Dataset,
+        columns: Vec<&'static str>,
+        date_format: String,
+        on: NaiveDate,
+    ) -> Result<Self, csv::Error> {
+        let writer = match format {
+            ExportFormat::Csv => TableWriter::Csv(vec![]),
+            ExportFormat::Xlsx => TableWriter::Xlsx(Box::new(XlsxWriter::new(dataset.name()).map_err(xlsx_failed)?)),
+            ExportFormat::Jsonl => TableWriter::Jsonl(vec![]),
+        };
+        let mut export = Export {
+            writer,
+            dataset,
+            columns,
+            date_format,
+            on,
+        };
+        if format != ExportFormat::Jsonl {
+            let header: Vec<Value> = export.columns.iter().map(|c| Value::Text(c.to_string())).collect();
+            export.write_row(&header)?;
+        }
+        Ok(export)
+    }
+
+    fn text(&self, value: &Value) -> String {
+        match value {
+            Value::Empty => String::new(),
+            Value::Text(text) | Value::Number(text) => text.clone(),
+            Value::Date(date) => date.format(&self.date_format).to_string(),
+        }
+    }
+
+    fn write_row(&mut self, values: &[Value]) -> Result<(), csv::Error> {
+        let texts: Vec<String> = values.iter().map(|v| self.text(v)).collect();
+        match &mut self.writer {
+            TableWriter::Csv(output) => {
+                let texts = values.iter().zip(texts).map(|(value, text)| match value {
+                    Value::Text(_) => csv_text(text),
+                    Value::Empty | Value::Number(_) | Value::Date(_) => text,
+                });
+                let mut writer = csv::Writer::from_writer(output);
+                writer.write_record(texts)?;
+                writer.flush()?;
+            }
+            TableWriter::Xlsx(writer) => {
+                let cells: Vec<Cell> = values
+                    .iter()
+                    .zip(&texts)
+                    .map(|(value, text)| match value {
+                        Value::Empty => Cell::Empty,
+                        Value::Number(number) => Cell::Number(number),
+                        Value::Text(_) | Value::Date(_) => Cell::Text(text),
+                    })
+                    .collect();
+                writer.write_row(&cells).map_err(xlsx_failed)?;
+            }
+            TableWriter::Jsonl(output) => {
+                let object: serde_json::Map<String, serde_json::Value> = self
+                    .columns
+                    .iter()
+                    .zip(values.iter().zip(texts))
+                    .map(|(column, (value, text))| {
+                        let json = match value {
+                            Value::Empty => serde_json::Value::Null,
+                            Value::Number(number) => {
+                                number.parse().map_or(serde_json::Value::String(text), serde_json::Value::Number)
+                            }
+                            Value::Text(_) | Value::Date(_) => serde_json::Value::String(text),
+                        };
+                        (column.to_string(), json)
+                    })
+                    .collect();
+                serde_json::to_writer(&mut *output, &object).map_err(std::io::Error::from)?;
+                output.push(b'\n');
+            }
+        }
+        Ok(())
+    }
+
+    /// Rows of employee - the employee with its current salary and contact or one row per its salary or contact
+    fn write_employee(&mut self, employee: &EmployeeDTO) -> Result<(), csv::Error> {
+        let on = self.on;
+        let rows: Vec<(Option<&SalaryDTO>, Option<&ContactDTO>)> = match self.dataset {
+            Dataset::Employees => vec![(
+                employee.salaries.iter().find(|s| is_valid_on(s.from_date, s.to_date, on)),
+                employee.contacts.iter().find(|c| is_valid_on(c.from_date, c.to_date, on)),
+            )],
+            Dataset::Salaries => employee.salaries.iter().map(|s| (Some(s), None)).collect(),
+            Dataset::Contacts => employee.contacts.iter().map(|c| (None, Some(c))).collect(),
+        };
+        for (salary, contact) in rows {
+            let values: Vec<Value> = self.columns.iter().map(|c| value(c, employee, salary, contact)).collect();
+            self.write_row(&values)?;
+        }
+        Ok(())
+    }
+
+    /// What was written since the last take
+    fn take(&mut self) -> Vec<u8> {
+        match &mut self.writer {
+            TableWriter::Csv(output) | TableWriter::Jsonl(output) => std::mem::take(output),
+            TableWriter::Xlsx(_) => vec![],
+        }
+    }
+
+    /// The rest of exported file
+    fn finish(self) -> Result<Vec<u8>, csv::Error> {
+        match self.writer {
+            TableWriter::Csv(output) | TableWriter::Jsonl(output) => Ok(output),
+            TableWriter::Xlsx(writer) => writer.finish().map_err(xlsx_failed),
+        }
+    }
+}
+
+fn xlsx_failed(e: XlsxError) -> csv::Error {
+    std::io::Error::other(e).into()
+}
+
+/// Columns of export - the ones asked for (in their order) or all of dataset without salary columns when logged user
+/// can't read salaries
+fn export_columns(dataset: Dataset, columns: Option<&str>, salary_reader: bool) -> Result<Vec<&'static str>, Error> {
+    let Some(columns) = columns else {
+        return Ok(dataset
+            .columns()
+            .iter()
+            .filter(|c| salary_reader || !is_salary_column(c))
+            .copied()
+            .collect());
+    };
+    columns
+        .split(',')
+        .map(|name| {
+            let column = dataset.columns().iter().find(|c| **c == name.trim()).ok_or_else(|| {
+                ErrorBadRequest(format!(
+                    "Unknown column {} - use some of: {}",
+                    name,
+                    dataset.columns().join(", ")
+                ))
+            })?;
+            if is_salary_column(column) && !salary_reader {
+                return Err(ErrorForbidden(format!("Column {} is exported just for admins", column)));
+            }
+            Ok(*column)
+        })
+        .collect()
+}
+
+/// Employees (and their salaries or contacts) in scope of logged user as CSV, XLSX or JSON Lines attachment - it is
+/// streamed as employees are read in batches. Salaries can be read just by admins - other users get employees without
+/// salary columns.
+async fn export(req: HttpRequest, db: web::Data<Database>, dataset: Dataset) -> Result<HttpResponse, Error> {
+    let user_id = logged_user(&req)?;
+    let search = employee_search(req.query_string())?;
+    let query = web::Query::<ExportQuery>::from_query(req.query_string())?.into_inner();
+    let date_format = query.date_format.unwrap_or_else(|| "%Y-%m-%d".to_string());
+    if StrftimeItems::new(&date_format).any(|item| matches!(item, Item::Error)) {
+        return Err(ErrorBadRequest(format!("Invalid date format {}", date_format)));
+    }
+    let group = query.employee_group;
+    let keys = db.keys();
+    let (is_admin, ids) = db::try_block(&db, move |conn| {
+        let is_admin = get_user(user_id, conn).is_some_and(|u| u.is_admin);
+        if search.include_deleted && !is_admin {
+            return Ok((false, None));
+        }
+        let scope = scope(user_id, conn)?;
+        let mut ids = EmployeeDTO::search_ids_in_scope_with_connection(&search, scope, keys.as_deref(), conn)?;
+        if let Some(members) = group_members(group, scope, keys.as_deref(), conn)? {
+            ids.retain(|id| members.contains(id));
+        }
+        Ok((is_admin, Some(ids)))
+    })
+    .await?;
+    let ids = ids.ok_or_else(|| ErrorForbidden("Just admin can export deleted employees"))?;
+    if dataset == Dataset::Salaries && !is_admin {
+        return Err(ErrorForbidden("Salaries are exported just for admins"));
+    }
+    let columns = export_columns(dataset, query.columns.as_deref(), is_admin)?;
+    let on = query.on.unwrap_or_else(|| Local::now().date_naive());
+    let export = Export::new(query.format, dataset, columns, date_format, on)
+        .map_err(|e| ErrorInternalServerError(format!("Export of {} failed: {}", dataset.name(), e)))?;
+
+    let batches: Vec<Vec<i32>> = ids.chunks(EXPORT_BATCH_SIZE).map(<[i32]>::to_vec).collect();
+    let database = db.get_ref().clone();
+    let body = futures::stream::unfold(
+        (Some(export), batches.into_iter(), database),
+        move |(export, mut batches, database)| async move {
+            let mut export = export?;
+            let chunk = match batches.next() {
+                Some(ids) => {
+                    let keys = database.keys();
+                    let employees = db::try_block(&database, move |conn| {
+                        EmployeeDTO::get_many_with_connection(&ids, keys.as_deref(), conn)
+                    })
+                    .await;
+                    match employees {
+                        Ok(employees) => employees
+                            .iter()
+                            .try_for_each(|e| export.write_employee(e))
+                            .map(|_| (export.take(), Some(export))),
+                        Err(e) => Err(std::io::Error::other(e.to_string()).into()),
+                    }
+                }
+                None => export.finish().map(|rest| (rest, None)),
+            };
+            Some(match chunk {
+                Ok((chunk, export)) => (Ok(web::Bytes::from(chunk)), (export, batches, database)),
+                Err(e) => {
+                    error!("Export of {} failed: {}", dataset.name(), e);
+                    (Err(std::io::Error::other(e.to_string())), (None, batches, database))
+                }
+            })
+        },
+    );
+    let (content_type, extension) = match query.format {
+        ExportFormat::Csv => ("text/csv; charset=utf-8", "csv"),
+        ExportFormat::Xlsx => ("application/vnd.openxmlformats-officedocument.spreadsheetml.sheet", "xlsx"),
+        ExportFormat::Jsonl => ("application/x-ndjson", "jsonl"),
+    };
+    Ok(HttpResponse::Ok()
+        .content_type(content_type)
+        .insert_header(ContentDisposition {
+            disposition: DispositionType::Attachment,
+            parameters: vec![DispositionParam::Filename(format!("{}_{}.{}", dataset.name(), on, extension))],
+        })
+        .streaming(body))
+}
+
+async fn export_employees(req: HttpRequest, db: web::Data<Database>) -> Result<HttpResponse, Error> {
+    export(req, db, Dataset::Employees).await
+}
+
+async fn export_salaries(req: HttpRequest, db: web::Data<Database>) -> Result<HttpResponse, Error> {
+    export(req, db, Dataset::Salaries).await
+}
+
+async fn export_contacts(req: HttpRequest, db: web::Data<Database>) -> Result<HttpResponse, Error> {
+    export(req, db, Dataset::Contacts).await
+}
+
+/// Exports of employees, salaries and contacts - `prefix` is prefix of exports
+pub fn config(cfg: &mut web::ServiceConfig, prefix: &str) {
+    cfg.service(
+        web::resource(format!("{}{}", prefix, "/employees"))
+            .wrap(Logged)
+            .route(web::get().to(export_employees)),
+    );
+    cfg.service(
+        web::resource(format!("{}{}", prefix, "/salaries"))
+            .wrap(Logged)
+            .route(web::get().to(export_salaries)),
+    );
+    cfg.service(
+        web::resource(format!("{}{}", prefix, "/contacts"))
+            .wrap(Logged)
+            .route(web::get().to(export_contacts)),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formulas_are_exported_as_text() {
+        let on = NaiveDate::from_ymd_opt(2020, 1, 1).unwrap();
+        let row = || {
+            vec![
+                Value::Text("=HYPERLINK(\"http://example.com\")".to_string()),
+                Value::Text("+48601234567".to_string()),
+                Value::Text("@SUM(A1)".to_string()),
+                Value::Text("Jan-Maria".to_string()),
+                Value::Number("-12.50".to_string()),
+            ]
+        };
+        let columns = vec!["first_name", "phone", "last_name", "city", "salary_amount"];
+
+        let mut csv = Export::new(ExportFormat::Csv, Dataset::Employees, columns.clone(), "%Y-%m-%d".to_string(), on)
+            .unwrap();
+        csv.write_row(&row()).unwrap();
+        let csv = String::from_utf8(csv.finish().unwrap()).unwrap();
+        assert_eq!(
+            csv.lines().nth(1).unwrap(),
+            "\"'=HYPERLINK(\"\"http://example.com\"\")\",'+48601234567,'@SUM(A1),Jan-Maria,-12.50"
+        );
+
+        let mut xlsx = Export::new(ExportFormat::Xlsx, Dataset::Employees, columns, "%Y-%m-%d".to_string(), on)
+            .unwrap();
+        xlsx.write_row(&row()).unwrap();
+        let rows = crate::xlsx::read_rows(&xlsx.finish().unwrap(), "%Y-%m-%d").unwrap();
+        assert_eq!(
+            rows[1],
+            vec!["=HYPERLINK(\"http://example.com\")", "+48601234567", "@SUM(A1)", "Jan-Maria", "-12.5"]
+        );
+    }
+
+    #[test]
+    fn salary_columns_are_exported_just_for_salary_readers() {
+        let columns = export_columns(Dataset::Employees, None, false).unwrap();
+        assert!(columns.contains(&"email"));
+        assert!(!columns.iter().any(|c| is_salary_column(c)));
+        assert_eq!(export_columns(Dataset::Employees, None, true).unwrap().len(), EMPLOYEE_COLUMNS.len());
+        assert_eq!(
+            export_columns(Dataset::Contacts, Some("last_name, email"), false).unwrap(),
+            vec!["last_name", "email"]
+        );
+        assert!(export_columns(Dataset::Employees, Some("first_name,salary_amount"), false).is_err());
+        assert!(export_columns(Dataset::Contacts, Some("salary_amount"), true).is_err());
+    }
+}
@@ -0,0 +1,201 @@
+use std::collections::BTreeMap;
+
+use actix_web::error::{ErrorBadRequest, ErrorPayloadTooLarge};
+use actix_web::http::Method;
+use actix_web::{web, Error, HttpRequest, HttpResponse};
+use dao::{import_employees_with_connection, Database, ImportOptions, ImportRow, IMPORT_FIELDS};
+use futures::StreamExt;
+
+use crate::db;
+use crate::export::FORMULA_PREFIXES;
+use crate::session::LoggedGuard::LoggedAsAdmin;
+use crate::xlsx;
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(default)]
+pub struct ImportQuery {
+    /// Just validate rows and report their errors
+    dry_run: bool,
+    /// Import all rows or none of them
+    atomic: bool,
+    /// Rows saved in one transaction - 100 when missing
+    chunk_size: Option<usize>,
+    /// chrono format of dates - `%Y-%m-%d` when missing
+    date_format: Option<String>,
+    /// Delimiter of CSV fields - `,` when missing
+    delimiter: Option<char>,
+    /// `field:Column` pairs (repeated `map`) - read separately as web::Query can't read repeated keys
+    #[serde(skip)]
+    map: Vec<String>,
+}
+
+/// ImportQuery from query string - repeated `map` is read separately (as `tag` of employee search)
+fn import_query(query: &str) -> Result<ImportQuery, Error> {
+    let (maps, others): (Vec<&str>, Vec<&str>) = query.split('&').partition(|p| p.starts_with("map="));
+    let mut import = web::Query::<ImportQuery>::from_query(&others.join("&"))?.into_inner();
+    import.map = web::Query::<Vec<(String, String)>>::from_query(&maps.join("&"))?
+        .into_inner()
+        .into_iter()
+        .map(|(_, map)| map)
+        .collect();
+    Ok(import)
+}
+
+/// Value of CSV field - `'` prefixing formula-like text (see export) is dropped
+fn csv_value(field: &str) -> String {
+    match field.strip_prefix('\'') {
+        Some(text) if text.starts_with(FORMULA_PREFIXES) => text.to_string(),
+        _ => field.to_string(),
+    }
+}
+
+/// Rows of CSV (UTF-8, BOM is skipped) or XLSX (first worksheet - its dates are formatted by `date_format`) file -
+/// the first one is header
+fn read_table(content: &[u8], delimiter: char, date_format: &str) -> Result<Vec<Vec<String>>, Error> {
+    if xlsx::is_xlsx(content) {
+        return xlsx::read_rows(content, date_format).map_err(|e| ErrorBadRequest(format!("Invalid XLSX file: {}", e)));
+    }
+    if !delimiter.is_ascii() {
+        return Err(ErrorBadRequest(format!("Invalid CSV delimiter: {}", delimiter)));
+    }
+    let content = content.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(content);
+    csv::ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .delimiter(delimiter as u8)
+        .from_reader(content)
+        .records()
+        .map(|record| record.map(|r| r.iter().map(csv_value).collect()))
+        .collect::<Result<_, _>>()
+        .map_err(|e| ErrorBadRequest(format!("Invalid CSV file: {}", e)))
+}
+
+/// Field of every column (None when column is ignored) - columns are mapped by `field:Column` pairs first and
+/// then by headers which are names of fields (case is ignored)
+fn map_columns(header: &[String], map: &[String]) -> Result<Vec<Option<&'static str>>, Error> {
+    let mut fields: Vec<Option<&'static str>> = vec![None; header.len()];
+    for pair in map {
+        let (field, column) = pair
+            .split_once(':')
+            .ok_or_else(|| ErrorBadRequest(format!("Invalid column mapping {} - use field:Column", pair)))?;
+        let field = IMPORT_FIELDS.iter().find(|f| **f == field.trim()).ok_or_else(|| {
+            ErrorBadRequest(format!("Unknown field {} - use one of: {}", field, IMPORT_FIELDS.join(", ")))
+        })?;
+        let index = header
+            .iter()
+            .position(|h| h.trim() == column.trim())
+            .ok_or_else(|| ErrorBadRequest(format!("There is no column {} for field {}", column, field)))?;
+        fields[index] = Some(field);
+    }
+    for (index, name) in header.iter().enumerate() {
+        let name = name.trim().to_lowercase();
+        if fields[index].is_none() && !fields.contains(&Some(name.as_str())) {
+            fields[index] = IMPORT_FIELDS.iter().find(|f| **f == name).copied();
+        }
+    }
+    Ok(fields)
+}
+
+/// Import employees (with salary and contact) from CSV or XLSX file sent as body - `?dry_run=&atomic=&chunk_size=
+/// &date_format=&delimiter=&map=field:Column` (see ImportQuery). Employees are updated when their `employee_number`
+/// is found. Errors of rows are reported (with created, updated and failed counts) in ImportResult.
+async fn import_employees(
+    req: HttpRequest,
+    db: web::Data<Database>,
+    mut payload: web::Payload,
+) -> Result<HttpResponse, Error> {
+    let query = import_query(req.query_string())?;
+    let max_size = db.config().documents.max_size;
+    let mut body = web::BytesMut::new();
+    while let Some(chunk) = payload.next().await {
+        let chunk = chunk?;
+        if (body.len() + chunk.len()) as u64 > max_size {
+            return Err(ErrorPayloadTooLarge(format!(
+                "Imported file can't be bigger than {} bytes",
+                max_size
+            )));
+        }
+        body.extend_from_slice(&chunk);
+    }
+    let defaults = ImportOptions::default();
+    let date_format = query.date_format.unwrap_or(defaults.date_format);
+    let mut table = read_table(&body, query.delimiter.unwrap_or(','), &date_format).map(Vec::into_iter)?;
+    let header = table.next().ok_or_else(|| ErrorBadRequest("Imported file is empty"))?;
+    let fields = map_columns(&header, &query.map)?;
+    let ignored_columns = header
+        .iter()
+        .zip(&fields)
+        .filter(|(name, field)| field.is_none() && !name.trim().is_empty())
+        .map(|(name, _)| name.clone())
+        .collect();
+    let rows: Vec<ImportRow> = table
+        .enumerate()
+        .filter(|(_, values)| values.iter().any(|v| !v.trim().is_empty()))
+        .map(|(index, values)| ImportRow {
+            row: index + 2,
+            values: fields
+                .iter()
+                .zip(values)
+                .filter_map(|(field, value)| field.map(|f| (f.to_string(), value)))
+                .collect::<BTreeMap<_, _>>(),
+        })
+        .collect();
+
+    let options = ImportOptions {
+        dry_run: query.dry_run,
+        atomic: query.atomic,
+        chunk_size: query.chunk_size.filter(|s| *s > 0).unwrap_or(defaults.chunk_size),
+        date_format,
+        default_currency: db.config().default_currency,
+    };
+    let rules = db.config().validation.clone();
+    let keys = db.keys();
+    let mut result = db::try_block(&db, move |conn| {
+        import_employees_with_connection(&rows, &options, &rules, keys.as_deref(), conn)
+    })
+    .await?;
+    result.ignored_columns = ignored_columns;
+    let body = serde_json::to_string(&result)?;
+    Ok(HttpResponse::Ok()
+        .content_type("application/json")
+        .body(body))
+}
+
+/// Import of employees - `prefix` is prefix of imports. Just admins can import.
+pub fn config(cfg: &mut web::ServiceConfig, prefix: &str) {
+    cfg.service(
+        web::resource(format!("{}{}", prefix, "/employees"))
+            .wrap(LoggedAsAdmin(&[Method::POST]))
+            .route(web::post().to(import_employees)),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn columns_are_mapped_by_map_and_header() {
+        let header: Vec<String> = ["Nr", "First_Name", "last_name", "Uwagi", "Nazwisko"]
+            .iter()
+            .map(|h| h.to_string())
+            .collect();
+        let fields = map_columns(&header, &["employee_number:Nr".to_string(), "last_name:Nazwisko".to_string()]);
+        assert_eq!(
+            vec![Some("employee_number"), Some("first_name"), None, None, Some("last_name")],
+            fields.unwrap()
+        );
+        assert!(map_columns(&header, &["salary:Nr".to_string()]).is_err());
+        assert!(map_columns(&header, &["first_name:Imię".to_string()]).is_err());
+        assert!(map_columns(&header, &["first_name".to_string()]).is_err());
+    }
+
+    #[test]
+    fn csv_is_read_with_delimiter() {
+        let rows = read_table("\u{FEFF}a;b\n1;\"2;3\"\n\n4\n'+48601234567;'quoted\n".as_bytes(), ';', "%Y-%m-%d");
+        assert_eq!(
+            vec![vec!["a", "b"], vec!["1", "2;3"], vec!["4"], vec!["+48601234567", "'quoted"]],
+            rows.unwrap()
+        );
+    }
+}
@@ -2,7 +2,6 @@ extern crate actix_service;
 extern crate actix_web;
 extern crate cookie;
 extern crate dao;
-#[macro_use]
 extern crate failure;
 #[macro_use]
 extern crate lazy_static;
@@ -17,11 +16,31 @@ extern crate uuid;
 
 use actix_web::{web, App, Error, HttpResponse, HttpServer};
 use actix_web::cookie::Cookie;
+use dao::Database;
 
 #[macro_use]
 mod session;
+mod absence;
+mod absence_type;
+mod contract;
+mod custom_field;
+mod db;
+mod department;
+mod document;
 mod employee;
+mod employee_group;
+mod etag;
+mod export;
+mod import;
+mod org;
+mod payroll;
+mod photo;
+mod position;
+mod report;
+mod tag;
+mod timesheet;
 mod user;
+mod xlsx;
 
 pub use session::LoginDTO;
 pub use user::UserDTO;
@@ -40,15 +59,43 @@ pub fn config(cfg: &mut web::ServiceConfig, prefix: &str) {
 
 pub fn config_all(cfg: &mut web::ServiceConfig) {
     user::config(cfg, "/users");
+    tag::config_employees(cfg, "/employees");
     employee::config(cfg, "/employees");
+    contract::config(cfg, "/employees");
+    document::config(cfg, "/employees");
+    photo::config(cfg, "/employees");
+    absence::config(cfg, "/employees");
+    absence::config_calendar(cfg, "/absences");
+    absence_type::config(cfg, "/absence-types");
+    timesheet::config(cfg, "/employees");
+    timesheet::config_reports(cfg, "/timesheets");
+    department::config(cfg, "/departments");
+    position::config(cfg, "/positions");
+    custom_field::config(cfg, "/custom-fields");
+    tag::config(cfg, "/tags");
+    employee_group::config(cfg, "/employee-groups");
+    org::config(cfg, "/org-chart");
+    payroll::config_rules(cfg, "/payroll-rules");
+    payroll::config_runs(cfg, "/payroll-runs");
+    report::config(cfg, "/reports");
+    import::config(cfg, "/import");
+    export::config(cfg, "/export");
     session::config(cfg, "/auth");
     config(cfg, "/");
 }
 
-pub async fn start() -> std::io::Result<()> {
+/// Configure all endpoints plus Database handle as application data
+pub fn config_with_db(db: Database) -> impl FnOnce(&mut web::ServiceConfig) {
+    move |cfg| {
+        cfg.app_data(web::Data::new(db));
+        config_all(cfg);
+    }
+}
+
+pub async fn start(db: Database) -> std::io::Result<()> {
     info!("Start REST");
 
-    HttpServer::new(|| App::new().configure(|cfg| config_all(cfg)))
+    HttpServer::new(move || App::new().configure(config_with_db(db.clone())))
         .bind("127.0.0.1:8088")?
         .run()
         .await
@@ -0,0 +1,28 @@
+use actix_web::{web, Error, HttpRequest, HttpResponse};
+use dao::{org_chart_with_connection, Database};
+
+use crate::db;
+use crate::employee::{logged_user, scope};
+use crate::session::LoggedGuard::Logged;
+
+/// Organization chart as nested JSON - whole organization or just subtree of logged user (see EmployeeScope)
+async fn get_org_chart(req: HttpRequest, db: web::Data<Database>) -> Result<HttpResponse, Error> {
+    let user_id = logged_user(&req)?;
+    let chart = db::try_block(&db, move |conn| {
+        let scope = scope(user_id, conn)?;
+        Ok(org_chart_with_connection(scope, conn)?)
+    })
+    .await?;
+    let body = serde_json::to_string(&chart)?;
+    Ok(HttpResponse::Ok()
+        .content_type("application/json")
+        .body(body))
+}
+
+pub fn config(cfg: &mut web::ServiceConfig, prefix: &str) {
+    cfg.service(
+        web::resource(prefix)
+            .wrap(Logged)
+            .route(web::get().to(get_org_chart)),
+    );
+}
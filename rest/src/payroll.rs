@@ -0,0 +1,320 @@
+use actix_web::error::{ErrorInternalServerError, ErrorNotFound};
+use actix_web::http::Method;
+use actix_web::web::Json;
+use actix_web::{web, Error, HttpRequest, HttpResponse};
+use dao::{Crud, DaoError, Database, PayrollRuleDTO, PayrollRunDTO, Searchable};
+
+use crate::db;
+use crate::employee::logged_user;
+use crate::etag;
+use crate::session::LoggedGuard::LoggedAsAdmin;
+
+/// Payroll is visible just to admins
+const ALL_METHODS: &[Method] = &[Method::GET, Method::PUT, Method::POST, Method::DELETE];
+
+async fn get_rules(db: web::Data<Database>) -> Result<HttpResponse, Error> {
+    let keys = db.keys();
+    let rules: Vec<PayrollRuleDTO> =
+        db::try_block(&db, move |conn| PayrollRuleDTO::get_all_with_connection(keys.as_deref(), conn)).await?;
+    let body = serde_json::to_string(&rules)?;
+    Ok(HttpResponse::Ok()
+        .content_type("application/json")
+        .body(body))
+}
+
+async fn get_rule(db: web::Data<Database>, path: web::Path<String>) -> Result<HttpResponse, Error> {
+    let id: i32 = path.parse().unwrap();
+    let keys = db.keys();
+    match db::block(&db, move |conn| PayrollRuleDTO::get_with_conn(id, keys.as_deref(), conn)).await? {
+        Some(rule) => etag::ok(&rule, rule.version.unwrap_or_default()),
+        None => Err(ErrorNotFound(format!(
+            "Can't find payroll rule with id = {}",
+            id
+        ))),
+    }
+}
+
+/// 412 with current state of payroll rule
+async fn rule_precondition_failed(db: &Database, id: i32) -> Result<HttpResponse, Error> {
+    let keys = db.keys();
+    let current = db::try_block(db, move |conn| Ok(PayrollRuleDTO::get_simple(id, keys.as_deref(), conn)?)).await?;
+    etag::precondition_failed(&current, current.version.unwrap_or_default())
+}
+
+/// Create payroll rule (without id) or update existing one. Update require If-Match with ETag
+/// of rule it is based on - and so does every PUT. Existing payroll runs are not affected.
+async fn update_rule(
+    req: HttpRequest,
+    db: web::Data<Database>,
+    rule_json: Json<PayrollRuleDTO>,
+) -> Result<HttpResponse, Error> {
+    let mut rule = rule_json.into_inner();
+    let if_match = if req.method() == Method::PUT || rule.id.is_some() {
+        Some(etag::if_match(&req)?)
+    } else {
+        None
+    };
+    let rules = db.config().validation.clone();
+    let keys = db.keys();
+    let saved = db::block(&db, move |conn| {
+        if let (Some(if_match), Some(id)) = (&if_match, rule.id) {
+            let current = PayrollRuleDTO::get_simple(id, keys.as_deref(), conn)?;
+            rule.version = Some(etag::expected_version(
+                if_match,
+                id,
+                current.version.unwrap_or_default(),
+            )?);
+        }
+        rule.try_persist_in_transaction(&rules, keys.as_deref(), conn)
+    })
+    .await?;
+    match saved {
+        Ok(rule) => etag::ok(&rule, rule.version.unwrap_or_default()),
+        Err(DaoError::StaleVersion { id, .. }) => rule_precondition_failed(&db, id).await,
+        Err(e) => Err(db::dao_error(e)),
+    }
+}
+
+async fn delete_rule(
+    req: HttpRequest,
+    db: web::Data<Database>,
+    path: web::Path<String>,
+) -> Result<HttpResponse, Error> {
+    let id: i32 = path.parse().unwrap();
+    let if_match = etag::if_match(&req)?;
+    let keys = db.keys();
+    let deleted = db::block(&db, move |conn| {
+        let mut rule = PayrollRuleDTO::get_simple(id, keys.as_deref(), conn)?;
+        rule.version = Some(etag::expected_version(
+            &if_match,
+            id,
+            rule.version.unwrap_or_default(),
+        )?);
+        rule.try_delete_with_conn(keys.as_deref(), conn)
+    })
+    .await?;
+    match deleted {
+        Ok(1) => Ok(HttpResponse::Ok()
+            .content_type("application/json")
+            .body(format!("Removed payroll rule with id = {}", id))),
+        Ok(n) => Err(ErrorInternalServerError(format!(
+            "Removed {} payroll rules with id = {}",
+            n, id
+        ))),
+        Err(DaoError::StaleVersion { .. }) => rule_precondition_failed(&db, id).await,
+        Err(e) if e.is_not_found() => Err(ErrorNotFound(format!(
+            "Not found payroll rule with id = {}",
+            id
+        ))),
+        Err(e) => Err(db::dao_error(e)),
+    }
+}
+
+/// All payroll runs - the latest month first
+async fn get_runs(db: web::Data<Database>) -> Result<HttpResponse, Error> {
+    let keys = db.keys();
+    let runs =
+        db::try_block(&db, move |conn| Ok(PayrollRunDTO::get_all_with_connection(keys.as_deref(), conn)?)).await?;
+    let body = serde_json::to_string(&runs)?;
+    Ok(HttpResponse::Ok()
+        .content_type("application/json")
+        .body(body))
+}
+
+/// `{"year": 2021, "month": 3}`
+#[derive(Deserialize, Debug)]
+pub struct NewRun {
+    pub year: i32,
+    pub month: u32,
+}
+
+/// Calculate draft payroll run of month - 422 when the month already has one
+async fn create_run(db: web::Data<Database>, new_run: Json<NewRun>) -> Result<HttpResponse, Error> {
+    let NewRun { year, month } = new_run.into_inner();
+    let keys = db.keys();
+    let run = db::try_block(&db, move |conn| {
+        PayrollRunDTO::create_with_connection(year, month, keys.as_deref(), conn)
+    })
+    .await?;
+    etag::ok(&run, run.version)
+}
+
+fn run_not_found(id: i32) -> Error {
+    ErrorNotFound(format!("Can't find payroll run with id = {}", id))
+}
+
+async fn get_run(db: web::Data<Database>, path: web::Path<String>) -> Result<HttpResponse, Error> {
+    let id: i32 = path.parse().unwrap();
+    let keys = db.keys();
+    match db::try_block(&db, move |conn| Ok(PayrollRunDTO::get_with_connection(id, keys.as_deref(), conn)?)).await? {
+        Some(run) => etag::ok(&run, run.version),
+        None => Err(run_not_found(id)),
+    }
+}
+
+/// Recalculate run from its snapshot and report payslips which differ from stored ones
+async fn verify_run(db: web::Data<Database>, path: web::Path<String>) -> Result<HttpResponse, Error> {
+    let id: i32 = path.parse().unwrap();
+    let keys = db.keys();
+    match db::try_block(&db, move |conn| Ok(PayrollRunDTO::get_with_connection(id, keys.as_deref(), conn)?)).await? {
+        Some(run) => {
+            let body = serde_json::to_string(&run.verify())?;
+            Ok(HttpResponse::Ok()
+                .content_type("application/json")
+                .body(body))
+        }
+        None => Err(run_not_found(id)),
+    }
+}
+
+/// 412 with current state of payroll run
+async fn run_precondition_failed(db: &Database, id: i32) -> Result<HttpResponse, Error> {
+    let keys = db.keys();
+    let current = db::try_block(db, move |conn| {
+        PayrollRunDTO::get_with_connection(id, keys.as_deref(), conn)?.ok_or_else(DaoError::not_found)
+    })
+    .await?;
+    etag::precondition_failed(&current, current.version)
+}
+
+/// Step of draft -> approved -> locked workflow
+#[derive(Clone, Copy, Debug)]
+enum Step {
+    Recalculate,
+    Approve,
+    Lock,
+}
+
+/// Move run by `step` - require If-Match with ETag of run it is based on. Run in wrong status is reported
+/// as 422.
+async fn step_run(
+    req: HttpRequest,
+    db: web::Data<Database>,
+    path: web::Path<String>,
+    step: Step,
+) -> Result<HttpResponse, Error> {
+    let id: i32 = path.parse().unwrap();
+    let user_id = logged_user(&req)?;
+    let if_match = etag::if_match(&req)?;
+    let keys = db.keys();
+    let moved = db::block(&db, move |conn| {
+        let run = PayrollRunDTO::get_with_connection(id, keys.as_deref(), conn)?.ok_or_else(DaoError::not_found)?;
+        let expected = etag::expected_version(&if_match, id, run.version)?;
+        match step {
+            Step::Recalculate => PayrollRunDTO::recalculate_with_connection(id, expected, keys.as_deref(), conn),
+            Step::Approve => PayrollRunDTO::approve_with_connection(id, expected, user_id, keys.as_deref(), conn),
+            Step::Lock => PayrollRunDTO::lock_with_connection(id, expected, keys.as_deref(), conn),
+        }
+    })
+    .await?;
+    match moved {
+        Ok(run) => etag::ok(&run, run.version),
+        Err(DaoError::StaleVersion { .. }) => run_precondition_failed(&db, id).await,
+        Err(e) if e.is_not_found() => Err(run_not_found(id)),
+        Err(e) => Err(db::dao_error(e)),
+    }
+}
+
+async fn recalculate_run(
+    req: HttpRequest,
+    db: web::Data<Database>,
+    path: web::Path<String>,
+) -> Result<HttpResponse, Error> {
+    step_run(req, db, path, Step::Recalculate).await
+}
+
+async fn approve_run(
+    req: HttpRequest,
+    db: web::Data<Database>,
+    path: web::Path<String>,
+) -> Result<HttpResponse, Error> {
+    step_run(req, db, path, Step::Approve).await
+}
+
+async fn lock_run(
+    req: HttpRequest,
+    db: web::Data<Database>,
+    path: web::Path<String>,
+) -> Result<HttpResponse, Error> {
+    step_run(req, db, path, Step::Lock).await
+}
+
+/// Just draft run can be deleted - 422 otherwise
+async fn delete_run(
+    req: HttpRequest,
+    db: web::Data<Database>,
+    path: web::Path<String>,
+) -> Result<HttpResponse, Error> {
+    let id: i32 = path.parse().unwrap();
+    let if_match = etag::if_match(&req)?;
+    let keys = db.keys();
+    let deleted = db::block(&db, move |conn| {
+        let run = PayrollRunDTO::get_with_connection(id, keys.as_deref(), conn)?.ok_or_else(DaoError::not_found)?;
+        let expected = etag::expected_version(&if_match, id, run.version)?;
+        PayrollRunDTO::delete_with_connection(id, expected, keys.as_deref(), conn)
+    })
+    .await?;
+    match deleted {
+        Ok(1) => Ok(HttpResponse::Ok()
+            .content_type("application/json")
+            .body(format!("Removed payroll run with id = {}", id))),
+        Ok(n) => Err(ErrorInternalServerError(format!(
+            "Removed {} payroll runs with id = {}",
+            n, id
+        ))),
+        Err(DaoError::StaleVersion { .. }) => run_precondition_failed(&db, id).await,
+        Err(e) if e.is_not_found() => Err(run_not_found(id)),
+        Err(e) => Err(db::dao_error(e)),
+    }
+}
+
+pub fn config_rules(cfg: &mut web::ServiceConfig, prefix: &str) {
+    cfg.service(
+        web::resource(prefix)
+            .wrap(LoggedAsAdmin(ALL_METHODS))
+            .route(web::get().to(get_rules))
+            .route(web::put().to(update_rule))
+            .route(web::post().to(update_rule)),
+    );
+    cfg.service(
+        web::resource(format!("{}{}", prefix, "/{id}"))
+            .wrap(LoggedAsAdmin(ALL_METHODS))
+            .route(web::get().to(get_rule))
+            .route(web::delete().to(delete_rule)),
+    );
+}
+
+pub fn config_runs(cfg: &mut web::ServiceConfig, prefix: &str) {
+    cfg.service(
+        web::resource(prefix)
+            .wrap(LoggedAsAdmin(ALL_METHODS))
+            .route(web::get().to(get_runs))
+            .route(web::post().to(create_run)),
+    );
+    cfg.service(
+        web::resource(format!("{}{}", prefix, "/{id}"))
+            .wrap(LoggedAsAdmin(ALL_METHODS))
+            .route(web::get().to(get_run))
+            .route(web::delete().to(delete_run)),
+    );
+    cfg.service(
+        web::resource(format!("{}{}", prefix, "/{id}/verify"))
+            .wrap(LoggedAsAdmin(ALL_METHODS))
+            .route(web::get().to(verify_run)),
+    );
+    cfg.service(
+        web::resource(format!("{}{}", prefix, "/{id}/recalculate"))
+            .wrap(LoggedAsAdmin(ALL_METHODS))
+            .route(web::post().to(recalculate_run)),
+    );
+    cfg.service(
+        web::resource(format!("{}{}", prefix, "/{id}/approve"))
+            .wrap(LoggedAsAdmin(ALL_METHODS))
+            .route(web::post().to(approve_run)),
+    );
+    cfg.service(
+        web::resource(format!("{}{}", prefix, "/{id}/lock"))
+            .wrap(LoggedAsAdmin(ALL_METHODS))
+            .route(web::post().to(lock_run)),
+    );
+}
@@ -0,0 +1,155 @@
+use actix_web::error::{ErrorInternalServerError, ErrorNotFound, ErrorPayloadTooLarge};
+use actix_web::http::header::{CacheControl, CacheDirective, EntityTag, ETag, Header, IfNoneMatch};
+use actix_web::http::Method;
+use actix_web::{web, Error, HttpRequest, HttpResponse};
+use dao::{Database, PhotoDTO};
+use futures::StreamExt;
+
+use crate::db;
+use crate::employee::{logged_user, scope};
+use crate::session::LoggedGuard::LoggedAsAdmin;
+
+/// Photo URLs carry version of photo (`v`) - what is read by them never changes
+const IMMUTABLE_MAX_AGE: u32 = 365 * 24 * 60 * 60;
+
+#[derive(Deserialize, Debug)]
+pub struct PhotoQuery {
+    /// Size of thumbnail (see dao::THUMBNAIL_SIZES) - original when missing
+    size: Option<u32>,
+    /// SHA-256 of photo as it is in its URLs
+    v: Option<String>,
+}
+
+fn photo_not_found(e_id: i32) -> Error {
+    ErrorNotFound(format!("Employee with id = {} has no photo", e_id))
+}
+
+/// Original or thumbnail of photo of employee in scope of logged user - it can be cached for good when it is read
+/// by URL with its version (see PhotoDTO), otherwise it has to be revalidated (by its ETag)
+async fn get_photo(
+    req: HttpRequest,
+    db: web::Data<Database>,
+    path: web::Path<String>,
+    query: web::Query<PhotoQuery>,
+) -> Result<HttpResponse, Error> {
+    let e_id: i32 = path.parse().unwrap();
+    let user_id = logged_user(&req)?;
+    let photo = db::try_block(&db, move |conn| {
+        if !scope(user_id, conn)?.contains(e_id, conn)? {
+            return Ok(None);
+        }
+        Ok(PhotoDTO::of_employee_with_connection(e_id, conn)?)
+    })
+    .await?;
+    let photo = photo.ok_or_else(|| photo_not_found(e_id))?;
+    let etag = EntityTag::new_strong(match query.size {
+        Some(size) => format!("{}-{}", photo.sha256, size),
+        None => photo.sha256.clone(),
+    });
+    let cache_control = if query.v.as_deref() == Some(photo.sha256.as_str()) {
+        CacheControl(vec![
+            CacheDirective::Private,
+            CacheDirective::MaxAge(IMMUTABLE_MAX_AGE),
+            CacheDirective::Extension("immutable".to_string(), None),
+        ])
+    } else {
+        CacheControl(vec![CacheDirective::Private, CacheDirective::NoCache])
+    };
+    let not_modified = match IfNoneMatch::parse(&req) {
+        Ok(IfNoneMatch::Any) => true,
+        Ok(IfNoneMatch::Items(tags)) => tags.iter().any(|tag| tag.weak_eq(&etag)),
+        Err(_) => false,
+    };
+    if not_modified {
+        return Ok(HttpResponse::NotModified()
+            .insert_header(ETag(etag))
+            .insert_header(cache_control)
+            .finish());
+    }
+    let database = db.get_ref().clone();
+    let size = query.size;
+    let content_type = photo.content_type.clone();
+    let content = db::block(&db, move |_| photo.content(e_id, size, database.storage())).await?;
+    match content {
+        Ok(content) => Ok(HttpResponse::Ok()
+            .content_type(content_type)
+            .insert_header(ETag(etag))
+            .insert_header(cache_control)
+            .body(content)),
+        Err(e) => Err(db::dao_error(e)),
+    }
+}
+
+/// Replace photo by JPEG, PNG or WebP image sent as body - its metadata is stripped and thumbnails are generated
+async fn put_photo(
+    req: HttpRequest,
+    db: web::Data<Database>,
+    path: web::Path<String>,
+    mut payload: web::Payload,
+) -> Result<HttpResponse, Error> {
+    let e_id: i32 = path.parse().unwrap();
+    let user_id = logged_user(&req)?;
+    let max_size = db.config().documents.max_size;
+    let mut body = web::BytesMut::new();
+    while let Some(chunk) = payload.next().await {
+        let chunk = chunk?;
+        if (body.len() + chunk.len()) as u64 > max_size {
+            return Err(ErrorPayloadTooLarge(format!(
+                "Photo can't be bigger than {} bytes",
+                max_size
+            )));
+        }
+        body.extend_from_slice(&chunk);
+    }
+    let database = db.get_ref().clone();
+    let saved = db::block(&db, move |conn| {
+        PhotoDTO::save_with_connection(e_id, &body, Some(user_id), max_size, database.storage(), conn)
+    })
+    .await?;
+    match saved {
+        Ok(photo) => {
+            let body = serde_json::to_string(&photo)?;
+            Ok(HttpResponse::Ok()
+                .content_type("application/json")
+                .body(body))
+        }
+        Err(e) if e.is_not_found() => Err(ErrorNotFound(format!(
+            "Can't find employee with id = {}",
+            e_id
+        ))),
+        Err(e) => Err(db::dao_error(e)),
+    }
+}
+
+/// Delete photo with its thumbnails
+async fn delete_photo(
+    db: web::Data<Database>,
+    path: web::Path<String>,
+) -> Result<HttpResponse, Error> {
+    let e_id: i32 = path.parse().unwrap();
+    let database = db.get_ref().clone();
+    let deleted = db::block(&db, move |conn| PhotoDTO::delete_with_connection(e_id, database.storage(), conn)).await?;
+    match deleted {
+        Ok(1) => Ok(HttpResponse::Ok()
+            .content_type("application/json")
+            .body(format!("Removed photo of employee with id = {}", e_id))),
+        Ok(n) => Err(ErrorInternalServerError(format!(
+            "Removed {} photos of employee with id = {}",
+            n, e_id
+        ))),
+        Err(e) if e.is_not_found() => Err(photo_not_found(e_id)),
+        Err(e) => Err(db::dao_error(e)),
+    }
+}
+
+/// Photo as sub-resource of employees - `prefix` is prefix of employees. It is changed just by admins and it can
+/// be read by users who see its employee.
+pub fn config(cfg: &mut web::ServiceConfig, prefix: &str) {
+    cfg.service(
+        web::resource(format!("{}{}", prefix, "/{id}/photo"))
+            .wrap(LoggedAsAdmin(&[Method::PUT, Method::DELETE]))
+            .route(web::get().to(get_photo))
+            .route(web::put().to(put_photo))
+            .route(web::delete().to(delete_photo)),
+    );
+}
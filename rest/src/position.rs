@@ -0,0 +1,124 @@
+use actix_web::error::{ErrorInternalServerError, ErrorNotFound};
+use actix_web::http::Method;
+use actix_web::web::Json;
+use actix_web::{web, Error, HttpRequest, HttpResponse};
+use dao::{Crud, DaoError, Database, PositionDTO, Searchable};
+
+use crate::db;
+use crate::etag;
+use crate::session::LoggedGuard::LoggedAsAdmin;
+
+async fn get_positions(db: web::Data<Database>) -> Result<HttpResponse, Error> {
+    let keys = db.keys();
+    let positions: Vec<PositionDTO> =
+        db::try_block(&db, move |conn| PositionDTO::get_all_with_connection(keys.as_deref(), conn)).await?;
+    let body = serde_json::to_string(&positions)?;
+    Ok(HttpResponse::Ok()
+        .content_type("application/json")
+        .body(body))
+}
+
+async fn get_position(db: web::Data<Database>, path: web::Path<String>) -> Result<HttpResponse, Error> {
+    let id: i32 = path.parse().unwrap();
+    let keys = db.keys();
+    match db::block(&db, move |conn| PositionDTO::get_with_conn(id, keys.as_deref(), conn)).await? {
+        Some(position) => etag::ok(&position, position.version.unwrap_or_default()),
+        None => Err(ErrorNotFound(format!(
+            "Can't find position with id = {}",
+            id
+        ))),
+    }
+}
+
+/// 412 with current state of position
+async fn position_precondition_failed(db: &Database, id: i32) -> Result<HttpResponse, Error> {
+    let keys = db.keys();
+    let current = db::try_block(db, move |conn| Ok(PositionDTO::get_simple(id, keys.as_deref(), conn)?)).await?;
+    etag::precondition_failed(&current, current.version.unwrap_or_default())
+}
+
+/// Create position (without id) or update existing one. Update require If-Match with ETag
+/// of position it is based on - and so does every PUT.
+async fn update_position(
+    req: HttpRequest,
+    db: web::Data<Database>,
+    position_json: Json<PositionDTO>,
+) -> Result<HttpResponse, Error> {
+    let mut position = position_json.into_inner();
+    let if_match = if req.method() == Method::PUT || position.id.is_some() {
+        Some(etag::if_match(&req)?)
+    } else {
+        None
+    };
+    let rules = db.config().validation.clone();
+    let keys = db.keys();
+    let saved = db::block(&db, move |conn| {
+        if let (Some(if_match), Some(id)) = (&if_match, position.id) {
+            let current = PositionDTO::get_simple(id, keys.as_deref(), conn)?;
+            position.version = Some(etag::expected_version(
+                if_match,
+                id,
+                current.version.unwrap_or_default(),
+            )?);
+        }
+        position.try_persist_in_transaction(&rules, keys.as_deref(), conn)
+    })
+    .await?;
+    match saved {
+        Ok(position) => etag::ok(&position, position.version.unwrap_or_default()),
+        Err(DaoError::StaleVersion { id, .. }) => position_precondition_failed(&db, id).await,
+        Err(e) => Err(db::dao_error(e)),
+    }
+}
+
+/// Contracts for deleted position are left without position
+async fn delete_position(
+    req: HttpRequest,
+    db: web::Data<Database>,
+    path: web::Path<String>,
+) -> Result<HttpResponse, Error> {
+    let id: i32 = path.parse().unwrap();
+    let if_match = etag::if_match(&req)?;
+    let keys = db.keys();
+    let deleted = db::block(&db, move |conn| {
+        let mut position = PositionDTO::get_simple(id, keys.as_deref(), conn)?;
+        position.version = Some(etag::expected_version(
+            &if_match,
+            id,
+            position.version.unwrap_or_default(),
+        )?);
+        position.try_delete_with_conn(keys.as_deref(), conn)
+    })
+    .await?;
+    match deleted {
+        Ok(1) => Ok(HttpResponse::Ok()
+            .content_type("application/json")
+            .body(format!("Removed position with id = {}", id))),
+        Ok(n) => Err(ErrorInternalServerError(format!(
+            "Removed {} positions with id = {}",
+            n, id
+        ))),
+        Err(DaoError::StaleVersion { .. }) => position_precondition_failed(&db, id).await,
+        Err(e) if e.is_not_found() => Err(ErrorNotFound(format!(
+            "Not found position with id = {}",
+            id
+        ))),
+        Err(e) => Err(db::dao_error(e)),
+    }
+}
+
+pub fn config(cfg: &mut web::ServiceConfig, prefix: &str) {
+    cfg.service(
+        web::resource(prefix)
+            .wrap(LoggedAsAdmin(&[Method::PUT, Method::POST]))
+            .route(web::get().to(get_positions))
+            .route(web::put().to(update_position))
+            .route(web::post().to(update_position)),
+    );
+    cfg.service(
+        web::resource(format!("{}{}", prefix, "/{id}"))
+            .wrap(LoggedAsAdmin(&[Method::DELETE]))
+            .route(web::get().to(get_position))
+            .route(web::delete().to(delete_position)),
+    );
+}
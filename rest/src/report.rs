@@ -0,0 +1,56 @@
+use actix_web::http::Method;
+use actix_web::{web, Error, HttpResponse};
+use chrono::{Datelike, Local, NaiveDate};
+use dao::{salary_report_with_connection, Currency, Database, ReportGrouping, SalaryReportParams};
+
+use crate::db;
+use crate::session::LoggedGuard::LoggedAsAdmin;
+
+/// `?from=YYYY-MM-DD&to=YYYY-MM-DD&currency=PLN&gross=true&group_by=department&employee_group=1` - from beginning
+/// of current year to today, all currencies, gross salaries, not grouped (by department or position) and of all
+/// employees (not just members of saved group) by default
+#[derive(Deserialize, Debug)]
+pub struct SalaryReportQuery {
+    pub from: Option<NaiveDate>,
+    pub to: Option<NaiveDate>,
+    pub currency: Option<Currency>,
+    pub gross: Option<bool>,
+    pub group_by: Option<ReportGrouping>,
+    pub employee_group: Option<i32>,
+}
+
+impl SalaryReportQuery {
+    fn params(&self, today: NaiveDate) -> SalaryReportParams {
+        SalaryReportParams {
+            from: self
+                .from
+                .unwrap_or_else(|| NaiveDate::from_ymd_opt(today.year(), 1, 1).unwrap()),
+            to: self.to.unwrap_or(today),
+            currency: self.currency,
+            gross: self.gross.unwrap_or(true),
+            group_by: self.group_by,
+            employee_group: self.employee_group,
+        }
+    }
+}
+
+async fn get_salary_report(
+    db: web::Data<Database>,
+    query: web::Query<SalaryReportQuery>,
+) -> Result<HttpResponse, Error> {
+    let params = query.params(Local::now().date_naive());
+    let keys = db.keys();
+    let report = db::try_block(&db, move |conn| salary_report_with_connection(&params, keys.as_deref(), conn)).await?;
+    let body = serde_json::to_string(&report)?;
+    Ok(HttpResponse::Ok()
+        .content_type("application/json")
+        .body(body))
+}
+
+pub fn config(cfg: &mut web::ServiceConfig, prefix: &str) {
+    cfg.service(
+        web::resource(format!("{}{}", prefix, "/salaries"))
+            .wrap(LoggedAsAdmin(&[Method::GET]))
+            .route(web::get().to(get_salary_report)),
+    );
+}
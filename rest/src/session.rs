@@ -1,6 +1,7 @@
 use std::collections::HashMap;
 use std::future::Future;
 use std::pin::Pin;
+use std::rc::Rc;
 use std::sync::Mutex;
 use std::task::{Context, Poll};
 
@@ -13,8 +14,11 @@ use actix_web::web::Json;
 use actix_web::{web, Error, HttpRequest, HttpResponse};
 use actix_web::http::Method;
 use futures::future::{ok, Ready};
+use dao::Database;
 use uuid::Uuid;
 
+use crate::db;
+
 use LoggedGuard::{Logged, LoggedAsAdmin, LoggedAsAdminWithException, LoggedWithException};
 
 lazy_static! {
@@ -32,7 +36,7 @@ pub enum LoggedGuard {
 
 impl<S> Transform<S, ServiceRequest> for LoggedGuard
 where
-    S: Service<ServiceRequest, Response = ServiceResponse, Error = Error>,
+    S: Service<ServiceRequest, Response = ServiceResponse, Error = Error> + 'static,
     S::Future: 'static,
 {
     type Response = ServiceResponse;
@@ -44,38 +48,38 @@ where
     fn new_transform(&self, service: S) -> Self::Future {
         match *self {
             Logged => ok(LoggedGuardMiddleware {
-                service,
+                service: Rc::new(service),
                 as_admin: &[],
                 except: &[],
             }),
             LoggedWithException(except) => ok(LoggedGuardMiddleware {
-                service,
+                service: Rc::new(service),
                 as_admin: &[],
-                except: except,
+                except,
             }),
             LoggedAsAdmin(as_admin) => ok(LoggedGuardMiddleware {
-                service,
-                as_admin: as_admin,
+                service: Rc::new(service),
+                as_admin,
                 except: &[],
             }),
             LoggedAsAdminWithException(as_admin, except) => ok(LoggedGuardMiddleware {
-                service,
-                as_admin: as_admin,
-                except: except,
+                service: Rc::new(service),
+                as_admin,
+                except,
             }),
         }
     }
 }
 
 pub struct LoggedGuardMiddleware<S> {
-    service: S,
+    service: Rc<S>,
     as_admin: &'static [Method],
     except: &'static [Method],
 }
 
 impl<S> Service<ServiceRequest> for LoggedGuardMiddleware<S>
 where
-    S: Service<ServiceRequest, Response = ServiceResponse, Error = Error>,
+    S: Service<ServiceRequest, Response = ServiceResponse, Error = Error> + 'static,
     S::Future: 'static,
 {
     type Response = ServiceResponse;
@@ -87,46 +91,55 @@ where
     }
 
     fn call(&self, req: ServiceRequest) -> Self::Future {
-        if is_logged(&req, self.as_admin, self.except) {
-            let fut = self.service.call(req);
-            Box::pin(async move {
-                let res = fut.await?;
-                Ok(res)
-            })
-        } else {
-            Box::pin(async move {
-                Ok(ServiceResponse::new(
+        let service = Rc::clone(&self.service);
+        let as_admin = self.as_admin;
+        let except = self.except;
+        Box::pin(async move {
+            match is_logged(&req, as_admin, except).await {
+                Ok(true) => service.call(req).await,
+                Ok(false) => Ok(ServiceResponse::new(
                     req.into_parts().0,
                     HttpResponse::new(StatusCode::UNAUTHORIZED),
-                ))
-            })
-        }
+                )),
+                Err(e) => Ok(req.error_response(e)),
+            }
+        })
     }
 }
 
-pub fn is_logged(req: &ServiceRequest, as_admin: &[Method], except: &[Method]) -> bool {
+pub async fn is_logged(
+    req: &ServiceRequest,
+    as_admin: &[Method],
+    except: &[Method],
+) -> Result<bool, Error> {
     if contain_method(req.method(), except) {
-        return true;
+        return Ok(true);
     }
     let session = req
         .cookie("session")
         .map_or("nothing".to_string(), |c| c.value().to_string());
-    if let Some((username, id)) = SESSIONS.lock().unwrap().get(&session) {
-        let user = dao::get_user(*id).unwrap();
+    let logged = SESSIONS.lock().unwrap().get(&session).cloned();
+    if let Some((username, id)) = logged {
+        let db = req
+            .app_data::<web::Data<Database>>()
+            .expect("Database is not configured as application data");
+        let user = db::block(db, move |conn| dao::get_user(id, conn))
+            .await?
+            .unwrap();
         let method = req.method();
         debug!(
             "session: {}, user: {}, is_admin: {}, method: {}, admin rights for methods: {:?}",
             session, user.username, user.is_admin, method, as_admin
         );
         let is_admin_method = contain_method(method, as_admin);
-        if as_admin.is_empty() || !is_admin_method || (is_admin_method && user.is_admin) {
+        if as_admin.is_empty() || !is_admin_method || user.is_admin {
             info!(
                 "Allow access to {} with session {} for user '{}'",
                 req.path(),
                 session,
                 username
             );
-            true
+            Ok(true)
         } else {
             error!(
                 "Unauthorized access to {} with session {} for user '{}'",
@@ -134,7 +147,7 @@ pub fn is_logged(req: &ServiceRequest, as_admin: &[Method], except: &[Method]) -
                 session,
                 username
             );
-            false
+            Ok(false)
         }
     } else {
         error!(
@@ -142,10 +155,20 @@ pub fn is_logged(req: &ServiceRequest, as_admin: &[Method], except: &[Method]) -
             req.path(),
             session
         );
-        false
+        Ok(false)
     }
 }
 
+/// Id of user logged in session of request
+pub fn logged_user_id(req: &HttpRequest) -> Option<i32> {
+    let session = req.cookie("session")?;
+    SESSIONS
+        .lock()
+        .unwrap()
+        .get(session.value())
+        .map(|(_, user_id)| *user_id)
+}
+
 fn contain_method(method: &Method, methods: &[Method]) -> bool {
     methods.iter().find(|m| m == method).is_some()
 }
@@ -156,18 +179,27 @@ pub struct LoginDTO {
     pub password: String,
 }
 
-async fn login(body: Json<LoginDTO>) -> Result<HttpResponse, Error> {
+async fn login(db: web::Data<Database>, body: Json<LoginDTO>) -> Result<HttpResponse, Error> {
     info!(
         "Try to login '{}' with password '{}'",
         &body.username, &body.password
     );
-    for user in dao::get_users() {
+    let username = body.username.clone();
+    let password = body.password.clone();
+    let (users, user) = db::block(&db, move |conn| {
+        (
+            dao::get_users(conn),
+            dao::validate_user(&username, &password, conn),
+        )
+    })
+    .await?;
+    for user in users {
         debug!(
             "There is user '{}' with password '{}' - admin {}",
             user.username, user.password, user.is_admin
         );
     }
-    if let Some(user) = dao::validate_user(&body.username, &body.password) {
+    if let Some(user) = user {
         let session_value = Uuid::new_v4().as_hyphenated().to_string();
         let session_cookie = Cookie::new("session", session_value.to_owned());
         let mut response = HttpResponse::Ok().content_type("text/plain").body(format!(
@@ -0,0 +1,43 @@
+use actix_web::http::Method;
+use actix_web::web::Json;
+use actix_web::{web, Error, HttpResponse};
+use dao::{change_tags_with_connection, get_tags_with_connection, Database, TagsChange};
+
+use crate::db;
+use crate::session::LoggedGuard::{Logged, LoggedAsAdmin};
+
+/// Tags with number of employees which have them
+async fn get_tags(db: web::Data<Database>) -> Result<HttpResponse, Error> {
+    let tags = db::try_block(&db, move |conn| Ok(get_tags_with_connection(conn)?)).await?;
+    let body = serde_json::to_string(&tags)?;
+    Ok(HttpResponse::Ok()
+        .content_type("application/json")
+        .body(body))
+}
+
+/// Add and remove tags of many employees at once - respond with tags of the employees by their id
+async fn change_tags(db: web::Data<Database>, change_json: Json<TagsChange>) -> Result<HttpResponse, Error> {
+    let change = change_json.into_inner();
+    let changed = db::try_block(&db, move |conn| change_tags_with_connection(&change, conn)).await?;
+    let body = serde_json::to_string(&changed)?;
+    Ok(HttpResponse::Ok()
+        .content_type("application/json")
+        .body(body))
+}
+
+pub fn config(cfg: &mut web::ServiceConfig, prefix: &str) {
+    cfg.service(
+        web::resource(prefix)
+            .wrap(Logged)
+            .route(web::get().to(get_tags)),
+    );
+}
+
+/// Bulk change of tags as sub-resource of employees - it has to be configured before `/employees/{id}`
+pub fn config_employees(cfg: &mut web::ServiceConfig, prefix: &str) {
+    cfg.service(
+        web::resource(format!("{}{}", prefix, "/tags"))
+            .wrap(LoggedAsAdmin(&[Method::POST]))
+            .route(web::post().to(change_tags)),
+    );
+}
@@ -0,0 +1,461 @@
+use actix_web::error::{ErrorBadRequest, ErrorForbidden, ErrorInternalServerError, ErrorNotFound};
+use actix_web::http::header::{ContentDisposition, DispositionParam, DispositionType};
+use actix_web::http::Method;
+use actix_web::web::Json;
+use actix_web::{web, Error, HttpRequest, HttpResponse};
+use chrono::{Datelike, Local, NaiveDate};
+use dao::{
+    can_approve, monthly_summary_with_connection, timesheet_rows_with_connection, Crud, DaoError, DaoResult,
+    Database, DbConnection, EmployeeDTO, PageRequest, TimesheetEntryDTO, TimesheetRow, TimesheetWeekDTO,
+    WeekStatus,
+};
+
+use crate::db;
+use crate::employee::{group_members, logged_user, scope};
+use crate::etag;
+use crate::session::LoggedGuard::Logged;
+
+/// Entry of timesheet of employee - None when there is no such entry of the employee or the employee is out
+/// of scope of logged user
+fn entry_of_employee(
+    user_id: i32,
+    e_id: i32,
+    t_id: i32,
+    conn: &mut DbConnection,
+) -> DaoResult<Option<TimesheetEntryDTO>> {
+    if !scope(user_id, conn)?.contains(e_id, conn)? {
+        return Ok(None);
+    }
+    Ok(TimesheetEntryDTO::get_with_conn(t_id, None, conn).filter(|e| e.employee_id == Some(e_id)))
+}
+
+fn parse_date(s: &str) -> Result<NaiveDate, Error> {
+    s.parse()
+        .map_err(|_| ErrorBadRequest(format!("'{}' is not date in format YYYY-MM-DD", s)))
+}
+
+/// `?from=YYYY-MM-DD&to=YYYY-MM-DD&page=1&per_page=50` - all entries and first page by default
+#[derive(Deserialize, Debug)]
+pub struct EntriesQuery {
+    pub from: Option<NaiveDate>,
+    pub to: Option<NaiveDate>,
+    pub page: Option<i64>,
+    pub per_page: Option<i64>,
+}
+
+/// Page of timesheet entries of employee ordered by date - employee out of scope of logged user is reported
+/// as not found
+async fn get_entries(
+    req: HttpRequest,
+    db: web::Data<Database>,
+    path: web::Path<String>,
+    query: web::Query<EntriesQuery>,
+) -> Result<HttpResponse, Error> {
+    let e_id: i32 = path.parse().unwrap();
+    let user_id = logged_user(&req)?;
+    let (from, to) = (query.from, query.to);
+    let page = PageRequest::new(query.page, query.per_page);
+    let keys = db.keys();
+    let entries = db::try_block(&db, move |conn| {
+        if !scope(user_id, conn)?.contains(e_id, conn)?
+            || EmployeeDTO::get_with_conn(e_id, keys.as_deref(), conn).is_none()
+        {
+            return Ok(None);
+        }
+        Ok(Some(TimesheetEntryDTO::page_by_employee_with_connection(
+            e_id, from, to, page, conn,
+        )?))
+    })
+    .await?;
+    match entries {
+        Some(entries) => {
+            let body = serde_json::to_string(&entries)?;
+            Ok(HttpResponse::Ok()
+                .content_type("application/json")
+                .body(body))
+        }
+        None => Err(ErrorNotFound(format!(
+            "Can't find employee with id = {}",
+            e_id
+        ))),
+    }
+}
+
+async fn get_entry(
+    req: HttpRequest,
+    db: web::Data<Database>,
+    path: web::Path<(String, String)>,
+) -> Result<HttpResponse, Error> {
+    let e_id: i32 = path.0.parse().unwrap();
+    let t_id: i32 = path.1.parse().unwrap();
+    let user_id = logged_user(&req)?;
+    match db::try_block(&db, move |conn| entry_of_employee(user_id, e_id, t_id, conn)).await? {
+        Some(entry) => etag::ok(&entry, entry.version.unwrap_or_default()),
+        None => Err(ErrorNotFound(format!(
+            "Can't find timesheet entry with id = {} of employee with id = {}",
+            t_id, e_id
+        ))),
+    }
+}
+
+/// 412 with current state of timesheet entry
+async fn entry_precondition_failed(db: &Database, id: i32) -> Result<HttpResponse, Error> {
+    let keys = db.keys();
+    let current = db::try_block(db, move |conn| Ok(TimesheetEntryDTO::get_simple(id, keys.as_deref(), conn)?)).await?;
+    etag::precondition_failed(&current, current.version.unwrap_or_default())
+}
+
+/// Create timesheet entry (without id) or update existing one. Update require If-Match with ETag of entry
+/// it is based on - and so does every PUT.
+async fn update_entry(
+    req: HttpRequest,
+    db: web::Data<Database>,
+    path: web::Path<String>,
+    entry_json: Json<TimesheetEntryDTO>,
+) -> Result<HttpResponse, Error> {
+    let e_id: i32 = path.parse().unwrap();
+    let user_id = logged_user(&req)?;
+    let mut entry = entry_json.into_inner();
+    entry.employee_id = Some(e_id);
+    let if_match = if req.method() == Method::PUT || entry.id.is_some() {
+        Some(etag::if_match(&req)?)
+    } else {
+        None
+    };
+    let rules = db.config().validation.clone();
+    let keys = db.keys();
+    let saved = db::block(&db, move |conn| {
+        if !scope(user_id, conn)?.contains(e_id, conn)? {
+            return Err(DaoError::not_found());
+        }
+        EmployeeDTO::get_simple(e_id, keys.as_deref(), conn)?;
+        if let (Some(if_match), Some(id)) = (&if_match, entry.id) {
+            let current = entry_of_employee(user_id, e_id, id, conn)?.ok_or_else(DaoError::not_found)?;
+            entry.version = Some(etag::expected_version(
+                if_match,
+                id,
+                current.version.unwrap_or_default(),
+            )?);
+        }
+        entry.try_persist_in_transaction(&rules, keys.as_deref(), conn)
+    })
+    .await?;
+    match saved {
+        Ok(entry) => etag::ok(&entry, entry.version.unwrap_or_default()),
+        Err(DaoError::StaleVersion { id, .. }) => entry_precondition_failed(&db, id).await,
+        Err(e) if e.is_not_found() => Err(ErrorNotFound(format!(
+            "Can't find employee with id = {} or its timesheet entry",
+            e_id
+        ))),
+        Err(e) => Err(db::dao_error(e)),
+    }
+}
+
+/// Entries of submitted or approved week can't be deleted - 422
+async fn delete_entry(
+    req: HttpRequest,
+    db: web::Data<Database>,
+    path: web::Path<(String, String)>,
+) -> Result<HttpResponse, Error> {
+    let e_id: i32 = path.0.parse().unwrap();
+    let t_id: i32 = path.1.parse().unwrap();
+    let user_id = logged_user(&req)?;
+    let if_match = etag::if_match(&req)?;
+    let keys = db.keys();
+    let deleted = db::block(&db, move |conn| {
+        let mut entry = entry_of_employee(user_id, e_id, t_id, conn)?.ok_or_else(DaoError::not_found)?;
+        entry.version = Some(etag::expected_version(
+            &if_match,
+            t_id,
+            entry.version.unwrap_or_default(),
+        )?);
+        entry.check_deletable(conn)?;
+        entry.try_delete_with_conn(keys.as_deref(), conn)
+    })
+    .await?;
+    match deleted {
+        Ok(1) => Ok(HttpResponse::Ok()
+            .content_type("application/json")
+            .body(format!("Removed timesheet entry with id = {}", t_id))),
+        Ok(n) => Err(ErrorInternalServerError(format!(
+            "Removed {} timesheet entries with id = {}",
+            n, t_id
+        ))),
+        Err(DaoError::StaleVersion { .. }) => entry_precondition_failed(&db, t_id).await,
+        Err(e) if e.is_not_found() => Err(ErrorNotFound(format!(
+            "Can't find timesheet entry with id = {} of employee with id = {}",
+            t_id, e_id
+        ))),
+        Err(e) => Err(db::dao_error(e)),
+    }
+}
+
+/// Submitted week - 404 when the week was never submitted
+async fn get_week(
+    req: HttpRequest,
+    db: web::Data<Database>,
+    path: web::Path<(String, String)>,
+) -> Result<HttpResponse, Error> {
+    let e_id: i32 = path.0.parse().unwrap();
+    let start = parse_date(&path.1)?;
+    let user_id = logged_user(&req)?;
+    let week = db::try_block(&db, move |conn| {
+        if !scope(user_id, conn)?.contains(e_id, conn)? {
+            return Ok(None);
+        }
+        Ok(TimesheetWeekDTO::get_with_connection(e_id, start, conn)?)
+    })
+    .await?;
+    match week {
+        Some(week) => etag::ok(&week, week.version),
+        None => Err(ErrorNotFound(format!(
+            "Week starting {} of employee with id = {} is not submitted",
+            start, e_id
+        ))),
+    }
+}
+
+/// Submit week starting on Monday for approval - again when it was rejected
+async fn submit_week(
+    req: HttpRequest,
+    db: web::Data<Database>,
+    path: web::Path<(String, String)>,
+) -> Result<HttpResponse, Error> {
+    let e_id: i32 = path.0.parse().unwrap();
+    let start = parse_date(&path.1)?;
+    let user_id = logged_user(&req)?;
+    let keys = db.keys();
+    let week = db::try_block(&db, move |conn| {
+        if !scope(user_id, conn)?.contains(e_id, conn)? {
+            return Err(DaoError::not_found());
+        }
+        EmployeeDTO::get_simple(e_id, keys.as_deref(), conn)?;
+        TimesheetWeekDTO::submit_with_connection(e_id, start, conn)
+    })
+    .await?;
+    etag::ok(&week, week.version)
+}
+
+/// 412 with current state of week
+async fn week_precondition_failed(db: &Database, e_id: i32, start: NaiveDate) -> Result<HttpResponse, Error> {
+    let current = db::try_block(db, move |conn| {
+        TimesheetWeekDTO::get_with_connection(e_id, start, conn)?.ok_or_else(DaoError::not_found)
+    })
+    .await?;
+    etag::precondition_failed(&current, current.version)
+}
+
+/// Approve or reject submitted week - allowed to admins and (recursive) managers of the employee. Require
+/// If-Match with ETag of week the decision is based on.
+async fn decide_week(
+    req: HttpRequest,
+    db: web::Data<Database>,
+    path: web::Path<(String, String)>,
+    decision: WeekStatus,
+) -> Result<HttpResponse, Error> {
+    let e_id: i32 = path.0.parse().unwrap();
+    let start = parse_date(&path.1)?;
+    let user_id = logged_user(&req)?;
+    let if_match = etag::if_match(&req)?;
+    let decided = db::block(&db, move |conn| {
+        if !scope(user_id, conn)?.contains(e_id, conn)? {
+            return Err(DaoError::not_found());
+        }
+        let week = TimesheetWeekDTO::get_with_connection(e_id, start, conn)?.ok_or_else(DaoError::not_found)?;
+        if !can_approve(user_id, e_id, conn)? {
+            return Ok(None);
+        }
+        let expected = etag::expected_version(&if_match, week.id, week.version)?;
+        TimesheetWeekDTO::decide_with_connection(e_id, start, expected, decision, user_id, conn).map(Some)
+    })
+    .await?;
+    match decided {
+        Ok(Some(week)) => etag::ok(&week, week.version),
+        Ok(None) => Err(ErrorForbidden(format!(
+            "You can't decide about timesheets of employee with id = {}",
+            e_id
+        ))),
+        Err(DaoError::StaleVersion { .. }) => week_precondition_failed(&db, e_id, start).await,
+        Err(e) if e.is_not_found() => Err(ErrorNotFound(format!(
+            "Week starting {} of employee with id = {} is not submitted",
+            start, e_id
+        ))),
+        Err(e) => Err(db::dao_error(e)),
+    }
+}
+
+async fn approve_week(
+    req: HttpRequest,
+    db: web::Data<Database>,
+    path: web::Path<(String, String)>,
+) -> Result<HttpResponse, Error> {
+    decide_week(req, db, path, WeekStatus::Approved).await
+}
+
+async fn reject_week(
+    req: HttpRequest,
+    db: web::Data<Database>,
+    path: web::Path<(String, String)>,
+) -> Result<HttpResponse, Error> {
+    decide_week(req, db, path, WeekStatus::Rejected).await
+}
+
+/// `?year=2021&month=3&employee_group=1` - current month and all employees by default
+#[derive(Deserialize, Debug)]
+pub struct SummaryQuery {
+    pub year: Option<i32>,
+    pub month: Option<u32>,
+    pub employee_group: Option<i32>,
+}
+
+/// Worked hours (and pay by hourly salaries) per employee logged user can see
+async fn get_summary(
+    req: HttpRequest,
+    db: web::Data<Database>,
+    query: web::Query<SummaryQuery>,
+) -> Result<HttpResponse, Error> {
+    let user_id = logged_user(&req)?;
+    let today = Local::now().date_naive();
+    let year = query.year.unwrap_or(today.year());
+    let month = query.month.unwrap_or(today.month());
+    let group = query.employee_group;
+    let keys = db.keys();
+    let summaries = db::try_block(&db, move |conn| {
+        let scope = scope(user_id, conn)?;
+        let mut summaries = monthly_summary_with_connection(year, month, scope, keys.as_deref(), conn)?;
+        if let Some(members) = group_members(group, scope, keys.as_deref(), conn)? {
+            summaries.retain(|s| members.contains(&s.employee_id));
+        }
+        Ok(summaries)
+    })
+    .await?;
+    let body = serde_json::to_string(&summaries)?;
+    Ok(HttpResponse::Ok()
+        .content_type("application/json")
+        .body(body))
+}
+
+/// `?from=YYYY-MM-DD&to=YYYY-MM-DD&employee_id=1&employee_group=1` - current month and all employees by default
+#[derive(Deserialize, Debug)]
+pub struct ExportQuery {
+    pub from: Option<NaiveDate>,
+    pub to: Option<NaiveDate>,
+    pub employee_id: Option<i32>,
+    pub employee_group: Option<i32>,
+}
+
+/// Timesheet entries as CSV with hours as decimal number
+fn to_csv(rows: &[TimesheetRow]) -> Result<Vec<u8>, csv::Error> {
+    let mut writer = csv::Writer::from_writer(vec![]);
+    writer.write_record([
+        "employee_id",
+        "first_name",
+        "last_name",
+        "work_date",
+        "hours",
+        "category",
+        "note",
+        "status",
+    ])?;
+    for row in rows {
+        writer.write_record([
+            row.employee_id.to_string(),
+            row.first_name.clone(),
+            row.last_name.clone(),
+            row.work_date.to_string(),
+            row.hours.decimal(),
+            row.category.clone(),
+            row.note.clone().unwrap_or_default(),
+            row.status.map_or("draft", |s| s.as_str()).to_string(),
+        ])?;
+    }
+    writer
+        .into_inner()
+        .map_err(|e| csv::Error::from(e.into_error()))
+}
+
+/// Timesheet entries of employees logged user can see as CSV attachment
+async fn export_csv(
+    req: HttpRequest,
+    db: web::Data<Database>,
+    query: web::Query<ExportQuery>,
+) -> Result<HttpResponse, Error> {
+    let user_id = logged_user(&req)?;
+    let today = Local::now().date_naive();
+    let from = query.from.unwrap_or_else(|| today.with_day(1).unwrap());
+    let to = query.to.unwrap_or_else(|| {
+        let next_month = from.with_day(1).unwrap() + chrono::Months::new(1);
+        next_month.pred_opt().unwrap()
+    });
+    let employee = query.employee_id;
+    let group = query.employee_group;
+    let keys = db.keys();
+    let rows = db::try_block(&db, move |conn| {
+        let scope = scope(user_id, conn)?;
+        let mut rows = timesheet_rows_with_connection(from, to, employee, scope, conn)?;
+        if let Some(members) = group_members(group, scope, keys.as_deref(), conn)? {
+            rows.retain(|r| members.contains(&r.employee_id));
+        }
+        Ok(rows)
+    })
+    .await?;
+    let body = to_csv(&rows).map_err(ErrorInternalServerError)?;
+    Ok(HttpResponse::Ok()
+        .content_type("text/csv; charset=utf-8")
+        .insert_header(ContentDisposition {
+            disposition: DispositionType::Attachment,
+            parameters: vec![DispositionParam::Filename(format!("timesheets_{}_{}.csv", from, to))],
+        })
+        .body(body))
+}
+
+/// Timesheets as sub-resource of employees - `prefix` is prefix of employees
+pub fn config(cfg: &mut web::ServiceConfig, prefix: &str) {
+    cfg.service(
+        web::resource(format!("{}{}", prefix, "/{id}/timesheets"))
+            .wrap(Logged)
+            .route(web::get().to(get_entries))
+            .route(web::put().to(update_entry))
+            .route(web::post().to(update_entry)),
+    );
+    cfg.service(
+        web::resource(format!("{}{}", prefix, "/{id}/timesheets/{entry_id}"))
+            .wrap(Logged)
+            .route(web::get().to(get_entry))
+            .route(web::delete().to(delete_entry)),
+    );
+    cfg.service(
+        web::resource(format!("{}{}", prefix, "/{id}/timesheets/weeks/{week_start}"))
+            .wrap(Logged)
+            .route(web::get().to(get_week)),
+    );
+    cfg.service(
+        web::resource(format!("{}{}", prefix, "/{id}/timesheets/weeks/{week_start}/submit"))
+            .wrap(Logged)
+            .route(web::post().to(submit_week)),
+    );
+    cfg.service(
+        web::resource(format!("{}{}", prefix, "/{id}/timesheets/weeks/{week_start}/approve"))
+            .wrap(Logged)
+            .route(web::post().to(approve_week)),
+    );
+    cfg.service(
+        web::resource(format!("{}{}", prefix, "/{id}/timesheets/weeks/{week_start}/reject"))
+            .wrap(Logged)
+            .route(web::post().to(reject_week)),
+    );
+}
+
+/// Monthly summary and CSV export - `prefix` is prefix of timesheets
+pub fn config_reports(cfg: &mut web::ServiceConfig, prefix: &str) {
+    cfg.service(
+        web::resource(format!("{}{}", prefix, "/summary"))
+            .wrap(Logged)
+            .route(web::get().to(get_summary)),
+    );
+    cfg.service(
+        web::resource(format!("{}{}", prefix, "/export"))
+            .wrap(Logged)
+            .route(web::get().to(export_csv)),
+    );
+}
@@ -1,9 +1,11 @@
 use actix_web::error::{ErrorImATeapot, ErrorInternalServerError, ErrorNotFound};
 use actix_web::web::Json;
-use actix_web::{web, Error, HttpResponse};
+use actix_web::{web, Error, HttpRequest, HttpResponse};
 use actix_web::http::Method;
-use dao::{NewUser, User};
+use dao::{DaoError, DaoResult, Database, NewUser, User};
 
+use crate::db;
+use crate::etag;
 use crate::session::LoggedGuard::{Logged, LoggedAsAdmin};
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
@@ -12,6 +14,22 @@ pub struct UserDTO {
     pub username: Option<String>,
     pub password: Option<String>,
     pub is_admin: Option<bool>,
+    /// Can export and anonymize personal data of employees
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub privacy_officer: Option<bool>,
+    pub version: Option<i32>,
+    /// Employee the user is - `null` unlink user from employee, missing field leave it as it is
+    #[serde(default, deserialize_with = "present", skip_serializing_if = "Option::is_none")]
+    pub employee_id: Option<Option<i32>>,
+}
+
+/// Field which is present in JSON (even as `null`) is Some
+fn present<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: serde::Deserialize<'de>,
+{
+    T::deserialize(deserializer).map(Some)
 }
 
 impl From<User> for UserDTO {
@@ -21,6 +39,9 @@ impl From<User> for UserDTO {
             username: Some(u.username),
             password: Some(u.password),
             is_admin: Some(u.is_admin),
+            privacy_officer: Some(u.privacy_officer),
+            version: Some(u.version),
+            employee_id: Some(u.employee_id),
         }
     }
 }
@@ -31,6 +52,8 @@ impl From<UserDTO> for NewUser {
             username: u.username.unwrap_or("".to_string()),
             password: u.password.unwrap_or("".to_string()),
             is_admin: u.is_admin.unwrap_or(false),
+            employee_id: u.employee_id.flatten(),
+            privacy_officer: u.privacy_officer.unwrap_or(false),
         }
     }
 }
@@ -46,67 +69,102 @@ impl UserDTO {
         if let Some(is_admin) = &self.is_admin {
             user.is_admin = *is_admin
         };
+        if let Some(privacy_officer) = &self.privacy_officer {
+            user.privacy_officer = *privacy_officer
+        };
+        if let Some(employee_id) = self.employee_id {
+            user.employee_id = employee_id
+        };
     }
 }
 
-async fn get_users() -> Result<HttpResponse, Error> {
-    let users: Vec<UserDTO> = dao::get_users().into_iter().map(UserDTO::from).collect();
+async fn get_users(db: web::Data<Database>) -> Result<HttpResponse, Error> {
+    let users: Vec<UserDTO> = db::block(&db, dao::get_users)
+        .await?
+        .into_iter()
+        .map(UserDTO::from)
+        .collect();
     let body = serde_json::to_string(&users)?;
     Ok(HttpResponse::Ok()
         .content_type("application/json")
         .body(body))
 }
 
-async fn get_user(path: web::Path<String>) -> Result<HttpResponse, Error> {
+async fn get_user(db: web::Data<Database>, path: web::Path<String>) -> Result<HttpResponse, Error> {
     let id: i32 = path.parse().unwrap();
-    match dao::get_user(id) {
-        Some(user) => {
-            let body = serde_json::to_string(&UserDTO::from(user))?;
-            Ok(HttpResponse::Ok()
-                .content_type("application/json")
-                .body(body))
-        }
+    match db::block(&db, move |conn| dao::get_user(id, conn)).await? {
+        Some(user) => etag::ok(&UserDTO::from(user.clone()), user.version),
+        None => Err(ErrorNotFound(format!("Can't find user with id = {}", id))),
+    }
+}
+
+/// 412 with current state of user
+async fn user_precondition_failed(db: &Database, id: i32) -> Result<HttpResponse, Error> {
+    match db::block(db, move |conn| dao::get_user(id, conn)).await? {
+        Some(user) => etag::precondition_failed(&UserDTO::from(user.clone()), user.version),
         None => Err(ErrorNotFound(format!("Can't find user with id = {}", id))),
     }
 }
 
-async fn update_user(user_json: Json<UserDTO>) -> Result<HttpResponse, Error> {
+/// Create user (without id) or update existing one. Update require If-Match with ETag
+/// of user it is based on - and so does every PUT.
+async fn update_user(
+    req: HttpRequest,
+    db: web::Data<Database>,
+    user_json: Json<UserDTO>,
+) -> Result<HttpResponse, Error> {
     let user = user_json.clone();
-    let result = if let Some(id) = user.id {
-        let mut existing_user = dao::get_user(id).unwrap();
-        user.update_user(&mut existing_user);
-        dao::update_user(&existing_user)
+    let if_match = if req.method() == Method::PUT || user.id.is_some() {
+        Some(etag::if_match(&req)?)
     } else {
-        dao::create_user(&NewUser::from(user))
+        None
     };
-    if result.is_ok() {
-        Ok(HttpResponse::Ok()
-            .content_type("application/json")
-            .body(serde_json::to_string(&result.unwrap())?))
-    } else {
-        Err(ErrorInternalServerError(format!(
+    let result = db::block(&db, move |conn| -> DaoResult<User> {
+        match (user.id, if_match) {
+            (Some(id), Some(if_match)) => {
+                let mut existing_user = dao::get_user(id, conn)
+                    .ok_or_else(DaoError::not_found)?;
+                existing_user.version = etag::expected_version(&if_match, id, existing_user.version)?;
+                user.update_user(&mut existing_user);
+                dao::update_user(&existing_user, conn)
+            }
+            _ => Ok(dao::create_user(&NewUser::from(user), conn)?),
+        }
+    })
+    .await?;
+    match result {
+        Ok(user) => etag::ok(&user, user.version),
+        Err(DaoError::StaleVersion { id, .. }) => user_precondition_failed(&db, id).await,
+        Err(e) if e.is_not_found() => Err(db::dao_error(e)),
+        Err(e) => Err(ErrorInternalServerError(format!(
             "Failed to update user {:?} because {:?}",
-            user_json,
-            result.unwrap_err()
-        )))
+            user_json, e
+        ))),
     }
 }
 
-async fn delete_user(path: web::Path<String>) -> Result<HttpResponse, Error> {
+async fn delete_user(
+    req: HttpRequest,
+    db: web::Data<Database>,
+    path: web::Path<String>,
+) -> Result<HttpResponse, Error> {
     let id: i32 = path.parse().unwrap();
-    if let Some(user) = dao::get_user(id) {
-        match dao::delete_user(&user) {
-            Ok(deleted) if deleted == 0 => Err(ErrorImATeapot("Deleted 0 users!?".to_string())),
-            Ok(deleted) if deleted > 1 => {
-                Err(ErrorImATeapot(format!("Deleted {}>1 users!?", deleted)))
-            }
-            Ok(_) => Ok(HttpResponse::Ok()
-                .content_type("application/json")
-                .body("{deleted:1}")),
-            Err(e) => Err(ErrorInternalServerError(e)),
-        }
-    } else {
-        Err(ErrorNotFound(format!("Can't find user with id = {}", id)))
+    let if_match = etag::if_match(&req)?;
+    let deleted = db::block(&db, move |conn| {
+        let mut user = dao::get_user(id, conn)
+            .ok_or_else(DaoError::not_found)?;
+        user.version = etag::expected_version(&if_match, id, user.version)?;
+        dao::delete_user(&user, conn)
+    })
+    .await?;
+    match deleted {
+        Ok(deleted) if deleted > 1 => Err(ErrorImATeapot(format!("Deleted {}>1 users!?", deleted))),
+        Ok(_) => Ok(HttpResponse::Ok()
+            .content_type("application/json")
+            .body("{deleted:1}")),
+        Err(DaoError::StaleVersion { .. }) => user_precondition_failed(&db, id).await,
+        Err(e) if e.is_not_found() => Err(ErrorNotFound(format!("Can't find user with id = {}", id))),
+        Err(e) => Err(ErrorInternalServerError(e)),
     }
 }
 
@@ -116,6 +174,9 @@ async fn get_user_template() -> Result<HttpResponse, Error> {
         username: Some("".to_string()),
         password: Some("".to_string()),
         is_admin: Some(false),
+        privacy_officer: Some(false),
+        version: None,
+        employee_id: Some(None),
     };
     let body = serde_json::to_string(&user)?;
     Ok(HttpResponse::Ok()
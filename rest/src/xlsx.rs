@@ -0,0 +1,180 @@
+use std::fmt::Write;
+use std::io::Cursor;
+
+use calamine::{Data, Reader, Xlsx};
+use chrono::NaiveTime;
+use rust_xlsxwriter::Workbook;
+pub use rust_xlsxwriter::XlsxError;
+use zip::ZipArchive;
+
+/// XLSX (Office Open XML workbook) is ZIP archive - it starts with local file header
+pub fn is_xlsx(content: &[u8]) -> bool {
+    content.starts_with(b"PK\x03\x04")
+}
+
+/// Biggest unpacked workbook - protection against ZIP bombs
+const MAX_UNPACKED_SIZE: u64 = 256 * 1024 * 1024;
+
+/// Text of cell - dates (without time) are formatted by `date_format` (chrono format), numbers as they are
+fn cell_text(cell: &Data, date_format: &str) -> Result<String, String> {
+    Ok(match cell {
+        Data::Empty => String::new(),
+        Data::String(text) | Data::DateTimeIso(text) | Data::DurationIso(text) => text.clone(),
+        Data::Int(number) => number.to_string(),
+        Data::Float(number) => number.to_string(),
+        Data::Bool(value) => value.to_string(),
+        Data::DateTime(date) => match date.as_datetime() {
+            Some(date) if date.time() == NaiveTime::MIN => {
+                let mut text = String::new();
+                write!(text, "{}", date.format(date_format))
+                    .map_err(|_| format!("invalid date format {}", date_format))?;
+                text
+            }
+            Some(date) => date.to_string(),
+            None => date.as_f64().to_string(),
+        },
+        Data::Error(error) => error.to_string(),
+    })
+}
+
+/// Rows of the first worksheet of XLSX workbook as text (see cell_text()) - they start at `A1`, so the first row is
+/// the first row of worksheet even when it is empty
+pub fn read_rows(content: &[u8], date_format: &str) -> Result<Vec<Vec<String>>, String> {
+    let mut archive = ZipArchive::new(Cursor::new(content)).map_err(|e| e.to_string())?;
+    let mut unpacked_size = 0;
+    for index in 0..archive.len() {
+        unpacked_size += archive.by_index_raw(index).map_err(|e| e.to_string())?.size();
+    }
+    if unpacked_size > MAX_UNPACKED_SIZE {
+        return Err(format!("workbook is bigger than {} bytes unpacked", MAX_UNPACKED_SIZE));
+    }
+    let mut workbook = Xlsx::new(Cursor::new(content)).map_err(|e| e.to_string())?;
+    let range = workbook
+        .worksheet_range_at(0)
+        .ok_or("workbook has no worksheet")?
+        .map_err(|e| e.to_string())?;
+    let Some((first_row, first_column)) = range.start() else {
+        return Ok(vec![]);
+    };
+    let mut rows = vec![vec![]; first_row as usize];
+    for cells in range.rows() {
+        let mut row = vec![String::new(); first_column as usize];
+        for cell in cells {
+            row.push(cell_text(cell, date_format)?);
+        }
+        while row.last().is_some_and(String::is_empty) {
+            row.pop();
+        }
+        rows.push(row);
+    }
+    Ok(rows)
+}
+
+/// Cell of written worksheet - numbers are decimal numbers as text (`"1234.56"`), text is written as string cell (even
+/// when it starts with `=` it is never taken for formula)
+pub enum Cell<'a> {
+    Empty,
+    Text(&'a str),
+    Number(&'a str),
+}
+
+/// Workbook with one worksheet written row by row - rows are kept in temporary file (not in memory) till the whole
+/// workbook is taken by finish()
+pub struct XlsxWriter {
+    workbook: Workbook,
+    rows: u32,
+}
+
+impl XlsxWriter {
+    /// Workbook with worksheet of given name (at most 31 characters and without any of `[]:*?/\`)
+    pub fn new(sheet_name: &str) -> Result<XlsxWriter, XlsxError> {
+        let mut workbook = Workbook::new();
+        workbook.add_worksheet_with_constant_memory().set_name(sheet_name)?;
+        Ok(XlsxWriter { workbook, rows: 0 })
+    }
+
+    pub fn write_row(&mut self, cells: &[Cell]) -> Result<(), XlsxError> {
+        let row = self.rows;
+        let sheet = self.workbook.worksheet_from_index(0)?;
+        for (column, cell) in cells.iter().enumerate() {
+            let column = u16::try_from(column).map_err(|_| XlsxError::RowColumnLimitError)?;
+            match cell {
+                Cell::Empty => {}
+                Cell::Text(text) => {
+                    sheet.write_string(row, column, *text)?;
+                }
+                Cell::Number(number) => match number.parse::<f64>() {
+                    Ok(value) => {
+                        sheet.write_number(row, column, value)?;
+                    }
+                    Err(_) => {
+                        sheet.write_string(row, column, *number)?;
+                    }
+                },
+            }
+        }
+        self.rows += 1;
+        Ok(())
+    }
+
+    /// The whole workbook
+    pub fn finish(mut self) -> Result<Vec<u8>, XlsxError> {
+        self.workbook.save_to_buffer()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rust_xlsxwriter::{ExcelDateTime, Format};
+
+    use super::*;
+
+    #[test]
+    fn rows_are_read_from_first_worksheet() {
+        let mut workbook = Workbook::new();
+        let sheet = workbook.add_worksheet();
+        sheet.write_string(0, 1, "first_name").unwrap();
+        sheet.write_string(0, 2, "hire_date").unwrap();
+        sheet.write_string(2, 1, "Jan & syn").unwrap();
+        let date = ExcelDateTime::from_ymd(2023, 1, 1).unwrap();
+        sheet.write_datetime_with_format(2, 2, &date, &Format::new().set_num_format("yyyy-mm-dd")).unwrap();
+        sheet.write_boolean(2, 3, true).unwrap();
+        sheet.write_number(3, 1, 12.5).unwrap();
+        sheet.write_string(3, 2, "").unwrap();
+        workbook.add_worksheet().write_string(0, 0, "ignored").unwrap();
+        let content = workbook.save_to_buffer().unwrap();
+
+        assert!(is_xlsx(&content));
+        assert_eq!(
+            read_rows(&content, "%d.%m.%Y").unwrap(),
+            vec![
+                vec!["", "first_name", "hire_date"],
+                vec![],
+                vec!["", "Jan & syn", "01.01.2023", "true"],
+                vec!["", "12.5"],
+            ]
+        );
+        assert!(read_rows(&content, "%Q").is_err(), "Invalid date format should be reported");
+        assert!(read_rows(b"PK\x03\x04 not really", "%Y-%m-%d").is_err());
+    }
+
+    #[test]
+    fn written_workbook_is_read_back() {
+        let mut writer = XlsxWriter::new("employees").unwrap();
+        writer.write_row(&[Cell::Text("id"), Cell::Text("name"), Cell::Text("amount")]).unwrap();
+        for i in 1..=30 {
+            let name = format!("Zośka \"{}\" <a&b>", i);
+            writer
+                .write_row(&[Cell::Number(&i.to_string()), Cell::Text(&name), Cell::Empty, Cell::Number("12.50")])
+                .unwrap();
+        }
+        let workbook = writer.finish().unwrap();
+
+        assert!(is_xlsx(&workbook));
+        let rows = read_rows(&workbook, "%Y-%m-%d").unwrap();
+        assert_eq!(rows.len(), 31);
+        assert_eq!(rows[0], vec!["id", "name", "amount"]);
+        assert_eq!(rows[30], vec!["30", "Zośka \"30\" <a&b>", "", "12.5"]);
+        assert!(XlsxWriter::new("employees: all").is_err(), "Invalid name of worksheet should be reported");
+    }
+}
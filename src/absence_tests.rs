@@ -0,0 +1,186 @@
+use actix_web::http::header::{ETAG, IF_MATCH};
+use actix_web::http::StatusCode;
+use actix_web::{test, App};
+use chrono::NaiveDate;
+use dao::{AbsenceBalance, AbsenceDTO, AbsenceStatus, AbsenceTypeDTO, CalendarEntry, EmployeeDTO};
+use rest::UserDTO;
+
+use crate::commons_for_tests;
+use crate::employee_tests::new_employee;
+use crate::main_tests::{login_as_admin, login_as_user};
+
+const VACATION: i32 = 1;
+
+fn vacation(from: NaiveDate, to: NaiveDate) -> AbsenceDTO {
+    AbsenceDTO {
+        id: None,
+        employee_id: None,
+        absence_type_id: VACATION,
+        from_date: from,
+        to_date: to,
+        days: 0,
+        status: AbsenceStatus::Requested,
+        decided_by: None,
+        comment: Some("Holiday".to_string()),
+        search_string: "".to_string(),
+        version: None,
+    }
+}
+
+#[actix_rt::test]
+async fn absence_is_requested_and_approved_by_manager() {
+    let db = setup_test!("absence_is_requested_and_approved_by_manager");
+
+    let app = test::init_service(App::new().configure(rest::config_with_db(db.clone()))).await;
+    let admin_session = login_as_admin(&app).await.unwrap();
+    let user_session = login_as_user(&app).await.unwrap();
+
+    let mut ids = vec![];
+    for (last_name, manager) in [("Manager", None), ("Developer", Some(0))] {
+        let employee = EmployeeDTO {
+            last_name: last_name.to_string(),
+            manager_id: manager.map(|m: usize| ids[m]),
+            ..new_employee()
+        };
+        let req = test::TestRequest::post()
+            .uri("/employees")
+            .cookie(admin_session.clone())
+            .set_json(&employee)
+            .to_request();
+        let created: EmployeeDTO = test::call_and_read_body_json(&app, req).await;
+        ids.push(created.id.unwrap());
+    }
+    let (manager, developer) = (ids[0], ids[1]);
+
+    // User is the manager
+    let req = test::TestRequest::put()
+        .uri("/users")
+        .cookie(admin_session.clone())
+        .insert_header((IF_MATCH, "\"1\""))
+        .set_json(&UserDTO {
+            id: Some(1),
+            username: None,
+            password: None,
+            is_admin: None,
+            privacy_officer: None,
+            version: None,
+            employee_id: Some(Some(manager)),
+        })
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert!(resp.status().is_success());
+
+    let absences_url = format!("/employees/{}/absences", developer);
+    let req = test::TestRequest::post()
+        .uri(&absences_url)
+        .cookie(user_session.clone())
+        .set_json(&AbsenceDTO {
+            status: AbsenceStatus::Approved,
+            ..vacation(
+                NaiveDate::from_ymd_opt(2021, 3, 1).unwrap(),
+                NaiveDate::from_ymd_opt(2021, 3, 7).unwrap(),
+            )
+        })
+        .to_request();
+    let requested: AbsenceDTO = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(requested.status, AbsenceStatus::Requested);
+    assert_eq!(requested.days, 5);
+    let absence_url = format!("{}/{}", absences_url, requested.id.unwrap());
+
+    // Overlapping absence is rejected
+    let req = test::TestRequest::post()
+        .uri(&absences_url)
+        .cookie(user_session.clone())
+        .set_json(vacation(
+            NaiveDate::from_ymd_opt(2021, 3, 5).unwrap(),
+            NaiveDate::from_ymd_opt(2021, 3, 10).unwrap(),
+        ))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(StatusCode::UNPROCESSABLE_ENTITY, resp.status());
+
+    // Manager can't approve its own absence
+    let req = test::TestRequest::post()
+        .uri(&format!("/employees/{}/absences", manager))
+        .cookie(user_session.clone())
+        .set_json(vacation(
+            NaiveDate::from_ymd_opt(2021, 3, 1).unwrap(),
+            NaiveDate::from_ymd_opt(2021, 3, 2).unwrap(),
+        ))
+        .to_request();
+    let own: AbsenceDTO = test::call_and_read_body_json(&app, req).await;
+    let req = test::TestRequest::post()
+        .uri(&format!("/employees/{}/absences/{}/approve", manager, own.id.unwrap()))
+        .cookie(user_session.clone())
+        .insert_header((IF_MATCH, "\"1\""))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(StatusCode::FORBIDDEN, resp.status());
+
+    let req = test::TestRequest::post()
+        .uri(&format!("{}/approve", absence_url))
+        .cookie(user_session.clone())
+        .insert_header((IF_MATCH, "\"1\""))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.headers().get(ETAG).unwrap(), "\"2\"");
+    let approved: AbsenceDTO = test::read_body_json(resp).await;
+    assert_eq!(approved.status, AbsenceStatus::Approved);
+    assert_eq!(approved.decided_by, Some(1));
+
+    let req = test::TestRequest::post()
+        .uri(&format!("{}/reject", absence_url))
+        .cookie(user_session.clone())
+        .insert_header((IF_MATCH, "\"2\""))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(StatusCode::UNPROCESSABLE_ENTITY, resp.status());
+
+    let req = test::TestRequest::delete()
+        .uri(&absence_url)
+        .cookie(user_session.clone())
+        .insert_header((IF_MATCH, "\"2\""))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(StatusCode::CONFLICT, resp.status());
+
+    let req = test::TestRequest::get()
+        .uri(&format!("{}/balance?year=2021", absences_url))
+        .cookie(user_session.clone())
+        .to_request();
+    let balance: Vec<AbsenceBalance> = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(balance[0].used, 5);
+    assert_eq!(balance[0].remaining, Some(21));
+
+    let req = test::TestRequest::get()
+        .uri("/absences/calendar?from=2021-03-01&to=2021-03-31")
+        .cookie(user_session.clone())
+        .to_request();
+    let calendar: Vec<CalendarEntry> = test::call_and_read_body_json(&app, req).await;
+    let entries: Vec<(String, AbsenceStatus)> = calendar
+        .into_iter()
+        .map(|e| (e.last_name, e.status))
+        .collect();
+    assert_eq!(
+        entries,
+        vec![
+            ("Developer".to_string(), AbsenceStatus::Approved),
+            ("Manager".to_string(), AbsenceStatus::Requested)
+        ]
+    );
+
+    // Absence type in use can't be deleted
+    let req = test::TestRequest::get()
+        .uri(&format!("/absence-types/{}", VACATION))
+        .cookie(admin_session.clone())
+        .to_request();
+    let vacation_type: AbsenceTypeDTO = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(vacation_type.yearly_entitlement, Some(26));
+    let req = test::TestRequest::delete()
+        .uri(&format!("/absence-types/{}", VACATION))
+        .cookie(admin_session.clone())
+        .insert_header((IF_MATCH, "\"1\""))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(StatusCode::CONFLICT, resp.status());
+}
@@ -5,10 +5,10 @@ use crate::main_tests::login_as_admin;
 
 #[test]
 async fn check_login_guard() {
-    setup_test!("check_login_guard");
+    let db = setup_test!("check_login_guard");
 
-    let mut app = test::init_service(App::new().configure(|cfg| rest::config_all(cfg))).await;
-    let session = login_as_admin(&mut app).await;
+    let app = test::init_service(App::new().configure(rest::config_with_db(db.clone()))).await;
+    let session = login_as_admin(&app).await;
 
     assert!(session.is_some());
     if let Some(session) = session {
@@ -17,7 +17,7 @@ async fn check_login_guard() {
             .uri("/users/template")
             .method(Method::GET)
             .to_request();
-        let resp = test::call_service(&mut app, req).await;
+        let resp = test::call_service(&app, req).await;
         info!("Hello");
         info!("GET /users/template => {:#?}", resp.status());
     }
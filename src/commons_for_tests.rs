@@ -1,52 +1,118 @@
-use std::env;
-use std::sync::Mutex;
+use std::ops::Deref;
+use std::sync::atomic::{AtomicUsize, Ordering};
 
-use diesel_migrations;
-use diesel_migrations::MigrationHarness;
-use dao::{get_connection, initialize_db, MIGRATIONS};
-
-lazy_static! {
-    pub static ref MUTEX: Mutex<i32> = Mutex::new(0i32);
-}
+use dao::{DbConfig, Database, EncryptionKey, StorageConfig};
 
+/// Every test get its own Database so tests don't need to be serialized
 #[macro_export]
 macro_rules! setup_test {
     ($test_name:expr) => {
-        #[allow(unused_variables)]
-        let lock = commons_for_tests::MUTEX.lock();
+        setup_test!($test_name, |config| config)
+    };
+    ($test_name:expr, $configure:expr) => {{
         commons_for_tests::initialize_log();
         info!("Start {}() test", $test_name);
-        commons_for_tests::setup_db();
-        defer! {
-            commons_for_tests::tear_down_db();
-            info!("End {}() test", $test_name);
-        }
-    };
+        commons_for_tests::setup_db($test_name, $configure)
+    }};
 }
 
 pub fn initialize_log() {
     let _ = log4rs::init_file("log4rs.yml", Default::default());
 }
 
-pub fn setup_db() {
-    info!("Initialize DB (if not exist), run migrations");
-    unsafe {
-        env::set_var("DATABASE_URL", ":memory:");
-        env::set_var("POOL_SIZE", "1");
+/// Every test use the same encryption key. Documents are stored in its own temporary
+/// directory removed at the end of test.
+fn test_config(database_url: &str) -> DbConfig {
+    static DOCUMENTS_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+    let documents_dir = std::env::temp_dir().join(format!(
+        "rust_backend_documents_{}_{}",
+        std::process::id(),
+        DOCUMENTS_COUNTER.fetch_add(1, Ordering::SeqCst)
+    ));
+    DbConfig::new(database_url)
+        .with_encryption_key(EncryptionKey::new([7; 32]))
+        .with_documents_dir(documents_dir)
+}
+
+/// Database used by single test - it is cleaned up when dropped (at the end of test)
+pub struct TestDb {
+    db: Database,
+    test_name: &'static str,
+    tear_down: Option<Box<dyn FnOnce()>>,
+}
+
+impl Deref for TestDb {
+    type Target = Database;
+
+    fn deref(&self) -> &Database {
+        &self.db
     }
-    initialize_db();
 }
 
-pub fn tear_down_db() {
-    let conn = &mut get_connection();
-    match conn.revert_all_migrations(MIGRATIONS) {
-        Ok(migrations) => {
-            for migration in migrations {
-                info!("Reverted {}", migration);
-            }
+impl Drop for TestDb {
+    fn drop(&mut self) {
+        if let Some(tear_down) = self.tear_down.take() {
+            tear_down();
         }
-        Err(e) => {
-            info!("Error reverting migrations: {}", e);
+        if let StorageConfig::Local(dir) = &self.db.config().documents.storage {
+            let _ = std::fs::remove_dir_all(dir);
         }
+        info!("End {}() test", self.test_name);
+    }
+}
+
+/// Fresh in-memory database - it is gone when last connection is closed
+#[cfg(feature = "sqlite")]
+pub fn setup_db<F>(test_name: &'static str, configure: F) -> TestDb
+where
+    F: FnOnce(DbConfig) -> DbConfig,
+{
+    info!("Initialize DB (if not exist), run migrations");
+    let db = Database::new(configure(test_config(":memory:"))).unwrap();
+    db.initialize();
+    TestDb {
+        db,
+        test_name,
+        tear_down: None,
+    }
+}
+
+/// PostgreSQL tests run only when TEST_DATABASE_URL point to (disposable) database.
+/// Every test create its own database next to it and drop it at the end.
+#[cfg(feature = "postgres")]
+pub fn setup_db<F>(test_name: &'static str, configure: F) -> TestDb
+where
+    F: FnOnce(DbConfig) -> DbConfig,
+{
+    use diesel::{Connection, PgConnection, RunQueryDsl};
+
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+    let test_db_url = std::env::var("TEST_DATABASE_URL")
+        .expect("TEST_DATABASE_URL have to point to PostgreSQL database used by tests");
+    let db_name = format!(
+        "rust_backend_test_{}_{}",
+        std::process::id(),
+        COUNTER.fetch_add(1, Ordering::SeqCst)
+    );
+    let (server_url, _) = test_db_url
+        .rsplit_once('/')
+        .expect("TEST_DATABASE_URL should be in format postgres://user@host/database");
+    let mut admin_conn = PgConnection::establish(&test_db_url)
+        .unwrap_or_else(|_| panic!("Error connecting to {}", test_db_url));
+    diesel::sql_query(format!("CREATE DATABASE {}", db_name))
+        .execute(&mut admin_conn)
+        .expect("Fail to create test database");
+    info!("Initialize DB {}, run migrations", db_name);
+    let db = Database::new(configure(test_config(&format!("{}/{}", server_url, db_name)))).unwrap();
+    db.initialize();
+    TestDb {
+        db,
+        test_name,
+        tear_down: Some(Box::new(move || {
+            let _ = diesel::sql_query(format!("DROP DATABASE IF EXISTS {} WITH (FORCE)", db_name))
+                .execute(&mut admin_conn);
+        })),
     }
 }
@@ -0,0 +1,127 @@
+use std::collections::HashMap;
+
+use actix_web::http::header::{ETAG, IF_MATCH};
+use actix_web::http::StatusCode;
+use actix_web::{test, App};
+use chrono::NaiveDate;
+use dao::{ContractDTO, ContractType, EmployeeDTO, FieldError, PositionDTO, SalaryReport, WorkingTime};
+
+use crate::commons_for_tests;
+use crate::employee_tests::new_employee;
+use crate::main_tests::{login_as_admin, login_as_user};
+
+#[actix_rt::test]
+async fn employee_with_contracts() {
+    let db = setup_test!("employee_with_contracts");
+
+    let app = test::init_service(App::new().configure(rest::config_with_db(db.clone()))).await;
+    let session = login_as_admin(&app).await.unwrap();
+    let user_session = login_as_user(&app).await.unwrap();
+
+    let developer = PositionDTO {
+        id: None,
+        name: "Developer".to_string(),
+        search_string: "".to_string(),
+        version: None,
+    };
+    let req = test::TestRequest::post()
+        .uri("/positions")
+        .cookie(session.clone())
+        .set_json(&developer)
+        .to_request();
+    let developer: PositionDTO = test::call_and_read_body_json(&app, req).await;
+
+    // Salary of new employee is linked to contract covering it
+    let mut employee = new_employee();
+    let fixed_term = ContractDTO {
+        id: None,
+        employee_id: None,
+        position_id: developer.id,
+        contract_type: ContractType::FixedTerm,
+        from_date: NaiveDate::from_ymd_opt(2020, 1, 1).unwrap(),
+        to_date: Some(NaiveDate::from_ymd_opt(2020, 12, 31).unwrap()),
+        working_time: WorkingTime::new(1, 2),
+        search_string: "".to_string(),
+        version: None,
+    };
+    employee.contracts.push(fixed_term.clone());
+    let req = test::TestRequest::post()
+        .uri("/employees")
+        .cookie(session.clone())
+        .set_json(&employee)
+        .to_request();
+    let created: EmployeeDTO = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(created.salaries[0].contract_id, created.contracts[0].id);
+    let contracts_url = format!("/employees/{}/contracts", created.id.unwrap());
+
+    let req = test::TestRequest::get()
+        .uri(&contracts_url)
+        .cookie(user_session.clone())
+        .to_request();
+    let contracts: Vec<ContractDTO> = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(contracts, created.contracts);
+
+    // Contracts of employee can't overlap
+    let req = test::TestRequest::post()
+        .uri(&contracts_url)
+        .cookie(session.clone())
+        .set_json(&fixed_term)
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(StatusCode::UNPROCESSABLE_ENTITY, resp.status());
+    let body: HashMap<String, Vec<FieldError>> = test::read_body_json(resp).await;
+    assert_eq!(body["errors"][0].field, "from_date");
+
+    let permanent = ContractDTO {
+        contract_type: ContractType::Permanent,
+        from_date: NaiveDate::from_ymd_opt(2021, 1, 1).unwrap(),
+        to_date: None,
+        working_time: WorkingTime::FULL,
+        ..fixed_term
+    };
+    let req = test::TestRequest::post()
+        .uri(&contracts_url)
+        .cookie(session.clone())
+        .set_json(&permanent)
+        .to_request();
+    let permanent: ContractDTO = test::call_and_read_body_json(&app, req).await;
+    let contract_url = format!("{}/{}", contracts_url, permanent.id.unwrap());
+
+    let req = test::TestRequest::get()
+        .uri(&contract_url)
+        .cookie(session.clone())
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.headers().get(ETAG).unwrap(), "\"1\"");
+
+    let req = test::TestRequest::get()
+        .uri("/reports/salaries?from=2020-01-01&to=2020-01-31&group_by=position")
+        .cookie(session.clone())
+        .to_request();
+    let report: SalaryReport = test::call_and_read_body_json(&app, req).await;
+    let group = report.statistics[0].group.clone().unwrap();
+    assert_eq!(group.id, developer.id);
+    assert_eq!(group.name, Some("Developer".to_string()));
+
+    let req = test::TestRequest::get()
+        .uri("/reports/salaries?group_by=team")
+        .cookie(session.clone())
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(StatusCode::BAD_REQUEST, resp.status());
+
+    let req = test::TestRequest::delete()
+        .uri(&contract_url)
+        .cookie(session.clone())
+        .insert_header((IF_MATCH, "\"1\""))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert!(resp.status().is_success());
+
+    let req = test::TestRequest::get()
+        .uri(&contract_url)
+        .cookie(session.clone())
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(StatusCode::NOT_FOUND, resp.status());
+}
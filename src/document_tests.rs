@@ -0,0 +1,239 @@
+use actix_web::http::header::{CONTENT_DISPOSITION, CONTENT_TYPE};
+use actix_web::http::StatusCode;
+use actix_web::{test, App};
+use dao::{Confidentiality, DbConfig, DocumentDTO, DocumentType, EmployeeDTO, StorageConfig};
+use rest::UserDTO;
+
+use crate::commons_for_tests;
+use crate::employee_tests::new_employee;
+use crate::main_tests::{login, login_as_admin, login_as_user};
+
+const PDF: &[u8] = b"%PDF-1.4\n%%EOF\n";
+const PDF_SHA256: &str = "14bcd090baf31edba64e9cbd8cdfc15f943344aa72cb3675ad8e91bfcbce03ad";
+const OTHER_SHA256: &str = "96c7d7022704062bb8be679e2bab72df2536293a9b6162a35c40e61beb276e39";
+
+/// `multipart/form-data` body with metadata fields and `file` part
+fn multipart(fields: &[(&str, &str)], file_name: &str, content_type: &str, content: &[u8]) -> Vec<u8> {
+    let mut body = Vec::new();
+    for (name, value) in fields {
+        body.extend_from_slice(
+            format!("--boundary\r\nContent-Disposition: form-data; name=\"{}\"\r\n\r\n{}\r\n", name, value).as_bytes(),
+        );
+    }
+    body.extend_from_slice(
+        format!(
+            "--boundary\r\nContent-Disposition: form-data; name=\"file\"; filename=\"{}\"\r\nContent-Type: {}\r\n\r\n",
+            file_name, content_type
+        )
+        .as_bytes(),
+    );
+    body.extend_from_slice(content);
+    body.extend_from_slice(b"\r\n--boundary--\r\n");
+    body
+}
+
+#[actix_rt::test]
+async fn documents_are_uploaded_and_downloaded() {
+    let db = setup_test!("documents_are_uploaded_and_downloaded");
+
+    let app = test::init_service(App::new().configure(rest::config_with_db(db.clone()))).await;
+    let session = login_as_admin(&app).await.unwrap();
+    let user_session = login_as_user(&app).await.unwrap();
+
+    let req = test::TestRequest::post()
+        .uri("/employees")
+        .cookie(session.clone())
+        .set_json(new_employee())
+        .to_request();
+    let employee: EmployeeDTO = test::call_and_read_body_json(&app, req).await;
+    let e_id = employee.id.unwrap();
+    let upload = |confidentiality: &str, content_type: &str, content: &[u8]| {
+        test::TestRequest::post()
+            .uri(&format!("/employees/{}/documents", e_id))
+            .cookie(session.clone())
+            .insert_header((CONTENT_TYPE, "multipart/form-data; boundary=boundary"))
+            .set_payload(multipart(
+                &[("document_type", "contract"), ("confidentiality", confidentiality), ("valid_from", "2022-01-01")],
+                "contract.pdf",
+                content_type,
+                content,
+            ))
+            .to_request()
+    };
+    let resp = test::call_service(&app, upload("internal", "application/pdf", PDF)).await;
+    assert_eq!(StatusCode::CREATED, resp.status());
+    let internal: DocumentDTO = test::read_body_json(resp).await;
+    assert_eq!(internal.document_type, DocumentType::Contract);
+    assert_eq!(internal.file_name, "contract.pdf");
+    assert_eq!(internal.size, PDF.len() as i64);
+    let resp = test::call_service(&app, upload("restricted", "application/pdf", PDF)).await;
+    let restricted: DocumentDTO = test::read_body_json(resp).await;
+    assert_eq!(restricted.confidentiality, Confidentiality::Restricted);
+    let resp = test::call_service(&app, upload("internal", "text/plain", b"plain text")).await;
+    assert_eq!(StatusCode::UNPROCESSABLE_ENTITY, resp.status());
+    let resp = test::call_service(&app, upload("internal", "image/png", PDF)).await;
+    assert_eq!(StatusCode::UNPROCESSABLE_ENTITY, resp.status(), "Content should match its type");
+
+    let list = |session| {
+        test::TestRequest::get()
+            .uri(&format!("/employees/{}/documents", e_id))
+            .cookie(session)
+            .to_request()
+    };
+    let documents: Vec<DocumentDTO> = test::call_and_read_body_json(&app, list(session.clone())).await;
+    assert_eq!(documents.len(), 2);
+    let documents: Vec<DocumentDTO> = test::call_and_read_body_json(&app, list(user_session.clone())).await;
+    assert_eq!(documents, vec![internal.clone()], "Restricted document is just for admins");
+
+    let content = |d_id: i32, session| {
+        test::TestRequest::get()
+            .uri(&format!("/employees/{}/documents/{}/content", e_id, d_id))
+            .cookie(session)
+            .to_request()
+    };
+    let resp = test::call_service(&app, content(internal.id, user_session.clone())).await;
+    assert_eq!(StatusCode::OK, resp.status());
+    assert_eq!(resp.headers().get(CONTENT_TYPE).unwrap(), "application/pdf");
+    assert_eq!(
+        resp.headers().get(CONTENT_DISPOSITION).unwrap(),
+        "attachment; filename=\"contract.pdf\""
+    );
+    assert_eq!(test::read_body(resp).await, PDF);
+    let resp = test::call_service(&app, content(restricted.id, user_session.clone())).await;
+    assert_eq!(StatusCode::NOT_FOUND, resp.status());
+    let resp = test::call_service(&app, content(restricted.id, session.clone())).await;
+    assert_eq!(StatusCode::OK, resp.status());
+
+    let req = test::TestRequest::delete()
+        .uri(&format!("/employees/{}/documents/{}", e_id, internal.id))
+        .cookie(session.clone())
+        .to_request();
+    assert_eq!(StatusCode::OK, test::call_service(&app, req).await.status());
+    let resp = test::call_service(&app, content(internal.id, session.clone())).await;
+    assert_eq!(StatusCode::NOT_FOUND, resp.status());
+}
+
+#[actix_rt::test]
+async fn too_big_document_is_rejected() {
+    let db = setup_test!("too_big_document_is_rejected", |mut config: DbConfig| {
+        config.documents.max_size = 8;
+        config
+    });
+
+    let app = test::init_service(App::new().configure(rest::config_with_db(db.clone()))).await;
+    let session = login_as_admin(&app).await.unwrap();
+    let req = test::TestRequest::post()
+        .uri("/employees")
+        .cookie(session.clone())
+        .set_json(new_employee())
+        .to_request();
+    let employee: EmployeeDTO = test::call_and_read_body_json(&app, req).await;
+    let req = test::TestRequest::post()
+        .uri(&format!("/employees/{}/documents", employee.id.unwrap()))
+        .cookie(session.clone())
+        .insert_header((CONTENT_TYPE, "multipart/form-data; boundary=boundary"))
+        .set_payload(multipart(&[("document_type", "contract")], "contract.pdf", "application/pdf", PDF))
+        .to_request();
+    assert_eq!(StatusCode::PAYLOAD_TOO_LARGE, test::call_service(&app, req).await.status());
+}
+
+#[actix_rt::test]
+async fn checksum_and_confidentiality_of_documents_are_checked() {
+    let db = setup_test!("checksum_and_confidentiality_of_documents_are_checked");
+
+    let app = test::init_service(App::new().configure(rest::config_with_db(db.clone()))).await;
+    let session = login_as_admin(&app).await.unwrap();
+
+    let mut ids = vec![];
+    for (last_name, manager) in [("Manager", None), ("Kowalski", Some(0)), ("Other", None)] {
+        let req = test::TestRequest::post()
+            .uri("/employees")
+            .cookie(session.clone())
+            .set_json(EmployeeDTO {
+                last_name: last_name.to_string(),
+                manager_id: manager.map(|m: usize| ids[m]),
+                ..new_employee()
+            })
+            .to_request();
+        let created: EmployeeDTO = test::call_and_read_body_json(&app, req).await;
+        ids.push(created.id.unwrap());
+    }
+    let (manager, e_id, other) = (ids[0], ids[1], ids[2]);
+    let mut sessions = vec![session.clone()];
+    for (username, linked) in [("manager", manager), ("itself", e_id), ("other", other)] {
+        let req = test::TestRequest::post()
+            .uri("/users")
+            .cookie(session.clone())
+            .set_json(&UserDTO {
+                id: None,
+                username: Some(username.to_string()),
+                password: Some(username.to_string()),
+                is_admin: Some(false),
+                privacy_officer: None,
+                version: None,
+                employee_id: Some(Some(linked)),
+            })
+            .to_request();
+        assert!(test::call_service(&app, req).await.status().is_success());
+        sessions.push(login(username, username, &app).await.unwrap());
+    }
+    let upload = |confidentiality: &str, sha256: &str| {
+        test::TestRequest::post()
+            .uri(&format!("/employees/{}/documents", e_id))
+            .cookie(session.clone())
+            .insert_header((CONTENT_TYPE, "multipart/form-data; boundary=boundary"))
+            .set_payload(multipart(
+                &[("document_type", "contract"), ("confidentiality", confidentiality), ("sha256", sha256)],
+                "contract.pdf",
+                "application/pdf",
+                PDF,
+            ))
+            .to_request()
+    };
+
+    // SHA-256 of other content
+    let resp = test::call_service(&app, upload("internal", OTHER_SHA256)).await;
+    assert_eq!(StatusCode::UNPROCESSABLE_ENTITY, resp.status());
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["errors"][0]["field"], "sha256");
+
+    // Admin, manager, employee itself and employee out of scope
+    for (confidentiality, readers) in [
+        ("internal", [true, true, true, false]),
+        ("confidential", [true, false, true, false]),
+        ("restricted", [true, false, false, false]),
+    ] {
+        let resp = test::call_service(&app, upload(confidentiality, &PDF_SHA256.to_uppercase())).await;
+        assert_eq!(StatusCode::CREATED, resp.status());
+        let document: DocumentDTO = test::read_body_json(resp).await;
+        for (session, readable) in sessions.iter().zip(readers) {
+            let req = test::TestRequest::get()
+                .uri(&format!("/employees/{}/documents/{}/content", e_id, document.id))
+                .cookie(session.clone())
+                .to_request();
+            let status = test::call_service(&app, req).await.status();
+            let expected = if readable { StatusCode::OK } else { StatusCode::NOT_FOUND };
+            assert_eq!(expected, status, "{} document read by {}", confidentiality, session.value());
+        }
+    }
+    let req = test::TestRequest::get()
+        .uri(&format!("/employees/{}/documents", e_id))
+        .cookie(sessions[1].clone())
+        .to_request();
+    let documents: Vec<DocumentDTO> = test::call_and_read_body_json(&app, req).await;
+    let levels: Vec<Confidentiality> = documents.iter().map(|d| d.confidentiality).collect();
+    assert_eq!(levels, vec![Confidentiality::Internal], "Manager should see just internal document");
+
+    // Content changed in storage doesn't match its checksum anymore
+    let StorageConfig::Local(dir) = &db.config().documents.storage else {
+        panic!("Tests store documents in local directory");
+    };
+    for file in std::fs::read_dir(dir.join(e_id.to_string())).unwrap() {
+        std::fs::write(file.unwrap().path(), b"%PDF-1.4 changed\n").unwrap();
+    }
+    let req = test::TestRequest::get()
+        .uri(&format!("/employees/{}/documents/{}/content", e_id, documents[0].id))
+        .cookie(session.clone())
+        .to_request();
+    assert_eq!(StatusCode::INTERNAL_SERVER_ERROR, test::call_service(&app, req).await.status());
+}
@@ -0,0 +1,785 @@
+use std::collections::HashMap;
+
+use actix_web::http::header::{ETAG, IF_MATCH};
+use actix_web::http::StatusCode;
+use actix_web::{test, App};
+use chrono::NaiveDate;
+use dao::{
+    AddressDTO, AddressKind, ContactDTO, Country, Currency, CustomFieldDTO, CustomFieldRules, CustomFieldType, EmailDTO,
+    EmailKind, EmergencyContactDTO, EmployeeDTO, EmployeeGroupDTO, EmployeeSearch, EmploymentStatus, FieldError, Money,
+    PayPeriod, PersonalDataExport, PhoneDTO, PhoneKind, SalaryDTO, TagsChange, ANONYMIZED_NAME,
+};
+use rest::UserDTO;
+use serde_json::json;
+
+use crate::commons_for_tests;
+use crate::main_tests::{login, login_as_admin, login_as_user};
+
+pub fn new_employee() -> EmployeeDTO {
+    EmployeeDTO {
+        id: None,
+        first_name: "Jan".to_string(),
+        last_name: "Kowalski".to_string(),
+        search_string: "JanKowalski".to_string(),
+        salaries: vec![SalaryDTO {
+            id: None,
+            employee_id: None,
+            from_date: NaiveDate::from_ymd_opt(2020, 1, 1).unwrap(),
+            to_date: Some(NaiveDate::from_ymd_opt(2020, 12, 31).unwrap()),
+            amount: Money::new(100000, Currency::PLN),
+            pay_period: PayPeriod::Monthly,
+            gross: true,
+            search_string: "".to_string(),
+            version: None,
+            contract_id: None,
+        }],
+        contacts: vec![ContactDTO {
+            id: None,
+            employee_id: None,
+            from_date: NaiveDate::from_ymd_opt(2020, 1, 1).unwrap(),
+            to_date: Some(NaiveDate::from_ymd_opt(2020, 12, 31).unwrap()),
+            emails: vec![EmailDTO {
+                kind: EmailKind::Work,
+                email: "jan.kowalski@Example.COM".to_string(),
+            }],
+            phones: vec![PhoneDTO {
+                kind: PhoneKind::Mobile,
+                number: "601 234 567".to_string(),
+            }],
+            addresses: vec![AddressDTO {
+                kind: AddressKind::Home,
+                street: "ul. Marszałkowska 1".to_string(),
+                city: "Warszawa".to_string(),
+                postal_code: "00-950".to_string(),
+                country: Country::PL,
+            }],
+            search_string: "".to_string(),
+            version: None,
+        }],
+        version: None,
+        department_id: None,
+        manager_id: None,
+        contracts: vec![],
+        employee_number: None,
+        date_of_birth: Some(NaiveDate::from_ymd_opt(1985, 1, 1).unwrap()),
+        national_id: Some("85010112345".to_string()),
+        hire_date: Some(NaiveDate::from_ymd_opt(2020, 1, 1).unwrap()),
+        termination_date: None,
+        status: EmploymentStatus::Active,
+        emergency_contacts: vec![EmergencyContactDTO {
+            name: "Anna Kowalska".to_string(),
+            relationship: "wife".to_string(),
+            phone: "602 345 678".to_string(),
+            email: None,
+        }],
+        deleted_at: None,
+        deleted_by: None,
+        anonymized_at: None,
+        custom_fields: Default::default(),
+        tags: vec![],
+        photo: None,
+    }
+}
+
+#[actix_rt::test]
+async fn create_and_get_employee() {
+    let db = setup_test!("create_and_get_employee");
+
+    let app = test::init_service(App::new().configure(rest::config_with_db(db.clone()))).await;
+    let admin_session = login_as_admin(&app).await.unwrap();
+    let user_session = login_as_user(&app).await.unwrap();
+
+    let req = test::TestRequest::post()
+        .uri("/employees")
+        .cookie(admin_session.clone())
+        .set_json(new_employee())
+        .to_request();
+    let created: EmployeeDTO = test::call_and_read_body_json(&app, req).await;
+    assert!(created.id.is_some());
+    assert_eq!(created.salaries.len(), 1);
+    assert_eq!(created.contacts.len(), 1);
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/employees/{}", created.id.unwrap()))
+        .cookie(user_session.clone())
+        .to_request();
+    let employee: EmployeeDTO = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(employee.id, created.id);
+    assert_eq!(employee.first_name, "Jan");
+    assert!(employee.salaries.is_empty(), "Salaries are shown just to admins");
+    assert_eq!(employee.contacts[0].phones[0].number, "+48601234567");
+    assert_eq!(employee.contacts[0].emails[0].email, "jan.kowalski@example.com");
+
+    let req = test::TestRequest::get()
+        .uri("/employees")
+        .cookie(user_session.clone())
+        .to_request();
+    let employees: Vec<EmployeeDTO> = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(employees.len(), 1);
+    assert!(employees[0].salaries.is_empty(), "Salaries are listed just to admins");
+    assert_eq!(employees[0].contacts, employee.contacts);
+    let req = test::TestRequest::get()
+        .uri("/employees")
+        .cookie(admin_session.clone())
+        .to_request();
+    let employees: Vec<EmployeeDTO> = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(employees[0].salaries[0].amount.amount(), "1000.00");
+}
+
+#[actix_rt::test]
+async fn employee_profile_is_searchable() {
+    let db = setup_test!("employee_profile_is_searchable");
+
+    let app = test::init_service(App::new().configure(rest::config_with_db(db.clone()))).await;
+    let session = login_as_admin(&app).await.unwrap();
+
+    let req = test::TestRequest::post()
+        .uri("/employees")
+        .cookie(session.clone())
+        .set_json(new_employee())
+        .to_request();
+    let created: EmployeeDTO = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(created.employee_number, Some("E00001".to_string()));
+    assert_eq!(created.national_id, Some("85010112345".to_string()));
+    assert_eq!(created.emergency_contacts[0].phone, "+48602345678");
+
+    let search = |query: &str| {
+        test::TestRequest::get()
+            .uri(&format!("/employees?{}", query))
+            .cookie(session.clone())
+            .to_request()
+    };
+    for (query, found) in [
+        ("national_id=850101-12345", 1),
+        ("national_id=85010112346", 0),
+        ("q=E0000", 1),
+        ("status=active&hired_from=2020-01-01", 1),
+        ("status=on_leave", 0),
+        ("born_on=1985-01-02", 0),
+    ] {
+        let employees: Vec<EmployeeDTO> = test::call_and_read_body_json(&app, search(query)).await;
+        assert_eq!(employees.len(), found, "{}", query);
+    }
+
+    // Terminated is final
+    let terminated = EmployeeDTO {
+        status: EmploymentStatus::Terminated,
+        termination_date: Some(NaiveDate::from_ymd_opt(2020, 12, 31).unwrap()),
+        ..created
+    };
+    let req = test::TestRequest::put()
+        .uri("/employees")
+        .cookie(session.clone())
+        .insert_header((IF_MATCH, "\"1\""))
+        .set_json(&terminated)
+        .to_request();
+    let terminated: EmployeeDTO = test::call_and_read_body_json(&app, req).await;
+    let req = test::TestRequest::put()
+        .uri("/employees")
+        .cookie(session.clone())
+        .insert_header((IF_MATCH, "\"2\""))
+        .set_json(EmployeeDTO {
+            status: EmploymentStatus::Active,
+            termination_date: None,
+            ..terminated
+        })
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(StatusCode::UNPROCESSABLE_ENTITY, resp.status());
+    let body: HashMap<String, Vec<FieldError>> = test::read_body_json(resp).await;
+    assert_eq!(body["errors"][0].field, "status");
+}
+
+#[actix_rt::test]
+async fn custom_fields_are_validated_and_searchable() {
+    let db = setup_test!("custom_fields_are_validated_and_searchable");
+
+    let app = test::init_service(App::new().configure(rest::config_with_db(db.clone()))).await;
+    let session = login_as_admin(&app).await.unwrap();
+
+    let req = test::TestRequest::post()
+        .uri("/custom-fields")
+        .cookie(session.clone())
+        .set_json(CustomFieldDTO {
+            id: None,
+            name: "t_shirt_size".to_string(),
+            field_type: CustomFieldType::Enum,
+            required: true,
+            rules: CustomFieldRules {
+                options: vec!["S".to_string(), "M".to_string()],
+                ..Default::default()
+            },
+            search_string: "".to_string(),
+            version: None,
+        })
+        .to_request();
+    let field: CustomFieldDTO = test::call_and_read_body_json(&app, req).await;
+    assert!(field.id.is_some());
+
+    let req = test::TestRequest::get()
+        .uri("/employees/template")
+        .cookie(session.clone())
+        .to_request();
+    let template: EmployeeDTO = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(template.custom_fields["t_shirt_size"], json!("S"));
+
+    let req = test::TestRequest::post()
+        .uri("/employees")
+        .cookie(session.clone())
+        .set_json(new_employee())
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(StatusCode::UNPROCESSABLE_ENTITY, resp.status());
+    let body: HashMap<String, Vec<FieldError>> = test::read_body_json(resp).await;
+    assert_eq!(body["errors"][0].field, "custom_fields.t_shirt_size");
+
+    let mut employee = new_employee();
+    employee.custom_fields.insert("t_shirt_size".to_string(), json!("M"));
+    let req = test::TestRequest::post()
+        .uri("/employees")
+        .cookie(session.clone())
+        .set_json(employee)
+        .to_request();
+    let created: EmployeeDTO = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(created.custom_fields["t_shirt_size"], json!("M"));
+
+    for (query, found) in [("custom_field=t_shirt_size:M", 1), ("custom_field=t_shirt_size:S", 0)] {
+        let req = test::TestRequest::get()
+            .uri(&format!("/employees?{}", query))
+            .cookie(session.clone())
+            .to_request();
+        let employees: Vec<EmployeeDTO> = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(employees.len(), found, "{}", query);
+    }
+    let req = test::TestRequest::get()
+        .uri("/employees?custom_field=shoe_size:42")
+        .cookie(session.clone())
+        .to_request();
+    assert_eq!(StatusCode::UNPROCESSABLE_ENTITY, test::call_service(&app, req).await.status());
+}
+
+#[actix_rt::test]
+async fn employees_are_tagged_and_grouped() {
+    let db = setup_test!("employees_are_tagged_and_grouped");
+
+    let app = test::init_service(App::new().configure(rest::config_with_db(db.clone()))).await;
+    let session = login_as_admin(&app).await.unwrap();
+
+    let mut ids = vec![];
+    for first_name in ["Jan", "Anna"] {
+        let req = test::TestRequest::post()
+            .uri("/employees")
+            .cookie(session.clone())
+            .set_json(EmployeeDTO {
+                first_name: first_name.to_string(),
+                ..new_employee()
+            })
+            .to_request();
+        let created: EmployeeDTO = test::call_and_read_body_json(&app, req).await;
+        ids.push(created.id.unwrap());
+    }
+    let change_tags = |change: TagsChange| {
+        test::TestRequest::post()
+            .uri("/employees/tags")
+            .cookie(session.clone())
+            .set_json(change)
+            .to_request()
+    };
+    let req = change_tags(TagsChange {
+        employee_ids: ids.clone(),
+        add: vec!["remote".to_string(), "contractor".to_string()],
+        remove: vec![],
+    });
+    let changed: HashMap<String, Vec<String>> = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(changed[&ids[0].to_string()], vec!["contractor", "remote"]);
+    let req = change_tags(TagsChange {
+        employee_ids: vec![ids[0]],
+        add: vec![],
+        remove: vec!["contractor".to_string()],
+    });
+    assert_eq!(StatusCode::OK, test::call_service(&app, req).await.status());
+    let req = change_tags(TagsChange {
+        employee_ids: vec![ids[0]],
+        add: vec!["!remote".to_string()],
+        remove: vec![],
+    });
+    assert_eq!(StatusCode::UNPROCESSABLE_ENTITY, test::call_service(&app, req).await.status());
+
+    let req = test::TestRequest::get()
+        .uri("/employees?tag=remote&tag=%21contractor")
+        .cookie(session.clone())
+        .to_request();
+    let employees: Vec<EmployeeDTO> = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(employees.len(), 1);
+    assert_eq!(employees[0].id, Some(ids[0]));
+    assert_eq!(employees[0].tags, vec!["remote"]);
+
+    let req = test::TestRequest::post()
+        .uri("/employee-groups")
+        .cookie(session.clone())
+        .set_json(EmployeeGroupDTO {
+            id: None,
+            name: "Contractors".to_string(),
+            filter: EmployeeSearch {
+                tag: vec!["contractor".to_string()],
+                ..Default::default()
+            },
+            search_string: "".to_string(),
+            version: None,
+        })
+        .to_request();
+    let group: EmployeeGroupDTO = test::call_and_read_body_json(&app, req).await;
+    let members = |group_id: i32| {
+        test::TestRequest::get()
+            .uri(&format!("/employee-groups/{}/employees", group_id))
+            .cookie(session.clone())
+            .to_request()
+    };
+    let found: Vec<EmployeeDTO> = test::call_and_read_body_json(&app, members(group.id.unwrap())).await;
+    assert_eq!(found.len(), 1);
+    assert_eq!(found[0].id, Some(ids[1]));
+    let resp = test::call_service(&app, members(group.id.unwrap() + 1)).await;
+    assert_eq!(StatusCode::NOT_FOUND, resp.status());
+}
+
+#[actix_rt::test]
+async fn delete_employee() {
+    let db = setup_test!("delete_employee");
+
+    let app = test::init_service(App::new().configure(rest::config_with_db(db.clone()))).await;
+    let session = login_as_admin(&app).await.unwrap();
+
+    let req = test::TestRequest::post()
+        .uri("/employees")
+        .cookie(session.clone())
+        .set_json(new_employee())
+        .to_request();
+    let created: EmployeeDTO = test::call_and_read_body_json(&app, req).await;
+    let url = format!("/employees/{}", created.id.unwrap());
+
+    let req = test::TestRequest::delete()
+        .uri(&url)
+        .cookie(session.clone())
+        .insert_header((IF_MATCH, "\"1\""))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert!(resp.status().is_success());
+
+    let req = test::TestRequest::get()
+        .uri(&url)
+        .cookie(session.clone())
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(StatusCode::NOT_FOUND, resp.status());
+
+    // Deleted employee is kept - admin can list and restore it
+    let req = test::TestRequest::get()
+        .uri("/employees?include_deleted=true")
+        .cookie(session.clone())
+        .to_request();
+    let employees: Vec<EmployeeDTO> = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(employees.len(), 1);
+    let deleted = employees[0].clone();
+    assert!(deleted.deleted_at.is_some());
+    assert!(deleted.deleted_by.is_some());
+
+    let restore_url = format!("{}/restore", url);
+    let req = test::TestRequest::post()
+        .uri(&restore_url)
+        .cookie(session.clone())
+        .insert_header((IF_MATCH, "\"1\""))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(StatusCode::PRECONDITION_FAILED, resp.status());
+    let req = test::TestRequest::post()
+        .uri(&restore_url)
+        .cookie(session.clone())
+        .insert_header((IF_MATCH, format!("\"{}\"", deleted.version.unwrap())))
+        .to_request();
+    let restored: EmployeeDTO = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(restored.deleted_at, None);
+    assert_eq!(restored.salaries, created.salaries);
+
+    let req = test::TestRequest::get()
+        .uri(&url)
+        .cookie(session.clone())
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert!(resp.status().is_success());
+
+    let session = login_as_user(&app).await.unwrap();
+    let req = test::TestRequest::get()
+        .uri("/employees?include_deleted=true")
+        .cookie(session.clone())
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(StatusCode::FORBIDDEN, resp.status());
+}
+
+#[actix_rt::test]
+async fn personal_data_is_exported_and_anonymized_by_privacy_officer() {
+    let db = setup_test!("personal_data_is_exported_and_anonymized_by_privacy_officer");
+
+    let app = test::init_service(App::new().configure(rest::config_with_db(db.clone()))).await;
+    let admin_session = login_as_admin(&app).await.unwrap();
+
+    let req = test::TestRequest::post()
+        .uri("/employees")
+        .cookie(admin_session.clone())
+        .set_json(new_employee())
+        .to_request();
+    let created: EmployeeDTO = test::call_and_read_body_json(&app, req).await;
+    let export_url = format!("/employees/{}/personal-data-export", created.id.unwrap());
+    let anonymize_url = format!("/employees/{}/anonymize", created.id.unwrap());
+
+    // Admin alone is not privacy officer
+    let req = test::TestRequest::get()
+        .uri(&export_url)
+        .cookie(admin_session.clone())
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(StatusCode::FORBIDDEN, resp.status());
+
+    let req = test::TestRequest::post()
+        .uri("/users")
+        .cookie(admin_session.clone())
+        .set_json(&UserDTO {
+            id: None,
+            username: Some("officer".to_string()),
+            password: Some("officer".to_string()),
+            is_admin: Some(false),
+            privacy_officer: Some(true),
+            version: None,
+            employee_id: None,
+        })
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert!(resp.status().is_success());
+    let session = login("officer", "officer", &app).await.unwrap();
+
+    let req = test::TestRequest::get()
+        .uri(&export_url)
+        .cookie(session.clone())
+        .to_request();
+    let export: PersonalDataExport = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(export.employee.national_id, created.national_id);
+    assert_eq!(export.employee.contacts, created.contacts);
+    assert_eq!(export.audit_entries.len(), 1);
+
+    let req = test::TestRequest::post()
+        .uri(&anonymize_url)
+        .cookie(session.clone())
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(StatusCode::PRECONDITION_REQUIRED, resp.status());
+    let req = test::TestRequest::post()
+        .uri(&anonymize_url)
+        .cookie(session.clone())
+        .insert_header((IF_MATCH, format!("\"{}\"", created.version.unwrap())))
+        .to_request();
+    let anonymized: EmployeeDTO = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(anonymized.first_name, ANONYMIZED_NAME);
+    assert_eq!(anonymized.national_id, None);
+    assert!(anonymized.contacts[0].emails.is_empty());
+    assert!(anonymized.salaries.is_empty(), "Salaries are shown just to admins");
+    let req = test::TestRequest::get()
+        .uri(&format!("/employees/{}", created.id.unwrap()))
+        .cookie(admin_session.clone())
+        .to_request();
+    let kept: EmployeeDTO = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(kept.salaries, created.salaries, "Salaries should be kept for reports");
+
+    let req = test::TestRequest::post()
+        .uri(&anonymize_url)
+        .cookie(session.clone())
+        .insert_header((IF_MATCH, format!("\"{}\"", anonymized.version.unwrap())))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(StatusCode::UNPROCESSABLE_ENTITY, resp.status());
+
+    let req = test::TestRequest::post()
+        .uri(&anonymize_url)
+        .cookie(session.clone())
+        .insert_header((IF_MATCH, format!("\"{}\"", created.version.unwrap())))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(StatusCode::PRECONDITION_FAILED, resp.status());
+    let current: EmployeeDTO = test::read_body_json(resp).await;
+    assert_eq!(current.version, kept.version);
+    assert!(current.salaries.is_empty(), "Salaries are shown just to admins");
+}
+
+#[actix_rt::test]
+async fn concurrent_update_of_employee_should_be_rejected() {
+    let db = setup_test!("concurrent_update_of_employee_should_be_rejected");
+
+    let app = test::init_service(App::new().configure(rest::config_with_db(db.clone()))).await;
+    let session = login_as_admin(&app).await.unwrap();
+
+    let req = test::TestRequest::post()
+        .uri("/employees")
+        .cookie(session.clone())
+        .set_json(new_employee())
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    let etag = resp.headers().get(ETAG).unwrap().clone();
+    assert_eq!("\"1\"", etag);
+    let created: EmployeeDTO = test::read_body_json(resp).await;
+
+    let mut first = created.clone();
+    first.salaries[0].amount = Money::new(200000, Currency::PLN);
+    let req = test::TestRequest::put()
+        .uri("/employees")
+        .cookie(session.clone())
+        .insert_header((IF_MATCH, etag.clone()))
+        .set_json(&first)
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert!(resp.status().is_success());
+    assert_eq!("\"2\"", resp.headers().get(ETAG).unwrap());
+
+    let mut second = created.clone();
+    second.last_name = "Nowak".to_string();
+    let req = test::TestRequest::put()
+        .uri("/employees")
+        .cookie(session.clone())
+        .insert_header((IF_MATCH, etag.clone()))
+        .set_json(&second)
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(StatusCode::PRECONDITION_FAILED, resp.status());
+    assert_eq!("\"2\"", resp.headers().get(ETAG).unwrap());
+    let current: EmployeeDTO = test::read_body_json(resp).await;
+    assert_eq!(current.last_name, "Kowalski");
+    assert_eq!(current.salaries[0].amount.amount(), "2000.00");
+
+    let url = format!("/employees/{}", created.id.unwrap());
+    let req = test::TestRequest::delete()
+        .uri(&url)
+        .cookie(session.clone())
+        .insert_header((IF_MATCH, etag))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(StatusCode::PRECONDITION_FAILED, resp.status());
+
+    let req = test::TestRequest::delete()
+        .uri(&url)
+        .cookie(session.clone())
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(StatusCode::PRECONDITION_REQUIRED, resp.status());
+}
+
+#[actix_rt::test]
+async fn invalid_periods_should_be_rejected() {
+    let db = setup_test!("invalid_periods_should_be_rejected");
+
+    let app = test::init_service(App::new().configure(rest::config_with_db(db.clone()))).await;
+    let session = login_as_admin(&app).await.unwrap();
+
+    let mut employee = new_employee();
+    employee.salaries[0].to_date = None;
+    employee.salaries.push(SalaryDTO {
+        from_date: NaiveDate::from_ymd_opt(2021, 1, 1).unwrap(),
+        to_date: Some(NaiveDate::from_ymd_opt(2020, 6, 30).unwrap()),
+        ..employee.salaries[0].clone()
+    });
+    let req = test::TestRequest::post()
+        .uri("/employees")
+        .cookie(session.clone())
+        .set_json(&employee)
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(StatusCode::UNPROCESSABLE_ENTITY, resp.status());
+    let body: HashMap<String, Vec<FieldError>> = test::read_body_json(resp).await;
+    let fields: Vec<&str> = body["errors"].iter().map(|e| e.field.as_str()).collect();
+    assert_eq!(fields, vec!["salaries[1].to_date", "salaries[1].from_date"]);
+
+    let req = test::TestRequest::get()
+        .uri("/employees")
+        .cookie(session.clone())
+        .to_request();
+    let employees: Vec<EmployeeDTO> = test::call_and_read_body_json(&app, req).await;
+    assert!(employees.is_empty());
+
+    // Open-ended salary followed by nothing is fine
+    employee.salaries.pop();
+    let req = test::TestRequest::post()
+        .uri("/employees")
+        .cookie(session.clone())
+        .set_json(&employee)
+        .to_request();
+    let created: EmployeeDTO = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(created.salaries[0].to_date, None);
+}
+
+#[actix_rt::test]
+async fn invalid_contact_details_should_be_rejected() {
+    let db = setup_test!("invalid_contact_details_should_be_rejected");
+
+    let app = test::init_service(App::new().configure(rest::config_with_db(db.clone()))).await;
+    let session = login_as_admin(&app).await.unwrap();
+
+    let mut employee = new_employee();
+    let contact = &mut employee.contacts[0];
+    contact.emails[0].email = "jan.kowalski@".to_string();
+    contact.phones[0].number = "12".to_string();
+    contact.addresses[0].postal_code = "00950".to_string();
+    let req = test::TestRequest::post()
+        .uri("/employees")
+        .cookie(session.clone())
+        .set_json(&employee)
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(StatusCode::UNPROCESSABLE_ENTITY, resp.status());
+    let body: HashMap<String, Vec<FieldError>> = test::read_body_json(resp).await;
+    let fields: Vec<&str> = body["errors"].iter().map(|e| e.field.as_str()).collect();
+    assert_eq!(
+        fields,
+        vec![
+            "contacts[0].emails[0].email",
+            "contacts[0].phones[0].number",
+            "contacts[0].addresses[0].postal_code"
+        ]
+    );
+}
+
+#[actix_rt::test]
+async fn get_employee_as_of_date() {
+    let db = setup_test!("get_employee_as_of_date");
+
+    let app = test::init_service(App::new().configure(rest::config_with_db(db.clone()))).await;
+    let admin_session = login_as_admin(&app).await.unwrap();
+    let user_session = login_as_user(&app).await.unwrap();
+
+    let mut employee = new_employee();
+    employee.salaries.push(SalaryDTO {
+        from_date: NaiveDate::from_ymd_opt(2021, 1, 1).unwrap(),
+        to_date: None,
+        amount: Money::new(200000, Currency::PLN),
+        ..employee.salaries[0].clone()
+    });
+    let req = test::TestRequest::post()
+        .uri("/employees")
+        .cookie(admin_session.clone())
+        .set_json(&employee)
+        .to_request();
+    let created: EmployeeDTO = test::call_and_read_body_json(&app, req).await;
+    let url = format!("/employees/{}", created.id.unwrap());
+
+    let req = test::TestRequest::get()
+        .uri(&format!("{}?on=2020-06-30", url))
+        .cookie(admin_session.clone())
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert!(resp.headers().get(ETAG).is_none());
+    let on_2020: EmployeeDTO = test::read_body_json(resp).await;
+    assert_eq!(on_2020.salaries.len(), 1);
+    assert_eq!(on_2020.salaries[0].amount.amount(), "1000.00");
+    assert_eq!(on_2020.contacts[0].phones[0].number, "+48601234567");
+
+    let req = test::TestRequest::get()
+        .uri(&format!("{}?on=2023-06-30", url))
+        .cookie(admin_session.clone())
+        .to_request();
+    let on_2023: EmployeeDTO = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(on_2023.salaries[0].amount.amount(), "2000.00");
+    assert!(on_2023.contacts.is_empty());
+
+    let req = test::TestRequest::get()
+        .uri(&format!("{}?on=yesterday", url))
+        .cookie(user_session.clone())
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(StatusCode::BAD_REQUEST, resp.status());
+
+    let req = test::TestRequest::get()
+        .uri("/employees/without-contact?on=2020-06-30")
+        .cookie(user_session.clone())
+        .to_request();
+    let without_contact: Vec<EmployeeDTO> = test::call_and_read_body_json(&app, req).await;
+    assert!(without_contact.is_empty());
+
+    // new_employee() has contact valid only in 2020 - so today it has none
+    let req = test::TestRequest::get()
+        .uri("/employees/without-contact")
+        .cookie(user_session.clone())
+        .to_request();
+    let without_contact: Vec<EmployeeDTO> = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(without_contact.len(), 1);
+    assert_eq!(without_contact[0].id, created.id);
+}
+
+#[actix_rt::test]
+async fn salaries_are_shown_just_to_admins() {
+    let db = setup_test!("salaries_are_shown_just_to_admins");
+
+    let app = test::init_service(App::new().configure(rest::config_with_db(db.clone()))).await;
+    let admin_session = login_as_admin(&app).await.unwrap();
+    let user_session = login_as_user(&app).await.unwrap();
+
+    let req = test::TestRequest::post()
+        .uri("/employees")
+        .cookie(admin_session.clone())
+        .set_json(new_employee())
+        .to_request();
+    let manager: EmployeeDTO = test::call_and_read_body_json(&app, req).await;
+    let req = test::TestRequest::post()
+        .uri("/employees")
+        .cookie(admin_session.clone())
+        .set_json(EmployeeDTO {
+            first_name: "Anna".to_string(),
+            manager_id: manager.id,
+            ..new_employee()
+        })
+        .to_request();
+    let report: EmployeeDTO = test::call_and_read_body_json(&app, req).await;
+    let req = test::TestRequest::post()
+        .uri("/employee-groups")
+        .cookie(admin_session.clone())
+        .set_json(EmployeeGroupDTO {
+            id: None,
+            name: "Everybody".to_string(),
+            filter: Default::default(),
+            search_string: "".to_string(),
+            version: None,
+        })
+        .to_request();
+    let group: EmployeeGroupDTO = test::call_and_read_body_json(&app, req).await;
+
+    // new_employee() has contact valid only in 2020 - so today both are without contact
+    for uri in [
+        "/employees".to_string(),
+        format!("/employees/{}", report.id.unwrap()),
+        format!("/employees/{}?on=2020-06-30", report.id.unwrap()),
+        format!("/employees/{}/reports", manager.id.unwrap()),
+        "/employees/without-contact".to_string(),
+        format!("/employee-groups/{}/employees", group.id.unwrap()),
+    ] {
+        for (session, admin) in [(&admin_session, true), (&user_session, false)] {
+            let req = test::TestRequest::get()
+                .uri(&uri)
+                .cookie(session.clone())
+                .to_request();
+            let employees = employees_in(test::call_and_read_body_json(&app, req).await);
+            assert!(!employees.is_empty(), "{} should return employees", uri);
+            let salaries: Vec<usize> = employees.iter().map(|e| e.salaries.len()).collect();
+            let expected = if admin { 1 } else { 0 };
+            assert!(
+                salaries.iter().all(|s| *s == expected),
+                "{} shows salaries {:?} to admin: {}",
+                uri,
+                salaries,
+                admin
+            );
+        }
+    }
+}
+
+/// Employees of response with one employee or list of them
+fn employees_in(body: serde_json::Value) -> Vec<EmployeeDTO> {
+    match body {
+        serde_json::Value::Array(_) => serde_json::from_value(body).unwrap(),
+        employee => vec![serde_json::from_value(employee).unwrap()],
+    }
+}
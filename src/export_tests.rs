@@ -0,0 +1,88 @@
+use actix_web::http::header::{CONTENT_DISPOSITION, CONTENT_TYPE};
+use actix_web::http::StatusCode;
+use actix_web::{test, App};
+use dao::EmployeeDTO;
+
+use crate::commons_for_tests;
+use crate::employee_tests::new_employee;
+use crate::main_tests::{login_as_admin, login_as_user};
+
+#[actix_rt::test]
+async fn employees_are_exported() {
+    let db = setup_test!("employees_are_exported");
+
+    let app = test::init_service(App::new().configure(rest::config_with_db(db.clone()))).await;
+    let session = login_as_admin(&app).await.unwrap();
+    let user_session = login_as_user(&app).await.unwrap();
+    for last_name in ["Kowalski", "Nowak"] {
+        let req = test::TestRequest::post()
+            .uri("/employees")
+            .cookie(session.clone())
+            .set_json(EmployeeDTO {
+                last_name: last_name.to_string(),
+                ..new_employee()
+            })
+            .to_request();
+        let _: EmployeeDTO = test::call_and_read_body_json(&app, req).await;
+    }
+    let export = |uri: &str, session| test::TestRequest::get().uri(uri).cookie(session).to_request();
+
+    let resp = test::call_service(&app, export("/export/employees?on=2020-06-01", session.clone())).await;
+    assert_eq!(StatusCode::OK, resp.status());
+    assert_eq!(resp.headers().get(CONTENT_TYPE).unwrap(), "text/csv; charset=utf-8");
+    assert!(resp.headers().get(CONTENT_DISPOSITION).unwrap().to_str().unwrap().contains("employees_2020-06-01.csv"));
+    let csv = String::from_utf8(test::read_body(resp).await.to_vec()).unwrap();
+    let lines: Vec<&str> = csv.lines().collect();
+    assert_eq!(lines.len(), 3);
+    assert!(lines[0].starts_with("id,employee_number,first_name,last_name,date_of_birth"));
+    assert!(lines[0].contains("salary_amount"));
+    assert!(lines[1].contains(",Jan,Kowalski,1985-01-01,2020-01-01,,active,"));
+    assert!(lines[1].contains(",1000.00,PLN,monthly,true,"));
+    assert!(lines[1].ends_with(",jan.kowalski@example.com,'+48601234567,ul. Marszałkowska 1,Warszawa,00-950,PL"));
+
+    let uri = "/export/employees?q=Nowak&columns=last_name,hire_date,salary_amount&date_format=%25d.%25m.%25Y";
+    let resp = test::call_service(&app, export(uri, user_session.clone())).await;
+    assert_eq!(StatusCode::FORBIDDEN, resp.status(), "Salaries are exported just for admins");
+    let uri = "/export/employees?q=Nowak&columns=last_name,hire_date&date_format=%25d.%25m.%25Y";
+    let csv = test::call_and_read_body(&app, export(uri, user_session.clone())).await;
+    assert_eq!(csv, "last_name,hire_date\nNowak,01.01.2020\n");
+    let csv = test::call_and_read_body(&app, export("/export/employees", user_session.clone())).await;
+    let header = String::from_utf8(csv.to_vec()).unwrap().lines().next().unwrap().to_string();
+    assert!(header.contains("email") && !header.contains("salary"), "Salary columns are left out for users");
+
+    let resp = test::call_service(&app, export("/export/salaries", user_session.clone())).await;
+    assert_eq!(StatusCode::FORBIDDEN, resp.status());
+    let uri = "/export/salaries?format=jsonl&columns=last_name,salary_from,salary_amount";
+    let jsonl = test::call_and_read_body(&app, export(uri, session.clone())).await;
+    let rows: Vec<serde_json::Value> = String::from_utf8(jsonl.to_vec())
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(
+        rows[1],
+        serde_json::json!({"last_name": "Nowak", "salary_from": "2020-01-01", "salary_amount": 1000.0})
+    );
+
+    let resp = test::call_service(&app, export("/export/contacts?format=xlsx", user_session.clone())).await;
+    assert_eq!(StatusCode::OK, resp.status());
+    assert_eq!(
+        resp.headers().get(CONTENT_TYPE).unwrap(),
+        "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet"
+    );
+    let xlsx = test::read_body(resp).await;
+    assert!(xlsx.starts_with(b"PK\x03\x04"));
+
+    let req = test::TestRequest::post()
+        .uri("/import/employees?dry_run=true")
+        .cookie(session.clone())
+        .set_payload(xlsx)
+        .to_request();
+    let result: dao::ImportResult = test::call_and_read_body_json(&app, req).await;
+    assert_eq!((result.created, result.updated, result.failed), (0, 2, 0), "Exported file can be imported back");
+
+    for uri in ["/export/employees?format=pdf", "/export/contacts?columns=nick", "/export/employees?date_format=%25Q"] {
+        let resp = test::call_service(&app, export(uri, session.clone())).await;
+        assert_eq!(StatusCode::BAD_REQUEST, resp.status(), "{}", uri);
+    }
+}
@@ -0,0 +1,78 @@
+use actix_web::http::StatusCode;
+use actix_web::{test, App};
+use dao::{Currency, EmployeeDTO, ImportResult, Money};
+
+use crate::commons_for_tests;
+use crate::main_tests::{login_as_admin, login_as_user};
+
+const EMPLOYEES: &str = "\u{FEFF}Nr;First_Name;last_name;Zatrudniony;salary_amount;email;Uwagi
+E-1;Jan;Kowalski;01.02.2021;5000;jan@example.com;
+E-2;Anna;Nowak;01.03.2021;6000.50;anna@example.com;
+;Bez;;2021-03-01;;nie-email;
+";
+
+#[actix_rt::test]
+async fn employees_are_imported_from_csv() {
+    let db = setup_test!("employees_are_imported_from_csv");
+
+    let app = test::init_service(App::new().configure(rest::config_with_db(db.clone()))).await;
+    let session = login_as_admin(&app).await.unwrap();
+    let user_session = login_as_user(&app).await.unwrap();
+    let import = |query: &str, session| {
+        test::TestRequest::post()
+            .uri(&format!(
+                "/import/employees?delimiter=%3B&date_format=%25d.%25m.%25Y&map=employee_number:Nr\
+                 &map=hire_date:Zatrudniony{}",
+                query
+            ))
+            .cookie(session)
+            .set_payload(EMPLOYEES)
+            .to_request()
+    };
+
+    let resp = test::call_service(&app, import("", user_session.clone())).await;
+    assert_eq!(StatusCode::UNAUTHORIZED, resp.status(), "Employees are imported just by admins");
+    let req = test::TestRequest::post()
+        .uri("/import/employees?map=employee_number:Numer")
+        .cookie(session.clone())
+        .set_payload(EMPLOYEES)
+        .to_request();
+    assert_eq!(StatusCode::BAD_REQUEST, test::call_service(&app, req).await.status());
+
+    let result: ImportResult = test::call_and_read_body_json(&app, import("&dry_run=true", session.clone())).await;
+    assert!(result.dry_run);
+    assert_eq!((result.created, result.updated, result.failed), (2, 0, 1));
+    assert_eq!(result.ignored_columns, vec!["Uwagi"]);
+    assert_eq!(result.errors[0].row, 4);
+    let fields: Vec<&str> = result.errors[0].errors.iter().map(|e| e.field.as_str()).collect();
+    assert_eq!(fields, vec!["last_name", "hire_date", "contact_from"]);
+    let req = test::TestRequest::get().uri("/employees").cookie(session.clone()).to_request();
+    let employees: Vec<EmployeeDTO> = test::call_and_read_body_json(&app, req).await;
+    assert!(employees.is_empty(), "Dry run shouldn't save anything");
+
+    let result: ImportResult = test::call_and_read_body_json(&app, import("", session.clone())).await;
+    assert!(!result.dry_run);
+    assert_eq!((result.created, result.updated, result.failed), (2, 0, 1));
+    let req = test::TestRequest::get().uri("/employees").cookie(session.clone()).to_request();
+    let employees: Vec<EmployeeDTO> = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(employees.len(), 2);
+    let anna = employees.iter().find(|e| e.employee_number.as_deref() == Some("E-2")).unwrap();
+    let req = test::TestRequest::get()
+        .uri(&format!("/employees/{}", anna.id.unwrap()))
+        .cookie(session.clone())
+        .to_request();
+    let anna: EmployeeDTO = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(anna.first_name, "Anna");
+    assert_eq!(anna.salaries[0].amount, Money::new(600050, Currency::PLN));
+    assert_eq!(anna.contacts[0].emails[0].email, "anna@example.com");
+
+    let result: ImportResult = test::call_and_read_body_json(&app, import("&atomic=true", session.clone())).await;
+    assert_eq!((result.created, result.updated, result.failed), (0, 0, 1), "Nothing is saved when row fails");
+    let req = test::TestRequest::post()
+        .uri("/import/employees")
+        .cookie(session.clone())
+        .set_payload("employee_number,first_name\nE-1,Janek\n")
+        .to_request();
+    let result: ImportResult = test::call_and_read_body_json(&app, req).await;
+    assert_eq!((result.created, result.updated, result.failed), (0, 1, 0));
+}
@@ -2,17 +2,16 @@ extern crate dao;
 #[cfg(test)]
 extern crate diesel;
 #[cfg(test)]
-extern crate diesel_migrations;
-#[cfg(test)]
 #[macro_use]
 extern crate lazy_static;
 #[macro_use]
 extern crate log;
 extern crate log4rs;
 extern crate rest;
-#[cfg(test)]
-#[macro_use]
-extern crate scopeguard;
+
+use std::time::Duration;
+
+use dao::{DbConfig, Database};
 
 #[cfg(test)]
 mod test_data;
@@ -20,19 +19,88 @@ mod test_data;
 #[macro_use]
 mod commons_for_tests;
 #[cfg(test)]
+mod absence_tests;
+#[cfg(test)]
 mod adhoc_tests;
 #[cfg(test)]
+mod contract_tests;
+#[cfg(test)]
+mod document_tests;
+#[cfg(test)]
 mod employee_tests;
 #[cfg(test)]
+mod export_tests;
+#[cfg(test)]
+mod import_tests;
+#[cfg(test)]
 mod main_tests;
 #[cfg(test)]
+mod org_tests;
+#[cfg(test)]
+mod payroll_tests;
+#[cfg(test)]
+mod photo_tests;
+#[cfg(test)]
+mod report_tests;
+#[cfg(test)]
+mod timesheet_tests;
+#[cfg(test)]
 mod user_tests;
 
+/// How often employees deleted before retention period are purged - the first purge is done on start
+const PURGE_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+
+async fn purge_deleted_periodically(db: Database) {
+    let mut interval = actix_rt::time::interval(PURGE_INTERVAL);
+    // The first tick is immediate - Database::initialize() have just purged
+    interval.tick().await;
+    loop {
+        interval.tick().await;
+        let db = db.clone();
+        match actix_rt::task::spawn_blocking(move || db.purge_deleted()).await {
+            Ok(Ok(_)) => {}
+            Ok(Err(e)) => error!("Purge of deleted employees failed: {}", e),
+            Err(e) => error!("Purge of deleted employees panicked: {}", e),
+        }
+    }
+}
+
 #[actix_rt::main]
 pub async fn main() -> std::io::Result<()> {
     log4rs::init_file("log4rs.yml", Default::default()).unwrap();
     info!("Start application");
 
-    dao::initialize_db();
-    rest::start().await
+    let db = match DbConfig::from_env().and_then(Database::new) {
+        Ok(db) => db,
+        Err(e) => {
+            error!("Invalid database configuration: {}", e);
+            std::process::exit(1);
+        }
+    };
+    db.initialize();
+    // `cargo run -- reencrypt` encrypts all data by the current key (after key rotation) and exits
+    if std::env::args().nth(1).as_deref() == Some("reencrypt") {
+        if db.config().encryption_keys.is_none() {
+            error!("Re-encryption needs ENCRYPTION_KEY or ENCRYPTION_KEY_FILE");
+            std::process::exit(1);
+        }
+        match db.reencrypt() {
+            Ok(count) => info!("Re-encryption finished - {} rows re-encrypted", count),
+            Err(e) => {
+                error!("Re-encryption failed: {}", e);
+                std::process::exit(1);
+            }
+        }
+        return Ok(());
+    }
+    if db.config().encryption_keys.is_none() {
+        warn!(
+            "ENCRYPTION_KEY (or ENCRYPTION_KEY_FILE) is not configured - salary and payslip amounts, phones and \
+             addresses are stored in PLAIN TEXT and national IDs can't be stored"
+        );
+    }
+    if db.config().retention_days.is_some() {
+        actix_rt::spawn(purge_deleted_periodically(db.clone()));
+    }
+    rest::start(db).await
 }
@@ -5,6 +5,8 @@ use actix_web::{test, App};
 use actix_web::cookie::Cookie;
 use actix_web::http::StatusCode;
 use bytes::Bytes;
+use dao::DbConfig;
+use std::time::Duration;
 
 use rest::LoginDTO;
 
@@ -13,13 +15,13 @@ use crate::test_data::URLS;
 
 #[actix_rt::test]
 async fn call_to_index_should_return_hello_world() {
-    setup_test!("call_to_index_should_return_hello_world");
+    let db = setup_test!("call_to_index_should_return_hello_world");
 
-    let mut app = test::init_service(App::new().configure(|cfg| rest::config_all(cfg))).await;
+    let app = test::init_service(App::new().configure(rest::config_with_db(db.clone()))).await;
     let req = test::TestRequest::default()
         .insert_header(("content-type", "text/plain"))
         .to_request();
-    let resp = test::call_service(&mut app, req).await;
+    let resp = test::call_service(&app, req).await;
 
     assert!(resp.status().is_success());
     let result = test::read_body(resp).await;
@@ -28,9 +30,9 @@ async fn call_to_index_should_return_hello_world() {
 
 #[actix_rt::test]
 async fn login_with_correct_credentials() {
-    setup_test!("login_with_correct_credentials");
+    let db = setup_test!("login_with_correct_credentials");
 
-    let mut app = test::init_service(App::new().configure(|cfg| rest::config_all(cfg))).await;
+    let app = test::init_service(App::new().configure(rest::config_with_db(db.clone()))).await;
     let credentials = LoginDTO {
         username: String::from("admin"),
         password: String::from("fb001dfcffd1c899f3297871406242f097aecf1a5342ccf3ebcd116146188e4b"),
@@ -39,7 +41,7 @@ async fn login_with_correct_credentials() {
         .uri("/auth")
         .set_json(&credentials)
         .to_request();
-    let resp = test::call_service(&mut app, req).await;
+    let resp = test::call_service(&app, req).await;
     let session = resp.response().cookies().find(|c| c.name() == "session");
 
     assert!(resp.status().is_success());
@@ -48,9 +50,9 @@ async fn login_with_correct_credentials() {
 
 #[actix_rt::test]
 async fn login_with_incorrect_credentials() {
-    setup_test!("login_with_incorrect_credentials");
+    let db = setup_test!("login_with_incorrect_credentials");
 
-    let mut app = test::init_service(App::new().configure(|cfg| rest::config_all(cfg))).await;
+    let app = test::init_service(App::new().configure(rest::config_with_db(db.clone()))).await;
     let credentials = LoginDTO {
         username: String::from("admin"),
         password: String::from("wrong password"),
@@ -59,7 +61,7 @@ async fn login_with_incorrect_credentials() {
         .uri("/auth")
         .set_json(&credentials)
         .to_request();
-    let resp = test::call_service(&mut app, req).await;
+    let resp = test::call_service(&app, req).await;
     let session = resp.response().cookies().find(|c| c.name() == "session");
 
     assert_eq!(StatusCode::UNAUTHORIZED, resp.status());
@@ -68,11 +70,11 @@ async fn login_with_incorrect_credentials() {
 
 #[actix_rt::test]
 async fn check_access_control() {
-    setup_test!("check_access_control");
+    let db = setup_test!("check_access_control");
 
-    let mut app = test::init_service(App::new().configure(|cfg| rest::config_all(cfg))).await;
-    let admin_session = login_as_admin(&mut app).await;
-    let user_session = login_as_user(&mut app).await;
+    let app = test::init_service(App::new().configure(rest::config_with_db(db.clone()))).await;
+    let admin_session = login_as_admin(&app).await;
+    let user_session = login_as_user(&app).await;
 
     assert!(user_session.is_some());
     assert!(admin_session.is_some());
@@ -92,10 +94,10 @@ async fn check_access_control() {
                 .method(url.method.clone())
                 .cookie(user_session.clone())
                 .to_request();
-            let resp_without_session = test::call_service(&mut app, req_without_session).await;
+            let resp_without_session = test::call_service(&app, req_without_session).await;
             let resp_with_admin_session =
-                test::call_service(&mut app, req_with_admin_session).await;
-            let resp_with_user_session = test::call_service(&mut app, req_with_user_session).await;
+                test::call_service(&app, req_with_admin_session).await;
+            let resp_with_user_session = test::call_service(&app, req_with_user_session).await;
             if url.guarded {
                 assert_eq!(
                     StatusCode::UNAUTHORIZED,
@@ -148,7 +150,29 @@ async fn check_access_control() {
     }
 }
 
-pub async fn login<S, B, E>(username: &str, password: &str, app: &mut S) -> Option<Cookie<'static>>
+#[actix_rt::test]
+async fn exhausted_pool_should_respond_with_service_unavailable() {
+    let db = setup_test!(
+        "exhausted_pool_should_respond_with_service_unavailable",
+        |config: DbConfig| config.with_connection_timeout(Duration::from_millis(100))
+    );
+
+    let app = test::init_service(App::new().configure(rest::config_with_db(db.clone()))).await;
+    let session = login_as_admin(&app).await.unwrap();
+
+    // hold the only connection from pool
+    let _conn = db.try_get_connection().unwrap();
+    let req = test::TestRequest::get()
+        .uri("/users")
+        .cookie(session.clone())
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+
+    assert_eq!(StatusCode::SERVICE_UNAVAILABLE, resp.status());
+    assert!(resp.headers().contains_key("retry-after"));
+}
+
+pub async fn login<S, B, E>(username: &str, password: &str, app: &S) -> Option<Cookie<'static>>
 where
     S: Service<Request, Response = ServiceResponse<B>, Error = E>,
     E: std::fmt::Debug,
@@ -168,7 +192,7 @@ where
         .map(|c| c.into_owned())
 }
 
-pub async fn login_as_admin<S, B, E>(app: &mut S) -> Option<Cookie<'static>>
+pub async fn login_as_admin<S, B, E>(app: &S) -> Option<Cookie<'static>>
 where
     S: Service<Request, Response = ServiceResponse<B>, Error = E>,
     E: std::fmt::Debug,
@@ -181,7 +205,7 @@ where
     .await
 }
 
-pub async fn login_as_user<S, B, E>(app: &mut S) -> Option<Cookie<'static>>
+pub async fn login_as_user<S, B, E>(app: &S) -> Option<Cookie<'static>>
 where
     S: Service<Request, Response = ServiceResponse<B>, Error = E>,
     E: std::fmt::Debug,
@@ -0,0 +1,175 @@
+use actix_web::http::header::{ETAG, IF_MATCH};
+use actix_web::http::StatusCode;
+use actix_web::{test, App};
+use dao::{DepartmentDTO, EmployeeDTO, OrgChartNode};
+use rest::UserDTO;
+
+use crate::commons_for_tests;
+use crate::employee_tests::new_employee;
+use crate::main_tests::{login_as_admin, login_as_user};
+
+#[actix_rt::test]
+async fn create_update_and_delete_department() {
+    let db = setup_test!("create_update_and_delete_department");
+
+    let app = test::init_service(App::new().configure(rest::config_with_db(db.clone()))).await;
+    let session = login_as_admin(&app).await.unwrap();
+
+    let company = DepartmentDTO {
+        id: None,
+        name: "Company".to_string(),
+        parent_id: None,
+        search_string: "".to_string(),
+        version: None,
+    };
+    let req = test::TestRequest::post()
+        .uri("/departments")
+        .cookie(session.clone())
+        .set_json(&company)
+        .to_request();
+    let company: DepartmentDTO = test::call_and_read_body_json(&app, req).await;
+    let it = DepartmentDTO {
+        name: "IT".to_string(),
+        parent_id: company.id,
+        id: None,
+        version: None,
+        ..company.clone()
+    };
+    let req = test::TestRequest::post()
+        .uri("/departments")
+        .cookie(session.clone())
+        .set_json(&it)
+        .to_request();
+    let it: DepartmentDTO = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(it.parent_id, company.id);
+
+    // Department can't be its own parent
+    let req = test::TestRequest::put()
+        .uri("/departments")
+        .cookie(session.clone())
+        .insert_header((IF_MATCH, "\"1\""))
+        .set_json(&DepartmentDTO {
+            parent_id: it.id,
+            ..company.clone()
+        })
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(StatusCode::UNPROCESSABLE_ENTITY, resp.status());
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/departments/{}/subtree", company.id.unwrap()))
+        .cookie(session.clone())
+        .to_request();
+    let subtree: Vec<DepartmentDTO> = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(subtree, vec![company.clone(), it.clone()]);
+
+    let req = test::TestRequest::delete()
+        .uri(&format!("/departments/{}", company.id.unwrap()))
+        .cookie(session.clone())
+        .insert_header((IF_MATCH, "\"1\""))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert!(resp.status().is_success());
+
+    // Sub-department is moved to the top level
+    let req = test::TestRequest::get()
+        .uri(&format!("/departments/{}", it.id.unwrap()))
+        .cookie(session.clone())
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.headers().get(ETAG).unwrap(), "\"2\"");
+    let it: DepartmentDTO = test::read_body_json(resp).await;
+    assert_eq!(it.parent_id, None);
+}
+
+#[actix_rt::test]
+async fn user_linked_to_employee_see_only_its_subtree() {
+    let db = setup_test!("user_linked_to_employee_see_only_its_subtree");
+
+    let app = test::init_service(App::new().configure(rest::config_with_db(db.clone()))).await;
+    let admin_session = login_as_admin(&app).await.unwrap();
+    let user_session = login_as_user(&app).await.unwrap();
+
+    let mut ids = vec![];
+    for (last_name, manager) in [("Ceo", None), ("Cto", Some(0)), ("Dev", Some(1)), ("Sales", Some(0))] {
+        let employee = EmployeeDTO {
+            last_name: last_name.to_string(),
+            manager_id: manager.map(|m: usize| ids[m]),
+            ..new_employee()
+        };
+        let req = test::TestRequest::post()
+            .uri("/employees")
+            .cookie(admin_session.clone())
+            .set_json(&employee)
+            .to_request();
+        let created: EmployeeDTO = test::call_and_read_body_json(&app, req).await;
+        ids.push(created.id.unwrap());
+    }
+
+    let req = test::TestRequest::get()
+        .uri("/org-chart")
+        .cookie(admin_session.clone())
+        .to_request();
+    let chart: Vec<OrgChartNode> = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(chart.len(), 1);
+    assert_eq!(chart[0].id, ids[0]);
+    let reports: Vec<i32> = chart[0].reports.iter().map(|r| r.id).collect();
+    assert_eq!(reports, vec![ids[1], ids[3]]);
+    assert_eq!(chart[0].reports[0].reports[0].id, ids[2]);
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/employees/{}/reports", ids[0]))
+        .cookie(admin_session.clone())
+        .to_request();
+    let reports: Vec<EmployeeDTO> = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(reports.len(), 3);
+
+    // Link user to CTO
+    let req = test::TestRequest::put()
+        .uri("/users")
+        .cookie(admin_session.clone())
+        .insert_header((IF_MATCH, "\"1\""))
+        .set_json(&UserDTO {
+            id: Some(1),
+            username: None,
+            password: None,
+            is_admin: None,
+            privacy_officer: None,
+            version: None,
+            employee_id: Some(Some(ids[1])),
+        })
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert!(resp.status().is_success());
+
+    let req = test::TestRequest::get()
+        .uri("/employees")
+        .cookie(user_session.clone())
+        .to_request();
+    let employees: Vec<EmployeeDTO> = test::call_and_read_body_json(&app, req).await;
+    let visible: Vec<i32> = employees.iter().filter_map(|e| e.id).collect();
+    assert_eq!(visible, vec![ids[1], ids[2]]);
+
+    let req = test::TestRequest::get()
+        .uri("/org-chart")
+        .cookie(user_session.clone())
+        .to_request();
+    let chart: Vec<OrgChartNode> = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(chart.len(), 1);
+    assert_eq!(chart[0].id, ids[1]);
+
+    for id in [ids[0], ids[3]] {
+        let req = test::TestRequest::get()
+            .uri(&format!("/employees/{}", id))
+            .cookie(user_session.clone())
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(StatusCode::NOT_FOUND, resp.status());
+    }
+    let req = test::TestRequest::get()
+        .uri(&format!("/employees/{}/reports", ids[0]))
+        .cookie(user_session.clone())
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(StatusCode::NOT_FOUND, resp.status());
+}
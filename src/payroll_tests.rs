@@ -0,0 +1,143 @@
+use actix_web::http::header::{CONTENT_TYPE, ETAG, IF_MATCH};
+use actix_web::http::StatusCode;
+use actix_web::{test, App};
+use chrono::NaiveDate;
+use dao::{
+    Currency, EmployeeDTO, LineKind, Money, PayPeriod, PayrollRuleDTO, PayrollRunDTO, PayrollVerification,
+    RuleKind, RunStatus, SalaryDTO,
+};
+
+use crate::commons_for_tests;
+use crate::employee_tests::new_employee;
+use crate::main_tests::{login_as_admin, login_as_user};
+
+#[actix_rt::test]
+async fn payroll_run_is_calculated_approved_and_locked() {
+    let db = setup_test!("payroll_run_is_calculated_approved_and_locked");
+
+    let app = test::init_service(App::new().configure(rest::config_with_db(db.clone()))).await;
+    let session = login_as_admin(&app).await.unwrap();
+    let user_session = login_as_user(&app).await.unwrap();
+
+    // 3100.00 PLN monthly since 16th of January - 16 of 31 days in the month
+    let mut employee = new_employee();
+    employee.salaries.push(SalaryDTO {
+        from_date: NaiveDate::from_ymd_opt(2021, 1, 16).unwrap(),
+        to_date: None,
+        amount: Money::new(310000, Currency::PLN),
+        pay_period: PayPeriod::Monthly,
+        ..employee.salaries[0].clone()
+    });
+    let req = test::TestRequest::post()
+        .uri("/employees")
+        .cookie(session.clone())
+        .set_json(&employee)
+        .to_request();
+    let employee: EmployeeDTO = test::call_and_read_body_json(&app, req).await;
+    let e_id = employee.id.unwrap();
+
+    let req = test::TestRequest::post()
+        .uri("/payroll-rules")
+        .cookie(session.clone())
+        .set_json(PayrollRuleDTO {
+            id: None,
+            name: "Social security".to_string(),
+            kind: RuleKind::Deduction,
+            percent: Some(1000),
+            amount: None,
+            active: true,
+            search_string: "".to_string(),
+            version: None,
+        })
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(StatusCode::OK, resp.status());
+
+    // Payroll is just for admins
+    let req = test::TestRequest::get()
+        .uri("/payroll-runs")
+        .cookie(user_session.clone())
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(StatusCode::UNAUTHORIZED, resp.status());
+
+    let new_run = || {
+        test::TestRequest::post()
+            .uri("/payroll-runs")
+            .cookie(session.clone())
+            .insert_header((CONTENT_TYPE, "application/json"))
+            .set_payload(r#"{"year": 2021, "month": 1}"#)
+            .to_request()
+    };
+    let run: PayrollRunDTO = test::call_and_read_body_json(&app, new_run()).await;
+    assert_eq!(run.status, RunStatus::Draft);
+    let payslip = &run.payslips[0];
+    assert_eq!(payslip.employee_id, e_id);
+    assert_eq!(
+        payslip.lines.iter().map(|l| (l.kind, l.amount.minor_units)).collect::<Vec<_>>(),
+        vec![(LineKind::Salary, 160000), (LineKind::Deduction, 16000)]
+    );
+    assert_eq!(payslip.net, Money::new(144000, Currency::PLN));
+
+    let resp = test::call_service(&app, new_run()).await;
+    assert_eq!(StatusCode::UNPROCESSABLE_ENTITY, resp.status());
+
+    let run_url = format!("/payroll-runs/{}", run.id);
+    let req = test::TestRequest::post()
+        .uri(&format!("{}/approve", run_url))
+        .cookie(session.clone())
+        .insert_header((IF_MATCH, "\"7\""))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(StatusCode::PRECONDITION_FAILED, resp.status());
+
+    let req = test::TestRequest::post()
+        .uri(&format!("{}/approve", run_url))
+        .cookie(session.clone())
+        .insert_header((IF_MATCH, "\"1\""))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.headers().get(ETAG).unwrap(), "\"2\"");
+    let approved: PayrollRunDTO = test::read_body_json(resp).await;
+    assert_eq!(approved.status, RunStatus::Approved);
+
+    // Only draft can be deleted
+    let req = test::TestRequest::delete()
+        .uri(&run_url)
+        .cookie(session.clone())
+        .insert_header((IF_MATCH, "\"2\""))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(StatusCode::UNPROCESSABLE_ENTITY, resp.status());
+
+    let req = test::TestRequest::post()
+        .uri(&format!("{}/lock", run_url))
+        .cookie(session.clone())
+        .insert_header((IF_MATCH, "\"2\""))
+        .to_request();
+    let locked: PayrollRunDTO = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(locked.status, RunStatus::Locked);
+
+    // Locked run survives deletion of the employee and is reproducible
+    let req = test::TestRequest::delete()
+        .uri(&format!("/employees/{}", e_id))
+        .cookie(session.clone())
+        .insert_header((IF_MATCH, format!("\"{}\"", employee.version.unwrap())))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(StatusCode::OK, resp.status());
+
+    let req = test::TestRequest::get()
+        .uri(&run_url)
+        .cookie(session.clone())
+        .to_request();
+    let again: PayrollRunDTO = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(again.payslips, locked.payslips);
+
+    let req = test::TestRequest::get()
+        .uri(&format!("{}/verify", run_url))
+        .cookie(session.clone())
+        .to_request();
+    let verification: PayrollVerification = test::call_and_read_body_json(&app, req).await;
+    assert!(verification.reproducible);
+}
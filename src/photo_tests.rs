@@ -0,0 +1,106 @@
+use std::io::Cursor;
+
+use actix_web::http::header::{CACHE_CONTROL, CONTENT_TYPE, ETAG, IF_NONE_MATCH};
+use actix_web::http::StatusCode;
+use actix_web::{test, App};
+use dao::{EmployeeDTO, PhotoDTO};
+use image::{DynamicImage, ImageFormat, Rgb, RgbImage};
+
+use crate::commons_for_tests;
+use crate::employee_tests::new_employee;
+use crate::main_tests::{login_as_admin, login_as_user};
+
+fn png(width: u32, height: u32) -> Vec<u8> {
+    let mut png = Vec::new();
+    DynamicImage::ImageRgb8(RgbImage::from_pixel(width, height, Rgb([10, 120, 200])))
+        .write_to(Cursor::new(&mut png), ImageFormat::Png)
+        .unwrap();
+    png
+}
+
+#[actix_rt::test]
+async fn photo_is_uploaded_with_thumbnails() {
+    let db = setup_test!("photo_is_uploaded_with_thumbnails");
+
+    let app = test::init_service(App::new().configure(rest::config_with_db(db.clone()))).await;
+    let session = login_as_admin(&app).await.unwrap();
+    let user_session = login_as_user(&app).await.unwrap();
+
+    let req = test::TestRequest::post()
+        .uri("/employees")
+        .cookie(session.clone())
+        .set_json(new_employee())
+        .to_request();
+    let employee: EmployeeDTO = test::call_and_read_body_json(&app, req).await;
+    let e_id = employee.id.unwrap();
+    assert_eq!(employee.photo, None);
+    let upload = |content: Vec<u8>, session| {
+        test::TestRequest::put()
+            .uri(&format!("/employees/{}/photo", e_id))
+            .cookie(session)
+            .insert_header((CONTENT_TYPE, "image/png"))
+            .set_payload(content)
+            .to_request()
+    };
+    let resp = test::call_service(&app, upload(png(300, 200), user_session.clone())).await;
+    assert_eq!(StatusCode::UNAUTHORIZED, resp.status(), "Photo is changed just by admins");
+    let resp = test::call_service(&app, upload(b"not an image".to_vec(), session.clone())).await;
+    assert_eq!(StatusCode::UNPROCESSABLE_ENTITY, resp.status());
+    let resp = test::call_service(&app, upload(png(300, 200), session.clone())).await;
+    assert_eq!(StatusCode::OK, resp.status());
+    let photo: PhotoDTO = test::read_body_json(resp).await;
+    assert_eq!((photo.width, photo.height), (300, 200));
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/employees/{}", e_id))
+        .cookie(user_session.clone())
+        .to_request();
+    let employee: EmployeeDTO = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(employee.photo, Some(photo.clone()));
+
+    let resp = test::call_service(
+        &app,
+        test::TestRequest::get().uri(&photo.thumbnails[&64]).cookie(user_session.clone()).to_request(),
+    )
+    .await;
+    assert_eq!(StatusCode::OK, resp.status());
+    assert_eq!(resp.headers().get(CONTENT_TYPE).unwrap(), "image/png");
+    assert!(resp.headers().get(CACHE_CONTROL).unwrap().to_str().unwrap().contains("immutable"));
+    let etag = resp.headers().get(ETAG).unwrap().clone();
+    let thumbnail = image::load_from_memory(&test::read_body(resp).await).unwrap();
+    assert_eq!((thumbnail.width(), thumbnail.height()), (64, 64));
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/employees/{}/photo?size=64", e_id))
+        .cookie(user_session.clone())
+        .insert_header((IF_NONE_MATCH, etag))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(StatusCode::NOT_MODIFIED, resp.status());
+    assert_eq!(resp.headers().get(CACHE_CONTROL).unwrap(), "private, no-cache");
+    let resp = test::call_service(
+        &app,
+        test::TestRequest::get().uri(&photo.url).cookie(user_session.clone()).to_request(),
+    )
+    .await;
+    assert_eq!(StatusCode::OK, resp.status());
+    let original = image::load_from_memory(&test::read_body(resp).await).unwrap();
+    assert_eq!((original.width(), original.height()), (300, 200));
+    let req = test::TestRequest::get()
+        .uri(&format!("/employees/{}/photo?size=100", e_id))
+        .cookie(user_session.clone())
+        .to_request();
+    assert_eq!(StatusCode::UNPROCESSABLE_ENTITY, test::call_service(&app, req).await.status());
+
+    let req = test::TestRequest::delete()
+        .uri(&format!("/employees/{}/photo", e_id))
+        .cookie(session.clone())
+        .to_request();
+    assert_eq!(StatusCode::OK, test::call_service(&app, req).await.status());
+    let resp = test::call_service(
+        &app,
+        test::TestRequest::get().uri(&photo.url).cookie(session.clone()).to_request(),
+    )
+    .await;
+    assert_eq!(StatusCode::NOT_FOUND, resp.status());
+}
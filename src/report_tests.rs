@@ -0,0 +1,60 @@
+use std::collections::HashMap;
+
+use actix_web::http::StatusCode;
+use actix_web::{test, App};
+use chrono::NaiveDate;
+use dao::{Currency, EmployeeDTO, FieldError, Money, SalaryDTO, SalaryReport};
+
+use crate::commons_for_tests;
+use crate::employee_tests::new_employee;
+use crate::main_tests::login_as_admin;
+
+#[actix_rt::test]
+async fn salary_report() {
+    let db = setup_test!("salary_report");
+
+    let app = test::init_service(App::new().configure(rest::config_with_db(db.clone()))).await;
+    let session = login_as_admin(&app).await.unwrap();
+
+    let mut employee = new_employee();
+    employee.salaries.push(SalaryDTO {
+        from_date: NaiveDate::from_ymd_opt(2021, 1, 1).unwrap(),
+        to_date: None,
+        amount: Money::new(150000, Currency::PLN),
+        ..employee.salaries[0].clone()
+    });
+    let req = test::TestRequest::post()
+        .uri("/employees")
+        .cookie(session.clone())
+        .set_json(&employee)
+        .to_request();
+    let created: EmployeeDTO = test::call_and_read_body_json(&app, req).await;
+
+    let req = test::TestRequest::get()
+        .uri("/reports/salaries?from=2020-11-01&to=2021-02-28&currency=PLN")
+        .cookie(session.clone())
+        .to_request();
+    let report: SalaryReport = test::call_and_read_body_json(&app, req).await;
+    let monthly: Vec<String> = report.monthly.iter().map(|m| m.total.to_string()).collect();
+    assert_eq!(monthly, vec!["1000.00 PLN", "1000.00 PLN", "1500.00 PLN", "1500.00 PLN"]);
+    assert_eq!(report.statistics[0].headcount, 1);
+    assert_eq!(report.statistics[0].median, Money::new(150000, Currency::PLN));
+    assert_eq!(report.raises[0].employee_id, created.id.unwrap());
+    assert!(report.params.gross);
+
+    let req = test::TestRequest::get()
+        .uri("/reports/salaries?from=2021-02-28&to=2020-11-01")
+        .cookie(session.clone())
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(StatusCode::UNPROCESSABLE_ENTITY, resp.status());
+    let body: HashMap<String, Vec<FieldError>> = test::read_body_json(resp).await;
+    assert_eq!(body["errors"][0].field, "to");
+
+    let req = test::TestRequest::get()
+        .uri("/reports/salaries?currency=XYZ")
+        .cookie(session.clone())
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(StatusCode::BAD_REQUEST, resp.status());
+}
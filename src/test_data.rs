@@ -58,6 +58,12 @@ lazy_static! {
             guarded: true,
             have_to_be_admin: false,
         },
+        UrlCall{
+            url: "/employees/without-contact",
+            method: Method::GET,
+            guarded: true,
+            have_to_be_admin: false,
+        },
         UrlCall{
             url: "/employees/1",
             method: Method::GET,
@@ -70,6 +76,24 @@ lazy_static! {
             guarded: true,
             have_to_be_admin: true,
         },
+        UrlCall{
+            url: "/employees/1/restore",
+            method: Method::POST,
+            guarded: true,
+            have_to_be_admin: true,
+        },
+        UrlCall{
+            url: "/employees/1/personal-data-export",
+            method: Method::GET,
+            guarded: true,
+            have_to_be_admin: false,
+        },
+        UrlCall{
+            url: "/employees/1/anonymize",
+            method: Method::POST,
+            guarded: true,
+            have_to_be_admin: false,
+        },
         UrlCall{
             url: "/employees",
             method: Method::GET,
@@ -88,6 +112,498 @@ lazy_static! {
             guarded: true,
             have_to_be_admin: true,
         },
+        UrlCall{
+            url: "/reports/salaries",
+            method: Method::GET,
+            guarded: true,
+            have_to_be_admin: true,
+        },
+        UrlCall{
+            url: "/departments",
+            method: Method::GET,
+            guarded: true,
+            have_to_be_admin: false,
+        },
+        UrlCall{
+            url: "/departments",
+            method: Method::PUT,
+            guarded: true,
+            have_to_be_admin: true,
+        },
+        UrlCall{
+            url: "/departments",
+            method: Method::POST,
+            guarded: true,
+            have_to_be_admin: true,
+        },
+        UrlCall{
+            url: "/departments/1/subtree",
+            method: Method::GET,
+            guarded: true,
+            have_to_be_admin: false,
+        },
+        UrlCall{
+            url: "/departments/1",
+            method: Method::GET,
+            guarded: true,
+            have_to_be_admin: false,
+        },
+        UrlCall{
+            url: "/departments/1",
+            method: Method::DELETE,
+            guarded: true,
+            have_to_be_admin: true,
+        },
+        UrlCall{
+            url: "/org-chart",
+            method: Method::GET,
+            guarded: true,
+            have_to_be_admin: false,
+        },
+        UrlCall{
+            url: "/employees/1/reports",
+            method: Method::GET,
+            guarded: true,
+            have_to_be_admin: false,
+        },
+        UrlCall{
+            url: "/positions",
+            method: Method::GET,
+            guarded: true,
+            have_to_be_admin: false,
+        },
+        UrlCall{
+            url: "/positions",
+            method: Method::PUT,
+            guarded: true,
+            have_to_be_admin: true,
+        },
+        UrlCall{
+            url: "/positions",
+            method: Method::POST,
+            guarded: true,
+            have_to_be_admin: true,
+        },
+        UrlCall{
+            url: "/positions/1",
+            method: Method::GET,
+            guarded: true,
+            have_to_be_admin: false,
+        },
+        UrlCall{
+            url: "/positions/1",
+            method: Method::DELETE,
+            guarded: true,
+            have_to_be_admin: true,
+        },
+        UrlCall{
+            url: "/custom-fields",
+            method: Method::GET,
+            guarded: true,
+            have_to_be_admin: false,
+        },
+        UrlCall{
+            url: "/custom-fields",
+            method: Method::PUT,
+            guarded: true,
+            have_to_be_admin: true,
+        },
+        UrlCall{
+            url: "/custom-fields",
+            method: Method::POST,
+            guarded: true,
+            have_to_be_admin: true,
+        },
+        UrlCall{
+            url: "/custom-fields/1",
+            method: Method::GET,
+            guarded: true,
+            have_to_be_admin: false,
+        },
+        UrlCall{
+            url: "/custom-fields/1",
+            method: Method::DELETE,
+            guarded: true,
+            have_to_be_admin: true,
+        },
+        UrlCall{
+            url: "/tags",
+            method: Method::GET,
+            guarded: true,
+            have_to_be_admin: false,
+        },
+        UrlCall{
+            url: "/employees/tags",
+            method: Method::POST,
+            guarded: true,
+            have_to_be_admin: true,
+        },
+        UrlCall{
+            url: "/employee-groups",
+            method: Method::GET,
+            guarded: true,
+            have_to_be_admin: false,
+        },
+        UrlCall{
+            url: "/employee-groups",
+            method: Method::PUT,
+            guarded: true,
+            have_to_be_admin: true,
+        },
+        UrlCall{
+            url: "/employee-groups",
+            method: Method::POST,
+            guarded: true,
+            have_to_be_admin: true,
+        },
+        UrlCall{
+            url: "/employee-groups/1",
+            method: Method::GET,
+            guarded: true,
+            have_to_be_admin: false,
+        },
+        UrlCall{
+            url: "/employee-groups/1",
+            method: Method::DELETE,
+            guarded: true,
+            have_to_be_admin: true,
+        },
+        UrlCall{
+            url: "/employee-groups/1/employees",
+            method: Method::GET,
+            guarded: true,
+            have_to_be_admin: false,
+        },
+        UrlCall{
+            url: "/employees/1/contracts",
+            method: Method::GET,
+            guarded: true,
+            have_to_be_admin: false,
+        },
+        UrlCall{
+            url: "/employees/1/contracts",
+            method: Method::PUT,
+            guarded: true,
+            have_to_be_admin: true,
+        },
+        UrlCall{
+            url: "/employees/1/contracts",
+            method: Method::POST,
+            guarded: true,
+            have_to_be_admin: true,
+        },
+        UrlCall{
+            url: "/employees/1/contracts/1",
+            method: Method::GET,
+            guarded: true,
+            have_to_be_admin: false,
+        },
+        UrlCall{
+            url: "/employees/1/contracts/1",
+            method: Method::DELETE,
+            guarded: true,
+            have_to_be_admin: true,
+        },
+        UrlCall{
+            url: "/employees/1/documents",
+            method: Method::GET,
+            guarded: true,
+            have_to_be_admin: false,
+        },
+        UrlCall{
+            url: "/employees/1/documents",
+            method: Method::POST,
+            guarded: true,
+            have_to_be_admin: true,
+        },
+        UrlCall{
+            url: "/employees/1/documents/1",
+            method: Method::GET,
+            guarded: true,
+            have_to_be_admin: false,
+        },
+        UrlCall{
+            url: "/employees/1/documents/1",
+            method: Method::DELETE,
+            guarded: true,
+            have_to_be_admin: true,
+        },
+        UrlCall{
+            url: "/employees/1/documents/1/content",
+            method: Method::GET,
+            guarded: true,
+            have_to_be_admin: false,
+        },
+        UrlCall{
+            url: "/employees/1/photo",
+            method: Method::GET,
+            guarded: true,
+            have_to_be_admin: false,
+        },
+        UrlCall{
+            url: "/employees/1/photo",
+            method: Method::PUT,
+            guarded: true,
+            have_to_be_admin: true,
+        },
+        UrlCall{
+            url: "/employees/1/photo",
+            method: Method::DELETE,
+            guarded: true,
+            have_to_be_admin: true,
+        },
+        UrlCall{
+            url: "/employees/1/absences",
+            method: Method::GET,
+            guarded: true,
+            have_to_be_admin: false,
+        },
+        UrlCall{
+            url: "/employees/1/absences",
+            method: Method::PUT,
+            guarded: true,
+            have_to_be_admin: false,
+        },
+        UrlCall{
+            url: "/employees/1/absences",
+            method: Method::POST,
+            guarded: true,
+            have_to_be_admin: false,
+        },
+        UrlCall{
+            url: "/employees/1/absences/balance",
+            method: Method::GET,
+            guarded: true,
+            have_to_be_admin: false,
+        },
+        UrlCall{
+            url: "/employees/1/absences/1",
+            method: Method::GET,
+            guarded: true,
+            have_to_be_admin: false,
+        },
+        UrlCall{
+            url: "/employees/1/absences/1",
+            method: Method::DELETE,
+            guarded: true,
+            have_to_be_admin: false,
+        },
+        UrlCall{
+            url: "/employees/1/absences/1/approve",
+            method: Method::POST,
+            guarded: true,
+            have_to_be_admin: false,
+        },
+        UrlCall{
+            url: "/employees/1/absences/1/reject",
+            method: Method::POST,
+            guarded: true,
+            have_to_be_admin: false,
+        },
+        UrlCall{
+            url: "/absences/calendar",
+            method: Method::GET,
+            guarded: true,
+            have_to_be_admin: false,
+        },
+        UrlCall{
+            url: "/absence-types",
+            method: Method::GET,
+            guarded: true,
+            have_to_be_admin: false,
+        },
+        UrlCall{
+            url: "/absence-types",
+            method: Method::PUT,
+            guarded: true,
+            have_to_be_admin: true,
+        },
+        UrlCall{
+            url: "/absence-types",
+            method: Method::POST,
+            guarded: true,
+            have_to_be_admin: true,
+        },
+        UrlCall{
+            url: "/absence-types/1",
+            method: Method::GET,
+            guarded: true,
+            have_to_be_admin: false,
+        },
+        UrlCall{
+            url: "/absence-types/1",
+            method: Method::DELETE,
+            guarded: true,
+            have_to_be_admin: true,
+        },
+        UrlCall{
+            url: "/employees/1/timesheets",
+            method: Method::GET,
+            guarded: true,
+            have_to_be_admin: false,
+        },
+        UrlCall{
+            url: "/employees/1/timesheets",
+            method: Method::PUT,
+            guarded: true,
+            have_to_be_admin: false,
+        },
+        UrlCall{
+            url: "/employees/1/timesheets",
+            method: Method::POST,
+            guarded: true,
+            have_to_be_admin: false,
+        },
+        UrlCall{
+            url: "/employees/1/timesheets/1",
+            method: Method::GET,
+            guarded: true,
+            have_to_be_admin: false,
+        },
+        UrlCall{
+            url: "/employees/1/timesheets/1",
+            method: Method::DELETE,
+            guarded: true,
+            have_to_be_admin: false,
+        },
+        UrlCall{
+            url: "/employees/1/timesheets/weeks/2021-03-01",
+            method: Method::GET,
+            guarded: true,
+            have_to_be_admin: false,
+        },
+        UrlCall{
+            url: "/employees/1/timesheets/weeks/2021-03-01/submit",
+            method: Method::POST,
+            guarded: true,
+            have_to_be_admin: false,
+        },
+        UrlCall{
+            url: "/employees/1/timesheets/weeks/2021-03-01/approve",
+            method: Method::POST,
+            guarded: true,
+            have_to_be_admin: false,
+        },
+        UrlCall{
+            url: "/employees/1/timesheets/weeks/2021-03-01/reject",
+            method: Method::POST,
+            guarded: true,
+            have_to_be_admin: false,
+        },
+        UrlCall{
+            url: "/timesheets/summary",
+            method: Method::GET,
+            guarded: true,
+            have_to_be_admin: false,
+        },
+        UrlCall{
+            url: "/timesheets/export",
+            method: Method::GET,
+            guarded: true,
+            have_to_be_admin: false,
+        },
+        UrlCall{
+            url: "/payroll-rules",
+            method: Method::GET,
+            guarded: true,
+            have_to_be_admin: true,
+        },
+        UrlCall{
+            url: "/payroll-rules",
+            method: Method::PUT,
+            guarded: true,
+            have_to_be_admin: true,
+        },
+        UrlCall{
+            url: "/payroll-rules",
+            method: Method::POST,
+            guarded: true,
+            have_to_be_admin: true,
+        },
+        UrlCall{
+            url: "/payroll-rules/1",
+            method: Method::GET,
+            guarded: true,
+            have_to_be_admin: true,
+        },
+        UrlCall{
+            url: "/payroll-rules/1",
+            method: Method::DELETE,
+            guarded: true,
+            have_to_be_admin: true,
+        },
+        UrlCall{
+            url: "/payroll-runs",
+            method: Method::GET,
+            guarded: true,
+            have_to_be_admin: true,
+        },
+        UrlCall{
+            url: "/payroll-runs",
+            method: Method::POST,
+            guarded: true,
+            have_to_be_admin: true,
+        },
+        UrlCall{
+            url: "/payroll-runs/1",
+            method: Method::GET,
+            guarded: true,
+            have_to_be_admin: true,
+        },
+        UrlCall{
+            url: "/payroll-runs/1",
+            method: Method::DELETE,
+            guarded: true,
+            have_to_be_admin: true,
+        },
+        UrlCall{
+            url: "/payroll-runs/1/verify",
+            method: Method::GET,
+            guarded: true,
+            have_to_be_admin: true,
+        },
+        UrlCall{
+            url: "/payroll-runs/1/recalculate",
+            method: Method::POST,
+            guarded: true,
+            have_to_be_admin: true,
+        },
+        UrlCall{
+            url: "/payroll-runs/1/approve",
+            method: Method::POST,
+            guarded: true,
+            have_to_be_admin: true,
+        },
+        UrlCall{
+            url: "/payroll-runs/1/lock",
+            method: Method::POST,
+            guarded: true,
+            have_to_be_admin: true,
+        },
+        UrlCall{
+            url: "/import/employees",
+            method: Method::POST,
+            guarded: true,
+            have_to_be_admin: true,
+        },
+        UrlCall{
+            url: "/export/employees",
+            method: Method::GET,
+            guarded: true,
+            have_to_be_admin: false,
+        },
+        UrlCall{
+            url: "/export/salaries",
+            method: Method::GET,
+            guarded: true,
+            have_to_be_admin: false,
+        },
+        UrlCall{
+            url: "/export/contacts",
+            method: Method::GET,
+            guarded: true,
+            have_to_be_admin: false,
+        },
         // IMPORTANT: this call have to be last as it logout the session
         UrlCall{
             url: "/auth",
@@ -0,0 +1,151 @@
+use std::collections::HashMap;
+
+use actix_web::http::header::{CONTENT_TYPE, ETAG, IF_MATCH};
+use actix_web::http::StatusCode;
+use actix_web::{test, App};
+use chrono::NaiveDate;
+use dao::{
+    Currency, EmployeeDTO, FieldError, Hours, Money, Page, PayPeriod, SalaryDTO, TimesheetEntryDTO,
+    TimesheetSummary, TimesheetWeekDTO, WeekStatus,
+};
+
+use crate::commons_for_tests;
+use crate::employee_tests::new_employee;
+use crate::main_tests::{login_as_admin, login_as_user};
+
+#[actix_rt::test]
+async fn timesheet_is_submitted_approved_and_exported() {
+    let db = setup_test!("timesheet_is_submitted_approved_and_exported");
+
+    let app = test::init_service(App::new().configure(rest::config_with_db(db.clone()))).await;
+    let session = login_as_admin(&app).await.unwrap();
+    let user_session = login_as_user(&app).await.unwrap();
+
+    // 40.00 PLN per hour since 2021
+    let mut employee = new_employee();
+    employee.salaries.push(SalaryDTO {
+        from_date: NaiveDate::from_ymd_opt(2021, 1, 1).unwrap(),
+        to_date: None,
+        amount: Money::new(4000, Currency::PLN),
+        pay_period: PayPeriod::Hourly,
+        ..employee.salaries[0].clone()
+    });
+    let req = test::TestRequest::post()
+        .uri("/employees")
+        .cookie(session.clone())
+        .set_json(&employee)
+        .to_request();
+    let employee: EmployeeDTO = test::call_and_read_body_json(&app, req).await;
+    let e_id = employee.id.unwrap();
+    let timesheets_url = format!("/employees/{}/timesheets", e_id);
+
+    let mut entries = vec![];
+    for (day, hours) in [(1, "8:00"), (2, "7.5")] {
+        let req = test::TestRequest::post()
+            .uri(&timesheets_url)
+            .cookie(session.clone())
+            .set_json(TimesheetEntryDTO {
+                id: None,
+                employee_id: None,
+                work_date: NaiveDate::from_ymd_opt(2021, 3, day).unwrap(),
+                hours: Hours::parse(hours).unwrap(),
+                category: "Project X".to_string(),
+                note: None,
+                search_string: "".to_string(),
+                version: None,
+            })
+            .to_request();
+        let entry: TimesheetEntryDTO = test::call_and_read_body_json(&app, req).await;
+        entries.push(entry);
+    }
+    assert_eq!(entries[1].hours, Hours::from_minutes(450));
+
+    // At most 24 hours a day
+    let req = test::TestRequest::post()
+        .uri(&timesheets_url)
+        .cookie(session.clone())
+        .set_json(TimesheetEntryDTO {
+            id: None,
+            version: None,
+            hours: Hours::parse("17").unwrap(),
+            ..entries[1].clone()
+        })
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(StatusCode::UNPROCESSABLE_ENTITY, resp.status());
+    let body: HashMap<String, Vec<FieldError>> = test::read_body_json(resp).await;
+    assert_eq!(body["errors"][0].field, "hours");
+
+    let req = test::TestRequest::get()
+        .uri(&format!("{}?from=2021-03-01&per_page=1&page=2", timesheets_url))
+        .cookie(session.clone())
+        .to_request();
+    let page: Page<TimesheetEntryDTO> = test::call_and_read_body_json(&app, req).await;
+    assert_eq!((page.total, page.page, page.per_page), (2, 2, 1));
+    assert_eq!(page.items, vec![entries[1].clone()]);
+
+    let week_url = format!("{}/weeks/2021-03-01", timesheets_url);
+    let req = test::TestRequest::post()
+        .uri(&format!("{}/submit", week_url))
+        .cookie(session.clone())
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.headers().get(ETAG).unwrap(), "\"1\"");
+    let week: TimesheetWeekDTO = test::read_body_json(resp).await;
+    assert_eq!(week.status, WeekStatus::Submitted);
+
+    let req = test::TestRequest::post()
+        .uri(&format!("{}/weeks/2021-03-02/submit", timesheets_url))
+        .cookie(session.clone())
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(StatusCode::UNPROCESSABLE_ENTITY, resp.status());
+
+    // User which is not manager of the employee can't approve its timesheet
+    let req = test::TestRequest::post()
+        .uri(&format!("{}/approve", week_url))
+        .cookie(user_session.clone())
+        .insert_header((IF_MATCH, "\"1\""))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(StatusCode::FORBIDDEN, resp.status());
+
+    let req = test::TestRequest::post()
+        .uri(&format!("{}/approve", week_url))
+        .cookie(session.clone())
+        .insert_header((IF_MATCH, "\"1\""))
+        .to_request();
+    let week: TimesheetWeekDTO = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(week.status, WeekStatus::Approved);
+
+    // Entries of approved week are locked
+    let req = test::TestRequest::delete()
+        .uri(&format!("{}/{}", timesheets_url, entries[0].id.unwrap()))
+        .cookie(session.clone())
+        .insert_header((IF_MATCH, "\"1\""))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(StatusCode::UNPROCESSABLE_ENTITY, resp.status());
+
+    let req = test::TestRequest::get()
+        .uri("/timesheets/summary?year=2021&month=3")
+        .cookie(session.clone())
+        .to_request();
+    let summaries: Vec<TimesheetSummary> = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(summaries[0].approved_hours, Hours::parse("15:30").unwrap());
+    assert_eq!(summaries[0].pay, vec![Money::new(62000, Currency::PLN)]);
+
+    let req = test::TestRequest::get()
+        .uri("/timesheets/export?from=2021-03-01&to=2021-03-31")
+        .cookie(session.clone())
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.headers().get(CONTENT_TYPE).unwrap(), "text/csv; charset=utf-8");
+    let body = test::read_body(resp).await;
+    let lines: Vec<&str> = std::str::from_utf8(&body).unwrap().lines().collect();
+    assert_eq!(lines[0], "employee_id,first_name,last_name,work_date,hours,category,note,status");
+    assert_eq!(
+        lines[2],
+        format!("{},Jan,Kowalski,2021-03-02,7.50,Project X,,approved", e_id)
+    );
+}
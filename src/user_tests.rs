@@ -1,4 +1,5 @@
 use actix_web::{test, App};
+use actix_web::http::header::{ETAG, IF_MATCH};
 use actix_web::http::StatusCode;
 use rest::UserDTO;
 
@@ -7,10 +8,10 @@ use crate::main_tests::{login_as_admin, login_as_user};
 
 #[actix_rt::test]
 async fn get_all_users() {
-    setup_test!("get_all_users");
+    let db = setup_test!("get_all_users");
 
-    let mut app = test::init_service(App::new().configure(|cfg| rest::config_all(cfg))).await;
-    let session = login_as_user(&mut app).await;
+    let app = test::init_service(App::new().configure(rest::config_with_db(db.clone()))).await;
+    let session = login_as_user(&app).await;
 
     assert!(session.is_some());
     if let Some(session) = session {
@@ -19,17 +20,17 @@ async fn get_all_users() {
             .uri("/users")
             .cookie(session.clone())
             .to_request();
-        let users: Vec<UserDTO> = test::call_and_read_body_json(&mut app, req).await;
+        let users: Vec<UserDTO> = test::call_and_read_body_json(&app, req).await;
         assert_eq!(users.len(), 2);
     }
 }
 
 #[actix_rt::test]
 async fn get_specific_user() {
-    setup_test!("get_specific_user");
+    let db = setup_test!("get_specific_user");
 
-    let mut app = test::init_service(App::new().configure(|cfg| rest::config_all(cfg))).await;
-    let session = login_as_user(&mut app).await;
+    let app = test::init_service(App::new().configure(rest::config_with_db(db.clone()))).await;
+    let session = login_as_user(&app).await;
 
     assert!(session.is_some());
     if let Some(session) = session {
@@ -38,29 +39,29 @@ async fn get_specific_user() {
             .uri("/users/1")
             .cookie(session.clone())
             .to_request();
-        let user: UserDTO = test::call_and_read_body_json(&mut app, req).await;
+        let user: UserDTO = test::call_and_read_body_json(&app, req).await;
         assert!(user.id.is_some());
         assert_eq!(user.id.unwrap(), 1);
         assert_eq!(user.username.unwrap(), String::from("user"));
-        assert_eq!(user.is_admin.unwrap(), false);
+        assert!(!user.is_admin.unwrap());
         let req = test::TestRequest::get()
             .uri("/users/2")
             .cookie(session.clone())
             .to_request();
-        let user: UserDTO = test::call_and_read_body_json(&mut app, req).await;
+        let user: UserDTO = test::call_and_read_body_json(&app, req).await;
         assert!(user.id.is_some());
         assert_eq!(user.id.unwrap(), 2);
         assert_eq!(user.username.unwrap(), String::from("admin"));
-        assert_eq!(user.is_admin.unwrap(), true);
+        assert!(user.is_admin.unwrap());
     }
 }
 
 #[actix_rt::test]
 async fn update_user() {
-    setup_test!("update_user");
+    let db = setup_test!("update_user");
 
-    let mut app = test::init_service(App::new().configure(|cfg| rest::config_all(cfg))).await;
-    let session = login_as_admin(&mut app).await;
+    let app = test::init_service(App::new().configure(rest::config_with_db(db.clone()))).await;
+    let session = login_as_admin(&app).await;
 
     assert!(session.is_some());
     if let Some(session) = session {
@@ -70,13 +71,17 @@ async fn update_user() {
             username: Some(String::from("updated")),
             password: Some(String::from("updated")),
             is_admin: Some(false),
+            privacy_officer: None,
+            version: None,
+            employee_id: None,
         };
         let req = test::TestRequest::post()
             .uri("/users")
             .cookie(session.clone())
+            .insert_header((IF_MATCH, "\"1\""))
             .set_json(&user)
             .to_request();
-        let resp = test::call_service(&mut app, req).await;
+        let resp = test::call_service(&app, req).await;
 
         assert!(resp.status().is_success());
 
@@ -84,26 +89,30 @@ async fn update_user() {
             .uri("/users/1")
             .cookie(session.clone())
             .to_request();
-        let user: UserDTO = test::call_and_read_body_json(&mut app, req).await;
+        let user: UserDTO = test::call_and_read_body_json(&app, req).await;
 
         assert!(user.id.is_some());
         assert_eq!(user.id.unwrap(), 1);
         assert_eq!(user.username.unwrap(), String::from("updated"));
         assert_eq!(user.password.unwrap(), String::from("updated"));
-        assert_eq!(user.is_admin.unwrap(), false);
+        assert!(!user.is_admin.unwrap());
 
         let user = UserDTO {
             id: Some(1),
             username: Some(String::from("updated2")),
             password: Some(String::from("updated2")),
             is_admin: Some(false),
+            privacy_officer: None,
+            version: None,
+            employee_id: None,
         };
         let req = test::TestRequest::put()
             .uri("/users")
             .cookie(session.clone())
+            .insert_header((IF_MATCH, "\"2\""))
             .set_json(&user)
             .to_request();
-        let resp = test::call_service(&mut app, req).await;
+        let resp = test::call_service(&app, req).await;
 
         assert!(resp.status().is_success());
 
@@ -111,22 +120,22 @@ async fn update_user() {
             .uri("/users/1")
             .cookie(session.clone())
             .to_request();
-        let user: UserDTO = test::call_and_read_body_json(&mut app, req).await;
+        let user: UserDTO = test::call_and_read_body_json(&app, req).await;
 
         assert!(user.id.is_some());
         assert_eq!(user.id.unwrap(), 1);
         assert_eq!(user.username.unwrap(), String::from("updated2"));
         assert_eq!(user.password.unwrap(), String::from("updated2"));
-        assert_eq!(user.is_admin.unwrap(), false);
+        assert!(!user.is_admin.unwrap());
     }
 }
 
 #[actix_rt::test]
 async fn delete_user() {
-    setup_test!("delete_user");
+    let db = setup_test!("delete_user");
 
-    let mut app = test::init_service(App::new().configure(|cfg| rest::config_all(cfg))).await;
-    let session = login_as_admin(&mut app).await;
+    let app = test::init_service(App::new().configure(rest::config_with_db(db.clone()))).await;
+    let session = login_as_admin(&app).await;
 
     assert!(session.is_some());
     if let Some(session) = session {
@@ -135,8 +144,9 @@ async fn delete_user() {
         let req = test::TestRequest::delete()
             .uri("/users/1")
             .cookie(session.clone())
+            .insert_header((IF_MATCH, "*"))
             .to_request();
-        let resp = test::call_service(&mut app, req).await;
+        let resp = test::call_service(&app, req).await;
 
         assert!(resp.status().is_success());
 
@@ -144,8 +154,97 @@ async fn delete_user() {
             .uri("/users/1")
             .cookie(session.clone())
             .to_request();
-        let resp = test::call_service(&mut app, req).await;
+        let resp = test::call_service(&app, req).await;
 
         assert_eq!(StatusCode::NOT_FOUND, resp.status());
     }
 }
+
+#[actix_rt::test]
+async fn concurrent_update_of_user_should_be_rejected() {
+    let db = setup_test!("concurrent_update_of_user_should_be_rejected");
+
+    let app = test::init_service(App::new().configure(rest::config_with_db(db.clone()))).await;
+    let session = login_as_admin(&app).await.unwrap();
+
+    let req = test::TestRequest::get()
+        .uri("/users/1")
+        .cookie(session.clone())
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    let etag = resp.headers().get(ETAG).unwrap().clone();
+    assert_eq!("\"1\"", etag);
+    let user: UserDTO = test::read_body_json(resp).await;
+
+    let first = UserDTO {
+        username: Some(String::from("first")),
+        ..user.clone()
+    };
+    let req = test::TestRequest::put()
+        .uri("/users")
+        .cookie(session.clone())
+        .insert_header((IF_MATCH, etag.clone()))
+        .set_json(&first)
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert!(resp.status().is_success());
+    assert_eq!("\"2\"", resp.headers().get(ETAG).unwrap());
+
+    // Second update is based on the same (now stale) version
+    let second = UserDTO {
+        username: Some(String::from("second")),
+        ..user.clone()
+    };
+    let req = test::TestRequest::put()
+        .uri("/users")
+        .cookie(session.clone())
+        .insert_header((IF_MATCH, etag.clone()))
+        .set_json(&second)
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(StatusCode::PRECONDITION_FAILED, resp.status());
+    assert_eq!("\"2\"", resp.headers().get(ETAG).unwrap());
+    let current: UserDTO = test::read_body_json(resp).await;
+    assert_eq!(current.username.unwrap(), String::from("first"));
+    assert_eq!(current.version, Some(2));
+
+    let req = test::TestRequest::delete()
+        .uri("/users/1")
+        .cookie(session.clone())
+        .insert_header((IF_MATCH, etag))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(StatusCode::PRECONDITION_FAILED, resp.status());
+}
+
+#[actix_rt::test]
+async fn update_and_delete_of_user_without_if_match_should_be_rejected() {
+    let db = setup_test!("update_and_delete_of_user_without_if_match_should_be_rejected");
+
+    let app = test::init_service(App::new().configure(rest::config_with_db(db.clone()))).await;
+    let session = login_as_admin(&app).await.unwrap();
+
+    let user = UserDTO {
+        id: Some(1),
+        username: Some(String::from("updated")),
+        password: Some(String::from("updated")),
+        is_admin: Some(false),
+        privacy_officer: None,
+        version: Some(1),
+        employee_id: None,
+    };
+    let req = test::TestRequest::put()
+        .uri("/users")
+        .cookie(session.clone())
+        .set_json(&user)
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(StatusCode::PRECONDITION_REQUIRED, resp.status());
+
+    let req = test::TestRequest::delete()
+        .uri("/users/1")
+        .cookie(session.clone())
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(StatusCode::PRECONDITION_REQUIRED, resp.status());
+}
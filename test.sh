@@ -1,4 +1,10 @@
 #!/bin/bash
 
 export RUST_BACKTRACE=1
-cargo test -- --nocapture --test-threads 1
+cargo test -- --nocapture
+
+# PostgreSQL tests run only when TEST_DATABASE_URL point to disposable database, e.g.:
+# TEST_DATABASE_URL=postgres://postgres@localhost/rust_backend_test ./test.sh
+if [ -n "$TEST_DATABASE_URL" ]; then
+  cargo test --no-default-features --features postgres -- --nocapture
+fi